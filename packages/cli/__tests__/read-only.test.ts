@@ -0,0 +1,22 @@
+import { describe, it, expect } from "bun:test";
+import { readOnlyRefusal } from "../src/read-only.js";
+
+/**
+ * Commands refused while --home / TOKSCALE_HOME points at another home
+ */
+
+describe("readOnlyRefusal", () => {
+  it("refuses imports, which record into the analyzed home", () => {
+    expect(readOnlyRefusal("import")).toContain("'tokscale import' isn't available with --home");
+  });
+
+  it("refuses budgets --enforce but not budgets", () => {
+    expect(readOnlyRefusal("budgets", { enforce: true })).toContain("'tokscale budgets --enforce'");
+    expect(readOnlyRefusal("budgets", {})).toBeUndefined();
+  });
+
+  it("allows reports", () => {
+    expect(readOnlyRefusal("models")).toBeUndefined();
+    expect(readOnlyRefusal("graph")).toBeUndefined();
+  });
+});
@@ -0,0 +1,87 @@
+import { describe, it, expect } from "bun:test";
+import { rerunOnChange } from "../src/watch.js";
+
+/**
+ * Live mode under --plain: the plain report reruns on session changes
+ */
+
+function fakeWatcher() {
+  const watcher = {
+    sources: undefined as string[] | undefined,
+    stopped: false,
+    change: () => {},
+    watch(sources: string[] | undefined, onChange: () => void) {
+      watcher.sources = sources;
+      watcher.change = onChange;
+      return () => {
+        watcher.stopped = true;
+      };
+    },
+  };
+  return watcher;
+}
+
+const tick = () => new Promise((resolve) => setTimeout(resolve, 0));
+
+describe("rerunOnChange", () => {
+  it("renders at once and after each change", async () => {
+    const watcher = fakeWatcher();
+    let renders = 0;
+    const stop = rerunOnChange(["claude"], async () => {
+      renders++;
+    }, watcher.watch);
+    await tick();
+    expect(renders).toBe(1);
+    expect(watcher.sources).toEqual(["claude"]);
+
+    watcher.change();
+    await tick();
+    expect(renders).toBe(2);
+
+    stop();
+    expect(watcher.stopped).toBe(true);
+  });
+
+  it("runs once more for changes during a render", async () => {
+    const watcher = fakeWatcher();
+    let renders = 0;
+    let finish = () => {};
+    rerunOnChange(undefined, () => {
+      renders++;
+      return new Promise<void>((resolve) => {
+        finish = resolve;
+      });
+    }, watcher.watch);
+    expect(renders).toBe(1);
+
+    watcher.change();
+    watcher.change();
+    finish();
+    await tick();
+    expect(renders).toBe(2);
+    finish();
+    await tick();
+    expect(renders).toBe(2);
+  });
+
+  it("keeps watching after a failed render", async () => {
+    const watcher = fakeWatcher();
+    let renders = 0;
+    const errors: unknown[] = [];
+    const consoleError = console.error;
+    console.error = (message: unknown) => errors.push(message);
+    try {
+      rerunOnChange(undefined, async () => {
+        renders++;
+        if (renders === 1) throw new Error("parse failed");
+      }, watcher.watch);
+      await tick();
+      watcher.change();
+      await tick();
+    } finally {
+      console.error = consoleError;
+    }
+    expect(renders).toBe(2);
+    expect(errors).toEqual(["parse failed"]);
+  });
+});
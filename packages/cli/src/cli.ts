@@ -6,6 +6,9 @@
  * All heavy computation is done in the native Rust module.
  */
 
+import { isPlain } from "./plain.js";
+import { readOnlyHome, readOnlyRefusal } from "./read-only.js";
+import "./verbose.js";
 import { Command, Option } from "commander";
 import { createRequire } from "module";
 const require = createRequire(import.meta.url);
@@ -14,6 +17,10 @@ import pc from "picocolors";
 import { login, logout, whoami } from "./auth.js";
 import { submit } from "./submit.js";
 import { generateWrapped } from "./wrapped.js";
+import { runQuery, ArrowExtensionUnavailableError, DuckDBUnavailableError } from "./query.js";
+import { parseListing, printTable, type ListingOptions } from "./listing.js";
+import { printRunSummary } from "./run-summary.js";
+import { rerunOnChange } from "./watch.js";
 
 import {
   loadCursorCredentials,
@@ -30,25 +37,44 @@ import {
   formatTotalsRow,
   formatNumber,
   formatCurrency,
+  formatCostCell,
   formatModelName,
+  ResponsiveTable,
 } from "./table.js";
 import {
   isNativeAvailable,
   getNativeVersion,
+  getNativeCapabilities,
   parseLocalSourcesAsync,
   finalizeReportAsync,
   finalizeMonthlyReportAsync,
   finalizeGraphAsync,
+  getReportSchema,
+  encryptForTeam,
   type ModelReport,
   type MonthlyReport,
   type ParsedMessages,
 } from "./native.js";
 import { createSpinner } from "./spinner.js";
+import { withSchemaVersion } from "./export.js";
+import { getLocale, setLocale, t, SUPPORTED_LOCALES } from "./i18n.js";
 import * as fs from "node:fs";
 import { performance } from "node:perf_hooks";
 import type { SourceType } from "./graph-types.js";
 import type { TUIOptions, TabType } from "./tui/types/index.js";
-import { loadSettings } from "./tui/config/settings.js";
+import { CONFIG_FILE, loadSettings } from "./tui/config/settings.js";
+import {
+  flattenSettings,
+  formatSettingValue,
+  getSetting,
+  parseSettingValue,
+  readSettingsFile,
+  serializeSettings,
+  setSetting,
+  unsetSetting,
+  writeSettingsFile,
+} from "./settings-file.js";
+import type { SettingsObject } from "./settings-file.js";
 
 type LaunchTUIFunction = (options?: TUIOptions) => Promise<void>;
 
@@ -109,6 +135,9 @@ interface FilterOptions {
   cursor?: boolean;
   amp?: boolean;
   droid?: boolean;
+  cline?: boolean;
+  roocode?: boolean;
+  recorded?: boolean;
 }
 
 interface DateFilterOptions {
@@ -118,6 +147,8 @@ interface DateFilterOptions {
   today?: boolean;
   week?: boolean;
   month?: boolean;
+  /** Reproduce the report as of this date (models and monthly only) */
+  asOf?: string;
 }
 
 interface CursorSyncResult {
@@ -140,6 +171,15 @@ function formatDate(date: Date): string {
 }
 
 function getDateFilters(options: DateFilterOptions): { since?: string; until?: string; year?: string } {
+  const filters = getRequestedDateFilters(options);
+  // --as-of: nothing after that day was known yet
+  if (options.asOf && (!filters.until || filters.until > options.asOf)) {
+    filters.until = options.asOf;
+  }
+  return filters;
+}
+
+function getRequestedDateFilters(options: DateFilterOptions): { since?: string; until?: string; year?: string } {
   const today = new Date();
   
   // --today: just today
@@ -170,24 +210,36 @@ function getDateFilters(options: DateFilterOptions): { since?: string; until?: s
 }
 
 function getDateRangeLabel(options: DateFilterOptions): string | null {
-  if (options.today) return "Today";
-  if (options.week) return "Last 7 days";
+  if (options.today) return t("range.today");
+  if (options.week) return t("range.last7Days");
   if (options.month) {
     const today = new Date();
-    return today.toLocaleString("en-US", { month: "long", year: "numeric" } as Intl.DateTimeFormatOptions);
+    return today.toLocaleString(getLocale(), { month: "long", year: "numeric" } as Intl.DateTimeFormatOptions);
   }
   if (options.year) return options.year;
   if (options.since || options.until) {
     const parts: string[] = [];
-    if (options.since) parts.push(`from ${options.since}`);
-    if (options.until) parts.push(`to ${options.until}`);
+    if (options.since) parts.push(t("range.from", { date: options.since }));
+    if (options.until) parts.push(t("range.to", { date: options.until }));
     return parts.join(" ");
   }
   return null;
 }
 
+/**
+ * Apply --locale for this run. An unsupported locale is reported and the
+ * detected one is kept.
+ */
+function applyLocaleOption(options: { locale?: string }): void {
+  if (options.locale && !setLocale(options.locale)) {
+    console.error(
+      pc.yellow(`  Unsupported locale: ${options.locale} (available: ${SUPPORTED_LOCALES.join(", ")}); using ${getLocale()}`)
+    );
+  }
+}
+
 function buildTUIOptions(
-  options: FilterOptions & DateFilterOptions,
+  options: FilterOptions & DateFilterOptions & { live?: boolean },
   initialTab?: TabType
 ): TUIOptions {
   const dateFilters = getDateFilters(options);
@@ -199,6 +251,7 @@ function buildTUIOptions(
     since: dateFilters.since,
     until: dateFilters.until,
     year: dateFilters.year,
+    live: options.live,
   };
 }
 
@@ -208,13 +261,32 @@ async function main() {
   program
     .name("tokscale")
     .description("Tokscale - Track AI coding costs across OpenCode, Claude Code, Codex, Gemini, Cursor, and Amp")
-    .version(pkg.version);
+    .version(pkg.version)
+    // Handled in plain.ts before commander runs; declared here for --help
+    .option("--plain", "Screen-reader-friendly output: no color, borders, spinners or TUI (any command)")
+    // Handled in read-only.ts; declared here for --help
+    .option("--home <dir>", "Report on another home directory (e.g. a backup) read-only, leaving your own data untouched")
+    // Handled in verbose.ts; declared here for --help
+    .option("--verbose", "Print pricing fetch telemetry (cache hits, requests, retries, network vs parse time) to stderr")
+    .hook("preAction", (_program, actionCommand) => {
+      let command = actionCommand;
+      while (command.parent && command.parent !== program) command = command.parent;
+      const refusal = readOnlyHome() ? readOnlyRefusal(command.name(), actionCommand.opts()) : undefined;
+      if (refusal) {
+        console.error(pc.red(`\n  ${refusal}\n`));
+        process.exit(1);
+      }
+    });
 
   program
     .command("monthly")
     .description("Show monthly usage report (launches TUI by default)")
     .option("--light", "Use legacy CLI table output instead of TUI")
     .option("--json", "Output as JSON (for scripting)")
+    .option("--limit <n>", "Show at most this many months")
+    .option("--offset <n>", "Skip this many months first")
+    .option("--columns <keys>", "Show only these columns, comma-separated (e.g. month,models,cost)")
+    .option("--no-pager", "Don't page tables taller than the terminal through $PAGER")
     .option("--opencode", "Show only OpenCode usage")
     .option("--claude", "Show only Claude Code usage")
     .option("--codex", "Show only Codex CLI usage")
@@ -222,18 +294,24 @@ async function main() {
     .option("--cursor", "Show only Cursor IDE usage")
     .option("--amp", "Show only Amp usage")
     .option("--droid", "Show only Factory Droid usage")
+    .option("--cline", "Show only Cline usage")
+    .option("--roocode", "Show only Roo Code usage")
+    .option("--recorded", "Show only recorded usage (see 'tokscale record')")
     .option("--today", "Show only today's usage")
     .option("--week", "Show last 7 days")
     .option("--month", "Show current month")
     .option("--since <date>", "Start date (YYYY-MM-DD)")
     .option("--until <date>", "End date (YYYY-MM-DD)")
     .option("--year <year>", "Filter to specific year")
+    .option("--as-of <date>", "Reproduce the report as it was on this date (YYYY-MM-DD): data up to it, prices archived by then")
+    .option("--locale <tag>", "Language for report text, e.g. pt-BR (default: locale setting, else from the environment)")
     .option("--benchmark", "Show processing time")
     .option("--no-spinner", "Disable spinner (for AI agents and scripts - keeps stdout clean)")
     .action(async (options) => {
+      applyLocaleOption(options);
       if (options.json) {
         await outputJsonReport("monthly", options);
-      } else if (options.light) {
+      } else if (options.light || options.asOf || isPlain()) {
         await showMonthlyReport(options, { spinner: options.spinner });
       } else {
         const launchTUI = await tryLoadTUI();
@@ -251,6 +329,11 @@ async function main() {
     .description("Show usage breakdown by model (launches TUI by default)")
     .option("--light", "Use legacy CLI table output instead of TUI")
     .option("--json", "Output as JSON (for scripting)")
+    .option("--limit <n>", "Show at most this many models")
+    .option("--offset <n>", "Skip this many models first")
+    .option("--columns <keys>", "Show only these columns, comma-separated (e.g. model,input,output,cost)")
+    .option("--by-family", "One line per model family (e.g. every Sonnet version), with its versions listed")
+    .option("--no-pager", "Don't page tables taller than the terminal through $PAGER")
     .option("--opencode", "Show only OpenCode usage")
     .option("--claude", "Show only Claude Code usage")
     .option("--codex", "Show only Codex CLI usage")
@@ -258,18 +341,24 @@ async function main() {
     .option("--cursor", "Show only Cursor IDE usage")
     .option("--amp", "Show only Amp usage")
     .option("--droid", "Show only Factory Droid usage")
+    .option("--cline", "Show only Cline usage")
+    .option("--roocode", "Show only Roo Code usage")
+    .option("--recorded", "Show only recorded usage (see 'tokscale record')")
     .option("--today", "Show only today's usage")
     .option("--week", "Show last 7 days")
     .option("--month", "Show current month")
     .option("--since <date>", "Start date (YYYY-MM-DD)")
     .option("--until <date>", "End date (YYYY-MM-DD)")
     .option("--year <year>", "Filter to specific year")
+    .option("--as-of <date>", "Reproduce the report as it was on this date (YYYY-MM-DD): data up to it, prices archived by then")
+    .option("--locale <tag>", "Language for report text, e.g. pt-BR (default: locale setting, else from the environment)")
     .option("--benchmark", "Show processing time")
     .option("--no-spinner", "Disable spinner (for AI agents and scripts - keeps stdout clean)")
     .action(async (options) => {
+      applyLocaleOption(options);
       if (options.json) {
         await outputJsonReport("models", options);
-      } else if (options.light) {
+      } else if (options.light || options.asOf || options.byFamily || isPlain()) {
         await showModelReport(options, { spinner: options.spinner });
       } else {
         const launchTUI = await tryLoadTUI();
@@ -286,6 +375,11 @@ async function main() {
     .command("graph")
     .description("Export contribution graph data as JSON")
     .option("--output <file>", "Write to file instead of stdout")
+    .option(
+      "--encrypt-to <publicKey>",
+      "Encrypt the export to a team public key (age1...); repeat for several keys",
+      (value: string, previous: string[] = []) => [...previous, value]
+    )
     .option("--opencode", "Include only OpenCode data")
     .option("--claude", "Include only Claude Code data")
     .option("--codex", "Include only Codex CLI data")
@@ -293,6 +387,9 @@ async function main() {
     .option("--cursor", "Include only Cursor IDE data")
     .option("--amp", "Include only Amp data")
     .option("--droid", "Include only Factory Droid data")
+    .option("--cline", "Include only Cline data")
+    .option("--roocode", "Include only Roo Code data")
+    .option("--recorded", "Include only recorded data (see 'tokscale record')")
     .option("--today", "Show only today's usage")
     .option("--week", "Show last 7 days")
     .option("--month", "Show current month")
@@ -317,6 +414,9 @@ async function main() {
     .option("--cursor", "Include only Cursor IDE data")
     .option("--amp", "Include only Amp data")
     .option("--droid", "Include only Factory Droid data")
+    .option("--cline", "Include only Cline data")
+    .option("--roocode", "Include only Roo Code data")
+    .option("--recorded", "Include only recorded data (see 'tokscale record')")
     .option("--no-spinner", "Disable loading spinner (for scripting)")
     .option("--short", "Display total tokens in abbreviated format (e.g., 7.14B)")
     .addOption(new Option("--agents", "Show Top OpenCode Agents (default)").conflicts("clients"))
@@ -365,6 +465,7 @@ async function main() {
     .option("--until <date>", "End date (YYYY-MM-DD)")
     .option("--year <year>", "Filter to specific year")
     .option("--dry-run", "Show what would be submitted without actually submitting")
+    .option("--rollup", "Send pre-aggregated daily rollups (one row per day, source and model) for a smaller payload")
     .action(async (options) => {
       await submit({
         opencode: options.opencode,
@@ -378,6 +479,7 @@ async function main() {
         until: options.until,
         year: options.year,
         dryRun: options.dryRun,
+        rollup: options.rollup,
       });
     });
 
@@ -395,13 +497,26 @@ async function main() {
     .option("--cursor", "Show only Cursor IDE usage")
     .option("--amp", "Show only Amp usage")
     .option("--droid", "Show only Factory Droid usage")
+    .option("--cline", "Show only Cline usage")
+    .option("--roocode", "Show only Roo Code usage")
     .option("--today", "Show only today's usage")
     .option("--week", "Show last 7 days")
     .option("--month", "Show current month")
     .option("--since <date>", "Start date (YYYY-MM-DD)")
     .option("--until <date>", "End date (YYYY-MM-DD)")
     .option("--year <year>", "Filter to specific year")
+    .option("--live", "Start in live mode: update as agents write their sessions (toggle with l)")
     .action(async (options) => {
+      if (isPlain() && options.live) {
+        console.log("\n  The interactive UI isn't available with --plain; the model report is printed again whenever sessions change. Press Ctrl+C to stop.");
+        await showLivePlainReport(options);
+        return;
+      }
+      if (isPlain()) {
+        console.log("\n  The interactive UI isn't available with --plain; showing the model report instead.");
+        await showModelReport(options, { spinner: false });
+        return;
+      }
       const launchTUI = await tryLoadTUI();
       if (launchTUI) {
         await launchTUI(buildTUIOptions(options));
@@ -416,111 +531,820 @@ async function main() {
     .description("Look up pricing for a model")
     .option("--json", "Output as JSON")
     .option("--provider <source>", "Force pricing source: 'litellm' or 'openrouter'")
+    .option("--compare <models>", "Compare blended per-1M-token rates with other models (comma-separated)")
+    .option("--input-ratio <ratio>", "Share of input tokens when blending, 0-1 (default: 0.75)")
     .option("--no-spinner", "Disable spinner (for AI agents and scripts - keeps stdout clean)")
-    .action(async (modelId: string, options: { json?: boolean; provider?: string; spinner?: boolean }) => {
+    .action(async (modelId: string, options: PricingCommandOptions) => {
       await handlePricingCommand(modelId, options);
     });
 
-  const cursorCommand = program
-    .command("cursor")
-    .description("Cursor IDE integration commands");
-
-  cursorCommand
-    .command("login")
-    .description("Login to Cursor (paste your session token)")
-    .action(async () => {
-      await cursorLogin();
+  program
+    .command("query <sql>")
+    .description("Run SQL over your usage data (tables: messages, daily; requires @duckdb/node-api)")
+    .option("--json", "Output rows as JSON")
+    .option("--opencode", "Include only OpenCode data")
+    .option("--claude", "Include only Claude Code data")
+    .option("--codex", "Include only Codex CLI data")
+    .option("--gemini", "Include only Gemini CLI data")
+    .option("--cursor", "Include only Cursor IDE data")
+    .option("--amp", "Include only Amp data")
+    .option("--droid", "Include only Factory Droid data")
+    .option("--cline", "Include only Cline data")
+    .option("--roocode", "Include only Roo Code data")
+    .option("--recorded", "Include only recorded data (see 'tokscale record')")
+    .option("--since <date>", "Start date (YYYY-MM-DD)")
+    .option("--until <date>", "End date (YYYY-MM-DD)")
+    .option("--year <year>", "Filter to specific year")
+    .option("--no-spinner", "Disable spinner (for AI agents and scripts - keeps stdout clean)")
+    .action(async (sql: string, options: QueryCommandOptions) => {
+      await handleQueryCommand(sql, options);
     });
 
-  cursorCommand
-    .command("logout")
-    .description("Logout from Cursor")
-    .action(async () => {
-      await cursorLogout();
+  program
+    .command("activity")
+    .description("Show active hours and cost per active hour, with idle gaps between messages trimmed")
+    .option("--json", "Output as JSON")
+    .option("--limit <n>", "Show at most this many sessions (default: 10)")
+    .option("--offset <n>", "Skip this many sessions first")
+    .option("--columns <keys>", "Show only these columns, comma-separated (e.g. session,started,cost)")
+    .option("--no-pager", "Don't page tables taller than the terminal through $PAGER")
+    .option("--idle <minutes>", "Gaps between messages longer than this are idle time (default: idleThresholdMinutes setting, else 30)")
+    .option("--tag <tag>", "Show only sessions with this tag: expensive, token-heavy, long-running, agent-heavy")
+    .option("--opencode", "Include only OpenCode data")
+    .option("--claude", "Include only Claude Code data")
+    .option("--codex", "Include only Codex CLI data")
+    .option("--gemini", "Include only Gemini CLI data")
+    .option("--cursor", "Include only Cursor IDE data")
+    .option("--amp", "Include only Amp data")
+    .option("--droid", "Include only Factory Droid data")
+    .option("--cline", "Include only Cline data")
+    .option("--roocode", "Include only Roo Code data")
+    .option("--recorded", "Include only recorded data (see 'tokscale record')")
+    .option("--today", "Show only today's usage")
+    .option("--week", "Show last 7 days")
+    .option("--month", "Show current month")
+    .option("--since <date>", "Start date (YYYY-MM-DD)")
+    .option("--until <date>", "End date (YYYY-MM-DD)")
+    .option("--year <year>", "Filter to specific year")
+    .option("--no-spinner", "Disable spinner (for AI agents and scripts - keeps stdout clean)")
+    .action(async (options: ActivityCommandOptions) => {
+      await handleActivityCommand(options);
     });
 
-  cursorCommand
-    .command("status")
-    .description("Check Cursor authentication status")
-    .action(async () => {
-      await cursorStatus();
+  program
+    .command("efficiency")
+    .description("Rank sources by cost per active hour and per session, over comparable sessions")
+    .option("--json", "Output as JSON")
+    .option("--columns <keys>", "Show only these columns, comma-separated (e.g. source,per-hour,per-session)")
+    .option("--no-pager", "Don't page tables taller than the terminal through $PAGER")
+    .option("--idle <minutes>", "Gaps between messages longer than this are idle time (default: idleThresholdMinutes setting, else 30)")
+    .option("--min-messages <n>", "Leave out sessions with fewer messages (default: 3)")
+    .option("--min-minutes <minutes>", "Leave out sessions with less active time (default: 5)")
+    .option("--family <family>", "Compare only usage of one model family, e.g. claude-sonnet")
+    .option("--opencode", "Include only OpenCode data")
+    .option("--claude", "Include only Claude Code data")
+    .option("--codex", "Include only Codex CLI data")
+    .option("--gemini", "Include only Gemini CLI data")
+    .option("--cursor", "Include only Cursor IDE data")
+    .option("--amp", "Include only Amp data")
+    .option("--droid", "Include only Factory Droid data")
+    .option("--cline", "Include only Cline data")
+    .option("--roocode", "Include only Roo Code data")
+    .option("--recorded", "Include only recorded data (see 'tokscale record')")
+    .option("--today", "Show only today's usage")
+    .option("--week", "Show last 7 days")
+    .option("--month", "Show current month")
+    .option("--since <date>", "Start date (YYYY-MM-DD)")
+    .option("--until <date>", "End date (YYYY-MM-DD)")
+    .option("--year <year>", "Filter to specific year")
+    .option("--no-spinner", "Disable spinner (for AI agents and scripts - keeps stdout clean)")
+    .action(async (options: EfficiencyCommandOptions) => {
+      await handleEfficiencyCommand(options);
     });
 
-  // Check if a subcommand was provided
-  const args = process.argv.slice(2);
-  const firstArg = args[0] || '';
-  // Global flags should go to main program
-  const isGlobalFlag = ['--help', '-h', '--version', '-V'].includes(firstArg);
-  const hasSubcommand = args.length > 0 && !firstArg.startsWith('-');
-  const knownCommands = ['monthly', 'models', 'graph', 'wrapped', 'login', 'logout', 'whoami', 'submit', 'cursor', 'tui', 'pricing', 'help'];
-  const isKnownCommand = hasSubcommand && knownCommands.includes(firstArg);
-
-  if (isKnownCommand || isGlobalFlag) {
-    // Run the specified subcommand or show full help/version
-    await program.parseAsync();
-  } else {
-    // No subcommand - launch TUI by default, or legacy CLI with --light, or JSON with --json
-    const defaultProgram = new Command();
-    defaultProgram
-      .option("--light", "Use legacy CLI table output instead of TUI")
-      .option("--json", "Output as JSON (for scripting)")
-      .option("--opencode", "Show only OpenCode usage")
-      .option("--claude", "Show only Claude Code usage")
-      .option("--codex", "Show only Codex CLI usage")
-      .option("--gemini", "Show only Gemini CLI usage")
-      .option("--cursor", "Show only Cursor IDE usage")
-      .option("--amp", "Show only Amp usage")
-      .option("--today", "Show only today's usage")
-      .option("--week", "Show last 7 days")
-      .option("--month", "Show current month")
-      .option("--since <date>", "Start date (YYYY-MM-DD)")
-      .option("--until <date>", "End date (YYYY-MM-DD)")
-      .option("--year <year>", "Filter to specific year")
-      .option("--benchmark", "Show processing time")
-      .option("--no-spinner", "Disable spinner (for AI agents and scripts - keeps stdout clean)")
-      .parse();
-    
-    const opts = defaultProgram.opts();
-    if (opts.json) {
-      await outputJsonReport("models", opts);
-    } else if (opts.light) {
-      await showModelReport(opts, { spinner: opts.spinner });
-    } else {
-      const launchTUI = await tryLoadTUI();
-      if (launchTUI) {
-        await launchTUI(buildTUIOptions(opts));
-      } else {
-        showTUIUnavailableMessage();
-        await showModelReport(opts, { spinner: opts.spinner });
-      }
-    }
-  }
-}
+  program
+    .command("branches")
+    .description("Show usage per git branch, to see what developing a feature branch cost")
+    .option("--json", "Output as JSON")
+    .option("--limit <n>", "Show at most this many branches")
+    .option("--offset <n>", "Skip this many branches first")
+    .option("--columns <keys>", "Show only these columns, comma-separated (e.g. branch,sessions,cost)")
+    .option("--no-pager", "Don't page tables taller than the terminal through $PAGER")
+    .option("--opencode", "Include only OpenCode data")
+    .option("--claude", "Include only Claude Code data")
+    .option("--codex", "Include only Codex CLI data")
+    .option("--gemini", "Include only Gemini CLI data")
+    .option("--cursor", "Include only Cursor IDE data")
+    .option("--amp", "Include only Amp data")
+    .option("--droid", "Include only Factory Droid data")
+    .option("--cline", "Include only Cline data")
+    .option("--roocode", "Include only Roo Code data")
+    .option("--recorded", "Include only recorded data (see 'tokscale record')")
+    .option("--today", "Show only today's usage")
+    .option("--week", "Show last 7 days")
+    .option("--month", "Show current month")
+    .option("--since <date>", "Start date (YYYY-MM-DD)")
+    .option("--until <date>", "End date (YYYY-MM-DD)")
+    .option("--year <year>", "Filter to specific year")
+    .option("--no-spinner", "Disable spinner (for AI agents and scripts - keeps stdout clean)")
+    .action(async (options: BranchesCommandOptions) => {
+      await handleBranchesCommand(options);
+    });
 
-function getEnabledSources(options: FilterOptions): SourceType[] | undefined {
-  const hasFilter = options.opencode || options.claude || options.codex || options.gemini || options.cursor || options.amp || options.droid;
-  if (!hasFilter) return undefined; // All sources
+  program
+    .command("commits")
+    .description("Estimate agent cost per git commit by matching session times against local commit history")
+    .option("--json", "Output as JSON")
+    .option("--limit <n>", "Show at most this many commits (default: 20)")
+    .option("--offset <n>", "Skip this many commits first")
+    .option("--columns <keys>", "Show only these columns, comma-separated (e.g. date,commit,cost)")
+    .option("--no-pager", "Don't page tables taller than the terminal through $PAGER")
+    .option("--window <hours>", "Charge usage to a commit made at most this many hours later (default: 24)")
+    .option("--author <pattern>", "Only consider commits by matching authors (as git log --author)")
+    .option("--opencode", "Include only OpenCode data")
+    .option("--claude", "Include only Claude Code data")
+    .option("--codex", "Include only Codex CLI data")
+    .option("--gemini", "Include only Gemini CLI data")
+    .option("--cursor", "Include only Cursor IDE data")
+    .option("--amp", "Include only Amp data")
+    .option("--droid", "Include only Factory Droid data")
+    .option("--cline", "Include only Cline data")
+    .option("--roocode", "Include only Roo Code data")
+    .option("--recorded", "Include only recorded data (see 'tokscale record')")
+    .option("--today", "Show only today's usage")
+    .option("--week", "Show last 7 days")
+    .option("--month", "Show current month")
+    .option("--since <date>", "Start date (YYYY-MM-DD)")
+    .option("--until <date>", "End date (YYYY-MM-DD)")
+    .option("--year <year>", "Filter to specific year")
+    .option("--no-spinner", "Disable spinner (for AI agents and scripts - keeps stdout clean)")
+    .action(async (options: CommitsCommandOptions) => {
+      await handleCommitsCommand(options);
+    });
 
-  const sources: SourceType[] = [];
-  if (options.opencode) sources.push("opencode");
-  if (options.claude) sources.push("claude");
-  if (options.codex) sources.push("codex");
-  if (options.gemini) sources.push("gemini");
-  if (options.cursor) sources.push("cursor");
-  if (options.amp) sources.push("amp");
-  if (options.droid) sources.push("droid");
-  return sources;
-}
+  program
+    .command("modes")
+    .description("Split spend between interactive chat and agentic use (tool loops, sub-agents, automated bursts)")
+    .option("--json", "Output as JSON")
+    .option("--opencode", "Include only OpenCode data")
+    .option("--claude", "Include only Claude Code data")
+    .option("--codex", "Include only Codex CLI data")
+    .option("--gemini", "Include only Gemini CLI data")
+    .option("--cursor", "Include only Cursor IDE data")
+    .option("--amp", "Include only Amp data")
+    .option("--droid", "Include only Factory Droid data")
+    .option("--cline", "Include only Cline data")
+    .option("--roocode", "Include only Roo Code data")
+    .option("--recorded", "Include only recorded data (see 'tokscale record')")
+    .option("--today", "Show only today's usage")
+    .option("--week", "Show last 7 days")
+    .option("--month", "Show current month")
+    .option("--since <date>", "Start date (YYYY-MM-DD)")
+    .option("--until <date>", "End date (YYYY-MM-DD)")
+    .option("--year <year>", "Filter to specific year")
+    .option("--no-spinner", "Disable spinner (for AI agents and scripts - keeps stdout clean)")
+    .action(async (options: ModesCommandOptions) => {
+      await handleModesCommand(options);
+    });
 
+  program
+    .command("drivers")
+    .description("Show what drives cost: turns by prompt length, tool calls and files touched (Claude Code and Codex)")
+    .option("--json", "Output as JSON")
+    .option("--claude", "Include only Claude Code data")
+    .option("--codex", "Include only Codex CLI data")
+    .option("--today", "Show only today's usage")
+    .option("--week", "Show last 7 days")
+    .option("--month", "Show current month")
+    .option("--since <date>", "Start date (YYYY-MM-DD)")
+    .option("--until <date>", "End date (YYYY-MM-DD)")
+    .option("--year <year>", "Filter to specific year")
+    .option("--no-spinner", "Disable spinner (for AI agents and scripts - keeps stdout clean)")
+    .action(async (options: DriversCommandOptions) => {
+      await handleDriversCommand(options);
+    });
 
+  program
+    .command("context")
+    .description("Show how often requests approach or exceed each model's context window")
+    .option("--json", "Output as JSON")
+    .option("--threshold <percent>", "Share of the context window that counts as near the limit (default: 90)")
+    .option("--opencode", "Include only OpenCode data")
+    .option("--claude", "Include only Claude Code data")
+    .option("--codex", "Include only Codex CLI data")
+    .option("--gemini", "Include only Gemini CLI data")
+    .option("--cursor", "Include only Cursor IDE data")
+    .option("--amp", "Include only Amp data")
+    .option("--droid", "Include only Factory Droid data")
+    .option("--cline", "Include only Cline data")
+    .option("--roocode", "Include only Roo Code data")
+    .option("--recorded", "Include only recorded data (see 'tokscale record')")
+    .option("--today", "Show only today's usage")
+    .option("--week", "Show last 7 days")
+    .option("--month", "Show current month")
+    .option("--since <date>", "Start date (YYYY-MM-DD)")
+    .option("--until <date>", "End date (YYYY-MM-DD)")
+    .option("--year <year>", "Filter to specific year")
+    .option("--no-spinner", "Disable spinner (for AI agents and scripts - keeps stdout clean)")
+    .action(async (options: ContextCommandOptions) => {
+      await handleContextCommand(options);
+    });
 
+  program
+    .command("projects")
+    .description("Show usage per project, grouping sessions by the git repository they ran in")
+    .option("--json", "Output as JSON")
+    .option("--limit <n>", "Show at most this many projects")
+    .option("--offset <n>", "Skip this many projects first")
+    .option("--columns <keys>", "Show only these columns, comma-separated (e.g. project,messages,cost)")
+    .option("--no-pager", "Don't page tables taller than the terminal through $PAGER")
+    .option("--opencode", "Include only OpenCode data")
+    .option("--claude", "Include only Claude Code data")
+    .option("--codex", "Include only Codex CLI data")
+    .option("--gemini", "Include only Gemini CLI data")
+    .option("--cursor", "Include only Cursor IDE data")
+    .option("--amp", "Include only Amp data")
+    .option("--droid", "Include only Factory Droid data")
+    .option("--cline", "Include only Cline data")
+    .option("--roocode", "Include only Roo Code data")
+    .option("--recorded", "Include only recorded data (see 'tokscale record')")
+    .option("--today", "Show only today's usage")
+    .option("--week", "Show last 7 days")
+    .option("--month", "Show current month")
+    .option("--since <date>", "Start date (YYYY-MM-DD)")
+    .option("--until <date>", "End date (YYYY-MM-DD)")
+    .option("--year <year>", "Filter to specific year")
+    .option("--no-spinner", "Disable spinner (for AI agents and scripts - keeps stdout clean)")
+    .action(async (options: ProjectsCommandOptions) => {
+      await handleProjectsCommand(options);
+    });
 
+  program
+    .command("rates")
+    .description("Show what each model actually cost per 1M tokens, next to its list price")
+    .option("--json", "Output as JSON")
+    .option("--limit <n>", "Show at most this many models")
+    .option("--offset <n>", "Skip this many models first")
+    .option("--columns <keys>", "Show only these columns, comma-separated (e.g. model,effective,cache-hits)")
+    .option("--no-pager", "Don't page tables taller than the terminal through $PAGER")
+    .option("--opencode", "Include only OpenCode data")
+    .option("--claude", "Include only Claude Code data")
+    .option("--codex", "Include only Codex CLI data")
+    .option("--gemini", "Include only Gemini CLI data")
+    .option("--cursor", "Include only Cursor IDE data")
+    .option("--amp", "Include only Amp data")
+    .option("--droid", "Include only Factory Droid data")
+    .option("--cline", "Include only Cline data")
+    .option("--roocode", "Include only Roo Code data")
+    .option("--recorded", "Include only recorded data (see 'tokscale record')")
+    .option("--today", "Show only today's usage")
+    .option("--week", "Show last 7 days")
+    .option("--month", "Show current month")
+    .option("--since <date>", "Start date (YYYY-MM-DD)")
+    .option("--until <date>", "End date (YYYY-MM-DD)")
+    .option("--year <year>", "Filter to specific year")
+    .option("--no-spinner", "Disable spinner (for AI agents and scripts - keeps stdout clean)")
+    .action(async (options: RatesCommandOptions) => {
+      await handleRatesCommand(options);
+    });
 
-/**
- * Sync Cursor usage data from API to local cache.
- * Only attempts sync if user is authenticated with Cursor.
+  program
+    .command("recent")
+    .description("Show hourly or 15-minute usage for the last few days, and the current burn rate")
+    .option("--json", "Output as JSON")
+    .option("--limit <n>", "Show at most this many buckets")
+    .option("--offset <n>", "Skip this many buckets first")
+    .option("--columns <keys>", "Show only these columns, comma-separated (e.g. start,cost)")
+    .option("--no-pager", "Don't page tables taller than the terminal through $PAGER")
+    .option("--granularity <width>", "Bucket width: 'hour' or '15m' (default: hour)")
+    .option("--days <n>", "Days to show, up to 14 (default: recentBucketDays setting, else 2)")
+    .option("--all", "Include empty buckets")
+    .option("--opencode", "Include only OpenCode data")
+    .option("--claude", "Include only Claude Code data")
+    .option("--codex", "Include only Codex CLI data")
+    .option("--gemini", "Include only Gemini CLI data")
+    .option("--cursor", "Include only Cursor IDE data")
+    .option("--amp", "Include only Amp data")
+    .option("--droid", "Include only Factory Droid data")
+    .option("--cline", "Include only Cline data")
+    .option("--roocode", "Include only Roo Code data")
+    .option("--recorded", "Include only recorded data (see 'tokscale record')")
+    .option("--no-spinner", "Disable spinner (for AI agents and scripts - keeps stdout clean)")
+    .action(async (options: RecentCommandOptions) => {
+      await handleRecentCommand(options);
+    });
+
+  program
+    .command("reconcile <invoice>")
+    .description("Check a provider billing CSV (Anthropic or OpenAI export) against computed costs per day and model")
+    .option("--json", "Output as JSON")
+    .option("--provider <provider>", "Invoice provider: 'anthropic' or 'openai' (default: detected from the CSV)")
+    .option("--tolerance <percent>", "Flag differences above this percentage (default: 5)")
+    .option("--min-difference <usd>", "Never flag differences up to this many USD (default: 0.01)")
+    .option("--mismatches-only", "Only show flagged lines")
+    .option("--no-spinner", "Disable spinner (for AI agents and scripts - keeps stdout clean)")
+    .action(async (invoice: string, options: ReconcileCommandOptions) => {
+      await handleReconcileCommand(invoice, options);
+    });
+
+  program
+    .command("diff [exports...]")
+    .description("Compare totals, model mix and per-project usage between two periods, or between two exported JSON reports")
+    .option("--json", "Output as JSON")
+    .option("--from <period>", "Earlier period: YYYY, YYYY-MM or YYYY-MM-DD")
+    .option("--to <period>", "Later period: YYYY, YYYY-MM or YYYY-MM-DD")
+    .option("--limit <n>", "Show at most this many models and projects (default: 10)")
+    .option("--opencode", "Include only OpenCode data")
+    .option("--claude", "Include only Claude Code data")
+    .option("--codex", "Include only Codex CLI data")
+    .option("--gemini", "Include only Gemini CLI data")
+    .option("--cursor", "Include only Cursor IDE data")
+    .option("--amp", "Include only Amp data")
+    .option("--droid", "Include only Factory Droid data")
+    .option("--cline", "Include only Cline data")
+    .option("--roocode", "Include only Roo Code data")
+    .option("--recorded", "Include only recorded data (see 'tokscale record')")
+    .option("--no-spinner", "Disable spinner (for AI agents and scripts - keeps stdout clean)")
+    .action(async (exports: string[], options: DiffCommandOptions) => {
+      await handleDiffCommand(exports, options);
+    });
+
+  program
+    .command("statements [month]")
+    .description("Monthly statements per provider, with line items by model, for booking AI costs per vendor")
+    .option("--format <format>", "'markdown' (default), 'csv' or 'json'")
+    .option("--provider <id>", "Only statements for this provider, e.g. anthropic")
+    .option("--output-dir <dir>", "Write a Markdown file per statement, or statements.csv, instead of printing")
+    .option("--opencode", "Include only OpenCode data")
+    .option("--claude", "Include only Claude Code data")
+    .option("--codex", "Include only Codex CLI data")
+    .option("--gemini", "Include only Gemini CLI data")
+    .option("--cursor", "Include only Cursor IDE data")
+    .option("--amp", "Include only Amp data")
+    .option("--droid", "Include only Factory Droid data")
+    .option("--cline", "Include only Cline data")
+    .option("--roocode", "Include only Roo Code data")
+    .option("--recorded", "Include only recorded data (see 'tokscale record')")
+    .option("--since <date>", "Start date (YYYY-MM-DD)")
+    .option("--until <date>", "End date (YYYY-MM-DD)")
+    .option("--year <year>", "Filter to specific year")
+    .option("--no-spinner", "Disable spinner (for AI agents and scripts - keeps stdout clean)")
+    .action(async (month: string | undefined, options: StatementsCommandOptions) => {
+      await handleStatementsCommand(month, options);
+    });
+
+  program
+    .command("budgets")
+    .description("Show spend against the budgets in settings.json, projected to period end at the current burn rate")
+    .option("--enforce", "Run the enforce hook of budgets over their cap (once per period)")
+    .option("--json", "Output as JSON")
+    .option("--no-spinner", "Disable spinner (for AI agents and scripts - keeps stdout clean)")
+    .action(async (options: BudgetsCommandOptions) => {
+      await handleBudgetsCommand(options);
+    });
+
+  program
+    .command("check")
+    .description("Check usage against thresholds and exit non-zero when one fails, for scheduled CI jobs")
+    .option("--max-daily <usd>", "Fail if any day costs more than this")
+    .option("--max-unknown-model-share <share>", "Fail if more than this share (0-1) of messages used models without a list price")
+    .option("--json", "Output as JSON")
+    .option("--opencode", "Include only OpenCode data")
+    .option("--claude", "Include only Claude Code data")
+    .option("--codex", "Include only Codex CLI data")
+    .option("--gemini", "Include only Gemini CLI data")
+    .option("--cursor", "Include only Cursor IDE data")
+    .option("--amp", "Include only Amp data")
+    .option("--droid", "Include only Factory Droid data")
+    .option("--cline", "Include only Cline data")
+    .option("--roocode", "Include only Roo Code data")
+    .option("--recorded", "Include only recorded data (see 'tokscale record')")
+    .option("--today", "Check only today's usage (the default)")
+    .option("--week", "Check last 7 days")
+    .option("--month", "Check current month")
+    .option("--since <date>", "Start date (YYYY-MM-DD)")
+    .option("--until <date>", "End date (YYYY-MM-DD)")
+    .option("--year <year>", "Filter to specific year")
+    .option("--no-spinner", "Disable spinner (for AI agents and scripts - keeps stdout clean)")
+    .addHelpText("after", `
+Exit codes: 0 when every check passes, 1 when one fails, 2 when usage
+couldn't be checked (bad arguments, prices unavailable).
+
+Example:
+  tokscale check --week --max-daily 50 --max-unknown-model-share 0.05`)
+    .action(async (options: CheckCommandOptions) => {
+      await handleCheckCommand(options);
+    });
+
+  program
+    .command("doctor")
+    .description("Check that prices can be fetched, and whether a failure is local or a provider outage")
+    .option("--json", "Output as JSON")
+    .option("--provider-status", "Check provider status pages even when every fetch works")
+    .option("--no-spinner", "Disable spinner (for AI agents and scripts - keeps stdout clean)")
+    .action(async (options: DoctorCommandOptions) => {
+      await handleDoctorCommand(options);
+    });
+
+  program
+    .command("capabilities")
+    .description("List the features, sources, pricing providers and export formats this install supports")
+    .option("--json", "Output as JSON")
+    .action((options: { json?: boolean }) => {
+      handleCapabilitiesCommand(options);
+    });
+
+  program
+    .command("schema <report>")
+    .description("Print the JSON Schema for a --json report: models, monthly or graph")
+    .action((report: string) => {
+      handleSchemaCommand(report);
+    });
+
+  program
+    .command("conformance <source> <paths...>")
+    .description("Check that session logs written by a tool parse cleanly (exits 1 on errors; for tool authors' CI)")
+    .option("--json", "Output as JSON")
+    .action(async (source: string, paths: string[], options: { json?: boolean }) => {
+      await handleConformanceCommand(source, paths, options);
+    });
+
+  const cacheCommand = program
+    .command("cache")
+    .description("Manage the local pricing and parse caches");
+
+  cacheCommand
+    .command("clean")
+    .description("Remove orphaned temp files, stale checkpoints and stale parse-cache entries")
+    .option("--dry-run", "Show what would be removed without deleting anything")
+    .option("--json", "Output as JSON")
+    .action(async (options: { dryRun?: boolean; json?: boolean }) => {
+      await handleCacheCleanCommand(options);
+    });
+
+  const configCommand = program
+    .command("config")
+    .description("Inspect settings.json (~/.config/tokscale/settings.json)");
+
+  configCommand
+    .command("check")
+    .description("Report unknown keys, mistyped values and wildcards in substring patterns, with line and column")
+    .option("--file <path>", "Check this file instead of settings.json")
+    .option("--json", "Output as JSON")
+    .action(async (options: { file?: string; json?: boolean }) => {
+      await handleConfigCheckCommand(options);
+    });
+
+  configCommand
+    .command("get <key>")
+    .description("Print a setting by dotted key, e.g. 'costDisplay.precision'")
+    .option("--json", "Output as JSON")
+    .action((key: string, options: { json?: boolean }) => {
+      handleConfigGetCommand(key, options);
+    });
+
+  configCommand
+    .command("set <key> <value>")
+    .description("Set a setting; the value is parsed as JSON when it can be (200, true, [\"a\"]), otherwise kept as text")
+    .action(async (key: string, value: string) => {
+      await handleConfigSetCommand(key, value);
+    });
+
+  configCommand
+    .command("unset <key>")
+    .description("Remove a setting, restoring its default")
+    .action(async (key: string) => {
+      await handleConfigUnsetCommand(key);
+    });
+
+  configCommand
+    .command("list")
+    .description("List every setting in settings.json as key = value")
+    .option("--json", "Output the whole file as JSON")
+    .action((options: { json?: boolean }) => {
+      handleConfigListCommand(options);
+    });
+
+  const secretsCommand = program
+    .command("secrets")
+    .description("Manage API keys for remote importers (kept in the OS keychain, not in settings)");
+
+  secretsCommand
+    .command("set <name>")
+    .description("Store an API key in the OS keychain, e.g. 'openrouter' (reads the key from stdin when piped)")
+    .action(async (name: string) => {
+      await handleSecretsSetCommand(name);
+    });
+
+  secretsCommand
+    .command("status <names...>")
+    .description("Show where each API key is found, without printing it")
+    .option("--json", "Output as JSON")
+    .action(async (names: string[], options: { json?: boolean }) => {
+      await handleSecretsStatusCommand(names, options);
+    });
+
+  const teamCommand = program
+    .command("team")
+    .description("Team keys for end-to-end encrypted exports (age format)");
+
+  teamCommand
+    .command("keygen")
+    .description("Generate a team key pair; share the public key, keep the secret key with the team's readers")
+    .option("--output <file>", "Write the secret key to a file instead of stdout")
+    .action(async (options: { output?: string }) => {
+      await handleTeamKeygenCommand(options);
+    });
+
+  teamCommand
+    .command("decrypt <file>")
+    .description("Decrypt an export made with 'graph --encrypt-to'")
+    .requiredOption("-i, --identity <keyFile>", "File holding the team secret key (AGE-SECRET-KEY-1...)")
+    .option("--output <file>", "Write to file instead of stdout")
+    .action(async (file: string, options: { identity: string; output?: string }) => {
+      await handleTeamDecryptCommand(file, options);
+    });
+
+  program
+    .command("share-session <id>")
+    .description("Export one session as a redacted JSON bundle (tokens, costs and timing; no content, hashed IDs) for a bug report or a teammate")
+    .option("--output <file>", "Write to file instead of stdout")
+    .option("--opencode", "Look only in OpenCode data")
+    .option("--claude", "Look only in Claude Code data")
+    .option("--codex", "Look only in Codex CLI data")
+    .option("--gemini", "Look only in Gemini CLI data")
+    .option("--cursor", "Look only in Cursor IDE data")
+    .option("--amp", "Look only in Amp data")
+    .option("--droid", "Look only in Factory Droid data")
+    .option("--recorded", "Look only in recorded data (see 'tokscale record')")
+    .option("--no-spinner", "Disable spinner (for AI agents and scripts - keeps stdout clean)")
+    .addHelpText("after", `
+<id> is a session ID as shown in the TUI's session browser, or enough of its
+start to pick one session. The bundle keeps models, token counts, costs and
+timing. Prompts, responses, paths and branch names are left out, and session
+and message IDs are hashed. Read one with 'tokscale inspect <file>'.`)
+    .action(async (id: string, options: ShareSessionCommandOptions) => {
+      await handleShareSessionCommand(id, options);
+    });
+
+  program
+    .command("inspect <bundle>")
+    .description("Show a session bundle made with 'share-session'")
+    .option("--json", "Output the bundle as JSON")
+    .action(async (bundle: string, options: { json?: boolean }) => {
+      await handleInspectCommand(bundle, options);
+    });
+
+  program
+    .command("record")
+    .description("Record usage events piped in as NDJSON (one JSON event per line)")
+    .option("--check", "Report recorded lines that no longer parse (e.g. torn by a crash) instead of recording")
+    .option("--json", "Output the result as JSON")
+    .addHelpText("after", `
+Each line is an event such as:
+  {"model":"gpt-4o","input":1200,"output":300,"timestamp":"2025-06-01T12:00:00Z"}
+
+Recorded usage is reported as the "recorded" source and is never submitted.`)
+    .action(async (options: { check?: boolean; json?: boolean }) => {
+      await handleRecordCommand(options);
+    });
+
+  const importCommand = program
+    .command("import")
+    .description("Import usage from provider APIs as recorded events");
+
+  importCommand
+    .command("openrouter")
+    .description("Import OpenRouter generations with their native token counts and billed cost")
+    .option("--ids <file>", "File to read generation IDs from, such as OpenRouter's activity CSV export")
+    .option("--json", "Output the result as JSON")
+    .addHelpText("after", `
+Generations the proxy recorded (gen-... IDs) are imported too. Imported
+generations replace the proxy's estimate and the matching local session
+messages, so nothing is counted twice; re-running skips what was imported.
+Needs an OpenRouter API key: tokscale secrets set openrouter`)
+    .action(async (options: { ids?: string; json?: boolean }) => {
+      await handleImportOpenRouterCommand(options);
+    });
+
+  program
+    .command("proxy")
+    .description("Run a local OpenAI/Anthropic API proxy that records the usage of every response")
+    .option("--host <host>", "Interface to listen on", "127.0.0.1")
+    .option("--port <port>", "Port to listen on", "8787")
+    .option("--openai-upstream <url>", "Upstream for OpenAI-format requests (any OpenAI-compatible API)")
+    .option("--anthropic-upstream <url>", "Upstream for Anthropic requests")
+    .action(async (options: ProxyCommandOptions) => {
+      await handleProxyCommand(options);
+    });
+
+  const serveCommand = program
+    .command("serve")
+    .description("Serve reports over HTTP and accept recorded events, with scoped API tokens")
+    .option("--host <host>", "Interface to listen on (other than loopback, needs at least one token)", "127.0.0.1")
+    .option("--port <port>", "Port to listen on", "8788")
+    .option("--rate-limit <n>", "Requests each client may make per minute (0 for no limit)", "120")
+    .option("--emit-events", "Also write webhook events to stdout as NDJSON (server messages stay on stderr)")
+    .addHelpText("after", `
+Endpoints (Authorization: Bearer <token>):
+  GET  /api/report, /api/graph   read   (?since=&until=&year=&sources=claude,codex)
+  GET  /api/messages, /api/sessions   read   (same, plus ?limit=&cursor= from nextCursor)
+  GET|POST /api/graphql          read   (usage, messages, sessions, models and pricing in one query)
+  POST /api/events               push   (NDJSON, as for 'tokscale record')
+  GET|POST /api/sync             push   (watermarked uploads from 'tokscale push')
+  GET|POST /api/tokens, DELETE /api/tokens/<name>   admin
+  GET  /openapi.json, /docs      none   (OpenAPI document and Swagger UI)
+
+Webhooks for new sessions and cost thresholds are configured under "webhooks"
+in ~/.config/tokscale/settings.json; "eventSinks" sends the same events to
+stdout, a file or an OpenTelemetry collector.`)
+    .action(async (options: ServeCommandOptions) => {
+      await handleServeCommand(options);
+    });
+
+  const serveTokenCommand = serveCommand
+    .command("token")
+    .description("Manage API tokens for 'tokscale serve'");
+
+  serveTokenCommand
+    .command("create <name>")
+    .description("Create a token and print it (shown only once)")
+    .requiredOption("--scope <scope>", "read, push or admin")
+    .action(async (name: string, options: { scope: string }) => {
+      await handleServeTokenCreateCommand(name, options);
+    });
+
+  serveTokenCommand
+    .command("list")
+    .description("List tokens (names and scopes only)")
+    .option("--json", "Output as JSON")
+    .action(async (options: { json?: boolean }) => {
+      await handleServeTokenListCommand(options);
+    });
+
+  serveTokenCommand
+    .command("revoke <name>")
+    .description("Revoke a token; a running server stops accepting it immediately")
+    .action(async (name: string) => {
+      await handleServeTokenRevokeCommand(name);
+    });
+
+  program
+    .command("push <server>")
+    .description("Send local usage to a 'tokscale serve' team server, uploading only what it doesn't have yet")
+    .option("--token <token>", "Push-scope token (default: the 'team-server' secret, see 'tokscale secrets set team-server')")
+    .option("--batch-size <n>", "Events per request", "500")
+    .option("--since <date>", "Only push usage from this date (YYYY-MM-DD)")
+    .option("--opencode", "Include only OpenCode data")
+    .option("--claude", "Include only Claude Code data")
+    .option("--codex", "Include only Codex CLI data")
+    .option("--gemini", "Include only Gemini CLI data")
+    .option("--cursor", "Include only Cursor IDE data")
+    .option("--amp", "Include only Amp data")
+    .option("--droid", "Include only Factory Droid data")
+    .option("--cline", "Include only Cline data")
+    .option("--roocode", "Include only Roo Code data")
+    .option("--recorded", "Include only recorded data (see 'tokscale record')")
+    .option("--json", "Output as JSON")
+    .option("--no-spinner", "Disable spinner (for AI agents and scripts - keeps stdout clean)")
+    .addHelpText("after", `
+The server remembers the newest event it has from each token, so each push
+only uploads newer usage, and an interrupted push continues where it stopped.`)
+    .action(async (server: string, options: PushCommandOptions) => {
+      await handlePushCommand(server, options);
+    });
+
+  const cursorCommand = program
+    .command("cursor")
+    .description("Cursor IDE integration commands");
+
+  cursorCommand
+    .command("login")
+    .description("Login to Cursor (paste your session token)")
+    .action(async () => {
+      await cursorLogin();
+    });
+
+  cursorCommand
+    .command("logout")
+    .description("Logout from Cursor")
+    .action(async () => {
+      await cursorLogout();
+    });
+
+  cursorCommand
+    .command("status")
+    .description("Check Cursor authentication status")
+    .action(async () => {
+      await cursorStatus();
+    });
+
+  // Check if a subcommand was provided
+  const args = process.argv.slice(2);
+  const firstArg = args[0] || '';
+  // Global flags should go to main program
+  const isGlobalFlag = ['--help', '-h', '--version', '-V'].includes(firstArg);
+  const hasSubcommand = args.length > 0 && !firstArg.startsWith('-');
+  const knownCommands = ['monthly', 'models', 'graph', 'wrapped', 'login', 'logout', 'whoami', 'submit', 'cursor', 'tui', 'pricing', 'query', 'cache', 'secrets', 'config', 'team', 'record', 'proxy', 'serve', 'push', 'schema', 'capabilities', 'activity', 'efficiency', 'share-session', 'inspect', 'check', 'branches', 'commits', 'modes', 'projects', 'rates', 'recent', 'reconcile', 'help'];
+  const isKnownCommand = hasSubcommand && knownCommands.includes(firstArg);
+
+  if (isKnownCommand || isGlobalFlag) {
+    // Run the specified subcommand or show full help/version
+    await program.parseAsync();
+  } else {
+    // No subcommand - launch TUI by default, or legacy CLI with --light, or JSON with --json
+    const defaultProgram = new Command();
+    defaultProgram
+      .option("--light", "Use legacy CLI table output instead of TUI")
+      .option("--json", "Output as JSON (for scripting)")
+      .option("--opencode", "Show only OpenCode usage")
+      .option("--claude", "Show only Claude Code usage")
+      .option("--codex", "Show only Codex CLI usage")
+      .option("--gemini", "Show only Gemini CLI usage")
+      .option("--cursor", "Show only Cursor IDE usage")
+      .option("--amp", "Show only Amp usage")
+      .option("--today", "Show only today's usage")
+      .option("--week", "Show last 7 days")
+      .option("--month", "Show current month")
+      .option("--since <date>", "Start date (YYYY-MM-DD)")
+      .option("--until <date>", "End date (YYYY-MM-DD)")
+      .option("--year <year>", "Filter to specific year")
+      .option("--locale <tag>", "Language for report text, e.g. pt-BR (default: locale setting, else from the environment)")
+    .option("--benchmark", "Show processing time")
+      .option("--no-spinner", "Disable spinner (for AI agents and scripts - keeps stdout clean)")
+      .parse();
+    
+    const opts = defaultProgram.opts();
+    applyLocaleOption(opts);
+    if (opts.json) {
+      await outputJsonReport("models", opts);
+    } else if (opts.light || isPlain()) {
+      await showModelReport(opts, { spinner: opts.spinner });
+    } else {
+      const launchTUI = await tryLoadTUI();
+      if (launchTUI) {
+        await launchTUI(buildTUIOptions(opts));
+      } else {
+        showTUIUnavailableMessage();
+        await showModelReport(opts, { spinner: opts.spinner });
+      }
+    }
+  }
+}
+
+/**
+ * Live mode without the TUI: the model report, printed again after each
+ * change to the session files, until interrupted
+ */
+async function showLivePlainReport(options: FilterOptions & DateFilterOptions): Promise<void> {
+  const sources = getEnabledSources(options)?.filter((s) => s !== "cursor");
+  await new Promise<void>((resolve) => {
+    // fs.watch watchers don't keep the process running on their own
+    const keepAlive = setInterval(() => {}, 60_000);
+    const stop = rerunOnChange(sources, async () => {
+      console.log(`\n  ${new Date().toLocaleTimeString()}`);
+      await showModelReport(options, { spinner: false });
+    });
+    process.once("SIGINT", () => {
+      stop();
+      clearInterval(keepAlive);
+      resolve();
+    });
+  });
+}
+
+function getEnabledSources(options: FilterOptions): SourceType[] | undefined {
+  const hasFilter = options.opencode || options.claude || options.codex || options.gemini || options.cursor || options.amp ||
+    options.droid || options.cline || options.roocode || options.recorded;
+  if (!hasFilter) return undefined; // All sources
+
+  const sources: SourceType[] = [];
+  if (options.opencode) sources.push("opencode");
+  if (options.claude) sources.push("claude");
+  if (options.codex) sources.push("codex");
+  if (options.gemini) sources.push("gemini");
+  if (options.cursor) sources.push("cursor");
+  if (options.amp) sources.push("amp");
+  if (options.droid) sources.push("droid");
+  if (options.cline) sources.push("cline");
+  if (options.roocode) sources.push("roocode");
+  if (options.recorded) sources.push("recorded");
+  return sources;
+}
+
+
+
+
+
+/**
+ * Sync Cursor usage data from API to local cache.
+ * Only attempts sync if user is authenticated with Cursor.
  */
 async function syncCursorData(): Promise<CursorSyncResult> {
+  if (readOnlyHome()) {
+    // Read the analyzed directory's cursor-cache as is
+    return { attempted: false, synced: true, rows: 0 };
+  }
   const credentials = loadCursorCredentials();
   if (!credentials) {
     return { attempted: false, synced: false, rows: 0 };
@@ -542,21 +1366,19 @@ interface LoadedDataSources {
 
 async function loadDataSourcesParallel(
   localSources: SourceType[],
-  dateFilters: { since?: string; until?: string; year?: string },
   onPhase?: (phase: string) => void
 ): Promise<LoadedDataSources> {
   const shouldParseLocal = localSources.length > 0;
 
+  // Prices load alongside parsing; the finalize step then reads them from cache.
+  // Date filters apply when finalizing, after free tiers are used up over the
+  // whole history.
   const [cursorResult, localResult] = await Promise.allSettled([
     syncCursorData(),
     shouldParseLocal
-      ? parseLocalSourcesAsync({
-          sources: localSources.filter(s => s !== 'cursor'),
-          since: dateFilters.since,
-          until: dateFilters.until,
-          year: dateFilters.year,
-        })
+      ? parseLocalSourcesAsync({ sources: localSources.filter(s => s !== 'cursor') })
       : Promise.resolve(null),
+    preloadPricing(),
   ]);
 
   const cursorSync: CursorSyncResult = cursorResult.status === 'fulfilled'
@@ -570,7 +1392,8 @@ async function loadDataSourcesParallel(
   return { cursorSync, localMessages };
 }
 
-async function showModelReport(options: FilterOptions & DateFilterOptions & { benchmark?: boolean }, extraOptions?: { spinner?: boolean }) {
+async function showModelReport(options: FilterOptions & DateFilterOptions & ListingOptions & { benchmark?: boolean; byFamily?: boolean }, extraOptions?: { spinner?: boolean }) {
+  const listing = parseListing(options);
   const dateFilters = getDateFilters(options);
   const enabledSources = getEnabledSources(options);
   const onlyCursor = enabledSources?.length === 1 && enabledSources[0] === 'cursor';
@@ -588,8 +1411,8 @@ async function showModelReport(options: FilterOptions & DateFilterOptions & { be
 
   const dateRange = getDateRangeLabel(options);
   const title = dateRange 
-    ? `Token Usage Report by Model (${dateRange})`
-    : "Token Usage Report by Model";
+    ? t("report.titleWithRange", { title: t("report.models.title"), range: dateRange })
+    : t("report.models.title");
   
   console.log(pc.cyan(`\n  ${title}`));
   if (options.benchmark) {
@@ -600,14 +1423,13 @@ async function showModelReport(options: FilterOptions & DateFilterOptions & { be
   const useSpinner = extraOptions?.spinner !== false;
   const spinner = useSpinner ? createSpinner({ color: "cyan" }) : null;
 
-  const localSources: SourceType[] = (enabledSources || ['opencode', 'claude', 'codex', 'gemini', 'cursor', 'amp', 'droid'])
+  const localSources: SourceType[] = (enabledSources || ['opencode', 'claude', 'codex', 'gemini', 'cursor', 'amp', 'droid', 'cline', 'roocode', 'recorded'])
     .filter(s => s !== 'cursor');
 
-  spinner?.start(pc.gray("Scanning session data..."));
+  spinner?.start(pc.gray(t("spinner.scanning")));
 
   const { cursorSync, localMessages } = await loadDataSourcesParallel(
     onlyCursor ? [] : localSources,
-    dateFilters,
     (phase) => spinner?.update(phase)
   );
   
@@ -620,18 +1442,20 @@ async function showModelReport(options: FilterOptions & DateFilterOptions & { be
     process.exit(1);
   }
 
-  spinner?.update(pc.gray("Finalizing report..."));
+  spinner?.update(pc.gray(t("spinner.finalizing")));
   const startTime = performance.now();
 
   let report: ModelReport;
   try {
-    const emptyMessages: ParsedMessages = { messages: [], opencodeCount: 0, claudeCount: 0, codexCount: 0, geminiCount: 0, ampCount: 0, droidCount: 0, processingTimeMs: 0 };
+    const emptyMessages: ParsedMessages = { messages: [], opencodeCount: 0, claudeCount: 0, codexCount: 0, geminiCount: 0, ampCount: 0, droidCount: 0, clineCount: 0, roocodeCount: 0, recordedCount: 0, processingTimeMs: 0 };
     report = await finalizeReportAsync({
       localMessages: localMessages || emptyMessages,
       includeCursor: includeCursor && cursorSync.synced,
       since: dateFilters.since,
       until: dateFilters.until,
       year: dateFilters.year,
+      asOf: options.asOf,
+      families: options.byFamily,
     });
   } catch (e) {
     if (spinner) {
@@ -650,37 +1474,60 @@ async function showModelReport(options: FilterOptions & DateFilterOptions & { be
       console.log(pc.yellow("  No Cursor data available."));
       console.log(pc.gray("  Run 'tokscale cursor login' to authenticate with Cursor.\n"));
     } else {
-      console.log(pc.yellow("  No usage data found.\n"));
+      console.log(pc.yellow(`  ${t("report.noData")}\n`));
     }
     return;
   }
 
   // Create table
-  const table = createUsageTable("Source/Model");
+  const table = options.byFamily
+    ? createUsageTable(t("column.providerFamily"), "family")
+    : createUsageTable(t("column.sourceModel"), "model");
   
   const settings = loadSettings();
   const filteredEntries = settings.includeUnusedModels 
     ? report.entries 
     : report.entries.filter(e => e.input + e.output + e.cacheRead + e.cacheWrite > 0);
 
-  for (const entry of filteredEntries) {
-    const sourceLabel = getSourceLabel(entry.source);
-    const modelDisplay = `${pc.dim(sourceLabel)} ${formatModelName(entry.model)}`;
-    table.push(
-      formatUsageRow(
-        modelDisplay,
-        [entry.model],
-        entry.input,
-        entry.output,
-        entry.cacheWrite,
-        entry.cacheRead,
-        entry.cost
-      )
-    );
+  if (report.families) {
+    // One line per family, its model versions listed
+    for (const family of report.families) {
+      const models = family.models.filter((e) => settings.includeUnusedModels || e.input + e.output + e.cacheRead + e.cacheWrite > 0);
+      if (models.length === 0) continue;
+      table.push(
+        formatUsageRow(
+          `${pc.dim(family.provider)} ${family.family}`,
+          models.map((e) => e.model),
+          family.input,
+          family.output,
+          family.cacheWrite,
+          family.cacheRead,
+          family.cost,
+          family.costShare
+        )
+      );
+    }
+  } else {
+    for (const entry of filteredEntries) {
+      const sourceLabel = getSourceLabel(entry.source);
+      const modelDisplay = `${pc.dim(sourceLabel)} ${formatModelName(entry.model)}`;
+      table.push(
+        formatUsageRow(
+          modelDisplay,
+          [entry.model],
+          entry.input,
+          entry.output,
+          entry.cacheWrite,
+          entry.cacheRead,
+          entry.cost,
+          entry.costShare
+        )
+      );
+    }
   }
 
   // Add totals row
-  table.push(
+  table.pushFooter(
     formatTotalsRow(
       report.totalInput,
       report.totalOutput,
@@ -690,16 +1537,20 @@ async function showModelReport(options: FilterOptions & DateFilterOptions & { be
     )
   );
 
-  console.log(table.toString());
+  printTable(table, listing);
 
   // Summary stats
   console.log(
     pc.gray(
-      `\n  Total: ${formatNumber(report.totalMessages)} messages, ` +
-        `${formatNumber(report.totalInput + report.totalOutput + report.totalCacheRead + report.totalCacheWrite)} tokens, ` +
-        `${pc.green(formatCurrency(report.totalCost))}`
+      "\n  " +
+        t("report.summary", {
+          messages: formatNumber(report.totalMessages),
+          tokens: formatNumber(report.totalInput + report.totalOutput + report.totalCacheRead + report.totalCacheWrite),
+          cost: pc.green(formatCurrency(report.totalCost)),
+        })
     )
   );
+  printRunSummary(report.summary);
 
   if (options.benchmark) {
     console.log(pc.gray(`  Processing time: ${processingTime.toFixed(0)}ms (Rust) + ${report.processingTimeMs}ms (parsing)`));
@@ -715,11 +1566,12 @@ async function showModelReport(options: FilterOptions & DateFilterOptions & { be
   console.log();
 }
 
-async function showMonthlyReport(options: FilterOptions & DateFilterOptions & { benchmark?: boolean }, extraOptions?: { spinner?: boolean }) {
+async function showMonthlyReport(options: FilterOptions & DateFilterOptions & ListingOptions & { benchmark?: boolean }, extraOptions?: { spinner?: boolean }) {
+  const listing = parseListing(options);
   const dateRange = getDateRangeLabel(options);
   const title = dateRange 
-    ? `Monthly Token Usage Report (${dateRange})`
-    : "Monthly Token Usage Report";
+    ? t("report.titleWithRange", { title: t("report.monthly.title"), range: dateRange })
+    : t("report.monthly.title");
 
   console.log(pc.cyan(`\n  ${title}`));
   if (options.benchmark) {
@@ -732,15 +1584,14 @@ async function showMonthlyReport(options: FilterOptions & DateFilterOptions & {
 
   const dateFilters = getDateFilters(options);
   const enabledSources = getEnabledSources(options);
-  const localSources: SourceType[] = (enabledSources || ['opencode', 'claude', 'codex', 'gemini', 'cursor', 'amp', 'droid'])
+  const localSources: SourceType[] = (enabledSources || ['opencode', 'claude', 'codex', 'gemini', 'cursor', 'amp', 'droid', 'cline', 'roocode', 'recorded'])
     .filter(s => s !== 'cursor');
   const includeCursor = !enabledSources || enabledSources.includes('cursor');
 
-  spinner?.start(pc.gray("Scanning session data..."));
+  spinner?.start(pc.gray(t("spinner.scanning")));
 
   const { cursorSync, localMessages } = await loadDataSourcesParallel(
     localSources,
-    dateFilters,
     (phase) => spinner?.update(phase)
   );
   
@@ -753,7 +1604,7 @@ async function showMonthlyReport(options: FilterOptions & DateFilterOptions & {
     process.exit(1);
   }
 
-  spinner?.update(pc.gray("Finalizing report..."));
+  spinner?.update(pc.gray(t("spinner.finalizing")));
   const startTime = performance.now();
 
   let report: MonthlyReport;
@@ -764,6 +1615,7 @@ async function showMonthlyReport(options: FilterOptions & DateFilterOptions & {
       since: dateFilters.since,
       until: dateFilters.until,
       year: dateFilters.year,
+      asOf: options.asOf,
     });
   } catch (e) {
     if (spinner) {
@@ -777,221 +1629,2229 @@ async function showMonthlyReport(options: FilterOptions & DateFilterOptions & {
   const processingTime = performance.now() - startTime;
   spinner?.stop();
 
-  if (report.entries.length === 0) {
-    console.log(pc.yellow("  No usage data found.\n"));
-    return;
+  if (report.entries.length === 0) {
+    console.log(pc.yellow(`  ${t("report.noData")}\n`));
+    return;
+  }
+
+  // Create table
+  const table = createUsageTable(t("column.month"), "month");
+
+  const settings = loadSettings();
+  const filteredEntries = settings.includeUnusedModels
+    ? report.entries
+    : report.entries.filter(e => e.input + e.output + e.cacheRead + e.cacheWrite > 0);
+
+  for (const entry of filteredEntries) {
+    table.push(
+      formatUsageRow(
+        entry.month,
+        entry.models,
+        entry.input,
+        entry.output,
+        entry.cacheWrite,
+        entry.cacheRead,
+        entry.cost,
+        entry.costShare
+      )
+    );
+  }
+
+  // Add totals row
+  table.pushFooter(
+    formatTotalsRow(
+      report.totalInput,
+      report.totalOutput,
+      report.totalCacheWrite,
+      report.totalCacheRead,
+      report.totalCost
+    )
+  );
+
+  printTable(table, listing);
+  console.log(pc.gray(`\n  ${t("report.totalCost", { cost: pc.green(formatCurrency(report.totalCost)) })}`));
+  if (report.totalCovered > 0) {
+    console.log(pc.gray(`  ${t("report.covered", { cost: pc.cyan(formatCurrency(report.totalCovered)) })}`));
+  }
+  printRunSummary(report.summary);
+
+  if (options.benchmark) {
+    console.log(pc.gray(`  Processing time: ${processingTime.toFixed(0)}ms (Rust) + ${report.processingTimeMs}ms (parsing)`));
+    if (cursorSync.attempted) {
+      if (cursorSync.synced) {
+        console.log(pc.gray(`  Cursor: ${cursorSync.rows} usage events synced (full lifetime data)`));
+      } else {
+        console.log(pc.yellow(`  Cursor: sync failed - ${cursorSync.error}`));
+      }
+    }
+  }
+
+  console.log();
+}
+
+type JsonReportType = "models" | "monthly";
+
+async function outputJsonReport(
+  reportType: JsonReportType,
+  options: FilterOptions & DateFilterOptions & { byFamily?: boolean }
+) {
+  const dateFilters = getDateFilters(options);
+  const enabledSources = getEnabledSources(options);
+  const onlyCursor = enabledSources?.length === 1 && enabledSources[0] === 'cursor';
+  const includeCursor = !enabledSources || enabledSources.includes('cursor');
+  const localSources: SourceType[] = (enabledSources || ['opencode', 'claude', 'codex', 'gemini', 'cursor', 'amp', 'droid', 'cline', 'roocode', 'recorded'])
+    .filter(s => s !== 'cursor');
+
+  const { cursorSync, localMessages } = await loadDataSourcesParallel(
+    onlyCursor ? [] : localSources
+  );
+  
+  if (!localMessages && !onlyCursor) {
+    console.error(JSON.stringify({ error: "Failed to parse local session files" }));
+    process.exit(1);
+  }
+
+  const emptyMessages: ParsedMessages = { messages: [], opencodeCount: 0, claudeCount: 0, codexCount: 0, geminiCount: 0, ampCount: 0, droidCount: 0, clineCount: 0, roocodeCount: 0, recordedCount: 0, processingTimeMs: 0 };
+
+  if (reportType === "models") {
+    const report = await finalizeReportAsync({
+      localMessages: localMessages || emptyMessages,
+      includeCursor: includeCursor && cursorSync.synced,
+      since: dateFilters.since,
+      until: dateFilters.until,
+      year: dateFilters.year,
+      asOf: options.asOf,
+      families: options.byFamily,
+    });
+    console.log(JSON.stringify(withSchemaVersion(report), null, 2));
+  } else {
+    const report = await finalizeMonthlyReportAsync({
+      localMessages: localMessages || emptyMessages,
+      includeCursor: includeCursor && cursorSync.synced,
+      since: dateFilters.since,
+      until: dateFilters.until,
+      year: dateFilters.year,
+      asOf: options.asOf,
+    });
+    console.log(JSON.stringify(withSchemaVersion(report), null, 2));
+  }
+}
+
+interface GraphCommandOptions extends FilterOptions, DateFilterOptions {
+  output?: string;
+  encryptTo?: string[];
+  benchmark?: boolean;
+  spinner?: boolean;
+}
+
+async function handleGraphCommand(options: GraphCommandOptions) {
+  const useSpinner = options.output && options.spinner !== false;
+  const spinner = useSpinner ? createSpinner({ color: "cyan" }) : null;
+
+  const dateFilters = getDateFilters(options);
+  const enabledSources = getEnabledSources(options);
+  const localSources: SourceType[] = (enabledSources || ['opencode', 'claude', 'codex', 'gemini', 'cursor', 'amp', 'droid', 'cline', 'roocode', 'recorded'])
+    .filter(s => s !== 'cursor');
+  const includeCursor = !enabledSources || enabledSources.includes('cursor');
+
+  spinner?.start(pc.gray("Scanning session data..."));
+
+  const { cursorSync, localMessages } = await loadDataSourcesParallel(
+    localSources,
+    (phase) => spinner?.update(phase)
+  );
+  
+  if (!localMessages) {
+    spinner?.error('Failed to parse local session files');
+    process.exit(1);
+  }
+
+  spinner?.update(pc.gray("Generating graph data..."));
+  const startTime = performance.now();
+
+  const data = await finalizeGraphAsync({
+    localMessages,
+    includeCursor: includeCursor && cursorSync.synced,
+    since: dateFilters.since,
+    until: dateFilters.until,
+    year: dateFilters.year,
+  });
+
+  const processingTime = performance.now() - startTime;
+  spinner?.stop();
+
+  const jsonOutput = JSON.stringify(withSchemaVersion(data), null, 2);
+  const output = options.encryptTo?.length ? encryptGraphOutput(jsonOutput, options.encryptTo) : jsonOutput;
+
+  // Output to file or stdout
+  if (options.output) {
+    fs.writeFileSync(options.output, output);
+    console.error(pc.green(`✓ Graph data written to ${options.output}`));
+    if (options.encryptTo?.length) {
+      console.error(pc.gray(`  Encrypted to ${options.encryptTo.length} team key(s)`));
+    }
+    console.error(
+      pc.gray(
+        `  ${data.contributions.length} days, ${data.summary.sources.length} sources, ${data.summary.models.length} models`
+      )
+    );
+    console.error(pc.gray(`  Total: ${formatCurrency(data.summary.totalCost)}`));
+    if (options.benchmark) {
+      console.error(pc.gray(`  Processing time: ${processingTime.toFixed(0)}ms (Rust native)`));
+      if (cursorSync.attempted) {
+        if (cursorSync.synced) {
+          console.error(pc.gray(`  Cursor: ${cursorSync.rows} usage events synced (full lifetime data)`));
+        } else {
+          console.error(pc.yellow(`  Cursor: sync failed - ${cursorSync.error}`));
+        }
+      }
+    }
+  } else if (typeof output === "string") {
+    console.log(output);
+  } else {
+    process.stdout.write(output);
+  }
+}
+
+function encryptGraphOutput(json: string, recipients: string[]): Buffer {
+  let encrypted: Buffer | null;
+  try {
+    encrypted = encryptForTeam(Buffer.from(json, "utf-8"), recipients);
+  } catch (error) {
+    console.error(pc.red(`\n  Could not encrypt: ${(error as Error).message}\n`));
+    process.exit(1);
+  }
+  if (!encrypted) {
+    console.error(pc.red("\n  Encryption needs the native module.\n"));
+    process.exit(1);
+  }
+  return encrypted;
+}
+
+interface WrappedCommandOptions extends FilterOptions {
+  output?: string;
+  year?: string;
+  spinner?: boolean;
+  short?: boolean;
+  agents?: boolean;
+  clients?: boolean;
+  disablePinned?: boolean;
+}
+
+async function handleWrappedCommand(options: WrappedCommandOptions) {
+  const useSpinner = options.spinner !== false;
+  const spinner = useSpinner ? createSpinner({ color: "cyan" }) : null;
+  const currentYear = new Date().getFullYear().toString();
+  const year = options.year || currentYear;
+  spinner?.start(pc.gray(`Generating your ${year} Wrapped...`));
+
+  try {
+    const enabledSources = getEnabledSources(options);
+    const outputPath = await generateWrapped({
+      output: options.output,
+      year,
+      sources: enabledSources,
+      short: options.short,
+      includeAgents: !options.clients,
+      pinSisyphus: !options.disablePinned,
+    });
+
+    spinner?.stop();
+    console.log(pc.green(`\n  ✓ Your Tokscale Wrapped image is ready!`));
+    console.log(pc.white(`  ${outputPath}`));
+    console.log();
+    console.log(pc.gray("  Share it on Twitter/X with #TokscaleWrapped"));
+    console.log();
+  } catch (error) {
+    if (spinner) {
+      spinner.error(`Failed to generate wrapped: ${(error as Error).message}`);
+    } else {
+      console.error(pc.red(`Failed to generate wrapped: ${(error as Error).message}`));
+    }
+    process.exit(1);
+  }
+}
+
+function handleCapabilitiesCommand(options: { json?: boolean }) {
+  const capabilities = getNativeCapabilities();
+  if (!capabilities) {
+    const error = isNativeAvailable()
+      ? "Native module is too old to report capabilities"
+      : "Native module not available";
+    if (options.json) {
+      console.log(JSON.stringify({ error, cliVersion: pkg.version }, null, 2));
+    } else {
+      console.log(pc.red(`\n  ${error}\n`));
+    }
+    process.exit(1);
+  }
+
+  if (options.json) {
+    console.log(JSON.stringify({ cliVersion: pkg.version, ...capabilities }, null, 2));
+    return;
+  }
+
+  console.log(pc.cyan(`\n  tokscale v${pkg.version} (native module v${capabilities.version})`));
+  console.log(pc.gray(`  Sources:           ${capabilities.sources.join(", ")}`));
+  console.log(pc.gray(`  Pricing providers: ${capabilities.pricingProviders.join(", ")}`));
+  console.log(pc.gray(`  Export formats:    ${capabilities.exportFormats.join(", ")}`));
+  console.log(pc.gray(`  Report schema:     v${capabilities.reportSchemaVersion} (${capabilities.reportKinds.join(", ")})`));
+  console.log(pc.gray(`  Features:          ${capabilities.features.join(", ")}`));
+  console.log();
+}
+
+function handleSchemaCommand(report: string) {
+  if (!["models", "monthly", "graph"].includes(report)) {
+    console.error(pc.red(`\n  Unknown report: ${report} (expected models, monthly or graph)\n`));
+    process.exit(1);
+  }
+
+  const schema = getReportSchema(report);
+  if (!schema) {
+    console.error(pc.red("\n  Native module not available - report schemas come from @tokscale/core\n"));
+    process.exit(1);
+  }
+  console.log(JSON.stringify(schema, null, 2));
+}
+
+async function handleConformanceCommand(source: string, paths: string[], options: { json?: boolean }) {
+  const core = await loadCoreForSecrets();
+  let report: import("@tokscale/core").ConformanceReport;
+  try {
+    report = core.checkParserConformance(source, paths);
+  } catch (error) {
+    if (options.json) {
+      console.log(JSON.stringify({ error: (error as Error).message }, null, 2));
+    } else {
+      console.error(pc.red(`\n  ${(error as Error).message}\n`));
+    }
+    process.exit(1);
+  }
+
+  if (options.json) {
+    console.log(JSON.stringify(report, null, 2));
+  } else {
+    console.log();
+    for (const finding of report.findings) {
+      const label = finding.severity === "error" ? pc.red("error") : pc.yellow("warning");
+      const where = finding.file ? `${pc.white(finding.file)} ` : "";
+      console.log(`  ${where}${label} ${pc.gray(`[${finding.code}]`)} ${finding.message}`);
+    }
+    if (report.findings.length > 0) console.log();
+
+    const formats = report.formats.length > 0 ? ` (${report.formats.join(", ")})` : "";
+    const summary = `${report.source}: ${report.messageCount} message(s) from ${report.fileCount} file(s)${formats}`;
+    console.log(report.passed ? pc.green(`  ${summary}, conforms`) : pc.red(`  ${summary}, does not conform`));
+    console.log();
+  }
+  if (!report.passed) process.exit(1);
+}
+
+async function handleCacheCleanCommand(options: { dryRun?: boolean; json?: boolean }) {
+  let core: typeof import("@tokscale/core");
+  try {
+    const mod = await import("@tokscale/core");
+    core = (mod.default ?? mod) as typeof import("@tokscale/core");
+  } catch (importErr) {
+    console.log(pc.red(`\n  Native module not available: ${(importErr as Error).message}\n`));
+    process.exit(1);
+  }
+
+  const report = core.cleanCache({ dryRun: options.dryRun });
+  if (options.json) {
+    console.log(JSON.stringify(report, null, 2));
+    return;
+  }
+
+  const verb = report.dryRun ? "Would remove" : "Removed";
+  console.log(pc.cyan(`\n  Cache: ${pc.white(report.cacheDir)} (${formatByteSize(report.totalBytes)})`));
+  for (const entry of report.removed) {
+    console.log(pc.gray(`  ${verb} ${entry.path} (${entry.reason}, ${formatByteSize(entry.bytes)})`));
+  }
+  if (report.parseEntriesPruned > 0) {
+    console.log(pc.gray(`  ${verb} ${report.parseEntriesPruned} parse-cache entries for deleted session files`));
+  }
+  if (report.removed.length === 0 && report.parseEntriesPruned === 0) {
+    console.log(pc.green("  Nothing to clean"));
+  } else {
+    console.log(pc.green(`  ${verb} ${report.removed.length} files, ${formatByteSize(report.bytesFreed)} freed`));
+  }
+  console.log();
+}
+
+async function loadCoreForSecrets(): Promise<typeof import("@tokscale/core")> {
+  try {
+    const mod = await import("@tokscale/core");
+    return (mod.default ?? mod) as typeof import("@tokscale/core");
+  } catch (importErr) {
+    console.log(pc.red(`\n  Native module not available: ${(importErr as Error).message}\n`));
+    process.exit(1);
+  }
+}
+
+async function readSecret(prompt: string): Promise<string> {
+  if (!process.stdin.isTTY) {
+    const chunks: Buffer[] = [];
+    for await (const chunk of process.stdin) chunks.push(chunk as Buffer);
+    return Buffer.concat(chunks).toString("utf-8").trim();
+  }
+
+  const readline = await import("node:readline");
+  const rl = readline.createInterface({ input: process.stdin, output: process.stdout });
+  return new Promise<string>((resolve) => {
+    rl.question(pc.white(prompt), (answer) => {
+      rl.close();
+      resolve(answer.trim());
+    });
+  });
+}
+
+async function handleSecretsSetCommand(name: string) {
+  const core = await loadCoreForSecrets();
+  const key = await readSecret(`  API key for ${name}: `);
+  if (!key) {
+    console.log(pc.red("\n  No key provided.\n"));
+    process.exit(1);
+  }
+
+  try {
+    core.storeApiKey(name, key);
+  } catch (error) {
+    console.log(pc.red(`\n  Could not store key: ${(error as Error).message}`));
+    console.log(pc.gray(`  Set ${core.apiKeyStatus(name).envVar} instead.\n`));
+    process.exit(1);
+  }
+  console.log(pc.green(`\n  Stored ${name} API key in the OS keychain.`));
+  console.log(pc.gray(`  Reference it from settings as "apiKeys": { "${name}": "keychain:${name}" } (optional).\n`));
+}
+
+async function handleSecretsStatusCommand(names: string[], options: { json?: boolean }) {
+  const core = await loadCoreForSecrets();
+  const statuses = names.map((name) => core.apiKeyStatus(name));
+  if (options.json) {
+    console.log(JSON.stringify(statuses, null, 2));
+    return;
+  }
+
+  console.log();
+  for (const status of statuses) {
+    const where = status.backend
+      ? pc.green(`found (${status.backend})`)
+      : pc.yellow(`not set - run 'tokscale secrets set ${status.name}' or set ${status.envVar}`);
+    console.log(`  ${pc.white(status.name)}: ${where}`);
+  }
+  console.log();
+}
+
+async function handleConfigCheckCommand(options: { file?: string; json?: boolean }) {
+  const core = await loadCoreForSecrets();
+  let check: import("@tokscale/core").SettingsCheck;
+  try {
+    check = core.checkSettings(options.file);
+  } catch (error) {
+    if (options.json) {
+      console.log(JSON.stringify({ error: (error as Error).message }, null, 2));
+    } else {
+      console.error(pc.red(`\n  ${(error as Error).message}\n`));
+    }
+    process.exit(1);
+  }
+
+  const errors = check.issues.filter((issue) => issue.severity === "error").length;
+  if (options.json) {
+    console.log(JSON.stringify(check, null, 2));
+  } else if (!check.exists) {
+    console.log(pc.gray(`\n  ${check.path} does not exist; defaults are in effect.\n`));
+  } else if (check.issues.length === 0) {
+    console.log(pc.green(`\n  ${check.path}: no problems found\n`));
+  } else {
+    console.log();
+    for (const issue of check.issues) {
+      const label = issue.severity === "error" ? pc.red("error") : pc.yellow("warning");
+      const where = issue.path ? `${issue.path}: ` : "";
+      console.log(`  ${pc.white(`${check.path}:${issue.line}:${issue.column}`)} ${label} ${where}${issue.message}`);
+    }
+    const warnings = check.issues.length - errors;
+    console.log(pc.gray(`\n  ${errors} error(s), ${warnings} warning(s)`));
+    if (errors > 0) {
+      console.log(pc.gray("  Files with errors are ignored; defaults (or the last good settings) stay in effect."));
+    }
+    console.log();
+  }
+  if (errors > 0) process.exit(1);
+}
+
+function readSettingsOrExit(json?: boolean): SettingsObject {
+  try {
+    return readSettingsFile();
+  } catch (error) {
+    const message = `Could not read ${CONFIG_FILE}: ${(error as Error).message}`;
+    if (json) {
+      console.log(JSON.stringify({ error: message }, null, 2));
+    } else {
+      console.error(pc.red(`\n  ${message}`));
+      console.error(pc.gray("  Run 'tokscale config check' for the line and column.\n"));
+    }
+    process.exit(1);
+  }
+}
+
+function handleConfigGetCommand(key: string, options: { json?: boolean }) {
+  const settings = readSettingsOrExit(options.json);
+  let value: unknown;
+  try {
+    value = getSetting(settings, key);
+  } catch (error) {
+    console.error(pc.red(`\n  ${(error as Error).message}\n`));
+    process.exit(1);
+  }
+  if (value === undefined) {
+    if (options.json) {
+      console.log(JSON.stringify({ error: `${key} is not set` }, null, 2));
+    } else {
+      console.error(pc.gray(`  ${key} is not set (the default applies)`));
+    }
+    process.exit(1);
+  }
+  console.log(options.json ? JSON.stringify(value, null, 2) : formatSettingValue(value));
+}
+
+/** Write `settings`, refusing when the result has schema errors */
+async function saveEditedSettings(settings: SettingsObject) {
+  const core = await loadCoreForSecrets();
+  let warnings: string[] = [];
+  try {
+    writeSettingsFile(serializeSettings(settings), (tempPath) => {
+      const check = core.checkSettings(tempPath);
+      const errors = check.issues.filter((issue) => issue.severity === "error");
+      if (errors.length > 0) {
+        throw new Error(errors.map((issue) => `${issue.path}: ${issue.message}`).join("; "));
+      }
+      warnings = check.issues.map((issue) => `${issue.path}: ${issue.message}`);
+    });
+  } catch (error) {
+    console.error(pc.red(`\n  Not saved: ${(error as Error).message}\n`));
+    process.exit(1);
+  }
+  for (const warning of warnings) {
+    console.error(pc.yellow(`  warning ${warning}`));
+  }
+}
+
+async function handleConfigSetCommand(key: string, rawValue: string) {
+  const settings = readSettingsOrExit();
+  const value = parseSettingValue(rawValue);
+  try {
+    setSetting(settings, key, value);
+  } catch (error) {
+    console.error(pc.red(`\n  ${(error as Error).message}\n`));
+    process.exit(1);
+  }
+  await saveEditedSettings(settings);
+  console.log(pc.green(`  ${key} = ${formatSettingValue(value)}`));
+}
+
+async function handleConfigUnsetCommand(key: string) {
+  const settings = readSettingsOrExit();
+  let removed: boolean;
+  try {
+    removed = unsetSetting(settings, key);
+  } catch (error) {
+    console.error(pc.red(`\n  ${(error as Error).message}\n`));
+    process.exit(1);
+  }
+  if (!removed) {
+    console.log(pc.gray(`  ${key} was not set`));
+    return;
+  }
+  await saveEditedSettings(settings);
+  console.log(pc.green(`  Removed ${key}`));
+}
+
+function handleConfigListCommand(options: { json?: boolean }) {
+  const settings = readSettingsOrExit(options.json);
+  if (options.json) {
+    console.log(JSON.stringify(settings, null, 2));
+    return;
+  }
+  const entries = flattenSettings(settings);
+  if (entries.length === 0) {
+    console.log(pc.gray(`  No settings in ${CONFIG_FILE}; defaults are in effect.`));
+    return;
+  }
+  for (const [key, value] of entries) {
+    console.log(`${key} = ${formatSettingValue(value)}`);
+  }
+}
+
+async function handleTeamKeygenCommand(options: { output?: string }) {
+  const core = await loadCoreForSecrets();
+  const key = core.generateTeamKey();
+  const keyFile = `# created: ${new Date().toISOString()}\n# public key: ${key.publicKey}\n${key.secretKey}\n`;
+
+  if (options.output) {
+    fs.writeFileSync(options.output, keyFile, { mode: 0o600 });
+    console.error(pc.green(`✓ Secret key written to ${options.output}`));
+  } else {
+    process.stdout.write(keyFile);
+  }
+  console.error(pc.gray(`  Public key: ${key.publicKey}`));
+  console.error(pc.gray(`  Export with: tokscale graph --encrypt-to ${key.publicKey} --output usage.age`));
+}
+
+async function handleTeamDecryptCommand(file: string, options: { identity: string; output?: string }) {
+  const core = await loadCoreForSecrets();
+  let decrypted: Buffer;
+  try {
+    decrypted = core.decryptForTeam(fs.readFileSync(file), fs.readFileSync(options.identity, "utf-8"));
+  } catch (error) {
+    console.error(pc.red(`\n  Could not decrypt ${file}: ${(error as Error).message}\n`));
+    process.exit(1);
+  }
+
+  if (options.output) {
+    fs.writeFileSync(options.output, decrypted);
+    console.error(pc.green(`✓ Decrypted to ${options.output}`));
+  } else {
+    process.stdout.write(decrypted);
+  }
+}
+
+interface ShareSessionCommandOptions extends FilterOptions {
+  output?: string;
+  spinner?: boolean;
+}
+
+async function handleShareSessionCommand(id: string, options: ShareSessionCommandOptions) {
+  const core = await loadCoreForSecrets();
+  const useSpinner = options.spinner !== false;
+  const spinner = useSpinner ? createSpinner({ color: "cyan" }) : null;
+  spinner?.start(pc.gray("Finding session..."));
+
+  let bundle: Awaited<ReturnType<typeof core.shareSession>>;
+  try {
+    bundle = await core.shareSession({ sessionId: id, sources: getEnabledSources(options) });
+  } catch (err) {
+    spinner?.stop();
+    console.error(pc.red(`\n  ${(err as Error).message || "Unknown error"}\n`));
+    process.exit(1);
+  }
+  spinner?.stop();
+
+  const json = `${JSON.stringify(bundle, null, 2)}\n`;
+  if (options.output) {
+    fs.writeFileSync(options.output, json);
+    console.error(pc.green(`✓ Session ${bundle.session.id} written to ${options.output}`));
+  } else {
+    process.stdout.write(json);
+  }
+  console.error(
+    pc.gray(
+      `  ${formatNumber(bundle.session.messageCount)} messages, ${formatCurrency(bundle.session.cost)}; ` +
+        "no prompts, responses or paths"
+    )
+  );
+  console.error(pc.gray(`  View with: tokscale inspect ${options.output ?? "<file>"}`));
+}
+
+async function handleInspectCommand(file: string, options: { json?: boolean }) {
+  const fail = (message: string): never => {
+    if (options.json) {
+      console.log(JSON.stringify({ error: message }, null, 2));
+    } else {
+      console.log(pc.red(`\n  ${message}\n`));
+    }
+    process.exit(1);
+  };
+
+  let json: string;
+  try {
+    json = fs.readFileSync(file, "utf-8");
+  } catch (err) {
+    return fail(`Can't read ${file}: ${(err as Error).message}`);
+  }
+  const core = await loadCoreForSecrets();
+  let bundle: ReturnType<typeof core.inspectSessionBundle>;
+  try {
+    bundle = core.inspectSessionBundle(json);
+  } catch (err) {
+    return fail(`Can't read ${file}: ${(err as Error).message || "Unknown error"}`);
+  }
+
+  if (options.json) {
+    console.log(JSON.stringify(bundle, null, 2));
+    return;
+  }
+
+  const session = bundle.session;
+  const tokens = session.tokens;
+  console.log(pc.cyan(`\n  Session ${session.id}`) + pc.gray(` (${getSourceLabel(session.source)})`));
+  console.log(
+    pc.gray(
+      `  ${new Date(session.start).toISOString()}, ${formatDurationMs(session.end - session.start)}, ` +
+        `${formatNumber(session.messageCount)} messages, ${formatCurrency(session.cost)}`
+    )
+  );
+  console.log(
+    pc.gray(
+      `  Tokens: ${formatNumber(tokens.input)} input, ${formatNumber(tokens.output)} output, ` +
+        `${formatNumber(tokens.cacheRead)} cache read, ${formatNumber(tokens.cacheWrite)} cache write`
+    )
+  );
+
+  const byModel = new Map<string, { messages: number; input: number; output: number; cacheRead: number; cacheWrite: number; cost: number }>();
+  for (const message of bundle.messages) {
+    const entry = byModel.get(message.model) ?? { messages: 0, input: 0, output: 0, cacheRead: 0, cacheWrite: 0, cost: 0 };
+    entry.messages += 1;
+    entry.input += message.tokens.input;
+    entry.output += message.tokens.output;
+    entry.cacheRead += message.tokens.cacheRead;
+    entry.cacheWrite += message.tokens.cacheWrite;
+    entry.cost += message.cost;
+    byModel.set(message.model, entry);
+  }
+  const table = new ResponsiveTable({
+    head: ["Model", "Messages", "Input", "Output", "Cache read", "Cache write", "Cost"],
+    colAligns: ["left", "right", "right", "right", "right", "right", "right"],
+  });
+  for (const [model, entry] of [...byModel].sort((a, b) => b[1].cost - a[1].cost)) {
+    table.push([
+      formatModelName(model),
+      formatNumber(entry.messages),
+      formatNumber(entry.input),
+      formatNumber(entry.output),
+      formatNumber(entry.cacheRead),
+      formatNumber(entry.cacheWrite),
+      formatCostCell(entry.cost),
+    ]);
+  }
+  console.log(table.toString());
+
+  const sidechain = bundle.messages.filter((m) => m.sidechain).length;
+  const toolCalls = bundle.messages.reduce((sum, m) => sum + m.toolCalls, 0);
+  if (sidechain > 0 || toolCalls > 0) {
+    console.log(pc.gray(`  ${formatNumber(toolCalls)} tool calls, ${formatNumber(sidechain)} messages from sub-agents`));
+  }
+  console.log(pc.gray(`  Bundle written ${bundle.createdAt} by tokscale ${bundle.tokscaleVersion}\n`));
+}
+
+/** Lines sent to the native recorder per call */
+const RECORD_BATCH_LINES = 500;
+
+async function handleRecordCommand(options: { check?: boolean; json?: boolean }) {
+  const core = await loadCoreForSecrets();
+  if (options.check) {
+    const damaged = core.checkRecorded();
+    if (options.json) {
+      console.log(JSON.stringify({ damaged }, null, 2));
+    } else if (damaged.length === 0) {
+      console.error(pc.green("✓ All recorded events are readable"));
+    } else {
+      for (const entry of damaged) {
+        console.error(pc.yellow(`  ${entry.file}:${entry.line}: ${entry.reason}`));
+      }
+      console.error(pc.gray(`  ${damaged.length} unreadable line${damaged.length === 1 ? "" : "s"}; reports skip them`));
+    }
+    process.exit(damaged.length === 0 ? 0 : 1);
+  }
+  if (process.stdin.isTTY) {
+    console.error(pc.yellow("  Pipe NDJSON events into 'tokscale record', e.g. my-gateway --usage-log | tokscale record"));
+    process.exit(1);
+  }
+
+  const readline = await import("node:readline");
+  const lines = readline.createInterface({ input: process.stdin, crlfDelay: Infinity });
+  let batch: string[] = [];
+  let batchStart = 1;
+  let recorded = 0;
+  const errors: { line: number; message: string }[] = [];
+
+  const flush = () => {
+    if (batch.length === 0) return;
+    const result = core.recordEvents(batch.join("\n"));
+    recorded += result.recorded;
+    for (const error of result.errors) {
+      errors.push({ line: batchStart + error.line - 1, message: error.message });
+    }
+    batchStart += batch.length;
+    batch = [];
+  };
+
+  try {
+    for await (const line of lines) {
+      batch.push(line);
+      if (batch.length >= RECORD_BATCH_LINES) flush();
+    }
+    flush();
+  } catch (error) {
+    console.error(pc.red(`\n  Could not record events: ${(error as Error).message}\n`));
+    process.exit(1);
+  }
+
+  if (options.json) {
+    console.log(JSON.stringify({ recorded, errors }, null, 2));
+  } else {
+    console.error(pc.green(`✓ Recorded ${recorded} event${recorded === 1 ? "" : "s"}`));
+    for (const error of errors.slice(0, 10)) {
+      console.error(pc.yellow(`  line ${error.line}: ${error.message}`));
+    }
+    if (errors.length > 10) {
+      console.error(pc.yellow(`  ... and ${errors.length - 10} more rejected lines`));
+    }
+  }
+  if (errors.length > 0) process.exitCode = 1;
+}
+
+async function handleImportOpenRouterCommand(options: { ids?: string; json?: boolean }) {
+  const core = await loadCoreForSecrets();
+  let idsText: string | undefined;
+  if (options.ids) {
+    try {
+      idsText = fs.readFileSync(options.ids, "utf-8");
+    } catch (error) {
+      console.error(pc.red(`\n  Could not read ${options.ids}: ${(error as Error).message}\n`));
+      process.exit(1);
+    }
+  }
+
+  let result: import("@tokscale/core").OpenRouterImportResult;
+  try {
+    result = await core.importOpenrouterGenerations({ idsText });
+  } catch (error) {
+    if (options.json) {
+      console.log(JSON.stringify({ error: (error as Error).message }, null, 2));
+    } else {
+      console.error(pc.red(`\n  ${(error as Error).message}\n`));
+    }
+    process.exit(1);
+  }
+
+  if (options.json) {
+    console.log(JSON.stringify(result, null, 2));
+  } else {
+    const { imported, skipped, failed } = result;
+    console.error(pc.green(`✓ Imported ${imported} generation${imported === 1 ? "" : "s"}`));
+    if (skipped > 0) {
+      console.error(pc.gray(`  ${skipped} already imported`));
+    }
+    for (const failure of failed.slice(0, 10)) {
+      console.error(pc.yellow(`  ${failure.id}: ${failure.reason}`));
+    }
+    if (failed.length > 10) {
+      console.error(pc.yellow(`  ... and ${failed.length - 10} more failed`));
+    }
+  }
+  if (result.failed.length > 0) process.exitCode = 1;
+}
+
+interface ProxyCommandOptions {
+  host: string;
+  port: string;
+  openaiUpstream?: string;
+  anthropicUpstream?: string;
+}
+
+async function handleProxyCommand(options: ProxyCommandOptions) {
+  const core = await loadCoreForSecrets();
+  const port = parseInt(options.port, 10);
+  if (!Number.isInteger(port) || port < 0 || port > 65535) {
+    console.error(pc.red(`\n  Invalid port: ${options.port}\n`));
+    process.exit(1);
+  }
+
+  const base = `http://${options.host}:${port}`;
+  console.error(pc.cyan(`\n  Tokscale proxy on ${base}\n`));
+  console.error(pc.gray(`  OPENAI_BASE_URL=${base}/v1`));
+  console.error(pc.gray(`  ANTHROPIC_BASE_URL=${base}`));
+  console.error(pc.gray("  Usage is recorded as the \"recorded\" source. Press Ctrl+C to stop.\n"));
+
+  try {
+    await core.runProxy({
+      host: options.host,
+      port,
+      openaiUpstream: options.openaiUpstream,
+      anthropicUpstream: options.anthropicUpstream,
+    });
+  } catch (error) {
+    console.error(pc.red(`\n  Proxy stopped: ${(error as Error).message}\n`));
+    process.exit(1);
+  }
+}
+
+interface ServeCommandOptions {
+  host: string;
+  port: string;
+  rateLimit: string;
+  emitEvents?: boolean;
+}
+
+async function handleServeCommand(options: ServeCommandOptions) {
+  const core = await loadCoreForSecrets();
+  const port = parseInt(options.port, 10);
+  if (!Number.isInteger(port) || port < 0 || port > 65535) {
+    console.error(pc.red(`\n  Invalid port: ${options.port}\n`));
+    process.exit(1);
+  }
+  const rateLimitPerMinute = parseInt(options.rateLimit, 10);
+  if (!Number.isInteger(rateLimitPerMinute) || rateLimitPerMinute < 0) {
+    console.error(pc.red(`\n  Invalid rate limit: ${options.rateLimit}\n`));
+    process.exit(1);
+  }
+
+  const tokens = core.listServeTokens();
+  console.error(pc.cyan(`\n  Tokscale server on http://${options.host}:${port}\n`));
+  if (tokens.length === 0) {
+    console.error(pc.yellow("  No API tokens; anyone on this machine can read and push usage."));
+    console.error(pc.gray("  Create one with: tokscale serve token create <name> --scope read\n"));
+  } else {
+    console.error(pc.gray(`  ${tokens.length} API token${tokens.length === 1 ? "" : "s"}. Press Ctrl+C to stop.\n`));
+  }
+
+  try {
+    await core.runServer({ host: options.host, port, rateLimitPerMinute, emitEvents: options.emitEvents });
+  } catch (error) {
+    console.error(pc.red(`\n  Server stopped: ${(error as Error).message}\n`));
+    process.exit(1);
+  }
+}
+
+async function handleServeTokenCreateCommand(name: string, options: { scope: string }) {
+  const core = await loadCoreForSecrets();
+  let token: string;
+  try {
+    token = core.createServeToken(name, options.scope);
+  } catch (error) {
+    console.error(pc.red(`\n  Could not create token: ${(error as Error).message}\n`));
+    process.exit(1);
+  }
+  process.stdout.write(`${token}\n`);
+  console.error(pc.green(`✓ Created ${options.scope} token "${name}"; it will not be shown again`));
+}
+
+async function handleServeTokenListCommand(options: { json?: boolean }) {
+  const core = await loadCoreForSecrets();
+  const tokens = core.listServeTokens();
+  if (options.json) {
+    console.log(JSON.stringify(tokens, null, 2));
+    return;
+  }
+  if (tokens.length === 0) {
+    console.log(pc.gray("  No API tokens"));
+    return;
+  }
+  for (const token of tokens) {
+    console.log(`  ${token.name.padEnd(24)} ${token.scope.padEnd(6)} ${pc.gray(token.createdAt)}`);
+  }
+}
+
+async function handleServeTokenRevokeCommand(name: string) {
+  const core = await loadCoreForSecrets();
+  if (!core.revokeServeToken(name)) {
+    console.error(pc.red(`\n  No token named "${name}"\n`));
+    process.exit(1);
+  }
+  console.error(pc.green(`✓ Revoked token "${name}"`));
+}
+
+interface PushCommandOptions extends FilterOptions {
+  token?: string;
+  batchSize: string;
+  since?: string;
+  json?: boolean;
+  spinner?: boolean;
+}
+
+async function handlePushCommand(server: string, options: PushCommandOptions) {
+  const core = await loadCoreForSecrets();
+  const batchSize = parseInt(options.batchSize, 10);
+  if (!Number.isFinite(batchSize) || batchSize < 1) {
+    console.error(pc.red(`\n  Invalid --batch-size: ${options.batchSize}\n`));
+    process.exit(1);
+  }
+  const useSpinner = options.spinner !== false;
+  const spinner = useSpinner ? createSpinner({ color: "cyan" }) : null;
+  spinner?.start(pc.gray(`Pushing usage to ${server}...`));
+
+  try {
+    const result = await core.pushUsage({
+      server,
+      token: options.token,
+      batchSize,
+      sources: getEnabledSources(options),
+      since: options.since,
+    });
+    spinner?.stop();
+
+    if (options.json) {
+      console.log(JSON.stringify(result, null, 2));
+      return;
+    }
+    console.error(pc.green(`✓ Pushed ${formatNumber(result.recorded)} new events in ${result.batches} batch${result.batches === 1 ? "" : "es"}`));
+    if (result.skipped + result.unsent > 0) {
+      console.error(pc.gray(`  ${formatNumber(result.skipped + result.unsent)} events were already on the server`));
+    }
+    if (result.watermark !== undefined) {
+      console.error(pc.gray(`  Server has usage up to ${new Date(result.watermark).toISOString()}`));
+    }
+  } catch (err) {
+    spinner?.stop();
+    const errorMsg = (err as Error).message || "Unknown error";
+    if (options.json) {
+      console.log(JSON.stringify({ error: errorMsg }, null, 2));
+    } else {
+      console.error(pc.red(`\n  ${errorMsg}\n`));
+    }
+    process.exit(1);
+  }
+}
+
+function formatByteSize(bytes: number): string {
+  const units = ["B", "KB", "MB", "GB"];
+  let value = bytes;
+  let unit = 0;
+  while (value >= 1024 && unit < units.length - 1) {
+    value /= 1024;
+    unit++;
+  }
+  return `${unit === 0 ? value : value.toFixed(1)} ${units[unit]}`;
+}
+
+interface QueryCommandOptions extends FilterOptions, DateFilterOptions {
+  json?: boolean;
+  spinner?: boolean;
+}
+
+async function handleQueryCommand(sql: string, options: QueryCommandOptions) {
+  const useSpinner = options.spinner !== false;
+  const spinner = useSpinner ? createSpinner({ color: "cyan" }) : null;
+  spinner?.start(pc.gray("Loading usage data into DuckDB..."));
+
+  try {
+    const result = await runQuery(sql, {
+      sources: getEnabledSources(options),
+      ...getDateFilters(options),
+    });
+    spinner?.stop();
+
+    if (options.json) {
+      console.log(JSON.stringify(result.rows, null, 2));
+      return;
+    }
+
+    const table = new ResponsiveTable({ head: result.columns });
+    for (const row of result.rows) {
+      table.push(result.columns.map((column) => formatQueryValue(row[column])));
+    }
+    console.log(table.toString());
+    console.log(pc.gray(`  ${result.rows.length} row${result.rows.length === 1 ? "" : "s"}\n`));
+  } catch (err) {
+    spinner?.stop();
+    const errorMsg = (err as Error).message || "Unknown error";
+    if (options.json) {
+      console.log(JSON.stringify({ error: errorMsg }, null, 2));
+    } else if (err instanceof DuckDBUnavailableError || err instanceof ArrowExtensionUnavailableError) {
+      console.log(pc.yellow(`\n  ${errorMsg}\n`));
+    } else {
+      console.log(pc.red(`\n  Query failed: ${errorMsg}\n`));
+    }
+    process.exit(1);
+  }
+}
+
+interface ActivityCommandOptions extends FilterOptions, DateFilterOptions, ListingOptions {
+  json?: boolean;
+  idle?: string;
+  tag?: string;
+  spinner?: boolean;
+}
+
+function formatDurationMs(ms: number): string {
+  const minutes = Math.floor(ms / 60000);
+  const hours = Math.floor(minutes / 60);
+  if (hours > 0) return `${hours}h ${minutes % 60}m`;
+  if (minutes > 0) return `${minutes}m`;
+  return `${Math.floor(ms / 1000)}s`;
+}
+
+async function handleActivityCommand(options: ActivityCommandOptions) {
+  const fail = (message: string): never => {
+    if (options.json) {
+      console.log(JSON.stringify({ error: message }, null, 2));
+    } else {
+      console.log(pc.red(`\n  ${message}\n`));
+    }
+    process.exit(1);
+  };
+
+  const idleMinutes = options.idle !== undefined ? Number(options.idle) : undefined;
+  if (idleMinutes !== undefined && !(idleMinutes > 0)) {
+    fail(`Invalid --idle: ${options.idle}`);
+  }
+  const listing = parseListing(options, 10);
+
+  const core = await loadCoreForSecrets();
+  const useSpinner = options.spinner !== false;
+  const spinner = useSpinner ? createSpinner({ color: "cyan" }) : null;
+  spinner?.start(pc.gray("Measuring session activity..."));
+
+  try {
+    const report = await core.getSessionActivity({
+      sources: getEnabledSources(options),
+      ...getDateFilters(options),
+      idleMinutes,
+      tag: options.tag,
+    });
+    spinner?.stop();
+
+    if (options.json) {
+      console.log(JSON.stringify(report, null, 2));
+      return;
+    }
+
+    const table = new ResponsiveTable({
+      head: ["Source", "Session", "Started", "Active", "Wall clock", "Cost", "Tags"],
+    });
+    for (const session of report.sessions) {
+      table.push([
+        session.source,
+        session.sessionId.slice(0, 12),
+        new Date(session.start).toISOString().slice(0, 16).replace("T", " "),
+        formatDurationMs(session.activeMs),
+        pc.gray(formatDurationMs(session.wallMs)),
+        formatCostCell(session.cost),
+        pc.yellow(session.tags.join(", ")),
+      ]);
+    }
+    printTable(table, listing);
+    console.log(
+      pc.gray(
+        `\n  ${formatNumber(report.sessionCount)} sessions, ${report.activeHours.toFixed(1)} active hours ` +
+          `(${report.wallHours.toFixed(1)} wall clock, idle gaps over ${report.idleThresholdMinutes} min trimmed)`
+      )
+    );
+    console.log(pc.gray(`  Cost per active hour: ${pc.green(formatCurrency(report.costPerActiveHour))}\n`));
+  } catch (err) {
+    spinner?.stop();
+    fail(`Error measuring activity: ${(err as Error).message || "Unknown error"}`);
+  }
+}
+
+interface EfficiencyCommandOptions extends FilterOptions, DateFilterOptions, ListingOptions {
+  json?: boolean;
+  idle?: string;
+  minMessages?: string;
+  minMinutes?: string;
+  family?: string;
+  spinner?: boolean;
+}
+
+async function handleEfficiencyCommand(options: EfficiencyCommandOptions) {
+  const fail = (message: string): never => {
+    if (options.json) {
+      console.log(JSON.stringify({ error: message }, null, 2));
+    } else {
+      console.log(pc.red(`\n  ${message}\n`));
+    }
+    process.exit(1);
+  };
+
+  const idleMinutes = options.idle !== undefined ? Number(options.idle) : undefined;
+  if (idleMinutes !== undefined && !(idleMinutes > 0)) {
+    fail(`Invalid --idle: ${options.idle}`);
+  }
+  const minMessages = options.minMessages !== undefined ? Number(options.minMessages) : undefined;
+  if (minMessages !== undefined && !(Number.isInteger(minMessages) && minMessages >= 1)) {
+    fail(`Invalid --min-messages: ${options.minMessages}`);
+  }
+  const minActiveMinutes = options.minMinutes !== undefined ? Number(options.minMinutes) : undefined;
+  if (minActiveMinutes !== undefined && !(minActiveMinutes >= 0)) {
+    fail(`Invalid --min-minutes: ${options.minMinutes}`);
+  }
+  const listing = parseListing(options);
+
+  const core = await loadCoreForSecrets();
+  const useSpinner = options.spinner !== false;
+  const spinner = useSpinner ? createSpinner({ color: "cyan" }) : null;
+  spinner?.start(pc.gray("Comparing sources..."));
+
+  try {
+    const report = await core.getEfficiencyLeaderboard({
+      sources: getEnabledSources(options),
+      ...getDateFilters(options),
+      idleMinutes,
+      minMessages,
+      minActiveMinutes,
+      family: options.family,
+    });
+    spinner?.stop();
+
+    if (options.json) {
+      console.log(JSON.stringify(report, null, 2));
+      return;
+    }
+    if (report.entries.length === 0) {
+      console.log(pc.yellow(`\n  ${t("report.noData")}\n`));
+      return;
+    }
+
+    const table = new ResponsiveTable({
+      head: ["#", "Source", "Sessions", "Active hours", "Per hour", "Per session", "Median session", "Per 1M tokens"],
+      keys: ["rank", "source", "sessions", "hours", "per-hour", "per-session", "median", "per-million"],
+      colAligns: ["right", "left", "right", "right", "right", "right", "right", "right"],
+    });
+    for (const entry of report.entries) {
+      if (entry.rank === undefined) {
+        table.push(["-", pc.gray(getSourceLabel(entry.source)), "0", "-", "-", "-", "-", "-"]);
+        continue;
+      }
+      table.push([
+        String(entry.rank),
+        getSourceLabel(entry.source),
+        formatNumber(entry.sessions),
+        entry.activeHours.toFixed(1),
+        formatCostCell(entry.costPerActiveHour),
+        formatCurrency(entry.costPerSession),
+        formatCurrency(entry.medianSessionCost),
+        formatCurrency(entry.costPerMillionTokens),
+      ]);
+    }
+    printTable(table, listing);
+
+    const excluded = report.entries.reduce((sum, e) => sum + e.excludedSessions, 0);
+    console.log(
+      pc.gray(
+        `\n  Sessions with at least ${report.minMessages} messages and ${report.minActiveMinutes} active minutes` +
+          (report.family ? ` on ${report.family} models` : "") +
+          `; ${formatNumber(excluded)} shorter sessions left out`
+      )
+    );
+    console.log(pc.gray(`  Idle gaps over ${report.idleThresholdMinutes} min trimmed\n`));
+  } catch (err) {
+    spinner?.stop();
+    fail(`Error comparing sources: ${(err as Error).message || "Unknown error"}`);
+  }
+}
+
+interface BranchesCommandOptions extends FilterOptions, DateFilterOptions, ListingOptions {
+  json?: boolean;
+  spinner?: boolean;
+}
+
+async function handleBranchesCommand(options: BranchesCommandOptions) {
+  const listing = parseListing(options);
+  const core = await loadCoreForSecrets();
+  const useSpinner = options.spinner !== false;
+  const spinner = useSpinner ? createSpinner({ color: "cyan" }) : null;
+  spinner?.start(pc.gray("Grouping usage by branch..."));
+
+  try {
+    const report = await core.getBranchReport({
+      sources: getEnabledSources(options),
+      ...getDateFilters(options),
+    });
+    spinner?.stop();
+
+    if (options.json) {
+      console.log(JSON.stringify(report, null, 2));
+      return;
+    }
+
+    const table = new ResponsiveTable({
+      head: ["Branch", "Project", "Sessions", "Active", "Cost"],
+    });
+    for (const entry of report.entries) {
+      table.push([
+        entry.branch,
+        entry.name ?? pc.gray("-"),
+        formatNumber(entry.sessionCount),
+        entry.firstDate === entry.lastDate
+          ? entry.firstDate
+          : `${entry.firstDate}${isPlain() ? " to " : " → "}${entry.lastDate}`,
+        formatCostCell(entry.cost),
+      ]);
+    }
+    printTable(table, listing);
+    if (report.unattributedMessages > 0) {
+      console.log(
+        pc.gray(`\n  ${formatNumber(report.unattributedMessages)} messages (${formatCurrency(report.unattributedCost)}) have no branch recorded`)
+      );
+    }
+    console.log(pc.gray(`\n  Total: ${pc.green(formatCurrency(report.totalCost))}\n`));
+  } catch (err) {
+    spinner?.stop();
+    const errorMsg = (err as Error).message || "Unknown error";
+    if (options.json) {
+      console.log(JSON.stringify({ error: errorMsg }, null, 2));
+    } else {
+      console.log(pc.red(`\n  Error building branch report: ${errorMsg}\n`));
+    }
+    process.exit(1);
+  }
+}
+
+interface CommitsCommandOptions extends FilterOptions, DateFilterOptions, ListingOptions {
+  json?: boolean;
+  window?: string;
+  author?: string;
+  spinner?: boolean;
+}
+
+async function handleCommitsCommand(options: CommitsCommandOptions) {
+  const fail = (message: string): never => {
+    if (options.json) {
+      console.log(JSON.stringify({ error: message }, null, 2));
+    } else {
+      console.log(pc.red(`\n  ${message}\n`));
+    }
+    process.exit(1);
+  };
+
+  const windowHours = options.window !== undefined ? Number(options.window) : undefined;
+  if (windowHours !== undefined && !(windowHours > 0)) {
+    fail(`Invalid --window: ${options.window}`);
+  }
+  const listing = parseListing(options, 20);
+
+  const core = await loadCoreForSecrets();
+  const useSpinner = options.spinner !== false;
+  const spinner = useSpinner ? createSpinner({ color: "cyan" }) : null;
+  spinner?.start(pc.gray("Matching sessions to commits..."));
+
+  try {
+    const report = await core.getCommitCosts({
+      sources: getEnabledSources(options),
+      ...getDateFilters(options),
+      windowHours,
+      author: options.author,
+    });
+    spinner?.stop();
+
+    if (options.json) {
+      console.log(JSON.stringify(report, null, 2));
+      return;
+    }
+
+    const table = new ResponsiveTable({
+      head: ["Date", "Project", "Commit", "Sessions", "Cost"],
+    });
+    for (const commit of report.commits) {
+      table.push([
+        new Date(commit.timestamp).toISOString().slice(0, 10),
+        commit.name,
+        `${pc.yellow(commit.hash.slice(0, 8))} ${commit.subject}`,
+        formatNumber(commit.sessionCount),
+        formatCurrency(commit.cost),
+      ]);
+    }
+    printTable(table, listing);
+    console.log(
+      pc.gray(
+        `\n  ${formatNumber(report.commits.length)} commits in ${report.repositories} repositories: ` +
+          `${pc.green(formatCurrency(report.meanCostPerCommit))} mean, ${pc.green(formatCurrency(report.medianCostPerCommit))} median per commit`
+      )
+    );
+    if (report.uncommittedMessages > 0) {
+      console.log(pc.gray(`  Not followed by a commit: ${formatCurrency(report.uncommittedCost)}`));
+    }
+    if (report.unattributedMessages > 0) {
+      console.log(pc.gray(`  Outside a git repository or without a working directory: ${formatCurrency(report.unattributedCost)}`));
+    }
+    console.log();
+  } catch (err) {
+    spinner?.stop();
+    fail(`Error computing commit costs: ${(err as Error).message || "Unknown error"}`);
+  }
+}
+
+interface ModesCommandOptions extends FilterOptions, DateFilterOptions {
+  json?: boolean;
+  spinner?: boolean;
+}
+
+async function handleModesCommand(options: ModesCommandOptions) {
+  const core = await loadCoreForSecrets();
+  const useSpinner = options.spinner !== false;
+  const spinner = useSpinner ? createSpinner({ color: "cyan" }) : null;
+  spinner?.start(pc.gray("Classifying usage..."));
+
+  try {
+    const report = await core.getUsageModes({
+      sources: getEnabledSources(options),
+      ...getDateFilters(options),
+    });
+    spinner?.stop();
+
+    if (options.json) {
+      console.log(JSON.stringify(report, null, 2));
+      return;
+    }
+
+    const table = new ResponsiveTable({
+      head: ["Source", "Mode", "Messages", "Tokens", "Cost"],
+    });
+    for (const entry of report.entries) {
+      table.push([
+        entry.source,
+        entry.mode === "agentic" ? pc.magenta(entry.mode) : pc.cyan(entry.mode),
+        formatNumber(entry.messageCount),
+        formatNumber(entry.input + entry.output + entry.cacheRead + entry.cacheWrite + entry.reasoning),
+        formatCostCell(entry.cost),
+      ]);
+    }
+    console.log(table.toString());
+    console.log(
+      pc.gray(
+        `\n  Interactive: ${pc.green(formatCurrency(report.interactiveCost))}  ` +
+          `Agentic: ${pc.green(formatCurrency(report.agenticCost))} (${Math.round(report.agenticShare * 100)}% of spend)\n`
+      )
+    );
+  } catch (err) {
+    spinner?.stop();
+    const errorMsg = (err as Error).message || "Unknown error";
+    if (options.json) {
+      console.log(JSON.stringify({ error: errorMsg }, null, 2));
+    } else {
+      console.log(pc.red(`\n  Error classifying usage: ${errorMsg}\n`));
+    }
+    process.exit(1);
+  }
+}
+
+interface DriversCommandOptions extends FilterOptions, DateFilterOptions {
+  json?: boolean;
+  spinner?: boolean;
+}
+
+async function handleDriversCommand(options: DriversCommandOptions) {
+  const core = await loadCoreForSecrets();
+  const useSpinner = options.spinner !== false;
+  const spinner = useSpinner ? createSpinner({ color: "cyan" }) : null;
+  spinner?.start(pc.gray("Grouping usage into turns..."));
+
+  try {
+    const sources = getEnabledSources(options) ?? ["claude", "codex"];
+    const report = await core.getCostDrivers({ sources, ...getDateFilters(options) });
+    spinner?.stop();
+
+    if (options.json) {
+      console.log(JSON.stringify(report, null, 2));
+      return;
+    }
+    if (report.turnCount === 0) {
+      console.log(pc.gray("\n  No prompts found. Turns come from Claude Code and Codex sessions.\n"));
+      return;
+    }
+
+    const sections: [string, typeof report.promptLength][] = [
+      ["Prompt length", report.promptLength],
+      ["Tool calls", report.toolCalls],
+      ["Files touched", report.filesTouched],
+    ];
+    for (const [title, buckets] of sections) {
+      if (buckets.length === 0) continue;
+      const table = new ResponsiveTable({
+        head: [title, "Turns", "Messages", "Tokens", "Cost", "Per turn", "Share"],
+      });
+      for (const bucket of buckets) {
+        table.push([
+          bucket.label,
+          formatNumber(bucket.turnCount),
+          formatNumber(bucket.messageCount),
+          formatNumber(bucket.tokens),
+          formatCurrency(bucket.cost),
+          formatCurrency(bucket.averageCost),
+          `${Math.round(bucket.share * 100)}%`,
+        ]);
+      }
+      console.log(table.toString());
+    }
+    console.log(pc.gray(`\n  ${formatNumber(report.turnCount)} turns, ${pc.green(formatCurrency(report.attributedCost))}`));
+    if (report.unattributedMessages > 0) {
+      console.log(
+        pc.gray(
+          `  ${formatNumber(report.unattributedMessages)} messages (${formatCurrency(report.unattributedCost)}) had no logged prompt`
+        )
+      );
+    }
+    console.log();
+  } catch (err) {
+    spinner?.stop();
+    const errorMsg = (err as Error).message || "Unknown error";
+    if (options.json) {
+      console.log(JSON.stringify({ error: errorMsg }, null, 2));
+    } else {
+      console.log(pc.red(`\n  Error grouping usage into turns: ${errorMsg}\n`));
+    }
+    process.exit(1);
+  }
+}
+
+interface ContextCommandOptions extends FilterOptions, DateFilterOptions {
+  json?: boolean;
+  threshold?: string;
+  spinner?: boolean;
+}
+
+async function handleContextCommand(options: ContextCommandOptions) {
+  const fail = (message: string): never => {
+    if (options.json) {
+      console.log(JSON.stringify({ error: message }, null, 2));
+    } else {
+      console.log(pc.red(`\n  ${message}\n`));
+    }
+    process.exit(1);
+  };
+
+  const percent = options.threshold !== undefined ? Number(options.threshold) : undefined;
+  if (percent !== undefined && !(percent > 0 && percent <= 100)) {
+    fail(`Invalid --threshold: ${options.threshold} (expected a percentage up to 100)`);
+  }
+
+  const core = await loadCoreForSecrets();
+  const useSpinner = options.spinner !== false;
+  const spinner = useSpinner ? createSpinner({ color: "cyan" }) : null;
+  spinner?.start(pc.gray("Measuring context window use..."));
+
+  try {
+    const report = await core.getContextPressure({
+      sources: getEnabledSources(options),
+      ...getDateFilters(options),
+      threshold: percent !== undefined ? percent / 100 : undefined,
+    });
+    spinner?.stop();
+
+    if (options.json) {
+      console.log(JSON.stringify(report, null, 2));
+      return;
+    }
+    if (report.models.length === 0) {
+      console.log(pc.gray("\n  No usage from models with a known context window.\n"));
+      return;
+    }
+
+    const table = new ResponsiveTable({
+      head: ["Model", "Window", "Messages", "Near limit", "Over", "Share", "Sessions", "Peak", "Cost near limit"],
+    });
+    for (const entry of report.models) {
+      const share = `${Math.round(entry.nearLimitShare * 100)}%`;
+      table.push([
+        entry.model,
+        formatNumber(entry.contextWindow),
+        formatNumber(entry.messageCount),
+        formatNumber(entry.nearLimitCount),
+        entry.overLimitCount > 0 ? pc.yellow(formatNumber(entry.overLimitCount)) : "0",
+        entry.nearLimitShare >= 0.1 ? pc.red(share) : share,
+        formatNumber(entry.sessionsAffected),
+        formatNumber(entry.peakContext),
+        formatCurrency(entry.nearLimitCost),
+      ]);
+    }
+    console.log(table.toString());
+    const flagged = report.nearLimitCount + report.overLimitCount;
+    console.log(
+      pc.gray(
+        `\n  ${formatNumber(flagged)} of ${formatNumber(report.messageCount)} messages at ${Math.round(report.threshold * 100)}%+ ` +
+          `of the context window, ${pc.green(formatCurrency(report.nearLimitCost))}`
+      )
+    );
+    if (report.modelsWithoutWindow.length > 0) {
+      console.log(pc.gray(`  No context window known for: ${report.modelsWithoutWindow.join(", ")}`));
+    }
+    console.log();
+  } catch (err) {
+    spinner?.stop();
+    const errorMsg = (err as Error).message || "Unknown error";
+    if (options.json) {
+      console.log(JSON.stringify({ error: errorMsg }, null, 2));
+    } else {
+      console.log(pc.red(`\n  Error measuring context window use: ${errorMsg}\n`));
+    }
+    process.exit(1);
+  }
+}
+
+interface ProjectsCommandOptions extends FilterOptions, DateFilterOptions, ListingOptions {
+  json?: boolean;
+  spinner?: boolean;
+}
+
+async function handleProjectsCommand(options: ProjectsCommandOptions) {
+  const listing = parseListing(options);
+  const core = await loadCoreForSecrets();
+  const useSpinner = options.spinner !== false;
+  const spinner = useSpinner ? createSpinner({ color: "cyan" }) : null;
+  spinner?.start(pc.gray("Grouping usage by project..."));
+
+  try {
+    const report = await core.getProjectReport({
+      sources: getEnabledSources(options),
+      ...getDateFilters(options),
+    });
+    spinner?.stop();
+
+    if (options.json) {
+      console.log(JSON.stringify(report, null, 2));
+      return;
+    }
+
+    const table = new ResponsiveTable({
+      head: ["Project", "Root", "Sources", "Messages", "Cost"],
+    });
+    for (const entry of report.entries) {
+      table.push([
+        entry.name,
+        pc.gray(entry.project),
+        entry.sources.join(", "),
+        formatNumber(entry.messageCount),
+        formatCostCell(entry.cost),
+      ]);
+    }
+    printTable(table, listing);
+    if (report.unattributedMessages > 0) {
+      console.log(
+        pc.gray(`\n  ${formatNumber(report.unattributedMessages)} messages (${formatCurrency(report.unattributedCost)}) came from sources that don't record a working directory`)
+      );
+    }
+    console.log(pc.gray(`\n  Total: ${pc.green(formatCurrency(report.totalCost))}\n`));
+  } catch (err) {
+    spinner?.stop();
+    const errorMsg = (err as Error).message || "Unknown error";
+    if (options.json) {
+      console.log(JSON.stringify({ error: errorMsg }, null, 2));
+    } else {
+      console.log(pc.red(`\n  Error building project report: ${errorMsg}\n`));
+    }
+    process.exit(1);
+  }
+}
+
+interface RatesCommandOptions extends FilterOptions, DateFilterOptions, ListingOptions {
+  json?: boolean;
+  spinner?: boolean;
+}
+
+async function handleRatesCommand(options: RatesCommandOptions) {
+  const listing = parseListing(options);
+  const core = await loadCoreForSecrets();
+  const useSpinner = options.spinner !== false;
+  const spinner = useSpinner ? createSpinner({ color: "cyan" }) : null;
+  spinner?.start(pc.gray("Computing effective rates..."));
+
+  try {
+    const report = await core.getEffectiveRates({
+      sources: getEnabledSources(options),
+      ...getDateFilters(options),
+    });
+    spinner?.stop();
+
+    if (options.json) {
+      console.log(JSON.stringify(report, null, 2));
+      return;
+    }
+
+    const rate = (value: number | null | undefined) => (value == null ? pc.gray("-") : core.formatPricePerMillion(value).replace(" / 1M tokens", ""));
+    const table = new ResponsiveTable({
+      head: ["Model", "Cost", "Effective", "Input (list)", "Output (list)", "Cache hits"],
+    });
+    for (const entry of report.entries) {
+      table.push([
+        formatModelName(entry.model),
+        formatCostCell(entry.cost),
+        rate(entry.effectivePerMillion),
+        `${rate(entry.inputPerMillion)} (${rate(entry.listInputPerMillion)})`,
+        `${rate(entry.outputPerMillion)} (${rate(entry.listOutputPerMillion)})`,
+        `${Math.round(entry.cacheReadShare * 100)}%`,
+      ]);
+    }
+    printTable(table, listing);
+    console.log(
+      pc.gray(`\n  Overall: ${pc.green(core.formatPricePerMillion(report.effectivePerMillion))} over ${formatNumber(report.totalTokens)} tokens\n`)
+    );
+  } catch (err) {
+    spinner?.stop();
+    const errorMsg = (err as Error).message || "Unknown error";
+    if (options.json) {
+      console.log(JSON.stringify({ error: errorMsg }, null, 2));
+    } else {
+      console.log(pc.red(`\n  Error computing rates: ${errorMsg}\n`));
+    }
+    process.exit(1);
+  }
+}
+
+interface RecentCommandOptions extends FilterOptions, ListingOptions {
+  json?: boolean;
+  granularity?: string;
+  days?: string;
+  all?: boolean;
+  spinner?: boolean;
+}
+
+async function handleRecentCommand(options: RecentCommandOptions) {
+  const fail = (message: string): never => {
+    if (options.json) {
+      console.log(JSON.stringify({ error: message }, null, 2));
+    } else {
+      console.log(pc.red(`\n  ${message}\n`));
+    }
+    process.exit(1);
+  };
+
+  const days = options.days !== undefined ? parseInt(options.days, 10) : undefined;
+  if (days !== undefined && !(days > 0 && days <= 14)) {
+    fail(`Invalid --days: ${options.days} (1-14)`);
+  }
+  const listing = parseListing(options);
+
+  const core = await loadCoreForSecrets();
+  const useSpinner = options.spinner !== false;
+  const spinner = useSpinner ? createSpinner({ color: "cyan" }) : null;
+  spinner?.start(pc.gray("Bucketing recent usage..."));
+
+  try {
+    const recent = await core.getRecentUsage({
+      sources: getEnabledSources(options),
+      granularity: options.granularity,
+      days,
+    });
+    spinner?.stop();
+
+    if (options.json) {
+      console.log(JSON.stringify(recent, null, 2));
+      return;
+    }
+
+    const table = new ResponsiveTable({
+      head: ["Start", "Messages", "Tokens", "Cost"],
+    });
+    for (const bucket of recent.buckets) {
+      if (!options.all && bucket.messageCount === 0) continue;
+      table.push([
+        new Date(bucket.start).toLocaleString(undefined, { dateStyle: "short", timeStyle: "short" }),
+        formatNumber(bucket.messageCount),
+        formatNumber(bucket.tokens),
+        formatCurrency(bucket.cost),
+      ]);
+    }
+    printTable(table, listing);
+    console.log(
+      pc.gray(
+        `\n  ${recent.bucketMinutes}-minute buckets over the last ${recent.days} days. ` +
+          `Burn rate: ${pc.green(formatCurrency(recent.costPerHour))}/h, ${formatNumber(recent.tokensPerHour)} tokens/h\n`
+      )
+    );
+  } catch (err) {
+    spinner?.stop();
+    fail(`Error bucketing recent usage: ${(err as Error).message || "Unknown error"}`);
+  }
+}
+
+interface ReconcileCommandOptions {
+  json?: boolean;
+  provider?: string;
+  tolerance?: string;
+  minDifference?: string;
+  mismatchesOnly?: boolean;
+  spinner?: boolean;
+}
+
+async function handleReconcileCommand(invoicePath: string, options: ReconcileCommandOptions) {
+  const fail = (message: string): never => {
+    if (options.json) {
+      console.log(JSON.stringify({ error: message }, null, 2));
+    } else {
+      console.log(pc.red(`\n  ${message}\n`));
+    }
+    process.exit(1);
+  };
+
+  let csv: string;
+  try {
+    csv = fs.readFileSync(invoicePath, "utf-8");
+  } catch (err) {
+    return fail(`Can't read ${invoicePath}: ${(err as Error).message}`);
+  }
+  const tolerance = options.tolerance !== undefined ? Number(options.tolerance) / 100 : undefined;
+  if (tolerance !== undefined && !(tolerance >= 0)) {
+    return fail(`Invalid --tolerance: ${options.tolerance}`);
+  }
+  const minDifference = options.minDifference !== undefined ? Number(options.minDifference) : undefined;
+  if (minDifference !== undefined && !(minDifference >= 0)) {
+    return fail(`Invalid --min-difference: ${options.minDifference}`);
+  }
+
+  const core = await loadCoreForSecrets();
+  const useSpinner = options.spinner !== false;
+  const spinner = useSpinner ? createSpinner({ color: "cyan" }) : null;
+  spinner?.start(pc.gray("Reconciling invoice..."));
+
+  let report: Awaited<ReturnType<typeof core.reconcileInvoice>>;
+  try {
+    report = await core.reconcileInvoice({ csv, provider: options.provider, tolerance, minDifference });
+  } catch (err) {
+    spinner?.stop();
+    return fail(`Reconciliation failed: ${(err as Error).message || "Unknown error"}`);
+  }
+  spinner?.stop();
+
+  if (options.json) {
+    console.log(JSON.stringify(report, null, 2));
+    return;
+  }
+
+  const lines = options.mismatchesOnly ? report.lines.filter((line) => line.mismatch) : report.lines;
+  const table = new ResponsiveTable({ head: ["Date", "Model", "Billed", "Computed", "Difference"] });
+  for (const line of lines) {
+    const difference = `${line.difference >= 0 ? "+" : "-"}${formatCurrency(Math.abs(line.difference))}`;
+    table.push([
+      line.date,
+      formatModelName(line.model),
+      formatCurrency(line.billed),
+      formatCurrency(line.computed),
+      line.mismatch ? pc.red(difference) : pc.gray(difference),
+    ]);
+  }
+  console.log(pc.cyan(`\n  ${report.provider} invoice, ${report.since ?? "-"} to ${report.until ?? "-"}`));
+  console.log(table.toString());
+  console.log(pc.gray(`  Billed: ${pc.white(formatCurrency(report.totalBilled))}  Computed: ${pc.white(formatCurrency(report.totalComputed))}`));
+  if (report.mismatches > 0) {
+    console.log(pc.yellow(`  ${report.mismatches} line${report.mismatches === 1 ? "" : "s"} outside tolerance\n`));
+  } else {
+    console.log(pc.green("  All lines within tolerance\n"));
+  }
+}
+
+interface DiffCommandOptions extends FilterOptions, ListingOptions {
+  json?: boolean;
+  from?: string;
+  to?: string;
+  spinner?: boolean;
+}
+
+async function handleDiffCommand(exports: string[], options: DiffCommandOptions) {
+  const fail = (message: string): never => {
+    if (options.json) {
+      console.log(JSON.stringify({ error: message }, null, 2));
+    } else {
+      console.log(pc.red(`\n  ${message}\n`));
+    }
+    process.exit(1);
+  };
+
+  if (exports.length > 0 && (options.from || options.to)) {
+    fail("Compare either two exports or --from/--to periods, not both");
+  }
+  if (exports.length === 0 && !(options.from && options.to)) {
+    fail("Give --from and --to periods, or two exported JSON reports");
+  }
+  if (exports.length !== 0 && exports.length !== 2) {
+    fail(`Expected two exported reports, got ${exports.length}`);
+  }
+  const listing = parseListing(options, 10);
+
+  const core = await loadCoreForSecrets();
+  let diff: Awaited<ReturnType<typeof core.getUsageDiff>>;
+  if (exports.length === 2) {
+    const [fromJson, toJson] = exports.map((path) => {
+      try {
+        return fs.readFileSync(path, "utf-8");
+      } catch (err) {
+        return fail(`Can't read ${path}: ${(err as Error).message}`);
+      }
+    });
+    try {
+      diff = core.diffReportExports(fromJson, toJson, exports[0], exports[1]);
+    } catch (err) {
+      return fail(`Can't compare exports: ${(err as Error).message || "Unknown error"}`);
+    }
+  } else {
+    const useSpinner = options.spinner !== false;
+    const spinner = useSpinner ? createSpinner({ color: "cyan" }) : null;
+    spinner?.start(pc.gray("Comparing periods..."));
+    try {
+      diff = await core.getUsageDiff({ sources: getEnabledSources(options), from: options.from!, to: options.to! });
+    } catch (err) {
+      spinner?.stop();
+      return fail(`Error comparing periods: ${(err as Error).message || "Unknown error"}`);
+    }
+    spinner?.stop();
+  }
+
+  if (options.json) {
+    console.log(JSON.stringify(diff, null, 2));
+    return;
+  }
+
+  const signed = (value: number, format: (n: number) => string) => {
+    const text = `${value >= 0 ? "+" : "-"}${format(Math.abs(value))}`;
+    return value > 0 ? pc.red(text) : value < 0 ? pc.green(text) : pc.gray(text);
+  };
+  const percent = (share: number) => `${Math.round(share * 100)}%`;
+  const label = (entry: { name: string; status: string }, format: (name: string) => string) => {
+    if (entry.status === "added") return pc.green(`${format(entry.name)} (new)`);
+    if (entry.status === "removed") return pc.gray(`${format(entry.name)} (gone)`);
+    return format(entry.name);
+  };
+
+  console.log(pc.cyan(`\n  ${diff.fromLabel}${isPlain() ? " to " : " → "}${diff.toLabel}`));
+  const totals = new ResponsiveTable({
+    head: ["", diff.fromLabel, diff.toLabel, "Change"],
+    colAligns: ["left", "right", "right", "right"],
+  });
+  totals.push(["Cost", formatCurrency(diff.from.cost), formatCurrency(diff.to.cost), signed(diff.costChange, formatCurrency)]);
+  totals.push(["Tokens", formatNumber(diff.from.tokens), formatNumber(diff.to.tokens), signed(diff.to.tokens - diff.from.tokens, formatNumber)]);
+  totals.push(["Messages", formatNumber(diff.from.messages), formatNumber(diff.to.messages), signed(diff.to.messages - diff.from.messages, formatNumber)]);
+  console.log(totals.toString());
+  if (diff.costChangePercent != null) {
+    console.log(pc.gray(`  Cost ${diff.costChangePercent >= 0 ? "up" : "down"} ${Math.abs(diff.costChangePercent).toFixed(1)}%`));
+  }
+
+  if (diff.models) {
+    console.log(pc.cyan("\n  Model mix"));
+    const models = new ResponsiveTable({
+      head: ["Model", diff.fromLabel, diff.toLabel, "Change", "Share"],
+      colAligns: ["left", "right", "right", "right", "right"],
+    });
+    for (const entry of diff.models) {
+      models.push([
+        label(entry, formatModelName),
+        formatCurrency(entry.fromCost),
+        formatCurrency(entry.toCost),
+        signed(entry.costChange, formatCurrency),
+        `${percent(entry.fromShare)}${isPlain() ? " to " : " → "}${percent(entry.toShare)}`,
+      ]);
+    }
+    printTable(models, listing);
+  }
+
+  if (diff.projects) {
+    console.log(pc.cyan("\n  Projects"));
+    const projects = new ResponsiveTable({
+      head: ["Project", diff.fromLabel, diff.toLabel, "Change"],
+      colAligns: ["left", "right", "right", "right"],
+    });
+    for (const entry of diff.projects) {
+      projects.push([
+        label(entry, (name) => name),
+        formatCurrency(entry.fromCost),
+        formatCurrency(entry.toCost),
+        signed(entry.costChange, formatCurrency),
+      ]);
+    }
+    printTable(projects, listing);
   }
+  console.log();
+}
 
-  // Create table
-  const table = createUsageTable("Month");
+interface StatementsCommandOptions extends FilterOptions, DateFilterOptions {
+  format?: string;
+  provider?: string;
+  outputDir?: string;
+  spinner?: boolean;
+}
 
-  const settings = loadSettings();
-  const filteredEntries = settings.includeUnusedModels
-    ? report.entries
-    : report.entries.filter(e => e.input + e.output + e.cacheRead + e.cacheWrite > 0);
+async function handleStatementsCommand(month: string | undefined, options: StatementsCommandOptions) {
+  const fail = (message: string): never => {
+    console.error(pc.red(`\n  ${message}\n`));
+    process.exit(1);
+  };
 
-  for (const entry of filteredEntries) {
-    table.push(
-      formatUsageRow(
-        entry.month,
-        entry.models,
-        entry.input,
-        entry.output,
-        entry.cacheWrite,
-        entry.cacheRead,
-        entry.cost
-      )
-    );
+  const format = (options.format ?? "markdown").toLowerCase();
+  if (!["markdown", "md", "csv", "json"].includes(format)) {
+    fail(`Invalid --format: ${options.format} (markdown, csv or json)`);
+  }
+  let dateFilters = getDateFilters(options);
+  if (month !== undefined) {
+    const match = /^(\d{4})-(\d{2})$/.exec(month);
+    if (!match || Number(match[2]) < 1 || Number(match[2]) > 12) {
+      fail(`Invalid month: ${month} (expected YYYY-MM)`);
+    }
+    const lastDay = new Date(Number(match![1]), Number(match![2]), 0).getDate();
+    dateFilters = { since: `${month}-01`, until: `${month}-${String(lastDay).padStart(2, "0")}` };
   }
 
-  // Add totals row
-  const totalInput = report.entries.reduce((sum, e) => sum + e.input, 0);
-  const totalOutput = report.entries.reduce((sum, e) => sum + e.output, 0);
-  const totalCacheRead = report.entries.reduce((sum, e) => sum + e.cacheRead, 0);
-  const totalCacheWrite = report.entries.reduce((sum, e) => sum + e.cacheWrite, 0);
+  const core = await loadCoreForSecrets();
+  const useSpinner = options.spinner !== false && options.outputDir !== undefined;
+  const spinner = useSpinner ? createSpinner({ color: "cyan" }) : null;
+  spinner?.start(pc.gray("Building statements..."));
 
-  table.push(
-    formatTotalsRow(totalInput, totalOutput, totalCacheWrite, totalCacheRead, report.totalCost)
-  );
+  let statements: Awaited<ReturnType<typeof core.getStatements>>;
+  try {
+    statements = await core.getStatements({
+      sources: getEnabledSources(options),
+      ...dateFilters,
+      provider: options.provider,
+    });
+  } catch (err) {
+    spinner?.stop();
+    return fail(`Error building statements: ${(err as Error).message || "Unknown error"}`);
+  }
+  spinner?.stop();
 
-  console.log(table.toString());
-  console.log(pc.gray(`\n  Total Cost: ${pc.green(formatCurrency(report.totalCost))}`));
+  if (statements.length === 0) {
+    fail("No usage in that period");
+  }
 
-  if (options.benchmark) {
-    console.log(pc.gray(`  Processing time: ${processingTime.toFixed(0)}ms (Rust) + ${report.processingTimeMs}ms (parsing)`));
-    if (cursorSync.attempted) {
-      if (cursorSync.synced) {
-        console.log(pc.gray(`  Cursor: ${cursorSync.rows} usage events synced (full lifetime data)`));
-      } else {
-        console.log(pc.yellow(`  Cursor: sync failed - ${cursorSync.error}`));
-      }
+  if (format === "json") {
+    console.log(JSON.stringify(statements, null, 2));
+    return;
+  }
+
+  if (format === "csv") {
+    const csv = core.statementsToCsv(statements);
+    if (options.outputDir === undefined) {
+      process.stdout.write(csv);
+      return;
     }
+    fs.mkdirSync(options.outputDir, { recursive: true });
+    const file = `${options.outputDir}/statements.csv`;
+    fs.writeFileSync(file, csv);
+    console.log(pc.green(`\n  Wrote ${statements.length} statements to ${file}\n`));
+    return;
   }
 
-  console.log();
+  if (options.outputDir === undefined) {
+    console.log(statements.map((statement) => core.statementToMarkdown(statement)).join("\n---\n\n"));
+    return;
+  }
+  fs.mkdirSync(options.outputDir, { recursive: true });
+  for (const statement of statements) {
+    const file = `${options.outputDir}/${statement.month}-${statement.provider.replace(/[^\w.-]/g, "_")}.md`;
+    fs.writeFileSync(file, core.statementToMarkdown(statement));
+    console.log(pc.gray(`  ${file}: ${formatCurrency(statement.totalCost)}`));
+  }
+  console.log(pc.green(`\n  Wrote ${statements.length} statements to ${options.outputDir}\n`));
 }
 
-type JsonReportType = "models" | "monthly";
+interface BudgetsCommandOptions {
+  enforce?: boolean;
+  json?: boolean;
+  spinner?: boolean;
+}
 
-async function outputJsonReport(
-  reportType: JsonReportType,
-  options: FilterOptions & DateFilterOptions
-) {
-  const dateFilters = getDateFilters(options);
-  const enabledSources = getEnabledSources(options);
-  const onlyCursor = enabledSources?.length === 1 && enabledSources[0] === 'cursor';
-  const includeCursor = !enabledSources || enabledSources.includes('cursor');
-  const localSources: SourceType[] = (enabledSources || ['opencode', 'claude', 'codex', 'gemini', 'cursor', 'amp', 'droid'])
-    .filter(s => s !== 'cursor');
+async function handleBudgetsCommand(options: BudgetsCommandOptions) {
+  const core = await loadCoreForSecrets();
+  const useSpinner = options.spinner !== false;
+  const spinner = useSpinner ? createSpinner({ color: "cyan" }) : null;
+  spinner?.start(pc.gray("Checking budgets..."));
 
-  const { cursorSync, localMessages } = await loadDataSourcesParallel(
-    onlyCursor ? [] : localSources,
-    dateFilters
-  );
-  
-  if (!localMessages && !onlyCursor) {
-    console.error(JSON.stringify({ error: "Failed to parse local session files" }));
+  let budgets: Awaited<ReturnType<typeof core.getBudgetStatus>>;
+  let enforcements: Awaited<ReturnType<typeof core.enforceBudgets>> = [];
+  try {
+    budgets = await core.getBudgetStatus();
+    if (options.enforce) {
+      enforcements = await core.enforceBudgets();
+    }
+  } catch (err) {
+    spinner?.stop();
+    const errorMsg = (err as Error).message || "Unknown error";
+    if (options.json) {
+      console.log(JSON.stringify({ error: errorMsg }, null, 2));
+    } else {
+      console.log(pc.red(`\n  Error checking budgets: ${errorMsg}\n`));
+    }
     process.exit(1);
   }
+  spinner?.stop();
 
-  const emptyMessages: ParsedMessages = { messages: [], opencodeCount: 0, claudeCount: 0, codexCount: 0, geminiCount: 0, ampCount: 0, droidCount: 0, processingTimeMs: 0 };
+  if (options.json) {
+    console.log(JSON.stringify(options.enforce ? { budgets, enforcements } : budgets, null, 2));
+    return;
+  }
+  if (budgets.length === 0) {
+    console.log(pc.gray(`\n  No budgets set. Add "budgets" to ${CONFIG_FILE}, e.g. [{ "period": "month", "amount": 500 }]\n`));
+    return;
+  }
 
-  if (reportType === "models") {
-    const report = await finalizeReportAsync({
-      localMessages: localMessages || emptyMessages,
-      includeCursor: includeCursor && cursorSync.synced,
-      since: dateFilters.since,
-      until: dateFilters.until,
-      year: dateFilters.year,
-    });
-    console.log(JSON.stringify(report, null, 2));
-  } else {
-    const report = await finalizeMonthlyReportAsync({
-      localMessages: localMessages || emptyMessages,
-      includeCursor: includeCursor && cursorSync.synced,
-      since: dateFilters.since,
-      until: dateFilters.until,
-      year: dateFilters.year,
-    });
-    console.log(JSON.stringify(report, null, 2));
+  const table = new ResponsiveTable({
+    head: ["Budget", "Period", "Spent", "Amount", "Projected", "Projected breach", "State"],
+    colAligns: ["left", "left", "right", "right", "right", "left", "left"],
+  });
+  for (const budget of budgets) {
+    const state =
+      budget.state === "exceeded" ? pc.red(budget.state) : budget.state === "projected" ? pc.yellow(budget.state) : pc.green(budget.state);
+    table.push([
+      budget.name,
+      `${budget.periodStart}${isPlain() ? " to " : " → "}${budget.periodEnd}`,
+      formatCurrency(budget.spent),
+      formatCurrency(budget.amount),
+      formatCurrency(budget.projectedCost),
+      budget.projectedBreach ? new Date(budget.projectedBreach).toLocaleString(undefined, { dateStyle: "short", timeStyle: "short" }) : pc.gray("-"),
+      state,
+    ]);
+  }
+  console.log(table.toString());
+  console.log(pc.gray("  Projections assume the burn rate of the last 24 hours holds until period end\n"));
+
+  if (options.enforce) {
+    if (enforcements.length === 0) {
+      console.log(pc.gray("  No caps to enforce (hooks run once per period, see ~/.config/tokscale/enforcements.jsonl)\n"));
+    }
+    for (const run of enforcements) {
+      if (run.error) {
+        console.log(pc.red(`  Enforcing "${run.budget}" failed: ${run.error}`));
+      } else {
+        console.log(pc.green(`  Enforced "${run.budget}": ${run.command}`));
+      }
+      if (run.output) {
+        console.log(pc.gray(run.output.split("\n").map((line) => `    ${line}`).join("\n")));
+      }
+    }
+    if (enforcements.length > 0) {
+      console.log();
+    }
   }
 }
 
-interface GraphCommandOptions extends FilterOptions, DateFilterOptions {
-  output?: string;
-  benchmark?: boolean;
+interface CheckCommandOptions extends FilterOptions, DateFilterOptions {
+  maxDaily?: string;
+  maxUnknownModelShare?: string;
+  json?: boolean;
   spinner?: boolean;
 }
 
-async function handleGraphCommand(options: GraphCommandOptions) {
-  const useSpinner = options.output && options.spinner !== false;
-  const spinner = useSpinner ? createSpinner({ color: "cyan" }) : null;
-
-  const dateFilters = getDateFilters(options);
-  const enabledSources = getEnabledSources(options);
-  const localSources: SourceType[] = (enabledSources || ['opencode', 'claude', 'codex', 'gemini', 'cursor', 'amp', 'droid'])
-    .filter(s => s !== 'cursor');
-  const includeCursor = !enabledSources || enabledSources.includes('cursor');
+/** Exit code when usage couldn't be checked, as opposed to failing a check */
+const CHECK_ERROR_EXIT = 2;
 
-  spinner?.start(pc.gray("Scanning session data..."));
+async function handleCheckCommand(options: CheckCommandOptions) {
+  const fail = (message: string): never => {
+    if (options.json) {
+      console.log(JSON.stringify({ error: message }, null, 2));
+    } else {
+      console.error(pc.red(`\n  ${message}\n`));
+    }
+    process.exit(CHECK_ERROR_EXIT);
+  };
 
-  const { cursorSync, localMessages } = await loadDataSourcesParallel(
-    localSources,
-    dateFilters,
-    (phase) => spinner?.update(phase)
-  );
-  
-  if (!localMessages) {
-    spinner?.error('Failed to parse local session files');
-    process.exit(1);
+  const maxDaily = options.maxDaily !== undefined ? Number(options.maxDaily) : undefined;
+  if (maxDaily !== undefined && !(maxDaily >= 0)) {
+    fail(`Invalid --max-daily: ${options.maxDaily}`);
   }
+  const maxUnknownModelShare = options.maxUnknownModelShare !== undefined ? Number(options.maxUnknownModelShare) : undefined;
+  if (maxUnknownModelShare !== undefined && !(maxUnknownModelShare >= 0 && maxUnknownModelShare <= 1)) {
+    fail(`Invalid --max-unknown-model-share: ${options.maxUnknownModelShare} (expected 0-1)`);
+  }
+  const requested = getDateFilters(options);
+  const dates = requested.since || requested.until || requested.year ? requested : getDateFilters({ today: true });
 
-  spinner?.update(pc.gray("Generating graph data..."));
-  const startTime = performance.now();
-
-  const data = await finalizeGraphAsync({
-    localMessages,
-    includeCursor: includeCursor && cursorSync.synced,
-    since: dateFilters.since,
-    until: dateFilters.until,
-    year: dateFilters.year,
-  });
+  const core = await loadCoreForSecrets();
+  const useSpinner = options.spinner !== false;
+  const spinner = useSpinner ? createSpinner({ color: "cyan" }) : null;
+  spinner?.start(pc.gray("Checking usage..."));
 
-  const processingTime = performance.now() - startTime;
+  let report: Awaited<ReturnType<typeof core.runChecks>>;
+  try {
+    report = await core.runChecks({ sources: getEnabledSources(options), ...dates, maxDaily, maxUnknownModelShare });
+  } catch (err) {
+    spinner?.stop();
+    return fail((err as Error).message || "Unknown error");
+  }
   spinner?.stop();
 
-  const jsonOutput = JSON.stringify(data, null, 2);
-
-  // Output to file or stdout
-  if (options.output) {
-    fs.writeFileSync(options.output, jsonOutput, "utf-8");
-    console.error(pc.green(`✓ Graph data written to ${options.output}`));
-    console.error(
-      pc.gray(
-        `  ${data.contributions.length} days, ${data.summary.sources.length} sources, ${data.summary.models.length} models`
-      )
-    );
-    console.error(pc.gray(`  Total: ${formatCurrency(data.summary.totalCost)}`));
-    if (options.benchmark) {
-      console.error(pc.gray(`  Processing time: ${processingTime.toFixed(0)}ms (Rust native)`));
-      if (cursorSync.attempted) {
-        if (cursorSync.synced) {
-          console.error(pc.gray(`  Cursor: ${cursorSync.rows} usage events synced (full lifetime data)`));
-        } else {
-          console.error(pc.yellow(`  Cursor: sync failed - ${cursorSync.error}`));
-        }
+  if (options.json) {
+    console.log(JSON.stringify(report, null, 2));
+  } else {
+    const percent = (share: number) => `${(share * 100).toFixed(1)}%`;
+    console.log();
+    for (const check of report.checks) {
+      const [actual, limit] =
+        check.name === "max-daily"
+          ? [`highest day ${formatCurrency(check.actual)}`, `limit ${formatCurrency(check.limit)}`]
+          : [`${percent(check.actual)} of messages without a list price`, `limit ${percent(check.limit)}`];
+      const mark = check.passed ? pc.green(isPlain() ? "ok  " : "✓ ") : pc.red(isPlain() ? "FAIL" : "✗ ");
+      console.log(`  ${mark} ${check.name}: ${actual} (${limit})`);
+      if (check.detail) {
+        console.log(pc.gray(`     ${check.detail}`));
       }
     }
-  } else {
-    console.log(jsonOutput);
+    console.log(pc.gray(`\n  ${formatNumber(report.messages)} messages checked\n`));
   }
+  if (!report.passed) process.exitCode = 1;
 }
 
-interface WrappedCommandOptions extends FilterOptions {
-  output?: string;
-  year?: string;
+interface DoctorCommandOptions {
+  json?: boolean;
+  providerStatus?: boolean;
   spinner?: boolean;
-  short?: boolean;
-  agents?: boolean;
-  clients?: boolean;
-  disablePinned?: boolean;
 }
 
-async function handleWrappedCommand(options: WrappedCommandOptions) {
+async function handleDoctorCommand(options: DoctorCommandOptions) {
+  const core = await loadCoreForSecrets();
   const useSpinner = options.spinner !== false;
   const spinner = useSpinner ? createSpinner({ color: "cyan" }) : null;
-  const currentYear = new Date().getFullYear().toString();
-  const year = options.year || currentYear;
-  spinner?.start(pc.gray(`Generating your ${year} Wrapped...`));
+  spinner?.start(pc.gray("Fetching pricing sources..."));
 
+  let health: Awaited<ReturnType<typeof core.checkPricingHealth>>;
   try {
-    const enabledSources = getEnabledSources(options);
-    const outputPath = await generateWrapped({
-      output: options.output,
-      year,
-      sources: enabledSources,
-      short: options.short,
-      includeAgents: !options.clients,
-      pinSisyphus: !options.disablePinned,
-    });
-
+    health = await core.checkPricingHealth(options.providerStatus ?? false);
+  } catch (err) {
     spinner?.stop();
-    console.log(pc.green(`\n  ✓ Your Tokscale Wrapped image is ready!`));
-    console.log(pc.white(`  ${outputPath}`));
-    console.log();
-    console.log(pc.gray("  Share it on Twitter/X with #TokscaleWrapped"));
-    console.log();
-  } catch (error) {
-    if (spinner) {
-      spinner.error(`Failed to generate wrapped: ${(error as Error).message}`);
+    const errorMsg = (err as Error).message || "Unknown error";
+    if (options.json) {
+      console.log(JSON.stringify({ error: errorMsg }, null, 2));
     } else {
-      console.error(pc.red(`Failed to generate wrapped: ${(error as Error).message}`));
+      console.log(pc.red(`\n  Error checking pricing: ${errorMsg}\n`));
     }
     process.exit(1);
   }
+  spinner?.stop();
+  const failed = health.sources.some((source) => source.required && !source.ok);
+
+  if (options.json) {
+    console.log(JSON.stringify(health, null, 2));
+  } else {
+    const sources = new ResponsiveTable({ head: ["Pricing source", "Result", "Models", "Time", "Cached copy"] });
+    for (const source of health.sources) {
+      const result = source.ok ? pc.green("ok") : source.required ? pc.red("failed") : pc.yellow("failed (optional)");
+      sources.push([
+        source.name,
+        result,
+        source.ok ? formatNumber(source.modelCount) : pc.gray("-"),
+        `${formatNumber(source.durationMs)} ms`,
+        source.cachedAt ? new Date(source.cachedAt).toLocaleString(undefined, { dateStyle: "short", timeStyle: "short" }) : pc.gray("none"),
+      ]);
+    }
+    console.log(sources.toString());
+    for (const source of health.sources.filter((s) => !s.ok)) {
+      console.log(pc.red(`  ${source.name}: ${source.error}`));
+      if (source.diagnosis) console.log(pc.gray(`    ${source.diagnosis}`));
+    }
+
+    if (health.providers.length > 0) {
+      const providers = new ResponsiveTable({ head: ["Provider", "Status", "Details"] });
+      for (const provider of health.providers) {
+        const state =
+          provider.state === "operational"
+            ? pc.green(provider.state)
+            : provider.state === "unreachable"
+              ? pc.gray(provider.state)
+              : provider.state === "outage"
+                ? pc.red(provider.state)
+                : pc.yellow(provider.state);
+        providers.push([provider.name, state, provider.description]);
+      }
+      console.log(providers.toString());
+    }
+    console.log();
+  }
+  if (failed) process.exit(1);
 }
 
-async function handlePricingCommand(modelId: string, options: { json?: boolean; provider?: string; spinner?: boolean }) {
+function formatQueryValue(value: unknown): string {
+  if (value === null || value === undefined) return "";
+  if (typeof value === "object") return JSON.stringify(value);
+  return String(value);
+}
+
+interface PricingCommandOptions {
+  json?: boolean;
+  provider?: string;
+  compare?: string;
+  inputRatio?: string;
+  spinner?: boolean;
+}
+
+async function handlePricingCommand(modelId: string, options: PricingCommandOptions) {
   const validProviders = ["litellm", "openrouter"];
   if (options.provider && !validProviders.includes(options.provider.toLowerCase())) {
     console.log(pc.red(`\n  Invalid provider: ${options.provider}`));
@@ -1020,6 +3880,11 @@ async function handlePricingCommand(modelId: string, options: { json?: boolean;
     process.exit(1);
   }
 
+  if (options.compare) {
+    await handlePricingComparison(core, modelId, options, spinner);
+    return;
+  }
+
   try {
     const provider = options.provider?.toLowerCase() || undefined;
     const nativeResult = await core.lookupPricing(modelId, provider);
@@ -1027,7 +3892,7 @@ async function handlePricingCommand(modelId: string, options: { json?: boolean;
 
     const result = {
       matchedKey: nativeResult.matchedKey,
-      source: nativeResult.source as "litellm" | "openrouter",
+      source: nativeResult.source,
       pricing: {
         input_cost_per_token: nativeResult.pricing.inputCostPerToken,
         output_cost_per_token: nativeResult.pricing.outputCostPerToken,
@@ -1049,23 +3914,29 @@ async function handlePricingCommand(modelId: string, options: { json?: boolean;
         },
       }, null, 2));
     } else {
-      const sourceLabel = result.source.toLowerCase() === "litellm" ? pc.blue("LiteLLM") : pc.magenta("OpenRouter");
-      const inputCost = result.pricing.input_cost_per_token ?? 0;
-      const outputCost = result.pricing.output_cost_per_token ?? 0;
-      const cacheReadCost = result.pricing.cache_read_input_token_cost;
-      const cacheWriteCost = result.pricing.cache_creation_input_token_cost;
+      const source = result.source.toLowerCase();
+      const sourceLabel =
+        source === "litellm" ? pc.blue("LiteLLM")
+        : source === "openrouter" ? pc.magenta("OpenRouter")
+        : source === "bundled" ? pc.yellow("Bundled list price")
+        : pc.cyan(result.source);
+      const rates = nativeResult.pricing;
+      const formatRate = (rate: number) => pc.green(core.formatPricePerMillion(rate));
 
       console.log(pc.cyan(`\n  Pricing for: ${pc.white(modelId)}`));
       console.log(pc.gray(`  Matched key: ${result.matchedKey}`));
       console.log(pc.gray(`  Source: `) + sourceLabel);
       console.log();
-      console.log(pc.white(`  Input:  `) + formatPricePerMillion(inputCost));
-      console.log(pc.white(`  Output: `) + formatPricePerMillion(outputCost));
-      if (cacheReadCost !== undefined) {
-        console.log(pc.white(`  Cache Read:  `) + formatPricePerMillion(cacheReadCost));
+      console.log(pc.white(`  Input:  `) + formatRate(rates.inputPerMillion));
+      console.log(pc.white(`  Output: `) + formatRate(rates.outputPerMillion));
+      if (rates.cacheReadPerMillion != null) {
+        console.log(pc.white(`  Cache Read:  `) + formatRate(rates.cacheReadPerMillion));
       }
-      if (cacheWriteCost !== undefined) {
-        console.log(pc.white(`  Cache Write: `) + formatPricePerMillion(cacheWriteCost));
+      if (rates.cacheWritePerMillion != null) {
+        console.log(pc.white(`  Cache Write: `) + formatRate(rates.cacheWritePerMillion));
+      }
+      if (rates.blendedPerMillion != null) {
+        console.log(pc.white(`  Blended (3:1): `) + formatRate(rates.blendedPerMillion));
       }
       console.log();
     }
@@ -1094,9 +3965,48 @@ async function handlePricingCommand(modelId: string, options: { json?: boolean;
   }
 }
 
-function formatPricePerMillion(costPerToken: number): string {
-  const perMillion = costPerToken * 1_000_000;
-  return pc.green(`$${perMillion.toFixed(2)}`) + pc.gray(" / 1M tokens");
+async function handlePricingComparison(
+  core: typeof import("@tokscale/core"),
+  modelId: string,
+  options: PricingCommandOptions,
+  spinner: ReturnType<typeof createSpinner> | null,
+) {
+  const inputRatio = options.inputRatio !== undefined ? Number(options.inputRatio) : undefined;
+  if (inputRatio !== undefined && !(inputRatio >= 0 && inputRatio <= 1)) {
+    spinner?.stop();
+    console.log(pc.red(`\n  Invalid --input-ratio: ${options.inputRatio} (expected a number from 0 to 1)\n`));
+    process.exit(1);
+  }
+
+  const others = options.compare!.split(",").map((m) => m.trim()).filter(Boolean);
+  try {
+    const comparison = await core.comparePricing([modelId, ...others], inputRatio);
+    spinner?.stop();
+
+    if (options.json) {
+      console.log(JSON.stringify({ inputRatio: inputRatio ?? 0.75, rows: comparison.rows, unpriced: comparison.unpriced }, null, 2));
+      return;
+    }
+
+    console.log(pc.cyan(`\n  Blended price per 1M tokens (${Math.round((inputRatio ?? 0.75) * 100)}% input)`));
+    console.log();
+    for (const line of comparison.table.split("\n").filter(Boolean)) {
+      console.log(`  ${line}`);
+    }
+    if (comparison.unpriced.length > 0) {
+      console.log(pc.gray(`\n  No pricing for: ${comparison.unpriced.join(", ")}`));
+    }
+    console.log();
+  } catch (err) {
+    spinner?.stop();
+    const errorMsg = (err as Error).message || "Unknown error";
+    if (options.json) {
+      console.log(JSON.stringify({ error: errorMsg }, null, 2));
+    } else {
+      console.log(pc.red(`\n  Error comparing pricing: ${errorMsg}\n`));
+    }
+    process.exit(1);
+  }
 }
 
 function getSourceLabel(source: string): string {
@@ -1115,6 +4025,12 @@ function getSourceLabel(source: string): string {
       return "Amp";
     case "droid":
       return "Droid";
+    case "cline":
+      return "Cline";
+    case "roocode":
+      return "Roo Code";
+    case "recorded":
+      return "Recorded";
     default:
       return source;
   }
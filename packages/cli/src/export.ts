@@ -0,0 +1,114 @@
+/**
+ * Report exports
+ *
+ * `tokscale graph` and the --json reports write the documents native core
+ * builds, tagged with the report schema version. The TUI's export action
+ * saves the view on screen through the same documents: the model report for
+ * the Overview and Models views, the contribution graph for Daily and Stats,
+ * and the session list for Sessions. CSV flattens each to one row per model,
+ * day or session; the models CSV also carries each row's share of the total
+ * and ends with the report's grand total row.
+ */
+
+import type { ModelReport, SessionEntry } from "./native.js";
+import { getReportSchemaVersion } from "./native.js";
+import type { TokenContributionData } from "./graph-types.js";
+
+export type ExportFormat = "json" | "csv";
+export const EXPORT_FORMATS: readonly ExportFormat[] = ["json", "csv"];
+
+/** What an export holds */
+export type ExportKind = "models" | "graph" | "sessions";
+
+export interface ExportData {
+  report: ModelReport;
+  graph: TokenContributionData;
+  sessions: SessionEntry[];
+}
+
+/**
+ * Tag a --json document with the report schema version it conforms to
+ */
+export function withSchemaVersion<T extends object>(report: T): T | ({ schemaVersion: string } & T) {
+  const schemaVersion = getReportSchemaVersion();
+  return schemaVersion ? { schemaVersion, ...report } : report;
+}
+
+function csvField(value: string | number | undefined): string {
+  const text = value === undefined ? "" : String(value);
+  return /[",\n\r]/.test(text) ? `"${text.replace(/"/g, '""')}"` : text;
+}
+
+function toCsv(header: string[], rows: Array<Array<string | number | undefined>>): string {
+  return [header, ...rows].map((row) => row.map(csvField).join(",")).join("\n") + "\n";
+}
+
+function modelsCsv(report: ModelReport): string {
+  return toCsv(
+    [
+      "source", "model", "provider", "input", "output", "cacheRead", "cacheWrite", "reasoning", "messages", "cost",
+      "costShare", "tokenShare",
+    ],
+    [
+      ...report.entries.map((e) => [
+        e.source, e.model, e.provider, e.input, e.output, e.cacheRead, e.cacheWrite, e.reasoning, e.messageCount, e.cost,
+        e.costShare, e.tokenShare,
+      ]),
+      [
+        "Total", "", "", report.totalInput, report.totalOutput, report.totalCacheRead, report.totalCacheWrite,
+        report.totalReasoning, report.totalMessages, report.totalCost,
+        report.totalCost > 0 ? 1 : 0, report.totalInput + report.totalOutput + report.totalCacheRead + report.totalCacheWrite > 0 ? 1 : 0,
+      ],
+    ]
+  );
+}
+
+function graphCsv(graph: TokenContributionData): string {
+  return toCsv(
+    ["date", "input", "output", "cacheRead", "cacheWrite", "reasoning", "tokens", "messages", "cost"],
+    graph.contributions.map((day) => [
+      day.date,
+      day.tokenBreakdown.input,
+      day.tokenBreakdown.output,
+      day.tokenBreakdown.cacheRead,
+      day.tokenBreakdown.cacheWrite,
+      day.tokenBreakdown.reasoning,
+      day.totals.tokens,
+      day.totals.messages,
+      day.totals.cost,
+    ])
+  );
+}
+
+function sessionsCsv(sessions: SessionEntry[]): string {
+  return toCsv(
+    ["source", "sessionId", "project", "models", "start", "end", "messages", "tokens", "cost"],
+    sessions.map((s) => [
+      s.source,
+      s.sessionId,
+      s.project,
+      s.models.join(" "),
+      new Date(s.start).toISOString(),
+      new Date(s.end).toISOString(),
+      s.messageCount,
+      s.tokens,
+      s.cost,
+    ])
+  );
+}
+
+/** The export document for `kind` in `format` */
+export function serializeExport(kind: ExportKind, format: ExportFormat, data: ExportData): string {
+  if (format === "csv") {
+    if (kind === "models") return modelsCsv(data.report);
+    if (kind === "graph") return graphCsv(data.graph);
+    return sessionsCsv(data.sessions);
+  }
+  const document = kind === "models" ? data.report : kind === "graph" ? data.graph : { sessions: data.sessions };
+  return JSON.stringify(withSchemaVersion(document), null, 2);
+}
+
+/** Default file name, e.g. tokscale-models-2025-06-01.csv */
+export function defaultExportPath(kind: ExportKind, format: ExportFormat, date = new Date()): string {
+  return `tokscale-${kind}-${date.toISOString().split("T")[0]}.${format}`;
+}
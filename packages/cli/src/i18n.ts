@@ -0,0 +1,160 @@
+/**
+ * Report localization
+ *
+ * Message catalogs for report strings. en-US is the source catalog; every
+ * other catalog must have the same keys (checked by the compiler). A key
+ * missing at runtime falls back to en-US, then to the key itself.
+ *
+ * The locale comes from, in order: setLocale() (the --locale flag),
+ * TOKSCALE_LOCALE, `locale` in settings.json, then LC_ALL / LC_MESSAGES /
+ * LANG. Unsupported locales fall back to the closest language, then en-US.
+ */
+
+import { loadSettings } from "./tui/config/settings.js";
+
+const enUS = {
+  "report.models.title": "Token Usage Report by Model",
+  "report.monthly.title": "Monthly Token Usage Report",
+  "report.titleWithRange": "{title} ({range})",
+  "report.noData": "No usage data found.",
+  "report.summary": "Total: {messages} messages, {tokens} tokens, {cost}",
+  "report.totalCost": "Total Cost: {cost}",
+  "report.covered": "Covered by free tiers and credits: {cost}",
+  "column.sourceModel": "Source/Model",
+  "column.providerFamily": "Provider/Family",
+  "column.month": "Month",
+  "column.models": "Models",
+  "column.input": "Input",
+  "column.output": "Output",
+  "column.cacheWrite": "Cache Write",
+  "column.cacheRead": "Cache Read",
+  "column.total": "Total",
+  "column.cost": "Cost",
+  "column.share": "% Cost",
+  "row.total": "Total",
+  "range.today": "Today",
+  "range.last7Days": "Last 7 days",
+  "range.from": "from {date}",
+  "range.to": "to {date}",
+  "spinner.scanning": "Scanning session data...",
+  "spinner.finalizing": "Finalizing report...",
+  "summary.sources": "Sources: {sources}",
+  "summary.sourceFiles": "{source} {parsed}/{files} files",
+  "summary.deduped": "Duplicates dropped: {count} messages",
+  "summary.unknownModels": "No list price for: {models} ({count} messages)",
+  "summary.pricing": "Prices fetched {age} ago",
+  "summary.pricingStale": "Prices fetched {age} ago and may be out of date",
+  "summary.pricingArchived": "Prices from the archive",
+  "summary.costShare": "Cost: {exact} billed by providers, {estimated} estimated from token counts",
+  "summary.pricingUnavailable": "Prices unavailable ({error}); costs are only those sources reported",
+  "summary.missingSources": "No session files for: {sources}",
+  "summary.skippedFiles": "Skipped {count} files without readable usage",
+};
+
+export type MessageKey = keyof typeof enUS;
+type Catalog = Record<MessageKey, string>;
+
+const ptBR: Catalog = {
+  "report.models.title": "Relatório de Uso de Tokens por Modelo",
+  "report.monthly.title": "Relatório Mensal de Uso de Tokens",
+  "report.titleWithRange": "{title} ({range})",
+  "report.noData": "Nenhum dado de uso encontrado.",
+  "report.summary": "Total: {messages} mensagens, {tokens} tokens, {cost}",
+  "report.totalCost": "Custo Total: {cost}",
+  "report.covered": "Coberto por níveis gratuitos e créditos: {cost}",
+  "column.sourceModel": "Origem/Modelo",
+  "column.providerFamily": "Provedor/Família",
+  "column.month": "Mês",
+  "column.models": "Modelos",
+  "column.input": "Entrada",
+  "column.output": "Saída",
+  "column.cacheWrite": "Escrita em Cache",
+  "column.cacheRead": "Leitura de Cache",
+  "column.total": "Total",
+  "column.cost": "Custo",
+  "column.share": "% Custo",
+  "row.total": "Total",
+  "range.today": "Hoje",
+  "range.last7Days": "Últimos 7 dias",
+  "range.from": "de {date}",
+  "range.to": "até {date}",
+  "spinner.scanning": "Lendo dados das sessões...",
+  "spinner.finalizing": "Finalizando relatório...",
+  "summary.sources": "Origens: {sources}",
+  "summary.sourceFiles": "{source} {parsed}/{files} arquivos",
+  "summary.deduped": "Duplicatas descartadas: {count} mensagens",
+  "summary.unknownModels": "Sem preço de tabela: {models} ({count} mensagens)",
+  "summary.pricing": "Preços obtidos há {age}",
+  "summary.pricingStale": "Preços obtidos há {age}, podem estar desatualizados",
+  "summary.pricingArchived": "Preços do arquivo histórico",
+  "summary.costShare": "Custo: {exact} cobrado pelos provedores, {estimated} estimado a partir dos tokens",
+  "summary.pricingUnavailable": "Preços indisponíveis ({error}); custos são apenas os informados pelas origens",
+  "summary.missingSources": "Nenhum arquivo de sessão para: {sources}",
+  "summary.skippedFiles": "{count} arquivos ignorados sem uso legível",
+};
+
+const CATALOGS: Record<string, Catalog> = {
+  "en-US": enUS,
+  "pt-BR": ptBR,
+};
+
+export const DEFAULT_LOCALE = "en-US";
+export const SUPPORTED_LOCALES = Object.keys(CATALOGS);
+
+let currentLocale: string | null = null;
+
+/**
+ * Closest supported locale for a tag such as "pt_BR.UTF-8", "pt-br" or "pt",
+ * or null when the language isn't supported
+ */
+export function resolveLocale(tag: string | undefined): string | null {
+  if (!tag) return null;
+  const normalized = tag.split(".")[0].split("@")[0].replace("_", "-");
+  if (!normalized || normalized === "C" || normalized === "POSIX") return null;
+
+  const exact = SUPPORTED_LOCALES.find((l) => l.toLowerCase() === normalized.toLowerCase());
+  if (exact) return exact;
+  const language = normalized.split("-")[0].toLowerCase();
+  return SUPPORTED_LOCALES.find((l) => l.split("-")[0].toLowerCase() === language) ?? null;
+}
+
+function detectLocale(): string {
+  const candidates = [
+    process.env.TOKSCALE_LOCALE,
+    loadSettings().locale,
+    process.env.LC_ALL,
+    process.env.LC_MESSAGES,
+    process.env.LANG,
+  ];
+  for (const candidate of candidates) {
+    const locale = resolveLocale(candidate);
+    if (locale) return locale;
+  }
+  return DEFAULT_LOCALE;
+}
+
+/** Locale used for report strings and number formatting */
+export function getLocale(): string {
+  if (!currentLocale) currentLocale = detectLocale();
+  return currentLocale;
+}
+
+/**
+ * Use `tag` for the rest of the process. Returns false, leaving the locale
+ * unchanged, when the tag's language has no catalog.
+ */
+export function setLocale(tag: string): boolean {
+  const locale = resolveLocale(tag);
+  if (!locale) return false;
+  currentLocale = locale;
+  return true;
+}
+
+/** Localized message for `key`, with `{name}` placeholders filled from `params` */
+export function t(key: MessageKey, params?: Record<string, string | number>): string {
+  const template = CATALOGS[getLocale()]?.[key] ?? enUS[key] ?? key;
+  if (!params) return template;
+  return template.replace(/\{(\w+)\}/g, (match, name: string) =>
+    name in params ? String(params[name]) : match
+  );
+}
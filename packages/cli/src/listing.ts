@@ -0,0 +1,91 @@
+/**
+ * Long table listings
+ *
+ * Table reports take `--limit` and `--offset` to show a window of rows, and
+ * `--columns` to pick columns by key (e.g. `--columns month,models,cost`), so
+ * years of data stay readable. Totals rows are kept whatever the window.
+ *
+ * When stdout is a terminal and a table is taller than it, the table goes
+ * through $PAGER (default `less`, with LESS=FRX unless LESS is set, as git
+ * does). `--no-pager`, or PAGER set to "" or "cat", prints it directly.
+ */
+
+import { spawnSync } from "node:child_process";
+import pc from "picocolors";
+import type { ResponsiveTable } from "./table.js";
+
+export interface ListingOptions {
+  limit?: string;
+  offset?: string;
+  columns?: string;
+  pager?: boolean;
+}
+
+export interface Listing {
+  limit?: number;
+  offset: number;
+  columns?: string[];
+  pager: boolean;
+}
+
+function exitWith(message: string): never {
+  console.error(pc.red(`\n  ${message}\n`));
+  process.exit(1);
+}
+
+/** Validate listing options, exiting with an error on bad values */
+export function parseListing(options: ListingOptions, defaultLimit?: number): Listing {
+  const limit = options.limit !== undefined ? Number(options.limit) : defaultLimit;
+  if (limit !== undefined && !(Number.isInteger(limit) && limit > 0)) {
+    exitWith(`Invalid --limit: ${options.limit}`);
+  }
+  const offset = options.offset !== undefined ? Number(options.offset) : 0;
+  if (!(Number.isInteger(offset) && offset >= 0)) {
+    exitWith(`Invalid --offset: ${options.offset}`);
+  }
+  const columns = options.columns
+    ?.split(",")
+    .map((key) => key.trim().toLowerCase())
+    .filter((key) => key !== "");
+  if (columns !== undefined && columns.length === 0) {
+    exitWith(`Invalid --columns: ${options.columns}`);
+  }
+  return { limit, offset, columns, pager: options.pager !== false };
+}
+
+/** Print a table with the listing applied, through the pager if it doesn't fit */
+export function printTable(table: ResponsiveTable, listing: Listing): void {
+  try {
+    table.select(listing);
+  } catch (err) {
+    exitWith((err as Error).message);
+  }
+
+  let text = table.toString();
+  const shown = Math.max(0, Math.min(table.rowCount - listing.offset, listing.limit ?? Infinity));
+  if (shown < table.rowCount) {
+    const range = shown > 0 ? `${listing.offset + 1}-${listing.offset + shown}` : "none";
+    text += "\n" + pc.gray(`  Showing ${range} of ${table.rowCount} rows (use --limit and --offset for more)`);
+  }
+  page(text, listing.pager);
+}
+
+function page(text: string, enabled: boolean): void {
+  const pager = process.env.PAGER ?? "less";
+  const height = process.stdout.rows;
+  if (!enabled || !process.stdout.isTTY || !height || pager === "" || pager === "cat" || text.split("\n").length < height) {
+    console.log(text);
+    return;
+  }
+
+  const result = spawnSync(pager, {
+    input: text + "\n",
+    stdio: ["pipe", "inherit", "inherit"],
+    shell: true,
+    env: { LESS: "FRX", ...process.env },
+  });
+  // 127: the shell couldn't find the pager
+  if (result.error || result.status === 127) {
+    console.log(text);
+  }
+}
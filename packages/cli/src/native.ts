@@ -5,6 +5,7 @@
  * Native module is REQUIRED - no TypeScript fallback.
  */
 
+import { isVerbose } from "./verbose.js";
 import type {
   TokenContributionData,
   GraphOptions as TSGraphOptions,
@@ -99,6 +100,12 @@ interface NativeModelUsage {
   reasoning: number;
   messageCount: number;
   cost: number;
+  /** USD covered by free tiers and promotional credits */
+  covered: number;
+  /** Fraction of the report's cost, 0 to 1 */
+  costShare: number;
+  /** Fraction of the report's tokens, 0 to 1 */
+  tokenShare: number;
 }
 
 interface NativeModelReport {
@@ -107,9 +114,13 @@ interface NativeModelReport {
   totalOutput: number;
   totalCacheRead: number;
   totalCacheWrite: number;
+  totalReasoning: number;
   totalMessages: number;
   totalCost: number;
+  totalCovered: number;
   processingTimeMs: number;
+  summary?: RunSummary;
+  families?: ModelFamily[];
 }
 
 interface NativeMonthlyUsage {
@@ -121,10 +132,19 @@ interface NativeMonthlyUsage {
   cacheWrite: number;
   messageCount: number;
   cost: number;
+  /** Fraction of the report's cost, 0 to 1 */
+  costShare: number;
+  /** Fraction of the report's tokens, 0 to 1 */
+  tokenShare: number;
 }
 
 interface NativeMonthlyReport {
   entries: NativeMonthlyUsage[];
+  totalInput: number;
+  totalOutput: number;
+  totalCacheRead: number;
+  totalCacheWrite: number;
+  totalMessages: number;
   totalCost: number;
   processingTimeMs: number;
 }
@@ -143,6 +163,9 @@ interface NativeParsedMessage {
   reasoning: number;
   sessionId: string;
   agent?: string;
+  estimatedTimestamp?: boolean;
+  cwd?: string;
+  billedCost?: number;
 }
 
 interface NativeParsedMessages {
@@ -153,7 +176,12 @@ interface NativeParsedMessages {
   geminiCount: number;
   ampCount: number;
   droidCount?: number;
+  clineCount?: number;
+  roocodeCount?: number;
+  recordedCount?: number;
   processingTimeMs: number;
+  files?: SourceFiles[];
+  deduped?: number;
 }
 
 interface NativeLocalParseOptions {
@@ -162,6 +190,7 @@ interface NativeLocalParseOptions {
   since?: string;
   until?: string;
   year?: string;
+  excludeEstimatedTimestamps?: boolean;
 }
 
 interface NativeFinalizeReportOptions {
@@ -171,6 +200,21 @@ interface NativeFinalizeReportOptions {
   since?: string;
   until?: string;
   year?: string;
+  recentGranularity?: string;
+  recentDays?: number;
+  asOf?: string;
+  model?: string;
+  families?: boolean;
+}
+
+export interface NativeCapabilities {
+  version: string;
+  features: string[];
+  sources: string[];
+  pricingProviders: string[];
+  exportFormats: string[];
+  reportKinds: string[];
+  reportSchemaVersion: string;
 }
 
 interface NativeCore {
@@ -180,6 +224,14 @@ interface NativeCore {
   finalizeReport(options: NativeFinalizeReportOptions): NativeModelReport;
   finalizeMonthlyReport(options: NativeFinalizeReportOptions): NativeMonthlyReport;
   finalizeGraph(options: NativeFinalizeReportOptions): NativeGraphResult;
+  formatCost?(cost: number): string;
+  getReportSchema?(kind: string): unknown;
+  reportSchemaVersion?(): string;
+  capabilities?(): NativeCapabilities;
+  encryptForTeam?(data: Buffer, recipients: string[]): Buffer;
+  preloadPricing?(): Promise<void>;
+  scanSources?(options: { sources?: string[] }): Array<{ source: string; path: string; exists: boolean }>;
+  getDataVersion?(options: { sources?: string[] }): string;
 }
 
 // =============================================================================
@@ -217,6 +269,64 @@ export function getNativeVersion(): string | null {
   return nativeCore?.version() ?? null;
 }
 
+/**
+ * Format a cost with the user's rounding/precision settings, or null when the
+ * native module (or an older build without formatCost) is unavailable
+ */
+export function formatCostNative(cost: number): string | null {
+  return nativeCore?.formatCost?.(cost) ?? null;
+}
+
+/**
+ * What the installed native module supports, or null when it is unavailable
+ * (or an older build without capabilities)
+ */
+export function getNativeCapabilities(): NativeCapabilities | null {
+  return nativeCore?.capabilities?.() ?? null;
+}
+
+/**
+ * Version of the `--json` report schema, or null when the native module (or an
+ * older build without report schemas) is unavailable
+ */
+export function getReportSchemaVersion(): string | null {
+  return nativeCore?.reportSchemaVersion?.() ?? null;
+}
+
+/**
+ * JSON Schema for a `--json` report ("models", "monthly" or "graph"), or null
+ * when the native module is unavailable
+ */
+export function getReportSchema(kind: string): unknown | null {
+  return nativeCore?.getReportSchema?.(kind) ?? null;
+}
+
+/**
+ * Encrypt data to team public keys ("age1..."), or null when the native module
+ * (or an older build without team encryption) is unavailable
+ */
+export function encryptForTeam(data: Buffer, recipients: string[]): Buffer | null {
+  return nativeCore?.encryptForTeam?.(data, recipients) ?? null;
+}
+
+/**
+ * Session directories of `sources` that exist, or an empty list when the
+ * native module (or an older build) is unavailable
+ */
+export function getSourceRoots(sources?: string[]): string[] {
+  const scanned = nativeCore?.scanSources?.({ sources }) ?? [];
+  return scanned.filter((s) => s.exists).map((s) => s.path);
+}
+
+/**
+ * Version of the session files of `sources`; changes when any is added,
+ * removed or written. Null when the native module (or an older build) is
+ * unavailable.
+ */
+export function getDataVersion(sources?: string[]): string | null {
+  return nativeCore?.getDataVersion?.({ sources }) ?? null;
+}
+
 /**
  * Convert native result to TypeScript format
  */
@@ -297,6 +407,12 @@ export interface ModelUsage {
   reasoning: number;
   messageCount: number;
   cost: number;
+  /** USD covered by free tiers and promotional credits */
+  covered: number;
+  /** Fraction of the report's cost, 0 to 1 */
+  costShare: number;
+  /** Fraction of the report's tokens, 0 to 1 */
+  tokenShare: number;
 }
 
 export interface ModelReport {
@@ -305,9 +421,33 @@ export interface ModelReport {
   totalOutput: number;
   totalCacheRead: number;
   totalCacheWrite: number;
+  totalReasoning: number;
   totalMessages: number;
   totalCost: number;
+  totalCovered: number;
   processingTimeMs: number;
+  summary?: RunSummary;
+  families?: ModelFamily[];
+}
+
+/** Usage of one model family, with its model versions to drill down into */
+export interface ModelFamily {
+  provider: string;
+  family: string;
+  /** Entries of the family's models, most expensive first */
+  models: ModelUsage[];
+  input: number;
+  output: number;
+  cacheRead: number;
+  cacheWrite: number;
+  reasoning: number;
+  messageCount: number;
+  cost: number;
+  covered: number;
+  /** Fraction of the report's cost, 0 to 1 */
+  costShare: number;
+  /** Fraction of the report's tokens, 0 to 1 */
+  tokenShare: number;
 }
 
 export interface MonthlyUsage {
@@ -319,12 +459,96 @@ export interface MonthlyUsage {
   cacheWrite: number;
   messageCount: number;
   cost: number;
+  /** Fraction of the report's cost, 0 to 1 */
+  costShare: number;
+  /** Fraction of the report's tokens, 0 to 1 */
+  tokenShare: number;
 }
 
 export interface MonthlyReport {
   entries: MonthlyUsage[];
+  totalInput: number;
+  totalOutput: number;
+  totalCacheRead: number;
+  totalCacheWrite: number;
+  totalMessages: number;
   totalCost: number;
   processingTimeMs: number;
+  summary?: RunSummary;
+}
+
+/** Session files of one source */
+export interface SourceFiles {
+  source: string;
+  files: number;
+  /** Files that yielded messages */
+  parsed: number;
+  /** Files that yielded none: empty, unreadable or without usage */
+  skipped: number;
+  /** Asked for by name rather than scanned by default */
+  named?: boolean;
+}
+
+/** What a report is missing; see the "Run Summary" section of the README */
+export interface Degradation {
+  degraded: boolean;
+  /** "fresh", "stale", "archived" or "unavailable" */
+  pricing: string;
+  pricingError?: string;
+  missingSources: string[];
+  skippedFiles: number;
+}
+
+/** What went into a report, so its numbers can be judged */
+export interface RunSummary {
+  sources: SourceFiles[];
+  messages: number;
+  messagesDeduped: number;
+  /** Models without a list price */
+  unknownModels: string[];
+  unknownModelMessages: number;
+  /** Seconds since the epoch; absent for archived prices (--as-of) */
+  pricingFetchedAt?: number;
+  pricingAgeHours?: number;
+  pricingStale: boolean;
+  /** USD billed by providers */
+  exactCost: number;
+  /** USD estimated from token counts and list prices */
+  estimatedCost: number;
+  /** 0-1 */
+  exactCostShare: number;
+  degradation?: Degradation;
+}
+
+export interface RecentBucket {
+  start: number;
+  tokens: number;
+  messageCount: number;
+  cost: number;
+}
+
+export interface RecentUsage {
+  granularity: string;
+  bucketMinutes: number;
+  days: number;
+  buckets: RecentBucket[];
+  costPerHour: number;
+  tokensPerHour: number;
+  processingTimeMs: number;
+}
+
+export interface SessionEntry {
+  source: string;
+  sessionId: string;
+  project?: string;
+  models: string[];
+  start: number;
+  end: number;
+  messageCount: number;
+  tokens: number;
+  cost: number;
+  /** Cost in equal slices of the session's span, oldest first */
+  timeline: number[];
 }
 
 // =============================================================================
@@ -345,6 +569,8 @@ export interface ParsedMessages {
     reasoning: number;
     sessionId: string;
     agent?: string;
+    cwd?: string;
+    billedCost?: number;
   }>;
   opencodeCount: number;
   claudeCount: number;
@@ -352,7 +578,12 @@ export interface ParsedMessages {
   geminiCount: number;
   ampCount: number;
   droidCount: number;
+  clineCount: number;
+  roocodeCount: number;
+  recordedCount: number;
   processingTimeMs: number;
+  files?: SourceFiles[];
+  deduped?: number;
 }
 
 export interface LocalParseOptions {
@@ -368,6 +599,14 @@ export interface FinalizeOptions {
   since?: string;
   until?: string;
   year?: string;
+  /** "hour" or "15m"; only used by finalizeReportAndGraphAsync */
+  recentGranularity?: string;
+  /** Prices and data as of this date; finalizeReportAsync and finalizeMonthlyReportAsync */
+  asOf?: string;
+  /** Comma-separated model globs; only used by finalizeReportAndGraphAsync */
+  model?: string;
+  /** Roll entries up by model family; only used by finalizeReportAsync */
+  families?: boolean;
 }
 
 
@@ -540,6 +779,11 @@ async function runInSubprocess<T>(method: string, args: unknown[]): Promise<T> {
       throw new Error(`Subprocess '${method}' failed: ${errorMsg}`);
     }
 
+    // The core's diagnostics go to stderr, which is otherwise only shown on failure
+    if (isVerbose() && stderr) {
+      process.stderr.write(stderr);
+    }
+
     try {
       return JSON.parse(stdout) as T;
     } catch (e) {
@@ -568,6 +812,16 @@ export async function parseLocalSourcesAsync(options: LocalParseOptions): Promis
   return runInSubprocess<ParsedMessages>("parseLocalSources", [nativeOptions]);
 }
 
+/**
+ * Fill the pricing cache while sessions are parsed, so the finalize step doesn't
+ * wait on the network. Failures are left for the finalize step to report.
+ */
+export async function preloadPricing(): Promise<void> {
+  try {
+    await nativeCore?.preloadPricing?.();
+  } catch {}
+}
+
 export async function finalizeReportAsync(options: FinalizeOptions): Promise<ModelReport> {
   if (!isNativeAvailable()) {
     throw new Error("Native module required. Run: bun run build:core");
@@ -580,6 +834,8 @@ export async function finalizeReportAsync(options: FinalizeOptions): Promise<Mod
     since: options.since,
     until: options.until,
     year: options.year,
+    asOf: options.asOf,
+    families: options.families,
   };
 
   return runInSubprocess<ModelReport>("finalizeReport", [nativeOptions]);
@@ -597,6 +853,7 @@ export async function finalizeMonthlyReportAsync(options: FinalizeOptions): Prom
     since: options.since,
     until: options.until,
     year: options.year,
+    asOf: options.asOf,
   };
 
   return runInSubprocess<MonthlyReport>("finalizeMonthlyReport", [nativeOptions]);
@@ -623,11 +880,15 @@ export async function finalizeGraphAsync(options: FinalizeOptions): Promise<Toke
 export interface ReportAndGraph {
   report: ModelReport;
   graph: TokenContributionData;
+  recent: RecentUsage;
+  sessions: SessionEntry[];
 }
 
 interface NativeReportAndGraph {
   report: NativeModelReport;
   graph: NativeGraphResult;
+  recent: RecentUsage;
+  sessions: SessionEntry[];
 }
 
 export async function finalizeReportAndGraphAsync(options: FinalizeOptions): Promise<ReportAndGraph> {
@@ -642,11 +903,15 @@ export async function finalizeReportAndGraphAsync(options: FinalizeOptions): Pro
     since: options.since,
     until: options.until,
     year: options.year,
+    recentGranularity: options.recentGranularity,
+    model: options.model,
   };
 
   const result = await runInSubprocess<NativeReportAndGraph>("finalizeReportAndGraph", [nativeOptions]);
   return {
     report: result.report,
     graph: fromNativeResult(result.graph),
+    recent: result.recent,
+    sessions: result.sessions,
   };
 }
@@ -0,0 +1,29 @@
+/**
+ * Plain output mode
+ *
+ * `--plain` (or TOKSCALE_PLAIN=1) renders output for screen readers and
+ * basic terminals: no color, no box-drawing table borders, no spinners, and
+ * reports print as text tables instead of launching the TUI. Columns stay
+ * aligned with spaces so each row reads as one line.
+ *
+ * The flag is accepted anywhere on the command line and removed from argv
+ * here, so subcommands don't each have to declare it. This module must be
+ * imported before picocolors, which reads NO_COLOR when it loads.
+ */
+
+const PLAIN_FLAG = "--plain";
+
+function envFlag(value: string | undefined): boolean {
+  return value !== undefined && value !== "" && value !== "0" && value.toLowerCase() !== "false";
+}
+
+const plain = process.argv.includes(PLAIN_FLAG) || envFlag(process.env.TOKSCALE_PLAIN);
+
+if (plain) {
+  process.env.NO_COLOR = "1";
+  process.argv = process.argv.filter((arg) => arg !== PLAIN_FLAG);
+}
+
+export function isPlain(): boolean {
+  return plain;
+}
@@ -0,0 +1,159 @@
+/**
+ * Ad-hoc SQL over usage data (tokscale query)
+ *
+ * Backed by DuckDB, which is not a dependency: power users install
+ * `@duckdb/node-api` to enable it. Messages come from the native core as an
+ * Arrow IPC stream and are loaded through DuckDB's `arrow` community
+ * extension, so no JSON export is needed.
+ *
+ * The extension is a native binary per DuckDB version and platform, so it
+ * isn't shipped with tokscale. The first query installs it into DuckDB's
+ * extension directory (~/.duckdb/extensions); later queries load it from
+ * there without the network. Offline machines can point
+ * TOKSCALE_DUCKDB_ARROW_EXTENSION at a downloaded `arrow.duckdb_extension`.
+ *
+ * Available relations:
+ *   messages - table, one row per message (source, modelId, providerId, sessionId,
+ *              timestamp, date, input, output, cacheRead, cacheWrite,
+ *              reasoning, cost, agent, training, mode)
+ *   daily    - view, messages grouped by date, source, modelId and providerId
+ */
+
+import * as fs from "node:fs";
+import * as os from "node:os";
+import * as path from "node:path";
+
+export interface QueryOptions {
+  sources?: string[];
+  since?: string;
+  until?: string;
+  year?: string;
+}
+
+export interface QueryResult {
+  columns: string[];
+  rows: Record<string, unknown>[];
+}
+
+const DUCKDB_MODULE = "@duckdb/node-api";
+/** An `arrow.duckdb_extension` file to load instead of DuckDB's installed one */
+const ARROW_EXTENSION_ENV = "TOKSCALE_DUCKDB_ARROW_EXTENSION";
+
+/** The slice of the @duckdb/node-api surface we use */
+interface DuckDBConnection {
+  run(sql: string): Promise<unknown>;
+  runAndReadAll(sql: string): Promise<{
+    columnNames(): string[];
+    getRowObjectsJson(): Record<string, unknown>[];
+  }>;
+}
+
+interface DuckDBModule {
+  DuckDBInstance: {
+    create(path: string): Promise<{ connect(): Promise<DuckDBConnection> }>;
+  };
+}
+
+export class DuckDBUnavailableError extends Error {
+  constructor(cause: string) {
+    super(`DuckDB is not installed (${cause}). Install it with: bun add @duckdb/node-api`);
+    this.name = "DuckDBUnavailableError";
+  }
+}
+
+export class ArrowExtensionUnavailableError extends Error {
+  constructor(cause: string) {
+    super(
+      `DuckDB's arrow extension is not installed and could not be installed (${cause}). ` +
+        `It is downloaded once, by the first query; run the query again while online, ` +
+        `or set ${ARROW_EXTENSION_ENV} to a downloaded arrow.duckdb_extension file.`
+    );
+    this.name = "ArrowExtensionUnavailableError";
+  }
+}
+
+const DAILY_VIEW = `
+CREATE VIEW daily AS
+SELECT date, source, modelId, providerId,
+       sum(input) AS input, sum(output) AS output,
+       sum(cacheRead) AS cacheRead, sum(cacheWrite) AS cacheWrite,
+       sum(reasoning) AS reasoning, sum(cost) AS cost, count(*) AS messages
+FROM messages
+GROUP BY ALL
+`;
+
+function sqlString(value: string): string {
+  return `'${value.replace(/'/g, "''")}'`;
+}
+
+function errorMessage(err: unknown): string {
+  return (err as Error)?.message || String(err);
+}
+
+/**
+ * Load the arrow extension: the configured file, else the installed
+ * extension, installing it (over the network) only when that fails
+ */
+async function loadArrowExtension(connection: DuckDBConnection): Promise<void> {
+  const file = process.env[ARROW_EXTENSION_ENV];
+  if (file) {
+    try {
+      await connection.run(`LOAD ${sqlString(file)}`);
+      return;
+    } catch (err) {
+      throw new ArrowExtensionUnavailableError(`loading ${file}: ${errorMessage(err)}`);
+    }
+  }
+  try {
+    await connection.run("LOAD arrow");
+    return;
+  } catch {
+    // Not installed yet
+  }
+  try {
+    await connection.run("INSTALL arrow FROM community");
+    await connection.run("LOAD arrow");
+  } catch (err) {
+    throw new ArrowExtensionUnavailableError(errorMessage(err));
+  }
+}
+
+export async function runQuery(sql: string, options: QueryOptions = {}): Promise<QueryResult> {
+  let duckdb: DuckDBModule;
+  try {
+    duckdb = (await import(DUCKDB_MODULE)) as DuckDBModule;
+  } catch (err) {
+    throw new DuckDBUnavailableError((err as Error).message || "import failed");
+  }
+
+  const mod = await import("@tokscale/core");
+  const core = (mod.default ?? mod) as typeof import("@tokscale/core");
+  const ipc = await core.getMessagesArrow({
+    sources: options.sources,
+    since: options.since,
+    until: options.until,
+    year: options.year,
+  });
+
+  const dir = fs.mkdtempSync(path.join(os.tmpdir(), "tokscale-query-"));
+  const arrowPath = path.join(dir, "messages.arrows");
+  try {
+    fs.writeFileSync(arrowPath, ipc);
+
+    const instance = await duckdb.DuckDBInstance.create(":memory:");
+    const connection = await instance.connect();
+    await loadArrowExtension(connection);
+    await connection.run(
+      `CREATE TABLE messages AS SELECT * FROM read_arrow(${sqlString(arrowPath)})`
+    );
+    await connection.run(DAILY_VIEW);
+
+    const reader = await connection.runAndReadAll(sql);
+    return {
+      columns: reader.columnNames(),
+      rows: reader.getRowObjectsJson(),
+    };
+  } finally {
+    fs.rmSync(dir, { recursive: true, force: true });
+  }
+}
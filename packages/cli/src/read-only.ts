@@ -0,0 +1,66 @@
+/**
+ * Read-only analysis of another home directory
+ *
+ * `--home <dir>` points every report at someone else's session data, such as
+ * a backup or a colleague's copied ~/.claude and ~/.local/share/opencode,
+ * instead of your own. Nothing is written on that data's behalf: the native
+ * core skips the parse cache, Cursor isn't synced (the directory's own
+ * cursor-cache is read), and commands that record, import, submit or log in
+ * refuse to run, as does `budgets --enforce`, which runs hooks and logs them
+ * to the analyzed home.
+ *
+ * Like --plain, the flag is accepted anywhere and removed from argv here. It
+ * is passed to the native core as TOKSCALE_HOME, which report subprocesses
+ * inherit.
+ */
+
+import { existsSync, statSync } from "node:fs";
+import { resolve } from "node:path";
+
+const HOME_FLAG = "--home";
+
+/** Commands that write to the home directory, your account or the network */
+export const WRITE_COMMANDS = ["submit", "push", "record", "import", "proxy", "serve", "login", "logout", "cursor"];
+
+/** Why `command` (top-level name) can't run on a read-only home, if it can't */
+export function readOnlyRefusal(command: string, options: { enforce?: boolean } = {}): string | undefined {
+  if (WRITE_COMMANDS.includes(command)) {
+    return `'tokscale ${command}' isn't available with --home (read-only analysis)`;
+  }
+  if (command === "budgets" && options.enforce) {
+    return "'tokscale budgets --enforce' isn't available with --home (read-only analysis); run 'tokscale budgets' to see spend";
+  }
+  return undefined;
+}
+
+function takeHomeFlag(): string | undefined {
+  const args = process.argv;
+  for (let i = 0; i < args.length; i++) {
+    if (args[i] === HOME_FLAG && i + 1 < args.length) {
+      const value = args[i + 1];
+      process.argv = [...args.slice(0, i), ...args.slice(i + 2)];
+      return value;
+    }
+    if (args[i].startsWith(`${HOME_FLAG}=`)) {
+      process.argv = [...args.slice(0, i), ...args.slice(i + 1)];
+      return args[i].slice(HOME_FLAG.length + 1);
+    }
+  }
+  return undefined;
+}
+
+const flagged = takeHomeFlag();
+const home = flagged ? resolve(flagged) : process.env.TOKSCALE_HOME || undefined;
+
+if (home) {
+  if (!existsSync(home) || !statSync(home).isDirectory()) {
+    console.error(`tokscale: --home ${home} is not a directory`);
+    process.exit(1);
+  }
+  process.env.TOKSCALE_HOME = home;
+}
+
+/** Home directory being analyzed read-only, if any */
+export function readOnlyHome(): string | undefined {
+  return home;
+}
@@ -0,0 +1,79 @@
+/**
+ * Run summary footer
+ *
+ * `models` and `monthly` end with what went into the report: sources and
+ * files scanned, duplicates dropped, models without a list price, how old
+ * the prices are and how much of the cost was billed rather than estimated.
+ * Gaps (prices unavailable, named sources without files, unreadable files)
+ * print in yellow.
+ * The core computes it; `--json` output carries it as `summary`.
+ */
+
+import pc from "picocolors";
+import { t } from "./i18n.js";
+import type { RunSummary } from "./native.js";
+import { formatNumber } from "./table.js";
+
+/** Unpriced models listed by name before the rest are only counted */
+const MAX_MODELS_LISTED = 5;
+
+function formatAge(hours: number): string {
+  if (hours < 1) return `${Math.max(1, Math.round(hours * 60))} min`;
+  if (hours < 48) return `${Math.round(hours)} h`;
+  return `${Math.round(hours / 24)} d`;
+}
+
+function formatShare(share: number): string {
+  return `${(share * 100).toFixed(share > 0 && share < 0.01 ? 1 : 0)}%`;
+}
+
+export function printRunSummary(summary: RunSummary | undefined): void {
+  if (!summary) return;
+  const lines: string[] = [];
+
+  const sources = summary.sources
+    .filter((s) => s.files > 0)
+    .map((s) => t("summary.sourceFiles", { source: s.source, parsed: formatNumber(s.parsed), files: formatNumber(s.files) }));
+  if (sources.length > 0) {
+    lines.push(t("summary.sources", { sources: sources.join(", ") }));
+  }
+  if (summary.messagesDeduped > 0) {
+    lines.push(t("summary.deduped", { count: formatNumber(summary.messagesDeduped) }));
+  }
+  if (summary.unknownModels.length > 0) {
+    const listed = summary.unknownModels.slice(0, MAX_MODELS_LISTED);
+    const more = summary.unknownModels.length - listed.length;
+    lines.push(
+      t("summary.unknownModels", {
+        models: listed.join(", ") + (more > 0 ? ` +${more}` : ""),
+        count: formatNumber(summary.unknownModelMessages),
+      })
+    );
+  }
+  const degradation = summary.degradation;
+  if (degradation?.pricing === "unavailable") {
+    lines.push(pc.yellow(t("summary.pricingUnavailable", { error: degradation.pricingError ?? "" })));
+  } else if (summary.pricingAgeHours === undefined) {
+    lines.push(t("summary.pricingArchived"));
+  } else {
+    const age = formatAge(summary.pricingAgeHours);
+    lines.push(summary.pricingStale ? pc.yellow(t("summary.pricingStale", { age })) : t("summary.pricing", { age }));
+  }
+  if (degradation && degradation.missingSources.length > 0) {
+    lines.push(pc.yellow(t("summary.missingSources", { sources: degradation.missingSources.join(", ") })));
+  }
+  if (degradation && degradation.skippedFiles > 0) {
+    lines.push(pc.yellow(t("summary.skippedFiles", { count: formatNumber(degradation.skippedFiles) })));
+  }
+  lines.push(
+    t("summary.costShare", {
+      exact: formatShare(summary.exactCostShare),
+      estimated: formatShare(summary.exactCost + summary.estimatedCost > 0 ? 1 - summary.exactCostShare : 0),
+    })
+  );
+
+  console.log();
+  for (const line of lines) {
+    console.log(pc.gray(`  ${line}`));
+  }
+}
@@ -0,0 +1,146 @@
+/**
+ * Raw settings.json access for `tokscale config`
+ *
+ * Works on the whole file rather than the TUI's validated subset, so keys
+ * only the native core reads (costDisplay, apiKeys, ...) survive edits. Key
+ * order is kept and new keys are appended; values are addressed by dotted
+ * paths, with numeric segments indexing arrays (`providerPrefixes.0`).
+ */
+
+import { existsSync, mkdirSync, readFileSync, renameSync, rmSync, writeFileSync } from "fs";
+import { dirname } from "path";
+import { CONFIG_FILE } from "./tui/config/settings.js";
+
+export type SettingsObject = Record<string, unknown>;
+
+/** Parsed settings file; empty when it doesn't exist. Throws on malformed JSON. */
+export function readSettingsFile(path = CONFIG_FILE): SettingsObject {
+  if (!existsSync(path)) return {};
+  const raw: unknown = JSON.parse(readFileSync(path, "utf-8"));
+  if (!raw || typeof raw !== "object" || Array.isArray(raw)) {
+    throw new Error(`${path} must contain a JSON object`);
+  }
+  return raw as SettingsObject;
+}
+
+/** Serialized form written by writeSettingsFile */
+export function serializeSettings(settings: SettingsObject): string {
+  return `${JSON.stringify(settings, null, 2)}\n`;
+}
+
+/**
+ * Replace the settings file with `content`. The file is written next to the
+ * original and renamed over it, so readers never see a partial write.
+ * `check` runs on the written temp file and can throw to abort.
+ */
+export function writeSettingsFile(content: string, check?: (tempPath: string) => void, path = CONFIG_FILE): void {
+  mkdirSync(dirname(path), { recursive: true });
+  const tempPath = `${path}.${process.pid}.tmp`;
+  writeFileSync(tempPath, content);
+  try {
+    check?.(tempPath);
+    renameSync(tempPath, path);
+  } catch (error) {
+    rmSync(tempPath, { force: true });
+    throw error;
+  }
+}
+
+function splitKey(key: string): string[] {
+  const segments = key.split(".");
+  if (segments.some((segment) => segment === "")) {
+    throw new Error(`Invalid key "${key}"`);
+  }
+  return segments;
+}
+
+function child(container: unknown, segment: string): unknown {
+  if (Array.isArray(container)) {
+    return /^\d+$/.test(segment) ? container[Number(segment)] : undefined;
+  }
+  if (container && typeof container === "object") {
+    return Object.prototype.hasOwnProperty.call(container, segment)
+      ? (container as SettingsObject)[segment]
+      : undefined;
+  }
+  return undefined;
+}
+
+/** Value at a dotted key, or undefined when unset */
+export function getSetting(settings: SettingsObject, key: string): unknown {
+  return splitKey(key).reduce<unknown>((value, segment) => child(value, segment), settings);
+}
+
+/**
+ * Set a dotted key, creating intermediate objects. Throws when a segment
+ * would have to descend into a string, number or boolean.
+ */
+export function setSetting(settings: SettingsObject, key: string, value: unknown): void {
+  const segments = splitKey(key);
+  let container: unknown = settings;
+  segments.forEach((segment, index) => {
+    const last = index === segments.length - 1;
+    const path = segments.slice(0, index + 1).join(".");
+
+    if (Array.isArray(container)) {
+      if (!/^\d+$/.test(segment) || Number(segment) > container.length) {
+        throw new Error(`${path}: array index must be between 0 and ${container.length}`);
+      }
+    }
+    const target = container as SettingsObject;
+    if (last) {
+      target[segment] = value;
+      return;
+    }
+
+    let next = child(container, segment);
+    if (next === undefined || next === null) {
+      next = {};
+      target[segment] = next;
+    } else if (typeof next !== "object") {
+      throw new Error(`${path} is ${JSON.stringify(next)}, not an object`);
+    }
+    container = next;
+  });
+}
+
+/** Remove a dotted key; returns false when it wasn't set */
+export function unsetSetting(settings: SettingsObject, key: string): boolean {
+  const segments = splitKey(key);
+  const parent = segments.length === 1 ? settings : getSetting(settings, segments.slice(0, -1).join("."));
+  const last = segments[segments.length - 1];
+  if (child(parent, last) === undefined) return false;
+  if (Array.isArray(parent)) {
+    parent.splice(Number(last), 1);
+  } else {
+    delete (parent as SettingsObject)[last];
+  }
+  return true;
+}
+
+/**
+ * Value given on the command line: JSON when it parses (numbers, booleans,
+ * null, arrays, objects, quoted strings), otherwise the text itself
+ */
+export function parseSettingValue(raw: string): unknown {
+  try {
+    return JSON.parse(raw);
+  } catch {
+    return raw;
+  }
+}
+
+/** Leaf values as [dotted key, value] pairs, in file order */
+export function flattenSettings(value: unknown, prefix = ""): Array<[string, unknown]> {
+  const isContainer = value !== null && typeof value === "object";
+  const entries = isContainer ? Object.entries(value as SettingsObject) : [];
+  if (!isContainer || entries.length === 0) {
+    return prefix ? [[prefix, value]] : [];
+  }
+  return entries.flatMap(([key, child]) => flattenSettings(child, prefix ? `${prefix}.${key}` : key));
+}
+
+/** Display form of a value: strings bare, everything else as JSON */
+export function formatSettingValue(value: unknown): string {
+  return typeof value === "string" ? value : JSON.stringify(value);
+}
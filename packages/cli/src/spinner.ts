@@ -9,6 +9,8 @@
  * - Same characters as OpenCode: ■ (active) / ⬝ (inactive)
  */
 
+import { isPlain } from "./plain.js";
+
 // =============================================================================
 // ANSI Color Helpers
 // =============================================================================
@@ -189,6 +191,9 @@ export class Spinner {
     this.message = message;
     this.frameIndex = 0;
 
+    // No animation or cursor control in plain mode; only the outcome is printed
+    if (isPlain()) return;
+
     // Hide cursor
     process.stdout.write(HIDE_CURSOR);
 
@@ -219,7 +224,7 @@ export class Spinner {
    */
   success(message: string): void {
     this.stop();
-    console.log(`  \x1b[32m✓\x1b[0m ${message}`);
+    console.log(isPlain() ? `  ${message}` : `  \x1b[32m✓\x1b[0m ${message}`);
   }
 
   /**
@@ -227,7 +232,7 @@ export class Spinner {
    */
   error(message: string): void {
     this.stop();
-    console.log(`  \x1b[31m✗\x1b[0m ${message}`);
+    console.log(isPlain() ? `  Error: ${message}` : `  \x1b[31m✗\x1b[0m ${message}`);
   }
 
   /**
@@ -238,6 +243,7 @@ export class Spinner {
       clearInterval(this.intervalId);
       this.intervalId = null;
     }
+    if (isPlain()) return;
     process.stdout.write(CLEAR_LINE);
     process.stdout.write(SHOW_CURSOR);
   }
@@ -28,6 +28,7 @@ interface SubmitOptions {
   until?: string;
   year?: string;
   dryRun?: boolean;
+  rollup?: boolean;
 }
 
 interface SubmitResponse {
@@ -51,6 +52,58 @@ interface SubmitResponse {
 
 type SourceType = "opencode" | "claude" | "codex" | "gemini" | "cursor" | "amp" | "droid";
 
+/**
+ * [date, source, modelId, input, output, cacheRead, cacheWrite, reasoning, messages, cost]
+ * Layout shared with the server (packages/frontend/src/lib/validation/rollup.ts)
+ */
+type RollupRow = [string, string, string, number, number, number, number, number, number, number];
+
+interface RollupSubmission {
+  format: "rollup";
+  version: 1;
+  meta: { generatedAt: string; version: string };
+  sources: string[];
+  rows: RollupRow[];
+}
+
+/**
+ * Pre-aggregated daily rollup: one row per (day, source, model). Totals,
+ * summaries and year ranges are left out; the server recomputes them.
+ */
+function toRollup(data: TokenContributionData): RollupSubmission {
+  const rows: RollupRow[] = [];
+  for (const day of data.contributions) {
+    for (const s of day.sources) {
+      rows.push([
+        day.date,
+        s.source,
+        s.modelId,
+        s.tokens.input,
+        s.tokens.output,
+        s.tokens.cacheRead,
+        s.tokens.cacheWrite,
+        s.tokens.reasoning,
+        s.messages,
+        s.cost,
+      ]);
+    }
+  }
+
+  return {
+    format: "rollup",
+    version: 1,
+    meta: { generatedAt: data.meta.generatedAt, version: data.meta.version },
+    sources: data.summary.sources,
+    rows,
+  };
+}
+
+function formatPayloadSize(bytes: number): string {
+  return bytes >= 1024 * 1024
+    ? `${(bytes / (1024 * 1024)).toFixed(1)} MB`
+    : `${(bytes / 1024).toFixed(1)} KB`;
+}
+
 async function checkGhCliExists(): Promise<boolean> {
   try {
     await execAsync("gh --version");
@@ -215,12 +268,8 @@ export async function submit(options: SubmitOptions = {}): Promise<void> {
     // Two-phase processing (same as TUI) for consistency:
     // Phase 1: Parse local sources + sync cursor in parallel
     const [localMessages, cursorSync] = await Promise.all([
-      parseLocalSourcesAsync({
-        sources: localSources,
-        since: options.since,
-        until: options.until,
-        year: options.year,
-      }),
+      // Unfiltered, so free tiers are used up by earlier usage too
+      parseLocalSourcesAsync({ sources: localSources }),
       includeCursor && loadCursorCredentials()
         ? syncCursorCache()
         : Promise.resolve({ synced: false, rows: 0 }),
@@ -252,6 +301,10 @@ export async function submit(options: SubmitOptions = {}): Promise<void> {
   console.log(pc.gray(`    Total cost: ${formatCurrency(data.summary.totalCost)}`));
   console.log(pc.gray(`    Sources: ${data.summary.sources.join(", ")}`));
   console.log(pc.gray(`    Models: ${data.summary.models.length} models`));
+
+  const body = JSON.stringify(options.rollup ? toRollup(data) : data);
+  const payloadKind = options.rollup ? "daily rollup" : "full graph";
+  console.log(pc.gray(`    Payload: ${formatPayloadSize(Buffer.byteLength(body))} (${payloadKind})`));
   console.log();
 
   if (data.summary.totalTokens === 0) {
@@ -277,7 +330,7 @@ export async function submit(options: SubmitOptions = {}): Promise<void> {
         "Content-Type": "application/json",
         Authorization: `Bearer ${credentials.token}`,
       },
-      body: JSON.stringify(data),
+      body,
     });
 
     const result: SubmitResponse = await response.json();
@@ -3,14 +3,19 @@
  */
 
 import Table from "cli-table3";
-import pc from "picocolors";
 import stringWidth from "string-width";
+import { formatCostNative } from "./native.js";
+import { getLocale, t } from "./i18n.js";
+import { isPlain } from "./plain.js";
+import { paintCost, paintRole } from "./theme.js";
 
 export type TableCellAlign = "left" | "right" | "center";
 export type TableRow = (string | number | { content: string; hAlign?: TableCellAlign })[];
 
 export interface TableOptions {
   head: string[];
+  /** Stable column names for `--columns`; derived from `head` when omitted */
+  keys?: string[];
   colAligns?: TableCellAlign[];
   style?: { head?: string[] };
   compactHead?: string[];
@@ -18,9 +23,26 @@ export interface TableOptions {
   compactThreshold?: number;
 }
 
+/** Rows and columns to show, from `--offset`, `--limit` and `--columns` */
+export interface TableSelection {
+  offset?: number;
+  limit?: number;
+  columns?: string[];
+}
+
+export function columnKey(header: string): string {
+  return header
+    .toLowerCase()
+    .replace(/[^a-z0-9]+/g, "-")
+    .replace(/^-|-$/g, "");
+}
+
 export class ResponsiveTable {
   private head: string[];
+  private keys: string[];
   private rows: TableRow[] = [];
+  private footer: TableRow[] = [];
+  private selection: TableSelection = {};
   private colAligns: TableCellAlign[];
   private style?: { head?: string[] };
   private compactHead?: string[];
@@ -30,6 +52,7 @@ export class ResponsiveTable {
 
   constructor(options: TableOptions) {
     this.head = options.head;
+    this.keys = options.keys ?? options.head.map(columnKey);
     this.colAligns = options.colAligns ?? Array.from({ length: this.head.length }, () => "left");
     this.style = options.style;
     this.compactHead = options.compactHead;
@@ -41,8 +64,36 @@ export class ResponsiveTable {
     this.rows.push(row);
   }
 
-  private filterRowToCompact(row: TableRow, compactIndices: number[]): TableRow {
-    return compactIndices.map((index) => row[index] ?? "");
+  /** Add a row, such as totals, that `--offset` and `--limit` don't cut */
+  pushFooter(row: TableRow): void {
+    this.footer.push(row);
+  }
+
+  get rowCount(): number {
+    return this.rows.length;
+  }
+
+  get columnKeys(): string[] {
+    return this.keys;
+  }
+
+  /** Restrict output to a window of rows and a subset of columns. Throws on unknown column keys. */
+  select(selection: TableSelection): void {
+    const unknown = selection.columns?.filter((key) => !this.keys.includes(key)) ?? [];
+    if (unknown.length > 0) {
+      throw new Error(`Unknown column${unknown.length > 1 ? "s" : ""} ${unknown.join(", ")} (available: ${this.keys.join(", ")})`);
+    }
+    this.selection = selection;
+  }
+
+  private selectedRows(): TableRow[] {
+    const offset = this.selection.offset ?? 0;
+    const end = this.selection.limit !== undefined ? offset + this.selection.limit : undefined;
+    return [...this.rows.slice(offset, end), ...this.footer];
+  }
+
+  private pickColumns(row: TableRow, indices: number[]): TableRow {
+    return indices.map((index) => row[index] ?? "");
   }
 
   private getCurrentTableConfig(): { head: string[]; colAligns: TableCellAlign[] } {
@@ -66,14 +117,24 @@ export class ResponsiveTable {
     const terminalWidth =
       Number.parseInt(process.env.COLUMNS ?? "", 10) || process.stdout.columns || 120;
 
-    this.compactMode = terminalWidth < this.compactThreshold && this.compactHead != null;
+    const columns = this.selection.columns;
+    this.compactMode = !columns && terminalWidth < this.compactThreshold && this.compactHead != null;
+
+    let { head, colAligns } = this.getCurrentTableConfig();
+    const indices = columns ? columns.map((key) => this.keys.indexOf(key)) : this.getCompactIndices();
+    if (columns) {
+      head = indices.map((index) => this.head[index]);
+      colAligns = indices.map((index) => this.colAligns[index]);
+    }
 
-    const { head, colAligns } = this.getCurrentTableConfig();
-    const compactIndices = this.getCompactIndices();
+    const rows = this.selectedRows();
+    const processedRows = this.compactMode || columns
+      ? rows.map((row) => this.pickColumns(row, indices))
+      : rows;
 
-    const processedRows = this.compactMode
-      ? this.rows.map((row) => this.filterRowToCompact(row, compactIndices))
-      : this.rows;
+    if (isPlain()) {
+      return renderPlain(head, processedRows, colAligns);
+    }
 
     const allRows = [
       head.map(String),
@@ -125,9 +186,10 @@ export class ResponsiveTable {
       });
     }
 
+    // Header color comes from the theme rather than cli-table3's color names
     const table = new Table({
-      head,
-      style: this.style,
+      head: this.style?.head?.length ? head.map((h) => paintRole("header", h)) : head,
+      style: { ...this.style, head: [] },
       colAligns,
       colWidths: finalWidths,
       wordWrap: true,
@@ -142,12 +204,46 @@ export class ResponsiveTable {
   }
 }
 
+function cellText(cell: TableRow[number]): string {
+  if (typeof cell === "object" && cell != null && "content" in cell) {
+    return String(cell.content);
+  }
+  return String(cell ?? "");
+}
+
+/**
+ * Columns padded with spaces, no borders. Line breaks inside a cell become
+ * ", " so every row stays on one line for screen readers.
+ */
+function renderPlain(head: string[], rows: TableRow[], colAligns: TableCellAlign[]): string {
+  const lines = [head, ...rows.map((row) => head.map((_, i) => cellText(row[i]).split("\n").join(", ")))];
+  const widths = head.map((_, i) => Math.max(...lines.map((line) => stringWidth(String(line[i])))));
+
+  return lines
+    .map((line) =>
+      line
+        .map((cell, i) => {
+          const text = String(cell);
+          const padding = " ".repeat(widths[i] - stringWidth(text));
+          return colAligns[i] === "right" ? padding + text : text + padding;
+        })
+        .join("  ")
+        .trimEnd()
+    )
+    .join("\n");
+}
+
 export function formatNumber(num: number): string {
-  return num.toLocaleString("en-US");
+  return num.toLocaleString(getLocale());
 }
 
 export function formatCurrency(amount: number): string {
-  return `$${amount.toFixed(2)}`;
+  return formatCostNative(amount) ?? `$${amount.toFixed(2)}`;
+}
+
+/** A row's cost, colored by the theme's cost thresholds */
+export function formatCostCell(amount: number): string {
+  return paintCost(amount, formatCurrency(amount));
 }
 
 export function formatModelName(modelName: string): string {
@@ -167,29 +263,37 @@ export function formatModelName(modelName: string): string {
 
 export function formatModelsMultiline(models: string[]): string {
   const unique = [...new Set(models.map(formatModelName))];
+  if (isPlain()) return unique.sort().join(", ");
   return unique.sort().map((m) => `- ${m}`).join("\n");
 }
 
-export function createUsageTable(firstColumnName: string): ResponsiveTable {
+export function createUsageTable(firstColumnName: string, firstColumnKey: string): ResponsiveTable {
   return new ResponsiveTable({
+    keys: [firstColumnKey, "models", "input", "output", "cache-write", "cache-read", "total", "cost", "share"],
     head: [
       firstColumnName,
-      "Models",
-      "Input",
-      "Output",
-      "Cache Write",
-      "Cache Read",
-      "Total",
-      "Cost",
+      t("column.models"),
+      t("column.input"),
+      t("column.output"),
+      t("column.cacheWrite"),
+      t("column.cacheRead"),
+      t("column.total"),
+      t("column.cost"),
+      t("column.share"),
     ],
     style: { head: ["cyan"] },
-    colAligns: ["left", "left", "right", "right", "right", "right", "right", "right"],
-    compactHead: [firstColumnName, "Models", "Input", "Output", "Cost"],
+    colAligns: ["left", "left", "right", "right", "right", "right", "right", "right", "right"],
+    compactHead: [firstColumnName, t("column.models"), t("column.input"), t("column.output"), t("column.cost")],
     compactColAligns: ["left", "left", "right", "right", "right"],
     compactThreshold: 100,
   });
 }
 
+/** A share of the report total, 0 to 1, as a percentage */
+export function formatShare(share: number): string {
+  return `${(share * 100).toLocaleString(getLocale(), { minimumFractionDigits: 1, maximumFractionDigits: 1 })}%`;
+}
+
 export function formatUsageRow(
   firstCol: string,
   models: string[],
@@ -197,7 +301,8 @@ export function formatUsageRow(
   output: number,
   cacheWrite: number,
   cacheRead: number,
-  cost: number
+  cost: number,
+  costShare: number
 ): TableRow {
   const total = input + output + cacheWrite + cacheRead;
   return [
@@ -208,7 +313,8 @@ export function formatUsageRow(
     formatNumber(cacheWrite),
     formatNumber(cacheRead),
     formatNumber(total),
-    formatCurrency(cost),
+    formatCostCell(cost),
+    formatShare(costShare),
   ];
 }
 
@@ -221,13 +327,14 @@ export function formatTotalsRow(
 ): TableRow {
   const total = input + output + cacheWrite + cacheRead;
   return [
-    pc.yellow("Total"),
+    paintRole("total", t("row.total")),
     "",
-    pc.yellow(formatNumber(input)),
-    pc.yellow(formatNumber(output)),
-    pc.yellow(formatNumber(cacheWrite)),
-    pc.yellow(formatNumber(cacheRead)),
-    pc.yellow(formatNumber(total)),
-    pc.yellow(formatCurrency(cost)),
+    paintRole("total", formatNumber(input)),
+    paintRole("total", formatNumber(output)),
+    paintRole("total", formatNumber(cacheWrite)),
+    paintRole("total", formatNumber(cacheRead)),
+    paintRole("total", formatNumber(total)),
+    paintRole("total", formatCurrency(cost)),
+    paintRole("total", formatShare(cost > 0 ? 1 : 0)),
   ];
 }
@@ -0,0 +1,164 @@
+/**
+ * Color themes
+ *
+ * CLI tables and the TUI take their colors from one theme: a built-in
+ * `dark` (default), `light` or `high-contrast`, or one of the user's own
+ * under `themes` in settings.json, picked with `theme`. A user theme sets
+ * only the roles it changes; the rest come from the theme it `extends`
+ * (default `dark`). Colors are hex (`#22c55e`) or basic names (`green`).
+ *
+ * Costs are colored by level: low below `costThresholds.warn`, medium from
+ * `warn`, high from `high`. Thresholds are in USD and apply per row (a
+ * model, a month, a session), not to totals.
+ */
+
+import pc from "picocolors";
+import { loadSettings } from "./tui/config/settings.js";
+
+export type ThemeRole = "header" | "total" | "muted" | "selection" | "costLow" | "costMedium" | "costHigh";
+
+export type ThemeColors = Record<ThemeRole, string>;
+
+export type BuiltinThemeName = "dark" | "light" | "high-contrast";
+
+/** A user theme from settings.json */
+export interface CustomTheme extends Partial<ThemeColors> {
+  extends?: BuiltinThemeName;
+}
+
+export interface CostThresholds {
+  warn: number;
+  high: number;
+}
+
+export const DEFAULT_THEME: BuiltinThemeName = "dark";
+export const DEFAULT_COST_THRESHOLDS: CostThresholds = { warn: 10, high: 100 };
+
+export const BUILTIN_THEMES: Record<BuiltinThemeName, ThemeColors> = {
+  dark: {
+    header: "cyan",
+    total: "yellow",
+    muted: "gray",
+    selection: "blue",
+    costLow: "green",
+    costMedium: "yellow",
+    costHigh: "red",
+  },
+  light: {
+    header: "blue",
+    total: "magenta",
+    muted: "#6b7280",
+    selection: "#2563eb",
+    costLow: "#15803d",
+    costMedium: "#b45309",
+    costHigh: "#b91c1c",
+  },
+  "high-contrast": {
+    header: "white",
+    total: "#ffff00",
+    muted: "white",
+    selection: "magenta",
+    costLow: "#00ff00",
+    costMedium: "#ffff00",
+    costHigh: "#ff0000",
+  },
+};
+
+const NAMED_COLORS = {
+  black: pc.black,
+  red: pc.red,
+  green: pc.green,
+  yellow: pc.yellow,
+  blue: pc.blue,
+  magenta: pc.magenta,
+  cyan: pc.cyan,
+  white: pc.white,
+  gray: pc.gray,
+} as const;
+
+const HEX_RE = /^#([0-9a-f]{6})$/i;
+
+export function isValidColor(color: string): boolean {
+  return color in NAMED_COLORS || HEX_RE.test(color);
+}
+
+export function isBuiltinTheme(name: string): name is BuiltinThemeName {
+  return name in BUILTIN_THEMES;
+}
+
+export interface Theme {
+  name: string;
+  colors: ThemeColors;
+  costThresholds: CostThresholds;
+}
+
+let cached: Theme | null = null;
+
+/**
+ * The theme chosen in settings. An unknown name, or invalid colors in a
+ * user theme, fall back to the built-in colors.
+ */
+export function getTheme(): Theme {
+  if (cached) return cached;
+  const settings = loadSettings();
+  const name = settings.theme ?? DEFAULT_THEME;
+  const custom = settings.themes?.[name];
+
+  let colors: ThemeColors;
+  if (isBuiltinTheme(name)) {
+    colors = BUILTIN_THEMES[name];
+  } else if (custom) {
+    const base = custom.extends && isBuiltinTheme(custom.extends) ? custom.extends : DEFAULT_THEME;
+    colors = { ...BUILTIN_THEMES[base] };
+    for (const role of Object.keys(colors) as ThemeRole[]) {
+      const color = custom[role];
+      if (typeof color === "string" && isValidColor(color)) colors[role] = color;
+    }
+  } else {
+    colors = BUILTIN_THEMES[DEFAULT_THEME];
+  }
+
+  cached = { name, colors, costThresholds: settings.costThresholds ?? DEFAULT_COST_THRESHOLDS };
+  return cached;
+}
+
+export type CostLevel = "low" | "medium" | "high";
+
+export function costLevel(cost: number, thresholds = getTheme().costThresholds): CostLevel {
+  if (cost >= thresholds.high) return "high";
+  if (cost >= thresholds.warn) return "medium";
+  return "low";
+}
+
+/** Theme color for a cost, for the TUI */
+export function costColor(cost: number): string {
+  const { colors } = getTheme();
+  const level = costLevel(cost);
+  return level === "high" ? colors.costHigh : level === "medium" ? colors.costMedium : colors.costLow;
+}
+
+/** Theme color for a role, for the TUI */
+export function themeColor(role: ThemeRole): string {
+  return getTheme().colors[role];
+}
+
+/** `text` in `color` for terminal output; plain when colors are off */
+export function paint(color: string, text: string): string {
+  if (!pc.isColorSupported) return text;
+  const named = NAMED_COLORS[color as keyof typeof NAMED_COLORS];
+  if (named) return named(text);
+  const hex = HEX_RE.exec(color)?.[1];
+  if (!hex) return text;
+  const [r, g, b] = [0, 2, 4].map((i) => Number.parseInt(hex.slice(i, i + 2), 16));
+  return `\x1b[38;2;${r};${g};${b}m${text}\x1b[39m`;
+}
+
+/** `text` in the theme color for `role` */
+export function paintRole(role: ThemeRole, text: string): string {
+  return paint(themeColor(role), text);
+}
+
+/** `text` in the color for `cost`'s level */
+export function paintCost(cost: number, text: string): string {
+  return paint(costColor(cost), text);
+}
@@ -1,17 +1,39 @@
-import { createEffect, createSignal, Switch, Match, onCleanup } from "solid-js";
+import { createEffect, createSignal, on, Show, Switch, Match, onCleanup } from "solid-js";
 import { useKeyboard, useTerminalDimensions, useRenderer } from "@opentui/solid";
+import type { KeyEvent } from "@opentui/core";
 import clipboardy from "clipboardy";
 import { Header } from "./components/Header.js";
 import { Footer } from "./components/Footer.js";
 import { ModelView } from "./components/ModelView.js";
 import { DailyView } from "./components/DailyView.js";
+import { SessionsView, filterSessions } from "./components/SessionsView.js";
 import { StatsView } from "./components/StatsView.js";
 import { OverviewView } from "./components/OverviewView.js";
 import { LoadingSpinner } from "./components/LoadingSpinner.js";
-import { useData, type DateFilters } from "./hooks/useData.js";
+import { LiveBar, LIVE_BAR_HEIGHT } from "./components/LiveBar.js";
+import {
+  FilterBar,
+  FILTER_FIELDS,
+  describeFilters,
+  draftFromFilters,
+  filterBarHeight,
+  isFiltered,
+  filtersFromDraft,
+  type FilterDraft,
+} from "./components/FilterBar.js";
+import {
+  ExportBar,
+  EXPORT_BAR_HEIGHT,
+  cycleExportFormat,
+  newExportDraft,
+  type ExportDraft,
+} from "./components/ExportBar.js";
+import { serializeExport } from "../export.js";
+import { watchSessionData } from "../watch.js";
+import { useData, loadExportData, type DateFilters } from "./hooks/useData.js";
 import type { ColorPaletteName } from "./config/themes.js";
 import { DEFAULT_PALETTE, getPaletteNames } from "./config/themes.js";
-import { loadSettings, saveSettings, getCacheTimestamp } from "./config/settings.js";
+import { loadSettings, saveSettings, getCacheTimestamp, loadLastFilter, saveLastFilter } from "./config/settings.js";
 import { TABS, ALL_SOURCES, type TUIOptions, type TabType, type SortType, type SourceType } from "./types/index.js";
 
 export type AppProps = TUIOptions;
@@ -35,9 +57,13 @@ export function App(props: AppProps) {
   const rows = () => terminalDimensions().height;
 
   const settings = loadSettings();
+  // Without filters on the command line, pick up where this profile left off
+  const lastFilter =
+    props.enabledSources || props.since || props.until || props.year ? null : loadLastFilter();
+  const lastSources = lastFilter?.sources?.filter((s): s is SourceType => ALL_SOURCES.includes(s as SourceType));
   const [activeTab, setActiveTab] = createSignal<TabType>(props.initialTab ?? "overview");
   const [enabledSources, setEnabledSources] = createSignal<Set<SourceType>>(
-    new Set(props.enabledSources ?? ALL_SOURCES)
+    new Set(props.enabledSources ?? (lastSources?.length ? lastSources : ALL_SOURCES))
   );
   const [sortBy, setSortBy] = createSignal<SortType>(props.sortBy ?? "tokens");
   const [sortDesc, setSortDesc] = createSignal(props.sortDesc ?? true);
@@ -47,22 +73,53 @@ export function App(props: AppProps) {
     props.colorPalette ?? (settings.colorPalette as ColorPaletteName) ?? DEFAULT_PALETTE
   );
 
-  const dateFilters: DateFilters = {
-    since: props.since,
-    until: props.until,
+  const [dateFilters, setDateFilters] = createSignal<DateFilters>({
+    since: props.since ?? lastFilter?.since,
+    until: props.until ?? lastFilter?.until,
     year: props.year,
-  };
+    model: lastFilter?.model,
+  });
+  // Filter editor; while open, keys go to the draft
+  const [filterDraft, setFilterDraft] = createSignal<FilterDraft | null>(null);
+  // Export dialog; while open, keys go to it
+  const [exportDraft, setExportDraft] = createSignal<ExportDraft | null>(null);
+
+  const { data, loading, error, refresh, loadingPhase, isRefreshing } = useData(
+    () => enabledSources(),
+    () => dateFilters()
+  );
 
-  const { data, loading, error, refresh, loadingPhase, isRefreshing } = useData(() => enabledSources(), dateFilters);
+  createEffect(on(
+    () => [enabledSources(), dateFilters()] as const,
+    ([sources, filters]) => {
+      saveLastFilter({
+        since: filters.since,
+        until: filters.until,
+        model: filters.model,
+        sources: Array.from(sources),
+      });
+    },
+    { defer: true }
+  ));
   
   const cacheTimestamp = () => !isRefreshing() && !loading() ? getCacheTimestamp() : null;
   
   const [selectedDate, setSelectedDate] = createSignal<string | null>(null);
 
+  // Session browser search; while typing, keys go to the query
+  const [sessionQuery, setSessionQuery] = createSignal("");
+  const [searching, setSearching] = createSignal(false);
+  const visibleSessions = () => filterSessions(data()?.sessions ?? [], sessionQuery(), sortBy(), sortDesc());
+  const updateSessionQuery = (query: string) => {
+    setSessionQuery(query);
+    setSelectedIndex(0);
+  };
+
   const [statusMessage, setStatusMessage] = createSignal<string | null>(null);
   let statusTimeout: ReturnType<typeof setTimeout> | null = null;
   const [autoRefreshEnabled, setAutoRefreshEnabled] = createSignal(settings.autoRefreshEnabled ?? false);
   const [autoRefreshMs, setAutoRefreshMs] = createSignal(settings.autoRefreshMs ?? 60000);
+  const [live, setLive] = createSignal(props.live ?? false);
 
   const showStatus = (msg: string, duration = 2000) => {
     if (statusTimeout) clearTimeout(statusTimeout);
@@ -136,7 +193,19 @@ export function App(props: AppProps) {
     onCleanup(() => clearInterval(interval));
   });
 
-  const contentHeight = () => Math.max(rows() - 4, 12);
+  // Live mode: refresh as soon as session files change
+  createEffect(() => {
+    if (!live()) return;
+    const stop = watchSessionData(Array.from(enabledSources()), () => refresh());
+    onCleanup(stop);
+  });
+
+  const contentHeight = () =>
+    Math.max(
+      rows() - 4 - (live() ? LIVE_BAR_HEIGHT : 0) - (exportDraft() ? EXPORT_BAR_HEIGHT : 0) -
+        filterBarHeight(filterDraft(), dateFilters()),
+      12
+    );
   const overviewChartHeight = () => Math.max(5, Math.floor(contentHeight() * 0.35));
   const overviewListHeight = () => Math.max(4, contentHeight() - overviewChartHeight() - 4);
   const overviewItemsPerPage = () => Math.max(1, Math.floor(overviewListHeight() / 2));
@@ -170,7 +239,115 @@ export function App(props: AppProps) {
     }
   };
 
+  const handleFilterKey = (draft: FilterDraft, key: KeyEvent) => {
+    const fieldIdx = FILTER_FIELDS.indexOf(draft.field);
+    if (key.name === "escape") {
+      setFilterDraft(null);
+    } else if (key.name === "return" || key.name === "enter") {
+      const filters = filtersFromDraft(draft);
+      if (typeof filters === "string") {
+        setFilterDraft({ ...draft, error: filters });
+        return;
+      }
+      setFilterDraft(null);
+      setDateFilters(filters);
+      setSelectedIndex(0);
+      setScrollOffset(0);
+      setSelectedDate(null);
+      showStatus("Filter applied");
+    } else if (key.name === "tab" || key.name === "down") {
+      const back = key.name === "tab" && key.shift;
+      const next = (fieldIdx + (back ? FILTER_FIELDS.length - 1 : 1)) % FILTER_FIELDS.length;
+      setFilterDraft({ ...draft, field: FILTER_FIELDS[next], error: undefined });
+    } else if (key.name === "up") {
+      const prev = (fieldIdx + FILTER_FIELDS.length - 1) % FILTER_FIELDS.length;
+      setFilterDraft({ ...draft, field: FILTER_FIELDS[prev], error: undefined });
+    } else if (key.name === "backspace") {
+      setFilterDraft({ ...draft, [draft.field]: draft[draft.field].slice(0, -1), error: undefined });
+    } else if (key.sequence && key.sequence.length === 1 && key.sequence >= " " && !key.ctrl && !key.meta) {
+      setFilterDraft({ ...draft, [draft.field]: draft[draft.field] + key.sequence, error: undefined });
+    }
+  };
+
+  const runExport = async (draft: ExportDraft) => {
+    const path = draft.path.trim();
+    if (!path) {
+      setExportDraft({ ...draft, error: "Enter a file to write to" });
+      return;
+    }
+    setExportDraft({ ...draft, writing: true, error: undefined });
+    try {
+      const exportData = await loadExportData(enabledSources(), dateFilters());
+      // The Sessions view exports what the search shows
+      const sessions = draft.kind === "sessions" ? visibleSessions() : exportData.sessions;
+      const fs = await import("node:fs");
+      fs.writeFileSync(path, serializeExport(draft.kind, draft.format, { ...exportData, sessions }));
+      setExportDraft(null);
+      showStatus(`Exported to ${path}`);
+    } catch (e) {
+      setExportDraft({ ...draft, writing: false, error: `Export failed: ${e instanceof Error ? e.message : String(e)}` });
+    }
+  };
+
+  const handleExportKey = (draft: ExportDraft, key: KeyEvent) => {
+    if (draft.writing) return;
+    if (key.name === "escape") {
+      setExportDraft(null);
+    } else if (key.name === "return" || key.name === "enter") {
+      void runExport(draft);
+    } else if (key.name === "tab" || key.name === "up" || key.name === "down") {
+      setExportDraft({ ...draft, field: draft.field === "format" ? "path" : "format" });
+    } else if (draft.field === "format") {
+      if (key.name === "left") setExportDraft(cycleExportFormat(draft, -1));
+      else if (key.name === "right" || key.name === "space" || key.sequence === " ") {
+        setExportDraft(cycleExportFormat(draft, 1));
+      }
+    } else if (key.name === "backspace") {
+      setExportDraft({ ...draft, path: draft.path.slice(0, -1), error: undefined });
+    } else if (key.sequence && key.sequence.length === 1 && key.sequence >= " " && !key.ctrl && !key.meta) {
+      setExportDraft({ ...draft, path: draft.path + key.sequence, error: undefined });
+    }
+  };
+
   useKeyboard((key) => {
+    const draft = filterDraft();
+    if (draft) {
+      handleFilterKey(draft, key);
+      return;
+    }
+    const exporting = exportDraft();
+    if (exporting) {
+      handleExportKey(exporting, key);
+      return;
+    }
+
+    if (searching()) {
+      if (key.name === "escape") {
+        setSearching(false);
+        updateSessionQuery("");
+      } else if (key.name === "return" || key.name === "enter") {
+        setSearching(false);
+      } else if (key.name === "backspace") {
+        updateSessionQuery(sessionQuery().slice(0, -1));
+      } else if (key.name === "up" || key.name === "down") {
+        setSearching(false);
+      } else if (key.sequence && key.sequence.length === 1 && key.sequence >= " " && !key.ctrl && !key.meta) {
+        updateSessionQuery(sessionQuery() + key.sequence);
+      }
+      if (searching()) return;
+    }
+
+    if (activeTab() === "sessions") {
+      if (key.name === "/" || key.sequence === "/") {
+        setSearching(true);
+        return;
+      }
+      if (key.name === "escape" && sessionQuery()) {
+        updateSessionQuery("");
+        return;
+      }
+    }
+
     if (key.name === "q") {
       renderer.destroy();
       return;
@@ -205,6 +382,18 @@ export function App(props: AppProps) {
       return;
     }
 
+    if (key.name === "f") {
+      setFilterDraft(draftFromFilters(dateFilters()));
+      return;
+    }
+
+    if (key.name === "l") {
+      const next = !live();
+      setLive(next);
+      showStatus(`Live mode: ${next ? "ON (updates as sessions are written)" : "OFF"}`);
+      return;
+    }
+
     if (key.name === "tab" || key.name === "right") {
       setActiveTab(cycleTabForward(activeTab()));
       setSelectedIndex(0);
@@ -251,6 +440,11 @@ export function App(props: AppProps) {
         if (entry) {
           textToCopy = `${entry.date}: ${entry.total.toLocaleString()} tokens, $${entry.cost.toFixed(2)}`;
         }
+      } else if (tab === "sessions") {
+        const session = visibleSessions()[selectedIndex()];
+        if (session) {
+          textToCopy = session.sessionId;
+        }
       } else if (tab === "overview") {
         const model = d.topModels[scrollOffset() + selectedIndex()];
         if (model) {
@@ -313,7 +507,9 @@ export function App(props: AppProps) {
         const d = data();
         const maxIndex = activeTab() === "model" 
           ? (d?.modelEntries.length ?? 0)
-          : (d?.dailyEntries.length ?? 0);
+          : activeTab() === "sessions"
+            ? visibleSessions().length
+            : (d?.dailyEntries.length ?? 0);
         if (maxIndex > 0) {
           setSelectedIndex(Math.min(selectedIndex() + 1, maxIndex - 1));
         }
@@ -322,22 +518,7 @@ export function App(props: AppProps) {
     }
 
     if (key.name === "e" && data()) {
-      const d = data()!;
-      const exportData = {
-        exportedAt: new Date().toISOString(),
-        totalCost: d.totalCost,
-        modelCount: d.modelCount,
-        models: d.modelEntries,
-        daily: d.dailyEntries,
-        stats: d.stats,
-      };
-      const filename = `tokscale-export-${new Date().toISOString().split("T")[0]}.json`;
-      import("node:fs")
-        .then((fs) => {
-          fs.writeFileSync(filename, JSON.stringify(exportData, null, 2));
-          showStatus(`Exported to ${filename}`);
-        })
-        .catch(() => showStatus("Export failed"));
+      setExportDraft(newExportDraft(activeTab()));
       return;
     }
   });
@@ -352,6 +533,17 @@ export function App(props: AppProps) {
   return (
     <box flexDirection="column" width={columns()} height={rows()} backgroundColor="black">
       <Header activeTab={activeTab()} onTabClick={handleTabClick} width={columns()} />
+      <Show when={live() && data()}>
+        <LiveBar data={data()!} width={columns()} />
+      </Show>
+      <FilterBar filters={dateFilters()} draft={filterDraft()} width={columns()} />
+      <Show when={exportDraft()}>
+        <ExportBar
+          draft={exportDraft()!}
+          filterLabel={isFiltered(dateFilters()) ? describeFilters(dateFilters()) : undefined}
+          width={columns()}
+        />
+      </Show>
 
       <box flexDirection="column" flexGrow={1} paddingX={1}>
         <Switch>
@@ -396,6 +588,18 @@ export function App(props: AppProps) {
                   width={columns()}
                 />
               </Match>
+              <Match when={activeTab() === "sessions"}>
+                <SessionsView
+                  data={data()!}
+                  sortBy={sortBy()}
+                  sortDesc={sortDesc()}
+                  selectedIndex={selectedIndex}
+                  query={sessionQuery()}
+                  searching={searching()}
+                  height={contentHeight()}
+                  width={columns()}
+                />
+              </Match>
               <Match when={activeTab() === "stats"}>
                 <StatsView
                   data={data()!}
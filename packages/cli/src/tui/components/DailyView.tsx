@@ -2,6 +2,7 @@ import { For, createMemo, type Accessor } from "solid-js";
 import type { TUIData, SortType } from "../hooks/useData.js";
 import { formatTokensCompact, formatCostFull } from "../utils/format.js";
 import { isNarrow } from "../utils/responsive.js";
+import { costColor, themeColor } from "../../theme.js";
 
 const STRIPE_BG = "#232328";
 
@@ -99,7 +100,7 @@ export function DailyView(props: DailyViewProps) {
   return (
     <box flexDirection="column">
       <box flexDirection="row">
-        <text fg="cyan" bold>
+        <text fg={themeColor("header")} bold>
           {renderHeader()}
         </text>
       </box>
@@ -107,7 +108,7 @@ export function DailyView(props: DailyViewProps) {
       <For each={formattedRows()}>
         {(row, i) => {
           const isActive = createMemo(() => i() === props.selectedIndex());
-          const rowBg = createMemo(() => isActive() ? "blue" : (i() % 2 === 1 ? STRIPE_BG : undefined));
+          const rowBg = createMemo(() => isActive() ? themeColor("selection") : (i() % 2 === 1 ? STRIPE_BG : undefined));
 
           return (
             <box flexDirection="row">
@@ -118,7 +119,7 @@ export function DailyView(props: DailyViewProps) {
                 {renderRowData(row)}
               </text>
               <text
-                fg="green"
+                fg={costColor(row.entry.cost)}
                 bg={rowBg()}
               >
                 {row.cost.padStart(COST_COL_WIDTH)}
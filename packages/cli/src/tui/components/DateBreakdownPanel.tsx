@@ -3,6 +3,7 @@ import type { DailyModelBreakdown } from "../types/index.js";
 import { getSourceColor, getSourceDisplayName } from "../utils/colors.js";
 import { formatTokens, formatCost } from "../utils/format.js";
 import { ModelRow } from "./ModelRow.js";
+import { costColor } from "../../theme.js";
 
 function formatDateDisplay(dateStr: string): string {
   const date = new Date(dateStr + "T00:00:00");
@@ -39,7 +40,7 @@ export function DateBreakdownPanel(props: DateBreakdownPanelProps) {
         <text bold fg="white">{formatDateDisplay(props.breakdown.date)}</text>
         <box flexDirection="row" gap={2}>
           <text fg="cyan">{formatTokens(props.breakdown.totalTokens)}</text>
-          <text fg="green" bold>{formatCost(props.breakdown.cost)}</text>
+          <text fg={costColor(props.breakdown.cost)} bold>{formatCost(props.breakdown.cost)}</text>
         </box>
       </box>
       
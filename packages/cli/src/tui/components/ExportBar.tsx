@@ -0,0 +1,97 @@
+import { For, Show } from "solid-js";
+import { EXPORT_FORMATS, defaultExportPath, type ExportFormat, type ExportKind } from "../../export.js";
+import type { TabType } from "../types/index.js";
+import { isNarrow } from "../utils/responsive.js";
+
+/** Rows the export bar takes above the view */
+export const EXPORT_BAR_HEIGHT = 2;
+
+const KIND_LABELS: Record<ExportKind, string> = {
+  models: "model report",
+  graph: "daily contributions",
+  sessions: "sessions",
+};
+
+/** What exporting from `tab` saves */
+export function exportKindForTab(tab: TabType): ExportKind {
+  if (tab === "daily" || tab === "stats") return "graph";
+  if (tab === "sessions") return "sessions";
+  return "models";
+}
+
+/** Export being set up, with the field that has focus */
+export interface ExportDraft {
+  kind: ExportKind;
+  format: ExportFormat;
+  path: string;
+  field: "format" | "path";
+  /** Set while the export is being written */
+  writing?: boolean;
+  error?: string;
+}
+
+export function newExportDraft(tab: TabType): ExportDraft {
+  const kind = exportKindForTab(tab);
+  return { kind, format: "json", path: defaultExportPath(kind, "json"), field: "format" };
+}
+
+/** Switch format; a path still at its default follows the new extension */
+export function cycleExportFormat(draft: ExportDraft, step: 1 | -1): ExportDraft {
+  const idx = EXPORT_FORMATS.indexOf(draft.format);
+  const format = EXPORT_FORMATS[(idx + step + EXPORT_FORMATS.length) % EXPORT_FORMATS.length];
+  const path = draft.path === defaultExportPath(draft.kind, draft.format) ? defaultExportPath(draft.kind, format) : draft.path;
+  return { ...draft, format, path, error: undefined };
+}
+
+interface ExportBarProps {
+  draft: ExportDraft;
+  /** Describes the active filter, if any */
+  filterLabel?: string;
+  width: number;
+}
+
+/**
+ * Export dialog: format and destination for the view on screen, with the
+ * active filter applied
+ */
+export function ExportBar(props: ExportBarProps) {
+  const focused = (field: ExportDraft["field"]) => props.draft.field === field;
+
+  return (
+    <box flexDirection="column" paddingX={1}>
+      <box flexDirection="row" gap={1}>
+        <text fg="yellow" bold>{`Export ${KIND_LABELS[props.draft.kind]}`}</text>
+        <Show when={props.filterLabel && !isNarrow(props.width)}>
+          <text dim>{`(${props.filterLabel})`}</text>
+        </Show>
+        <text fg={focused("format") ? "yellow" : undefined}>Format:</text>
+        <For each={EXPORT_FORMATS}>
+          {(format) => (
+            <text
+              bg={props.draft.format === format ? (focused("format") ? "blue" : "gray") : undefined}
+              fg={props.draft.format === format ? "white" : undefined}
+            >
+              {` ${format.toUpperCase()} `}
+            </text>
+          )}
+        </For>
+        <text fg={focused("path") ? "yellow" : undefined}>To:</text>
+        <text bg={focused("path") ? "blue" : undefined} fg={focused("path") ? "white" : undefined}>
+          {`${props.draft.path}${focused("path") ? "▏" : ""}`}
+        </text>
+      </box>
+      <Show
+        when={props.draft.error}
+        fallback={
+          <text dim>
+            {props.draft.writing
+              ? "Writing..."
+              : "←/→ format • tab field • enter export • esc cancel"}
+          </text>
+        }
+      >
+        <text fg="red">{props.draft.error}</text>
+      </Show>
+    </box>
+  );
+}
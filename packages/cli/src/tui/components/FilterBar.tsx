@@ -0,0 +1,125 @@
+import { For, Show } from "solid-js";
+import type { DateFilters } from "../hooks/useData.js";
+import { isNarrow } from "../utils/responsive.js";
+
+export type FilterField = "since" | "until" | "model";
+export const FILTER_FIELDS: readonly FilterField[] = ["since", "until", "model"];
+
+const FIELD_LABELS: Record<FilterField, string> = {
+  since: "Since",
+  until: "Until",
+  model: "Model",
+};
+
+/** Filter being edited, with the field that has focus */
+export interface FilterDraft {
+  since: string;
+  until: string;
+  model: string;
+  field: FilterField;
+  error?: string;
+}
+
+const DATE_RE = /^\d{4}-\d{2}-\d{2}$/;
+
+/** Start editing from the active filter; a --year becomes its date range */
+export function draftFromFilters(filters: DateFilters): FilterDraft {
+  return {
+    since: filters.since ?? (filters.year ? `${filters.year}-01-01` : ""),
+    until: filters.until ?? (filters.year ? `${filters.year}-12-31` : ""),
+    model: filters.model ?? "",
+    field: "since",
+  };
+}
+
+/** The filter a draft describes, or an error message */
+export function filtersFromDraft(draft: FilterDraft): DateFilters | string {
+  const since = draft.since.trim();
+  const until = draft.until.trim();
+  const model = draft.model.split(",").map((p) => p.trim()).filter(Boolean).join(", ");
+  for (const date of [since, until]) {
+    if (date && (!DATE_RE.test(date) || Number.isNaN(Date.parse(date)))) {
+      return `"${date}" is not a YYYY-MM-DD date`;
+    }
+  }
+  if (since && until && since > until) {
+    return "Since is after Until";
+  }
+  return { since: since || undefined, until: until || undefined, model: model || undefined };
+}
+
+export function isFiltered(filters: DateFilters): boolean {
+  return Boolean(filters.since || filters.until || filters.year || filters.model);
+}
+
+/** Rows the filter bar takes above the view */
+export function filterBarHeight(draft: FilterDraft | null, filters: DateFilters): number {
+  if (draft) return 2;
+  return isFiltered(filters) ? 1 : 0;
+}
+
+export function describeFilters(filters: DateFilters): string {
+  const parts: string[] = [];
+  if (filters.year) parts.push(filters.year);
+  if (filters.since || filters.until) parts.push(`${filters.since ?? "…"} → ${filters.until ?? "…"}`);
+  if (filters.model) parts.push(`model ${filters.model}`);
+  return parts.join(" • ");
+}
+
+interface FilterBarProps {
+  filters: DateFilters;
+  draft: FilterDraft | null;
+  width: number;
+}
+
+/**
+ * Active filter summary, or the filter editor while one is being edited
+ */
+export function FilterBar(props: FilterBarProps) {
+  return (
+    <Show
+      when={props.draft}
+      fallback={
+        <Show when={isFiltered(props.filters)}>
+          <box flexDirection="row" gap={1} paddingX={1}>
+            <text fg="yellow">Filter</text>
+            <text>{describeFilters(props.filters)}</text>
+            <text dim>• f edit</text>
+          </box>
+        </Show>
+      }
+    >
+      {(draft) => (
+        <box flexDirection="column" paddingX={1}>
+          <box flexDirection="row" gap={2}>
+            <For each={FILTER_FIELDS}>
+              {(field) => {
+                const focused = () => draft().field === field;
+                return (
+                  <box flexDirection="row" gap={1}>
+                    <text fg={focused() ? "yellow" : undefined} bold={focused()}>{`${FIELD_LABELS[field]}:`}</text>
+                    <text bg={focused() ? "blue" : undefined} fg={focused() ? "white" : undefined}>
+                      {`${draft()[field] || (focused() ? "" : "any")}${focused() ? "▏" : ""}`}
+                    </text>
+                  </box>
+                );
+              }}
+            </For>
+          </box>
+          <Show
+            when={draft().error}
+            fallback={
+              <text dim>
+                {isNarrow(props.width)
+                  ? "tab field • enter apply • esc cancel"
+                  : "YYYY-MM-DD dates • models: comma-separated, * and ? globs • tab field • enter apply • esc cancel"}
+              </text>
+            }
+          >
+            <text fg="red">{draft().error}</text>
+          </Show>
+        </box>
+      )}
+    </Show>
+  );
+}
@@ -194,7 +194,7 @@ export function Footer(props: FooterProps) {
                   <box onMouseDown={props.onRefresh}>
                     <text fg="yellow">[r:refresh]</text>
                   </box>
-                  <text dim>• e export • q quit</text>
+                  <text dim>• f filter • l live • e export • q quit</text>
                 </>
               }
             >
@@ -209,7 +209,7 @@ export function Footer(props: FooterProps) {
               <box onMouseDown={props.onRefresh}>
                 <text fg="yellow">[r]</text>
               </box>
-              <text dim>•e•q</text>
+              <text dim>•l•e•q</text>
             </Show>
           }
         >
@@ -29,6 +29,7 @@ export function Header(props: HeaderProps) {
         <Tab name={getTabName("Overview", "Ovw")} tabId="overview" active={props.activeTab === "overview"} onClick={props.onTabClick} />
         <Tab name={getTabName("Models", "Mod")} tabId="model" active={props.activeTab === "model"} onClick={props.onTabClick} />
         <Tab name={getTabName("Daily", "Day")} tabId="daily" active={props.activeTab === "daily"} onClick={props.onTabClick} />
+        <Tab name={getTabName("Sessions", "Ses")} tabId="sessions" active={props.activeTab === "sessions"} onClick={props.onTabClick} />
         <Tab name={getTabName("Stats", "Sta")} tabId="stats" active={props.activeTab === "stats"} onClick={props.onTabClick} />
       </box>
       <Show when={!isNarrowTerminal()}>
@@ -0,0 +1,72 @@
+import { For, Show, createMemo, createSignal, onCleanup, onMount } from "solid-js";
+import type { TUIData } from "../hooks/useData.js";
+import { getSourceColor } from "../utils/colors.js";
+import { formatCostFull, formatTokensCompact } from "../utils/format.js";
+import { isNarrow } from "../utils/responsive.js";
+
+/** A session with a message this recent counts as active */
+export const ACTIVE_WINDOW_MS = 5 * 60 * 1000;
+/** Active sessions listed by name; the rest are counted */
+const MAX_LISTED_SESSIONS = 3;
+const CLOCK_INTERVAL_MS = 10_000;
+
+/** Rows the live bar takes above the view */
+export const LIVE_BAR_HEIGHT = 2;
+
+interface LiveBarProps {
+  data: TUIData;
+  width: number;
+}
+
+/**
+ * Live mode summary: today's totals, burn per minute over the last hour and
+ * the sessions that had a message in the last few minutes
+ */
+export function LiveBar(props: LiveBarProps) {
+  const [now, setNow] = createSignal(Date.now());
+  onMount(() => {
+    const id = setInterval(() => setNow(Date.now()), CLOCK_INTERVAL_MS);
+    onCleanup(() => clearInterval(id));
+  });
+
+  const today = createMemo(() => {
+    const date = new Date(now()).toISOString().split("T")[0];
+    return props.data.dailyEntries.find((d) => d.date === date);
+  });
+  const costPerMinute = () => (props.data.recent?.costPerHour ?? 0) / 60;
+  const activeSessions = createMemo(() =>
+    (props.data.sessions ?? []).filter((s) => now() - s.end <= ACTIVE_WINDOW_MS)
+  );
+
+  return (
+    <box flexDirection="column" paddingX={1}>
+      <box flexDirection="row" gap={1}>
+        <text fg="red" bold>● LIVE</text>
+        <text dim>Today</text>
+        <text fg="green" bold>{formatCostFull(today()?.cost ?? 0)}</text>
+        <text fg="cyan">{`${formatTokensCompact(today()?.total ?? 0)} tokens`}</text>
+        <text dim>|</text>
+        <text dim>Burn</text>
+        <text fg="yellow" bold>{`${formatCostFull(costPerMinute())}/min`}</text>
+        <Show when={!isNarrow(props.width)}>
+          <text dim>(last hour)</text>
+        </Show>
+      </box>
+      <box flexDirection="row" gap={1}>
+        <Show when={activeSessions().length > 0} fallback={<text dim>No active sessions</text>}>
+          <text dim>{`Active (${activeSessions().length}):`}</text>
+          <For each={activeSessions().slice(0, MAX_LISTED_SESSIONS)}>
+            {(session) => (
+              <text fg={getSourceColor(session.source)}>
+                {`${session.project ?? session.sessionId.slice(0, 8)} ${formatCostFull(session.cost)}`}
+              </text>
+            )}
+          </For>
+          <Show when={activeSessions().length > MAX_LISTED_SESSIONS}>
+            <text dim>{`+${activeSessions().length - MAX_LISTED_SESSIONS} more`}</text>
+          </Show>
+        </Show>
+      </box>
+    </box>
+  );
+}
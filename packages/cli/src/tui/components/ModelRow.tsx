@@ -1,6 +1,7 @@
 import { Show, createMemo } from "solid-js";
 import { TokenBreakdown, type TokenBreakdownData } from "./TokenBreakdown.js";
 import { getModelColor } from "../utils/colors.js";
+import { themeColor } from "../../theme.js";
 
 interface ModelRowProps {
   modelId: string;
@@ -14,7 +15,7 @@ interface ModelRowProps {
 
 export function ModelRow(props: ModelRowProps) {
   const color = () => getModelColor(props.modelId);
-  const bgColor = createMemo(() => props.isActive ? "blue" : undefined);
+  const bgColor = createMemo(() => props.isActive ? themeColor("selection") : undefined);
   
   const truncateName = (name: string) => {
     const max = props.maxNameWidth ?? 50;
@@ -2,6 +2,7 @@ import { For, createMemo, type Accessor } from "solid-js";
 import type { TUIData, SortType } from "../hooks/useData.js";
 import { getModelColor, getSourceDisplayName } from "../utils/colors.js";
 import { formatTokensCompact, formatCostFull } from "../utils/format.js";
+import { costColor, themeColor } from "../../theme.js";
 import { isNarrow, isVeryNarrow } from "../utils/responsive.js";
 
 const STRIPE_BG = "#232328";
@@ -110,7 +111,7 @@ export function ModelView(props: ModelViewProps) {
   return (
     <box flexDirection="column">
       <box flexDirection="row">
-        <text fg="cyan" bold>
+        <text fg={themeColor("header")} bold>
           {renderHeader()}
         </text>
       </box>
@@ -118,7 +119,7 @@ export function ModelView(props: ModelViewProps) {
       <For each={formattedRows()}>
         {(row, i) => {
           const isActive = createMemo(() => i() === props.selectedIndex());
-          const rowBg = createMemo(() => isActive() ? "blue" : (i() % 2 === 1 ? STRIPE_BG : undefined));
+          const rowBg = createMemo(() => isActive() ? themeColor("selection") : (i() % 2 === 1 ? STRIPE_BG : undefined));
           
           return (
             <box flexDirection="row">
@@ -133,7 +134,7 @@ export function ModelView(props: ModelViewProps) {
                 {renderRowData(row)}
               </text>
               <text
-                fg="green"
+                fg={costColor(row.entry.cost)}
                 bg={rowBg()}
               >
                 {row.cost.padStart(COST_COL_WIDTH)}
@@ -0,0 +1,189 @@
+import { For, Show, createMemo, type Accessor } from "solid-js";
+import type { SessionEntry, SortType } from "../types/index.js";
+import type { TUIData } from "../hooks/useData.js";
+import { getSourceColor, getSourceDisplayName } from "../utils/colors.js";
+import { formatTokens, formatTokensCompact, formatCostFull } from "../utils/format.js";
+import { fuzzyScore } from "../utils/fuzzy.js";
+import { isNarrow } from "../utils/responsive.js";
+import { costColor, themeColor } from "../../theme.js";
+
+const STRIPE_BG = "#232328";
+
+const DATE_COL_WIDTH = 13;
+const SESSION_COL_WIDTH = 11;
+const TOKENS_COL_WIDTH = 10;
+const COST_COL_WIDTH = 11;
+const MIN_LABEL_COLUMN = 12;
+const DETAIL_WIDTH = 40;
+const TIMELINE_ROWS = 6;
+const BAR_CHARS = [" ", "▁", "▂", "▃", "▄", "▅", "▆", "▇", "█"];
+
+/** Text the search matches: session ID, project and models */
+function searchText(session: SessionEntry): string {
+  return [session.sessionId, session.project ?? "", ...session.models].join(" ");
+}
+
+/** Sessions matching `query`, in the chosen sort order */
+export function filterSessions(
+  sessions: SessionEntry[],
+  query: string,
+  sortBy: SortType,
+  sortDesc: boolean
+): SessionEntry[] {
+  const matched = query.trim()
+    ? sessions.filter((session) => fuzzyScore(query, searchText(session)) !== null)
+    : [...sessions];
+  return matched.sort((a, b) => {
+    let cmp = 0;
+    if (sortBy === "cost") cmp = a.cost - b.cost;
+    else if (sortBy === "tokens") cmp = a.tokens - b.tokens;
+    else cmp = a.end - b.end;
+    return sortDesc ? -cmp : cmp;
+  });
+}
+
+function formatStart(ms: number): string {
+  const d = new Date(ms);
+  const pad = (n: number) => String(n).padStart(2, "0");
+  return `${pad(d.getMonth() + 1)}-${pad(d.getDate())} ${pad(d.getHours())}:${pad(d.getMinutes())}`;
+}
+
+function formatSpan(ms: number): string {
+  const minutes = Math.floor(ms / 60_000);
+  if (minutes < 1) return `${Math.floor(ms / 1000)}s`;
+  if (minutes < 60) return `${minutes}m`;
+  return `${Math.floor(minutes / 60)}h ${minutes % 60}m`;
+}
+
+function truncate(text: string, width: number): string {
+  if (text.length <= width) return text.padEnd(width);
+  return width > 1 ? `${text.slice(0, width - 1)}…` : text.slice(0, width);
+}
+
+/** Column chart of `points`, top row first, one character per point */
+function timelineRows(points: number[], rows: number): string[] {
+  const max = Math.max(...points, 0);
+  const eighths = points.map((p) => (max > 0 ? Math.round((p / max) * rows * 8) : 0));
+  return Array.from({ length: rows }, (_, r) => {
+    const floor = (rows - 1 - r) * 8;
+    return eighths.map((e) => BAR_CHARS[Math.max(0, Math.min(8, e - floor))]).join("");
+  });
+}
+
+interface SessionsViewProps {
+  data: TUIData;
+  sortBy: SortType;
+  sortDesc: boolean;
+  selectedIndex: Accessor<number>;
+  query: string;
+  searching: boolean;
+  height: number;
+  width: number;
+}
+
+export function SessionsView(props: SessionsViewProps) {
+  const showDetail = () => !isNarrow(props.width);
+  const listWidth = () => Math.max(props.width - 2 - (showDetail() ? DETAIL_WIDTH + 1 : 0), 0);
+  const labelWidth = () =>
+    Math.max(listWidth() - DATE_COL_WIDTH - SESSION_COL_WIDTH - TOKENS_COL_WIDTH - COST_COL_WIDTH, MIN_LABEL_COLUMN);
+
+  const sessions = createMemo(() =>
+    filterSessions(props.data.sessions ?? [], props.query, props.sortBy, props.sortDesc)
+  );
+  const selected = () => sessions()[props.selectedIndex()];
+
+  // Scroll so the selected row stays in view
+  const maxRows = () => Math.max(props.height - 4, 1);
+  const firstRow = () => Math.max(0, props.selectedIndex() - maxRows() + 1);
+  const visibleSessions = createMemo(() => sessions().slice(firstRow(), firstRow() + maxRows()));
+
+  const sortArrow = () => (props.sortDesc ? "▼" : "▲");
+  const header = () => {
+    const label = (name: string, sort: SortType) => (props.sortBy === sort ? `${sortArrow()} ${name}` : name);
+    return `${label("Last", "date").padEnd(DATE_COL_WIDTH)}${"Session".padEnd(SESSION_COL_WIDTH)}${"Project / Models".padEnd(labelWidth())}${label("Tokens", "tokens").padStart(TOKENS_COL_WIDTH)}${label("Cost", "cost").padStart(COST_COL_WIDTH)}`;
+  };
+
+  const renderRow = (session: SessionEntry) => {
+    const label = session.project ? `${session.project} · ${session.models.join(", ")}` : session.models.join(", ");
+    return `${formatStart(session.end).padEnd(DATE_COL_WIDTH)}${truncate(session.sessionId, SESSION_COL_WIDTH - 1)} ${truncate(label, labelWidth())}${formatTokensCompact(session.tokens).padStart(TOKENS_COL_WIDTH)}`;
+  };
+
+  return (
+    <box flexDirection="column">
+      <box flexDirection="row" gap={1}>
+        <Show
+          when={props.searching || props.query}
+          fallback={<text dim>{`${sessions().length} sessions • / search`}</text>}
+        >
+          <text fg="yellow">{`/${props.query}${props.searching ? "▏" : ""}`}</text>
+          <text dim>
+            {`${sessions().length} of ${props.data.sessions?.length ?? 0} • ${props.searching ? "enter keep • esc clear" : "/ edit • esc clear"}`}
+          </text>
+        </Show>
+      </box>
+
+      <box flexDirection="row" gap={1}>
+        <box flexDirection="column" width={listWidth()}>
+          <text fg={themeColor("header")} bold>{header()}</text>
+          <For each={visibleSessions()}>
+            {(session, i) => {
+              const isActive = createMemo(() => firstRow() + i() === props.selectedIndex());
+              const rowBg = createMemo(() => (isActive() ? themeColor("selection") : i() % 2 === 1 ? STRIPE_BG : undefined));
+              return (
+                <box flexDirection="row">
+                  <text bg={rowBg()} fg={isActive() ? "white" : undefined}>{renderRow(session)}</text>
+                  <text fg={costColor(session.cost)} bg={rowBg()}>{formatCostFull(session.cost).padStart(COST_COL_WIDTH)}</text>
+                </box>
+              );
+            }}
+          </For>
+          <Show when={sessions().length === 0}>
+            <text dim>{props.query ? "No sessions match" : "No sessions in this range"}</text>
+          </Show>
+        </box>
+
+        <Show when={showDetail() && selected()}>
+          <SessionDetail session={selected()!} />
+        </Show>
+      </box>
+    </box>
+  );
+}
+
+function SessionDetail(props: { session: SessionEntry }) {
+  const s = () => props.session;
+  const timeline = () => timelineRows(s().timeline, TIMELINE_ROWS);
+  const peak = () => Math.max(...s().timeline, 0);
+
+  return (
+    <box flexDirection="column" width={DETAIL_WIDTH} paddingX={1}>
+      <text bold fg="white">{truncate(s().sessionId, DETAIL_WIDTH - 2)}</text>
+      <text fg={getSourceColor(s().source)}>{`● ${getSourceDisplayName(s().source)}`}</text>
+      <Show when={s().project}>
+        <text>{`Project  ${s().project}`}</text>
+      </Show>
+      <For each={s().models}>
+        {(model, i) => <text>{`${i() === 0 ? "Models  " : "        "} ${truncate(model, DETAIL_WIDTH - 11)}`}</text>}
+      </For>
+      <text dim>{`${new Date(s().start).toLocaleString()} (${formatSpan(s().end - s().start)})`}</text>
+      <box flexDirection="row" gap={2}>
+        <text>{`${s().messageCount} msgs`}</text>
+        <text fg="cyan">{formatTokens(s().tokens)}</text>
+        <text fg={costColor(s().cost)} bold>{formatCostFull(s().cost)}</text>
+      </box>
+
+      <box marginTop={1}>
+        <text fg="cyan" bold>Cost timeline</text>
+      </box>
+      <For each={timeline()}>
+        {(row, i) => (
+          <box flexDirection="row">
+            <text dim>{(i() === 0 ? formatCostFull(peak()) : "").padStart(8)}</text>
+            <text fg="green">{` ${row}`}</text>
+          </box>
+        )}
+      </For>
+      <text dim>{`${" ".repeat(9)}${formatStart(s().start).slice(6)}${"→".padStart(Math.max(s().timeline.length - 10, 1))}${formatStart(s().end).slice(6)}`}</text>
+    </box>
+  );
+}
@@ -20,6 +20,7 @@ interface StatsViewProps {
 const MONTHS = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
 const MONTHS_SHORT = ["J", "F", "M", "A", "M", "J", "J", "A", "S", "O", "N", "D"];
 const DAYS = ["", "Mon", "", "Wed", "", "Fri", ""];
+const SPARK_LEVELS = "▁▂▃▄▅▆▇█";
 
 interface MonthLabel {
   month: string;
@@ -86,6 +87,21 @@ export function StatsView(props: StatsViewProps) {
 
   const totalWeeks = createMemo(() => (grid()[0] || []).length);
 
+  // Most recent 15-minute buckets that fit on one line, one character each
+  const recentSparkline = createMemo(() => {
+    const recent = props.data.recent;
+    if (!recent || recent.buckets.length === 0) return null;
+    const available = Math.max(8, (props.width ?? 80) - 16);
+    const buckets = recent.buckets.slice(-available);
+    const values = buckets.map(b => metric() === "tokens" ? b.tokens : b.cost);
+    const maxValue = Math.max(0, ...values);
+    const line = values
+      .map(v => v <= 0 ? " " : SPARK_LEVELS[Math.min(SPARK_LEVELS.length - 1, Math.floor((v / maxValue) * SPARK_LEVELS.length))])
+      .join("");
+    const hours = Math.round((buckets.length * recent.bucketMinutes) / 60);
+    return { line, hours, costPerHour: recent.costPerHour, tokensPerHour: recent.tokensPerHour };
+  });
+
   const monthLabelRow = createMemo(() => {
     const weeks = totalWeeks();
     const positions = monthPositions();
@@ -233,6 +249,20 @@ export function StatsView(props: StatsViewProps) {
           </box>
         </box>
 
+        <Show when={recentSparkline()}>
+          <box flexDirection="column" marginTop={1}>
+            <box flexDirection="row" gap={1}>
+              <text dim>{isNarrowTerminal() ? "Burn:" : "Burn rate:"}</text>
+              <text fg="cyan">{`$${recentSparkline()!.costPerHour.toFixed(2)}/h`}</text>
+              <text dim>{`(${formatTokens(recentSparkline()!.tokensPerHour)} tokens in the last hour)`}</text>
+            </box>
+            <box flexDirection="row" gap={1}>
+              <text dim>{`Last ${recentSparkline()!.hours}h:`}</text>
+              <text fg={getGradeColor(palette(), 4)}>{recentSparkline()!.line}</text>
+            </box>
+          </box>
+        </Show>
+
         <Show when={!isNarrowTerminal()}>
           <box marginTop={1}>
             <text fg="yellow" italic>{`Your total spending is $${props.data.totalCost.toFixed(2)} on AI coding assistants!`}</text>
@@ -3,6 +3,7 @@ export { Footer } from "./Footer.js";
 export { OverviewView } from "./OverviewView.js";
 export { ModelView } from "./ModelView.js";
 export { DailyView } from "./DailyView.js";
+export { SessionsView } from "./SessionsView.js";
 export { StatsView } from "./StatsView.js";
 export { BarChart } from "./BarChart.js";
 export type { ChartDataPoint } from "./BarChart.js";
@@ -2,10 +2,12 @@ import { homedir } from "os";
 import { join } from "path";
 import { readFileSync, writeFileSync, existsSync, mkdirSync } from "fs";
 import type { TUIData, DailyModelBreakdown } from "../types/index.js";
+import { readOnlyHome } from "../../read-only.js";
+import type { CostThresholds, CustomTheme } from "../../theme.js";
 
 const CONFIG_DIR = join(homedir(), ".config", "tokscale");
 const CACHE_DIR = join(homedir(), ".cache", "tokscale");
-const CONFIG_FILE = join(CONFIG_DIR, "settings.json");
+export const CONFIG_FILE = join(CONFIG_DIR, "settings.json");
 const LEGACY_CONFIG_FILE = join(CONFIG_DIR, "tui-settings.json");
 const CACHE_FILE = join(CACHE_DIR, "tui-data-cache.json");
 
@@ -13,12 +15,36 @@ const CACHE_STALE_THRESHOLD_MS = 60 * 1000;
 const MIN_AUTO_REFRESH_MS = 30000;
 const MAX_AUTO_REFRESH_MS = 3600000;
 const DEFAULT_AUTO_REFRESH_MS = 60000;
+export const DEFAULT_IDLE_THRESHOLD_MINUTES = 30;
+
+/** Filter the TUI was last left with */
+export interface TUIFilter {
+  since?: string;
+  until?: string;
+  /** Comma-separated model globs */
+  model?: string;
+  sources?: string[];
+}
 
 export interface TokscaleSettings {
   colorPalette: string;
   autoRefreshEnabled?: boolean;
   autoRefreshMs?: number;
   includeUnusedModels?: boolean;
+  idleThresholdMinutes?: number;
+  /** Locale for report strings, e.g. "pt-BR" (default: from the environment) */
+  locale?: string;
+  /** Last-used TUI filter per profile (TOKSCALE_PROFILE, else "default") */
+  tuiFilters?: Record<string, TUIFilter>;
+  /** Built-in theme ("dark", "light", "high-contrast") or a key of `themes` */
+  theme?: string;
+  themes?: Record<string, CustomTheme>;
+  /** Per-row cost levels in USD: medium from `warn`, high from `high` */
+  costThresholds?: CostThresholds;
+}
+
+function isObject(value: unknown): value is Record<string, unknown> {
+  return Boolean(value) && typeof value === "object" && !Array.isArray(value);
 }
 
 function validateSettings(raw: unknown): TokscaleSettings {
@@ -27,6 +53,7 @@ function validateSettings(raw: unknown): TokscaleSettings {
     autoRefreshEnabled: false, 
     autoRefreshMs: DEFAULT_AUTO_REFRESH_MS,
     includeUnusedModels: false,
+    idleThresholdMinutes: DEFAULT_IDLE_THRESHOLD_MINUTES,
   };
   
   if (!raw || typeof raw !== "object") return defaults;
@@ -43,12 +70,45 @@ function validateSettings(raw: unknown): TokscaleSettings {
   
   const includeUnusedModels = typeof obj.includeUnusedModels === "boolean" ? obj.includeUnusedModels : defaults.includeUnusedModels;
   
-  return { colorPalette, autoRefreshEnabled, autoRefreshMs, includeUnusedModels };
+  let idleThresholdMinutes = defaults.idleThresholdMinutes;
+  if (typeof obj.idleThresholdMinutes === "number" && Number.isFinite(obj.idleThresholdMinutes) && obj.idleThresholdMinutes > 0) {
+    idleThresholdMinutes = obj.idleThresholdMinutes;
+  }
+  
+  const locale = typeof obj.locale === "string" ? obj.locale : undefined;
+
+  const tuiFilters = isObject(obj.tuiFilters) ? (obj.tuiFilters as Record<string, TUIFilter>) : undefined;
+
+  const theme = typeof obj.theme === "string" ? obj.theme : undefined;
+  const themes = isObject(obj.themes) ? (obj.themes as Record<string, CustomTheme>) : undefined;
+
+  let costThresholds: CostThresholds | undefined;
+  if (isObject(obj.costThresholds)) {
+    const { warn, high } = obj.costThresholds;
+    if (typeof warn === "number" && typeof high === "number" && warn >= 0 && high >= warn) {
+      costThresholds = { warn, high };
+    }
+  }
+  
+  return {
+    colorPalette,
+    autoRefreshEnabled,
+    autoRefreshMs,
+    includeUnusedModels,
+    idleThresholdMinutes,
+    locale,
+    tuiFilters,
+    theme,
+    themes,
+    costThresholds,
+  };
 }
 
 interface CachedTUIData {
   timestamp: number;
   enabledSources: string[];
+  /** Filter the data was loaded with; missing in caches from older versions */
+  filterKey?: string;
   data: Omit<TUIData, 'dailyBreakdowns'> & {
     dailyBreakdowns: Array<[string, DailyModelBreakdown]>;
   };
@@ -66,7 +126,7 @@ export function loadSettings(): TokscaleSettings {
     }
   } catch {
   }
-  return { colorPalette: "blue", autoRefreshEnabled: false, autoRefreshMs: DEFAULT_AUTO_REFRESH_MS, includeUnusedModels: false };
+  return { colorPalette: "blue", autoRefreshEnabled: false, autoRefreshMs: DEFAULT_AUTO_REFRESH_MS, includeUnusedModels: false, idleThresholdMinutes: DEFAULT_IDLE_THRESHOLD_MINUTES };
 }
 
 export function saveSettings(updates: Partial<TokscaleSettings>): void {
@@ -75,7 +135,15 @@ export function saveSettings(updates: Partial<TokscaleSettings>): void {
       mkdirSync(CONFIG_DIR, { recursive: true });
     }
     const current = loadSettings();
-    const merged = { ...current, ...updates };
+    // Keep keys the TUI doesn't model (costDisplay, apiKeys, ...)
+    let raw: Record<string, unknown> = {};
+    if (existsSync(CONFIG_FILE)) {
+      const parsed: unknown = JSON.parse(readFileSync(CONFIG_FILE, "utf-8"));
+      if (parsed && typeof parsed === "object" && !Array.isArray(parsed)) {
+        raw = parsed as Record<string, unknown>;
+      }
+    }
+    const merged = { ...raw, ...current, ...updates };
     writeFileSync(CONFIG_FILE, JSON.stringify(merged, null, 2));
   } catch {
   }
@@ -94,7 +162,23 @@ function sourcesMatch(enabledSources: Set<string>, cachedSources: string[]): boo
   return true;
 }
 
-export function loadCachedData(enabledSources: Set<string>): TUIData | null {
+/** Key of the current profile in `tuiFilters` */
+export function profileName(): string {
+  return process.env.TOKSCALE_PROFILE?.trim() || "default";
+}
+
+export function loadLastFilter(): TUIFilter | null {
+  return loadSettings().tuiFilters?.[profileName()] ?? null;
+}
+
+export function saveLastFilter(filter: TUIFilter): void {
+  const tuiFilters = { ...(loadSettings().tuiFilters ?? {}), [profileName()]: filter };
+  saveSettings({ tuiFilters });
+}
+
+export function loadCachedData(enabledSources: Set<string>, filterKey = ""): TUIData | null {
+  // The cache holds your own data; never mix it with a --home analysis
+  if (readOnlyHome()) return null;
   try {
     if (!existsSync(CACHE_FILE)) {
       return null;
@@ -102,7 +186,7 @@ export function loadCachedData(enabledSources: Set<string>): TUIData | null {
     
     const cached: CachedTUIData = JSON.parse(readFileSync(CACHE_FILE, "utf-8"));
     
-    if (!sourcesMatch(enabledSources, cached.enabledSources)) {
+    if (!sourcesMatch(enabledSources, cached.enabledSources) || (cached.filterKey ?? "") !== filterKey) {
       return null;
     }
     
@@ -119,7 +203,8 @@ export function loadCachedData(enabledSources: Set<string>): TUIData | null {
   }
 }
 
-export function saveCachedData(data: TUIData, enabledSources: Set<string>): void {
+export function saveCachedData(data: TUIData, enabledSources: Set<string>, filterKey = ""): void {
+  if (readOnlyHome()) return;
   try {
     if (!existsSync(CACHE_DIR)) {
       mkdirSync(CACHE_DIR, { recursive: true });
@@ -129,6 +214,7 @@ export function saveCachedData(data: TUIData, enabledSources: Set<string>): void
     const cached: CachedTUIData = {
       timestamp: Date.now(),
       enabledSources: Array.from(enabledSources),
+      filterKey,
       data: {
         ...data,
         dailyBreakdowns: serializableBreakdowns,
@@ -140,7 +226,7 @@ export function saveCachedData(data: TUIData, enabledSources: Set<string>): void
   }
 }
 
-export function isCacheStale(enabledSources: Set<string>): boolean {
+export function isCacheStale(enabledSources: Set<string>, filterKey = ""): boolean {
   try {
     if (!existsSync(CACHE_FILE)) {
       return true;
@@ -149,7 +235,7 @@ export function isCacheStale(enabledSources: Set<string>): boolean {
     const cached: CachedTUIData = JSON.parse(readFileSync(CACHE_FILE, "utf-8"));
     const cacheAge = Date.now() - cached.timestamp;
     
-    if (!sourcesMatch(enabledSources, cached.enabledSources)) {
+    if (!sourcesMatch(enabledSources, cached.enabledSources) || (cached.filterKey ?? "") !== filterKey) {
       return true;
     }
     
@@ -13,16 +13,20 @@ import type {
   ChartDataPoint,
   LoadingPhase,
   DailyModelBreakdown,
+  RecentActivity,
+  SessionEntry,
 } from "../types/index.js";
 import {
   parseLocalSourcesAsync,
   finalizeReportAndGraphAsync,
   type ParsedMessages,
+  type ReportAndGraph,
 } from "../../native.js";
 
 import { syncCursorCache, loadCursorCredentials } from "../../cursor.js";
+import { readOnlyHome } from "../../read-only.js";
 import { getModelColor } from "../utils/colors.js";
-import { loadCachedData, saveCachedData, isCacheStale, loadSettings } from "../config/settings.js";
+import { loadCachedData, saveCachedData, isCacheStale, loadSettings, DEFAULT_IDLE_THRESHOLD_MINUTES } from "../config/settings.js";
 
 export type {
   SortType,
@@ -35,12 +39,21 @@ export type {
   TotalBreakdown,
   TUIData,
   LoadingPhase,
+  SessionEntry,
 };
 
 export interface DateFilters {
   since?: string;
   until?: string;
   year?: string;
+  /** Comma-separated model globs, e.g. "claude-*-4, gpt-4o" */
+  model?: string;
+}
+
+/** Cache key for data loaded with `filters` */
+function filterKey(filters?: DateFilters): string {
+  const { since, until, year, model } = filters ?? {};
+  return since || until || year || model ? JSON.stringify({ since, until, year, model }) : "";
 }
 
 function buildContributionGrid(contributions: ContributionDay[]): GridCell[][] {
@@ -105,7 +118,11 @@ function calculatePeakHour(messages: Array<{ timestamp: number }>): string {
   return `${displayHour}${suffix}`;
 }
 
-function calculateLongestSession(messages: Array<{ sessionId: string; timestamp: number }>): string {
+/**
+ * Longest active time in one session. Gaps between messages longer than the
+ * idle threshold are time away, so an overnight-open session isn't 14 hours.
+ */
+function calculateLongestSession(messages: Array<{ sessionId: string; timestamp: number }>, idleThresholdMs: number): string {
   if (messages.length === 0) return "N/A";
   
   const sessions = new Map<string, number[]>();
@@ -121,9 +138,12 @@ function calculateLongestSession(messages: Array<{ sessionId: string; timestamp:
   let maxDurationMs = 0;
   for (const [, timestamps] of sessions) {
     if (timestamps.length < 2) continue;
-    const minTs = Math.min(...timestamps);
-    const maxTs = Math.max(...timestamps);
-    const duration = maxTs - minTs;
+    timestamps.sort((a, b) => a - b);
+    let duration = 0;
+    for (let i = 1; i < timestamps.length; i++) {
+      const gap = timestamps[i] - timestamps[i - 1];
+      if (gap <= idleThresholdMs) duration += gap;
+    }
     if (duration > maxDurationMs) {
       maxDurationMs = duration;
     }
@@ -143,23 +163,37 @@ function calculateLongestSession(messages: Array<{ sessionId: string; timestamp:
   return `${totalSeconds}s`;
 }
 
-async function loadData(
-  enabledSources: Set<SourceType>, 
+interface NativeData {
+  result: ReportAndGraph;
+  localMessages: ParsedMessages | null;
+}
+
+/**
+ * Parse the enabled sources and build the report, graph, recent usage and
+ * sessions in one native call. With `syncCursor` false the Cursor cache is
+ * used as last synced.
+ */
+async function loadNativeData(
+  enabledSources: Set<SourceType>,
   dateFilters?: DateFilters,
-  setPhase?: (phase: LoadingPhase) => void
-): Promise<TUIData> {
+  setPhase?: (phase: LoadingPhase) => void,
+  syncCursor = true
+): Promise<NativeData> {
   const sources = Array.from(enabledSources);
   const localSources = sources.filter(s => s !== "cursor");
   const includeCursor = sources.includes("cursor");
-  const { since, until, year } = dateFilters ?? {};
+  const { since, until, year, model } = dateFilters ?? {};
 
   setPhase?.("parsing-sources");
   
   const phase1Results = await Promise.allSettled([
-    includeCursor && loadCursorCredentials() ? syncCursorCache() : Promise.resolve({ synced: false, rows: 0 }),
+    // With --home, read that directory's cursor-cache instead of syncing your own
+    readOnlyHome() || !syncCursor
+      ? Promise.resolve({ synced: true, rows: 0 })
+      : includeCursor && loadCursorCredentials() ? syncCursorCache() : Promise.resolve({ synced: false, rows: 0 }),
     localSources.length > 0
-      ? parseLocalSourcesAsync({ sources: localSources as ("opencode" | "claude" | "codex" | "gemini" | "amp" | "droid")[], since, until, year })
-      : Promise.resolve({ messages: [], opencodeCount: 0, claudeCount: 0, codexCount: 0, geminiCount: 0, ampCount: 0, droidCount: 0, processingTimeMs: 0 } as ParsedMessages),
+      ? parseLocalSourcesAsync({ sources: localSources as ("opencode" | "claude" | "codex" | "gemini" | "amp" | "droid" | "cline" | "roocode")[] })
+      : Promise.resolve({ messages: [], opencodeCount: 0, claudeCount: 0, codexCount: 0, geminiCount: 0, ampCount: 0, droidCount: 0, clineCount: 0, roocodeCount: 0, recordedCount: 0, processingTimeMs: 0 } as ParsedMessages),
   ]);
 
   const cursorSync = phase1Results[0].status === "fulfilled" 
@@ -177,18 +211,42 @@ async function loadData(
     geminiCount: 0,
     ampCount: 0,
     droidCount: 0,
+    clineCount: 0,
+    roocodeCount: 0,
+    recordedCount: 0,
     processingTimeMs: 0,
   };
 
   setPhase?.("finalizing-report");
   // Single call ensures consistent pricing between report and graph
-  const { report, graph } = await finalizeReportAndGraphAsync({
+  const result = await finalizeReportAndGraphAsync({
     localMessages: localMessages || emptyMessages,
     includeCursor: includeCursor && cursorSync.synced,
     since,
     until,
     year,
+    model,
+    recentGranularity: "15m",
   });
+  return { result, localMessages };
+}
+
+/** Native report, graph and sessions for an export of what the TUI shows */
+export async function loadExportData(
+  enabledSources: Set<SourceType>,
+  dateFilters?: DateFilters
+): Promise<ReportAndGraph> {
+  const { result } = await loadNativeData(enabledSources, dateFilters, undefined, false);
+  return result;
+}
+
+async function loadData(
+  enabledSources: Set<SourceType>, 
+  dateFilters?: DateFilters,
+  setPhase?: (phase: LoadingPhase) => void
+): Promise<TUIData> {
+  const { result, localMessages } = await loadNativeData(enabledSources, dateFilters, setPhase);
+  const { report, graph, recent: recentUsage, sessions } = result;
 
   const settings = loadSettings();
   const allModelEntries: ModelEntry[] = report.entries.map(e => ({
@@ -315,7 +373,7 @@ async function loadData(
     favoriteModel,
     totalTokens: report.totalInput + report.totalOutput + report.totalCacheRead + report.totalCacheWrite,
     sessions: report.totalMessages,
-    longestSession: calculateLongestSession(localMessages?.messages || []),
+    longestSession: calculateLongestSession(localMessages?.messages || [], (settings.idleThresholdMinutes ?? DEFAULT_IDLE_THRESHOLD_MINUTES) * 60 * 1000),
     currentStreak,
     longestStreak,
     activeDays: dailyEntries.length,
@@ -416,6 +474,13 @@ async function loadData(
     });
   }
 
+  const recent: RecentActivity = {
+    bucketMinutes: recentUsage.bucketMinutes,
+    buckets: recentUsage.buckets.map(b => ({ start: b.start, tokens: b.tokens, cost: b.cost })),
+    costPerHour: recentUsage.costPerHour,
+    tokensPerHour: recentUsage.tokensPerHour,
+  };
+
   return {
     modelEntries,
     dailyEntries,
@@ -428,13 +493,16 @@ async function loadData(
     chartData,
     topModels,
     dailyBreakdowns,
+    recent,
+    sessions,
   };
 }
 
-export function useData(enabledSources: Accessor<Set<SourceType>>, dateFilters?: DateFilters) {
+export function useData(enabledSources: Accessor<Set<SourceType>>, dateFilters: Accessor<DateFilters> = () => ({})) {
   const initialSources = enabledSources();
-  const initialCachedData = loadCachedData(initialSources);
-  const initialCacheIsStale = initialCachedData ? isCacheStale(initialSources) : true;
+  const initialKey = filterKey(dateFilters());
+  const initialCachedData = loadCachedData(initialSources, initialKey);
+  const initialCacheIsStale = initialCachedData ? isCacheStale(initialSources, initialKey) : true;
   
   const [data, setData] = createSignal<TUIData | null>(initialCachedData);
   const [loading, setLoading] = createSignal(!initialCachedData);
@@ -459,13 +527,14 @@ export function useData(enabledSources: Accessor<Set<SourceType>>, dateFilters?:
     setRefreshTrigger(prev => prev + 1);
   };
 
-  const doLoad = (sources: Set<SourceType>, skipCacheCheck = false) => {
+  const doLoad = (sources: Set<SourceType>, filters: DateFilters, skipCacheCheck = false) => {
     ++currentRequestId; // Invalidate any in-flight requests immediately
     const shouldSkipCache = skipCacheCheck || forceRefresh();
+    const key = filterKey(filters);
     
     if (!shouldSkipCache) {
-      const cachedData = loadCachedData(sources);
-      const cacheIsStale = isCacheStale(sources);
+      const cachedData = loadCachedData(sources, key);
+      const cacheIsStale = isCacheStale(sources, key);
       
       if (cachedData && !cacheIsStale) {
         setData(cachedData);
@@ -492,11 +561,11 @@ export function useData(enabledSources: Accessor<Set<SourceType>>, dateFilters?:
     
     const requestId = currentRequestId;
     setError(null);
-    loadData(sources, dateFilters, setLoadingPhase)
+    loadData(sources, filters, setLoadingPhase)
       .then((freshData) => {
         if (requestId !== currentRequestId) return;
         setData(freshData);
-        saveCachedData(freshData, sources);
+        saveCachedData(freshData, sources, key);
       })
       .catch((e: unknown) => {
         if (requestId !== currentRequestId) return;
@@ -515,15 +584,15 @@ export function useData(enabledSources: Accessor<Set<SourceType>>, dateFilters?:
   };
 
   if (initialCachedData && initialCacheIsStale) {
-    doLoad(initialSources, true);
+    doLoad(initialSources, dateFilters(), true);
   } else if (!initialCachedData) {
-    doLoad(initialSources, false);
+    doLoad(initialSources, dateFilters(), false);
   }
 
   createEffect(on(
-    () => [enabledSources(), refreshTrigger()] as const,
-    ([sources]) => {
-      doLoad(sources);
+    () => [enabledSources(), dateFilters(), refreshTrigger()] as const,
+    ([sources, filters]) => {
+      doLoad(sources, filters);
     },
     { defer: true }
   ));
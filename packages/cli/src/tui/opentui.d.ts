@@ -30,6 +30,8 @@ declare module "@opentui/core" {
   
   export interface KeyEvent {
     name: string;
+    /** Raw characters the key produced */
+    sequence: string;
     eventType: "press" | "release";
     repeated?: boolean;
     ctrl?: boolean;
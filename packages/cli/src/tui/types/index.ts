@@ -1,8 +1,8 @@
 import type { ColorPaletteName } from "../config/themes.js";
 
-export type TabType = "overview" | "model" | "daily" | "stats";
+export type TabType = "overview" | "model" | "daily" | "sessions" | "stats";
 export type SortType = "cost" | "tokens" | "date";
-export type SourceType = "opencode" | "claude" | "codex" | "cursor" | "gemini" | "amp" | "droid";
+export type SourceType = "opencode" | "claude" | "codex" | "cursor" | "gemini" | "amp" | "droid" | "cline" | "roocode";
 
 export type { ColorPaletteName };
 
@@ -85,6 +85,34 @@ export interface ChartDataPoint {
   total: number;
 }
 
+export interface RecentBucket {
+  start: number;
+  tokens: number;
+  cost: number;
+}
+
+export interface RecentActivity {
+  bucketMinutes: number;
+  /** Oldest first; the last bucket holds now */
+  buckets: RecentBucket[];
+  costPerHour: number;
+  tokensPerHour: number;
+}
+
+export interface SessionEntry {
+  source: string;
+  sessionId: string;
+  project?: string;
+  models: string[];
+  start: number;
+  end: number;
+  messageCount: number;
+  tokens: number;
+  cost: number;
+  /** Cost in equal slices of the session's span, oldest first */
+  timeline: number[];
+}
+
 export interface TUIData {
   modelEntries: ModelEntry[];
   dailyEntries: DailyEntry[];
@@ -97,6 +125,10 @@ export interface TUIData {
   chartData: ChartDataPoint[];
   topModels: ModelWithPercentage[];
   dailyBreakdowns: Map<string, DailyModelBreakdown>;
+  /** Missing in data cached by older versions */
+  recent?: RecentActivity;
+  /** Most recent first; missing in data cached by older versions */
+  sessions?: SessionEntry[];
 }
 
 export interface TUISettings {
@@ -138,6 +170,8 @@ export interface TUIOptions {
   until?: string;
   year?: string;
   colorPalette?: ColorPaletteName;
+  /** Start in live mode, refreshing as session files change */
+  live?: boolean;
 }
 
 
@@ -162,7 +196,9 @@ export const SOURCE_LABELS: Record<SourceType, string> = {
   gemini: "GM",
   amp: "AM",
   droid: "DR",
+  cline: "CL",
+  roocode: "RC",
 } as const;
 
-export const TABS: readonly TabType[] = ["overview", "model", "daily", "stats"] as const;
-export const ALL_SOURCES: readonly SourceType[] = ["opencode", "claude", "codex", "cursor", "gemini", "amp", "droid"] as const;
+export const TABS: readonly TabType[] = ["overview", "model", "daily", "sessions", "stats"] as const;
+export const ALL_SOURCES: readonly SourceType[] = ["opencode", "claude", "codex", "cursor", "gemini", "amp", "droid", "cline", "roocode"] as const;
@@ -0,0 +1,24 @@
+/**
+ * Fuzzy match score of `query` against `text`, or null when the query's
+ * characters don't all appear in order. Case-insensitive; consecutive
+ * matches and matches at word starts score higher.
+ */
+export function fuzzyScore(query: string, text: string): number | null {
+  const q = query.toLowerCase();
+  const t = text.toLowerCase();
+  if (!q) return 0;
+
+  let score = 0;
+  let run = 0;
+  let ti = 0;
+  for (const ch of q) {
+    if (ch === " ") continue;
+    const found = t.indexOf(ch, ti);
+    if (found === -1) return null;
+    run = found === ti ? run + 1 : 1;
+    score += run;
+    if (found === 0 || /[\s/\-_.:]/.test(t[found - 1])) score += 2;
+    ti = found + 1;
+  }
+  return score;
+}
@@ -0,0 +1,26 @@
+/**
+ * Verbose diagnostics
+ *
+ * `--verbose` (or TOKSCALE_VERBOSE=1) prints pricing fetch telemetry to
+ * stderr once prices are loaded: per source, cache hits and misses, requests,
+ * retries, failures and time on the network versus parsing. It helps tell
+ * why a startup is slow.
+ *
+ * Like --plain, the flag is accepted anywhere and removed from argv here. It
+ * is passed to the native core as TOKSCALE_VERBOSE, which report subprocesses
+ * inherit.
+ */
+
+const VERBOSE_FLAG = "--verbose";
+
+const verbose = process.argv.includes(VERBOSE_FLAG);
+
+if (verbose) {
+  process.env.TOKSCALE_VERBOSE = "1";
+  process.argv = process.argv.filter((arg) => arg !== VERBOSE_FLAG);
+}
+
+export function isVerbose(): boolean {
+  const value = process.env.TOKSCALE_VERBOSE;
+  return value !== undefined && value !== "" && value !== "0" && value.toLowerCase() !== "false";
+}
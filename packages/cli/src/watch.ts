@@ -0,0 +1,113 @@
+/**
+ * Watch session data for changes
+ *
+ * Agents append to their session files as they run. Live views (the TUI's
+ * live mode) watch each source's session directory with fs.watch and call
+ * back once writes settle. Editors, lock files and atime updates also fire
+ * events, so a change only counts when the native data version (paths, sizes
+ * and modification times of the session files) moved.
+ *
+ * Where recursive fs.watch isn't available, or a directory can't be watched,
+ * the data version is polled instead.
+ *
+ * Without the TUI (`--plain`), live mode reprints the report on each change
+ * instead; see rerunOnChange.
+ */
+
+import { watch, type FSWatcher } from "node:fs";
+import { getDataVersion, getSourceRoots } from "./native.js";
+
+/** Wait this long after the last event before checking for changes */
+const DEBOUNCE_MS = 500;
+/** Polling interval when directories can't be watched */
+const POLL_MS = 2000;
+
+/**
+ * Call `onChange` whenever session files of `sources` (all when undefined)
+ * change. Returns a function that stops watching.
+ */
+export function watchSessionData(sources: string[] | undefined, onChange: () => void): () => void {
+  let version = getDataVersion(sources);
+  let timer: ReturnType<typeof setTimeout> | null = null;
+
+  const check = () => {
+    timer = null;
+    const next = getDataVersion(sources);
+    // Without a data version (older native builds) every event counts
+    if (next === null || next !== version) {
+      version = next;
+      onChange();
+    }
+  };
+  const schedule = () => {
+    if (timer) clearTimeout(timer);
+    timer = setTimeout(check, DEBOUNCE_MS);
+  };
+
+  const watchers: FSWatcher[] = [];
+  let poll: ReturnType<typeof setInterval> | null = null;
+  const startPolling = () => {
+    if (!poll && version !== null) poll = setInterval(check, POLL_MS);
+  };
+  for (const root of getSourceRoots(sources)) {
+    try {
+      const watcher = watch(root, { recursive: true, persistent: false }, schedule);
+      watcher.on("error", () => {
+        watcher.close();
+        startPolling();
+      });
+      watchers.push(watcher);
+    } catch {
+      startPolling();
+    }
+  }
+  // Nothing to watch yet: a source's directory appears on its first session
+  if (watchers.length === 0) {
+    startPolling();
+  }
+
+  return () => {
+    if (timer) clearTimeout(timer);
+    if (poll) clearInterval(poll);
+    for (const watcher of watchers) watcher.close();
+  };
+}
+
+/**
+ * Run `render` now and again after session files of `sources` change, one
+ * run at a time: changes during a run start one more run after it. Returns
+ * a function that stops watching.
+ */
+export function rerunOnChange(
+  sources: string[] | undefined,
+  render: () => Promise<void>,
+  watchData: typeof watchSessionData = watchSessionData
+): () => void {
+  let running = false;
+  let pending = false;
+  let stopped = false;
+
+  const run = async () => {
+    if (running) {
+      pending = true;
+      return;
+    }
+    running = true;
+    do {
+      pending = false;
+      try {
+        await render();
+      } catch (error) {
+        console.error(error instanceof Error ? error.message : String(error));
+      }
+    } while (pending && !stopped);
+    running = false;
+  };
+
+  const stop = watchData(sources, () => void run());
+  void run();
+  return () => {
+    stopped = true;
+    stop();
+  };
+}
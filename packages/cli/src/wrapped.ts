@@ -63,6 +63,8 @@ const SOURCE_DISPLAY_NAMES: Record<string, string> = {
   gemini: "Gemini CLI",
   cursor: "Cursor IDE",
   amp: "Amp",
+  cline: "Cline",
+  roocode: "Roo Code",
 };
 
 const ASSETS_BASE_URL = "https://tokscale.ai/assets/logos";
@@ -210,8 +212,8 @@ async function ensureFontsLoaded(): Promise<void> {
 
 async function loadWrappedData(options: WrappedOptions): Promise<WrappedData> {
   const year = options.year || new Date().getFullYear().toString();
-  const sources = options.sources || ["opencode", "claude", "codex", "gemini", "cursor", "amp", "droid"];
-  const localSources = sources.filter(s => s !== "cursor") as ("opencode" | "claude" | "codex" | "gemini" | "amp" | "droid")[];
+  const sources = options.sources || ["opencode", "claude", "codex", "gemini", "cursor", "amp", "droid", "cline", "roocode"];
+  const localSources = sources.filter(s => s !== "cursor") as ("opencode" | "claude" | "codex" | "gemini" | "amp" | "droid" | "cline" | "roocode")[];
   const includeCursor = sources.includes("cursor");
 
   const since = `${year}-01-01`;
@@ -220,8 +222,8 @@ async function loadWrappedData(options: WrappedOptions): Promise<WrappedData> {
   const phase1Results = await Promise.allSettled([
     includeCursor && loadCursorCredentials() ? syncCursorCache() : Promise.resolve({ synced: false, rows: 0 }),
     localSources.length > 0
-      ? parseLocalSourcesAsync({ sources: localSources, since, until, year })
-      : Promise.resolve({ messages: [], opencodeCount: 0, claudeCount: 0, codexCount: 0, geminiCount: 0, ampCount: 0, droidCount: 0, processingTimeMs: 0 } as ParsedMessages),
+      ? parseLocalSourcesAsync({ sources: localSources })
+      : Promise.resolve({ messages: [], opencodeCount: 0, claudeCount: 0, codexCount: 0, geminiCount: 0, ampCount: 0, droidCount: 0, clineCount: 0, roocodeCount: 0, recordedCount: 0, processingTimeMs: 0 } as ParsedMessages),
   ]);
 
   const cursorSync = phase1Results[0].status === "fulfilled" 
@@ -239,6 +241,9 @@ async function loadWrappedData(options: WrappedOptions): Promise<WrappedData> {
     geminiCount: 0,
     ampCount: 0,
     droidCount: 0,
+    clineCount: 0,
+    roocodeCount: 0,
+    recordedCount: 0,
     processingTimeMs: 0,
   };
 
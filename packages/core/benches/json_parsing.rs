@@ -1,6 +1,6 @@
 //! Benchmark: simd-json vs serde_json parsing performance
 //!
-//! Run with: cargo bench --features noop
+//! Run with: cargo bench -p tokscale-core
 
 use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
 use serde::Deserialize;
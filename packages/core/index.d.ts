@@ -1,5 +1,419 @@
 /* auto-generated by NAPI-RS */
 /* eslint-disable */
+export interface ActivityOptions {
+  homeDir?: string
+  sources?: Array<string>
+  since?: string
+  until?: string
+  year?: string
+  /**
+   * Gaps between messages longer than this many minutes are idle time
+   * (default: `idleThresholdMinutes` from settings, else 30)
+   */
+  idleMinutes?: number
+  /**
+   * Only sessions with this tag ("expensive", "token-heavy",
+   * "long-running" or "agent-heavy")
+   */
+  tag?: string
+}
+
+export interface ActivityReport {
+  /** Longest active time first */
+  sessions: Array<SessionActivity>
+  sessionCount: number
+  activeHours: number
+  /** Hours from first to last message, idle gaps included */
+  wallHours: number
+  cost: number
+  costPerActiveHour: number
+  idleThresholdMinutes: number
+  processingTimeMs: number
+}
+
+/** Whether an API key is configured, and where */
+export declare function apiKeyStatus(name: string): ApiKeyStatus
+
+/** Where an API key was found, without the key itself */
+export interface ApiKeyStatus {
+  name: string
+  /** "keychain" or "env"; unset when no key was found */
+  backend?: string
+  /** Environment variable checked when the keychain has no key */
+  envVar: string
+}
+
+export interface BranchReport {
+  /** Most expensive first */
+  entries: Array<BranchUsage>
+  /** Messages from sources that don't record a branch, or from a detached checkout */
+  unattributedMessages: number
+  unattributedCost: number
+  totalCost: number
+  processingTimeMs: number
+}
+
+/** Usage on one branch of one project */
+export interface BranchUsage {
+  branch: string
+  /** Repository root, when the sessions recorded their working directory */
+  project?: string
+  name?: string
+  sources: Array<string>
+  input: number
+  output: number
+  cacheRead: number
+  cacheWrite: number
+  reasoning: number
+  messageCount: number
+  sessionCount: number
+  /** First and last day with usage on the branch (YYYY-MM-DD) */
+  firstDate: string
+  lastDate: string
+  cost: number
+}
+
+export interface BundleMessage {
+  /** Hashed message ID, for sources that record one */
+  id?: string
+  /** Milliseconds since the session's first message */
+  offsetMs: number
+  model: string
+  provider: string
+  tokens: TokenBreakdown
+  cost: number
+  /** `cost` was billed by the provider rather than estimated */
+  billed: boolean
+  /** Written by a sub-agent */
+  sidechain: boolean
+  toolCalls: number
+  /** Timestamp was synthesized rather than read from the session file */
+  estimatedTimestamp: boolean
+}
+
+export interface BundleSession {
+  /** Hashed session ID */
+  id: string
+  source: string
+  /** Sorted */
+  models: Array<string>
+  /** First and last message, Unix milliseconds */
+  start: number
+  end: number
+  messageCount: number
+  tokens: TokenBreakdown
+  cost: number
+}
+
+/** Spend against one budget in its current period, projected to period end */
+export interface BudgetStatus {
+  name: string
+  /** "day", "week" or "month" */
+  period: string
+  /** USD per period */
+  amount: number
+  /** First and last day of the current period (YYYY-MM-DD, UTC) */
+  periodStart: string
+  periodEnd: string
+  spent: number
+  /** Burn rate: cost per hour over the last 24 hours */
+  costPerHour: number
+  /** Spend at period end if the burn rate holds */
+  projectedCost: number
+  /**
+   * When the budget will be exceeded at the burn rate (RFC 3339); unset
+   * when that is after period end or it already is
+   */
+  projectedBreach?: string
+  /** "ok", "projected" (will be exceeded before period end) or "exceeded" */
+  state: string
+}
+
+export interface CacheCleanEntry {
+  path: string
+  bytes: number
+  /** "orphaned-temp" or "expired" */
+  reason: string
+}
+
+export interface CacheCleanOptions {
+  /** Report what would be removed without deleting anything */
+  dryRun?: boolean
+}
+
+export interface CacheCleanReport {
+  cacheDir: string
+  dryRun: boolean
+  removed: Array<CacheCleanEntry>
+  bytesFreed: number
+  parseEntriesPruned: number
+  totalBytes: number
+}
+
+/** What this build supports */
+export interface Capabilities {
+  version: string
+  features: Array<string>
+  /** Session sources that can be parsed */
+  sources: Array<string>
+  /** Price sources `lookupPricing` can be forced to */
+  pricingProviders: Array<string>
+  exportFormats: Array<string>
+  /** Reports with a JSON Schema (see `getReportSchema`) */
+  reportKinds: Array<string>
+  reportSchemaVersion: string
+}
+
+/**
+ * Features, sources, pricing providers and export formats compiled into this
+ * build, so wrappers can adapt to the installed version
+ */
+export declare function capabilities(): Capabilities
+
+/**
+ * Remove orphaned temp files, stale checkpoints and parse-cache entries for
+ * deleted session files from the cache directory
+ */
+export declare function cleanCache(options: CacheCleanOptions): CacheCleanReport
+
+export interface ChartPoint {
+  /** Unix milliseconds */
+  timestamp: number
+  value: number
+}
+
+export interface ChartSeries {
+  metric: string
+  method: string
+  points: Array<ChartPoint>
+  /** Number of points before downsampling (one per message) */
+  totalPoints: number
+  processingTimeMs: number
+}
+
+/** Options for a downsampled chart series */
+export interface ChartSeriesOptions {
+  homeDir?: string
+  sources?: Array<string>
+  since?: string
+  until?: string
+  year?: string
+  excludeEstimatedTimestamps?: boolean
+  /** Maximum number of points to return (default 500) */
+  points?: number
+  /** "tokens" (default) or "cost" */
+  metric?: string
+  /** "lttb" (default) or "max" (largest value per time bucket) */
+  method?: string
+}
+
+export interface CheckOptions {
+  homeDir?: string
+  sources?: Array<string>
+  since?: string
+  until?: string
+  year?: string
+  /** USD no day may cost more than */
+  maxDaily?: number
+  /** Share of messages (0-1) that may use models without a list price */
+  maxUnknownModelShare?: number
+}
+
+export interface CheckReport {
+  /** Every check passed */
+  passed: boolean
+  checks: Array<ThresholdCheck>
+  /** Messages checked */
+  messages: number
+}
+
+/**
+ * Find lines in ~/.config/tokscale/recorded/ that no longer parse, such as
+ * an event torn by a crash. Reports skip them; nothing is modified.
+ */
+export declare function checkRecorded(homeDir?: string | undefined | null): Array<DamagedRecord>
+
+/**
+ * Parse `paths` (files, or directories searched recursively) with the
+ * parser for `source` and report anything tokscale would lose or have to
+ * repair, so tools can check their log output stays readable
+ */
+export declare function checkParserConformance(source: string, paths: Array<string>): ConformanceReport
+
+/**
+ * Fetch each pricing source afresh, bypassing the cache. Provider status
+ * pages are checked when a fetch fails, or always with `providerStatus`, so
+ * a failure can be told apart from the provider being down.
+ */
+export declare function checkPricingHealth(providerStatus?: boolean | undefined | null): Promise<PricingHealth>
+
+/**
+ * Check settings.json (or `path`) for unknown keys, mistyped values and
+ * wildcards in substring patterns, with line and column for each
+ */
+export declare function checkSettings(path?: string | undefined | null): SettingsCheck
+
+/** Agent usage charged to one commit */
+export interface CommitCost {
+  /** Repository root */
+  project: string
+  name: string
+  hash: string
+  author: string
+  subject: string
+  /** Commit time in Unix milliseconds */
+  timestamp: number
+  messageCount: number
+  sessionCount: number
+  cost: number
+}
+
+export interface CommitCostOptions {
+  homeDir?: string
+  sources?: Array<string>
+  since?: string
+  until?: string
+  year?: string
+  /**
+   * How long before a commit a message may be and still be charged to it
+   * (default: 24)
+   */
+  windowHours?: number
+  /** Only consider commits whose author matches (as `git log --author`) */
+  author?: string
+}
+
+export interface CommitCostReport {
+  /** Newest first; commits no usage was charged to are left out */
+  commits: Array<CommitCost>
+  /** Git repositories the usage ran in */
+  repositories: number
+  committedCost: number
+  meanCostPerCommit: number
+  medianCostPerCommit: number
+  /** Usage in a repository with no commit within the window after it */
+  uncommittedMessages: number
+  uncommittedCost: number
+  /** Usage without a working directory, or outside any git repository */
+  unattributedMessages: number
+  unattributedCost: number
+  processingTimeMs: number
+}
+
+/**
+ * Compare models by blended price per million tokens. `inputRatio` is the
+ * share of input tokens (default 0.75).
+ */
+export declare function comparePricing(modelIds: Array<string>, inputRatio?: number | undefined | null): Promise<RateComparison>
+
+export interface ConformanceFinding {
+  /** "error" (tokscale loses or repairs usage) or "warning" */
+  severity: string
+  /**
+   * Stable identifier: "schema-drift", "no-usage", "missing-model",
+   * "negative-tokens", "bad-timestamp", "estimated-timestamp",
+   * "empty-usage", "missing-session" or "unreadable"
+   */
+  code: string
+  /** File the finding is about; unset for the run as a whole */
+  file?: string
+  message: string
+  /** Messages (or fields) affected */
+  count: number
+}
+
+export interface ConformanceReport {
+  source: string
+  passed: boolean
+  fileCount: number
+  messageCount: number
+  /** Formats files were detected as, for sources with several */
+  formats: Array<string>
+  findings: Array<ConformanceFinding>
+}
+
+export interface ContextOptions {
+  homeDir?: string
+  sources?: Array<string>
+  since?: string
+  until?: string
+  year?: string
+  /**
+   * Share of the context window from which a message counts as near the
+   * limit (default: 0.9)
+   */
+  threshold?: number
+}
+
+/** How close one model's requests run to its context window */
+export interface ContextPressure {
+  model: string
+  /** From the pricing catalog */
+  contextWindow: number
+  messageCount: number
+  /** Messages at or past the threshold, up to the window */
+  nearLimitCount: number
+  /** Messages past the window; usually a longer window than the catalog lists */
+  overLimitCount: number
+  /** Share of messages near or over the limit (0-1) */
+  nearLimitShare: number
+  /** Cost of messages near or over the limit */
+  nearLimitCost: number
+  /** Sessions with at least one message near or over the limit */
+  sessionsAffected: number
+  /** Largest context (input + cache read + cache write) of one message */
+  peakContext: number
+  averageContext: number
+}
+
+export interface ContextPressureReport {
+  threshold: number
+  /** Most messages near or over the limit first */
+  models: Array<ContextPressure>
+  /** Messages of models with a known context window */
+  messageCount: number
+  nearLimitCount: number
+  overLimitCount: number
+  nearLimitCost: number
+  /** Models the pricing catalog lists no context window for */
+  modelsWithoutWindow: Array<string>
+  processingTimeMs: number
+}
+
+/** Turns in one bucket of prompt length, tool calls or files touched */
+export interface CostDriverBucket {
+  /** e.g. "500-1,999 chars", "3-9 calls", "1 file" */
+  label: string
+  turnCount: number
+  messageCount: number
+  tokens: number
+  cost: number
+  /** Cost per turn */
+  averageCost: number
+  /** Share of the cost of all bucketed turns (0-1) */
+  share: number
+}
+
+export interface CostDriverReport {
+  /** Ascending; empty buckets are left out */
+  promptLength: Array<CostDriverBucket>
+  toolCalls: Array<CostDriverBucket>
+  /** Turns from sources that log file paths only */
+  filesTouched: Array<CostDriverBucket>
+  turnCount: number
+  attributedCost: number
+  /**
+   * Messages outside any turn: sources that don't log prompts, and
+   * responses before a session's first prompt
+   */
+  unattributedMessages: number
+  unattributedCost: number
+  processingTimeMs: number
+}
+
+/** Create a serve-mode API token; the returned token is not stored and cannot be shown again */
+export declare function createServeToken(name: string, scope: string, homeDir?: string | undefined | null): string
+
 /** Daily contribution data */
 export interface DailyContribution {
   date: string
@@ -16,6 +430,31 @@ export interface DailyTotals {
   messages: number
 }
 
+/** Unreadable line in a recorded-events file */
+export interface DamagedRecord {
+  file: string
+  /** 1-based */
+  line: number
+  reason: string
+}
+
+/** Decrypt team data with the contents of an age key file */
+/** What a report is missing */
+export interface Degradation {
+  /** Any of the gaps below */
+  degraded: boolean
+  /** "fresh", "stale", "archived" (`--as-of`) or "unavailable" */
+  pricing: string
+  /** Why prices could not be loaded, when unavailable */
+  pricingError?: string
+  /** Sources asked for by name that have no session files */
+  missingSources: Array<string>
+  /** Session files that yielded no usage: empty, unreadable or corrupt */
+  skippedFiles: number
+}
+
+export declare function decryptForTeam(data: Buffer, identities: string): Buffer
+
 /** Data summary statistics */
 export interface DataSummary {
   totalTokens: number
@@ -28,6 +467,135 @@ export interface DataSummary {
   models: Array<string>
 }
 
+/** One model or project on either side of a diff */
+export interface DiffEntry {
+  /** Model id, or project root */
+  key: string
+  /** Model id, or the project's directory name */
+  name: string
+  fromCost: number
+  toCost: number
+  costChange: number
+  /** Share of that side's total cost (0-1) */
+  fromShare: number
+  toShare: number
+  fromTokens: number
+  toTokens: number
+  fromMessages: number
+  toMessages: number
+  /** "added", "removed", "changed" or "unchanged" */
+  status: string
+}
+
+export interface DiffOptions {
+  homeDir?: string
+  sources?: Array<string>
+  /** Year, month or day: "2025", "2025-05" or "2025-05-03" */
+  from: string
+  to: string
+  excludeEstimatedTimestamps?: boolean
+}
+
+/** Compare two exported reports (`models --json` or `projects --json` output) */
+export declare function diffReportExports(fromJson: string, toJson: string, fromLabel: string, toLabel: string): UsageDiff
+
+export interface DiffTotals {
+  cost: number
+  tokens: number
+  messages: number
+}
+
+/** What one model actually cost per million tokens over a period */
+export interface EffectiveRate {
+  model: string
+  provider: string
+  /** Sources the model was used from */
+  sources: Array<string>
+  /** Input, cache read and cache write tokens */
+  inputTokens: number
+  /** Output and reasoning tokens */
+  outputTokens: number
+  trainingTokens: number
+  /** Share of input tokens that were cache reads */
+  cacheReadShare: number
+  cost: number
+  /** Cost over all tokens, in USD per million */
+  effectivePerMillion: number
+  /** Input-side cost over input tokens; unset when the model has no price */
+  inputPerMillion?: number
+  /** Output-side cost over output tokens; unset when the model has no price */
+  outputPerMillion?: number
+  listInputPerMillion?: number
+  listOutputPerMillion?: number
+  messageCount: number
+}
+
+export interface EffectiveRateReport {
+  /** Most expensive first */
+  entries: Array<EffectiveRate>
+  totalTokens: number
+  totalCost: number
+  effectivePerMillion: number
+  processingTimeMs: number
+}
+
+export interface EfficiencyOptions {
+  homeDir?: string
+  sources?: Array<string>
+  since?: string
+  until?: string
+  year?: string
+  /**
+   * Gaps between messages longer than this many minutes are idle time
+   * (default: `idleThresholdMinutes` from settings, else 30)
+   */
+  idleMinutes?: number
+  /** Sessions with fewer messages aren't compared (default 3) */
+  minMessages?: number
+  /** Sessions with less active time aren't compared (default 5) */
+  minActiveMinutes?: number
+  /** Only compare usage of models in this family, e.g. "claude-sonnet" */
+  family?: string
+}
+
+export interface EfficiencyReport {
+  /** Cheapest per active hour first */
+  entries: Array<SourceEfficiency>
+  idleThresholdMinutes: number
+  minMessages: number
+  minActiveMinutes: number
+  family?: string
+  processingTimeMs: number
+}
+
+/** Encrypt data to one or more team public keys ("age1...") */
+export declare function encryptForTeam(data: Buffer, recipients: Array<string>): Buffer
+
+/**
+ * Run the `enforce` hook of each budget exceeded in its current period and
+ * not enforced yet in it. Returns the runs, which are also logged.
+ */
+export declare function enforceBudgets(homeDir?: string | undefined | null): Promise<Array<EnforcementEvent>>
+
+/** One run of a budget's `enforce` hook, as recorded in enforcements.jsonl */
+export interface EnforcementEvent {
+  budget: string
+  /** First day of the period the cap was enforced for */
+  periodStart: string
+  /** USD spent and the budget amount when the hook ran */
+  spent: number
+  amount: number
+  command: string
+  /** When the hook ran (RFC 3339) */
+  ranAt: string
+  /** Unset when the hook couldn't start, timed out or was killed */
+  exitCode?: number
+  /** End of the hook's stdout and stderr */
+  output: string
+  /** Why the run failed; unset when the hook exited with 0 */
+  error?: string
+}
+
 /** Finalize graph */
 export declare function finalizeGraph(options: FinalizeGraphOptions): Promise<GraphResult>
 
@@ -49,6 +617,8 @@ export interface FinalizeMonthlyOptions {
   since?: string
   until?: string
   year?: string
+  /** Reproduce the report as of this date (YYYY-MM-DD) */
+  asOf?: string
 }
 
 /** Finalize monthly report */
@@ -71,17 +641,159 @@ export interface FinalizeReportOptions {
   since?: string
   until?: string
   year?: string
+  /** Bucket width for recent usage: "hour" (default) or "15m" */
+  recentGranularity?: string
+  /**
+   * Days of recent usage to bucket (default: `recentBucketDays` from
+   * settings, else 2)
+   */
+  recentDays?: number
+  /**
+   * Reproduce the report as of this date (YYYY-MM-DD): prices from the
+   * price history, no messages after it. `finalizeReport` only.
+   */
+  asOf?: string
+  /**
+   * Only models matching these comma-separated globs (e.g. "claude-*,
+   * gpt-4o"). `finalizeReportAndGraph` only.
+   */
+  model?: string
+  /** Also roll entries up by model family. `finalizeReport` only. */
+  families?: boolean
 }
 
+/**
+ * Format a cost using the rounding, precision and cents settings, so every
+ * frontend displays amounts the same way
+ */
+export declare function formatCost(cost: number): string
+
+/** Format a USD-per-million-tokens rate, e.g. "$3.00 / 1M tokens" */
+export declare function formatPricePerMillion(rate: number): string
+
+/** Daily contributions as an Arrow IPC stream, one row per (date, source, model) */
+export declare function generateGraphArrow(options: ReportOptions): Promise<Buffer>
+
+/**
+ * Generate graph data with pricing, checkpointing progress so an interrupted
+ * first ingest of a large history resumes instead of starting over
+ */
+export declare function generateGraphResumable(options: ReportOptions): Promise<GraphResult>
+
 /** Generate graph data with pricing calculation */
 export declare function generateGraphWithPricing(options: ReportOptions): Promise<GraphResult>
 
+/** Generate a team key pair (same format as `age-keygen`) */
+export declare function generateTeamKey(): TeamKey
+
+/** Status of every budget in settings.json */
+export declare function getBudgetStatus(homeDir?: string | undefined | null): Promise<Array<BudgetStatus>>
+
+/**
+ * Usage per git branch, keyed by project so same-named branches in
+ * different repositories stay apart. Shows what developing a feature
+ * branch cost.
+ */
+export declare function getBranchReport(options: ReportOptions): Promise<BranchReport>
+
+/** Per-message time series downsampled to at most `points` points */
+export declare function getChartSeries(options: ChartSeriesOptions): Promise<ChartSeries>
+
+/**
+ * Estimated agent cost per commit. Each message is charged to the first
+ * commit in the same repository at or after it, read from the local git
+ * history.
+ */
+export declare function getCommitCosts(options: CommitCostOptions): Promise<CommitCostReport>
+
+/**
+ * Messages whose context approaches or exceeds the model's context window,
+ * per model, with the windows from pricing catalog metadata
+ */
+export declare function getContextPressure(options: ContextOptions): Promise<ContextPressureReport>
+
+/**
+ * Cost of turns (a prompt and the responses to it) by prompt length, tool
+ * calls and files touched. Prompts are only measured, never kept.
+ */
+export declare function getCostDrivers(options: ReportOptions): Promise<CostDriverReport>
+
+/**
+ * Changes whenever a session file of the requested sources is added,
+ * removed or written (by path, size and modification time). Only scans
+ * directories, so it is cheap enough to poll, and lets a file watcher skip
+ * events that don't touch session data.
+ */
+export declare function getDataVersion(options: SourceScanOptions): string
+
+/**
+ * Realized USD per million tokens per model (total cost over total tokens,
+ * also split into input and output), next to list prices
+ */
+export declare function getEffectiveRates(options: ReportOptions): Promise<EffectiveRateReport>
+
+/**
+ * Sources ranked by cost per active hour, with cost per session, over
+ * comparable sessions only
+ */
+export declare function getEfficiencyLeaderboard(options: EfficiencyOptions): Promise<EfficiencyReport>
+
+/**
+ * Parse and price all messages, returned as an Arrow IPC stream
+ * (one row per message) for `tableFromIPC` in apache-arrow
+ */
+export declare function getMessagesArrow(options: ReportOptions): Promise<Buffer>
+
 /** Get model usage report with pricing calculation */
 export declare function getModelReport(options: ReportOptions): Promise<ModelReport>
 
 /** Get monthly usage report with pricing calculation */
 export declare function getMonthlyReport(options: ReportOptions): Promise<MonthlyReport>
 
+/**
+ * Cache hits and misses, fetches, requests, retries and failures per pricing
+ * source in this process, with time spent on the network versus parsing
+ */
+export declare function getPricingTelemetry(): Array<PricingTelemetry>
+
+/**
+ * Usage per project. Sessions are grouped by the git repository their
+ * working directory is in, so subdirectories, monorepo packages and linked
+ * worktrees count toward the same project.
+ */
+export declare function getProjectReport(options: ReportOptions): Promise<ProjectReport>
+
+/**
+ * Hourly or 15-minute usage over the last few days, and the current burn
+ * rate
+ */
+export declare function getRecentUsage(options: RecentUsageOptions): Promise<RecentUsage>
+
+/** JSON Schema for a `--json` report: "models", "monthly" or "graph" */
+export declare function getReportSchema(kind: string): any
+
+/**
+ * Active hours and cost per active hour. Gaps between messages longer than
+ * the idle threshold don't count, so a session left open overnight counts
+ * only the time it was being used.
+ */
+export declare function getSessionActivity(options: ActivityOptions): Promise<ActivityReport>
+
+/**
+ * One statement per provider per month, ordered by month then provider,
+ * with a line item per model
+ */
+export declare function getStatements(options: StatementOptions): Promise<Array<Statement>>
+
+/** Compare totals, model mix and per-project usage between two periods */
+export declare function getUsageDiff(options: DiffOptions): Promise<UsageDiff>
+
+/**
+ * Spend split between interactive chat and agentic use (tool loops,
+ * sub-agents and automated bursts), per source
+ */
+export declare function getUsageModes(options: ReportOptions): Promise<UsageModeReport>
+
 /** Metadata about the graph generation */
 export interface GraphMeta {
   generatedAt: string
@@ -102,6 +814,23 @@ export interface GraphResult {
 /** Simple health check to verify the native module is working */
 export declare function healthCheck(): string
 
+/** Generation that couldn't be imported */
+export interface ImportFailure {
+  id: string
+  reason: string
+}
+
+/**
+ * Fetch OpenRouter generations (native tokens and billed cost) and record
+ * them. Uses the "openrouter" API key (see `setSecret`).
+ */
+export declare function importOpenrouterGenerations(options: OpenRouterImportOptions): Promise<OpenRouterImportResult>
+
+/** Read a bundle written by `shareSession` */
+export declare function inspectSessionBundle(json: string): SessionBundle
+
+export declare function listServeTokens(homeDir?: string | undefined | null): Array<ServeToken>
+
 /** Options for parsing local sources only (no Cursor) */
 export interface LocalParseOptions {
   homeDir?: string
@@ -109,9 +838,40 @@ export interface LocalParseOptions {
   since?: string
   until?: string
   year?: string
+  /** Drop messages whose timestamp was synthesized (e.g. older Amp threads) */
+  excludeEstimatedTimestamps?: boolean
 }
 
-export declare function lookupPricing(modelId: string, provider?: string | undefined | null): Promise<PricingLookupResult>
+/**
+ * Look up pricing for a model.
+ *
+ * `provider` forces a pricing source ("litellm" or "openrouter"). `backend` is
+ * the provider the model ran on (e.g. "vertex_ai", "gemini"); when set and no
+ * source is forced, provider-scoped LiteLLM keys are preferred.
+ */
+export declare function lookupPricing(modelId: string, provider?: string | undefined | null, backend?: string | undefined | null): Promise<PricingLookupResult>
+
+/** Usage of one model family, with its model versions to drill down into */
+export interface ModelFamily {
+  provider: string
+  family: string
+  /** Entries of the family's models, most expensive first */
+  models: Array<ModelUsage>
+  input: number
+  output: number
+  cacheRead: number
+  cacheWrite: number
+  reasoning: number
+  messageCount: number
+  /** USD charged, after free tiers and credits */
+  cost: number
+  /** USD covered by free tiers and promotional credits */
+  covered: number
+  /** Fraction of the report's cost, 0 to 1 */
+  costShare: number
+  /** Fraction of the report's tokens, 0 to 1 */
+  tokenShare: number
+}
 
 /** Model report result */
 export interface ModelReport {
@@ -120,9 +880,16 @@ export interface ModelReport {
   totalOutput: number
   totalCacheRead: number
   totalCacheWrite: number
+  totalReasoning: number
   totalMessages: number
   totalCost: number
+  /** USD covered by free tiers and promotional credits */
+  totalCovered: number
   processingTimeMs: number
+  /** What went into the report */
+  summary?: RunSummary
+  /** Entries rolled up by model family, when asked for */
+  families?: Array<ModelFamily>
 }
 
 /** Model usage summary for reports */
@@ -135,15 +902,31 @@ export interface ModelUsage {
   cacheRead: number
   cacheWrite: number
   reasoning: number
+  /** Fine-tuning training tokens */
+  training: number
   messageCount: number
+  /** USD charged, after free tiers and credits */
   cost: number
+  /** USD covered by free tiers and promotional credits */
+  covered: number
+  /** Fraction of the report's cost, 0 to 1 */
+  costShare: number
+  /** Fraction of the report's tokens, 0 to 1 */
+  tokenShare: number
 }
 
 /** Monthly report result */
 export interface MonthlyReport {
   entries: Array<MonthlyUsage>
+  totalInput: number
+  totalOutput: number
+  totalCacheRead: number
+  totalCacheWrite: number
+  totalMessages: number
   totalCost: number
   processingTimeMs: number
+  /** What went into the report */
+  summary?: RunSummary
 }
 
 /** Monthly usage summary */
@@ -156,6 +939,10 @@ export interface MonthlyUsage {
   cacheWrite: number
   messageCount: number
   cost: number
+  /** Fraction of the report's cost, 0 to 1 */
+  costShare: number
+  /** Fraction of the report's tokens, 0 to 1 */
+  tokenShare: number
 }
 
 export interface NativePricing {
@@ -163,6 +950,57 @@ export interface NativePricing {
   outputCostPerToken: number
   cacheReadInputTokenCost?: number
   cacheCreationInputTokenCost?: number
+  trainingCostPerToken?: number
+  /** USD per million input tokens */
+  inputPerMillion: number
+  /** USD per million output tokens */
+  outputPerMillion: number
+  cacheReadPerMillion?: number
+  cacheWritePerMillion?: number
+  /** USD per million tokens at the default 3:1 input:output mix */
+  blendedPerMillion?: number
+}
+
+/** A field whose type no longer matched what a session parser expects */
+/** Options for `importOpenrouterGenerations` */
+export interface OpenRouterImportOptions {
+  homeDir?: string
+  /** Generation IDs to import, besides those the proxy recorded */
+  ids?: Array<string>
+  /** Text to find generation IDs in, such as OpenRouter's activity CSV */
+  idsText?: string
+}
+
+export interface OpenRouterImportResult {
+  imported: number
+  /** Imported before */
+  skipped: number
+  failed: Array<ImportFailure>
+}
+
+export interface ParseDiagnostic {
+  source: string
+  /** Path of the field, e.g. "message/usage/input_tokens" */
+  field: string
+  expected: string
+  found: string
+  /** Number of occurrences */
+  count: number
+}
+
+export interface ParseDiagnostics {
+  drift: Array<ParseDiagnostic>
+  formats: Array<ParseFormat>
+  pricingConflicts: Array<PricingConflict>
+  timestampCorrections: Array<TimestampCorrection>
+}
+
+/** Files parsed as one detected format (e.g. Amp "usage-ledger") */
+export interface ParseFormat {
+  source: string
+  format: string
+  /** Number of files */
+  count: number
 }
 
 export interface ParsedMessage {
@@ -177,7 +1015,15 @@ export interface ParsedMessage {
   cacheRead: number
   cacheWrite: number
   reasoning: number
+  /** Fine-tuning training tokens */
+  training?: number
   agent?: string
+  /** Timestamp was synthesized rather than read from the session file */
+  estimatedTimestamp?: boolean
+  /** Working directory, for sources that record it */
+  cwd?: string
+  /** Cost the provider billed (an imported OpenRouter generation), used instead of pricing */
+  billedCost?: number
 }
 
 /** Result of parsing local sources (excludes Cursor - it's network-synced) */
@@ -189,7 +1035,14 @@ export interface ParsedMessages {
   geminiCount: number
   ampCount: number
   droidCount: number
+  clineCount: number
+  roocodeCount: number
+  recordedCount: number
   processingTimeMs: number
+  /** Session files per source scanned, for the run summary */
+  files?: Array<SourceFiles>
+  /** Messages dropped as duplicates */
+  deduped?: number
 }
 
 /**
@@ -198,19 +1051,288 @@ export interface ParsedMessages {
  */
 export declare function parseLocalSources(options: LocalParseOptions): ParsedMessages
 
+/**
+ * Load prices into the on-disk cache, fetching them if it is stale. The CLI
+ * runs this while it parses local sessions, so the step that prices
+ * messages afterwards reads a fresh cache instead of waiting on the network.
+ */
+export declare function preloadPricing(): Promise<void>
+
+/** A model LiteLLM and OpenRouter price differently (USD per token) */
+export interface PricingConflict {
+  modelId: string
+  litellmKey: string
+  openrouterKey: string
+  litellmInput?: number
+  litellmOutput?: number
+  openrouterInput?: number
+  openrouterOutput?: number
+  /** Source whose prices were used, per the `pricingMerge` setting */
+  chosen: string
+}
+
+export interface PricingHealth {
+  sources: Array<PricingSourceCheck>
+  /** Checked when a fetch failed or when asked for; empty otherwise */
+  providers: Array<ProviderStatus>
+}
+
 export interface PricingLookupResult {
   modelId: string
   matchedKey: string
+  matchedPrefix?: string
   source: string
   pricing: NativePricing
 }
 
+export interface PricingSourceCheck {
+  /** "litellm" or "openrouter" */
+  name: string
+  /** Whether pricing is unavailable without it */
+  required: boolean
+  ok: boolean
+  /** Models in the fetched table */
+  modelCount: number
+  error?: string
+  durationMs: number
+  /** When the cached table in use was fetched (RFC 3339), if there is one */
+  cachedAt?: string
+  /** For a failed fetch with provider status checked: whose side it failed on */
+  diagnosis?: string
+}
+
+/** Pricing fetch counters for one source, since the process started */
+export interface PricingTelemetry {
+  source: string
+  cacheHits: number
+  cacheMisses: number
+  /** Tables fetched (or attempted) because the cache had none */
+  fetches: number
+  failures: number
+  requests: number
+  retries: number
+  /** Response bytes downloaded */
+  bytes: number
+  /** Wall-clock time of fetches */
+  fetchMs: number
+  /** Waiting on responses, summed over requests (which overlap for OpenRouter) */
+  networkMs: number
+  /** Decoding JSON, summed over responses */
+  parseMs: number
+}
+
+/** Options for `runProxy` */
+export interface ProjectReport {
+  /** Most expensive first */
+  entries: Array<ProjectUsage>
+  /** Messages from sources that don't record a working directory */
+  unattributedMessages: number
+  unattributedCost: number
+  totalCost: number
+  processingTimeMs: number
+}
+
+/** Usage for one repository, or one directory outside any repository */
+export interface ProjectUsage {
+  /** Repository root the sessions' working directories resolved to */
+  project: string
+  name: string
+  /** Working directories recorded by the sessions */
+  paths: Array<string>
+  sources: Array<string>
+  input: number
+  output: number
+  cacheRead: number
+  cacheWrite: number
+  reasoning: number
+  messageCount: number
+  cost: number
+}
+
+/** A provider's status page, or a probe of its API */
+export interface ProviderStatus {
+  /** "GitHub", "OpenRouter", "Anthropic", "OpenAI" */
+  name: string
+  /** Pricing source this provider serves ("litellm", "openrouter"), if any */
+  pricingSource?: string
+  url: string
+  /** "operational", "degraded", "outage" or "unreachable" */
+  state: string
+  description: string
+}
+
+export interface ProxyOptions {
+  /** Interface to listen on (default: 127.0.0.1) */
+  host?: string
+  /** Port to listen on (default: 8787) */
+  port?: number
+  /** Base URL for OpenAI-format requests (default: https://api.openai.com) */
+  openaiUpstream?: string
+  /** Base URL for Anthropic requests (default: https://api.anthropic.com) */
+  anthropicUpstream?: string
+  homeDir?: string
+}
+
+/** Options for `pushUsage` */
+export interface PushOptions {
+  /** Base URL of a `tokscale serve` server, e.g. https://usage.example.com */
+  server: string
+  /** Push-scope token; defaults to the "team-server" secret */
+  token?: string
+  /** Events per request (default 500) */
+  batchSize?: number
+  homeDir?: string
+  sources?: Array<string>
+  since?: string
+}
+
+export interface PushResult {
+  /** Events the server recorded */
+  recorded: number
+  /** Events the server already had */
+  skipped: number
+  /** Events older than the server's watermark, not sent */
+  unsent: number
+  batches: number
+  /** Server watermark after the push (Unix milliseconds) */
+  watermark?: number
+}
+
+/**
+ * Send local usage to a team server, uploading only what it hasn't
+ * acknowledged yet. An interrupted push continues where it stopped when run
+ * again.
+ */
+export declare function pushUsage(options: PushOptions): Promise<PushResult>
+
+export interface RateComparison {
+  /** Cheapest first */
+  rows: Array<RateComparisonRow>
+  /** Models with no input price */
+  unpriced: Array<string>
+  /** The rows as an aligned text table */
+  table: string
+}
+
+export interface RateComparisonRow {
+  modelId: string
+  /** Price table key the model resolved to */
+  matchedKey: string
+  /** USD per million blended tokens */
+  blendedPerMillion: number
+  /** Blended rate over the cheapest model's; 1 for the cheapest */
+  relative: number
+}
+
+export interface RecentBucket {
+  /** Bucket start, Unix milliseconds */
+  start: number
+  tokens: number
+  messageCount: number
+  cost: number
+}
+
+export interface RecentUsage {
+  /** "hour" or "15m" */
+  granularity: string
+  bucketMinutes: number
+  days: number
+  /** Oldest first, empty buckets included; the last one holds now */
+  buckets: Array<RecentBucket>
+  /** Cost over the last 60 minutes */
+  costPerHour: number
+  /** Tokens over the last 60 minutes */
+  tokensPerHour: number
+  processingTimeMs: number
+}
+
+export interface RecentUsageOptions {
+  homeDir?: string
+  sources?: Array<string>
+  /** "hour" (default) or "15m" */
+  granularity?: string
+  /**
+   * Days to bucket, up to 14 (default: `recentBucketDays` from settings,
+   * else 2)
+   */
+  days?: number
+}
+
+/**
+ * Compare a provider billing export with the costs tokscale computed for
+ * the same days, per day and model
+ */
+export declare function reconcileInvoice(options: ReconcileOptions): Promise<ReconciliationReport>
+
+export interface ReconcileOptions {
+  /** Billing CSV exported from the provider's console */
+  csv: string
+  /** "anthropic" or "openai"; detected from the CSV header when unset */
+  provider?: string
+  homeDir?: string
+  sources?: Array<string>
+  /** Relative difference tolerated before a line is flagged (default 0.05) */
+  tolerance?: number
+  /** Differences up to this many USD are never flagged (default 0.01) */
+  minDifference?: number
+}
+
+/** Billed and computed cost for one (day, model) */
+export interface ReconciliationLine {
+  /** UTC day, YYYY-MM-DD */
+  date: string
+  /** Model without its date suffix */
+  model: string
+  billed: number
+  computed: number
+  /** Computed minus billed; negative when tokscale under-counts */
+  difference: number
+  mismatch: boolean
+}
+
+export interface ReconciliationReport {
+  provider: string
+  /** First and last billed day */
+  since?: string
+  until?: string
+  /** Ordered by date then model */
+  lines: Array<ReconciliationLine>
+  totalBilled: number
+  totalComputed: number
+  mismatches: number
+}
+
+/** Record NDJSON usage events under ~/.config/tokscale/recorded/ */
+export declare function recordEvents(ndjson: string, homeDir?: string | undefined | null): RecordResult
+
+/** Rejected line from `recordEvents` */
+export interface RecordError {
+  /** 1-based line number within the input */
+  line: number
+  message: string
+}
+
+export interface RecordResult {
+  recorded: number
+  errors: Array<RecordError>
+}
+
+/** Re-read settings.json if it changed since it was last loaded */
+export declare function reloadSettings(): SettingsReload
+
 /** Combined result for report and graph (single pricing lookup) */
 export interface ReportAndGraph {
   report: ModelReport
   graph: GraphResult
+  /** Fine-grained buckets for the last few days, ignoring date filters */
+  recent: RecentUsage
+  /** Every session in the date range, most recent first */
+  sessions: Array<SessionEntry>
 }
 
+/** Semver of the report schema, printed as `schemaVersion` in `--json` output */
+export declare function reportSchemaVersion(): string
+
 /** Options for reports */
 export interface ReportOptions {
   homeDir?: string
@@ -218,6 +1340,233 @@ export interface ReportOptions {
   since?: string
   until?: string
   year?: string
+  /** Drop messages whose timestamp was synthesized (e.g. older Amp threads) */
+  excludeEstimatedTimestamps?: boolean
+}
+
+/** Revoke a serve-mode API token; false if there was none by that name */
+export declare function revokeServeToken(name: string, homeDir?: string | undefined | null): boolean
+
+/** How a report's numbers came about, so they can be judged */
+export interface RunSummary {
+  /** Sources scanned and their session files */
+  sources: Array<SourceFiles>
+  /** Messages in the report */
+  messages: number
+  /** Messages dropped as duplicates of ones already counted */
+  messagesDeduped: number
+  /**
+   * Models without a list price, whose messages cost nothing unless
+   * their source reported a cost
+   */
+  unknownModels: Array<string>
+  unknownModelMessages: number
+  /**
+   * When the price tables were fetched, in seconds since the epoch;
+   * absent for archived prices (`--as-of`)
+   */
+  pricingFetchedAt?: number
+  pricingAgeHours?: number
+  /** Prices fetched more than a day ago */
+  pricingStale: boolean
+  /** USD billed by providers (imported usage), before free tiers */
+  exactCost: number
+  /** USD estimated from token counts and list prices, before free tiers */
+  estimatedCost: number
+  /** Share of the cost that was billed, 0-1 */
+  exactCostShare: number
+  /** What the report is missing */
+  degradation: Degradation
+}
+
+/** Check usage against CI thresholds */
+export declare function runChecks(options: CheckOptions): Promise<CheckReport>
+
+/** Run the metering proxy; resolves only if it fails */
+export declare function runProxy(options: ProxyOptions): Promise<void>
+
+/** Serve reports and accept recorded events over HTTP; resolves only if it fails */
+export declare function runServer(options: ServeOptions): Promise<void>
+
+/**
+ * One session's usage as a redacted bundle: token counts, costs and timing,
+ * without content, paths or raw IDs
+ */
+export declare function shareSession(options: ShareSessionOptions): Promise<SessionBundle>
+
+/** List sources with file counts, total size and date ranges, without parsing */
+export declare function scanSources(options: SourceScanOptions): Array<SourceSummary>
+
+/** One provider's usage in one calendar month */
+export interface Statement {
+  provider: string
+  /** YYYY-MM */
+  month: string
+  /** Most expensive first */
+  lines: Array<StatementLine>
+  messageCount: number
+  input: number
+  output: number
+  cacheRead: number
+  cacheWrite: number
+  reasoning: number
+  totalCost: number
+}
+
+/** Usage of one model on a statement, with the list rates it is priced at */
+export interface StatementLine {
+  model: string
+  messageCount: number
+  input: number
+  output: number
+  cacheRead: number
+  cacheWrite: number
+  reasoning: number
+  /** List rates in USD per million tokens; unset when the model has no price */
+  inputPerMillion?: number
+  outputPerMillion?: number
+  cacheReadPerMillion?: number
+  cacheWritePerMillion?: number
+  cost: number
+  /** Fraction of the statement's cost, 0 to 1 */
+  costShare: number
+}
+
+export interface StatementOptions {
+  homeDir?: string
+  sources?: Array<string>
+  since?: string
+  until?: string
+  year?: string
+  /** Only statements for this provider id, e.g. "anthropic" */
+  provider?: string
+  excludeEstimatedTimestamps?: boolean
+}
+
+/** All statements as one CSV, a row per line item and a "Total" row per statement */
+export declare function statementsToCsv(statements: Array<Statement>): string
+
+/** A statement as a Markdown document, ready to convert to PDF */
+export declare function statementToMarkdown(statement: Statement): string
+
+/** Store an API key (e.g. for "openrouter") in the OS keychain */
+export declare function storeApiKey(name: string, key: string): void
+
+/**
+ * Schema drift and detected file formats seen by parsers, and pricing
+ * conflicts seen by lookups, since the last call. Drifted fields are coerced
+ * or skipped instead of dropping the whole record.
+ */
+export declare function takeParseDiagnostics(): ParseDiagnostics
+
+/** Options for `runServer` */
+export interface ServeOptions {
+  /** Interface to listen on (default: 127.0.0.1); other addresses need a token */
+  host?: string
+  /** Port to listen on (default: 8788) */
+  port?: number
+  /** Requests each client may make per minute (default: 120; 0 for no limit) */
+  rateLimitPerMinute?: number
+  /** Also write webhook events to stdout as NDJSON */
+  emitEvents?: boolean
+  homeDir?: string
+}
+
+/** API token for serve mode, without its secret */
+export interface ServeToken {
+  name: string
+  /** "read", "push" or "admin" */
+  scope: string
+  createdAt: string
+}
+
+export interface SessionActivity {
+  source: string
+  sessionId: string
+  /** First and last message, Unix milliseconds */
+  start: number
+  end: number
+  /** `end - start` */
+  wallMs: number
+  /** Wall time minus idle gaps */
+  activeMs: number
+  messageCount: number
+  cost: number
+  /** All tokens, cache included */
+  tokens: number
+  /** Messages written by sub-agents */
+  subAgentMessages: number
+  /** Outlier tags from the `sessionTags` thresholds in settings */
+  tags: Array<string>
+}
+
+/** One session, redacted for sharing */
+export interface SessionBundle {
+  /** Always "tokscale-session-bundle" */
+  format: string
+  version: number
+  /** tokscale version that wrote the bundle */
+  tokscaleVersion: string
+  /** RFC 3339 */
+  createdAt: string
+  session: BundleSession
+  /** Oldest first */
+  messages: Array<BundleMessage>
+}
+
+/** One session in the TUI session browser */
+export interface SessionEntry {
+  source: string
+  sessionId: string
+  /**
+   * Repository (or directory) name, for sources that record the working
+   * directory
+   */
+  project?: string
+  /** Sorted */
+  models: Array<string>
+  /** First and last message, Unix milliseconds */
+  start: number
+  end: number
+  messageCount: number
+  tokens: number
+  cost: number
+  /** Cost in equal slices of `start..=end`, oldest first */
+  timeline: Array<number>
+}
+
+export interface ShareSessionOptions {
+  homeDir?: string
+  sources?: Array<string>
+  /** Session ID, or an unambiguous prefix of one */
+  sessionId: string
+}
+
+export interface SettingsCheck {
+  path: string
+  exists: boolean
+  /** In file order */
+  issues: Array<SettingsIssue>
+}
+
+/** Problem found in settings.json */
+export interface SettingsIssue {
+  /** "error" (the file is rejected) or "warning" (the file loads) */
+  severity: string
+  /** Dotted key path, e.g. "costDisplay.precision"; empty for malformed JSON */
+  path: string
+  /** 1-based; 0 when unknown */
+  line: number
+  column: number
+  message: string
+}
+
+/** Outcome of re-reading settings.json */
+export interface SettingsReload {
+  /** "unchanged", "applied" or "rejected" */
+  status: string
+  /** Why the file was rejected; the previous settings stay in effect */
+  error?: string
 }
 
 /** Source contribution for a specific day */
@@ -230,6 +1579,87 @@ export interface SourceContribution {
   messages: number
 }
 
+/** What a session and an hour of work cost in one source */
+export interface SourceEfficiency {
+  source: string
+  /** 1 for the lowest cost per active hour; unset without comparable sessions */
+  rank?: number
+  /** Comparable sessions */
+  sessions: number
+  /** Sessions too short to compare */
+  excludedSessions: number
+  activeHours: number
+  cost: number
+  /** All tokens, cache included */
+  tokens: number
+  costPerSession: number
+  medianSessionCost: number
+  costPerActiveHour: number
+  costPerMillionTokens: number
+}
+
+/** Session files of one source */
+export interface SourceFiles {
+  source: string
+  files: number
+  /** Files that yielded messages */
+  parsed: number
+  /** Files that yielded none: empty, unreadable or without usage */
+  skipped: number
+  /** Asked for by name (`--claude`) rather than scanned by default */
+  named: boolean
+}
+
+/** Options for scanning source metadata */
+export interface SourceScanOptions {
+  homeDir?: string
+  sources?: Array<string>
+}
+
+/** Session data found for one source (timestamps are file modification times) */
+export interface SourceSummary {
+  source: string
+  path: string
+  exists: boolean
+  fileCount: number
+  totalBytes: number
+  earliestTimestamp?: number
+  latestTimestamp?: number
+}
+
+/** A team key pair in age format */
+export interface TeamKey {
+  /** "age1..."; share this with everyone who uploads */
+  publicKey: string
+  /** "AGE-SECRET-KEY-1..."; only the team's readers need it */
+  secretKey: string
+}
+
+/**
+ * Timestamps of one source corrected one way: "timezone" (read in the
+ * source's configured zone), "seconds" (scaled to milliseconds), "future"
+ * (moved back to now), or "no-offset" (no UTC offset and no zone set, so
+ * left as UTC)
+ */
+/** One limit and how usage measured up to it */
+export interface ThresholdCheck {
+  /** "max-daily" or "max-unknown-model-share" */
+  name: string
+  passed: boolean
+  limit: number
+  /** Highest day's cost, or the share of messages without a list price */
+  actual: number
+  /** Days over the limit, or the models without a list price */
+  detail?: string
+}
+
+export interface TimestampCorrection {
+  source: string
+  kind: string
+  /** Number of messages */
+  count: number
+}
+
 /** Token breakdown by type */
 export interface TokenBreakdown {
   input: number
@@ -237,11 +1667,57 @@ export interface TokenBreakdown {
   cacheRead: number
   cacheWrite: number
   reasoning: number
+  /** Fine-tuning training tokens, priced at the model's training rate */
+  training: number
+}
+
+/** Usage from one source in one mode */
+export interface UsageDiff {
+  fromLabel: string
+  toLabel: string
+  from: DiffTotals
+  to: DiffTotals
+  costChange: number
+  /** Unset when the first side cost nothing */
+  costChangePercent?: number
+  /** Largest cost change first; unset when a compared export has no models */
+  models?: Array<DiffEntry>
+  /** Largest cost change first; unset when a compared export has no projects */
+  projects?: Array<DiffEntry>
+}
+
+export interface UsageModeEntry {
+  source: string
+  /** "interactive" or "agentic" */
+  mode: string
+  input: number
+  output: number
+  cacheRead: number
+  cacheWrite: number
+  reasoning: number
+  messageCount: number
+  cost: number
+}
+
+export interface UsageModeReport {
+  /** Most expensive first */
+  entries: Array<UsageModeEntry>
+  interactiveCost: number
+  agenticCost: number
+  /** Agentic share of total cost (0-1) */
+  agenticShare: number
+  processingTimeMs: number
 }
 
 /** Version of the native module */
 export declare function version(): string
 
+/**
+ * Apply edits to settings.json without a restart by polling it every
+ * `intervalMs` (default 2000) on a background thread
+ */
+export declare function watchSettings(intervalMs?: number | undefined | null): void
+
 /** Year summary */
 export interface YearSummary {
   year: string
@@ -576,14 +576,70 @@ if (!nativeBinding) {
 }
 
 module.exports = nativeBinding
+module.exports.apiKeyStatus = nativeBinding.apiKeyStatus
+module.exports.capabilities = nativeBinding.capabilities
+module.exports.checkParserConformance = nativeBinding.checkParserConformance
+module.exports.checkPricingHealth = nativeBinding.checkPricingHealth
+module.exports.checkRecorded = nativeBinding.checkRecorded
+module.exports.checkSettings = nativeBinding.checkSettings
+module.exports.cleanCache = nativeBinding.cleanCache
+module.exports.comparePricing = nativeBinding.comparePricing
+module.exports.createServeToken = nativeBinding.createServeToken
+module.exports.decryptForTeam = nativeBinding.decryptForTeam
+module.exports.diffReportExports = nativeBinding.diffReportExports
+module.exports.encryptForTeam = nativeBinding.encryptForTeam
+module.exports.enforceBudgets = nativeBinding.enforceBudgets
 module.exports.finalizeGraph = nativeBinding.finalizeGraph
 module.exports.finalizeMonthlyReport = nativeBinding.finalizeMonthlyReport
 module.exports.finalizeReport = nativeBinding.finalizeReport
 module.exports.finalizeReportAndGraph = nativeBinding.finalizeReportAndGraph
+module.exports.formatCost = nativeBinding.formatCost
+module.exports.formatPricePerMillion = nativeBinding.formatPricePerMillion
+module.exports.generateGraphArrow = nativeBinding.generateGraphArrow
+module.exports.generateGraphResumable = nativeBinding.generateGraphResumable
 module.exports.generateGraphWithPricing = nativeBinding.generateGraphWithPricing
+module.exports.generateTeamKey = nativeBinding.generateTeamKey
+module.exports.getBranchReport = nativeBinding.getBranchReport
+module.exports.getBudgetStatus = nativeBinding.getBudgetStatus
+module.exports.getChartSeries = nativeBinding.getChartSeries
+module.exports.getCommitCosts = nativeBinding.getCommitCosts
+module.exports.getContextPressure = nativeBinding.getContextPressure
+module.exports.getCostDrivers = nativeBinding.getCostDrivers
+module.exports.getDataVersion = nativeBinding.getDataVersion
+module.exports.getEffectiveRates = nativeBinding.getEffectiveRates
+module.exports.getEfficiencyLeaderboard = nativeBinding.getEfficiencyLeaderboard
+module.exports.getMessagesArrow = nativeBinding.getMessagesArrow
 module.exports.getModelReport = nativeBinding.getModelReport
 module.exports.getMonthlyReport = nativeBinding.getMonthlyReport
+module.exports.getPricingTelemetry = nativeBinding.getPricingTelemetry
+module.exports.getProjectReport = nativeBinding.getProjectReport
+module.exports.getRecentUsage = nativeBinding.getRecentUsage
+module.exports.getReportSchema = nativeBinding.getReportSchema
+module.exports.getSessionActivity = nativeBinding.getSessionActivity
+module.exports.getStatements = nativeBinding.getStatements
+module.exports.getUsageDiff = nativeBinding.getUsageDiff
+module.exports.getUsageModes = nativeBinding.getUsageModes
 module.exports.healthCheck = nativeBinding.healthCheck
+module.exports.importOpenrouterGenerations = nativeBinding.importOpenrouterGenerations
+module.exports.inspectSessionBundle = nativeBinding.inspectSessionBundle
+module.exports.listServeTokens = nativeBinding.listServeTokens
 module.exports.lookupPricing = nativeBinding.lookupPricing
 module.exports.parseLocalSources = nativeBinding.parseLocalSources
+module.exports.preloadPricing = nativeBinding.preloadPricing
+module.exports.pushUsage = nativeBinding.pushUsage
+module.exports.reconcileInvoice = nativeBinding.reconcileInvoice
+module.exports.recordEvents = nativeBinding.recordEvents
+module.exports.reloadSettings = nativeBinding.reloadSettings
+module.exports.reportSchemaVersion = nativeBinding.reportSchemaVersion
+module.exports.revokeServeToken = nativeBinding.revokeServeToken
+module.exports.runChecks = nativeBinding.runChecks
+module.exports.runProxy = nativeBinding.runProxy
+module.exports.runServer = nativeBinding.runServer
+module.exports.scanSources = nativeBinding.scanSources
+module.exports.shareSession = nativeBinding.shareSession
+module.exports.statementsToCsv = nativeBinding.statementsToCsv
+module.exports.statementToMarkdown = nativeBinding.statementToMarkdown
+module.exports.storeApiKey = nativeBinding.storeApiKey
+module.exports.takeParseDiagnostics = nativeBinding.takeParseDiagnostics
 module.exports.version = nativeBinding.version
+module.exports.watchSettings = nativeBinding.watchSettings
@@ -0,0 +1,154 @@
+//! Session duration with idle time trimmed
+//!
+//! A session's wall-clock span (first to last message) overstates the time
+//! spent in it: a terminal left open overnight makes a 20 minute session look
+//! like 14 hours. Active time only adds up the gaps between consecutive
+//! messages that are at most the idle threshold; longer gaps are treated as
+//! the user being away.
+//!
+//! Sessions are also tagged when they are outliers; see
+//! [`crate::session_tags`].
+
+use crate::session_tags::TagThresholds;
+use crate::sessions::UnifiedMessage;
+use crate::{ActivityReport, SessionActivity};
+use std::collections::HashMap;
+
+/// Default idle threshold when neither the caller nor settings set one
+pub const DEFAULT_IDLE_MINUTES: f64 = 30.0;
+
+const MS_PER_MINUTE: f64 = 60_000.0;
+const MS_PER_HOUR: f64 = 3_600_000.0;
+
+/// Idle threshold to use: the caller's, else `idleThresholdMinutes` from
+/// settings, else the default. Non-positive values are ignored.
+pub fn idle_minutes(requested: Option<f64>) -> f64 {
+    let positive = |m: &f64| *m > 0.0;
+    requested
+        .filter(positive)
+        .or_else(|| crate::config::settings().idle_threshold_minutes.filter(positive))
+        .unwrap_or(DEFAULT_IDLE_MINUTES)
+}
+
+/// Sum of the gaps between consecutive `timestamps` (sorted) that are at
+/// most `idle_ms`
+pub fn active_ms(timestamps: &[i64], idle_ms: i64) -> i64 {
+    timestamps
+        .windows(2)
+        .map(|pair| pair[1] - pair[0])
+        .filter(|gap| *gap <= idle_ms)
+        .sum()
+}
+
+fn total_tokens(msg: &UnifiedMessage) -> i64 {
+    let t = &msg.tokens;
+    t.input + t.output + t.cache_read + t.cache_write + t.reasoning + t.training
+}
+
+/// Activity per session, longest active time first, tagged by `thresholds`
+pub fn sessions(messages: &[UnifiedMessage], idle_minutes: f64, thresholds: &TagThresholds) -> Vec<SessionActivity> {
+    let idle_ms = (idle_minutes * MS_PER_MINUTE) as i64;
+    let mut grouped: HashMap<(&str, &str), Vec<&UnifiedMessage>> = HashMap::new();
+    for msg in messages.iter().filter(|m| !m.session_id.is_empty()) {
+        grouped.entry((msg.source.as_str(), msg.session_id.as_str())).or_default().push(msg);
+    }
+
+    let mut sessions: Vec<SessionActivity> = grouped
+        .into_iter()
+        .map(|((source, session_id), messages)| {
+            let mut timestamps: Vec<i64> = messages.iter().map(|m| m.timestamp).collect();
+            timestamps.sort_unstable();
+            let start = timestamps[0];
+            let end = timestamps[timestamps.len() - 1];
+            let mut session = SessionActivity {
+                source: source.to_string(),
+                session_id: session_id.to_string(),
+                start,
+                end,
+                wall_ms: end - start,
+                active_ms: active_ms(&timestamps, idle_ms),
+                message_count: messages.len() as i32,
+                cost: messages.iter().map(|m| m.cost).sum(),
+                tokens: messages.iter().map(|m| total_tokens(m)).sum(),
+                sub_agent_messages: messages.iter().filter(|m| m.sidechain).count() as i32,
+                tags: Vec::new(),
+            };
+            session.tags = thresholds.tags(&session);
+            session
+        })
+        .collect();
+    sessions.sort_by(|a, b| b.active_ms.cmp(&a.active_ms).then_with(|| a.start.cmp(&b.start)));
+    sessions
+}
+
+/// Totals over `sessions`. Cost per hour is over active time; sessions with
+/// a single message (no measurable time) still count toward cost.
+pub fn report(sessions: Vec<SessionActivity>, idle_minutes: f64, processing_time_ms: u32) -> ActivityReport {
+    let active_ms: i64 = sessions.iter().map(|s| s.active_ms).sum();
+    let wall_ms: i64 = sessions.iter().map(|s| s.wall_ms).sum();
+    let cost: f64 = sessions.iter().map(|s| s.cost).sum();
+    let active_hours = active_ms as f64 / MS_PER_HOUR;
+    ActivityReport {
+        session_count: sessions.len() as i32,
+        active_hours,
+        wall_hours: wall_ms as f64 / MS_PER_HOUR,
+        cost,
+        cost_per_active_hour: if active_ms > 0 { cost / active_hours } else { 0.0 },
+        idle_threshold_minutes: idle_minutes,
+        sessions,
+        processing_time_ms,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::message;
+
+    const MINUTE: i64 = 60_000;
+    const T0: i64 = 1_740_823_200_000;
+
+    #[test]
+    fn test_active_time_skips_idle_gaps() {
+        let timestamps = [T0, T0 + 5 * MINUTE, T0 + 15 * MINUTE, T0 + 14 * 60 * MINUTE, T0 + 14 * 60 * MINUTE + 5 * MINUTE];
+        assert_eq!(active_ms(&timestamps, 30 * MINUTE), 20 * MINUTE);
+        assert_eq!(active_ms(&timestamps, 24 * 60 * MINUTE), timestamps[4] - T0);
+        assert_eq!(active_ms(&[T0], 30 * MINUTE), 0);
+    }
+
+    #[test]
+    fn test_overnight_session_is_not_fourteen_hours() {
+        let messages = vec![
+            message("s1", T0, 1.0),
+            message("s1", T0 + 20 * MINUTE, 1.0),
+            // Picked up again the next morning
+            message("s1", T0 + 14 * 60 * MINUTE, 1.0),
+            message("s1", T0 + 14 * 60 * MINUTE + 10 * MINUTE, 1.0),
+            message("s2", T0, 0.5),
+        ];
+
+        let sessions = sessions(&messages, 30.0, &TagThresholds::default());
+        assert_eq!(sessions.len(), 2);
+        assert_eq!(sessions[0].session_id, "s1");
+        assert_eq!(sessions[0].active_ms, 30 * MINUTE);
+        assert_eq!(sessions[0].wall_ms, 14 * 60 * MINUTE + 10 * MINUTE);
+        assert!(sessions[0].tags.is_empty(), "30 active minutes is not long-running");
+
+        let report = report(sessions, 30.0, 0);
+        assert!((report.active_hours - 0.5).abs() < 1e-9);
+        assert!((report.cost_per_active_hour - 9.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_sessions_are_tagged() {
+        let mut messages: Vec<UnifiedMessage> =
+            (0..5).map(|i| message("s1", T0 + i * MINUTE, 3.0).with_sidechain(i > 0)).collect();
+        messages.push(message("s2", T0, 0.5));
+        let thresholds = TagThresholds { sub_agent_messages: Some(4), ..Default::default() };
+
+        let sessions = sessions(&messages, 30.0, &thresholds);
+        assert_eq!(sessions[0].sub_agent_messages, 4);
+        assert_eq!(sessions[0].tags, vec!["expensive", "agent-heavy"]);
+        assert!(sessions[1].tags.is_empty());
+    }
+}
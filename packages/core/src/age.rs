@@ -0,0 +1,205 @@
+//! age encryption for team exports
+//!
+//! Exported aggregates can be encrypted to one or more team public keys, so
+//! whoever stores the file only ever sees ciphertext. The output is a standard
+//! age v1 file (<https://age-encryption.org/v1>): keys come from `age-keygen`
+//! and files decrypt with `age -d -i key.txt` as well as with [`decrypt`].
+//!
+//! This only covers exported files. `tokscale push` and `/api/sync` send
+//! plaintext NDJSON over the connection, so use HTTPS for those.
+//!
+//! The format itself is implemented by the `age` crate; this module keeps the
+//! small `Result<_, String>` surface the CLI and the Node bindings use.
+
+use age::secrecy::ExposeSecret;
+use std::fmt;
+use std::io::{Read, Write};
+
+/// Team public key ("age1...")
+#[derive(Clone, PartialEq, Eq)]
+pub struct Recipient(age::x25519::Recipient);
+
+/// Team private key ("AGE-SECRET-KEY-1...")
+pub struct Identity(age::x25519::Identity);
+
+impl Recipient {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        s.trim()
+            .parse()
+            .map(Recipient)
+            .map_err(|e| format!("not an age public key: {} ({})", s, e))
+    }
+}
+
+impl fmt::Display for Recipient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl fmt::Debug for Recipient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Recipient").field(&self.0.to_string()).finish()
+    }
+}
+
+impl Identity {
+    pub fn generate() -> Result<Self, String> {
+        Ok(Identity(age::x25519::Identity::generate()))
+    }
+
+    pub fn parse(s: &str) -> Result<Self, String> {
+        s.trim()
+            .parse()
+            .map(Identity)
+            .map_err(|e| format!("not an age secret key ({})", e))
+    }
+
+    pub fn recipient(&self) -> Result<Recipient, String> {
+        Ok(Recipient(self.0.to_public()))
+    }
+
+    pub fn to_secret_string(&self) -> String {
+        self.0.to_string().expose_secret().to_string()
+    }
+}
+
+/// Identities in an age key file; comments and blank lines are skipped
+pub fn parse_identities(text: &str) -> Result<Vec<Identity>, String> {
+    let identities = text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(Identity::parse)
+        .collect::<Result<Vec<_>, _>>()?;
+    if identities.is_empty() {
+        return Err("no age secret keys found".to_string());
+    }
+    Ok(identities)
+}
+
+/// Encrypt `plaintext` so any of `recipients` can decrypt it
+pub fn encrypt(plaintext: &[u8], recipients: &[Recipient]) -> Result<Vec<u8>, String> {
+    if recipients.is_empty() {
+        return Err("at least one recipient is required".to_string());
+    }
+    let encryptor = age::Encryptor::with_recipients(recipients.iter().map(|r| &r.0 as &dyn age::Recipient))
+        .map_err(|e| format!("age encryption failed: {}", e))?;
+
+    let mut out = Vec::with_capacity(plaintext.len() + 256);
+    let mut writer = encryptor
+        .wrap_output(&mut out)
+        .map_err(|e| format!("age encryption failed: {}", e))?;
+    writer
+        .write_all(plaintext)
+        .and_then(|_| writer.finish())
+        .map_err(|e| format!("age encryption failed: {}", e))?;
+    Ok(out)
+}
+
+/// Decrypt an age file with whichever of `identities` it was encrypted to
+pub fn decrypt(data: &[u8], identities: &[Identity]) -> Result<Vec<u8>, String> {
+    let decryptor = age::Decryptor::new(data).map_err(|e| format!("invalid age file: {}", e))?;
+    let mut reader = decryptor
+        .decrypt(identities.iter().map(|i| &i.0 as &dyn age::Identity))
+        .map_err(|e| format!("age decryption failed: {}", e))?;
+
+    let mut plaintext = Vec::new();
+    reader
+        .read_to_end(&mut plaintext)
+        .map_err(|e| format!("age decryption failed: {}", e))?;
+    Ok(plaintext)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    const CHUNK_SIZE: usize = 64 * 1024;
+
+    fn team() -> (Identity, Recipient) {
+        let identity = Identity::generate().unwrap();
+        let recipient = identity.recipient().unwrap();
+        (identity, recipient)
+    }
+
+    #[test]
+    fn test_key_strings_round_trip() {
+        let (identity, recipient) = team();
+        let public = recipient.to_string();
+        let secret = identity.to_secret_string();
+        assert!(public.starts_with("age1"));
+        assert!(secret.starts_with("AGE-SECRET-KEY-1"));
+
+        assert_eq!(Recipient::parse(&public).unwrap(), recipient);
+        assert_eq!(Identity::parse(&secret).unwrap().recipient().unwrap(), recipient);
+        assert!(Recipient::parse(&secret).is_err());
+
+        let key_file = format!("# created: 2025-01-01\n# public key: {}\n{}\n", public, secret);
+        assert_eq!(parse_identities(&key_file).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let (identity, recipient) = team();
+        for size in [0, 1, 1000, CHUNK_SIZE, CHUNK_SIZE + 1, 3 * CHUNK_SIZE - 7] {
+            let plaintext: Vec<u8> = (0..size).map(|i| (i % 251) as u8).collect();
+            let encrypted = encrypt(&plaintext, std::slice::from_ref(&recipient)).unwrap();
+            assert!(encrypted.starts_with(b"age-encryption.org/v1\n-> X25519 "));
+            assert_eq!(decrypt(&encrypted, std::slice::from_ref(&identity)).unwrap(), plaintext, "size {}", size);
+        }
+    }
+
+    #[test]
+    fn test_any_recipient_can_decrypt() {
+        let (alice, alice_pub) = team();
+        let (bob, bob_pub) = team();
+        let (eve, _) = team();
+
+        let encrypted = encrypt(b"{\"rows\":[]}", &[alice_pub, bob_pub]).unwrap();
+        assert_eq!(decrypt(&encrypted, &[alice]).unwrap(), b"{\"rows\":[]}");
+        assert_eq!(decrypt(&encrypted, &[bob]).unwrap(), b"{\"rows\":[]}");
+        assert!(decrypt(&encrypted, &[eve]).is_err());
+    }
+
+    #[test]
+    fn test_tampering_detected() {
+        let (identity, recipient) = team();
+        let encrypted = encrypt(b"usage", &[recipient]).unwrap();
+
+        let mut payload = encrypted.clone();
+        *payload.last_mut().unwrap() ^= 1;
+        assert!(decrypt(&payload, std::slice::from_ref(&identity)).is_err());
+
+        // Dropping the final chunk is caught by the last-chunk flag
+        let truncated = &encrypted[..encrypted.len() - 5];
+        assert!(decrypt(truncated, std::slice::from_ref(&identity)).is_err());
+
+        let mut header = encrypted.clone();
+        header[3] = b'X';
+        assert!(decrypt(&header, &[identity]).is_err());
+    }
+
+    #[test]
+    fn test_decrypts_reference_file() {
+        // x25519 vector from the C2SP age test kit: a text header, a blank
+        // line, then the age file
+        let fixture = std::fs::read(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/age/x25519")).unwrap();
+        let split = fixture.windows(2).position(|w| w == b"\n\n").unwrap();
+        let header = std::str::from_utf8(&fixture[..split]).unwrap();
+        let field = |name: &str| {
+            header
+                .lines()
+                .find_map(|line| line.strip_prefix(name))
+                .unwrap()
+                .trim()
+                .to_string()
+        };
+
+        let identities = parse_identities(&field("identity:")).unwrap();
+        let plaintext = decrypt(&fixture[split + 2..], &identities).unwrap();
+        let digest: String = openssl::sha::sha256(&plaintext).iter().map(|b| format!("{:02x}", b)).collect();
+        assert_eq!(digest, field("payload:"));
+    }
+}
@@ -8,6 +8,7 @@ use crate::{
     TokenBreakdown, YearSummary,
 };
 use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
 /// Aggregate messages into daily contributions
@@ -17,7 +18,7 @@ pub fn aggregate_by_date(messages: Vec<UnifiedMessage>) -> Vec<DailyContribution
     }
 
     // Estimate unique days (typically 1-365) - use message count / 10 as heuristic
-    let estimated_days = (messages.len() / 10).max(30).min(400);
+    let estimated_days = (messages.len() / 10).clamp(30, 400);
 
     // Parallel aggregation using fold/reduce pattern
     let daily_map: HashMap<String, DayAccumulator> = messages
@@ -40,6 +41,11 @@ pub fn aggregate_by_date(messages: Vec<UnifiedMessage>) -> Vec<DailyContribution
             },
         );
 
+    finish_days(daily_map)
+}
+
+/// Convert per-day accumulators into sorted contributions with intensities
+pub(crate) fn finish_days(daily_map: HashMap<String, DayAccumulator>) -> Vec<DailyContribution> {
     // Convert to sorted vector with pre-allocated capacity
     let mut contributions: Vec<DailyContribution> = Vec::with_capacity(daily_map.len());
     contributions.extend(daily_map.into_iter().map(|(date, acc)| acc.into_contribution(date)));
@@ -63,8 +69,8 @@ pub fn calculate_summary(contributions: &[DailyContribution]) -> DataSummary {
         .map(|c| c.totals.cost)
         .fold(0.0, f64::max);
 
-    let mut sources_set = std::collections::HashSet::with_capacity(5);
-    let mut models_set = std::collections::HashSet::with_capacity(20);
+    let mut sources_set = std::collections::BTreeSet::new();
+    let mut models_set = std::collections::BTreeSet::new();
 
     for c in contributions {
         for s in &c.sources {
@@ -155,7 +161,8 @@ pub fn generate_graph_result(
 // Internal helpers
 // =============================================================================
 
-struct DayAccumulator {
+#[derive(Serialize, Deserialize)]
+pub(crate) struct DayAccumulator {
     totals: DailyTotals,
     token_breakdown: TokenBreakdown,
     sources: HashMap<String, SourceContribution>,
@@ -172,12 +179,13 @@ impl Default for DayAccumulator {
 }
 
 impl DayAccumulator {
-    fn add_message(&mut self, msg: &UnifiedMessage) {
+    pub(crate) fn add_message(&mut self, msg: &UnifiedMessage) {
         let total_tokens = msg.tokens.input
             .saturating_add(msg.tokens.output)
             .saturating_add(msg.tokens.cache_read)
             .saturating_add(msg.tokens.cache_write)
-            .saturating_add(msg.tokens.reasoning);
+            .saturating_add(msg.tokens.reasoning)
+            .saturating_add(msg.tokens.training);
 
         self.totals.tokens = self.totals.tokens.saturating_add(total_tokens);
         self.totals.cost += msg.cost;
@@ -188,6 +196,7 @@ impl DayAccumulator {
         self.token_breakdown.cache_read = self.token_breakdown.cache_read.saturating_add(msg.tokens.cache_read);
         self.token_breakdown.cache_write = self.token_breakdown.cache_write.saturating_add(msg.tokens.cache_write);
         self.token_breakdown.reasoning = self.token_breakdown.reasoning.saturating_add(msg.tokens.reasoning);
+        self.token_breakdown.training = self.token_breakdown.training.saturating_add(msg.tokens.training);
 
         // Update source contribution
         let key = format!("{}:{}", msg.source, msg.model_id);
@@ -195,9 +204,9 @@ impl DayAccumulator {
             .sources
             .entry(key)
             .or_insert_with(|| SourceContribution {
-                source: msg.source.clone(),
+                source: msg.source.to_string(),
                 model_id: msg.model_id.clone(),
-                provider_id: msg.provider_id.clone(),
+                provider_id: msg.provider_id.to_string(),
                 tokens: TokenBreakdown::default(),
                 cost: 0.0,
                 messages: 0,
@@ -208,6 +217,7 @@ impl DayAccumulator {
         source.tokens.cache_read = source.tokens.cache_read.saturating_add(msg.tokens.cache_read);
         source.tokens.cache_write = source.tokens.cache_write.saturating_add(msg.tokens.cache_write);
         source.tokens.reasoning = source.tokens.reasoning.saturating_add(msg.tokens.reasoning);
+        source.tokens.training = source.tokens.training.saturating_add(msg.tokens.training);
         source.cost += msg.cost;
         source.messages = source.messages.saturating_add(1);
     }
@@ -222,6 +232,7 @@ impl DayAccumulator {
         self.token_breakdown.cache_read = self.token_breakdown.cache_read.saturating_add(other.token_breakdown.cache_read);
         self.token_breakdown.cache_write = self.token_breakdown.cache_write.saturating_add(other.token_breakdown.cache_write);
         self.token_breakdown.reasoning = self.token_breakdown.reasoning.saturating_add(other.token_breakdown.reasoning);
+        self.token_breakdown.training = self.token_breakdown.training.saturating_add(other.token_breakdown.training);
 
         for (key, source) in other.sources {
             let entry = self
@@ -241,18 +252,21 @@ impl DayAccumulator {
             entry.tokens.cache_read = entry.tokens.cache_read.saturating_add(source.tokens.cache_read);
             entry.tokens.cache_write = entry.tokens.cache_write.saturating_add(source.tokens.cache_write);
             entry.tokens.reasoning = entry.tokens.reasoning.saturating_add(source.tokens.reasoning);
+            entry.tokens.training = entry.tokens.training.saturating_add(source.tokens.training);
             entry.cost += source.cost;
             entry.messages = entry.messages.saturating_add(source.messages);
         }
     }
 
     fn into_contribution(self, date: String) -> DailyContribution {
+        let mut sources: Vec<SourceContribution> = self.sources.into_values().collect();
+        sources.sort_by(|a, b| (&a.source, &a.model_id).cmp(&(&b.source, &b.model_id)));
         DailyContribution {
             date,
             totals: self.totals,
             intensity: 0, // Will be calculated later
             token_breakdown: self.token_breakdown,
-            sources: self.sources.into_values().collect(),
+            sources,
         }
     }
 }
@@ -290,3 +304,36 @@ fn calculate_intensities(contributions: &mut [DailyContribution]) {
         };
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{message, TestMessage};
+
+    const T0: i64 = 1_750_000_000_000;
+
+    #[test]
+    fn test_output_order_does_not_depend_on_input_order() {
+        let messages = vec![
+            message("s1", T0, 1.0).with_source("codex").with_model("gpt-5"),
+            message("s1", T0, 1.0).with_model("sonnet"),
+            message("s1", T0, 2.0).with_model("opus"),
+            message("s1", T0, 0.5).with_source("amp").with_model("sonnet"),
+        ];
+        let mut reversed = messages.clone();
+        reversed.reverse();
+
+        let days = aggregate_by_date(messages);
+        let keys: Vec<(&str, &str)> =
+            days[0].sources.iter().map(|s| (s.source.as_str(), s.model_id.as_str())).collect();
+        assert_eq!(keys, vec![("amp", "sonnet"), ("claude", "opus"), ("claude", "sonnet"), ("codex", "gpt-5")]);
+
+        let summary = calculate_summary(&days);
+        assert_eq!(summary.sources, vec!["amp", "claude", "codex"]);
+        assert_eq!(summary.models, vec!["gpt-5", "opus", "sonnet"]);
+        assert_eq!(
+            serde_json::to_string(&aggregate_by_date(reversed)[0].sources).unwrap(),
+            serde_json::to_string(&days[0].sources).unwrap()
+        );
+    }
+}
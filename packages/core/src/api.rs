@@ -0,0 +1,540 @@
+//! Rust library API
+//!
+//! One entry point for using tokscale as a crate instead of through Node:
+//!
+//! ```no_run
+//! use tokscale_core::{Filter, PricingConfig, Source, Tokscale};
+//!
+//! # async fn run() -> Result<(), tokscale_core::TokscaleError> {
+//! let tokscale = Tokscale::builder()
+//!     .sources([Source::Claude, Source::Codex])
+//!     .pricing(PricingConfig::default())
+//!     .build()?;
+//!
+//! let report = tokscale.report(Filter::default().since("2025-01-01")).await?;
+//! println!("{} messages, ${:.2}", report.total_messages, report.total_cost);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! This wires scanning, parsing (with the parse cache), pricing and
+//! aggregation the same way the Node bindings do. With memory limits set
+//! (see [`crate::streaming`]), reports and graphs are aggregated while
+//! files are parsed instead.
+
+use crate::aggregator::DayAccumulator;
+use crate::pricing::{ModelPricing, PricingProvider, PricingService, ServiceCell};
+use crate::recorder::Recorder;
+use crate::sessions::{Source, UnifiedMessage};
+use crate::streaming::{self, MemoryLimits};
+use crate::{
+    aggregator, apply_pricing, build_model_report, config, cost_display, effective_rates, filter_messages_for_report,
+    free_tier, parse_cache, parse_file, pipeline, report_includes, scanner, EffectiveRateReport, GraphResult,
+    ModelReport, ModelTotals, ReportOptions,
+};
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Instant;
+
+#[derive(Debug, thiserror::Error)]
+pub enum TokscaleError {
+    #[error("could not determine home directory")]
+    NoHomeDir,
+
+    #[error("unknown source: {0}")]
+    UnknownSource(String),
+
+    #[error("pricing unavailable: {0}")]
+    Pricing(String),
+
+    #[error("recording failed: {0}")]
+    Record(String),
+}
+
+/// Where message costs come from
+#[derive(Clone, Default)]
+pub struct PricingConfig {
+    kind: PricingKind,
+}
+
+#[derive(Clone, Default)]
+enum PricingKind {
+    /// LiteLLM and OpenRouter prices, fetched once and cached on disk
+    #[default]
+    Remote,
+    /// Remote prices plus registered providers, which take precedence
+    WithProviders {
+        providers: Vec<Arc<dyn PricingProvider>>,
+        service: Arc<ServiceCell>,
+    },
+    /// Costs as reported by the sources themselves (zero for most)
+    Reported,
+    Tables(Arc<PricingService>),
+}
+
+impl PricingConfig {
+    /// Keep the costs sources record themselves; never touches the network
+    pub fn reported() -> Self {
+        Self { kind: PricingKind::Reported }
+    }
+
+    /// Remote prices plus `provider` (e.g. an internal rate card service).
+    /// Its prices win over LiteLLM and OpenRouter for models it lists;
+    /// providers added earlier win over later ones.
+    pub fn with_provider(self, provider: impl PricingProvider + 'static) -> Self {
+        let mut providers = match self.kind {
+            PricingKind::WithProviders { providers, .. } => providers,
+            _ => Vec::new(),
+        };
+        providers.push(Arc::new(provider));
+        Self {
+            kind: PricingKind::WithProviders { providers, service: Arc::default() },
+        }
+    }
+
+    /// Price messages from the given LiteLLM-style tables (model id → prices)
+    pub fn from_tables(
+        litellm: HashMap<String, ModelPricing>,
+        openrouter: HashMap<String, ModelPricing>,
+    ) -> Self {
+        Self {
+            kind: PricingKind::Tables(Arc::new(PricingService::new(litellm, openrouter))),
+        }
+    }
+}
+
+/// Date range applied to messages. Dates are `YYYY-MM-DD`, bounds inclusive.
+#[derive(Debug, Clone, Default)]
+pub struct Filter {
+    pub since: Option<String>,
+    pub until: Option<String>,
+    pub year: Option<String>,
+    /// Drop messages whose timestamp was synthesized
+    pub exclude_estimated_timestamps: bool,
+}
+
+impl Filter {
+    pub fn since(mut self, date: impl Into<String>) -> Self {
+        self.since = Some(date.into());
+        self
+    }
+
+    pub fn until(mut self, date: impl Into<String>) -> Self {
+        self.until = Some(date.into());
+        self
+    }
+
+    pub fn year(mut self, year: impl Into<String>) -> Self {
+        self.year = Some(year.into());
+        self
+    }
+
+    pub fn exclude_estimated_timestamps(mut self) -> Self {
+        self.exclude_estimated_timestamps = true;
+        self
+    }
+
+    fn to_report_options(&self) -> ReportOptions {
+        ReportOptions {
+            home_dir: None,
+            sources: None,
+            since: self.since.clone(),
+            until: self.until.clone(),
+            year: self.year.clone(),
+            exclude_estimated_timestamps: Some(self.exclude_estimated_timestamps),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct TokscaleBuilder {
+    home_dir: Option<PathBuf>,
+    sources: Vec<Source>,
+    pricing: PricingConfig,
+    no_parse_cache: bool,
+    memory: Option<MemoryLimits>,
+}
+
+impl TokscaleBuilder {
+    /// Directory session data is found under (default: the user's home)
+    pub fn home_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.home_dir = Some(dir.into());
+        self
+    }
+
+    /// Sources to read (default: all)
+    pub fn sources<S: Into<Source>>(mut self, sources: impl IntoIterator<Item = S>) -> Self {
+        self.sources = sources.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn pricing(mut self, pricing: PricingConfig) -> Self {
+        self.pricing = pricing;
+        self
+    }
+
+    /// Parse every file on each call instead of reusing the on-disk parse cache
+    pub fn without_parse_cache(mut self) -> Self {
+        self.no_parse_cache = true;
+        self
+    }
+
+    /// Aggregate while parsing, holding at most `limits` at once, instead of
+    /// parsing every file first (default: `memory` in settings.json)
+    pub fn memory_limits(mut self, limits: MemoryLimits) -> Self {
+        self.memory = Some(limits);
+        self
+    }
+
+    pub fn build(self) -> Result<Tokscale, TokscaleError> {
+        if let Some(Source::Other(name)) = self.sources.iter().find(|s| matches!(s, Source::Other(_))) {
+            return Err(TokscaleError::UnknownSource(name.clone()));
+        }
+
+        let home_dir = self
+            .home_dir
+            .or_else(|| std::env::var_os("HOME").map(PathBuf::from))
+            .or_else(dirs::home_dir)
+            .ok_or(TokscaleError::NoHomeDir)?;
+
+        Ok(Tokscale {
+            home_dir: home_dir.to_string_lossy().into_owned(),
+            sources: self.sources.iter().map(|s| s.to_string()).collect(),
+            pricing: self.pricing,
+            use_parse_cache: !self.no_parse_cache,
+            memory: self.memory.or_else(|| config::settings().memory.limits()),
+        })
+    }
+}
+
+/// Configured access to local usage data
+pub struct Tokscale {
+    home_dir: String,
+    sources: Vec<String>,
+    pricing: PricingConfig,
+    use_parse_cache: bool,
+    memory: Option<MemoryLimits>,
+}
+
+impl Tokscale {
+    pub fn builder() -> TokscaleBuilder {
+        TokscaleBuilder::default()
+    }
+
+    /// Price tables in use; `None` when reported costs are kept
+    async fn pricing_service(&self) -> Result<Option<Arc<PricingService>>, TokscaleError> {
+        Ok(match &self.pricing.kind {
+            PricingKind::Remote => Some(PricingService::get_or_init().await.map_err(TokscaleError::Pricing)?),
+            PricingKind::WithProviders { providers, service } => Some(
+                service
+                    .get_or_try_init(|| PricingService::with_providers(providers.clone()))
+                    .await
+                    .map_err(TokscaleError::Pricing)?,
+            ),
+            PricingKind::Tables(service) => Some(Arc::clone(service)),
+            PricingKind::Reported => None,
+        })
+    }
+
+    /// Every priced message matching `filter`. Prices load while files are
+    /// found and parsed, and aren't waited for when nothing was parsed.
+    pub async fn messages(&self, filter: Filter) -> Result<Vec<UnifiedMessage>, TokscaleError> {
+        let ingested =
+            pipeline::ingest(&self.home_dir, &self.sources, self.use_parse_cache, self.pricing_service()).await?;
+        let Some(pricing) = ingested.prices else {
+            return Ok(Vec::new());
+        };
+        let messages: Vec<UnifiedMessage> = ingested
+            .parsed
+            .into_par_iter()
+            .flat_map(|(session_type, messages)| match &pricing {
+                Some(pricing) => messages
+                    .into_iter()
+                    .map(|msg| apply_pricing(session_type, msg, pricing))
+                    .collect(),
+                None => messages,
+            })
+            .collect();
+
+        Ok(filter_messages_for_report(messages, &filter.to_report_options()))
+    }
+
+    /// Limits streamed aggregation keeps to; `None` when every message is
+    /// parsed before aggregating
+    pub fn memory_limits(&self) -> Option<MemoryLimits> {
+        self.memory
+    }
+
+    /// Fold the priced messages matching `filter` into `init`, one file's
+    /// worth at a time in scan order, without holding them all. Always
+    /// streams, whatever the memory limits.
+    pub async fn fold_messages<R, F>(&self, filter: Filter, init: R, mut fold: F) -> Result<R, TokscaleError>
+    where
+        R: Send + 'static,
+        F: FnMut(&mut R, Vec<UnifiedMessage>) + Send + 'static,
+    {
+        let (pricing, files) = futures::join!(self.pricing_service(), pipeline::discover(&self.home_dir, &self.sources));
+        let pricing = pricing?;
+        let limits = self.memory.unwrap_or_default();
+        let options = filter.to_report_options();
+        let folding = pipeline::blocking(move || {
+            let mut acc = init;
+            let parse = |session_type, path: &std::path::Path| {
+                let messages = match &pricing {
+                    Some(pricing) => parse_file(session_type, path)
+                        .into_iter()
+                        .map(|msg| apply_pricing(session_type, msg, pricing))
+                        .collect(),
+                    None => parse_file(session_type, path),
+                };
+                filter_messages_for_report(messages, &options)
+            };
+            streaming::for_each_file(&files, limits, parse, |messages| fold(&mut acc, messages));
+            acc
+        });
+        Ok(folding.await)
+    }
+
+    /// Usage and cost per (source, provider, model), most expensive first
+    pub async fn report(&self, filter: Filter) -> Result<ModelReport, TokscaleError> {
+        let start = Instant::now();
+        let options = filter.to_report_options();
+        // Free tiers are used up by every message, in the report or not
+        if self.memory.is_none() {
+            let messages = self.messages(Filter::default()).await?;
+            return Ok(build_model_report(messages, &options, start));
+        }
+
+        // Free tiers are consumed in time order, so the messages they may
+        // cover are kept until the end, whatever their date
+        let tiers = config::settings().free_tiers.clone();
+        let folded = self.fold_messages(Filter::default(), (ModelTotals::default(), Vec::new()), {
+            let tiers = tiers.clone();
+            let options = options.clone();
+            move |(totals, tiered): &mut (ModelTotals, Vec<UnifiedMessage>), messages| {
+                for msg in messages {
+                    if free_tier::may_cover(&tiers, &msg) {
+                        tiered.push(msg);
+                    } else if report_includes(&msg, &options) {
+                        totals.add(&msg, 0.0);
+                    }
+                }
+            }
+        });
+        let (mut totals, tiered) = folded.await?;
+        for (msg, covered) in tiered.iter().zip(free_tier::covered(&tiered, &tiers)) {
+            if report_includes(msg, &options) {
+                totals.add(msg, covered);
+            }
+        }
+        let mut report = totals.finish(start);
+        cost_display::settings().apply_to_model_report(&mut report);
+        Ok(report)
+    }
+
+    /// Realized price per million tokens for each model, most expensive
+    /// first. Per-direction rates need price tables, so they are unset with
+    /// [`PricingConfig::reported`].
+    pub async fn effective_rates(&self, filter: Filter) -> Result<EffectiveRateReport, TokscaleError> {
+        let start = Instant::now();
+        let pricing = self.pricing_service().await?;
+        let messages = self.messages(filter).await?;
+        let entries = effective_rates::build(&messages, pricing.as_deref());
+        Ok(effective_rates::report(entries, start.elapsed().as_millis() as u32))
+    }
+
+    /// Changes whenever the session files this reads do (by path, size and
+    /// modification time), so results can be cached until it moves. Only
+    /// scans directories; nothing is parsed.
+    pub fn data_version(&self) -> u64 {
+        parse_cache::fingerprint(&scanner::scan_all_sources(&self.home_dir, &self.sources).all_files())
+    }
+
+    /// Recorder writing to this home directory's recorded events, read back
+    /// as the "recorded" source
+    pub fn recorder(&self) -> Result<Recorder, TokscaleError> {
+        Recorder::open(&self.home_dir).map_err(TokscaleError::Record)
+    }
+
+    /// Daily contributions with yearly and overall summaries
+    pub async fn graph(&self, filter: Filter) -> Result<GraphResult, TokscaleError> {
+        let start = Instant::now();
+        let contributions = if self.memory.is_some() {
+            let days = self.fold_messages(filter, HashMap::new(), |days: &mut HashMap<String, DayAccumulator>, messages| {
+                for msg in &messages {
+                    days.entry(msg.date.clone()).or_default().add_message(msg);
+                }
+            });
+            aggregator::finish_days(days.await?)
+        } else {
+            aggregator::aggregate_by_date(self.messages(filter).await?)
+        };
+        let mut graph = aggregator::generate_graph_result(contributions, start.elapsed().as_millis() as u32);
+        cost_display::settings().apply_to_graph(&mut graph);
+        Ok(graph)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn claude_home() -> TempDir {
+        let home = TempDir::new().unwrap();
+        let project = home.path().join(".claude/projects/demo");
+        fs::create_dir_all(&project).unwrap();
+        fs::write(
+            project.join("session.jsonl"),
+            concat!(
+                r#"{"type":"assistant","timestamp":"2025-03-01T10:00:00.000Z","requestId":"r1","message":{"id":"m1","model":"claude-sonnet-4","usage":{"input_tokens":1000,"output_tokens":100}}}"#,
+                "\n",
+                r#"{"type":"assistant","timestamp":"2025-04-01T10:00:00.000Z","requestId":"r2","message":{"id":"m2","model":"claude-sonnet-4","usage":{"input_tokens":500,"output_tokens":50}}}"#,
+            ),
+        )
+        .unwrap();
+        home
+    }
+
+    fn pricing() -> PricingConfig {
+        let price = ModelPricing {
+            input_cost_per_token: Some(0.000003),
+            output_cost_per_token: Some(0.000015),
+            cache_creation_input_token_cost: None,
+            cache_read_input_token_cost: None,
+            ..Default::default()
+        };
+        PricingConfig::from_tables(HashMap::from([("claude-sonnet-4".to_string(), price)]), HashMap::new())
+    }
+
+    #[test]
+    fn test_report_with_tables() {
+        let home = claude_home();
+        let tokscale = Tokscale::builder()
+            .home_dir(home.path())
+            .sources(["claude"])
+            .pricing(pricing())
+            .without_parse_cache()
+            .build()
+            .unwrap();
+
+        let report = block_on(tokscale.report(Filter::default())).unwrap();
+        assert_eq!(report.total_messages, 2);
+        assert_eq!(report.total_input, 1500);
+        assert!((report.total_cost - 0.00675).abs() < 1e-9);
+
+        let march = block_on(tokscale.report(Filter::default().until("2025-03-31"))).unwrap();
+        assert_eq!(march.total_messages, 1);
+    }
+
+    #[test]
+    fn test_effective_rates_with_tables() {
+        let home = claude_home();
+        let tokscale = Tokscale::builder()
+            .home_dir(home.path())
+            .sources(["claude"])
+            .pricing(pricing())
+            .without_parse_cache()
+            .build()
+            .unwrap();
+
+        let rates = block_on(tokscale.effective_rates(Filter::default())).unwrap();
+        assert_eq!(rates.entries.len(), 1);
+        let sonnet = &rates.entries[0];
+        assert_eq!(sonnet.input_tokens, 1500);
+        assert!((sonnet.input_per_million.unwrap() - 3.0).abs() < 1e-9);
+        assert!((sonnet.output_per_million.unwrap() - 15.0).abs() < 1e-9);
+        assert_eq!(rates.total_tokens, 1650);
+    }
+
+    #[test]
+    fn test_graph_with_reported_costs() {
+        let home = claude_home();
+        let tokscale = Tokscale::builder()
+            .home_dir(home.path())
+            .sources([Source::Claude])
+            .pricing(PricingConfig::reported())
+            .without_parse_cache()
+            .build()
+            .unwrap();
+
+        let graph = block_on(tokscale.graph(Filter::default().year("2025"))).unwrap();
+        assert_eq!(graph.contributions.len(), 2);
+        assert_eq!(graph.summary.total_cost, 0.0);
+    }
+
+    #[test]
+    fn test_recorded_messages_are_reported() {
+        let home = TempDir::new().unwrap();
+        let tokscale = Tokscale::builder()
+            .home_dir(home.path())
+            .sources([Source::Recorded])
+            .pricing(pricing())
+            .without_parse_cache()
+            .build()
+            .unwrap();
+
+        let tokens = crate::TokenBreakdown { input: 1000, output: 200, cache_read: 0, cache_write: 0, reasoning: 0, training: 0 };
+        let msg = UnifiedMessage::new("claude", "claude-sonnet-4", "anthropic", "job-1", 1740787200000, tokens, 0.0);
+        tokscale.recorder().unwrap().record(&msg).unwrap();
+
+        let report = block_on(tokscale.report(Filter::default())).unwrap();
+        assert_eq!(report.total_messages, 1);
+        assert_eq!(report.entries[0].source, "recorded");
+        assert!((report.total_cost - 0.006).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_streamed_results_match() {
+        let home = claude_home();
+        let project = home.path().join(".claude/projects/other");
+        fs::create_dir_all(&project).unwrap();
+        fs::write(
+            project.join("session.jsonl"),
+            r#"{"type":"assistant","timestamp":"2025-03-01T12:00:00.000Z","requestId":"r3","message":{"id":"m3","model":"claude-sonnet-4","usage":{"input_tokens":200,"output_tokens":20}}}"#,
+        )
+        .unwrap();
+        let builder = || Tokscale::builder().home_dir(home.path()).sources(["claude"]).pricing(pricing()).without_parse_cache();
+        let whole = builder().build().unwrap();
+        let streamed = builder().memory_limits(MemoryLimits { files_in_flight: 1, sort_chunk: 1 }).build().unwrap();
+        assert!(streamed.memory_limits().is_some());
+
+        let (a, b) = (block_on(whole.report(Filter::default())).unwrap(), block_on(streamed.report(Filter::default())).unwrap());
+        assert_eq!((a.total_messages, a.total_input, a.entries.len()), (3, 1700, 1));
+        assert_eq!((b.total_messages, b.total_input, b.entries.len()), (3, 1700, 1));
+        assert!((a.total_cost - b.total_cost).abs() < 1e-12);
+
+        let filter = || Filter::default().since("2025-03-01").until("2025-03-31");
+        let (a, b) = (block_on(whole.graph(filter())).unwrap(), block_on(streamed.graph(filter())).unwrap());
+        assert_eq!(b.contributions.len(), 1);
+        assert_eq!(a.contributions[0].totals.messages, b.contributions[0].totals.messages);
+        assert_eq!(a.contributions[0].token_breakdown.input, b.contributions[0].token_breakdown.input);
+    }
+
+    #[test]
+    fn test_data_version_follows_files() {
+        let home = claude_home();
+        let tokscale = Tokscale::builder().home_dir(home.path()).sources(["claude"]).build().unwrap();
+        let version = tokscale.data_version();
+        assert_eq!(tokscale.data_version(), version);
+
+        let project = home.path().join(".claude/projects/demo");
+        fs::write(project.join("other.jsonl"), "{}\n").unwrap();
+        let with_new_file = tokscale.data_version();
+        assert_ne!(with_new_file, version);
+
+        let mut file = fs::OpenOptions::new().append(true).open(project.join("other.jsonl")).unwrap();
+        std::io::Write::write_all(&mut file, b"{}\n").unwrap();
+        assert_ne!(tokscale.data_version(), with_new_file);
+    }
+
+    #[test]
+    fn test_unknown_source_rejected() {
+        let result = Tokscale::builder().sources(["claude", "zed"]).build();
+        assert!(matches!(result, Err(TokscaleError::UnknownSource(name)) if name == "zed"));
+    }
+}
@@ -0,0 +1,197 @@
+//! Compressed session files and archives
+//!
+//! Old logs are often compressed to save space. A session file compressed on
+//! its own (`session.jsonl.gz`, `session.json.zst`) is found where the
+//! uncompressed file would be, and parsers read it through [`open`] and
+//! [`read`], which decompress by extension.
+//!
+//! A tarball (`.tar.gz`, `.tgz`, `.tar.zst`) in a source's directory counts
+//! like the files it holds: it is unpacked to a temporary directory, the
+//! files there matching the source's pattern are parsed, and the directory
+//! is removed. Members keep their relative paths, so files read next to a
+//! session file (Droid's transcript) are found. The parse cache keeps the
+//! result by the archive's size and modification time, so an archive is
+//! only unpacked again when it changes.
+//!
+//! Builds without the `archives` feature skip compressed files.
+
+use crate::scanner::{self, SessionType};
+use crate::sessions::UnifiedMessage;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+const GZIP: &str = ".gz";
+const ZSTD: &str = ".zst";
+const TARBALLS: &[&str] = &[".tar.gz", ".tgz", ".tar.zst"];
+
+/// `name` without a compression extension, when compressed files are read
+pub fn strip(name: &str) -> &str {
+    if !cfg!(feature = "archives") {
+        return name;
+    }
+    name.strip_suffix(GZIP).or_else(|| name.strip_suffix(ZSTD)).unwrap_or(name)
+}
+
+/// File stem of `path` once decompressed ("abc" for "abc.jsonl.gz")
+pub fn file_stem(path: &Path) -> Option<&str> {
+    let name = strip(path.file_name()?.to_str()?);
+    Some(Path::new(name).file_stem()?.to_str().unwrap_or(name))
+}
+
+pub fn is_tarball(path: &Path) -> bool {
+    let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+    cfg!(feature = "archives") && TARBALLS.iter().any(|ext| name.ends_with(ext))
+}
+
+/// Open a session file, decompressing it if its name says it is compressed
+pub fn open(path: &Path) -> io::Result<Box<dyn Read>> {
+    let file = File::open(path)?;
+    let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+    decoder(name, file)
+}
+
+#[cfg(feature = "archives")]
+fn decoder(name: &str, file: File) -> io::Result<Box<dyn Read>> {
+    if name.ends_with(GZIP) || name.ends_with(".tgz") {
+        Ok(Box::new(flate2::read::MultiGzDecoder::new(io::BufReader::new(file))))
+    } else if name.ends_with(ZSTD) {
+        Ok(Box::new(zstd::stream::read::Decoder::new(file)?))
+    } else {
+        Ok(Box::new(file))
+    }
+}
+
+#[cfg(not(feature = "archives"))]
+fn decoder(_name: &str, file: File) -> io::Result<Box<dyn Read>> {
+    Ok(Box::new(file))
+}
+
+/// Whole contents of a session file, decompressed
+pub fn read(path: &Path) -> io::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    open(path)?.read_to_end(&mut data)?;
+    Ok(data)
+}
+
+pub fn read_to_string(path: &Path) -> io::Result<String> {
+    let mut text = String::new();
+    open(path)?.read_to_string(&mut text)?;
+    Ok(text)
+}
+
+/// Temporary directory a tarball is unpacked to, removed when dropped
+#[cfg_attr(not(feature = "archives"), allow(dead_code))]
+struct Unpacked(PathBuf);
+
+impl Drop for Unpacked {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+#[cfg(feature = "archives")]
+fn unpack(path: &Path) -> io::Result<Unpacked> {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let dir = std::env::temp_dir().join(format!(
+        "tokscale-archive-{}-{}",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    fs::create_dir_all(&dir)?;
+    let unpacked = Unpacked(dir);
+    // Entries pointing outside the directory are skipped by `unpack`
+    tar::Archive::new(open(path)?).unpack(&unpacked.0)?;
+    Ok(unpacked)
+}
+
+#[cfg(not(feature = "archives"))]
+fn unpack(_path: &Path) -> io::Result<Unpacked> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "built without archive support"))
+}
+
+/// Messages of the `session_type` files in the tarball at `path`, each
+/// parsed with `parse`. Tarballs inside it are not opened.
+pub fn parse_tarball<F>(path: &Path, session_type: SessionType, mut parse: F) -> Vec<UnifiedMessage>
+where
+    F: FnMut(SessionType, &Path) -> Vec<UnifiedMessage>,
+{
+    let unpacked = match unpack(path) {
+        Ok(unpacked) => unpacked,
+        Err(e) => {
+            eprintln!("[tokscale] Failed to unpack {}: {}", path.display(), e);
+            return Vec::new();
+        }
+    };
+    let mut files = scanner::scan_directory(&unpacked.0.to_string_lossy(), session_type.file_pattern());
+    files.retain(|file| !is_tarball(file));
+    files.sort();
+    files.iter().flat_map(|file| parse(session_type, file)).collect()
+}
+
+#[cfg(all(test, feature = "archives"))]
+mod tests {
+    use super::*;
+    use crate::TokenBreakdown;
+    use std::io::Write;
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn test_reads_compressed_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let plain = dir.path().join("a.jsonl");
+        let gz = dir.path().join("b.jsonl.gz");
+        let zst = dir.path().join("c.jsonl.zst");
+        fs::write(&plain, b"{}\n").unwrap();
+        fs::write(&gz, gzip(b"{\"gz\":1}\n")).unwrap();
+        fs::write(&zst, zstd::encode_all(&b"{\"zst\":1}\n"[..], 0).unwrap()).unwrap();
+
+        assert_eq!(read(&plain).unwrap(), b"{}\n");
+        assert_eq!(read_to_string(&gz).unwrap(), "{\"gz\":1}\n");
+        assert_eq!(read(&zst).unwrap(), b"{\"zst\":1}\n");
+        assert_eq!(file_stem(&gz), Some("b"));
+        assert_eq!(file_stem(Path::new("x/uuid.settings.json.zst")), Some("uuid.settings"));
+
+        let mut found = scanner::scan_directory(&dir.path().to_string_lossy(), "*.jsonl");
+        found.sort();
+        assert_eq!(found, [plain, gz, zst]);
+    }
+
+    #[test]
+    fn test_parses_tarball_members() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut builder = tar::Builder::new(Vec::new());
+        for (name, data) in [("proj/s1.jsonl", &b"{}\n"[..]), ("proj/s2.jsonl.gz", &gzip(b"{}\n")), ("notes.txt", b"x")] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            builder.append_data(&mut header, name, data).unwrap();
+        }
+        let tarball = dir.path().join("2024.tar.zst");
+        fs::write(&tarball, zstd::encode_all(&builder.into_inner().unwrap()[..], 0).unwrap()).unwrap();
+
+        assert!(is_tarball(&tarball));
+        assert_eq!(scanner::scan_directory(&dir.path().to_string_lossy(), "*.jsonl"), std::slice::from_ref(&tarball));
+
+        let mut unpacked_to = Vec::new();
+        let messages = parse_tarball(&tarball, SessionType::Claude, |session_type, path| {
+            unpacked_to.push(path.parent().unwrap().to_path_buf());
+            let session = file_stem(path).unwrap();
+            let lines = read_to_string(path).unwrap().lines().count() as i64;
+            let tokens = TokenBreakdown { input: lines, ..TokenBreakdown::default() };
+            vec![UnifiedMessage::new(session_type.name(), "sonnet", "anthropic", session, 0, tokens, 0.0)]
+        });
+        let sessions: Vec<&str> = messages.iter().map(|m| m.session_id.as_str()).collect();
+        assert_eq!(sessions, ["s1", "s2"]);
+        assert!(messages.iter().all(|m| m.tokens.input == 1));
+        // Members kept their directories, and were removed afterwards
+        assert!(unpacked_to.iter().all(|dir| dir.ends_with("proj") && !dir.exists()));
+    }
+}
@@ -0,0 +1,728 @@
+//! Arrow IPC export
+//!
+//! Encodes messages and daily aggregates as an Arrow IPC stream so the Node and
+//! WASM bindings can hand one buffer to `apache-arrow` (`tableFromIPC`) instead
+//! of building millions of JS objects across the FFI boundary.
+//!
+//! Only the column types tokscale needs are supported (Utf8, Int32, Int64,
+//! Float64), so the flatbuffers metadata is written by a small encoder here
+//! rather than pulling in the full `arrow` crate. Rows are split into batches
+//! of `BATCH_ROWS` to keep 32-bit string offsets in range.
+
+use crate::sessions::UnifiedMessage;
+use crate::usage_mode;
+use crate::DailyContribution;
+
+/// Rows per record batch
+pub const BATCH_ROWS: usize = 65_536;
+
+const CONTINUATION: u32 = 0xFFFF_FFFF;
+/// MetadataVersion::V5
+const METADATA_VERSION: i16 = 4;
+/// MessageHeader union tags
+const HEADER_SCHEMA: u8 = 1;
+const HEADER_RECORD_BATCH: u8 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataType {
+    Utf8,
+    Int32,
+    Int64,
+    Float64,
+}
+
+impl DataType {
+    /// `Type` union tag in the Arrow schema
+    fn type_tag(self) -> u8 {
+        match self {
+            DataType::Int32 | DataType::Int64 => 2,
+            DataType::Float64 => 3,
+            DataType::Utf8 => 5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Field {
+    pub name: &'static str,
+    pub data_type: DataType,
+    pub nullable: bool,
+}
+
+const fn field(name: &'static str, data_type: DataType) -> Field {
+    Field { name, data_type, nullable: false }
+}
+
+/// One column of a record batch, already in Arrow buffer layout
+#[derive(Debug, Clone)]
+pub enum Column {
+    Utf8 {
+        offsets: Vec<i32>,
+        data: Vec<u8>,
+        /// Validity bitmap, only present when the column has nulls
+        validity: Option<Vec<u8>>,
+        null_count: usize,
+    },
+    Int32(Vec<i32>),
+    Int64(Vec<i64>),
+    Float64(Vec<f64>),
+}
+
+impl Column {
+    pub fn utf8<'a>(values: impl IntoIterator<Item = &'a str>) -> Self {
+        Self::utf8_nullable(values.into_iter().map(Some))
+    }
+
+    pub fn utf8_nullable<'a>(values: impl IntoIterator<Item = Option<&'a str>>) -> Self {
+        let mut offsets = vec![0i32];
+        let mut data = Vec::new();
+        let mut nulls = Vec::new();
+
+        for (i, value) in values.into_iter().enumerate() {
+            match value {
+                Some(s) => data.extend_from_slice(s.as_bytes()),
+                None => nulls.push(i),
+            }
+            offsets.push(data.len() as i32);
+        }
+
+        let rows = offsets.len() - 1;
+        let validity = (!nulls.is_empty()).then(|| {
+            let mut bitmap = vec![0xFFu8; rows.div_ceil(8)];
+            for &i in &nulls {
+                bitmap[i / 8] &= !(1 << (i % 8));
+            }
+            bitmap
+        });
+
+        Column::Utf8 { offsets, data, validity, null_count: nulls.len() }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Column::Utf8 { offsets, .. } => offsets.len() - 1,
+            Column::Int32(v) => v.len(),
+            Column::Int64(v) => v.len(),
+            Column::Float64(v) => v.len(),
+        }
+    }
+
+    fn null_count(&self) -> usize {
+        match self {
+            Column::Utf8 { null_count, .. } => *null_count,
+            _ => 0,
+        }
+    }
+
+    /// Buffers in Arrow order: validity, then offsets/values, then string data
+    fn buffers(&self) -> Vec<Vec<u8>> {
+        fn le_bytes<T, const N: usize>(values: &[T], to_le: impl Fn(&T) -> [u8; N]) -> Vec<u8> {
+            values.iter().flat_map(to_le).collect()
+        }
+
+        match self {
+            Column::Utf8 { offsets, data, validity, .. } => vec![
+                validity.clone().unwrap_or_default(),
+                le_bytes(offsets, |v| v.to_le_bytes()),
+                data.clone(),
+            ],
+            Column::Int32(v) => vec![Vec::new(), le_bytes(v, |v| v.to_le_bytes())],
+            Column::Int64(v) => vec![Vec::new(), le_bytes(v, |v| v.to_le_bytes())],
+            Column::Float64(v) => vec![Vec::new(), le_bytes(v, |v| v.to_le_bytes())],
+        }
+    }
+}
+
+/// Writes an Arrow IPC stream: schema, record batches, end-of-stream marker
+pub struct StreamWriter {
+    out: Vec<u8>,
+    fields: Vec<Field>,
+}
+
+impl StreamWriter {
+    pub fn new(fields: &[Field]) -> Self {
+        let mut writer = Self { out: Vec::new(), fields: fields.to_vec() };
+        let schema = schema_message(fields);
+        writer.write_message(&schema, &[]);
+        writer
+    }
+
+    /// Append one record batch; columns must match the schema in order
+    pub fn write_batch(&mut self, columns: &[Column]) {
+        debug_assert_eq!(columns.len(), self.fields.len());
+        let rows = columns.first().map(Column::len).unwrap_or(0);
+
+        let mut body = Vec::new();
+        let mut nodes = Vec::with_capacity(columns.len());
+        let mut buffers = Vec::new();
+        for column in columns {
+            debug_assert_eq!(column.len(), rows);
+            nodes.push([column.len() as i64, column.null_count() as i64]);
+            for buffer in column.buffers() {
+                buffers.push([body.len() as i64, buffer.len() as i64]);
+                body.extend_from_slice(&buffer);
+                body.resize(align8(body.len()), 0);
+            }
+        }
+
+        let header = record_batch_message(rows, &nodes, &buffers, body.len());
+        self.write_message(&header, &body);
+    }
+
+    pub fn finish(mut self) -> Vec<u8> {
+        self.out.extend_from_slice(&CONTINUATION.to_le_bytes());
+        self.out.extend_from_slice(&0u32.to_le_bytes());
+        self.out
+    }
+
+    fn write_message(&mut self, metadata: &[u8], body: &[u8]) {
+        // The 8-byte prefix plus metadata must end on an 8-byte boundary
+        let padded = align8(metadata.len() + 8) - 8;
+        self.out.extend_from_slice(&CONTINUATION.to_le_bytes());
+        self.out.extend_from_slice(&(padded as i32).to_le_bytes());
+        self.out.extend_from_slice(metadata);
+        self.out.resize(self.out.len() + padded - metadata.len(), 0);
+        self.out.extend_from_slice(body);
+    }
+}
+
+fn align8(n: usize) -> usize {
+    n.div_ceil(8) * 8
+}
+
+fn schema_message(fields: &[Field]) -> Vec<u8> {
+    let mut fb = FlatBuilder::default();
+
+    let field_offsets: Vec<usize> = fields
+        .iter()
+        .map(|f| {
+            let name = fb.string(f.name);
+            fb.start_table();
+            match f.data_type {
+                DataType::Utf8 => {}
+                DataType::Int32 | DataType::Int64 => {
+                    let bits = if f.data_type == DataType::Int32 { 32 } else { 64 };
+                    fb.add_i32(0, bits);
+                    fb.add_u8(1, 1);
+                }
+                // Precision::DOUBLE
+                DataType::Float64 => fb.add_i16(0, 2),
+            }
+            let data_type = fb.end_table();
+            let children = fb.offset_vector(&[]);
+
+            fb.start_table();
+            fb.add_offset(0, name);
+            fb.add_offset(3, data_type);
+            fb.add_offset(5, children);
+            fb.add_u8(1, f.nullable as u8);
+            fb.add_u8(2, f.data_type.type_tag());
+            fb.end_table()
+        })
+        .collect();
+    let fields_vector = fb.offset_vector(&field_offsets);
+
+    fb.start_table();
+    fb.add_offset(1, fields_vector);
+    // Endianness::Little
+    fb.add_i16(0, 0);
+    let schema = fb.end_table();
+
+    finish_message(fb, HEADER_SCHEMA, schema, 0)
+}
+
+fn record_batch_message(
+    rows: usize,
+    nodes: &[[i64; 2]],
+    buffers: &[[i64; 2]],
+    body_len: usize,
+) -> Vec<u8> {
+    let mut fb = FlatBuilder::default();
+    let nodes = fb.struct_vector(nodes);
+    let buffers = fb.struct_vector(buffers);
+
+    fb.start_table();
+    fb.add_i64(0, rows as i64);
+    fb.add_offset(1, nodes);
+    fb.add_offset(2, buffers);
+    let batch = fb.end_table();
+
+    finish_message(fb, HEADER_RECORD_BATCH, batch, body_len)
+}
+
+fn finish_message(mut fb: FlatBuilder, header_type: u8, header: usize, body_len: usize) -> Vec<u8> {
+    fb.start_table();
+    fb.add_i64(3, body_len as i64);
+    fb.add_offset(2, header);
+    fb.add_i16(0, METADATA_VERSION);
+    fb.add_u8(1, header_type);
+    let message = fb.end_table();
+    fb.finish(message)
+}
+
+/// Minimal flatbuffers encoder. Like the reference builder it fills the buffer
+/// back to front, so every offset below is measured from the end of the buffer.
+#[derive(Default)]
+struct FlatBuilder {
+    buf: Vec<u8>,
+    min_align: usize,
+    table_start: usize,
+    table_fields: Vec<(u16, usize)>,
+}
+
+impl FlatBuilder {
+    fn offset(&self) -> usize {
+        self.buf.len()
+    }
+
+    fn prepend(&mut self, bytes: &[u8]) {
+        self.buf.splice(0..0, bytes.iter().copied());
+    }
+
+    /// Pad so that after writing `additional` bytes the offset is a multiple of `align`
+    fn prep(&mut self, align: usize, additional: usize) {
+        self.min_align = self.min_align.max(align);
+        let pad = (align - (self.offset() + additional) % align) % align;
+        self.prepend(&vec![0; pad]);
+    }
+
+    fn scalar<const N: usize>(&mut self, bytes: [u8; N]) -> usize {
+        self.prep(N, 0);
+        self.prepend(&bytes);
+        self.offset()
+    }
+
+    fn uoffset(&mut self, target: usize) -> usize {
+        self.prep(4, 0);
+        let relative = (self.offset() + 4 - target) as u32;
+        self.scalar(relative.to_le_bytes())
+    }
+
+    fn string(&mut self, s: &str) -> usize {
+        self.prep(4, s.len() + 1);
+        self.prepend(&[0]);
+        self.prepend(s.as_bytes());
+        self.scalar((s.len() as u32).to_le_bytes())
+    }
+
+    fn offset_vector(&mut self, targets: &[usize]) -> usize {
+        self.prep(4, 4 * targets.len());
+        for &target in targets.iter().rev() {
+            self.uoffset(target);
+        }
+        self.scalar((targets.len() as u32).to_le_bytes())
+    }
+
+    /// Vector of structs made of two `long`s (FieldNode, Buffer)
+    fn struct_vector(&mut self, items: &[[i64; 2]]) -> usize {
+        self.prep(4, 16 * items.len());
+        self.prep(8, 16 * items.len());
+        for item in items.iter().rev() {
+            self.prepend(&item[1].to_le_bytes());
+            self.prepend(&item[0].to_le_bytes());
+        }
+        self.scalar((items.len() as u32).to_le_bytes())
+    }
+
+    fn start_table(&mut self) {
+        self.table_fields.clear();
+        self.table_start = self.offset();
+    }
+
+    fn add_u8(&mut self, id: u16, value: u8) {
+        let at = self.scalar([value]);
+        self.table_fields.push((id, at));
+    }
+
+    fn add_i16(&mut self, id: u16, value: i16) {
+        let at = self.scalar(value.to_le_bytes());
+        self.table_fields.push((id, at));
+    }
+
+    fn add_i32(&mut self, id: u16, value: i32) {
+        let at = self.scalar(value.to_le_bytes());
+        self.table_fields.push((id, at));
+    }
+
+    fn add_i64(&mut self, id: u16, value: i64) {
+        let at = self.scalar(value.to_le_bytes());
+        self.table_fields.push((id, at));
+    }
+
+    fn add_offset(&mut self, id: u16, target: usize) {
+        let at = self.uoffset(target);
+        self.table_fields.push((id, at));
+    }
+
+    fn end_table(&mut self) -> usize {
+        // soffset to the vtable, patched once the vtable is written
+        let table = self.scalar([0u8; 4]);
+
+        let slots = self.table_fields.iter().map(|(id, _)| *id as usize + 1).max().unwrap_or(0);
+        let mut vtable = vec![0u16; 2 + slots];
+        vtable[0] = (4 + 2 * slots) as u16;
+        vtable[1] = (table - self.table_start) as u16;
+        for &(id, at) in &self.table_fields {
+            vtable[2 + id as usize] = (table - at) as u16;
+        }
+        let bytes: Vec<u8> = vtable.iter().flat_map(|v| v.to_le_bytes()).collect();
+        self.prepend(&bytes);
+
+        let vtable_at = self.offset();
+        let pos = self.buf.len() - table;
+        self.buf[pos..pos + 4].copy_from_slice(&((vtable_at - table) as i32).to_le_bytes());
+        self.table_fields.clear();
+        table
+    }
+
+    fn finish(mut self, root: usize) -> Vec<u8> {
+        self.prep(self.min_align.max(4), 4);
+        self.uoffset(root);
+        self.buf
+    }
+}
+
+// =============================================================================
+// tokscale schemas
+// =============================================================================
+
+pub const MESSAGE_FIELDS: &[Field] = &[
+    field("source", DataType::Utf8),
+    field("modelId", DataType::Utf8),
+    field("providerId", DataType::Utf8),
+    field("sessionId", DataType::Utf8),
+    field("timestamp", DataType::Int64),
+    field("date", DataType::Utf8),
+    field("input", DataType::Int64),
+    field("output", DataType::Int64),
+    field("cacheRead", DataType::Int64),
+    field("cacheWrite", DataType::Int64),
+    field("reasoning", DataType::Int64),
+    field("cost", DataType::Float64),
+    Field { name: "agent", data_type: DataType::Utf8, nullable: true },
+    field("training", DataType::Int64),
+    // "interactive" or "agentic" (see `usage_mode`)
+    field("mode", DataType::Utf8),
+];
+
+/// One row per (date, source, model) from the daily contributions
+pub const CONTRIBUTION_FIELDS: &[Field] = &[
+    field("date", DataType::Utf8),
+    field("source", DataType::Utf8),
+    field("modelId", DataType::Utf8),
+    field("providerId", DataType::Utf8),
+    field("input", DataType::Int64),
+    field("output", DataType::Int64),
+    field("cacheRead", DataType::Int64),
+    field("cacheWrite", DataType::Int64),
+    field("reasoning", DataType::Int64),
+    field("cost", DataType::Float64),
+    field("messages", DataType::Int32),
+    field("training", DataType::Int64),
+];
+
+/// Encode messages as an Arrow IPC stream with `MESSAGE_FIELDS`
+pub fn messages_to_ipc(messages: &[UnifiedMessage]) -> Vec<u8> {
+    let mut writer = StreamWriter::new(MESSAGE_FIELDS);
+    let modes = usage_mode::classify(messages);
+    for (chunk, modes) in messages.chunks(BATCH_ROWS).zip(modes.chunks(BATCH_ROWS)) {
+        let int64 = |f: fn(&UnifiedMessage) -> i64| Column::Int64(chunk.iter().map(f).collect());
+        writer.write_batch(&[
+            Column::utf8(chunk.iter().map(|m| m.source.as_str())),
+            Column::utf8(chunk.iter().map(|m| m.model_id.as_str())),
+            Column::utf8(chunk.iter().map(|m| m.provider_id.as_str())),
+            Column::utf8(chunk.iter().map(|m| m.session_id.as_str())),
+            int64(|m| m.timestamp),
+            Column::utf8(chunk.iter().map(|m| m.date.as_str())),
+            int64(|m| m.tokens.input),
+            int64(|m| m.tokens.output),
+            int64(|m| m.tokens.cache_read),
+            int64(|m| m.tokens.cache_write),
+            int64(|m| m.tokens.reasoning),
+            Column::Float64(chunk.iter().map(|m| m.cost).collect()),
+            Column::utf8_nullable(chunk.iter().map(|m| m.agent.as_deref())),
+            int64(|m| m.tokens.training),
+            Column::utf8(modes.iter().map(|m| m.as_str())),
+        ]);
+    }
+    writer.finish()
+}
+
+/// Encode daily contributions as an Arrow IPC stream with `CONTRIBUTION_FIELDS`
+pub fn contributions_to_ipc(contributions: &[DailyContribution]) -> Vec<u8> {
+    let rows: Vec<(&str, &crate::SourceContribution)> = contributions
+        .iter()
+        .flat_map(|day| day.sources.iter().map(move |s| (day.date.as_str(), s)))
+        .collect();
+
+    let mut writer = StreamWriter::new(CONTRIBUTION_FIELDS);
+    for chunk in rows.chunks(BATCH_ROWS) {
+        let int64 = |f: fn(&crate::SourceContribution) -> i64| {
+            Column::Int64(chunk.iter().map(|(_, s)| f(s)).collect())
+        };
+        writer.write_batch(&[
+            Column::utf8(chunk.iter().map(|(date, _)| *date)),
+            Column::utf8(chunk.iter().map(|(_, s)| s.source.as_str())),
+            Column::utf8(chunk.iter().map(|(_, s)| s.model_id.as_str())),
+            Column::utf8(chunk.iter().map(|(_, s)| s.provider_id.as_str())),
+            int64(|s| s.tokens.input),
+            int64(|s| s.tokens.output),
+            int64(|s| s.tokens.cache_read),
+            int64(|s| s.tokens.cache_write),
+            int64(|s| s.tokens.reasoning),
+            Column::Float64(chunk.iter().map(|(_, s)| s.cost).collect()),
+            Column::Int32(chunk.iter().map(|(_, s)| s.messages).collect()),
+            int64(|s| s.tokens.training),
+        ]);
+    }
+    writer.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{message, TestMessage};
+
+    // Just enough of a flatbuffers/IPC reader to check what we wrote
+
+    fn u32_at(buf: &[u8], pos: usize) -> u32 {
+        u32::from_le_bytes(buf[pos..pos + 4].try_into().unwrap())
+    }
+
+    fn i64_at(buf: &[u8], pos: usize) -> i64 {
+        i64::from_le_bytes(buf[pos..pos + 8].try_into().unwrap())
+    }
+
+    fn deref(buf: &[u8], pos: usize) -> usize {
+        pos + u32_at(buf, pos) as usize
+    }
+
+    /// Absolute position of field `id` in the table at `table`
+    fn field_pos(buf: &[u8], table: usize, id: usize) -> Option<usize> {
+        let soffset = i32::from_le_bytes(buf[table..table + 4].try_into().unwrap());
+        let vtable = (table as i64 - soffset as i64) as usize;
+        let vt_size = u16::from_le_bytes([buf[vtable], buf[vtable + 1]]) as usize;
+        if 4 + 2 * id >= vt_size {
+            return None;
+        }
+        let rel = u16::from_le_bytes([buf[vtable + 4 + 2 * id], buf[vtable + 5 + 2 * id]]);
+        (rel != 0).then_some(table + rel as usize)
+    }
+
+    fn string_at(buf: &[u8], pos: usize) -> String {
+        let start = deref(buf, pos);
+        let len = u32_at(buf, start) as usize;
+        String::from_utf8(buf[start + 4..start + 4 + len].to_vec()).unwrap()
+    }
+
+    struct Message<'a> {
+        metadata: &'a [u8],
+        root: usize,
+        body: &'a [u8],
+    }
+
+    impl Message<'_> {
+        fn header_type(&self) -> u8 {
+            self.metadata[field_pos(self.metadata, self.root, 1).unwrap()]
+        }
+
+        fn header(&self) -> usize {
+            deref(self.metadata, field_pos(self.metadata, self.root, 2).unwrap())
+        }
+    }
+
+    fn read_stream(stream: &[u8]) -> Vec<Message<'_>> {
+        let mut messages = Vec::new();
+        let mut pos = 0;
+        loop {
+            assert_eq!(u32_at(stream, pos), CONTINUATION);
+            let len = u32_at(stream, pos + 4) as usize;
+            pos += 8;
+            if len == 0 {
+                assert_eq!(pos, stream.len());
+                return messages;
+            }
+            assert_eq!((pos + len) % 8, 0, "metadata must end 8-byte aligned");
+            let metadata = &stream[pos..pos + len];
+            let root = deref(metadata, 0);
+            let body_len = i64_at(metadata, field_pos(metadata, root, 3).unwrap()) as usize;
+            pos += len;
+            messages.push(Message { metadata, root, body: &stream[pos..pos + body_len] });
+            pos += body_len;
+        }
+    }
+
+    fn schema_fields(message: &Message) -> Vec<(String, u8, bool)> {
+        let buf = message.metadata;
+        let fields = deref(buf, field_pos(buf, message.header(), 1).unwrap());
+        (0..u32_at(buf, fields) as usize)
+            .map(|i| {
+                let f = deref(buf, fields + 4 + 4 * i);
+                let name = string_at(buf, field_pos(buf, f, 0).unwrap());
+                let type_tag = buf[field_pos(buf, f, 2).unwrap()];
+                let nullable = buf[field_pos(buf, f, 1).unwrap()] == 1;
+                (name, type_tag, nullable)
+            })
+            .collect()
+    }
+
+    /// (row count, buffers as (offset, length) into the body)
+    fn batch_layout(message: &Message) -> (i64, Vec<(usize, usize)>) {
+        let buf = message.metadata;
+        let batch = message.header();
+        let rows = i64_at(buf, field_pos(buf, batch, 0).unwrap());
+        let buffers = deref(buf, field_pos(buf, batch, 2).unwrap());
+        let layout = (0..u32_at(buf, buffers) as usize)
+            .map(|i| {
+                let at = buffers + 4 + 16 * i;
+                (i64_at(buf, at) as usize, i64_at(buf, at + 8) as usize)
+            })
+            .collect();
+        (rows, layout)
+    }
+
+    #[test]
+    fn test_empty_stream_has_schema_and_eos() {
+        let stream = messages_to_ipc(&[]);
+        let messages = read_stream(&stream);
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].header_type(), HEADER_SCHEMA);
+    }
+
+    #[test]
+    fn test_schema_fields() {
+        let stream = messages_to_ipc(&[]);
+        let messages = read_stream(&stream);
+        let fields = schema_fields(&messages[0]);
+
+        assert_eq!(fields.len(), MESSAGE_FIELDS.len());
+        assert_eq!(fields[1], ("modelId".to_string(), 5, false));
+        assert_eq!(fields[4], ("timestamp".to_string(), 2, false));
+        assert_eq!(fields[11], ("cost".to_string(), 3, false));
+        assert_eq!(fields[12], ("agent".to_string(), 5, true));
+    }
+
+    #[test]
+    fn test_record_batch_values() {
+        let msgs = vec![
+            message("session-1", 1_700_000_000_000, 0.25),
+            message("session-1", 1_700_000_060_000, 0.25).with_model("claude-opus-4-5").with_agent("Sisyphus"),
+        ];
+        let stream = messages_to_ipc(&msgs);
+        let messages = read_stream(&stream);
+        assert_eq!(messages.len(), 2);
+
+        let batch = &messages[1];
+        assert_eq!(batch.header_type(), HEADER_RECORD_BATCH);
+        let (rows, buffers) = batch_layout(batch);
+        assert_eq!(rows, 2);
+        assert!(buffers.iter().all(|(offset, _)| offset % 8 == 0));
+
+        // modelId: validity, offsets, data (buffers 3..6)
+        let (off, len) = buffers[4];
+        let offsets: Vec<i32> = batch.body[off..off + len]
+            .chunks(4)
+            .map(|c| i32::from_le_bytes(c.try_into().unwrap()))
+            .collect();
+        assert_eq!(offsets, vec![0, 15, 30]);
+        let (off, len) = buffers[5];
+        assert_eq!(&batch.body[off..off + len], b"claude-sonnet-4claude-opus-4-5");
+
+        // timestamp values (buffers 12..14)
+        let (off, _) = buffers[13];
+        assert_eq!(i64_at(batch.body, off + 8), 1_700_000_060_000);
+
+        // agent validity bitmap, before training's two buffers and mode's three:
+        // only the second row is set
+        let (off, len) = buffers[buffers.len() - 8];
+        assert_eq!(len, 1);
+        assert_eq!(batch.body[off] & 0b11, 0b10);
+    }
+
+    #[test]
+    fn test_batches_split_by_row_count() {
+        let msgs: Vec<UnifiedMessage> =
+            (0..BATCH_ROWS + 1).map(|i| message("session-1", i as i64, 0.25).with_model("gpt-4o")).collect();
+        let stream = messages_to_ipc(&msgs);
+        let messages = read_stream(&stream);
+
+        assert_eq!(messages.len(), 3);
+        assert_eq!(batch_layout(&messages[1]).0, BATCH_ROWS as i64);
+        assert_eq!(batch_layout(&messages[2]).0, 1);
+    }
+
+    fn read_with_arrow(stream: &[u8]) -> (arrow_schema::SchemaRef, Vec<arrow_array::RecordBatch>) {
+        let reader = arrow_ipc::reader::StreamReader::try_new(stream, None).unwrap();
+        let schema = reader.schema();
+        let batches = reader.collect::<Result<Vec<_>, _>>().unwrap();
+        (schema, batches)
+    }
+
+    fn arrow_type(data_type: DataType) -> arrow_schema::DataType {
+        match data_type {
+            DataType::Utf8 => arrow_schema::DataType::Utf8,
+            DataType::Int32 => arrow_schema::DataType::Int32,
+            DataType::Int64 => arrow_schema::DataType::Int64,
+            DataType::Float64 => arrow_schema::DataType::Float64,
+        }
+    }
+
+    #[test]
+    fn test_readable_by_arrow_ipc() {
+        use arrow_array::cast::AsArray;
+        use arrow_array::types::{Float64Type, Int64Type};
+        use arrow_array::Array;
+
+        let msgs: Vec<UnifiedMessage> = (0..BATCH_ROWS + 2)
+            .map(|i| message("session-1", 1_700_000_000_000 + i as i64, 0.25))
+            .chain([message("session-2", 1_700_000_100_000, 1.5).with_model("claude-opus-4-5").with_agent("Sisyphus")])
+            .collect();
+        let (schema, batches) = read_with_arrow(&messages_to_ipc(&msgs));
+
+        for (field, expected) in schema.fields().iter().zip(MESSAGE_FIELDS) {
+            assert_eq!(field.name(), expected.name);
+            assert_eq!(field.data_type(), &arrow_type(expected.data_type));
+            assert_eq!(field.is_nullable(), expected.nullable);
+        }
+        assert_eq!(schema.fields().len(), MESSAGE_FIELDS.len());
+        assert_eq!(batches.iter().map(|b| b.num_rows()).collect::<Vec<_>>(), vec![BATCH_ROWS, 3]);
+
+        let last = &batches[1];
+        let column = |name: &str| last.column_by_name(name).unwrap();
+        assert_eq!(column("modelId").as_string::<i32>().value(2), "claude-opus-4-5");
+        assert_eq!(column("sessionId").as_string::<i32>().value(0), "session-1");
+        assert_eq!(column("timestamp").as_primitive::<Int64Type>().value(1), 1_700_000_000_000 + BATCH_ROWS as i64 + 1);
+        assert_eq!(column("cost").as_primitive::<Float64Type>().value(2), 1.5);
+
+        let agent = column("agent").as_string::<i32>();
+        assert_eq!(agent.null_count(), 2);
+        assert!(agent.is_null(0));
+        assert_eq!(agent.value(2), "Sisyphus");
+
+        let contributions = crate::aggregator::aggregate_by_date(msgs);
+        let (schema, batches) = read_with_arrow(&contributions_to_ipc(&contributions));
+        for (field, expected) in schema.fields().iter().zip(CONTRIBUTION_FIELDS) {
+            assert_eq!(field.name(), expected.name);
+            assert_eq!(field.data_type(), &arrow_type(expected.data_type));
+        }
+        let rows: usize = batches.iter().map(|b| b.num_rows()).sum();
+        assert_eq!(rows, contributions.iter().map(|day| day.sources.len()).sum::<usize>());
+        let messages = batches[0].column_by_name("messages").unwrap();
+        let total: i32 = messages.as_primitive::<arrow_array::types::Int32Type>().iter().flatten().sum();
+        assert_eq!(total as usize, BATCH_ROWS + 3);
+    }
+
+    #[test]
+    fn test_contributions_flattened_per_source() {
+        let msgs = vec![
+            message("session-1", 1_700_000_000_000, 0.25),
+            message("session-1", 1_700_000_000_000, 0.25).with_model("claude-opus-4-5"),
+        ];
+        let contributions = crate::aggregator::aggregate_by_date(msgs);
+        let stream = contributions_to_ipc(&contributions);
+        let messages = read_stream(&stream);
+
+        assert_eq!(schema_fields(&messages[0]).len(), CONTRIBUTION_FIELDS.len());
+        assert_eq!(batch_layout(&messages[1]).0, 2);
+    }
+}
@@ -0,0 +1,212 @@
+//! Spending budgets with burn-rate projection
+//!
+//! A budget caps spend per calendar day, week (from Monday) or month, in UTC
+//! like message dates, and is set in settings.json:
+//!
+//! ```json
+//! "budgets": [{ "name": "team", "period": "month", "amount": 500, "sources": ["claude"] }]
+//! ```
+//!
+//! Knowing a budget was exceeded is late. Each budget is also projected to
+//! the end of its period at the current burn rate, the cost per hour over the
+//! last [`BURN_WINDOW_HOURS`] hours, so it can be flagged as soon as the
+//! projection crosses the amount, with the time it would be crossed.
+
+use crate::sessions::UnifiedMessage;
+use crate::BudgetStatus;
+use chrono::{DateTime, Datelike, Duration, Months, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Hours of recent usage the burn rate is measured over
+pub const BURN_WINDOW_HOURS: i64 = 24;
+
+pub const OK: &str = "ok";
+pub const PROJECTED: &str = "projected";
+pub const EXCEEDED: &str = "exceeded";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum BudgetPeriod {
+    Day,
+    Week,
+    #[default]
+    Month,
+}
+
+impl BudgetPeriod {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            BudgetPeriod::Day => "day",
+            BudgetPeriod::Week => "week",
+            BudgetPeriod::Month => "month",
+        }
+    }
+
+    /// First day of the period containing `day`, and first day of the next
+    fn bounds(self, day: NaiveDate) -> (NaiveDate, NaiveDate) {
+        let start = match self {
+            BudgetPeriod::Day => day,
+            BudgetPeriod::Week => day - Duration::days(day.weekday().num_days_from_monday() as i64),
+            BudgetPeriod::Month => day.with_day(1).unwrap_or(day),
+        };
+        let next = match self {
+            BudgetPeriod::Day => start + Duration::days(1),
+            BudgetPeriod::Week => start + Duration::days(7),
+            BudgetPeriod::Month => start.checked_add_months(Months::new(1)).unwrap_or(start),
+        };
+        (start, next)
+    }
+}
+
+/// One entry of `budgets` in settings.json
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Budget {
+    /// Shown in alerts; defaults to the period ("month")
+    pub name: Option<String>,
+    pub period: BudgetPeriod,
+    /// USD per period
+    pub amount: f64,
+    /// Sources counted toward the budget; empty for all
+    pub sources: Vec<String>,
+    /// Command run once the budget is exceeded, making it a hard cap (see
+    /// [`crate::enforcement`])
+    pub enforce: Option<String>,
+}
+
+impl Budget {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.amount.is_nan() || self.amount <= 0.0 {
+            return Err(format!("amount must be positive, got {}", self.amount));
+        }
+        if self.enforce.as_deref().is_some_and(|command| command.trim().is_empty()) {
+            return Err("enforce must be a command".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn name(&self) -> &str {
+        self.name.as_deref().unwrap_or(self.period.as_str())
+    }
+
+    fn counts(&self, msg: &UnifiedMessage) -> bool {
+        self.sources.is_empty() || self.sources.iter().any(|s| s == msg.source.as_str())
+    }
+}
+
+/// Spend so far in the current period, and where it is heading
+pub fn status(budget: &Budget, messages: &[UnifiedMessage], now: DateTime<Utc>) -> BudgetStatus {
+    let (start, next) = budget.period.bounds(now.date_naive());
+    let first_day = start.format("%Y-%m-%d").to_string();
+    let last_day = next.pred_opt().unwrap_or(next).format("%Y-%m-%d").to_string();
+    let window_start = (now - Duration::hours(BURN_WINDOW_HOURS)).timestamp_millis();
+    let now_ms = now.timestamp_millis();
+
+    let (mut spent, mut recent) = (0.0, 0.0);
+    for msg in messages.iter().filter(|m| budget.counts(m)) {
+        if msg.date.as_str() >= first_day.as_str() && msg.date.as_str() <= last_day.as_str() {
+            spent += msg.cost;
+        }
+        if msg.timestamp > window_start && msg.timestamp <= now_ms {
+            recent += msg.cost;
+        }
+    }
+
+    let cost_per_hour = recent / BURN_WINDOW_HOURS as f64;
+    let end = next.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc();
+    let hours_left = ((end - now).num_seconds().max(0) as f64) / 3600.0;
+    let projected_cost = spent + cost_per_hour * hours_left;
+
+    let projected_breach = if spent < budget.amount && cost_per_hour > 0.0 {
+        let hours = (budget.amount - spent) / cost_per_hour;
+        let breach = now + Duration::seconds((hours * 3600.0).ceil() as i64);
+        (breach < end).then(|| breach.to_rfc3339_opts(chrono::SecondsFormat::Secs, true))
+    } else {
+        None
+    };
+    let state = if spent >= budget.amount {
+        EXCEEDED
+    } else if projected_breach.is_some() {
+        PROJECTED
+    } else {
+        OK
+    };
+
+    BudgetStatus {
+        name: budget.name().to_string(),
+        period: budget.period.as_str().to_string(),
+        amount: budget.amount,
+        period_start: first_day,
+        period_end: last_day,
+        spent,
+        cost_per_hour,
+        projected_cost,
+        projected_breach,
+        state: state.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{message, TestMessage};
+    use chrono::TimeZone;
+
+    fn at(y: i32, m: u32, d: u32, h: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(y, m, d, h, 0, 0).unwrap()
+    }
+
+    fn budget(period: BudgetPeriod, amount: f64) -> Budget {
+        Budget { period, amount, ..Budget::default() }
+    }
+
+    #[test]
+    fn test_period_bounds() {
+        let day = NaiveDate::from_ymd_opt(2025, 5, 14).unwrap(); // a Wednesday
+        let ymd = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+        assert_eq!(BudgetPeriod::Day.bounds(day), (ymd(2025, 5, 14), ymd(2025, 5, 15)));
+        assert_eq!(BudgetPeriod::Week.bounds(day), (ymd(2025, 5, 12), ymd(2025, 5, 19)));
+        assert_eq!(BudgetPeriod::Month.bounds(day), (ymd(2025, 5, 1), ymd(2025, 6, 1)));
+    }
+
+    #[test]
+    fn test_projects_breach_before_period_end() {
+        let now = at(2025, 5, 10, 12);
+        // $50 early in the month, then $48 over the last day: $2/hour
+        let messages = vec![
+            message("s1", at(2025, 5, 2, 12).timestamp_millis(), 50.0),
+            message("s1", at(2025, 5, 10, 0).timestamp_millis(), 48.0),
+            message("s1", at(2025, 5, 10, 1).timestamp_millis(), 1000.0).with_source("codex"),
+        ];
+        let mut budget = budget(BudgetPeriod::Month, 200.0);
+        budget.sources = vec!["claude".to_string()];
+
+        let status = status(&budget, &messages, now);
+        assert_eq!(status.spent, 98.0);
+        assert_eq!(status.cost_per_hour, 2.0);
+        assert_eq!(status.state, PROJECTED);
+        assert_eq!((status.period_start.as_str(), status.period_end.as_str()), ("2025-05-01", "2025-05-31"));
+        // $102 left at $2/hour is 51 hours away
+        let breach = DateTime::parse_from_rfc3339(status.projected_breach.as_deref().unwrap()).unwrap();
+        assert_eq!(breach.timestamp(), (now + Duration::hours(51)).timestamp());
+        assert!(status.projected_cost > 200.0);
+    }
+
+    #[test]
+    fn test_states() {
+        let now = at(2025, 5, 10, 12);
+        let messages = vec![message("s1", at(2025, 5, 10, 6).timestamp_millis(), 12.0)];
+
+        // $0.50/hour over the 12 hours left today ends at $18
+        let status = status(&budget(BudgetPeriod::Day, 20.0), &messages, now);
+        assert_eq!(status.state, OK);
+        assert!(status.projected_breach.is_none());
+        assert!((status.projected_cost - 18.0).abs() < 1e-9);
+
+        assert_eq!(super::status(&budget(BudgetPeriod::Day, 15.0), &messages, now).state, PROJECTED);
+        assert_eq!(super::status(&budget(BudgetPeriod::Day, 10.0), &messages, now).state, EXCEEDED);
+        assert!(budget(BudgetPeriod::Week, 0.0).validate().is_err());
+        let blank_hook = Budget { enforce: Some(" ".to_string()), ..budget(BudgetPeriod::Week, 10.0) };
+        assert!(blank_hook.validate().is_err());
+    }
+}
@@ -0,0 +1,245 @@
+//! Cache directory garbage collection
+//!
+//! Removes leftovers that nothing else cleans up: `.tmp` files from
+//! interrupted atomic writes, aggregation checkpoints that are long past being
+//! useful, and parse-cache entries for session files that have since been
+//! deleted. Pricing snapshots (`pricing-*.json`) are kept at any age: they are
+//! rewritten on every fetch, and an old one is what prices offline runs.
+//!
+//! Runs on demand (`cache clean`, optionally as a dry run) and automatically
+//! at most once a day after a parse.
+
+use crate::chunked::CHECKPOINT_FILE;
+use crate::parse_cache::{self, ParseCache};
+use crate::pricing::cache::get_cache_dir;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// Temp files younger than this may belong to a write in progress
+const TMP_GRACE: Duration = Duration::from_secs(60 * 60);
+/// Checkpoints older than this are removed
+const CHECKPOINT_MAX_AGE: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+/// Minimum time between automatic collections
+const AUTO_GC_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+const AUTO_GC_MARKER: &str = ".last-gc";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GcReason {
+    OrphanedTemp,
+    Expired,
+}
+
+impl GcReason {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            GcReason::OrphanedTemp => "orphaned-temp",
+            GcReason::Expired => "expired",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GcItem {
+    pub path: PathBuf,
+    pub bytes: u64,
+    pub reason: GcReason,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct GcReport {
+    /// Files removed (or that would be, in a dry run)
+    pub removed: Vec<GcItem>,
+    pub bytes_freed: u64,
+    /// Parse-cache entries for session files that no longer exist
+    pub parse_entries_pruned: usize,
+    /// Size of the cache directory before collection
+    pub total_bytes: u64,
+}
+
+/// Collect garbage in `dir`. With `dry_run` nothing is modified, but the
+/// report lists what would be removed.
+pub fn collect(dir: &Path, dry_run: bool, now: SystemTime) -> std::io::Result<GcReport> {
+    let mut report = GcReport::default();
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(report),
+        Err(e) => return Err(e),
+    };
+
+    for entry in entries.flatten() {
+        let Ok(metadata) = entry.metadata() else { continue };
+        if !metadata.is_file() {
+            continue;
+        }
+        report.total_bytes += metadata.len();
+
+        let path = entry.path();
+        let age = metadata
+            .modified()
+            .ok()
+            .and_then(|mtime| now.duration_since(mtime).ok())
+            .unwrap_or_default();
+        let Some(reason) = classify(&path, age) else { continue };
+
+        if !dry_run {
+            if let Err(e) = fs::remove_file(&path) {
+                eprintln!("[tokscale] failed to remove {}: {}", path.display(), e);
+                continue;
+            }
+        }
+        report.bytes_freed += metadata.len();
+        report.removed.push(GcItem { path, bytes: metadata.len(), reason });
+    }
+
+    let mut parse_cache = ParseCache::load(dir.join(parse_cache::CACHE_FILE));
+    report.parse_entries_pruned = parse_cache.prune_missing();
+    if !dry_run && report.parse_entries_pruned > 0 {
+        parse_cache.save()?;
+    }
+
+    Ok(report)
+}
+
+fn classify(path: &Path, age: Duration) -> Option<GcReason> {
+    let name = path.file_name()?.to_str()?;
+
+    if name.starts_with('.') && name.ends_with(".tmp") {
+        return (age > TMP_GRACE).then_some(GcReason::OrphanedTemp);
+    }
+    if name == CHECKPOINT_FILE {
+        return (age > CHECKPOINT_MAX_AGE).then_some(GcReason::Expired);
+    }
+    None
+}
+
+/// Collect garbage in the cache directory if the last run was more than a day
+/// ago. Failures are reported but never interrupt the caller.
+pub fn maybe_auto_collect() {
+    let dir = get_cache_dir();
+    let marker = dir.join(AUTO_GC_MARKER);
+    let now = SystemTime::now();
+
+    let due = fs::metadata(&marker)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|last| now.duration_since(last).ok())
+        .is_none_or(|elapsed| elapsed > AUTO_GC_INTERVAL);
+    if !due || !dir.is_dir() {
+        return;
+    }
+
+    if let Err(e) = collect(&dir, false, now) {
+        eprintln!("[tokscale] cache cleanup failed: {}", e);
+    }
+    let _ = fs::write(&marker, b"");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pricing::cache::CachedData;
+    use crate::scanner::SessionType;
+    use crate::sessions::UnifiedMessage;
+    use crate::TokenBreakdown;
+    use std::time::UNIX_EPOCH;
+    use tempfile::TempDir;
+
+    const HOUR: Duration = Duration::from_secs(60 * 60);
+    const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+    fn write_aged(path: &Path, content: &[u8], age: Duration) {
+        fs::write(path, content).unwrap();
+        let file = fs::File::options().write(true).open(path).unwrap();
+        file.set_modified(SystemTime::now() - age).unwrap();
+    }
+
+    fn snapshot(age: Duration) -> Vec<u8> {
+        let written = SystemTime::now() - age;
+        let timestamp = written.duration_since(UNIX_EPOCH).unwrap().as_secs();
+        serde_json::to_vec(&CachedData { timestamp, data: serde_json::json!({"m": 1}) }).unwrap()
+    }
+
+    fn names(report: &GcReport) -> Vec<String> {
+        let mut names: Vec<String> = report
+            .removed
+            .iter()
+            .map(|item| item.path.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    fn setup(dir: &Path) {
+        write_aged(&dir.join(".pricing-litellm.json.1.ab.tmp"), b"partial", 2 * HOUR);
+        write_aged(&dir.join(".parse-cache.bin.7.tmp"), b"in progress", Duration::ZERO);
+        write_aged(&dir.join("pricing-litellm.json"), &snapshot(HOUR), HOUR);
+        write_aged(&dir.join("pricing-openrouter.json"), &snapshot(30 * DAY), 30 * DAY);
+        write_aged(&dir.join(CHECKPOINT_FILE), b"{}", 8 * DAY);
+        write_aged(&dir.join("unrelated.json"), b"[1, 2, 3]", 30 * DAY);
+    }
+
+    #[test]
+    fn test_collect_removes_stale_files() {
+        let dir = TempDir::new().unwrap();
+        setup(dir.path());
+
+        let report = collect(dir.path(), false, SystemTime::now()).unwrap();
+        assert_eq!(
+            names(&report),
+            vec![".pricing-litellm.json.1.ab.tmp", "aggregate-checkpoint.json"]
+        );
+        assert_eq!(report.bytes_freed, report.removed.iter().map(|i| i.bytes).sum::<u64>());
+        assert!(report.total_bytes > report.bytes_freed);
+
+        assert!(dir.path().join(".parse-cache.bin.7.tmp").exists());
+        assert!(dir.path().join("pricing-litellm.json").exists());
+        assert!(dir.path().join("unrelated.json").exists());
+        // Old prices are still the offline fallback
+        assert!(dir.path().join("pricing-openrouter.json").exists());
+    }
+
+    #[test]
+    fn test_dry_run_keeps_files() {
+        let dir = TempDir::new().unwrap();
+        setup(dir.path());
+
+        let report = collect(dir.path(), true, SystemTime::now()).unwrap();
+        assert_eq!(report.removed.len(), 2);
+        assert!(report.removed.iter().all(|item| item.path.exists()));
+        assert!(report.removed.iter().any(|item| item.reason == GcReason::OrphanedTemp));
+    }
+
+    #[test]
+    fn test_prunes_parse_cache_entries() {
+        let dir = TempDir::new().unwrap();
+        let sessions = dir.path().join("sessions");
+        fs::create_dir(&sessions).unwrap();
+        let kept = sessions.join("kept.jsonl");
+        let deleted = sessions.join("deleted.jsonl");
+        fs::write(&kept, "x").unwrap();
+        fs::write(&deleted, "x").unwrap();
+
+        let files = vec![(SessionType::Claude, kept), (SessionType::Claude, deleted.clone())];
+        let mut cache = ParseCache::load(dir.path().join(parse_cache::CACHE_FILE));
+        cache.parse_files(&files, |_, _| {
+            vec![UnifiedMessage::new("claude", "m", "p", "s", 0, TokenBreakdown::default(), 0.0)]
+        });
+        cache.save().unwrap();
+        fs::remove_file(&deleted).unwrap();
+
+        let report = collect(dir.path(), true, SystemTime::now()).unwrap();
+        assert_eq!(report.parse_entries_pruned, 1);
+
+        collect(dir.path(), false, SystemTime::now()).unwrap();
+        let report = collect(dir.path(), false, SystemTime::now()).unwrap();
+        assert_eq!(report.parse_entries_pruned, 0);
+    }
+
+    #[test]
+    fn test_missing_dir() {
+        let report = collect(Path::new("/nonexistent/tokscale-cache"), false, SystemTime::now()).unwrap();
+        assert!(report.removed.is_empty());
+        assert_eq!(report.total_bytes, 0);
+    }
+}
@@ -0,0 +1,156 @@
+//! Threshold checks for CI
+//!
+//! `tokscale check` tests usage against limits and exits non-zero when one is
+//! broken, for scheduled CI jobs that guard a team's spend and pricing
+//! coverage:
+//!
+//! - `--max-daily <usd>`: no day in the range costs more than this
+//! - `--max-unknown-model-share <0-1>`: at most this share of the messages
+//!   used models without a list price (which count as free, so a growing
+//!   share means reports understate spend)
+//!
+//! Only the limits given are checked. Costs are before free tiers and
+//! credits, like budgets.
+
+use crate::pricing::PricingService;
+use crate::run_summary;
+use crate::sessions::UnifiedMessage;
+use crate::{CheckReport, ThresholdCheck};
+use std::collections::BTreeMap;
+
+pub const MAX_DAILY: &str = "max-daily";
+pub const MAX_UNKNOWN_MODEL_SHARE: &str = "max-unknown-model-share";
+
+/// Days or models named in a failed check's detail before the rest are counted
+const MAX_LISTED: usize = 5;
+
+#[derive(Debug, Clone, Default)]
+pub struct Thresholds {
+    /// USD
+    pub max_daily: Option<f64>,
+    /// 0-1
+    pub max_unknown_model_share: Option<f64>,
+}
+
+impl Thresholds {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_daily.is_none() && self.max_unknown_model_share.is_none() {
+            return Err("Nothing to check; give --max-daily or --max-unknown-model-share".to_string());
+        }
+        if let Some(max) = self.max_daily.filter(|max| !(max.is_finite() && *max >= 0.0)) {
+            return Err(format!("--max-daily must be a non-negative amount, got {}", max));
+        }
+        if let Some(max) = self.max_unknown_model_share.filter(|max| !(0.0..=1.0).contains(max)) {
+            return Err(format!("--max-unknown-model-share must be between 0 and 1, got {}", max));
+        }
+        Ok(())
+    }
+}
+
+/// `items` joined, with the ones past [`MAX_LISTED`] counted
+fn listed(items: &[String]) -> String {
+    let more = items.len().saturating_sub(MAX_LISTED);
+    let shown = items[..items.len() - more].join(", ");
+    if more > 0 {
+        format!("{} +{}", shown, more)
+    } else {
+        shown
+    }
+}
+
+fn max_daily(messages: &[UnifiedMessage], max: f64) -> ThresholdCheck {
+    let mut days: BTreeMap<&str, f64> = BTreeMap::new();
+    for msg in messages {
+        *days.entry(msg.date.as_str()).or_default() += msg.cost;
+    }
+    let highest = days.values().copied().fold(0.0, f64::max);
+    let over: Vec<String> =
+        days.iter().filter(|(_, cost)| **cost > max).map(|(day, cost)| format!("{} ${:.2}", day, cost)).collect();
+    ThresholdCheck {
+        name: MAX_DAILY.to_string(),
+        passed: over.is_empty(),
+        limit: max,
+        actual: highest,
+        detail: (!over.is_empty()).then(|| listed(&over)),
+    }
+}
+
+fn max_unknown_model_share(messages: &[UnifiedMessage], pricing: Option<&PricingService>, max: f64) -> ThresholdCheck {
+    let (models, unknown) = pricing.map(|pricing| run_summary::unknown_models(messages, pricing)).unwrap_or_default();
+    let share = if messages.is_empty() { 0.0 } else { unknown as f64 / messages.len() as f64 };
+    ThresholdCheck {
+        name: MAX_UNKNOWN_MODEL_SHARE.to_string(),
+        passed: share <= max,
+        limit: max,
+        actual: share,
+        detail: (!models.is_empty()).then(|| listed(&models)),
+    }
+}
+
+/// `messages`, priced with `pricing`, against validated `thresholds`.
+/// `pricing` is only needed for the unknown-model share of some messages.
+pub fn run(messages: &[UnifiedMessage], pricing: Option<&PricingService>, thresholds: &Thresholds) -> CheckReport {
+    let mut checks = Vec::new();
+    if let Some(max) = thresholds.max_daily {
+        checks.push(max_daily(messages, max));
+    }
+    if let Some(max) = thresholds.max_unknown_model_share {
+        checks.push(max_unknown_model_share(messages, pricing, max));
+    }
+    CheckReport {
+        passed: checks.iter().all(|check| check.passed),
+        messages: messages.len() as i64,
+        checks,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pricing::ModelPricing;
+    use crate::testutil::{message, TestMessage};
+    use std::collections::HashMap;
+
+    const DAY: i64 = 86_400_000;
+    const T0: i64 = 1_748_779_200_000;
+
+    fn pricing() -> PricingService {
+        let sonnet = ModelPricing { input_cost_per_token: Some(3e-6), ..ModelPricing::default() };
+        PricingService::new(HashMap::from([("claude-sonnet-4".to_string(), sonnet)]), HashMap::new())
+    }
+
+    #[test]
+    fn test_thresholds() {
+        let messages = vec![
+            message("s1", T0, 30.0),
+            message("s1", T0 + 60_000, 25.0),
+            message("s1", T0 + DAY, 10.0),
+            message("s1", T0 + DAY, 0.0).with_model("in-house-model-x"),
+        ];
+        let thresholds = Thresholds { max_daily: Some(50.0), max_unknown_model_share: Some(0.3) };
+        let report = run(&messages, Some(&pricing()), &thresholds);
+
+        assert!(!report.passed);
+        let daily = &report.checks[0];
+        assert!(!daily.passed);
+        assert!((daily.actual - 55.0).abs() < 1e-9);
+        assert_eq!(daily.detail.as_deref(), Some(format!("{} $55.00", messages[0].date).as_str()));
+        let unknown = &report.checks[1];
+        assert!(unknown.passed, "1 of 4 messages is under 30%");
+        assert_eq!(unknown.detail.as_deref(), Some("in-house-model-x"));
+
+        let strict = Thresholds { max_daily: None, max_unknown_model_share: Some(0.05) };
+        assert!(!run(&messages, Some(&pricing()), &strict).passed);
+        let lenient = Thresholds { max_daily: Some(60.0), max_unknown_model_share: None };
+        assert!(run(&messages, None, &lenient).passed);
+        assert!(run(&[], None, &strict).passed, "no usage breaks no limit");
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(Thresholds::default().validate().unwrap_err().contains("Nothing to check"));
+        assert!(Thresholds { max_daily: Some(-1.0), ..Thresholds::default() }.validate().is_err());
+        assert!(Thresholds { max_unknown_model_share: Some(5.0), ..Thresholds::default() }.validate().is_err());
+        assert!(Thresholds { max_daily: Some(0.0), max_unknown_model_share: Some(1.0) }.validate().is_ok());
+    }
+}
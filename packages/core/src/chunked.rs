@@ -0,0 +1,392 @@
+//! Resume-safe chunked aggregation
+//!
+//! A first ingest of years of history can take minutes. Files are parsed in
+//! chunks and the running per-day aggregates are checkpointed to disk after
+//! every chunk, so an interrupted run picks up where it stopped instead of
+//! starting over.
+//!
+//! A checkpoint is only reused if every file it covers is unchanged (same size
+//! and modification time); otherwise aggregation restarts from scratch rather
+//! than double-counting appended sessions.
+
+use crate::aggregator::{finish_days, DayAccumulator};
+use crate::scanner::SessionType;
+use crate::sessions::UnifiedMessage;
+use crate::DailyContribution;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+const CHECKPOINT_VERSION: u32 = 2;
+pub(crate) const CHECKPOINT_FILE: &str = "aggregate-checkpoint.json";
+
+/// Files parsed between checkpoints
+pub const DEFAULT_CHUNK_SIZE: usize = 500;
+
+#[derive(Default, Serialize, Deserialize)]
+struct Checkpoint {
+    version: u32,
+    /// Caller-defined description of the run (sources, filters); a checkpoint
+    /// from a run with a different scope is never resumed
+    scope: String,
+    /// Processed file path -> fingerprint at the time it was parsed
+    files: HashMap<String, String>,
+    /// Dedup keys already counted (Claude request/message ids)
+    seen_dedup_keys: HashSet<String>,
+    days: HashMap<String, DayAccumulator>,
+}
+
+/// Outcome of a completed chunked run
+pub struct ChunkedOutcome {
+    pub contributions: Vec<DailyContribution>,
+    /// Files parsed during this run
+    pub files_parsed: usize,
+    /// Files skipped because an earlier, interrupted run already counted them
+    pub files_resumed: usize,
+}
+
+pub struct ChunkedAggregator {
+    checkpoint_path: PathBuf,
+    chunk_size: usize,
+    scope: String,
+}
+
+impl ChunkedAggregator {
+    pub fn new(checkpoint_path: impl Into<PathBuf>) -> Self {
+        Self {
+            checkpoint_path: checkpoint_path.into(),
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            scope: String::new(),
+        }
+    }
+
+    pub fn with_scope(mut self, scope: impl Into<String>) -> Self {
+        self.scope = scope.into();
+        self
+    }
+
+    #[cfg(test)]
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size.max(1);
+        self
+    }
+
+    pub fn default_checkpoint_path() -> PathBuf {
+        crate::pricing::cache::get_cache_path(CHECKPOINT_FILE)
+    }
+
+    /// Aggregate `files`, resuming from a valid checkpoint if one exists.
+    /// The checkpoint is removed once the run completes.
+    pub fn run<F>(
+        &self,
+        files: &[(SessionType, PathBuf)],
+        parse: F,
+    ) -> std::io::Result<ChunkedOutcome>
+    where
+        F: Fn(SessionType, &Path) -> Vec<UnifiedMessage> + Sync,
+    {
+        let mut checkpoint = self.load(files);
+        let files_resumed = checkpoint.files.len();
+        let mut files_parsed = 0;
+
+        while let Some(parsed) = self.process_chunk(&mut checkpoint, files, &parse)? {
+            files_parsed += parsed;
+        }
+
+        self.discard();
+        Ok(ChunkedOutcome {
+            contributions: finish_days(checkpoint.days),
+            files_parsed,
+            files_resumed,
+        })
+    }
+
+    /// Remove any checkpoint left by an earlier run
+    pub fn discard(&self) {
+        let _ = fs::remove_file(&self.checkpoint_path);
+    }
+
+    /// Parse the next chunk of unprocessed files and persist the checkpoint.
+    /// Returns the number of files parsed, or `None` when nothing is left.
+    fn process_chunk<F>(
+        &self,
+        checkpoint: &mut Checkpoint,
+        files: &[(SessionType, PathBuf)],
+        parse: &F,
+    ) -> std::io::Result<Option<usize>>
+    where
+        F: Fn(SessionType, &Path) -> Vec<UnifiedMessage> + Sync,
+    {
+        let chunk: Vec<&(SessionType, PathBuf)> = files
+            .iter()
+            .filter(|(_, path)| !checkpoint.files.contains_key(&path_key(path)))
+            .take(self.chunk_size)
+            .collect();
+        if chunk.is_empty() {
+            return Ok(None);
+        }
+
+        let parsed: Vec<(String, String, Vec<UnifiedMessage>)> = chunk
+            .par_iter()
+            .map(|(session_type, path)| {
+                // Fingerprint before parsing so a concurrent append invalidates the checkpoint
+                let fingerprint = fingerprint(path).unwrap_or_default();
+                (path_key(path), fingerprint, parse(*session_type, path))
+            })
+            .collect();
+
+        let count = parsed.len();
+        for (key, fingerprint, messages) in parsed {
+            for msg in messages {
+                if let Some(dedup_key) = msg.dedup_key.as_ref().filter(|k| !k.is_empty()) {
+                    if !checkpoint.seen_dedup_keys.insert(dedup_key.clone()) {
+                        continue;
+                    }
+                }
+                checkpoint.days.entry(msg.date.clone()).or_default().add_message(&msg);
+            }
+            checkpoint.files.insert(key, fingerprint);
+        }
+
+        self.save(checkpoint)?;
+        Ok(Some(count))
+    }
+
+    /// Load the checkpoint if it is current and covers only unchanged files
+    fn load(&self, files: &[(SessionType, PathBuf)]) -> Checkpoint {
+        let fresh = Checkpoint {
+            version: CHECKPOINT_VERSION,
+            scope: self.scope.clone(),
+            ..Default::default()
+        };
+
+        let Some(checkpoint) = fs::read(&self.checkpoint_path)
+            .ok()
+            .and_then(|data| serde_json::from_slice::<Checkpoint>(&data).ok())
+        else {
+            return fresh;
+        };
+        if checkpoint.version != CHECKPOINT_VERSION || checkpoint.scope != self.scope {
+            return fresh;
+        }
+
+        let current: HashSet<String> = files.iter().map(|(_, p)| path_key(p)).collect();
+        let valid = checkpoint.files.iter().all(|(path, recorded)| {
+            current.contains(path) && fingerprint(Path::new(path)).as_ref() == Some(recorded)
+        });
+
+        if valid {
+            checkpoint
+        } else {
+            fresh
+        }
+    }
+
+    fn save(&self, checkpoint: &Checkpoint) -> std::io::Result<()> {
+        let dir = self
+            .checkpoint_path
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+        fs::create_dir_all(&dir)?;
+
+        let content = serde_json::to_vec(checkpoint)?;
+        let file_name = self
+            .checkpoint_path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(|| CHECKPOINT_FILE.to_string());
+        let tmp_path = dir.join(format!(".{}.{}.tmp", file_name, std::process::id()));
+
+        let write_result = (|| {
+            let mut file = fs::File::create(&tmp_path)?;
+            file.write_all(&content)?;
+            file.sync_all()?;
+            fs::rename(&tmp_path, &self.checkpoint_path)
+        })();
+
+        if write_result.is_err() {
+            let _ = fs::remove_file(&tmp_path);
+        }
+
+        write_result
+    }
+}
+
+fn path_key(path: &Path) -> String {
+    path.to_string_lossy().into_owned()
+}
+
+fn fingerprint(path: &Path) -> Option<String> {
+    let metadata = fs::metadata(path).ok()?;
+    let mtime = metadata
+        .modified()
+        .ok()?
+        .duration_since(UNIX_EPOCH)
+        .ok()?
+        .as_millis();
+    Some(format!("{}:{}", metadata.len(), mtime))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TokenBreakdown;
+    use tempfile::TempDir;
+
+    /// Each test file holds "<date> <input tokens> [dedup key]" lines
+    fn parse_test_file(_: SessionType, path: &Path) -> Vec<UnifiedMessage> {
+        fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(|line| {
+                let parts: Vec<&str> = line.split_whitespace().collect();
+                let timestamp = chrono::NaiveDate::parse_from_str(parts[0], "%Y-%m-%d")
+                    .unwrap()
+                    .and_hms_opt(12, 0, 0)
+                    .unwrap()
+                    .and_utc()
+                    .timestamp_millis();
+                let mut msg = UnifiedMessage::new(
+                    "claude",
+                    "claude-sonnet-4",
+                    "anthropic",
+                    "s1",
+                    timestamp,
+                    TokenBreakdown {
+                        input: parts[1].parse().unwrap(),
+                        ..Default::default()
+                    },
+                    0.5,
+                );
+                msg.dedup_key = parts.get(2).map(|k| k.to_string());
+                msg
+            })
+            .collect()
+    }
+
+    fn write_files(dir: &Path, contents: &[&str]) -> Vec<(SessionType, PathBuf)> {
+        contents
+            .iter()
+            .enumerate()
+            .map(|(i, content)| {
+                let path = dir.join(format!("{}.jsonl", i));
+                fs::write(&path, content).unwrap();
+                (SessionType::Claude, path)
+            })
+            .collect()
+    }
+
+    fn totals(contributions: &[DailyContribution]) -> Vec<(String, i64, i32)> {
+        contributions
+            .iter()
+            .map(|c| (c.date.clone(), c.totals.tokens, c.totals.messages))
+            .collect()
+    }
+
+    #[test]
+    fn test_run_matches_single_pass() {
+        let dir = TempDir::new().unwrap();
+        let files = write_files(
+            dir.path(),
+            &["2025-01-01 10\n2025-01-02 20", "2025-01-02 5 dup\n2025-01-03 1", "2025-01-02 5 dup"],
+        );
+        let aggregator = ChunkedAggregator::new(dir.path().join("cp.json")).with_chunk_size(1);
+
+        let outcome = aggregator.run(&files, parse_test_file).unwrap();
+
+        assert_eq!(outcome.files_parsed, 3);
+        assert_eq!(outcome.files_resumed, 0);
+        assert_eq!(
+            totals(&outcome.contributions),
+            vec![
+                ("2025-01-01".to_string(), 10, 1),
+                ("2025-01-02".to_string(), 25, 2),
+                ("2025-01-03".to_string(), 1, 1),
+            ]
+        );
+        assert!(!dir.path().join("cp.json").exists());
+    }
+
+    #[test]
+    fn test_resume_after_interruption() {
+        let dir = TempDir::new().unwrap();
+        let files = write_files(dir.path(), &["2025-01-01 10", "2025-01-01 20", "2025-01-02 30"]);
+        let checkpoint_path = dir.path().join("cp.json");
+
+        // First run is interrupted after one chunk
+        let first = ChunkedAggregator::new(&checkpoint_path).with_chunk_size(2);
+        let mut checkpoint = first.load(&files);
+        assert_eq!(first.process_chunk(&mut checkpoint, &files, &parse_test_file).unwrap(), Some(2));
+        assert!(checkpoint_path.exists());
+
+        let outcome = ChunkedAggregator::new(&checkpoint_path)
+            .run(&files, parse_test_file)
+            .unwrap();
+        assert_eq!(outcome.files_resumed, 2);
+        assert_eq!(outcome.files_parsed, 1);
+        assert_eq!(
+            totals(&outcome.contributions),
+            vec![("2025-01-01".to_string(), 30, 2), ("2025-01-02".to_string(), 30, 1)]
+        );
+    }
+
+    #[test]
+    fn test_modified_file_invalidates_checkpoint() {
+        let dir = TempDir::new().unwrap();
+        let files = write_files(dir.path(), &["2025-01-01 10", "2025-01-02 20"]);
+        let checkpoint_path = dir.path().join("cp.json");
+
+        let first = ChunkedAggregator::new(&checkpoint_path).with_chunk_size(1);
+        let mut checkpoint = first.load(&files);
+        first.process_chunk(&mut checkpoint, &files, &parse_test_file).unwrap();
+
+        // Session appended after it was checkpointed
+        fs::write(&files[0].1, "2025-01-01 10\n2025-01-01 7").unwrap();
+
+        let outcome = ChunkedAggregator::new(&checkpoint_path)
+            .run(&files, parse_test_file)
+            .unwrap();
+        assert_eq!(outcome.files_resumed, 0);
+        assert_eq!(outcome.files_parsed, 2);
+        assert_eq!(outcome.contributions[0].totals.tokens, 17);
+    }
+
+    #[test]
+    fn test_checkpoint_from_other_scope_ignored() {
+        let dir = TempDir::new().unwrap();
+        let files = write_files(dir.path(), &["2025-01-01 10", "2025-01-02 20"]);
+        let checkpoint_path = dir.path().join("cp.json");
+
+        let first = ChunkedAggregator::new(&checkpoint_path)
+            .with_scope("year=2024")
+            .with_chunk_size(1);
+        let mut checkpoint = first.load(&files);
+        first.process_chunk(&mut checkpoint, &files, &parse_test_file).unwrap();
+
+        let outcome = ChunkedAggregator::new(&checkpoint_path)
+            .with_scope("year=2025")
+            .run(&files, parse_test_file)
+            .unwrap();
+        assert_eq!(outcome.files_resumed, 0);
+        assert_eq!(outcome.files_parsed, 2);
+    }
+
+    #[test]
+    fn test_corrupt_checkpoint_ignored() {
+        let dir = TempDir::new().unwrap();
+        let files = write_files(dir.path(), &["2025-01-01 10"]);
+        let checkpoint_path = dir.path().join("cp.json");
+        fs::write(&checkpoint_path, "{ truncated").unwrap();
+
+        let outcome = ChunkedAggregator::new(&checkpoint_path)
+            .run(&files, parse_test_file)
+            .unwrap();
+        assert_eq!(outcome.files_parsed, 1);
+        assert_eq!(outcome.contributions[0].totals.tokens, 10);
+    }
+}
@@ -0,0 +1,249 @@
+//! Timestamp sanity
+//!
+//! Session files are stamped by clocks tokscale doesn't control. Some tools
+//! log local time without a UTC offset, which parsers read as UTC, and a
+//! machine whose clock ran ahead logs messages in the future. Parsed messages
+//! go through [`sanitize`] before anything is reported, as configured by
+//! `timestamps` in settings.json:
+//!
+//! ```json
+//! "timestamps": { "timezones": { "cursor": "local", "gemini": "+09:00" }, "futureToleranceMinutes": 10 }
+//! ```
+//!
+//! - A timestamp without an offset from a source listed in `timezones` is
+//!   read in that zone: "local" is this machine's, anything else a fixed
+//!   offset like "-05:00". Sources not listed stay UTC.
+//! - A timestamp that only makes sense as seconds rather than milliseconds
+//!   (it would fall before 1973 otherwise) is scaled.
+//! - A timestamp more than `futureToleranceMinutes` (default 10) ahead of
+//!   now is moved back to now.
+//!
+//! Scaled and moved timestamps are marked estimated, so
+//! `--exclude-estimated-timestamps` drops them. Every correction, and every
+//! offset-less timestamp left as UTC, is counted per source in the parse
+//! diagnostics. `"sanitize": false` turns the pass off.
+
+use crate::config;
+use crate::diagnostics;
+use crate::sessions::UnifiedMessage;
+use chrono::{DateTime, FixedOffset, Local, NaiveDateTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// A shifted offset-less timestamp
+pub const TIMEZONE: &str = "timezone";
+/// An offset-less timestamp left as UTC because its source has no timezone
+pub const NO_OFFSET: &str = "no-offset";
+/// A timestamp in seconds scaled to milliseconds
+pub const SECONDS: &str = "seconds";
+/// A future timestamp moved back to now
+pub const FUTURE: &str = "future";
+
+const DEFAULT_FUTURE_TOLERANCE_MINUTES: f64 = 10.0;
+/// Milliseconds timestamps below this are before 1973-03-03
+const SECONDS_BELOW: i64 = 100_000_000_000;
+/// 2000-01-01T00:00:00Z; scaled timestamps must land after it
+const EARLIEST_PLAUSIBLE: i64 = 946_684_800_000;
+
+/// `timestamps` in settings.json
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct TimestampSettings {
+    /// Correct timestamps at all (default true)
+    pub sanitize: Option<bool>,
+    /// Zone of each source's offset-less timestamps: "local" or "+HH:MM"
+    pub timezones: BTreeMap<String, String>,
+    pub future_tolerance_minutes: Option<f64>,
+}
+
+impl TimestampSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        for (source, zone) in &self.timezones {
+            Zone::parse(zone).map_err(|reason| format!("timezones.{}: {}", source, reason))?;
+        }
+        if let Some(minutes) = self.future_tolerance_minutes.filter(|m| m.is_nan() || *m < 0.0) {
+            return Err(format!("futureToleranceMinutes must not be negative, got {}", minutes));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Zone {
+    Local,
+    Fixed(FixedOffset),
+}
+
+impl Zone {
+    fn parse(text: &str) -> Result<Zone, String> {
+        let text = text.trim();
+        let invalid = || format!("\"{}\" is not \"local\" or an offset like \"+02:00\"", text);
+        if text.eq_ignore_ascii_case("local") {
+            return Ok(Zone::Local);
+        }
+        if text.eq_ignore_ascii_case("utc") || text == "Z" {
+            return Ok(Zone::Fixed(FixedOffset::east_opt(0).unwrap()));
+        }
+        let (sign, rest) = match text.split_at_checked(1) {
+            Some(("+", rest)) => (1, rest),
+            Some(("-", rest)) => (-1, rest),
+            _ => return Err(invalid()),
+        };
+        let (hours, minutes) = rest.split_once(':').unwrap_or((rest, "0"));
+        let hours: i32 = hours.parse().ok().filter(|h| *h <= 14).ok_or_else(invalid)?;
+        let minutes: i32 = minutes.parse().ok().filter(|m| *m < 60).ok_or_else(invalid)?;
+        FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+            .map(Zone::Fixed)
+            .ok_or_else(invalid)
+    }
+
+    /// UTC milliseconds of a wall-clock time in this zone that was read as
+    /// UTC. DST gaps and overlaps resolve to the earlier instant.
+    fn to_utc(self, wall_ms: i64) -> Option<i64> {
+        let wall = DateTime::from_timestamp_millis(wall_ms)?.naive_utc();
+        match self {
+            Zone::Local => Local.from_local_datetime(&wall).earliest().map(|dt| dt.timestamp_millis()),
+            Zone::Fixed(offset) => offset.from_local_datetime(&wall).earliest().map(|dt| dt.timestamp_millis()),
+        }
+    }
+}
+
+/// Unix milliseconds of an RFC 3339 timestamp, or of one without an offset
+/// ("2025-06-01T09:30:00", read as UTC), and whether the offset was missing
+pub fn parse_timestamp(text: &str) -> Option<(i64, bool)> {
+    if let Ok(dt) = DateTime::parse_from_rfc3339(text) {
+        return Some((dt.timestamp_millis(), false));
+    }
+    ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(text.trim(), format).ok())
+        .map(|dt| (dt.and_utc().timestamp_millis(), true))
+}
+
+/// Correct improbable timestamps per the `timestamps` setting
+pub fn sanitize(messages: &mut [UnifiedMessage]) {
+    let settings = &config::settings().timestamps;
+    if settings.sanitize != Some(false) {
+        sanitize_with(messages, settings, Utc::now().timestamp_millis());
+    }
+}
+
+fn sanitize_with(messages: &mut [UnifiedMessage], settings: &TimestampSettings, now_ms: i64) {
+    let zones: BTreeMap<&str, Zone> = settings
+        .timezones
+        .iter()
+        .filter_map(|(source, zone)| Some((source.as_str(), Zone::parse(zone).ok()?)))
+        .collect();
+    let tolerance = settings.future_tolerance_minutes.unwrap_or(DEFAULT_FUTURE_TOLERANCE_MINUTES);
+    let latest = now_ms + (tolerance.max(0.0) * 60_000.0) as i64;
+
+    for msg in messages {
+        if msg.naive_timestamp {
+            match zones.get(msg.source.as_str()).and_then(|zone| zone.to_utc(msg.timestamp)) {
+                Some(timestamp) => {
+                    msg.set_timestamp(timestamp);
+                    msg.naive_timestamp = false;
+                    diagnostics::record_timestamp_correction(msg.source.as_str(), TIMEZONE);
+                }
+                None => diagnostics::record_timestamp_correction(msg.source.as_str(), NO_OFFSET),
+            }
+        }
+
+        let scaled = msg.timestamp.saturating_mul(1000);
+        if msg.timestamp > 0 && msg.timestamp < SECONDS_BELOW && (EARLIEST_PLAUSIBLE..=latest).contains(&scaled) {
+            msg.set_timestamp(scaled);
+            msg.estimated_timestamp = true;
+            diagnostics::record_timestamp_correction(msg.source.as_str(), SECONDS);
+        }
+
+        if msg.timestamp > latest {
+            msg.set_timestamp(now_ms);
+            msg.estimated_timestamp = true;
+            diagnostics::record_timestamp_correction(msg.source.as_str(), FUTURE);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{message, TestMessage};
+
+    /// 2025-06-01T12:00:00Z
+    const NOW: i64 = 1_748_779_200_000;
+    const HOUR: i64 = 3_600_000;
+
+    fn count(corrections: &[diagnostics::TimestampCorrection], kind: &str) -> u64 {
+        corrections.iter().filter(|c| c.kind == kind).map(|c| c.count).sum()
+    }
+
+    #[test]
+    fn test_parse_timestamp() {
+        assert_eq!(parse_timestamp("2025-06-01T12:00:00Z"), Some((NOW, false)));
+        assert_eq!(parse_timestamp("2025-06-01T14:00:00.000+02:00"), Some((NOW, false)));
+        assert_eq!(parse_timestamp("2025-06-01T12:00:00"), Some((NOW, true)));
+        assert_eq!(parse_timestamp("2025-06-01 12:00:00.5"), Some((NOW + 500, true)));
+        assert_eq!(parse_timestamp("yesterday"), None);
+    }
+
+    #[test]
+    fn test_zones() {
+        assert_eq!(Zone::parse("+09:00").unwrap().to_utc(NOW), Some(NOW - 9 * HOUR));
+        assert_eq!(Zone::parse("-5").unwrap().to_utc(NOW), Some(NOW + 5 * HOUR));
+        assert_eq!(Zone::parse("UTC").unwrap().to_utc(NOW), Some(NOW));
+        assert_eq!(Zone::parse("local").unwrap(), Zone::Local);
+        assert!(Zone::parse("Europe/Berlin").is_err());
+        assert!(Zone::parse("+25:00").is_err());
+        assert!(Zone::parse("+02:75").is_err());
+    }
+
+    #[test]
+    fn test_sanitize() {
+        let settings = TimestampSettings {
+            timezones: BTreeMap::from([("test-clock-a".to_string(), "+02:00".to_string())]),
+            ..TimestampSettings::default()
+        };
+        let mut messages = vec![
+            message("s1", NOW, 0.0).with_source("test-clock-a"),
+            message("s1", NOW, 0.0).with_source("test-clock-b"),
+            message("s1", NOW / 1000, 0.0).with_source("test-clock-a"),
+            message("s1", NOW + 5 * 60_000, 0.0).with_source("test-clock-a"),
+            message("s1", NOW + 2 * HOUR, 0.0).with_source("test-clock-b"),
+        ];
+        messages[0].naive_timestamp = true;
+        messages[1].naive_timestamp = true;
+        sanitize_with(&mut messages, &settings, NOW);
+        let a = diagnostics::take_timestamp_corrections_for("test-clock-a");
+        let b = diagnostics::take_timestamp_corrections_for("test-clock-b");
+
+        // Shifted from +02:00, not estimated; a second pass leaves it alone
+        assert_eq!((messages[0].timestamp, messages[0].estimated_timestamp), (NOW - 2 * HOUR, false));
+        sanitize_with(&mut messages[..1], &settings, NOW);
+        assert_eq!(messages[0].timestamp, NOW - 2 * HOUR);
+        // No zone for this source: left as UTC, but counted
+        assert_eq!(messages[1].timestamp, NOW);
+        assert_eq!(count(&b, NO_OFFSET), 1);
+        // Seconds scaled
+        assert_eq!((messages[2].timestamp, messages[2].estimated_timestamp), (NOW, true));
+        assert_eq!(messages[2].date, "2025-06-01");
+        // Within the tolerance, then beyond it
+        assert_eq!((messages[3].timestamp, messages[3].estimated_timestamp), (NOW + 5 * 60_000, false));
+        assert_eq!((messages[4].timestamp, messages[4].estimated_timestamp), (NOW, true));
+
+        assert_eq!(count(&a, TIMEZONE), 1);
+        assert_eq!(count(&a, SECONDS), 1);
+        assert_eq!(count(&b, FUTURE), 1);
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut settings = TimestampSettings::default();
+        settings.timezones.insert("cursor".to_string(), "local".to_string());
+        assert!(settings.validate().is_ok());
+        settings.timezones.insert("gemini".to_string(), "JST".to_string());
+        assert!(settings.validate().unwrap_err().starts_with("timezones.gemini:"));
+        settings.timezones.clear();
+        settings.future_tolerance_minutes = Some(-1.0);
+        assert!(settings.validate().is_err());
+    }
+}
@@ -0,0 +1,259 @@
+//! Agent cost per commit
+//!
+//! Sessions don't say which commit their work ended up in, but the timing
+//! does: a message is charged to the first commit made in the same
+//! repository at or after it. Messages with no commit within the window
+//! after them are work that was never committed (or committed much later,
+//! which is indistinguishable from abandoned work).
+
+use crate::sessions::UnifiedMessage;
+use crate::workspace::{Project, WorkspaceResolver};
+use crate::{CommitCost, CommitCostReport};
+use std::collections::HashMap;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// Default time a message may precede the commit it's charged to
+pub const DEFAULT_WINDOW_HOURS: f64 = 24.0;
+
+/// Field separator in the `git log` format (ASCII unit separator)
+const SEP: char = '\u{1f}';
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Commit {
+    pub hash: String,
+    /// Commit time in Unix milliseconds
+    pub timestamp: i64,
+    pub author: String,
+    pub subject: String,
+}
+
+/// Non-merge commits on any ref of the repository at `root` made between
+/// `since` and `until` (Unix milliseconds), oldest first
+pub fn read_log(root: &Path, since: i64, until: i64, author: Option<&str>) -> Result<Vec<Commit>, String> {
+    let mut command = Command::new("git");
+    command
+        .arg("-C")
+        .arg(root)
+        .args(["log", "--all", "--no-merges"])
+        .arg(format!("--since=@{}", since / 1000))
+        .arg(format!("--until=@{}", until / 1000 + 1))
+        .arg(format!("--format=%H{SEP}%ct{SEP}%an{SEP}%s"));
+    if let Some(author) = author {
+        command.arg(format!("--author={}", author));
+    }
+    let output = command
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| format!("Failed to run git: {}", e))?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    let mut commits = parse_log(&String::from_utf8_lossy(&output.stdout));
+    commits.sort_by_key(|c| c.timestamp);
+    Ok(commits)
+}
+
+/// Lines of `hash SEP unix-seconds SEP author SEP subject`
+fn parse_log(text: &str) -> Vec<Commit> {
+    text.lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(4, SEP);
+            let hash = fields.next()?.to_string();
+            let seconds: i64 = fields.next()?.parse().ok()?;
+            Some(Commit {
+                hash,
+                timestamp: seconds * 1000,
+                author: fields.next()?.to_string(),
+                subject: fields.next().unwrap_or_default().to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Where a project's messages went
+#[derive(Debug, Default)]
+pub struct Attribution {
+    pub commits: Vec<CommitCost>,
+    /// Messages with no commit within the window after them
+    pub uncommitted_messages: i32,
+    pub uncommitted_cost: f64,
+}
+
+/// Charge each message to the first of `commits` (oldest first) at or after
+/// it, if that commit is at most `window_ms` later. Commits nothing was
+/// charged to are left out.
+pub fn attribute(project: &Project, messages: &[&UnifiedMessage], commits: &[Commit], window_ms: i64) -> Attribution {
+    let mut charged: HashMap<usize, (CommitCost, Vec<&str>)> = HashMap::new();
+    let mut attribution = Attribution::default();
+
+    for msg in messages {
+        let next = commits.partition_point(|c| c.timestamp < msg.timestamp);
+        let Some(commit) = commits.get(next).filter(|c| c.timestamp - msg.timestamp <= window_ms) else {
+            attribution.uncommitted_messages += 1;
+            attribution.uncommitted_cost += msg.cost;
+            continue;
+        };
+        let (entry, sessions) = charged.entry(next).or_insert_with(|| {
+            let entry = CommitCost {
+                project: project.root.clone(),
+                name: project.name.clone(),
+                hash: commit.hash.clone(),
+                author: commit.author.clone(),
+                subject: commit.subject.clone(),
+                timestamp: commit.timestamp,
+                message_count: 0,
+                session_count: 0,
+                cost: 0.0,
+            };
+            (entry, Vec::new())
+        });
+        if !sessions.contains(&msg.session_id.as_str()) {
+            sessions.push(&msg.session_id);
+        }
+        entry.message_count += 1;
+        entry.cost += msg.cost;
+    }
+
+    attribution.commits = charged
+        .into_values()
+        .map(|(mut entry, sessions)| {
+            entry.session_count = sessions.len() as i32;
+            entry
+        })
+        .collect();
+    attribution
+}
+
+/// Cost per commit across every repository the messages ran in, newest
+/// commit first. Projects that aren't git repositories count as
+/// unattributed, as do messages without a working directory.
+pub fn report(
+    messages: &[UnifiedMessage],
+    window_hours: f64,
+    author: Option<&str>,
+    processing_time_ms: u32,
+) -> CommitCostReport {
+    let window_ms = (window_hours * 3_600_000.0) as i64;
+    let mut resolver = WorkspaceResolver::new();
+    let mut projects: HashMap<Project, Vec<&UnifiedMessage>> = HashMap::new();
+    let (mut unattributed_messages, mut unattributed_cost) = (0, 0.0);
+
+    for msg in messages {
+        match &msg.cwd {
+            Some(cwd) => projects.entry(resolver.resolve(cwd)).or_default().push(msg),
+            None => {
+                unattributed_messages += 1;
+                unattributed_cost += msg.cost;
+            }
+        }
+    }
+
+    let mut commits = Vec::new();
+    let (mut uncommitted_messages, mut uncommitted_cost) = (0, 0.0);
+    let mut repositories = 0;
+    for (project, messages) in &projects {
+        let since = messages.iter().map(|m| m.timestamp).min().unwrap_or_default();
+        let until = messages.iter().map(|m| m.timestamp).max().unwrap_or_default() + window_ms;
+        let Ok(log) = read_log(Path::new(&project.root), since, until, author) else {
+            unattributed_messages += messages.len() as i32;
+            unattributed_cost += messages.iter().map(|m| m.cost).sum::<f64>();
+            continue;
+        };
+        repositories += 1;
+        let attribution = attribute(project, messages, &log, window_ms);
+        commits.extend(attribution.commits);
+        uncommitted_messages += attribution.uncommitted_messages;
+        uncommitted_cost += attribution.uncommitted_cost;
+    }
+
+    commits.sort_by(|a, b| b.timestamp.cmp(&a.timestamp).then_with(|| a.hash.cmp(&b.hash)));
+    let committed_cost: f64 = commits.iter().map(|c| c.cost).sum();
+    let mut costs: Vec<f64> = commits.iter().map(|c| c.cost).collect();
+    costs.sort_by(f64::total_cmp);
+    CommitCostReport {
+        repositories,
+        mean_cost_per_commit: if costs.is_empty() { 0.0 } else { committed_cost / costs.len() as f64 },
+        median_cost_per_commit: median(&costs),
+        commits,
+        committed_cost,
+        uncommitted_messages,
+        uncommitted_cost,
+        unattributed_messages,
+        unattributed_cost,
+        processing_time_ms,
+    }
+}
+
+fn median(sorted: &[f64]) -> f64 {
+    match sorted.len() {
+        0 => 0.0,
+        n if n % 2 == 1 => sorted[n / 2],
+        n => (sorted[n / 2 - 1] + sorted[n / 2]) / 2.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::message;
+
+    const HOUR: i64 = 3_600_000;
+    const T0: i64 = 1_740_823_200_000;
+
+    fn commit(hash: &str, timestamp: i64) -> Commit {
+        Commit { hash: hash.into(), timestamp, author: "dev".into(), subject: format!("commit {}", hash) }
+    }
+
+    #[test]
+    fn test_parse_log() {
+        let text = "abc123\u{1f}1740823200\u{1f}Ada Lovelace\u{1f}Fix: split \u{1f} in subjects\nnot a commit line\n";
+        let commits = parse_log(text);
+        assert_eq!(commits.len(), 1);
+        assert_eq!(commits[0].timestamp, T0);
+        assert_eq!(commits[0].author, "Ada Lovelace");
+        assert_eq!(commits[0].subject, "Fix: split \u{1f} in subjects");
+    }
+
+    #[test]
+    fn test_messages_charged_to_the_next_commit() {
+        let project = Project { root: "/src/app".into(), name: "app".into() };
+        let commits = vec![commit("a", T0 + HOUR), commit("b", T0 + 3 * HOUR)];
+        let messages = [
+            message("s1", T0, 1.0),
+            message("s1", T0 + HOUR, 0.5),
+            message("s2", T0 + 2 * HOUR, 2.0),
+            // After the last commit
+            message("s2", T0 + 4 * HOUR, 4.0),
+        ];
+        let refs: Vec<&UnifiedMessage> = messages.iter().collect();
+
+        let mut attribution = attribute(&project, &refs, &commits, 24 * HOUR);
+        attribution.commits.sort_by(|a, b| a.hash.cmp(&b.hash));
+        assert_eq!(attribution.commits.len(), 2);
+        assert_eq!((attribution.commits[0].message_count, attribution.commits[0].session_count), (2, 1));
+        assert!((attribution.commits[0].cost - 1.5).abs() < 1e-9);
+        assert!((attribution.commits[1].cost - 2.0).abs() < 1e-9);
+        assert_eq!(attribution.uncommitted_messages, 1);
+        assert!((attribution.uncommitted_cost - 4.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_commits_outside_the_window_are_not_charged() {
+        let project = Project { root: "/src/app".into(), name: "app".into() };
+        let commits = vec![commit("a", T0 + 30 * HOUR)];
+        let messages = [message("s1", T0, 1.0)];
+        let refs: Vec<&UnifiedMessage> = messages.iter().collect();
+
+        let attribution = attribute(&project, &refs, &commits, 24 * HOUR);
+        assert!(attribution.commits.is_empty());
+        assert_eq!(attribution.uncommitted_messages, 1);
+    }
+
+    #[test]
+    fn test_median() {
+        assert_eq!(median(&[]), 0.0);
+        assert_eq!(median(&[1.0, 2.0, 10.0]), 2.0);
+        assert_eq!(median(&[1.0, 2.0, 4.0, 10.0]), 3.0);
+    }
+}
@@ -0,0 +1,428 @@
+//! User settings
+//!
+//! Reads `~/.config/tokscale/settings.json`, the same file the TUI writes. Only
+//! the keys the native core cares about are modelled here; anything else is
+//! ignored, and a missing or malformed file falls back to defaults. Unknown
+//! keys and mistyped values are reported on stderr with their line and column
+//! (see [`crate::config_schema`]) rather than dropped silently.
+//!
+//! Long-running hosts (the TUI's auto refresh) can pick up edits without a
+//! restart: [`reload`] re-reads the file when it changed, and [`watch`] does so
+//! on a background thread. A reloaded file that fails to parse or validate is
+//! rejected and the last good settings stay in effect. Cost display and
+//! provider inference follow reloads; pricing rules (`normalizeRules`,
+//! `providerPrefixes`, `pricingMerge`) are read once, when prices are first
+//! loaded.
+
+use crate::budget::Budget;
+use crate::clock::TimestampSettings;
+use crate::config_schema::{self, Severity};
+use crate::cost_display::CostDisplay;
+use crate::free_tier::FreeTier;
+use crate::pricing::merge::MergePolicy;
+use crate::pricing::normalize::{NormalizeRuleSet, RULES_VERSION};
+use crate::secrets::SecretRef;
+use crate::session_tags::TagThresholds;
+#[cfg(feature = "server")]
+use crate::serve::sinks::SinkConfig;
+#[cfg(feature = "server")]
+use crate::serve::webhooks::Webhook;
+use crate::sessions::provider::{ProviderRule, ProviderSettings};
+use crate::streaming::MemorySettings;
+use crate::workspace::PathMapping;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Once, RwLock};
+use std::time::{Duration, SystemTime};
+
+const SETTINGS_FILE: &str = "settings.json";
+
+/// Most decimal places `costDisplay.precision` accepts
+const MAX_COST_PRECISION: u32 = 10;
+
+static STORE: Lazy<SettingsStore> = Lazy::new(|| SettingsStore::open(settings_path()));
+static WATCHER: Once = Once::new();
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Settings {
+    /// Extra model normalization rules, tried before the bundled ones
+    pub normalize_rules: Option<NormalizeRuleSet>,
+    /// Provider prefixes tried for bare model names, highest priority first
+    pub provider_prefixes: Option<Vec<String>>,
+    /// Model-pattern → provider rules and per-source defaults
+    pub provider_inference: ProviderSettings,
+    /// Cost rounding, precision and formatting in reports
+    pub cost_display: CostDisplay,
+    /// Where each remote importer's API key is stored ("keychain:<name>" or
+    /// "env:<VAR>"); never the key itself
+    pub api_keys: HashMap<String, String>,
+    /// Which price wins when LiteLLM and OpenRouter disagree on a model
+    pub pricing_merge: MergePolicy,
+    /// Gaps between messages longer than this don't count toward a session's
+    /// active time
+    pub idle_threshold_minutes: Option<f64>,
+    /// Days of usage kept in hourly and 15-minute buckets for the TUI and
+    /// burn rate
+    pub recent_bucket_days: Option<u32>,
+    /// Endpoints `tokscale serve` notifies about session activity. Builds
+    /// without the server ignore them.
+    #[cfg(feature = "server")]
+    pub webhooks: Vec<Webhook>,
+    /// Other places `tokscale serve` sends the same events: stdout, a file or
+    /// an OpenTelemetry collector
+    #[cfg(feature = "server")]
+    pub event_sinks: Vec<SinkConfig>,
+    /// Spending limits per day, week or month
+    pub budgets: Vec<Budget>,
+    /// Free-tier allowances and promotional credits per provider
+    pub free_tiers: Vec<FreeTier>,
+    /// Thresholds over which sessions are tagged as outliers
+    pub session_tags: TagThresholds,
+    /// Container path prefixes and the host paths they are mounted from,
+    /// applied when attributing sessions to projects
+    pub path_mappings: Vec<PathMapping>,
+    /// Timezones of offset-less timestamps per source and how far in the
+    /// future a timestamp may be before it is corrected
+    pub timestamps: TimestampSettings,
+    /// Streamed aggregation for hosts short on memory
+    pub memory: MemorySettings,
+    /// Session files parsed at once (default: one per CPU)
+    pub parse_threads: Option<usize>,
+}
+
+impl Settings {
+    pub fn load_from(path: &Path) -> Self {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    /// Strict load used on reload: malformed JSON and invalid values are errors
+    pub fn parse(content: &str) -> Result<Self, String> {
+        if let Some(issue) = config_schema::check(content).into_iter().find(|i| i.severity == Severity::Error) {
+            return Err(format!("{}:{}", SETTINGS_FILE, issue));
+        }
+        let settings: Settings = serde_json::from_str(content).map_err(|e| e.to_string())?;
+        settings.validate()?;
+        Ok(settings)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if let Some(precision) = self.cost_display.precision {
+            if precision > MAX_COST_PRECISION {
+                return Err(format!(
+                    "costDisplay.precision must be at most {}, got {}",
+                    MAX_COST_PRECISION, precision
+                ));
+            }
+        }
+        if let Some(rules) = &self.normalize_rules {
+            if rules.version > RULES_VERSION {
+                return Err(format!(
+                    "normalizeRules.version {} is newer than this build supports ({})",
+                    rules.version, RULES_VERSION
+                ));
+            }
+        }
+
+        let inference = &self.provider_inference;
+        let source_rules = inference.sources.values().flat_map(|s| &s.rules);
+        if inference.rules.iter().chain(source_rules).any(|r: &ProviderRule| r.provider.is_empty()) {
+            return Err("providerInference rules need a non-empty provider".to_string());
+        }
+
+        if let Some(minutes) = self.idle_threshold_minutes {
+            if minutes.is_nan() || minutes <= 0.0 {
+                return Err(format!("idleThresholdMinutes must be positive, got {}", minutes));
+            }
+        }
+
+        if let Some(days) = self.recent_bucket_days {
+            if days == 0 || days > crate::recent::MAX_RECENT_DAYS {
+                return Err(format!(
+                    "recentBucketDays must be between 1 and {}, got {}",
+                    crate::recent::MAX_RECENT_DAYS,
+                    days
+                ));
+            }
+        }
+
+        for (name, reference) in &self.api_keys {
+            SecretRef::parse(reference).map_err(|reason| format!("apiKeys.{}: {}", name, reason))?;
+        }
+        #[cfg(feature = "server")]
+        for (index, webhook) in self.webhooks.iter().enumerate() {
+            webhook.validate().map_err(|reason| format!("webhooks[{}]: {}", index, reason))?;
+        }
+        #[cfg(feature = "server")]
+        for (index, sink) in self.event_sinks.iter().enumerate() {
+            sink.validate().map_err(|reason| format!("eventSinks[{}]: {}", index, reason))?;
+        }
+        for (index, budget) in self.budgets.iter().enumerate() {
+            budget.validate().map_err(|reason| format!("budgets[{}]: {}", index, reason))?;
+        }
+        for (index, tier) in self.free_tiers.iter().enumerate() {
+            tier.validate().map_err(|reason| format!("freeTiers[{}]: {}", index, reason))?;
+        }
+        self.session_tags.validate().map_err(|reason| format!("sessionTags.{}", reason))?;
+        for (index, mapping) in self.path_mappings.iter().enumerate() {
+            mapping.validate().map_err(|reason| format!("pathMappings[{}]: {}", index, reason))?;
+        }
+        self.timestamps.validate().map_err(|reason| format!("timestamps.{}", reason))?;
+        self.memory.validate().map_err(|reason| format!("memory.{}", reason))?;
+        if self.parse_threads == Some(0) {
+            return Err("parseThreads must be at least 1".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Outcome of re-reading the settings file
+#[derive(Debug, Clone, PartialEq)]
+pub enum Reload {
+    /// File not modified since the last load
+    Unchanged,
+    Applied,
+    /// File changed but is invalid; the previous settings are kept
+    Rejected(String),
+}
+
+/// Settings file plus the last good settings read from it
+pub struct SettingsStore {
+    path: PathBuf,
+    state: RwLock<StoreState>,
+}
+
+struct StoreState {
+    settings: Arc<Settings>,
+    modified: Option<SystemTime>,
+}
+
+impl SettingsStore {
+    pub fn open(path: PathBuf) -> Self {
+        if let Ok(content) = fs::read_to_string(&path) {
+            report_issues(&path, &content);
+        }
+        let state = StoreState {
+            settings: Arc::new(Settings::load_from(&path)),
+            modified: modified_time(&path),
+        };
+        Self { path, state: RwLock::new(state) }
+    }
+
+    pub fn current(&self) -> Arc<Settings> {
+        Arc::clone(&self.state.read().unwrap_or_else(|e| e.into_inner()).settings)
+    }
+
+    pub fn reload(&self) -> Reload {
+        let modified = modified_time(&self.path);
+        let mut state = self.state.write().unwrap_or_else(|e| e.into_inner());
+        if modified == state.modified {
+            return Reload::Unchanged;
+        }
+        // Remember the version even when rejected so a bad file is reported once
+        state.modified = modified;
+
+        let loaded = match fs::read_to_string(&self.path) {
+            Ok(content) => {
+                report_warnings(&self.path, &content);
+                Settings::parse(&content)
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Settings::default()),
+            Err(e) => Err(e.to_string()),
+        };
+        match loaded {
+            Ok(settings) => {
+                state.settings = Arc::new(settings);
+                Reload::Applied
+            }
+            Err(reason) => Reload::Rejected(reason),
+        }
+    }
+}
+
+/// Print every schema issue in `content`; used when the file is first read,
+/// where errors fall back to defaults instead of being rejected
+fn report_issues(path: &Path, content: &str) {
+    for issue in config_schema::check(content) {
+        eprintln!("[tokscale] {} {}:{}", issue.severity.as_str(), path.display(), issue);
+    }
+}
+
+/// Print schema warnings only; errors come back from [`Settings::parse`]
+fn report_warnings(path: &Path, content: &str) {
+    for issue in config_schema::check(content) {
+        if issue.severity == Severity::Warning {
+            eprintln!("[tokscale] warning {}:{}", path.display(), issue);
+        }
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+pub fn config_dir() -> PathBuf {
+    dirs::home_dir()
+        .unwrap_or_else(|| PathBuf::from("/tmp"))
+        .join(".config")
+        .join("tokscale")
+}
+
+pub fn settings_path() -> PathBuf {
+    config_dir().join(SETTINGS_FILE)
+}
+
+/// Current settings; the last good ones if a reload was rejected
+pub fn settings() -> Arc<Settings> {
+    STORE.current()
+}
+
+/// Re-read the settings file if it changed since it was last loaded
+pub fn reload() -> Reload {
+    let outcome = STORE.reload();
+    if let Reload::Rejected(reason) = &outcome {
+        eprintln!(
+            "[tokscale] Ignoring invalid {} (keeping previous settings): {}",
+            STORE.path.display(),
+            reason
+        );
+    }
+    outcome
+}
+
+/// Poll the settings file every `interval` on a background thread. Only the
+/// first call starts a watcher.
+pub fn watch(interval: Duration) {
+    WATCHER.call_once(|| {
+        std::thread::spawn(move || loop {
+            std::thread::sleep(interval);
+            reload();
+        });
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_load_missing_file() {
+        let settings = Settings::load_from(Path::new("/nonexistent/settings.json"));
+        assert!(settings.normalize_rules.is_none());
+    }
+
+    #[test]
+    fn test_load_ignores_tui_keys() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join(SETTINGS_FILE);
+        fs::write(
+            &path,
+            r#"{
+                "colorPalette": "green",
+                "autoRefreshEnabled": true,
+                "normalizeRules": {
+                    "version": 1,
+                    "rules": [{"all": ["opus"], "any": ["5"], "canonical": "claude-opus-5"}]
+                }
+            }"#,
+        )
+        .unwrap();
+
+        let settings = Settings::load_from(&path);
+        let rules = settings.normalize_rules.unwrap();
+        assert_eq!(rules.version, 1);
+        assert_eq!(rules.rules[0].canonical, "claude-opus-5");
+    }
+
+    #[test]
+    fn test_load_malformed_file() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join(SETTINGS_FILE);
+        fs::write(&path, "{ not json").unwrap();
+        assert!(Settings::load_from(&path).normalize_rules.is_none());
+    }
+
+    #[test]
+    fn test_load_cost_display() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join(SETTINGS_FILE);
+        fs::write(
+            &path,
+            r#"{"costDisplay": {"rounding": "halfEven", "precision": 4, "centsBelowDollar": true}}"#,
+        )
+        .unwrap();
+
+        let settings = Settings::load_from(&path);
+        assert_eq!(settings.cost_display.rounding, crate::cost_display::RoundingMode::HalfEven);
+        assert_eq!(settings.cost_display.precision, Some(4));
+        assert!(settings.cost_display.cents_below_dollar);
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid_values() {
+        assert!(Settings::parse(r#"{"costDisplay": {"precision": 2}}"#).is_ok());
+        assert!(Settings::parse("{ not json").is_err());
+        assert!(Settings::parse(r#"{"costDisplay": {"rounding": "sideways"}}"#).is_err());
+
+        let err = Settings::parse(r#"{"costDisplay": {"precision": 12}}"#).unwrap_err();
+        assert!(err.contains("precision"), "{}", err);
+        let err = Settings::parse(r#"{"normalizeRules": {"version": 99, "rules": []}}"#).unwrap_err();
+        assert!(err.contains("normalizeRules"), "{}", err);
+        let err = Settings::parse(r#"{"providerInference": {"rules": [{"patterns": ["x"], "provider": ""}]}}"#)
+            .unwrap_err();
+        assert!(err.contains("providerInference"), "{}", err);
+        let err = Settings::parse(r#"{"apiKeys": {"openai": "sk-live-1234"}}"#).unwrap_err();
+        assert!(err.contains("apiKeys.openai"), "{}", err);
+        assert!(Settings::parse(r#"{"apiKeys": {"openai": "keychain:openai"}}"#).is_ok());
+        let err = Settings::parse(r#"{"idleThresholdMinutes": 0}"#).unwrap_err();
+        assert!(err.contains("idleThresholdMinutes"), "{}", err);
+        let err = Settings::parse(r#"{"recentBucketDays": 30}"#).unwrap_err();
+        assert!(err.contains("recentBucketDays"), "{}", err);
+    }
+
+    #[test]
+    fn test_parse_reports_schema_errors_with_position() {
+        let err = Settings::parse("{\n  \"costDisplay\": {\"precision\": \"2\"}\n}").unwrap_err();
+        assert_eq!(err, "settings.json:2:19: costDisplay.precision: expected an integer, found string \"2\"");
+        // Unknown keys only warn
+        assert!(Settings::parse(r#"{"budjet": 200, "colorPalette": "green"}"#).is_ok());
+    }
+
+    #[test]
+    fn test_reload_applies_and_keeps_last_good() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join(SETTINGS_FILE);
+        // Distinct mtimes without sleeping between writes
+        let write = |content: &str, secs: u64| {
+            fs::write(&path, content).unwrap();
+            let file = fs::File::options().write(true).open(&path).unwrap();
+            file.set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(secs)).unwrap();
+        };
+
+        write(r#"{"costDisplay": {"precision": 2}}"#, 1_000);
+        let store = SettingsStore::open(path.clone());
+        assert_eq!(store.current().cost_display.precision, Some(2));
+        assert_eq!(store.reload(), Reload::Unchanged);
+
+        write(r#"{"costDisplay": {"precision": 4}}"#, 2_000);
+        assert_eq!(store.reload(), Reload::Applied);
+        assert_eq!(store.current().cost_display.precision, Some(4));
+
+        write(r#"{"costDisplay": {"precision": "four"}}"#, 3_000);
+        assert!(matches!(store.reload(), Reload::Rejected(_)));
+        assert_eq!(store.current().cost_display.precision, Some(4));
+        assert_eq!(store.reload(), Reload::Unchanged);
+
+        fs::remove_file(&path).unwrap();
+        assert_eq!(store.reload(), Reload::Applied);
+        assert_eq!(store.current().cost_display.precision, None);
+    }
+}
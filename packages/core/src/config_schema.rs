@@ -0,0 +1,550 @@
+//! Settings file check
+//!
+//! serde skips keys it doesn't know, so a misspelled option such as
+//! `idleTresholdMinutes` silently does nothing. [`check`] walks settings.json
+//! against every key the native core and the TUI read and reports, with the
+//! line and column each starts at:
+//!
+//! - unknown keys, with the closest known key when one is a likely typo
+//! - values of the wrong type
+//! - `*` or `?` in match patterns, which are plain substrings, not globs
+//!
+//! Type mismatches are errors (serde would reject the file); the rest are
+//! warnings and the file still loads.
+
+use serde_json::Value;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Str,
+    Int,
+    Num,
+    Bool,
+    Obj,
+    Arr,
+}
+
+impl Kind {
+    fn describe(self) -> &'static str {
+        match self {
+            Kind::Str => "a string",
+            Kind::Int => "an integer",
+            Kind::Num => "a number",
+            Kind::Bool => "true or false",
+            Kind::Obj => "an object",
+            Kind::Arr => "an array",
+        }
+    }
+
+    fn accepts(self, value: &Value) -> bool {
+        match (self, value) {
+            // Every setting is optional; serde reports the few that aren't
+            (_, Value::Null) => true,
+            (Kind::Str, Value::String(_)) | (Kind::Bool, Value::Bool(_)) => true,
+            (Kind::Obj, Value::Object(_)) | (Kind::Arr, Value::Array(_)) => true,
+            (Kind::Num, Value::Number(_)) => true,
+            (Kind::Int, Value::Number(n)) => n.is_u64() || n.is_i64(),
+            _ => false,
+        }
+    }
+}
+
+/// Key paths, `/`-separated; `*` matches any object key or array index
+const SCHEMA: &[(&str, Kind)] = &[
+    // TUI
+    ("colorPalette", Kind::Str),
+    ("autoRefreshEnabled", Kind::Bool),
+    ("autoRefreshMs", Kind::Num),
+    ("includeUnusedModels", Kind::Bool),
+    ("locale", Kind::Str),
+    ("tuiFilters", Kind::Obj),
+    ("tuiFilters/*", Kind::Obj),
+    ("tuiFilters/*/since", Kind::Str),
+    ("tuiFilters/*/until", Kind::Str),
+    ("tuiFilters/*/model", Kind::Str),
+    ("tuiFilters/*/sources", Kind::Arr),
+    ("tuiFilters/*/sources/*", Kind::Str),
+    ("theme", Kind::Str),
+    ("themes", Kind::Obj),
+    ("themes/*", Kind::Obj),
+    ("themes/*/*", Kind::Str),
+    ("costThresholds", Kind::Obj),
+    ("costThresholds/warn", Kind::Num),
+    ("costThresholds/high", Kind::Num),
+    // Native core
+    ("normalizeRules", Kind::Obj),
+    ("normalizeRules/version", Kind::Int),
+    ("normalizeRules/rules", Kind::Arr),
+    ("normalizeRules/rules/*", Kind::Obj),
+    ("normalizeRules/rules/*/all", Kind::Arr),
+    ("normalizeRules/rules/*/all/*", Kind::Str),
+    ("normalizeRules/rules/*/any", Kind::Arr),
+    ("normalizeRules/rules/*/any/*", Kind::Str),
+    ("normalizeRules/rules/*/none", Kind::Arr),
+    ("normalizeRules/rules/*/none/*", Kind::Str),
+    ("normalizeRules/rules/*/canonical", Kind::Str),
+    ("normalizeRules/rules/*/provider", Kind::Str),
+    ("normalizeRules/rules/*/family", Kind::Str),
+    ("providerPrefixes", Kind::Arr),
+    ("providerPrefixes/*", Kind::Str),
+    ("providerInference", Kind::Obj),
+    ("providerInference/rules", Kind::Arr),
+    ("providerInference/rules/*", Kind::Obj),
+    ("providerInference/rules/*/patterns", Kind::Arr),
+    ("providerInference/rules/*/patterns/*", Kind::Str),
+    ("providerInference/rules/*/provider", Kind::Str),
+    ("providerInference/sources", Kind::Obj),
+    ("providerInference/sources/*", Kind::Obj),
+    ("providerInference/sources/*/rules", Kind::Arr),
+    ("providerInference/sources/*/rules/*", Kind::Obj),
+    ("providerInference/sources/*/rules/*/patterns", Kind::Arr),
+    ("providerInference/sources/*/rules/*/patterns/*", Kind::Str),
+    ("providerInference/sources/*/rules/*/provider", Kind::Str),
+    ("providerInference/sources/*/default", Kind::Str),
+    ("costDisplay", Kind::Obj),
+    ("costDisplay/rounding", Kind::Str),
+    ("costDisplay/precision", Kind::Int),
+    ("costDisplay/centsBelowDollar", Kind::Bool),
+    ("apiKeys", Kind::Obj),
+    ("apiKeys/*", Kind::Str),
+    ("pricingMerge", Kind::Obj),
+    ("pricingMerge/prefer", Kind::Str),
+    ("pricingMerge/flagConflicts", Kind::Bool),
+    ("idleThresholdMinutes", Kind::Num),
+    ("recentBucketDays", Kind::Int),
+    ("webhooks", Kind::Arr),
+    ("webhooks/*", Kind::Obj),
+    ("webhooks/*/url", Kind::Str),
+    ("webhooks/*/events", Kind::Arr),
+    ("webhooks/*/events/*", Kind::Str),
+    ("webhooks/*/costThreshold", Kind::Num),
+    ("webhooks/*/secret", Kind::Str),
+    ("eventSinks", Kind::Arr),
+    ("eventSinks/*", Kind::Obj),
+    ("eventSinks/*/type", Kind::Str),
+    ("eventSinks/*/path", Kind::Str),
+    ("eventSinks/*/url", Kind::Str),
+    ("eventSinks/*/events", Kind::Arr),
+    ("eventSinks/*/events/*", Kind::Str),
+    ("eventSinks/*/costThreshold", Kind::Num),
+    ("budgets", Kind::Arr),
+    ("budgets/*", Kind::Obj),
+    ("budgets/*/name", Kind::Str),
+    ("budgets/*/period", Kind::Str),
+    ("budgets/*/amount", Kind::Num),
+    ("budgets/*/sources", Kind::Arr),
+    ("budgets/*/sources/*", Kind::Str),
+    ("budgets/*/enforce", Kind::Str),
+    ("freeTiers", Kind::Arr),
+    ("freeTiers/*", Kind::Obj),
+    ("freeTiers/*/name", Kind::Str),
+    ("freeTiers/*/provider", Kind::Str),
+    ("freeTiers/*/models", Kind::Str),
+    ("freeTiers/*/period", Kind::Str),
+    ("freeTiers/*/tokens", Kind::Int),
+    ("freeTiers/*/credits", Kind::Num),
+    ("freeTiers/*/since", Kind::Str),
+    ("freeTiers/*/until", Kind::Str),
+    ("sessionTags", Kind::Obj),
+    ("sessionTags/cost", Kind::Num),
+    ("sessionTags/tokens", Kind::Int),
+    ("sessionTags/activeMinutes", Kind::Num),
+    ("sessionTags/subAgentMessages", Kind::Int),
+    ("pathMappings", Kind::Arr),
+    ("pathMappings/*", Kind::Obj),
+    ("pathMappings/*/from", Kind::Str),
+    ("pathMappings/*/to", Kind::Str),
+    ("timestamps", Kind::Obj),
+    ("timestamps/sanitize", Kind::Bool),
+    ("timestamps/timezones", Kind::Obj),
+    ("timestamps/timezones/*", Kind::Str),
+    ("timestamps/futureToleranceMinutes", Kind::Num),
+    ("memory", Kind::Obj),
+    ("memory/streaming", Kind::Bool),
+    ("memory/filesInFlight", Kind::Int),
+    ("memory/sortChunk", Kind::Int),
+    ("parseThreads", Kind::Int),
+];
+
+/// Substring patterns where `*` and `?` have no special meaning
+const PATTERN_PATHS: &[&str] = &[
+    "normalizeRules/rules/*/all/*",
+    "normalizeRules/rules/*/any/*",
+    "normalizeRules/rules/*/none/*",
+    "providerInference/rules/*/patterns/*",
+    "providerInference/sources/*/rules/*/patterns/*",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Severity {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Issue {
+    pub severity: Severity,
+    /// Dotted key path, e.g. "costDisplay.precision" or "providerPrefixes[2]"
+    pub path: String,
+    /// 1-based; 0 when the position couldn't be found
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl std::fmt::Display for Issue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}: ", self.line, self.column)?;
+        if !self.path.is_empty() {
+            write!(f, "{}: ", self.path)?;
+        }
+        f.write_str(&self.message)
+    }
+}
+
+/// Problems in a settings file, in file order. Malformed JSON is one error
+/// at the position serde stopped.
+pub fn check(content: &str) -> Vec<Issue> {
+    let root: Value = match serde_json::from_str(content) {
+        Ok(value) => value,
+        Err(e) => {
+            return vec![Issue {
+                severity: Severity::Error,
+                path: String::new(),
+                line: e.line(),
+                column: e.column(),
+                message: format!("invalid JSON: {}", e),
+            }]
+        }
+    };
+    let Value::Object(_) = &root else {
+        return vec![Issue {
+            severity: Severity::Error,
+            path: String::new(),
+            line: 1,
+            column: 1,
+            message: "settings must be a JSON object".to_string(),
+        }];
+    };
+
+    let mut locator = Locator::new(content);
+    locator.value(&mut Vec::new());
+
+    let mut walk = Walk { positions: &locator.positions, issues: Vec::new() };
+    walk.children(&root, &mut Vec::new(), &mut Vec::new(), "");
+    walk.issues.sort_by_key(|issue| (issue.line, issue.column));
+    walk.issues
+}
+
+struct Walk<'a> {
+    positions: &'a HashMap<Vec<String>, (usize, usize)>,
+    issues: Vec<Issue>,
+}
+
+impl Walk<'_> {
+    /// Check the members of an object or array at `concrete` (actual keys
+    /// and indexes) whose schema path is `pattern`
+    fn children(&mut self, value: &Value, concrete: &mut Vec<String>, pattern: &mut Vec<String>, display: &str) {
+        let members: Vec<(String, String, &Value)> = match value {
+            Value::Object(map) => map
+                .iter()
+                .map(|(key, child)| {
+                    let display = if display.is_empty() { key.clone() } else { format!("{}.{}", display, key) };
+                    (key.clone(), display, child)
+                })
+                .collect(),
+            Value::Array(items) => items
+                .iter()
+                .enumerate()
+                .map(|(index, child)| (index.to_string(), format!("{}[{}]", display, index), child))
+                .collect(),
+            _ => return,
+        };
+
+        for (key, child_display, child) in members {
+            concrete.push(key.clone());
+            let (line, column) = self.positions.get(concrete).copied().unwrap_or((0, 0));
+            let issue = |severity, message| Issue { severity, path: child_display.clone(), line, column, message };
+
+            match lookup(pattern, &key) {
+                None => {
+                    let mut message = "unknown key".to_string();
+                    if let Some(suggestion) = suggest(pattern, &key) {
+                        message.push_str(&format!(" (did you mean \"{}\"?)", suggestion));
+                    }
+                    self.issues.push(issue(Severity::Warning, message));
+                }
+                Some((_, kind)) if !kind.accepts(child) => {
+                    let message = format!("expected {}, found {}", kind.describe(), describe_value(child));
+                    self.issues.push(issue(Severity::Error, message));
+                }
+                Some((segment, _)) => {
+                    pattern.push(segment);
+                    if let Value::String(s) = child {
+                        if s.contains(['*', '?']) && PATTERN_PATHS.contains(&pattern.join("/").as_str()) {
+                            let message = format!(
+                                "\"{}\" is matched as a plain substring; wildcards aren't supported, so '*' and '?' only match themselves",
+                                s
+                            );
+                            self.issues.push(issue(Severity::Warning, message));
+                        }
+                    }
+                    self.children(child, concrete, pattern, &child_display);
+                    pattern.pop();
+                }
+            }
+            concrete.pop();
+        }
+    }
+}
+
+/// Schema segment (`key` itself or `*`) and kind for `key` under `parent`
+fn lookup(parent: &[String], key: &str) -> Option<(String, Kind)> {
+    let matches = |segment: &str| {
+        SCHEMA.iter().find(|&&(path, _)| {
+            let segments: Vec<&str> = path.split('/').collect();
+            segments.len() == parent.len() + 1
+                && segments[..parent.len()].iter().zip(parent).all(|(a, b)| a == b)
+                && segments[parent.len()] == segment
+        })
+    };
+    matches(key)
+        .map(|(_, kind)| (key.to_string(), *kind))
+        .or_else(|| matches("*").map(|(_, kind)| ("*".to_string(), *kind)))
+}
+
+/// Known sibling key within two edits of `key`
+fn suggest(parent: &[String], key: &str) -> Option<&'static str> {
+    SCHEMA
+        .iter()
+        .filter_map(|&(path, _)| {
+            let segments: Vec<&'static str> = path.split('/').collect();
+            let is_sibling = segments.len() == parent.len() + 1
+                && segments[..parent.len()].iter().zip(parent).all(|(a, b)| a == b);
+            let name = segments[parent.len().min(segments.len() - 1)];
+            (is_sibling && name != "*").then_some(name)
+        })
+        .map(|name| (edit_distance(&key.to_lowercase(), &name.to_lowercase()), name))
+        .filter(|(distance, _)| *distance <= 2)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, name)| name)
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitute = previous[j] + usize::from(ca != *cb);
+            current.push(substitute.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+fn describe_value(value: &Value) -> String {
+    match value {
+        Value::Null => "null".to_string(),
+        Value::Bool(b) => b.to_string(),
+        Value::Number(n) => format!("number {}", n),
+        Value::String(s) => format!("string \"{}\"", s),
+        Value::Array(_) => "an array".to_string(),
+        Value::Object(_) => "an object".to_string(),
+    }
+}
+
+/// Line and column of every key (and array element) in a JSON document,
+/// keyed by path. Only run on text serde has already accepted.
+struct Locator<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    line: usize,
+    column: usize,
+    positions: HashMap<Vec<String>, (usize, usize)>,
+}
+
+impl<'a> Locator<'a> {
+    fn new(content: &'a str) -> Self {
+        Self { bytes: content.as_bytes(), pos: 0, line: 1, column: 1, positions: HashMap::new() }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.pos).copied()
+    }
+
+    fn bump(&mut self) -> Option<u8> {
+        let b = self.peek()?;
+        self.pos += 1;
+        if b == b'\n' {
+            self.line += 1;
+            self.column = 1;
+        } else if b & 0xC0 != 0x80 {
+            // Count characters, not UTF-8 continuation bytes
+            self.column += 1;
+        }
+        Some(b)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(|b| b.is_ascii_whitespace()) {
+            self.bump();
+        }
+    }
+
+    fn string(&mut self) -> Option<String> {
+        let start = self.pos;
+        self.bump()?;
+        loop {
+            match self.bump()? {
+                b'\\' => {
+                    self.bump()?;
+                }
+                b'"' => break,
+                _ => {}
+            }
+        }
+        serde_json::from_slice(&self.bytes[start..self.pos]).ok()
+    }
+
+    fn value(&mut self, path: &mut Vec<String>) -> Option<()> {
+        self.skip_whitespace();
+        match self.peek()? {
+            b'{' => {
+                self.bump();
+                loop {
+                    self.skip_whitespace();
+                    match self.peek()? {
+                        b'}' => {
+                            self.bump();
+                            return Some(());
+                        }
+                        b',' => {
+                            self.bump();
+                        }
+                        _ => {
+                            let at = (self.line, self.column);
+                            let key = self.string()?;
+                            path.push(key);
+                            self.positions.insert(path.clone(), at);
+                            self.skip_whitespace();
+                            self.bump()?; // ':'
+                            self.value(path)?;
+                            path.pop();
+                        }
+                    }
+                }
+            }
+            b'[' => {
+                self.bump();
+                let mut index = 0;
+                loop {
+                    self.skip_whitespace();
+                    match self.peek()? {
+                        b']' => {
+                            self.bump();
+                            return Some(());
+                        }
+                        b',' => {
+                            self.bump();
+                        }
+                        _ => {
+                            path.push(index.to_string());
+                            self.positions.insert(path.clone(), (self.line, self.column));
+                            self.value(path)?;
+                            path.pop();
+                            index += 1;
+                        }
+                    }
+                }
+            }
+            b'"' => self.string().map(|_| ()),
+            _ => {
+                while self.peek().is_some_and(|b| !matches!(b, b',' | b'}' | b']') && !b.is_ascii_whitespace()) {
+                    self.bump();
+                }
+                Some(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_valid_file_has_no_issues() {
+        let content = r#"{
+            "colorPalette": "green",
+            "costDisplay": {"precision": 2, "rounding": "halfEven"},
+            "apiKeys": {"openrouter": "keychain:openrouter"},
+            "providerInference": {"sources": {"opencode": {"default": "anthropic"}}},
+            "recentBucketDays": null
+        }"#;
+        assert_eq!(check(content), vec![]);
+    }
+
+    #[test]
+    fn test_unknown_key_with_suggestion_and_position() {
+        let content = "{\n  \"costDisplay\": {\n    \"precison\": 2\n  },\n  \"fontSize\": 14\n}";
+        let issues = check(content);
+        assert_eq!(issues.len(), 2);
+
+        assert_eq!(issues[0].path, "costDisplay.precison");
+        assert_eq!((issues[0].line, issues[0].column), (3, 5));
+        assert_eq!(issues[0].severity, Severity::Warning);
+        assert!(issues[0].message.contains("did you mean \"precision\""), "{}", issues[0].message);
+
+        assert_eq!(issues[1].path, "fontSize");
+        assert_eq!((issues[1].line, issues[1].column), (5, 3));
+        assert_eq!(issues[1].message, "unknown key");
+    }
+
+    #[test]
+    fn test_type_mismatch_is_an_error() {
+        let content = r#"{"idleThresholdMinutes": "30", "providerPrefixes": ["openai", 3]}"#;
+        let issues = check(content);
+        assert_eq!(issues.len(), 2);
+        assert_eq!(issues[0].severity, Severity::Error);
+        assert_eq!(issues[0].path, "idleThresholdMinutes");
+        assert_eq!(issues[0].message, "expected a number, found string \"30\"");
+        assert_eq!(issues[1].path, "providerPrefixes[1]");
+        assert_eq!((issues[1].line, issues[1].column), (1, 63));
+    }
+
+    #[test]
+    fn test_wildcard_in_pattern_warns() {
+        let content = r#"{"providerInference": {"rules": [{"patterns": ["gpt-*"], "provider": "openai"}]}}"#;
+        let issues = check(content);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].path, "providerInference.rules[0].patterns[0]");
+        assert!(issues[0].message.contains("plain substring"));
+    }
+
+    #[test]
+    fn test_malformed_json_reports_position() {
+        let issues = check("{\n  \"locale\": \"pt-BR\",\n}");
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].severity, Severity::Error);
+        assert_eq!(issues[0].line, 3);
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("budjet", "budget"), 1);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("precison", "precision"), 1);
+    }
+}
@@ -0,0 +1,256 @@
+//! Parser conformance checks for tool authors
+//!
+//! Agent tools change their log formats, and tokscale finds out when users
+//! report missing usage. This lets a tool's own test suite run the exact
+//! parser tokscale uses over log files it writes and fail on anything that
+//! would be lost or repaired: files that yield no usage, fields the parser had
+//! to coerce (schema drift), messages without a model, impossible timestamps.
+//!
+//! The fixtures under `tests/fixtures/sessions/<source>/` are reference
+//! output for each source and are published with the npm package; every one
+//! of them passes.
+
+use crate::diagnostics;
+use crate::sessions::{self, UnifiedMessage};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+pub use crate::config_schema::Severity;
+
+/// Sources with a file parser, by the names used everywhere else
+pub const SOURCES: &[&str] = &["opencode", "claude", "codex", "gemini", "cursor", "amp", "droid", "cline", "roocode"];
+
+/// Allowed clock drift for timestamps ahead of now
+const FUTURE_SLACK_MS: i64 = 24 * 60 * 60 * 1000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Finding {
+    pub severity: Severity,
+    /// Stable identifier, e.g. `schema-drift`
+    pub code: &'static str,
+    /// File the finding is about; `None` for the run as a whole
+    pub file: Option<PathBuf>,
+    pub message: String,
+    /// Messages (or fields) affected
+    pub count: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct Conformance {
+    pub source: String,
+    pub files: usize,
+    pub messages: usize,
+    /// Formats files were detected as, for sources with several
+    pub formats: Vec<String>,
+    pub findings: Vec<Finding>,
+}
+
+impl Conformance {
+    pub fn passed(&self) -> bool {
+        !self.findings.iter().any(|f| f.severity == Severity::Error)
+    }
+}
+
+/// Parse one file with the parser for `source`; `None` if there is none
+pub fn parse_file(source: &str, path: &Path) -> Option<Vec<UnifiedMessage>> {
+    let messages = match source {
+        "opencode" => sessions::opencode::parse_opencode_file(path).into_iter().collect(),
+        "claude" => sessions::claudecode::parse_claude_file(path),
+        "codex" => sessions::codex::parse_codex_file(path),
+        "gemini" => sessions::gemini::parse_gemini_file(path),
+        "cursor" => sessions::cursor::parse_cursor_file(path),
+        "amp" => sessions::amp::parse_amp_file(path),
+        "droid" => sessions::droid::parse_droid_file(path),
+        "cline" => sessions::cline::parse_cline_file(path),
+        "roocode" => sessions::cline::parse_roocode_file(path),
+        _ => return None,
+    };
+    Some(messages)
+}
+
+/// Files to check: `paths` themselves, and files under any directory, sorted
+fn expand(paths: &[PathBuf]) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            files.extend(
+                walkdir::WalkDir::new(path)
+                    .into_iter()
+                    .filter_map(|e| e.ok())
+                    .filter(|e| e.file_type().is_file())
+                    .map(|e| e.into_path()),
+            );
+        } else {
+            files.push(path.clone());
+        }
+    }
+    files.sort();
+    files.dedup();
+    files
+}
+
+fn message_findings(messages: &[UnifiedMessage], now_ms: i64) -> Vec<(Severity, &'static str, &'static str)> {
+    let mut found = Vec::new();
+    for msg in messages {
+        let tokens = &msg.tokens;
+        let counts = [tokens.input, tokens.output, tokens.cache_read, tokens.cache_write, tokens.reasoning];
+        if msg.model_id.trim().is_empty() {
+            found.push((Severity::Error, "missing-model", "no model id"));
+        }
+        if counts.iter().any(|&n| n < 0) {
+            found.push((Severity::Error, "negative-tokens", "negative token counts"));
+        }
+        if msg.timestamp <= 0 || msg.date.is_empty() || msg.timestamp > now_ms + FUTURE_SLACK_MS {
+            found.push((Severity::Error, "bad-timestamp", "timestamps missing or in the future"));
+        } else if msg.estimated_timestamp {
+            found.push((Severity::Warning, "estimated-timestamp", "no timestamp of their own; estimated"));
+        }
+        if counts.iter().all(|&n| n == 0) {
+            found.push((Severity::Warning, "empty-usage", "no token counts"));
+        }
+        if msg.session_id.trim().is_empty() {
+            found.push((Severity::Warning, "missing-session", "no session id"));
+        }
+    }
+    found
+}
+
+fn check_file(source: &str, path: &Path, now_ms: i64, report: &mut Conformance) {
+    let file = Some(path.to_path_buf());
+    if let Err(e) = std::fs::metadata(path) {
+        report.findings.push(Finding {
+            severity: Severity::Error,
+            code: "unreadable",
+            file,
+            message: e.to_string(),
+            count: 1,
+        });
+        return;
+    }
+
+    let (messages, drift, formats) = diagnostics::capture(|| parse_file(source, path).unwrap_or_default());
+    report.files += 1;
+    report.messages += messages.len();
+    for format in formats {
+        if !report.formats.contains(&format.format) {
+            report.formats.push(format.format);
+        }
+    }
+
+    for drift in drift {
+        report.findings.push(Finding {
+            severity: Severity::Error,
+            code: "schema-drift",
+            file: file.clone(),
+            message: format!("{}: expected {}, found {}", drift.field, drift.expected, drift.found),
+            count: drift.count,
+        });
+    }
+    if messages.is_empty() {
+        report.findings.push(Finding {
+            severity: Severity::Warning,
+            code: "no-usage",
+            file: file.clone(),
+            message: "no usage records".to_string(),
+            count: 0,
+        });
+    }
+
+    let mut counted: BTreeMap<(&str, &str), (Severity, u64)> = BTreeMap::new();
+    for (severity, code, what) in message_findings(&messages, now_ms) {
+        counted.entry((code, what)).or_insert((severity, 0)).1 += 1;
+    }
+    for ((code, what), (severity, count)) in counted {
+        report.findings.push(Finding {
+            severity,
+            code,
+            file: file.clone(),
+            message: format!("{} of {} messages have {}", count, messages.len(), what),
+            count,
+        });
+    }
+}
+
+/// Check `paths` (files, or directories searched recursively) against the
+/// parser for `source`. Fails when any error is found, or when no file
+/// yields usage at all.
+pub fn check(source: &str, paths: &[PathBuf]) -> Result<Conformance, String> {
+    if !SOURCES.contains(&source) {
+        return Err(format!("Unknown source '{}' (expected one of: {})", source, SOURCES.join(", ")));
+    }
+    let mut report = Conformance {
+        source: source.to_string(),
+        files: 0,
+        messages: 0,
+        formats: Vec::new(),
+        findings: Vec::new(),
+    };
+    let now_ms = chrono::Utc::now().timestamp_millis();
+    let files = expand(paths);
+    for path in &files {
+        check_file(source, path, now_ms, &mut report);
+    }
+    if report.messages == 0 {
+        report.findings.push(Finding {
+            severity: Severity::Error,
+            code: "no-usage",
+            file: None,
+            message: if files.is_empty() {
+                "no files to check".to_string()
+            } else {
+                format!("none of {} file(s) yielded usage", files.len())
+            },
+            count: 0,
+        });
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn fixtures(source: &str) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures/sessions")
+            .join(source)
+    }
+
+    #[test]
+    fn test_reference_fixtures_conform() {
+        for source in SOURCES {
+            let report = check(source, &[fixtures(source)]).unwrap();
+            let errors: Vec<&Finding> = report.findings.iter().filter(|f| f.severity == Severity::Error).collect();
+            assert!(report.passed(), "{} fixtures fail conformance: {:?}", source, errors);
+            assert!(report.messages > 0);
+        }
+    }
+
+    #[test]
+    fn test_drift_and_missing_usage_fail() {
+        let dir = tempfile::tempdir().unwrap();
+        let drifted = dir.path().join("msg_drift.json");
+        fs::write(
+            &drifted,
+            r#"{"id":"msg_1","sessionID":"ses_1","role":"assistant","modelID":"claude-sonnet-4","providerID":"anthropic",
+                "time":{"created":1750075190000},"tokens":{"input":"12","output":3,"reasoning":0,"cache":{"read":0,"write":0}}}"#,
+        )
+        .unwrap();
+        let report = check("opencode", std::slice::from_ref(&drifted)).unwrap();
+        assert!(!report.passed());
+        assert_eq!(report.messages, 1);
+        let drift = report.findings.iter().find(|f| f.code == "schema-drift").unwrap();
+        assert_eq!(drift.file.as_deref(), Some(drifted.as_path()));
+        assert!(drift.message.starts_with("tokens/input:"), "{}", drift.message);
+
+        let empty = dir.path().join("session.jsonl");
+        fs::write(&empty, "{\"type\":\"user\"}\n").unwrap();
+        let report = check("claude", &[empty, dir.path().join("missing.jsonl")]).unwrap();
+        let codes: Vec<&str> = report.findings.iter().map(|f| f.code).collect();
+        assert_eq!(codes, vec!["unreadable", "no-usage", "no-usage"]);
+        assert!(!report.passed());
+
+        assert!(check("vim", &[]).is_err());
+    }
+}
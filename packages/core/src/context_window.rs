@@ -0,0 +1,185 @@
+//! Context window pressure
+//!
+//! A request's context is everything the model reads: fresh input plus cache
+//! reads and writes. As it nears the model's context window, agents compact
+//! or truncate history, and quality drops while every turn is billed at
+//! close to the full window. This flags messages whose context reaches
+//! [`DEFAULT_THRESHOLD`] of the window listed in the pricing catalog (or
+//! exceeds it) and reports, per model, how often that happens.
+//!
+//! Messages past the window usually mean the catalog lists a smaller window
+//! than the one the request ran with (a long-context beta, say), so they are
+//! counted apart rather than as errors. Models the catalog has no window for
+//! are listed and otherwise skipped.
+
+use crate::sessions::UnifiedMessage;
+use crate::{ContextPressure, ContextPressureReport};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// Share of the window from which a message counts as near the limit
+pub const DEFAULT_THRESHOLD: f64 = 0.9;
+
+pub fn validate_threshold(threshold: f64) -> Result<(), String> {
+    if threshold.is_nan() || threshold <= 0.0 || threshold > 1.0 {
+        return Err(format!("threshold must be above 0 and at most 1, got {}", threshold));
+    }
+    Ok(())
+}
+
+/// Tokens the model read for one message
+pub fn context_tokens(msg: &UnifiedMessage) -> i64 {
+    msg.tokens.input + msg.tokens.cache_read + msg.tokens.cache_write
+}
+
+#[derive(Default)]
+struct Model<'a> {
+    messages: u32,
+    near: u32,
+    over: u32,
+    total_context: i64,
+    peak_context: i64,
+    near_cost: f64,
+    sessions: BTreeSet<(&'a str, &'a str)>,
+}
+
+/// `window` gives the context window for a model on a provider; it is asked
+/// once per pair. Models are ordered by messages near or over the limit, most
+/// first.
+pub fn report(
+    messages: &[UnifiedMessage],
+    threshold: f64,
+    mut window: impl FnMut(&str, &str) -> Option<u64>,
+    processing_time_ms: u32,
+) -> ContextPressureReport {
+    let mut windows: HashMap<(&str, &str), Option<u64>> = HashMap::new();
+    let mut models: BTreeMap<(&str, u64), Model> = BTreeMap::new();
+    let mut without_window = BTreeSet::new();
+
+    for msg in messages {
+        let key = (msg.model_id.as_str(), msg.provider_id.as_str());
+        let Some(limit) = *windows.entry(key).or_insert_with(|| window(key.0, key.1)) else {
+            without_window.insert(msg.model_id.as_str());
+            continue;
+        };
+        let context = context_tokens(msg);
+        let model = models.entry((msg.model_id.as_str(), limit)).or_default();
+        model.messages += 1;
+        model.total_context += context;
+        model.peak_context = model.peak_context.max(context);
+        if context as f64 >= limit as f64 * threshold {
+            if context as u64 > limit {
+                model.over += 1;
+            } else {
+                model.near += 1;
+            }
+            model.near_cost += msg.cost;
+            model.sessions.insert((msg.source.as_str(), msg.session_id.as_str()));
+        }
+    }
+
+    let mut entries: Vec<ContextPressure> = models
+        .into_iter()
+        .map(|((model_id, limit), m)| ContextPressure {
+            model: model_id.to_string(),
+            context_window: limit as i64,
+            message_count: m.messages,
+            near_limit_count: m.near,
+            over_limit_count: m.over,
+            near_limit_share: (m.near + m.over) as f64 / m.messages as f64,
+            near_limit_cost: m.near_cost,
+            sessions_affected: m.sessions.len() as u32,
+            peak_context: m.peak_context,
+            average_context: m.total_context as f64 / m.messages as f64,
+        })
+        .collect();
+    entries.sort_by(|a, b| {
+        (b.near_limit_count + b.over_limit_count)
+            .cmp(&(a.near_limit_count + a.over_limit_count))
+            .then_with(|| a.model.cmp(&b.model))
+    });
+
+    ContextPressureReport {
+        threshold,
+        message_count: entries.iter().map(|e| e.message_count).sum(),
+        near_limit_count: entries.iter().map(|e| e.near_limit_count).sum(),
+        over_limit_count: entries.iter().map(|e| e.over_limit_count).sum(),
+        near_limit_cost: entries.iter().map(|e| e.near_limit_cost).sum(),
+        models: entries,
+        models_without_window: without_window.into_iter().map(str::to_string).collect(),
+        processing_time_ms,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{message, TestMessage};
+    use crate::TokenBreakdown;
+
+    const T0: i64 = 1_750_000_000_000;
+
+    fn usage(input: i64, cache_read: i64) -> TokenBreakdown {
+        TokenBreakdown { input, cache_read, output: 500, ..TokenBreakdown::default() }
+    }
+
+    fn windows(model: &str, _provider: &str) -> Option<u64> {
+        match model {
+            "sonnet" => Some(200_000),
+            "haiku" => Some(100_000),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_flags_messages_near_and_over_the_window() {
+        let messages = vec![
+            message("s1", T0, 0.1).with_model("sonnet").with_tokens(usage(1_000, 50_000)),
+            message("s1", T0, 0.5).with_model("sonnet").with_tokens(usage(2_000, 178_000)), // exactly 90%
+            message("s2", T0, 0.75).with_model("sonnet").with_tokens(usage(5_000, 190_000)),
+            message("s2", T0, 1.0).with_model("sonnet").with_tokens(usage(10_000, 200_000)), // past the window
+            message("s3", T0, 0.01).with_model("haiku").with_tokens(usage(1_000, 10_000)),
+            message("s4", T0, 9.0).with_model("mystery").with_tokens(usage(1_000_000, 0)),
+        ];
+
+        let report = report(&messages, DEFAULT_THRESHOLD, windows, 0);
+        assert_eq!(report.models_without_window, vec!["mystery"]);
+        assert_eq!(report.message_count, 5);
+        assert_eq!((report.near_limit_count, report.over_limit_count), (2, 1));
+        assert_eq!(report.near_limit_cost, 2.25);
+
+        let sonnet = &report.models[0];
+        assert_eq!(sonnet.model, "sonnet");
+        assert_eq!(sonnet.context_window, 200_000);
+        assert_eq!(sonnet.near_limit_share, 0.75);
+        assert_eq!(sonnet.sessions_affected, 2);
+        assert_eq!(sonnet.peak_context, 210_000);
+
+        let haiku = &report.models[1];
+        assert_eq!((haiku.near_limit_count, haiku.near_limit_share), (0, 0.0));
+        assert_eq!(haiku.average_context, 11_000.0);
+    }
+
+    #[test]
+    fn test_threshold_and_window_lookups() {
+        let messages = vec![
+            message("s1", T0, 0.5).with_model("sonnet").with_tokens(usage(0, 120_000)),
+            message("s2", T0, 0.5).with_model("sonnet").with_tokens(usage(0, 100_000)),
+        ];
+        let mut asked = 0;
+        let report = report(
+            &messages,
+            0.6,
+            |model, provider| {
+                asked += 1;
+                windows(model, provider)
+            },
+            0,
+        );
+        assert_eq!(asked, 1, "one lookup per model and provider");
+        assert_eq!(report.near_limit_count, 1);
+
+        assert!(validate_threshold(0.0).is_err());
+        assert!(validate_threshold(1.5).is_err());
+        assert!(validate_threshold(1.0).is_ok());
+    }
+}
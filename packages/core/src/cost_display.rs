@@ -0,0 +1,251 @@
+//! Cost rounding and display
+//!
+//! Reports keep full precision unless `costDisplay.precision` is set in
+//! settings. When it is, every cost in a report is rounded here, the same way
+//! for every report, instead of by each caller. Totals are summed from the
+//! unrounded values and rounded last, so they don't accumulate rounding error.
+
+use crate::{GraphResult, ModelReport, MonthlyReport, RecentUsage, RunSummary};
+use serde::{Deserialize, Serialize};
+
+/// Most decimal places honoured; beyond this f64 can't represent the result
+const MAX_PRECISION: u32 = 10;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RoundingMode {
+    /// Ties away from zero (0.125 → 0.13)
+    #[default]
+    HalfUp,
+    /// Ties to the even digit (0.125 → 0.12)
+    HalfEven,
+    /// Toward positive infinity
+    Up,
+    /// Toward negative infinity
+    Down,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct CostDisplay {
+    pub rounding: RoundingMode,
+    /// Decimal places kept in report costs; unset keeps full precision.
+    /// Formatting uses 2 when unset.
+    pub precision: Option<u32>,
+    /// Format amounts under $1 as cents, e.g. "42¢"
+    pub cents_below_dollar: bool,
+}
+
+impl CostDisplay {
+    /// Round a report cost; a no-op unless a precision is configured
+    pub fn round(&self, cost: f64) -> f64 {
+        match self.precision {
+            Some(precision) => round_to(cost, precision, self.rounding),
+            None => cost,
+        }
+    }
+
+    pub fn format(&self, cost: f64) -> String {
+        let precision = self.precision.unwrap_or(2).min(MAX_PRECISION);
+        let rounded = round_to(cost, precision, self.rounding);
+
+        if self.cents_below_dollar && rounded.abs() < 1.0 {
+            let cent_digits = precision.saturating_sub(2) as usize;
+            return format!("{:.*}¢", cent_digits, rounded * 100.0);
+        }
+        if rounded < 0.0 {
+            return format!("-${:.*}", precision as usize, -rounded);
+        }
+        format!("${:.*}", precision as usize, rounded)
+    }
+
+    pub fn apply_to_model_report(&self, report: &mut ModelReport) {
+        for entry in &mut report.entries {
+            entry.cost = self.round(entry.cost);
+            entry.covered = self.round(entry.covered);
+        }
+        report.total_cost = self.round(report.total_cost);
+        report.total_covered = self.round(report.total_covered);
+        if let Some(summary) = &mut report.summary {
+            self.apply_to_summary(summary);
+        }
+        for family in report.families.iter_mut().flatten() {
+            family.cost = self.round(family.cost);
+            family.covered = self.round(family.covered);
+            for entry in &mut family.models {
+                entry.cost = self.round(entry.cost);
+                entry.covered = self.round(entry.covered);
+            }
+        }
+    }
+
+    pub fn apply_to_monthly_report(&self, report: &mut MonthlyReport) {
+        for entry in &mut report.entries {
+            entry.cost = self.round(entry.cost);
+        }
+        report.total_cost = self.round(report.total_cost);
+        if let Some(summary) = &mut report.summary {
+            self.apply_to_summary(summary);
+        }
+    }
+
+    fn apply_to_summary(&self, summary: &mut RunSummary) {
+        summary.exact_cost = self.round(summary.exact_cost);
+        summary.estimated_cost = self.round(summary.estimated_cost);
+    }
+
+    pub fn apply_to_recent(&self, recent: &mut RecentUsage) {
+        for bucket in &mut recent.buckets {
+            bucket.cost = self.round(bucket.cost);
+        }
+        recent.cost_per_hour = self.round(recent.cost_per_hour);
+    }
+
+    pub fn apply_to_graph(&self, graph: &mut GraphResult) {
+        for day in &mut graph.contributions {
+            day.totals.cost = self.round(day.totals.cost);
+            for source in &mut day.sources {
+                source.cost = self.round(source.cost);
+            }
+        }
+        for year in &mut graph.years {
+            year.total_cost = self.round(year.total_cost);
+        }
+        graph.summary.total_cost = self.round(graph.summary.total_cost);
+        graph.summary.average_per_day = self.round(graph.summary.average_per_day);
+        graph.summary.max_cost_in_single_day = self.round(graph.summary.max_cost_in_single_day);
+    }
+}
+
+/// Cost display settings from the user's settings file
+pub fn settings() -> CostDisplay {
+    crate::config::settings().cost_display.clone()
+}
+
+fn round_to(value: f64, precision: u32, mode: RoundingMode) -> f64 {
+    let scale = 10f64.powi(precision.min(MAX_PRECISION) as i32);
+    // Snap away binary noise first so 0.285 rounds as the decimal it looks like
+    let scaled = (value * scale * 1e6).round() / 1e6;
+
+    let rounded = match mode {
+        RoundingMode::HalfUp => scaled.round(),
+        RoundingMode::HalfEven => scaled.round_ties_even(),
+        RoundingMode::Up => scaled.ceil(),
+        RoundingMode::Down => scaled.floor(),
+    };
+    rounded / scale
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DataSummary, GraphMeta, ModelUsage};
+
+    fn display(precision: Option<u32>, rounding: RoundingMode, cents: bool) -> CostDisplay {
+        CostDisplay { rounding, precision, cents_below_dollar: cents }
+    }
+
+    #[test]
+    fn test_rounding_modes() {
+        assert_eq!(round_to(0.125, 2, RoundingMode::HalfUp), 0.13);
+        assert_eq!(round_to(0.125, 2, RoundingMode::HalfEven), 0.12);
+        assert_eq!(round_to(0.135, 2, RoundingMode::HalfEven), 0.14);
+        assert_eq!(round_to(0.121, 2, RoundingMode::Up), 0.13);
+        assert_eq!(round_to(0.129, 2, RoundingMode::Down), 0.12);
+        assert_eq!(round_to(0.285, 2, RoundingMode::HalfUp), 0.29);
+        assert_eq!(round_to(1234.5, 0, RoundingMode::HalfUp), 1235.0);
+    }
+
+    #[test]
+    fn test_round_without_precision_is_noop() {
+        assert_eq!(CostDisplay::default().round(0.123456789), 0.123456789);
+    }
+
+    #[test]
+    fn test_format() {
+        assert_eq!(CostDisplay::default().format(12.345), "$12.35");
+        assert_eq!(CostDisplay::default().format(0.42), "$0.42");
+        assert_eq!(display(Some(4), RoundingMode::HalfUp, false).format(3.0), "$3.0000");
+        assert_eq!(display(None, RoundingMode::Down, false).format(-1.239), "-$1.24");
+    }
+
+    #[test]
+    fn test_format_cents_below_dollar() {
+        let cents = display(None, RoundingMode::HalfUp, true);
+        assert_eq!(cents.format(0.42), "42¢");
+        assert_eq!(cents.format(0.004), "0¢");
+        assert_eq!(cents.format(1.5), "$1.50");
+        // Rounds up into dollars
+        assert_eq!(cents.format(0.996), "$1.00");
+
+        let precise = display(Some(4), RoundingMode::HalfUp, true);
+        assert_eq!(precise.format(0.42375), "42.38¢");
+    }
+
+    #[test]
+    fn test_totals_rounded_from_unrounded_sum() {
+        let entry = |cost| ModelUsage {
+            source: "claude".into(),
+            model: "m".into(),
+            provider: "anthropic".into(),
+            input: 0,
+            output: 0,
+            cache_read: 0,
+            cache_write: 0,
+            reasoning: 0,
+            training: 0,
+            message_count: 1,
+            cost,
+            covered: 0.0,
+            cost_share: 0.0,
+            token_share: 0.0,
+        };
+        let mut report = ModelReport {
+            entries: vec![entry(0.004), entry(0.004), entry(0.004)],
+            total_input: 0,
+            total_output: 0,
+            total_cache_read: 0,
+            total_cache_write: 0,
+            total_reasoning: 0,
+            total_messages: 3,
+            total_cost: 0.012,
+            total_covered: 0.0,
+            processing_time_ms: 0,
+            summary: None,
+            families: None,
+        };
+
+        display(Some(2), RoundingMode::HalfUp, false).apply_to_model_report(&mut report);
+        assert!(report.entries.iter().all(|e| e.cost == 0.0));
+        assert_eq!(report.total_cost, 0.01);
+    }
+
+    #[test]
+    fn test_apply_to_graph() {
+        let mut graph = GraphResult {
+            meta: GraphMeta {
+                generated_at: String::new(),
+                version: String::new(),
+                date_range_start: String::new(),
+                date_range_end: String::new(),
+                processing_time_ms: 0,
+            },
+            summary: DataSummary {
+                total_tokens: 0,
+                total_cost: 1.23456,
+                total_days: 1,
+                active_days: 1,
+                average_per_day: 1.23456,
+                max_cost_in_single_day: 1.23456,
+                sources: vec![],
+                models: vec![],
+            },
+            years: vec![],
+            contributions: vec![],
+        };
+
+        display(Some(1), RoundingMode::Down, false).apply_to_graph(&mut graph);
+        assert_eq!(graph.summary.total_cost, 1.2);
+        assert_eq!(graph.summary.max_cost_in_single_day, 1.2);
+    }
+}
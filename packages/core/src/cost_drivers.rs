@@ -0,0 +1,172 @@
+//! What drives cost
+//!
+//! Spend is grouped into turns: a user prompt and every response to it until
+//! the next prompt in the same session. Turns are then bucketed by prompt
+//! length, tool calls and files touched, the stats parsers derive from
+//! content without keeping it, to show which kind of turn the money goes to.
+//!
+//! Only sources that log prompts form turns (Claude Code and Codex). Their
+//! responses before a session's first prompt, and all other messages, are
+//! counted as unattributed. Files are counted per response, so a file named
+//! in two responses of a turn counts twice.
+
+use crate::sessions::UnifiedMessage;
+use crate::{CostDriverBucket, CostDriverReport};
+
+/// Lower bound and label of each bucket, ascending
+type Buckets = &'static [(u32, &'static str)];
+
+const PROMPT_BUCKETS: Buckets = &[
+    (0, "under 100 chars"),
+    (100, "100-499 chars"),
+    (500, "500-1,999 chars"),
+    (2_000, "2,000-9,999 chars"),
+    (10_000, "10,000+ chars"),
+];
+const TOOL_CALL_BUCKETS: Buckets = &[(0, "no tools"), (1, "1-2 calls"), (3, "3-9 calls"), (10, "10-29 calls"), (30, "30+ calls")];
+const FILE_BUCKETS: Buckets = &[(0, "no files"), (1, "1 file"), (2, "2-4 files"), (5, "5-9 files"), (10, "10+ files")];
+
+#[derive(Debug, Clone, Default)]
+struct Turn {
+    prompt_chars: u32,
+    tool_calls: u32,
+    files_touched: Option<u32>,
+    messages: u32,
+    tokens: i64,
+    cost: f64,
+}
+
+impl Turn {
+    fn add(&mut self, msg: &UnifiedMessage) {
+        self.tool_calls += msg.tool_calls;
+        if let Some(files) = msg.files_touched {
+            self.files_touched = Some(self.files_touched.unwrap_or(0) + files);
+        }
+        self.messages += 1;
+        self.tokens += msg.tokens.input + msg.tokens.output + msg.tokens.cache_read + msg.tokens.cache_write;
+        self.cost += msg.cost;
+    }
+}
+
+/// Turns in session order, plus messages outside any turn
+fn turns(messages: &[UnifiedMessage]) -> (Vec<Turn>, Vec<&UnifiedMessage>) {
+    let mut ordered: Vec<&UnifiedMessage> = messages.iter().collect();
+    ordered.sort_by(|a, b| {
+        (a.source.as_str(), &a.session_id, a.timestamp).cmp(&(b.source.as_str(), &b.session_id, b.timestamp))
+    });
+
+    let mut turns: Vec<Turn> = Vec::new();
+    let mut unattributed = Vec::new();
+    // Session of the last turn started
+    let mut open: Option<(&str, &str)> = None;
+    for msg in ordered {
+        let session = (msg.source.as_str(), msg.session_id.as_str());
+        if let Some(chars) = msg.prompt_chars {
+            turns.push(Turn { prompt_chars: chars, ..Turn::default() });
+            open = Some(session);
+        } else if open != Some(session) {
+            unattributed.push(msg);
+            continue;
+        }
+        if let Some(turn) = turns.last_mut() {
+            turn.add(msg);
+        }
+    }
+    (turns, unattributed)
+}
+
+fn bucket_index(buckets: Buckets, value: u32) -> usize {
+    buckets.iter().rposition(|(lower, _)| value >= *lower).unwrap_or(0)
+}
+
+/// Non-empty buckets in ascending order
+fn bucketed(turns: &[Turn], buckets: Buckets, value: impl Fn(&Turn) -> Option<u32>) -> Vec<CostDriverBucket> {
+    let mut out: Vec<CostDriverBucket> = buckets
+        .iter()
+        .map(|(_, label)| CostDriverBucket {
+            label: label.to_string(),
+            turn_count: 0,
+            message_count: 0,
+            tokens: 0,
+            cost: 0.0,
+            average_cost: 0.0,
+            share: 0.0,
+        })
+        .collect();
+    for turn in turns {
+        let Some(value) = value(turn) else { continue };
+        let bucket = &mut out[bucket_index(buckets, value)];
+        bucket.turn_count += 1;
+        bucket.message_count += turn.messages;
+        bucket.tokens += turn.tokens;
+        bucket.cost += turn.cost;
+    }
+
+    let total: f64 = out.iter().map(|b| b.cost).sum();
+    out.retain(|b| b.turn_count > 0);
+    for bucket in &mut out {
+        bucket.average_cost = bucket.cost / bucket.turn_count as f64;
+        bucket.share = if total > 0.0 { bucket.cost / total } else { 0.0 };
+    }
+    out
+}
+
+pub fn report(messages: &[UnifiedMessage], processing_time_ms: u32) -> CostDriverReport {
+    let (turns, unattributed) = turns(messages);
+    CostDriverReport {
+        prompt_length: bucketed(&turns, PROMPT_BUCKETS, |t| Some(t.prompt_chars)),
+        tool_calls: bucketed(&turns, TOOL_CALL_BUCKETS, |t| Some(t.tool_calls)),
+        files_touched: bucketed(&turns, FILE_BUCKETS, |t| t.files_touched),
+        turn_count: turns.len() as u32,
+        attributed_cost: turns.iter().map(|t| t.cost).sum(),
+        unattributed_messages: unattributed.len() as u32,
+        unattributed_cost: unattributed.iter().map(|m| m.cost).sum(),
+        processing_time_ms,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{message, TestMessage};
+
+    #[test]
+    fn test_turns_follow_prompts_within_a_session() {
+        let mut first = message("s1", 1_000, 1.0).with_prompt_chars(Some(50));
+        first.tool_calls = 2;
+        first.files_touched = Some(1);
+        let mut second = message("s1", 2_000, 2.0);
+        second.tool_calls = 3;
+        second.files_touched = Some(2);
+        let messages = vec![
+            second,
+            message("s1", 500, 0.5), // before the session's first prompt
+            first,
+            message("s1", 3_000, 4.0).with_prompt_chars(Some(3_000)),
+            message("s2", 1_500, 8.0), // another session, no prompt
+            message("", 1_000, 16.0).with_source("cursor").with_model("gpt-5").with_provider("openai"),
+        ];
+
+        let report = report(&messages, 0);
+        assert_eq!(report.turn_count, 2);
+        assert_eq!(report.attributed_cost, 7.0);
+        assert_eq!(report.unattributed_messages, 3);
+        assert_eq!(report.unattributed_cost, 24.5);
+
+        let labels = |buckets: &[CostDriverBucket]| buckets.iter().map(|b| b.label.clone()).collect::<Vec<_>>();
+        assert_eq!(labels(&report.prompt_length), vec!["under 100 chars", "2,000-9,999 chars"]);
+        assert_eq!(report.prompt_length[0].cost, 3.0);
+        assert_eq!(report.prompt_length[0].message_count, 2);
+        assert_eq!(labels(&report.tool_calls), vec!["no tools", "3-9 calls"]);
+        assert_eq!(labels(&report.files_touched), vec!["2-4 files"], "turns without file stats are left out");
+        assert_eq!(report.files_touched[0].share, 1.0);
+    }
+
+    #[test]
+    fn test_bucket_bounds() {
+        assert_eq!(bucket_index(TOOL_CALL_BUCKETS, 0), 0);
+        assert_eq!(bucket_index(TOOL_CALL_BUCKETS, 2), 1);
+        assert_eq!(bucket_index(TOOL_CALL_BUCKETS, 3), 2);
+        assert_eq!(bucket_index(PROMPT_BUCKETS, u32::MAX), 4);
+    }
+}
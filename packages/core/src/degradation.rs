@@ -0,0 +1,157 @@
+//! Degraded reports
+//!
+//! A report renders from whatever could be read, and says what it is
+//! missing instead of failing. Four things go wrong, alone or together:
+//!
+//! | Condition | Behavior | `summary.degradation` |
+//! |-----------|----------|-----------------------|
+//! | Prices can't be fetched, cache still fresh | Cached prices are used | `pricing: "fresh"` |
+//! | Prices can't be fetched, cache expired | The expired cache is used (see [`crate::pricing::provider`]) | `pricing: "stale"` |
+//! | Cached or fetched prices over a day old | Used as they are | `pricing: "stale"` |
+//! | Prices can't be fetched, nothing cached | Costs are those sources reported and bundled rates; the rest cost $0 | `pricing: "unavailable"`, `pricingError` |
+//! | A source asked for by name has no session files | Left out | `missingSources` |
+//! | Session files are empty, unreadable or corrupt | Left out; the rest of the source is counted | `skippedFiles` |
+//!
+//! `degraded` is set when any of them applies. Archived prices (`--as-of`)
+//! are complete by definition and don't degrade a report. The CLI prints
+//! each gap under the run summary; `--json` output carries the object.
+
+use crate::pricing::PricingService;
+use crate::{Degradation, SourceFiles};
+
+pub const FRESH: &str = "fresh";
+pub const STALE: &str = "stale";
+pub const ARCHIVED: &str = "archived";
+pub const UNAVAILABLE: &str = "unavailable";
+
+/// What a report over `files`, priced with `pricing`, is missing
+pub fn assess(files: &[SourceFiles], pricing: &PricingService, pricing_stale: bool) -> Degradation {
+    let pricing_error = pricing.unavailable_reason().map(str::to_string);
+    let state = match (&pricing_error, pricing.fetched_at()) {
+        (Some(_), _) => UNAVAILABLE,
+        (None, None) => ARCHIVED,
+        (None, Some(_)) if pricing_stale => STALE,
+        (None, Some(_)) => FRESH,
+    };
+    let missing_sources: Vec<String> =
+        files.iter().filter(|f| f.named && f.files == 0).map(|f| f.source.clone()).collect();
+    let skipped_files = files.iter().map(|f| f.skipped).sum();
+    Degradation {
+        degraded: state == STALE || state == UNAVAILABLE || !missing_sources.is_empty() || skipped_files > 0,
+        pricing: state.to_string(),
+        pricing_error,
+        missing_sources,
+        skipped_files,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pricing::ModelPricing;
+    use crate::{run_summary, LocalParseOptions};
+    use std::collections::HashMap;
+    use std::fs;
+    use std::path::Path;
+    use std::time::Instant;
+
+    const NOW: u64 = 1_750_075_200;
+    const HOUR: u64 = 3600;
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    enum Prices {
+        Fresh,
+        Stale,
+        Unavailable,
+    }
+
+    fn pricing(prices: Prices) -> PricingService {
+        let opus = ModelPricing {
+            input_cost_per_token: Some(5e-6),
+            output_cost_per_token: Some(25e-6),
+            ..ModelPricing::default()
+        };
+        let table = HashMap::from([("claude-opus-4-5-20251101".to_string(), opus)]);
+        let tables = || PricingService::new(table.clone(), HashMap::new());
+        match prices {
+            Prices::Fresh => tables().with_fetched_at(NOW - HOUR),
+            Prices::Stale => tables().with_fetched_at(NOW - 72 * HOUR),
+            Prices::Unavailable => PricingService::unavailable("litellm: HTTP 503"),
+        }
+    }
+
+    /// A home with the Claude fixture, and a corrupt session file if asked
+    fn home(corrupt: bool) -> tempfile::TempDir {
+        let home = tempfile::tempdir().unwrap();
+        let project = home.path().join(".claude/projects/app");
+        fs::create_dir_all(&project).unwrap();
+        let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/sessions/claude/session-fixture.jsonl");
+        fs::copy(fixture, project.join("session-fixture.jsonl")).unwrap();
+        if corrupt {
+            fs::write(project.join("corrupt.jsonl"), b"\x00\x9f{\"type\":\"assistant\",\"message\":{\"usa").unwrap();
+        }
+        home
+    }
+
+    /// Every combination of prices, missing sources and corrupt files still
+    /// renders a report of the readable usage, with each gap flagged
+    #[test]
+    fn test_degradation_matrix() {
+        for prices in [Prices::Fresh, Prices::Stale, Prices::Unavailable] {
+            for missing in [false, true] {
+                for corrupt in [false, true] {
+                    let case = format!("prices {:?}, missing {}, corrupt {}", prices, missing, corrupt);
+                    let home = home(corrupt);
+                    let mut sources = vec!["claude".to_string()];
+                    if missing {
+                        sources.push("gemini".to_string());
+                    }
+                    let options = LocalParseOptions {
+                        home_dir: None,
+                        sources: Some(sources),
+                        since: None,
+                        until: None,
+                        year: None,
+                        exclude_estimated_timestamps: None,
+                    };
+                    let parsed = crate::parse_local(&home.path().to_string_lossy(), &options);
+
+                    let pricing = pricing(prices);
+                    let messages: Vec<_> =
+                        parsed.messages.iter().map(|msg| crate::price_parsed(msg, &pricing)).collect();
+                    let files = parsed.files.clone().unwrap_or_default();
+                    let summary = run_summary::build(&messages, files, 0, &pricing, NOW);
+                    let report = crate::aggregate_models(messages, Instant::now());
+
+                    assert!(!report.entries.is_empty(), "{}: readable usage is reported", case);
+                    assert!(report.entries.iter().all(|e| e.source == "claude"), "{}", case);
+                    let degradation = &summary.degradation;
+                    let expected = match prices {
+                        Prices::Fresh => FRESH,
+                        Prices::Stale => STALE,
+                        Prices::Unavailable => UNAVAILABLE,
+                    };
+                    assert_eq!(degradation.pricing, expected, "{}", case);
+                    assert_eq!(degradation.pricing_error.is_some(), prices == Prices::Unavailable, "{}", case);
+                    if prices == Prices::Unavailable {
+                        assert_eq!(report.total_cost, 0.0, "{}: unpriced usage costs nothing", case);
+                    } else {
+                        assert!(report.total_cost > 0.0, "{}", case);
+                    }
+                    let missing_sources: &[&str] = if missing { &["gemini"] } else { &[] };
+                    assert_eq!(degradation.missing_sources, missing_sources, "{}", case);
+                    assert_eq!(degradation.skipped_files, corrupt as u32, "{}", case);
+                    assert_eq!(degradation.degraded, prices != Prices::Fresh || missing || corrupt, "{}", case);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_archived_prices_not_degraded() {
+        let files = [SourceFiles { source: "claude".into(), files: 0, parsed: 0, skipped: 0, named: false }];
+        let degradation = assess(&files, &PricingService::new(HashMap::new(), HashMap::new()), false);
+        assert_eq!(degradation.pricing, ARCHIVED);
+        assert!(!degradation.degraded, "sources scanned by default may have no files");
+    }
+}
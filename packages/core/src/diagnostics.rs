@@ -0,0 +1,296 @@
+//! Parse diagnostics
+//!
+//! Collects schema drift seen while parsing session files: fields whose type
+//! no longer matches what a parser expects, or documents that could not be
+//! read even after repair. Each distinct drift is logged to stderr once and
+//! counted, so frontends can surface it without failing the whole run.
+//!
+//! Parsers that support several file formats also record which format each
+//! file was detected as, and price lookups record models that LiteLLM and
+//! OpenRouter price differently. Timestamps corrected by [`crate::clock`] are
+//! counted per source and kind of correction.
+//!
+//! [`capture`] collects what one piece of work records on its own thread
+//! instead, for callers that need it attributed (conformance checks).
+
+use once_cell::sync::Lazy;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashSet};
+use std::sync::Mutex;
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct DriftKey {
+    source: &'static str,
+    field: String,
+    expected: &'static str,
+    found: String,
+}
+
+#[derive(Default)]
+struct Collector {
+    drift: BTreeMap<DriftKey, u64>,
+    formats: BTreeMap<(&'static str, &'static str), u64>,
+    price_conflicts: BTreeMap<String, PriceConflict>,
+    timestamp_corrections: BTreeMap<(String, &'static str), u64>,
+    /// Drift already logged to stderr during this process
+    logged: HashSet<(&'static str, String)>,
+}
+
+static COLLECTOR: Lazy<Mutex<Collector>> = Lazy::new(|| Mutex::new(Collector::default()));
+
+thread_local! {
+    /// Set while [`capture`] runs on this thread
+    static CAPTURED: RefCell<Option<Collector>> = const { RefCell::new(None) };
+}
+
+/// Run `f`, collecting the drift and formats it records on this thread apart
+/// from the process-wide counters (and without logging drift to stderr)
+pub fn capture<T>(f: impl FnOnce() -> T) -> (T, Vec<SchemaDrift>, Vec<FormatCount>) {
+    let outer = CAPTURED.with(|c| c.replace(Some(Collector::default())));
+    let value = f();
+    let mut captured = CAPTURED.with(|c| c.replace(outer)).unwrap_or_default();
+    let drift = drain_drift_map(&mut captured.drift, |_| true);
+    let formats = drain_format_map(&mut captured.formats, |_| true);
+    (value, drift, formats)
+}
+
+/// Apply `record` to the capturing collector if there is one on this thread
+fn record_captured(record: impl FnOnce(&mut Collector)) -> bool {
+    CAPTURED.with(|c| match c.borrow_mut().as_mut() {
+        Some(collector) => {
+            record(collector);
+            true
+        }
+        None => false,
+    })
+}
+
+/// One kind of schema drift and how often it was seen
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaDrift {
+    pub source: String,
+    /// Path of the offending field, e.g. `message/usage/input_tokens`
+    pub field: String,
+    pub expected: String,
+    pub found: String,
+    pub count: u64,
+}
+
+/// Files parsed as one detected format
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormatCount {
+    pub source: String,
+    pub format: String,
+    pub count: u64,
+}
+
+/// A model LiteLLM and OpenRouter price differently
+#[derive(Debug, Clone, PartialEq)]
+pub struct PriceConflict {
+    pub model_id: String,
+    pub litellm_key: String,
+    pub openrouter_key: String,
+    /// (input, output) USD per token
+    pub litellm: (Option<f64>, Option<f64>),
+    pub openrouter: (Option<f64>, Option<f64>),
+    /// Source whose prices were used
+    pub chosen: &'static str,
+}
+
+/// Record a price conflict; repeats for the same model replace the earlier one
+pub fn record_price_conflict(conflict: PriceConflict) {
+    let Ok(mut collector) = COLLECTOR.lock() else { return };
+    collector.price_conflicts.insert(conflict.model_id.clone(), conflict);
+}
+
+/// Price conflicts since the last call, clearing them
+pub fn take_price_conflicts() -> Vec<PriceConflict> {
+    let Ok(mut collector) = COLLECTOR.lock() else { return Vec::new() };
+    std::mem::take(&mut collector.price_conflicts).into_values().collect()
+}
+
+/// Timestamps of one source corrected one way (see [`crate::clock`])
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimestampCorrection {
+    pub source: String,
+    pub kind: String,
+    pub count: u64,
+}
+
+/// Record that a `source` timestamp was corrected as `kind`
+pub fn record_timestamp_correction(source: &str, kind: &'static str) {
+    let Ok(mut collector) = COLLECTOR.lock() else { return };
+    *collector.timestamp_corrections.entry((source.to_string(), kind)).or_insert(0) += 1;
+}
+
+/// Timestamp corrections since the last call, clearing the counters
+pub fn take_timestamp_corrections() -> Vec<TimestampCorrection> {
+    drain_timestamp_corrections(|_| true)
+}
+
+/// Timestamp corrections for one source only (tests run in parallel)
+#[cfg(test)]
+pub fn take_timestamp_corrections_for(source: &str) -> Vec<TimestampCorrection> {
+    drain_timestamp_corrections(|key| key == source)
+}
+
+fn drain_timestamp_corrections(matches: impl Fn(&str) -> bool) -> Vec<TimestampCorrection> {
+    let Ok(mut collector) = COLLECTOR.lock() else { return Vec::new() };
+    let (taken, kept): (BTreeMap<_, _>, BTreeMap<_, _>) = std::mem::take(&mut collector.timestamp_corrections)
+        .into_iter()
+        .partition(|((source, _), _)| matches(source));
+    collector.timestamp_corrections = kept;
+
+    taken
+        .into_iter()
+        .map(|((source, kind), count)| TimestampCorrection { source, kind: kind.to_string(), count })
+        .collect()
+}
+
+/// Record that `field` in a `source` document held `found` instead of `expected`
+pub fn record_drift(source: &'static str, field: &str, expected: &'static str, found: &str) {
+    let key = DriftKey {
+        source,
+        field: field.to_string(),
+        expected,
+        found: found.to_string(),
+    };
+    if record_captured(|c| *c.drift.entry(key.clone()).or_insert(0) += 1) {
+        return;
+    }
+    let Ok(mut collector) = COLLECTOR.lock() else { return };
+
+    if collector.logged.insert((source, field.to_string())) {
+        eprintln!(
+            "[tokscale] {}: schema drift at '{}' (expected {}, found {})",
+            source, field, expected, found
+        );
+    }
+    *collector.drift.entry(key).or_insert(0) += 1;
+}
+
+/// Drift recorded since the last call, clearing the counters
+pub fn take_drift() -> Vec<SchemaDrift> {
+    drain(|_| true)
+}
+
+/// Drift recorded for one source only (tests run in parallel)
+#[cfg(test)]
+pub fn take_drift_for(source: &str) -> Vec<SchemaDrift> {
+    drain(|key| key.source == source)
+}
+
+/// Record that a `source` file was detected as `format`
+pub fn record_format(source: &'static str, format: &'static str) {
+    if record_captured(|c| *c.formats.entry((source, format)).or_insert(0) += 1) {
+        return;
+    }
+    let Ok(mut collector) = COLLECTOR.lock() else { return };
+    *collector.formats.entry((source, format)).or_insert(0) += 1;
+}
+
+/// Detected formats since the last call, clearing the counters
+pub fn take_formats() -> Vec<FormatCount> {
+    drain_formats(|_| true)
+}
+
+/// Detected formats for one source only (tests run in parallel)
+#[cfg(test)]
+pub fn take_formats_for(source: &str) -> Vec<FormatCount> {
+    drain_formats(|key| key == source)
+}
+
+fn drain_formats(matches: impl Fn(&str) -> bool) -> Vec<FormatCount> {
+    let Ok(mut collector) = COLLECTOR.lock() else { return Vec::new() };
+    drain_format_map(&mut collector.formats, matches)
+}
+
+fn drain_format_map(
+    formats: &mut BTreeMap<(&'static str, &'static str), u64>,
+    matches: impl Fn(&str) -> bool,
+) -> Vec<FormatCount> {
+    let (taken, kept): (BTreeMap<_, _>, BTreeMap<_, _>) = std::mem::take(formats)
+        .into_iter()
+        .partition(|((source, _), _)| matches(source));
+    *formats = kept;
+
+    taken
+        .into_iter()
+        .map(|((source, format), count)| FormatCount {
+            source: source.to_string(),
+            format: format.to_string(),
+            count,
+        })
+        .collect()
+}
+
+fn drain(matches: impl Fn(&DriftKey) -> bool) -> Vec<SchemaDrift> {
+    let Ok(mut collector) = COLLECTOR.lock() else { return Vec::new() };
+    drain_drift_map(&mut collector.drift, matches)
+}
+
+fn drain_drift_map(drift: &mut BTreeMap<DriftKey, u64>, matches: impl Fn(&DriftKey) -> bool) -> Vec<SchemaDrift> {
+    let (taken, kept): (BTreeMap<_, _>, BTreeMap<_, _>) = std::mem::take(drift)
+        .into_iter()
+        .partition(|(key, _)| matches(key));
+    *drift = kept;
+
+    taken
+        .into_iter()
+        .map(|(key, count)| SchemaDrift {
+            source: key.source.to_string(),
+            field: key.field,
+            expected: key.expected.to_string(),
+            found: key.found,
+            count,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_and_take_drift() {
+        record_drift("test-diag", "usage/input", "integer", "string");
+        record_drift("test-diag", "usage/input", "integer", "string");
+        record_drift("test-diag", "model", "string", "array");
+
+        let drift = take_drift_for("test-diag");
+        assert_eq!(drift.len(), 2);
+        assert_eq!(drift[0].field, "model");
+        assert_eq!(drift[1].field, "usage/input");
+        assert_eq!(drift[1].count, 2);
+
+        assert!(take_drift_for("test-diag").is_empty());
+    }
+
+    #[test]
+    fn test_record_and_take_formats() {
+        record_format("test-format", "v2");
+        record_format("test-format", "v2");
+        record_format("test-format", "v1");
+
+        let formats = take_formats_for("test-format");
+        assert_eq!(formats.len(), 2);
+        assert_eq!(formats[0].format, "v1");
+        assert_eq!(formats[1].count, 2);
+        assert!(take_formats_for("test-format").is_empty());
+    }
+
+    #[test]
+    fn test_capture_keeps_records_apart() {
+        let (value, drift, formats) = capture(|| {
+            record_drift("test-capture", "usage/output", "integer", "string");
+            record_format("test-capture", "v3");
+            7
+        });
+        assert_eq!(value, 7);
+        assert_eq!(drift.len(), 1);
+        assert_eq!(drift[0].field, "usage/output");
+        assert_eq!(formats[0].format, "v3");
+        assert!(take_drift_for("test-capture").is_empty());
+        assert!(take_formats_for("test-capture").is_empty());
+    }
+}
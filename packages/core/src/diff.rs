@@ -0,0 +1,290 @@
+//! Usage diff between two periods or two exported reports
+//!
+//! A month-end review asks what changed: did spend go up, which models took
+//! over, which projects grew. Each side is reduced to a [`Snapshot`] of
+//! totals, per-model and per-project usage, then compared entry by entry.
+//!
+//! Periods are a year (`2025`), a month (`2025-05`) or a day (`2025-05-03`),
+//! read from local session data. Exports are the JSON printed by
+//! `tokscale models --json` (model mix) or `tokscale projects --json`
+//! (per-project usage); a section is compared only when both sides have it.
+
+use crate::sessions::UnifiedMessage;
+use crate::{workspace, DiffEntry, DiffTotals, UsageDiff};
+use chrono::{Months, NaiveDate};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// Usage of one model or project on one side of a diff
+#[derive(Debug, Clone, PartialEq)]
+pub struct Item {
+    /// Model id, or project root
+    pub key: String,
+    pub name: String,
+    pub cost: f64,
+    pub tokens: i64,
+    pub messages: i32,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Snapshot {
+    pub cost: f64,
+    pub tokens: i64,
+    pub messages: i32,
+    pub models: Option<Vec<Item>>,
+    pub projects: Option<Vec<Item>>,
+}
+
+/// First and last day (YYYY-MM-DD) of a year, month or day
+pub fn period_range(period: &str) -> Option<(String, String)> {
+    let parts: Vec<&str> = period.split('-').collect();
+    let (first, last) = match parts.as_slice() {
+        [year] if year.len() == 4 => {
+            let year = year.parse().ok()?;
+            (NaiveDate::from_ymd_opt(year, 1, 1)?, NaiveDate::from_ymd_opt(year, 12, 31)?)
+        }
+        [year, month] if year.len() == 4 => {
+            let first = NaiveDate::from_ymd_opt(year.parse().ok()?, month.parse().ok()?, 1)?;
+            (first, first.checked_add_months(Months::new(1))?.pred_opt()?)
+        }
+        [_, _, _] => {
+            let day = NaiveDate::parse_from_str(period, "%Y-%m-%d").ok()?;
+            (day, day)
+        }
+        _ => return None,
+    };
+    Some((first.format("%Y-%m-%d").to_string(), last.format("%Y-%m-%d").to_string()))
+}
+
+fn message_tokens(msg: &UnifiedMessage) -> i64 {
+    msg.tokens.input + msg.tokens.output + msg.tokens.cache_read + msg.tokens.cache_write
+}
+
+/// Totals, model mix and projects for a set of messages
+pub fn snapshot(messages: &[UnifiedMessage]) -> Snapshot {
+    let mut models: BTreeMap<&str, Item> = BTreeMap::new();
+    for msg in messages {
+        let item = models.entry(&msg.model_id).or_insert_with(|| Item {
+            key: msg.model_id.clone(),
+            name: msg.model_id.clone(),
+            cost: 0.0,
+            tokens: 0,
+            messages: 0,
+        });
+        item.cost += msg.cost;
+        item.tokens += message_tokens(msg);
+        item.messages += 1;
+    }
+
+    let projects = workspace::project_report(messages, 0)
+        .entries
+        .into_iter()
+        .map(|entry| Item {
+            tokens: entry.input + entry.output + entry.cache_read + entry.cache_write,
+            key: entry.project,
+            name: entry.name,
+            cost: entry.cost,
+            messages: entry.message_count,
+        })
+        .collect();
+
+    Snapshot {
+        cost: messages.iter().map(|m| m.cost).sum(),
+        tokens: messages.iter().map(message_tokens).sum(),
+        messages: messages.len() as i32,
+        models: Some(models.into_values().collect()),
+        projects: Some(projects),
+    }
+}
+
+fn number(value: &Value, field: &str) -> f64 {
+    value.get(field).and_then(Value::as_f64).unwrap_or(0.0)
+}
+
+fn entry_tokens(entry: &Value) -> i64 {
+    ["input", "output", "cacheRead", "cacheWrite"]
+        .iter()
+        .map(|field| number(entry, field) as i64)
+        .sum()
+}
+
+/// Read a `models --json` or `projects --json` export
+pub fn snapshot_from_export(json: &str) -> Result<Snapshot, String> {
+    let value: Value = serde_json::from_str(json).map_err(|e| format!("Not valid JSON: {}", e))?;
+    let entries = value
+        .get("entries")
+        .and_then(Value::as_array)
+        .ok_or("Not a tokscale report export (no \"entries\")")?;
+
+    let has = |field: &str| entries.iter().all(|e| e.get(field).and_then(Value::as_str).is_some());
+    let key_field = if has("model") {
+        "model"
+    } else if has("project") {
+        "project"
+    } else {
+        return Err("Only 'tokscale models --json' and 'tokscale projects --json' exports can be compared".to_string());
+    };
+
+    // Model reports have one entry per (source, model); the diff is per model
+    let mut items: BTreeMap<String, Item> = BTreeMap::new();
+    for entry in entries {
+        let key = entry[key_field].as_str().unwrap_or_default().to_string();
+        let name = entry.get("name").and_then(Value::as_str).unwrap_or(&key).to_string();
+        let item = items.entry(key.clone()).or_insert_with(|| Item { key, name, cost: 0.0, tokens: 0, messages: 0 });
+        item.cost += number(entry, "cost");
+        item.tokens += entry_tokens(entry);
+        item.messages += number(entry, "messageCount") as i32;
+    }
+    let items: Vec<Item> = items.into_values().collect();
+
+    let messages = match value.get("totalMessages").and_then(Value::as_i64) {
+        Some(total) => total as i32,
+        None => items.iter().map(|i| i.messages).sum::<i32>() + number(&value, "unattributedMessages") as i32,
+    };
+    let mut snapshot = Snapshot {
+        cost: value
+            .get("totalCost")
+            .and_then(Value::as_f64)
+            .unwrap_or_else(|| items.iter().map(|i| i.cost).sum()),
+        tokens: items.iter().map(|i| i.tokens).sum(),
+        messages,
+        models: None,
+        projects: None,
+    };
+    if key_field == "model" {
+        snapshot.models = Some(items);
+    } else {
+        snapshot.projects = Some(items);
+    }
+    Ok(snapshot)
+}
+
+fn share(cost: f64, total: f64) -> f64 {
+    if total > 0.0 {
+        cost / total
+    } else {
+        0.0
+    }
+}
+
+/// Entries on either side, largest cost change first
+fn compare(from: &[Item], from_total: f64, to: &[Item], to_total: f64) -> Vec<DiffEntry> {
+    let mut keys: Vec<&str> = from.iter().chain(to).map(|i| i.key.as_str()).collect();
+    keys.sort_unstable();
+    keys.dedup();
+
+    let mut entries: Vec<DiffEntry> = keys
+        .into_iter()
+        .map(|key| {
+            let before = from.iter().find(|i| i.key == key);
+            let after = to.iter().find(|i| i.key == key);
+            let status = match (before, after) {
+                (None, _) => "added",
+                (_, None) => "removed",
+                (Some(a), Some(b)) if a.cost == b.cost && a.tokens == b.tokens => "unchanged",
+                _ => "changed",
+            };
+            let from_cost = before.map_or(0.0, |i| i.cost);
+            let to_cost = after.map_or(0.0, |i| i.cost);
+            DiffEntry {
+                key: key.to_string(),
+                name: after.or(before).map(|i| i.name.clone()).unwrap_or_default(),
+                from_cost,
+                to_cost,
+                cost_change: to_cost - from_cost,
+                from_share: share(from_cost, from_total),
+                to_share: share(to_cost, to_total),
+                from_tokens: before.map_or(0, |i| i.tokens),
+                to_tokens: after.map_or(0, |i| i.tokens),
+                from_messages: before.map_or(0, |i| i.messages),
+                to_messages: after.map_or(0, |i| i.messages),
+                status: status.to_string(),
+            }
+        })
+        .collect();
+    entries.sort_by(|a, b| {
+        b.cost_change
+            .abs()
+            .total_cmp(&a.cost_change.abs())
+            .then_with(|| a.key.cmp(&b.key))
+    });
+    entries
+}
+
+pub fn diff(from_label: String, from: &Snapshot, to_label: String, to: &Snapshot) -> UsageDiff {
+    let section = |a: &Option<Vec<Item>>, b: &Option<Vec<Item>>| match (a, b) {
+        (Some(a), Some(b)) => Some(compare(a, from.cost, b, to.cost)),
+        _ => None,
+    };
+    UsageDiff {
+        from_label,
+        to_label,
+        from: DiffTotals { cost: from.cost, tokens: from.tokens, messages: from.messages },
+        to: DiffTotals { cost: to.cost, tokens: to.tokens, messages: to.messages },
+        cost_change: to.cost - from.cost,
+        cost_change_percent: (from.cost > 0.0).then(|| (to.cost - from.cost) / from.cost * 100.0),
+        models: section(&from.models, &to.models),
+        projects: section(&from.projects, &to.projects),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{message, TestMessage};
+
+    #[test]
+    fn test_period_range() {
+        assert_eq!(period_range("2025-02"), Some(("2025-02-01".into(), "2025-02-28".into())));
+        assert_eq!(period_range("2024-12"), Some(("2024-12-01".into(), "2024-12-31".into())));
+        assert_eq!(period_range("2025"), Some(("2025-01-01".into(), "2025-12-31".into())));
+        assert_eq!(period_range("2025-06-03"), Some(("2025-06-03".into(), "2025-06-03".into())));
+        assert_eq!(period_range("2025-13"), None);
+        assert_eq!(period_range("last month"), None);
+    }
+
+    #[test]
+    fn test_diff_model_mix() {
+        let priced = |model: &str, cost: f64| message("s1", 0, cost).with_model(model).with_input(100).with_output(10);
+        let from = snapshot(&[priced("sonnet", 3.0), priced("opus", 1.0)]);
+        let to = snapshot(&[priced("sonnet", 3.0), priced("haiku", 0.5), priced("haiku", 0.5)]);
+        let diff = diff("2025-05".into(), &from, "2025-06".into(), &to);
+
+        assert_eq!(diff.cost_change, 0.0);
+        assert_eq!(diff.cost_change_percent, Some(0.0));
+        let models = diff.models.unwrap();
+        let status: Vec<(&str, &str)> = models.iter().map(|e| (e.key.as_str(), e.status.as_str())).collect();
+        assert_eq!(status, vec![("haiku", "added"), ("opus", "removed"), ("sonnet", "unchanged")]);
+        assert_eq!(models[0].to_messages, 2);
+        assert_eq!(models[0].to_tokens, 220);
+        assert_eq!(models[2].from_share, 0.75);
+        assert_eq!(models[2].to_share, 0.75);
+    }
+
+    #[test]
+    fn test_export_sections() {
+        let models = r#"{"schemaVersion":"1.0.0","entries":[
+            {"source":"claude","model":"sonnet","input":10,"output":5,"cacheRead":0,"cacheWrite":0,"messageCount":2,"cost":1.5},
+            {"source":"opencode","model":"sonnet","input":10,"output":5,"cacheRead":0,"cacheWrite":0,"messageCount":1,"cost":0.5}
+        ],"totalMessages":3,"totalCost":2.0}"#;
+        let projects = r#"{"entries":[{"project":"/src/app","name":"app","input":1,"output":1,"cacheRead":0,"cacheWrite":0,"messageCount":4,"cost":3.0}],
+            "unattributedMessages":1,"unattributedCost":1.0,"totalCost":4.0}"#;
+
+        let from = snapshot_from_export(models).unwrap();
+        assert_eq!(from.models.as_ref().unwrap().len(), 1);
+        assert_eq!(from.models.as_ref().unwrap()[0].messages, 3);
+        assert_eq!(from.tokens, 30);
+
+        let to = snapshot_from_export(projects).unwrap();
+        assert_eq!(to.messages, 5);
+        assert_eq!(to.projects.as_ref().unwrap()[0].name, "app");
+
+        // Different kinds of export only share their totals
+        let diff = diff("a".into(), &from, "b".into(), &to);
+        assert!(diff.models.is_none() && diff.projects.is_none());
+        assert_eq!(diff.cost_change, 2.0);
+
+        assert!(snapshot_from_export(r#"{"entries":[{"month":"2025-05"}]}"#).is_err());
+        assert!(snapshot_from_export("not json").is_err());
+    }
+}
@@ -0,0 +1,234 @@
+//! Time-series downsampling for charts
+//!
+//! A year of per-message data can run to hundreds of thousands of points while
+//! a chart only has a few hundred pixels to draw them in. Two methods:
+//!
+//! - LTTB (Largest-Triangle-Three-Buckets) keeps the points that best preserve
+//!   the visual shape of the line
+//! - Bucket max splits the time range into equal buckets and keeps the largest
+//!   point of each, so spikes are never smoothed away
+
+use crate::sessions::UnifiedMessage;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Point {
+    /// Unix milliseconds
+    pub timestamp: i64,
+    pub value: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Method {
+    Lttb,
+    BucketMax,
+}
+
+impl Method {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "lttb" => Some(Method::Lttb),
+            "max" => Some(Method::BucketMax),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Method::Lttb => "lttb",
+            Method::BucketMax => "max",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Metric {
+    Tokens,
+    Cost,
+}
+
+impl Metric {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "tokens" => Some(Metric::Tokens),
+            "cost" => Some(Metric::Cost),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Metric::Tokens => "tokens",
+            Metric::Cost => "cost",
+        }
+    }
+}
+
+/// One point per message, sorted by timestamp
+pub fn message_points(messages: &[UnifiedMessage], metric: Metric) -> Vec<Point> {
+    let mut points: Vec<Point> = messages
+        .iter()
+        .map(|msg| {
+            let value = match metric {
+                Metric::Tokens => msg
+                    .tokens
+                    .input
+                    .saturating_add(msg.tokens.output)
+                    .saturating_add(msg.tokens.cache_read)
+                    .saturating_add(msg.tokens.cache_write)
+                    .saturating_add(msg.tokens.reasoning)
+                    .saturating_add(msg.tokens.training) as f64,
+                Metric::Cost => msg.cost,
+            };
+            Point { timestamp: msg.timestamp, value }
+        })
+        .collect();
+    points.sort_by_key(|p| p.timestamp);
+    points
+}
+
+/// Reduce time-sorted `points` to at most `target` points
+pub fn downsample(points: &[Point], target: usize, method: Method) -> Vec<Point> {
+    if target == 0 || points.len() <= target {
+        return points.to_vec();
+    }
+    match method {
+        // LTTB keeps the first and last points and at least one between
+        Method::Lttb if points.len() <= 3 => points.to_vec(),
+        Method::Lttb => lttb(points, target.max(3)),
+        Method::BucketMax => bucket_max(points, target),
+    }
+}
+
+fn lttb(points: &[Point], target: usize) -> Vec<Point> {
+    let len = points.len();
+    // First and last points are always kept; the rest is split into buckets
+    let every = (len - 2) as f64 / (target - 2) as f64;
+
+    let mut sampled = Vec::with_capacity(target);
+    sampled.push(points[0]);
+    let mut a = 0;
+
+    for i in 0..target - 2 {
+        // Average of the next bucket is the third triangle vertex
+        let next_start = ((i + 1) as f64 * every) as usize + 1;
+        let next_end = (((i + 2) as f64 * every) as usize + 1).min(len);
+        let next = &points[next_start..next_end];
+        let avg_x = next.iter().map(|p| p.timestamp as f64).sum::<f64>() / next.len() as f64;
+        let avg_y = next.iter().map(|p| p.value).sum::<f64>() / next.len() as f64;
+
+        let start = (i as f64 * every) as usize + 1;
+        let end = next_start;
+        let (ax, ay) = (points[a].timestamp as f64, points[a].value);
+
+        let mut best = start;
+        let mut best_area = -1.0;
+        for (j, p) in points[start..end].iter().enumerate() {
+            let area = ((ax - avg_x) * (p.value - ay) - (ax - p.timestamp as f64) * (avg_y - ay)).abs();
+            if area > best_area {
+                best_area = area;
+                best = start + j;
+            }
+        }
+
+        sampled.push(points[best]);
+        a = best;
+    }
+
+    sampled.push(points[len - 1]);
+    sampled
+}
+
+fn bucket_max(points: &[Point], target: usize) -> Vec<Point> {
+    let first = points[0].timestamp;
+    let span = (points[points.len() - 1].timestamp - first) as f64 + 1.0;
+
+    let mut buckets: Vec<Option<Point>> = vec![None; target];
+    for p in points {
+        let index = (((p.timestamp - first) as f64 / span) * target as f64) as usize;
+        let slot = &mut buckets[index.min(target - 1)];
+        if slot.is_none_or(|best| p.value > best.value) {
+            *slot = Some(*p);
+        }
+    }
+
+    buckets.into_iter().flatten().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn series(values: &[f64]) -> Vec<Point> {
+        values
+            .iter()
+            .enumerate()
+            .map(|(i, &value)| Point { timestamp: i as i64 * 1000, value })
+            .collect()
+    }
+
+    #[test]
+    fn test_small_series_unchanged() {
+        let points = series(&[1.0, 2.0, 3.0]);
+        assert_eq!(downsample(&points, 10, Method::Lttb), points);
+        assert_eq!(downsample(&points, 3, Method::BucketMax), points);
+        assert_eq!(downsample(&points, 0, Method::Lttb), points);
+        assert_eq!(downsample(&points[..2], 1, Method::Lttb), points[..2]);
+    }
+
+    #[test]
+    fn test_lttb_keeps_endpoints_and_spike() {
+        let mut values = vec![1.0; 1000];
+        values[500] = 100.0;
+        let points = series(&values);
+
+        let sampled = downsample(&points, 20, Method::Lttb);
+        assert_eq!(sampled.len(), 20);
+        assert_eq!(sampled[0], points[0]);
+        assert_eq!(sampled[19], points[999]);
+        assert!(sampled.iter().any(|p| p.value == 100.0));
+        assert!(sampled.windows(2).all(|w| w[0].timestamp < w[1].timestamp));
+    }
+
+    #[test]
+    fn test_bucket_max() {
+        let points = series(&[1.0, 5.0, 2.0, 3.0, 9.0, 4.0, 0.0, 7.0]);
+        let sampled = downsample(&points, 4, Method::BucketMax);
+        let values: Vec<f64> = sampled.iter().map(|p| p.value).collect();
+        assert_eq!(values, vec![5.0, 3.0, 9.0, 7.0]);
+        assert_eq!(sampled[2].timestamp, 4000);
+    }
+
+    #[test]
+    fn test_bucket_max_skips_empty_buckets() {
+        let points = vec![
+            Point { timestamp: 0, value: 1.0 },
+            Point { timestamp: 1, value: 2.0 },
+            Point { timestamp: 1_000_000, value: 3.0 },
+        ];
+        let sampled = downsample(&points, 2, Method::BucketMax);
+        assert_eq!(sampled.len(), 2);
+        assert_eq!(sampled[0].value, 2.0);
+        assert_eq!(sampled[1].value, 3.0);
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn prop_downsample_bounded_and_ordered(
+            samples in proptest::collection::vec((0..1_000_000i64, 0..10_000u32), 0..500),
+            target in 0..50usize,
+        ) {
+            let mut points: Vec<Point> =
+                samples.into_iter().map(|(timestamp, value)| Point { timestamp, value: value as f64 }).collect();
+            points.sort_by_key(|p| p.timestamp);
+
+            for method in [Method::Lttb, Method::BucketMax] {
+                let sampled = downsample(&points, target, method);
+                if target > 0 {
+                    proptest::prop_assert!(sampled.len() <= points.len().min(target.max(3)));
+                }
+                proptest::prop_assert!(sampled.windows(2).all(|w| w[0].timestamp <= w[1].timestamp));
+                proptest::prop_assert!(sampled.iter().all(|p| points.contains(p)));
+            }
+        }
+    }
+}
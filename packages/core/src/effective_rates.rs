@@ -0,0 +1,192 @@
+//! Effective price per million tokens
+//!
+//! List prices say what one token costs. What a model actually cost over a
+//! period also depends on how much input was served from cache and how much
+//! output was reasoning, so dividing its cost by its tokens gives a different
+//! (usually lower) rate. That realized rate is what budgets and invoices see.
+
+use crate::pricing::rates::TOKENS_PER_MILLION;
+use crate::pricing::PricingService;
+use crate::sessions::UnifiedMessage;
+use crate::{EffectiveRate, EffectiveRateReport, TokenBreakdown};
+use std::collections::HashMap;
+
+#[derive(Default)]
+struct Totals {
+    sources: Vec<String>,
+    input_tokens: i64,
+    output_tokens: i64,
+    cache_read: i64,
+    training: i64,
+    cost: f64,
+    input_cost: f64,
+    output_cost: f64,
+    /// Cost that couldn't be split by direction (no price for the model)
+    unsplit_cost: f64,
+    message_count: i32,
+}
+
+/// Realized rates per (provider, model), most expensive first.
+///
+/// A message's cost is split between input (input and cache tokens) and
+/// output (output and reasoning tokens) in proportion to what each side
+/// costs at list price. Models without a price keep their overall rate but
+/// get no per-direction rates.
+pub fn build(messages: &[UnifiedMessage], pricing: Option<&PricingService>) -> Vec<EffectiveRate> {
+    let mut models: HashMap<(&str, &str), Totals> = HashMap::new();
+
+    for msg in messages {
+        let totals = models.entry((msg.provider_id.as_str(), msg.model_id.as_str())).or_default();
+        let source = msg.source.to_string();
+        if !totals.sources.contains(&source) {
+            totals.sources.push(source);
+        }
+        let t = &msg.tokens;
+        totals.input_tokens += t.input + t.cache_read + t.cache_write;
+        totals.output_tokens += t.output + t.reasoning;
+        totals.cache_read += t.cache_read;
+        totals.training += t.training;
+        totals.cost += msg.cost;
+        totals.message_count += 1;
+
+        match pricing.and_then(|pricing| split_cost(msg, pricing)) {
+            Some((input, output)) => {
+                totals.input_cost += input;
+                totals.output_cost += output;
+            }
+            None => totals.unsplit_cost += msg.cost,
+        }
+    }
+
+    let mut entries: Vec<EffectiveRate> = models
+        .into_iter()
+        .map(|((provider, model), totals)| {
+            let list = pricing.and_then(|pricing| pricing.lookup_with_provider(model, Some(provider)));
+            let split = totals.unsplit_cost == 0.0;
+            EffectiveRate {
+                model: model.to_string(),
+                provider: provider.to_string(),
+                sources: totals.sources,
+                input_tokens: totals.input_tokens,
+                output_tokens: totals.output_tokens,
+                training_tokens: totals.training,
+                cache_read_share: ratio(totals.cache_read as f64, totals.input_tokens),
+                cost: totals.cost,
+                effective_per_million: per_million(totals.cost, totals.input_tokens + totals.output_tokens + totals.training)
+                    .unwrap_or(0.0),
+                input_per_million: per_million(totals.input_cost, totals.input_tokens).filter(|_| split),
+                output_per_million: per_million(totals.output_cost, totals.output_tokens).filter(|_| split),
+                list_input_per_million: list.as_ref().and_then(|l| l.pricing.input_per_million()),
+                list_output_per_million: list.as_ref().and_then(|l| l.pricing.output_per_million()),
+                message_count: totals.message_count,
+            }
+        })
+        .collect();
+
+    entries.sort_by(|a, b| b.cost.total_cmp(&a.cost).then_with(|| a.model.cmp(&b.model)));
+    entries
+}
+
+/// Summary over `entries`
+pub fn report(entries: Vec<EffectiveRate>, processing_time_ms: u32) -> EffectiveRateReport {
+    let total_tokens: i64 = entries.iter().map(|e| e.input_tokens + e.output_tokens + e.training_tokens).sum();
+    let total_cost: f64 = entries.iter().map(|e| e.cost).sum();
+    EffectiveRateReport {
+        effective_per_million: per_million(total_cost, total_tokens).unwrap_or(0.0),
+        entries,
+        total_tokens,
+        total_cost,
+        processing_time_ms,
+    }
+}
+
+/// `msg.cost` divided into (input, output) by list-price share. Training
+/// cost belongs to neither side.
+fn split_cost(msg: &UnifiedMessage, pricing: &PricingService) -> Option<(f64, f64)> {
+    let t = &msg.tokens;
+    let price = |tokens: TokenBreakdown| pricing.calculate_token_cost(&msg.model_id, Some(msg.provider_id.as_str()), &tokens);
+    let input = price(TokenBreakdown { input: t.input, cache_read: t.cache_read, cache_write: t.cache_write, ..Default::default() });
+    let output = price(TokenBreakdown { output: t.output, reasoning: t.reasoning, ..Default::default() });
+    let training = price(TokenBreakdown { training: t.training, ..Default::default() });
+
+    let listed = input + output + training;
+    if listed <= 0.0 {
+        // Free or unpriced; a free message splits trivially
+        return (msg.cost == 0.0).then_some((0.0, 0.0));
+    }
+    let scale = msg.cost / listed;
+    Some((input * scale, output * scale))
+}
+
+fn per_million(cost: f64, tokens: i64) -> Option<f64> {
+    (tokens > 0).then(|| cost / tokens as f64 * TOKENS_PER_MILLION)
+}
+
+fn ratio(part: f64, whole: i64) -> f64 {
+    if whole > 0 {
+        part / whole as f64
+    } else {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pricing::ModelPricing;
+    use crate::testutil::{message, TestMessage};
+
+    fn service() -> PricingService {
+        let sonnet = ModelPricing {
+            input_cost_per_token: Some(3e-6),
+            output_cost_per_token: Some(1.5e-5),
+            cache_read_input_token_cost: Some(3e-7),
+            ..Default::default()
+        };
+        PricingService::new(HashMap::from([("claude-sonnet-4".to_string(), sonnet)]), HashMap::new())
+    }
+
+    /// A message priced at `pricing`'s list rates
+    fn priced(model: &str, tokens: TokenBreakdown, pricing: &PricingService) -> UnifiedMessage {
+        let cost = pricing.calculate_token_cost(model, Some("anthropic"), &tokens);
+        message("s1", 1_740_823_200_000, cost).with_model(model).with_tokens(tokens)
+    }
+
+    #[test]
+    fn test_cache_reads_lower_the_effective_input_rate() {
+        let pricing = service();
+        let messages = vec![
+            priced("claude-sonnet-4", TokenBreakdown { input: 100_000, output: 10_000, ..Default::default() }, &pricing),
+            priced("claude-sonnet-4", TokenBreakdown { cache_read: 900_000, output: 10_000, ..Default::default() }, &pricing),
+        ];
+
+        let rates = build(&messages, Some(&pricing));
+        assert_eq!(rates.len(), 1);
+        let sonnet = &rates[0];
+        assert_eq!(sonnet.input_tokens, 1_000_000);
+        assert_eq!(sonnet.output_tokens, 20_000);
+        assert!((sonnet.cache_read_share - 0.9).abs() < 1e-9);
+        // 0.30 for fresh input + 0.27 for cache reads over 1M input tokens
+        assert!((sonnet.input_per_million.unwrap() - 0.57).abs() < 1e-9);
+        assert!((sonnet.output_per_million.unwrap() - 15.0).abs() < 1e-9);
+        assert!((sonnet.list_input_per_million.unwrap() - 3.0).abs() < 1e-9);
+        // 0.57 + 0.30 over 1.02M tokens
+        assert!((sonnet.effective_per_million - 0.87 / 1.02).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_unpriced_model_has_no_direction_split() {
+        let pricing = service();
+        let mut msg = priced("mystery-model", TokenBreakdown { input: 1_000, output: 1_000, ..Default::default() }, &pricing);
+        msg.cost = 0.02;
+
+        let rates = build(&[msg], Some(&pricing));
+        assert!((rates[0].effective_per_million - 10.0).abs() < 1e-9);
+        assert_eq!(rates[0].input_per_million, None);
+        assert_eq!(rates[0].list_input_per_million, None);
+
+        let summary = report(rates, 0);
+        assert_eq!(summary.total_tokens, 2_000);
+        assert!((summary.effective_per_million - 10.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,202 @@
+//! Cost efficiency across sources
+//!
+//! Ranks the tools against each other by what an hour or a session of work
+//! in them costs, to help decide which subscription is worth paying for.
+//! Time is active time (see [`crate::activity`]), so a terminal left open
+//! doesn't make a tool look cheap.
+//!
+//! Tools don't see the same work, so only comparable sessions count: ones
+//! with at least `min_messages` messages and `min_active_minutes` of active
+//! time, which leaves out one-off questions and aborted starts. A model
+//! family (see [`crate::families`]) narrows the comparison further, to
+//! sessions run on the same kind of model. Sessions left out are counted
+//! per source.
+
+use crate::activity;
+use crate::pricing::normalize::NormalizeRules;
+use crate::session_tags::TagThresholds;
+use crate::sessions::UnifiedMessage;
+use crate::{EfficiencyReport, SourceEfficiency};
+use std::collections::BTreeMap;
+
+pub const DEFAULT_MIN_MESSAGES: u32 = 3;
+pub const DEFAULT_MIN_ACTIVE_MINUTES: f64 = 5.0;
+
+const MS_PER_MINUTE: f64 = 60_000.0;
+const MS_PER_HOUR: f64 = 3_600_000.0;
+const TOKENS_PER_MILLION: f64 = 1_000_000.0;
+
+/// Which sessions are comparable
+#[derive(Debug, Clone, PartialEq)]
+pub struct Workload {
+    pub min_messages: u32,
+    pub min_active_minutes: f64,
+    /// Only messages of models in this family
+    pub family: Option<String>,
+}
+
+impl Default for Workload {
+    fn default() -> Self {
+        Self { min_messages: DEFAULT_MIN_MESSAGES, min_active_minutes: DEFAULT_MIN_ACTIVE_MINUTES, family: None }
+    }
+}
+
+fn median(values: &mut [f64]) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    values.sort_by(f64::total_cmp);
+    let mid = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        (values[mid - 1] + values[mid]) / 2.0
+    } else {
+        values[mid]
+    }
+}
+
+fn per(value: f64, over: f64) -> f64 {
+    if over > 0.0 {
+        value / over
+    } else {
+        0.0
+    }
+}
+
+/// Sources ranked by cost per active hour, cheapest first. Sources without
+/// a comparable session are listed last, unranked.
+pub fn leaderboard(
+    messages: &[UnifiedMessage],
+    idle_minutes: f64,
+    workload: &Workload,
+    rules: &NormalizeRules,
+) -> Vec<SourceEfficiency> {
+    let in_family = |msg: &&UnifiedMessage| match &workload.family {
+        Some(family) => rules.family(&msg.model_id).is_some_and(|(_, f)| f.eq_ignore_ascii_case(family)),
+        None => true,
+    };
+    let messages: Vec<UnifiedMessage> = messages.iter().filter(in_family).cloned().collect();
+    let sessions = activity::sessions(&messages, idle_minutes, &TagThresholds::default());
+
+    let min_active_ms = (workload.min_active_minutes * MS_PER_MINUTE) as i64;
+    let mut by_source: BTreeMap<String, SourceEfficiency> = BTreeMap::new();
+    let mut session_costs: BTreeMap<String, Vec<f64>> = BTreeMap::new();
+    for session in &sessions {
+        let entry = by_source.entry(session.source.clone()).or_insert_with(|| empty_entry(&session.source));
+        if session.message_count < workload.min_messages as i32 || session.active_ms < min_active_ms {
+            entry.excluded_sessions += 1;
+            continue;
+        }
+        entry.sessions += 1;
+        entry.active_hours += session.active_ms as f64 / MS_PER_HOUR;
+        entry.cost += session.cost;
+        entry.tokens += session.tokens;
+        session_costs.entry(session.source.clone()).or_default().push(session.cost);
+    }
+
+    let mut entries: Vec<SourceEfficiency> = by_source.into_values().collect();
+    for entry in &mut entries {
+        entry.cost_per_session = per(entry.cost, entry.sessions as f64);
+        entry.median_session_cost = median(&mut session_costs.remove(&entry.source).unwrap_or_default());
+        entry.cost_per_active_hour = per(entry.cost, entry.active_hours);
+        entry.cost_per_million_tokens = per(entry.cost, entry.tokens as f64 / TOKENS_PER_MILLION);
+    }
+    entries.sort_by(|a, b| {
+        (a.sessions == 0)
+            .cmp(&(b.sessions == 0))
+            .then_with(|| a.cost_per_active_hour.total_cmp(&b.cost_per_active_hour))
+            .then_with(|| a.source.cmp(&b.source))
+    });
+    for (index, entry) in entries.iter_mut().enumerate() {
+        entry.rank = if entry.sessions > 0 { Some(index as u32 + 1) } else { None };
+    }
+    entries
+}
+
+fn empty_entry(source: &str) -> SourceEfficiency {
+    SourceEfficiency {
+        source: source.to_string(),
+        rank: None,
+        sessions: 0,
+        excluded_sessions: 0,
+        active_hours: 0.0,
+        cost: 0.0,
+        tokens: 0,
+        cost_per_session: 0.0,
+        median_session_cost: 0.0,
+        cost_per_active_hour: 0.0,
+        cost_per_million_tokens: 0.0,
+    }
+}
+
+pub fn report(
+    entries: Vec<SourceEfficiency>,
+    idle_minutes: f64,
+    workload: Workload,
+    processing_time_ms: u32,
+) -> EfficiencyReport {
+    EfficiencyReport {
+        entries,
+        idle_threshold_minutes: idle_minutes,
+        min_messages: workload.min_messages,
+        min_active_minutes: workload.min_active_minutes,
+        family: workload.family,
+        processing_time_ms,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{message, TestMessage};
+
+    const MINUTE: i64 = 60_000;
+    const T0: i64 = 1_740_823_200_000;
+
+    /// A session of `count` messages a minute apart, costing `cost` each
+    fn session(source: &str, model: &str, id: &str, count: i64, cost: f64) -> Vec<UnifiedMessage> {
+        (0..count)
+            .map(|i| message(id, T0 + i * MINUTE, cost).with_source(source).with_model(model))
+            .collect()
+    }
+
+    #[test]
+    fn test_ranked_by_cost_per_active_hour() {
+        let messages = [
+            // 30 minutes at $0.10 a message: $3.10, $6.20 an hour
+            session("claude", "claude-sonnet-4", "c1", 31, 0.1),
+            // 10 minutes at $0.20 a message: $2.20, $13.20 an hour
+            session("amp", "claude-sonnet-4", "a1", 11, 0.2),
+            // A one-off question doesn't count
+            session("amp", "claude-sonnet-4", "a2", 1, 5.0),
+            session("codex", "gpt-5", "x1", 2, 1.0),
+        ]
+        .concat();
+        let entries = leaderboard(&messages, 30.0, &Workload::default(), &NormalizeRules::bundled());
+
+        let ranks: Vec<(&str, Option<u32>)> = entries.iter().map(|e| (e.source.as_str(), e.rank)).collect();
+        assert_eq!(ranks, [("claude", Some(1)), ("amp", Some(2)), ("codex", None)]);
+        assert!((entries[0].cost_per_active_hour - 6.2).abs() < 1e-9);
+        assert_eq!((entries[1].sessions, entries[1].excluded_sessions), (1, 1));
+        assert!((entries[1].cost_per_session - 2.2).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_family_narrows_comparison() {
+        let messages = [
+            session("claude", "claude-sonnet-4", "c1", 10, 0.1),
+            session("codex", "gpt-5", "x1", 10, 0.1),
+        ]
+        .concat();
+        let workload = Workload { family: Some("claude-sonnet".into()), ..Workload::default() };
+        let entries = leaderboard(&messages, 30.0, &workload, &NormalizeRules::bundled());
+        let sources: Vec<&str> = entries.iter().map(|e| e.source.as_str()).collect();
+        assert_eq!(sources, ["claude"]);
+    }
+
+    #[test]
+    fn test_median() {
+        assert_eq!(median(&mut [3.0, 1.0, 2.0]), 2.0);
+        assert_eq!(median(&mut [4.0, 1.0]), 2.5);
+        assert_eq!(median(&mut []), 0.0);
+    }
+}
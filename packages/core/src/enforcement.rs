@@ -0,0 +1,305 @@
+//! Spend cap enforcement
+//!
+//! A budget with `enforce` is a hard cap: once its spend reaches the amount,
+//! tokscale runs that command, for example a script that disables an API key
+//! or stops a gateway:
+//!
+//! ```json
+//! "budgets": [{ "name": "agents", "period": "day", "amount": 40, "enforce": "~/bin/revoke-key.sh agents" }]
+//! ```
+//!
+//! The command runs through the shell (`sh -c`, or `cmd /C` on Windows) once
+//! per period, with the budget status as JSON on stdin and in the
+//! `TOKSCALE_BUDGET*` environment variables, and is killed after
+//! [`HOOK_TIMEOUT`], along with the processes it started on Unix (the hook
+//! runs in a process group of its own). Output is only waited for until
+//! then, so a background process keeping the hook's pipes open can't hold
+//! up the check. Each run and its outcome is appended to
+//! `~/.config/tokscale/enforcements.jsonl`; a budget with a run for its
+//! current period there is not enforced again, even if the run failed, so a
+//! broken script isn't retried on every check.
+//!
+//! Caps are checked by `tokscale budgets --enforce` and, while it runs, on
+//! every webhook check of `tokscale serve`.
+
+use crate::budget::{self, Budget};
+use crate::sessions::UnifiedMessage;
+use crate::{BudgetStatus, EnforcementEvent};
+use chrono::{DateTime, Utc};
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Time a hook gets before it is killed
+pub const HOOK_TIMEOUT: Duration = Duration::from_secs(60);
+/// Time output gets to arrive once the hook exits or is killed
+const OUTPUT_GRACE: Duration = Duration::from_secs(2);
+const LOG_FILE: &str = "enforcements.jsonl";
+/// Characters of hook output kept in the log
+const OUTPUT_LIMIT: usize = 2000;
+
+pub fn log_path(home_dir: &str) -> PathBuf {
+    Path::new(home_dir).join(".config").join("tokscale").join(LOG_FILE)
+}
+
+/// Every recorded run, oldest first. Unreadable lines are skipped.
+pub fn read_log(path: &Path) -> Result<Vec<EnforcementEvent>, String> {
+    match fs::read_to_string(path) {
+        Ok(content) => Ok(content.lines().filter_map(|line| serde_json::from_str(line).ok()).collect()),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(format!("Failed to read {}: {}", path.display(), e)),
+    }
+}
+
+fn append(path: &Path, event: &EnforcementEvent) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    let line = serde_json::to_string(event).map_err(|e| e.to_string())?;
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut file| writeln!(file, "{}", line))
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// Run the hook of every capped budget that is exceeded and not yet enforced
+/// this period, recording each run in the log at `path`. Returns the runs.
+pub fn enforce(
+    budgets: &[Budget],
+    messages: &[UnifiedMessage],
+    now: DateTime<Utc>,
+    path: &Path,
+) -> Result<Vec<EnforcementEvent>, String> {
+    let mut log = read_log(path)?;
+    let mut events = Vec::new();
+    for cap in budgets.iter().filter(|b| b.enforce.is_some() && b.validate().is_ok()) {
+        let status = budget::status(cap, messages, now);
+        let enforced = log.iter().any(|e| e.budget == status.name && e.period_start == status.period_start);
+        if status.state != budget::EXCEEDED || enforced {
+            continue;
+        }
+        let event = run_hook(cap.enforce.as_deref().unwrap_or_default(), &status, HOOK_TIMEOUT);
+        append(path, &event)?;
+        log.push(event.clone());
+        events.push(event);
+    }
+    Ok(events)
+}
+
+fn shell(command: &str) -> Command {
+    if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.args(["/C", command]);
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.args(["-c", command]);
+        #[cfg(unix)]
+        std::os::unix::process::CommandExt::process_group(&mut shell, 0);
+        shell
+    }
+}
+
+/// Kill the hook, and on Unix every process in its group
+fn kill(child: &mut Child) {
+    #[cfg(unix)]
+    if let Ok(pid) = libc::pid_t::try_from(child.id()) {
+        // SAFETY: kill has no memory effects; the group is the hook's own
+        unsafe { libc::kill(-pid, libc::SIGKILL) };
+    }
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+/// Read `pipe` on a thread of its own, sending what arrives
+fn capture<R: Read + Send + 'static>(pipe: Option<R>) -> Receiver<Vec<u8>> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let Some(mut pipe) = pipe else {
+            return;
+        };
+        let mut buf = [0; 8192];
+        while let Ok(read @ 1..) = pipe.read(&mut buf) {
+            if sender.send(buf[..read].to_vec()).is_err() {
+                break;
+            }
+        }
+    });
+    receiver
+}
+
+/// Output that arrived before the pipe closed or `until`, whichever is first
+fn collect(output: &Receiver<Vec<u8>>, until: Instant) -> Vec<u8> {
+    let mut buf = Vec::new();
+    while let Ok(chunk) = output.recv_timeout(until.saturating_duration_since(Instant::now())) {
+        buf.extend(chunk);
+    }
+    buf
+}
+
+/// Last [`OUTPUT_LIMIT`] characters of stdout and stderr
+fn tail(stdout: &[u8], stderr: &[u8]) -> String {
+    let text = format!("{}{}", String::from_utf8_lossy(stdout), String::from_utf8_lossy(stderr));
+    let text = text.trim();
+    let skip = text.chars().count().saturating_sub(OUTPUT_LIMIT);
+    text.chars().skip(skip).collect()
+}
+
+/// Run `command` for `status`, waiting at most `timeout`
+pub fn run_hook(command: &str, status: &BudgetStatus, timeout: Duration) -> EnforcementEvent {
+    let mut event = EnforcementEvent {
+        budget: status.name.clone(),
+        period_start: status.period_start.clone(),
+        spent: status.spent,
+        amount: status.amount,
+        command: command.to_string(),
+        ran_at: Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+        exit_code: None,
+        output: String::new(),
+        error: None,
+    };
+
+    let spawned = shell(command)
+        .env("TOKSCALE_BUDGET", &status.name)
+        .env("TOKSCALE_BUDGET_PERIOD", &status.period)
+        .env("TOKSCALE_BUDGET_PERIOD_START", &status.period_start)
+        .env("TOKSCALE_BUDGET_AMOUNT", status.amount.to_string())
+        .env("TOKSCALE_BUDGET_SPENT", status.spent.to_string())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn();
+    let mut child = match spawned {
+        Ok(child) => child,
+        Err(e) => {
+            event.error = Some(format!("Failed to run hook: {}", e));
+            return event;
+        }
+    };
+    let stdout = capture(child.stdout.take());
+    let stderr = capture(child.stderr.take());
+    if let Some(mut stdin) = child.stdin.take() {
+        // A hook that doesn't read stdin closes it early; that's fine
+        let _ = serde_json::to_writer(&mut stdin, status);
+    }
+
+    let deadline = Instant::now() + timeout;
+    let exit = loop {
+        match child.try_wait() {
+            Ok(Some(exit)) => break Ok(exit),
+            Ok(None) if Instant::now() >= deadline => {
+                kill(&mut child);
+                break Err(format!("Hook timed out after {}s", timeout.as_secs()));
+            }
+            Ok(None) => thread::sleep(Duration::from_millis(50)),
+            Err(e) => {
+                kill(&mut child);
+                break Err(format!("Failed to wait for hook: {}", e));
+            }
+        }
+    };
+    // Processes the hook started may still hold the pipes open
+    let until = Instant::now() + OUTPUT_GRACE;
+    event.output = tail(&collect(&stdout, until), &collect(&stderr, until));
+    match exit {
+        Ok(exit) => {
+            event.exit_code = exit.code();
+            if !exit.success() {
+                event.error = Some(match exit.code() {
+                    Some(code) => format!("Hook exited with code {}", code),
+                    None => "Hook was killed by a signal".to_string(),
+                });
+            }
+        }
+        Err(e) => event.error = Some(e),
+    }
+    event
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::budget::BudgetPeriod;
+    use crate::testutil::message;
+    use chrono::TimeZone;
+
+    fn cap(name: &str, amount: f64, command: &str) -> Budget {
+        Budget {
+            name: Some(name.to_string()),
+            period: BudgetPeriod::Day,
+            amount,
+            enforce: Some(command.to_string()),
+            ..Budget::default()
+        }
+    }
+
+    #[test]
+    fn test_enforces_once_per_period() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(LOG_FILE);
+        let marker = dir.path().join("ran");
+        let command = format!("echo \"$TOKSCALE_BUDGET $TOKSCALE_BUDGET_SPENT\" >> '{}'; cat", marker.display());
+        let budgets = vec![cap("agents", 10.0, &command), cap("team", 100.0, &command), Budget::default()];
+        let now = Utc.with_ymd_and_hms(2025, 5, 10, 12, 0, 0).unwrap();
+        let messages = vec![message("s1", (now - chrono::Duration::hours(2)).timestamp_millis(), 12.5)];
+
+        let events = enforce(&budgets, &messages, now, &path).unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!((events[0].budget.as_str(), events[0].period_start.as_str()), ("agents", "2025-05-10"));
+        assert_eq!(events[0].exit_code, Some(0));
+        assert!(events[0].error.is_none());
+        // The status came on stdin
+        assert!(events[0].output.contains("\"state\":\"exceeded\""), "{}", events[0].output);
+        assert_eq!(fs::read_to_string(&marker).unwrap(), "agents 12.5\n");
+
+        // Already enforced today; the log is read back
+        assert!(enforce(&budgets, &messages, now, &path).unwrap().is_empty());
+        assert_eq!(read_log(&path).unwrap().len(), 1);
+
+        // A new period enforces again
+        let tomorrow = now + chrono::Duration::days(1);
+        let messages = vec![message("s1", tomorrow.timestamp_millis(), 20.0)];
+        assert_eq!(enforce(&budgets, &messages, tomorrow, &path).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_failed_and_slow_hooks() {
+        let now = Utc.with_ymd_and_hms(2025, 5, 10, 12, 0, 0).unwrap();
+        let status = budget::status(&cap("agents", 10.0, ""), &[message("s1", now.timestamp_millis(), 20.0)], now);
+
+        let failed = run_hook("echo nope >&2; exit 3", &status, HOOK_TIMEOUT);
+        assert_eq!(failed.exit_code, Some(3));
+        assert_eq!(failed.output, "nope");
+        assert_eq!(failed.error.as_deref(), Some("Hook exited with code 3"));
+
+        let slow = run_hook("echo started; sleep 5", &status, Duration::from_millis(200));
+        assert_eq!(slow.exit_code, None);
+        assert!(slow.error.unwrap().contains("timed out"));
+        assert_eq!(slow.output, "started");
+    }
+
+    #[test]
+    fn test_background_processes_dont_hold_up_hooks() {
+        let now = Utc.with_ymd_and_hms(2025, 5, 10, 12, 0, 0).unwrap();
+        let status = budget::status(&cap("agents", 10.0, ""), &[message("s1", now.timestamp_millis(), 20.0)], now);
+
+        // The background sleep keeps stdout open after the hook exits
+        let start = Instant::now();
+        let detached = run_hook("sleep 30 & echo done", &status, HOOK_TIMEOUT);
+        assert_eq!(detached.exit_code, Some(0));
+        assert_eq!(detached.output, "done");
+        assert!(start.elapsed() < Duration::from_secs(10));
+
+        // A timed-out hook's children go with it, closing the pipes
+        let start = Instant::now();
+        let slow = run_hook("sleep 30 & sleep 30", &status, Duration::from_millis(200));
+        assert!(slow.error.unwrap().contains("timed out"));
+        assert!(start.elapsed() < OUTPUT_GRACE);
+    }
+}
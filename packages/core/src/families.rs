@@ -0,0 +1,116 @@
+//! Model family rollups
+//!
+//! A model report has a row per source, provider and model version, so a
+//! year of Sonnet usage is spread over every Sonnet release. A rollup puts
+//! the rows in the catalog's hierarchy instead: provider → family → model
+//! version, with one line per family and its versions kept for drill-down.
+//!
+//! Families come from the normalization rules (see
+//! [`crate::pricing::normalize`]), so `normalizeRules` in settings.json can
+//! add or regroup them. A model no rule names a family for is a family of
+//! its own, under the provider it was used through.
+
+use crate::pricing::normalize::NormalizeRules;
+use crate::{shares, ModelFamily, ModelReport};
+use std::collections::HashMap;
+
+/// `report`'s entries by family, most expensive first
+pub fn rollup(report: &ModelReport, rules: &NormalizeRules) -> Vec<ModelFamily> {
+    let mut families: HashMap<(String, String), ModelFamily> = HashMap::new();
+    for entry in &report.entries {
+        let (provider, family) = match rules.family(&entry.model) {
+            Some((provider, family)) if !provider.is_empty() => (provider, family),
+            Some((_, family)) => (entry.provider.as_str(), family),
+            None => (entry.provider.as_str(), entry.model.as_str()),
+        };
+        let rollup = families
+            .entry((provider.to_string(), family.to_string()))
+            .or_insert_with(|| empty_family(provider, family));
+        rollup.input += entry.input;
+        rollup.output += entry.output;
+        rollup.cache_read += entry.cache_read;
+        rollup.cache_write += entry.cache_write;
+        rollup.reasoning += entry.reasoning;
+        rollup.message_count += entry.message_count;
+        rollup.cost += entry.cost;
+        rollup.covered += entry.covered;
+        // Entries come most expensive first, so each family's models do too
+        rollup.models.push(entry.clone());
+    }
+
+    let total_tokens =
+        shares::tokens(report.total_input, report.total_output, report.total_cache_read, report.total_cache_write);
+    let mut families: Vec<ModelFamily> = families.into_values().collect();
+    for family in &mut families {
+        family.cost_share = shares::share(family.cost, report.total_cost);
+        family.token_share = shares::share(
+            shares::tokens(family.input, family.output, family.cache_read, family.cache_write),
+            total_tokens,
+        );
+    }
+    families.sort_by(|a, b| {
+        b.cost
+            .total_cmp(&a.cost)
+            .then_with(|| a.provider.cmp(&b.provider))
+            .then_with(|| a.family.cmp(&b.family))
+    });
+    families
+}
+
+fn empty_family(provider: &str, family: &str) -> ModelFamily {
+    ModelFamily {
+        provider: provider.to_string(),
+        family: family.to_string(),
+        models: Vec::new(),
+        input: 0,
+        output: 0,
+        cache_read: 0,
+        cache_write: 0,
+        reasoning: 0,
+        message_count: 0,
+        cost: 0.0,
+        covered: 0.0,
+        cost_share: 0.0,
+        token_share: 0.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sessions::UnifiedMessage;
+    use crate::{ModelTotals, TokenBreakdown};
+    use std::time::Instant;
+
+    fn report(rows: &[(&str, &str, &str, f64)]) -> ModelReport {
+        let mut totals = ModelTotals::default();
+        for (source, model, provider, cost) in rows {
+            let tokens = TokenBreakdown { input: 100, ..TokenBreakdown::default() };
+            totals.add(&UnifiedMessage::new(*source, *model, *provider, "s1", 1_748_779_200_000, tokens, *cost), 0.0);
+        }
+        totals.finish(Instant::now())
+    }
+
+    #[test]
+    fn test_versions_roll_up() {
+        let report = report(&[
+            ("claude", "claude-sonnet-4-5-20250929", "anthropic", 4.0),
+            ("claude", "claude-3-5-sonnet-20241022", "anthropic", 1.0),
+            ("opencode", "claude-sonnet-4-5-20250929", "anthropic", 1.0),
+            ("codex", "gpt-4o", "openai", 2.0),
+            ("opencode", "glm-4.6", "zai", 0.0),
+        ]);
+        let families = rollup(&report, &NormalizeRules::bundled());
+
+        let lines: Vec<(&str, &str, f64, usize)> =
+            families.iter().map(|f| (f.provider.as_str(), f.family.as_str(), f.cost, f.models.len())).collect();
+        assert_eq!(
+            lines,
+            [("anthropic", "claude-sonnet", 6.0, 3), ("openai", "gpt-4", 2.0, 1), ("zai", "glm-4.6", 0.0, 1)]
+        );
+        assert_eq!(families[0].models[0].cost, 4.0);
+        assert_eq!(families[0].message_count, 3);
+        assert_eq!(families[0].cost_share, 0.75);
+        assert_eq!(families[0].token_share, 0.6);
+    }
+}
@@ -0,0 +1,238 @@
+//! Free tiers and promotional credits
+//!
+//! Some usage is never charged: Gemini's free tier covers a number of tokens
+//! a day, and providers hand out promotional credits. Both are set per
+//! provider in settings.json:
+//!
+//! ```json
+//! "freeTiers": [
+//!   { "name": "gemini free", "provider": "google", "models": "gemini-*-flash*", "period": "day", "tokens": 1000000 },
+//!   { "name": "launch credits", "provider": "openai", "period": "total", "credits": 100, "until": "2025-12-31" }
+//! ]
+//! ```
+//!
+//! Messages consume allowances in the order they were sent, each tier in
+//! the order listed. A message only partly covered by a token allowance is
+//! covered in proportion to its tokens. Model reports show the covered
+//! amount apart from the cost still charged. Allowances are used up by the
+//! whole history before reports are filtered by date: a report starting in
+//! the middle of a month, or after a credit ran out, sees only what earlier
+//! messages left.
+
+use crate::model_glob::ModelGlob;
+use crate::sessions::UnifiedMessage;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AllowancePeriod {
+    /// Renews every UTC day
+    #[default]
+    Day,
+    /// Renews every calendar month
+    Month,
+    /// Never renews, as for one-off credits
+    Total,
+}
+
+/// One entry of `freeTiers` in settings.json
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct FreeTier {
+    pub name: Option<String>,
+    /// Provider whose messages it covers, as in reports ("google")
+    pub provider: String,
+    /// Comma-separated model patterns (see [`crate::model_glob`]); all models
+    /// when unset
+    pub models: Option<String>,
+    pub period: AllowancePeriod,
+    /// Tokens of all kinds free per period
+    pub tokens: Option<i64>,
+    /// USD of credit per period
+    pub credits: Option<f64>,
+    /// First and last day (YYYY-MM-DD) the allowance applies, inclusive
+    pub since: Option<String>,
+    pub until: Option<String>,
+}
+
+impl FreeTier {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.provider.trim().is_empty() {
+            return Err("provider is required".to_string());
+        }
+        if let Some(models) = &self.models {
+            ModelGlob::parse(models)?;
+        }
+        let tokens = self.tokens.is_some_and(|t| t > 0);
+        let credits = self.credits.is_some_and(|c| c.is_finite() && c > 0.0);
+        if !tokens && !credits {
+            return Err("needs positive tokens or credits".to_string());
+        }
+        Ok(())
+    }
+
+    fn covers(&self, msg: &UnifiedMessage, models: Option<&ModelGlob>) -> bool {
+        msg.provider_id.as_str().eq_ignore_ascii_case(self.provider.trim())
+            && models.is_none_or(|glob| glob.matches(&msg.model_id))
+            && self.since.as_deref().is_none_or(|since| msg.date.as_str() >= since)
+            && self.until.as_deref().is_none_or(|until| msg.date.as_str() <= until)
+    }
+
+    fn period_key<'a>(&self, date: &'a str) -> &'a str {
+        match self.period {
+            AllowancePeriod::Day => date,
+            AllowancePeriod::Month => date.get(..7).unwrap_or(date),
+            AllowancePeriod::Total => "",
+        }
+    }
+}
+
+/// Whether a tier is for `msg`'s provider, so that it may cover some of it
+pub fn may_cover(tiers: &[FreeTier], msg: &UnifiedMessage) -> bool {
+    tiers.iter().any(|tier| msg.provider_id.as_str().eq_ignore_ascii_case(tier.provider.trim()))
+}
+
+/// What is left of an allowance in one period
+#[derive(Debug, Clone, Copy)]
+struct Remaining {
+    tokens: i64,
+    credits: f64,
+}
+
+/// How much of each message's cost `tiers` cover, in the order of
+/// `messages`. Never more than a message's cost.
+pub fn covered(messages: &[UnifiedMessage], tiers: &[FreeTier]) -> Vec<f64> {
+    let mut covered = vec![0.0; messages.len()];
+    let tiers: Vec<(&FreeTier, Option<ModelGlob>)> = tiers
+        .iter()
+        .filter(|tier| tier.validate().is_ok())
+        .map(|tier| (tier, tier.models.as_deref().and_then(|m| ModelGlob::parse(m).ok())))
+        .collect();
+    if tiers.is_empty() {
+        return covered;
+    }
+
+    let mut order: Vec<usize> = (0..messages.len()).collect();
+    order.sort_by_key(|&i| messages[i].timestamp);
+
+    for (tier, models) in &tiers {
+        let mut remaining: HashMap<&str, Remaining> = HashMap::new();
+        for &i in &order {
+            let msg = &messages[i];
+            let uncovered = msg.cost - covered[i];
+            if uncovered <= 0.0 || !tier.covers(msg, models.as_ref()) {
+                continue;
+            }
+            let left = remaining.entry(tier.period_key(&msg.date)).or_insert(Remaining {
+                tokens: tier.tokens.unwrap_or(0).max(0),
+                credits: tier.credits.unwrap_or(0.0).max(0.0),
+            });
+
+            let mut cover = 0.0;
+            let t = &msg.tokens;
+            let tokens = t.input + t.output + t.cache_read + t.cache_write + t.reasoning + t.training;
+            if left.tokens > 0 && tokens > 0 {
+                let free = left.tokens.min(tokens);
+                left.tokens -= free;
+                cover = uncovered * free as f64 / tokens as f64;
+            }
+            if left.credits > 0.0 && cover < uncovered {
+                let credit = left.credits.min(uncovered - cover);
+                left.credits -= credit;
+                cover += credit;
+            }
+            covered[i] += cover;
+        }
+    }
+    covered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{message, noon_utc, TestMessage};
+
+    const HOUR: i64 = 3_600_000;
+    /// 2025-06-01T00:00:00Z
+    const DAY: i64 = 1_748_736_000_000;
+
+    #[test]
+    fn test_token_allowance_per_day() {
+        let tier = FreeTier {
+            provider: "google".to_string(),
+            models: Some("gemini-*-flash".to_string()),
+            tokens: Some(1_000),
+            ..FreeTier::default()
+        };
+        let messages = vec![
+            // Out of order: the earlier message takes the allowance first
+            message("s1", DAY + 2 * HOUR, 1.0).with_model("gemini-2.5-flash").with_provider("google").with_input(1_000),
+            message("s1", DAY + HOUR, 0.6).with_model("gemini-2.5-flash").with_provider("google").with_input(600),
+            message("s1", DAY + HOUR, 5.0).with_model("gemini-2.5-pro").with_provider("google").with_input(1_000),
+            message("s1", DAY + HOUR, 1.0).with_model("gemini-2.5-flash").with_provider("anthropic").with_input(1_000),
+            // Next day, fresh allowance
+            message("s1", DAY + 25 * HOUR, 0.5).with_model("gemini-2.5-flash").with_provider("google").with_input(500),
+        ];
+        let covered = covered(&messages, &[tier]);
+        for (got, want) in covered.iter().zip([0.4, 0.6, 0.0, 0.0, 0.5]) {
+            assert!((got - want).abs() < 1e-9, "{:?}", covered);
+        }
+    }
+
+    #[test]
+    fn test_credits_after_tokens_and_validity() {
+        let tiers = vec![
+            FreeTier { provider: "openai".to_string(), period: AllowancePeriod::Day, tokens: Some(100), ..FreeTier::default() },
+            FreeTier {
+                provider: "OpenAI".to_string(),
+                period: AllowancePeriod::Total,
+                credits: Some(3.0),
+                until: Some("2025-06-01".to_string()),
+                ..FreeTier::default()
+            },
+        ];
+        let messages = vec![
+            message("s1", DAY, 2.0).with_model("gpt-4o").with_provider("openai").with_input(200),
+            message("s1", DAY + HOUR, 4.0).with_model("gpt-4o").with_provider("openai").with_input(100),
+            message("s1", DAY + 25 * HOUR, 4.0).with_model("gpt-4o").with_provider("openai").with_input(100),
+        ];
+        let covered = covered(&messages, &tiers);
+        // Half the first message's tokens are free, credits cover the rest
+        assert!((covered[0] - 2.0).abs() < 1e-9);
+        assert!((covered[1] - 2.0).abs() < 1e-9);
+        // The next day: tokens renew, credits have expired
+        assert!((covered[2] - 4.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_filtered_report_sees_used_credit() {
+        let tier = FreeTier {
+            provider: "openai".to_string(),
+            period: AllowancePeriod::Total,
+            credits: Some(5.0),
+            ..FreeTier::default()
+        };
+        let mut messages = vec![
+            message("s1", noon_utc("2025-06-01"), 3.0).with_model("gpt-4o").with_provider("openai"),
+            message("s1", noon_utc("2025-06-02"), 3.0).with_model("gpt-4o").with_provider("openai"),
+            message("s1", noon_utc("2025-06-03"), 3.0).with_model("gpt-4o").with_provider("openai"),
+        ];
+        let mut covered = covered(&messages, std::slice::from_ref(&tier));
+
+        // --since 2025-06-02: the first day's message is out of the report
+        // but has already used 3 of the 5 credits
+        crate::retain_with_coverage(&mut messages, &mut covered, |m| crate::in_dates(&m.date, Some("2025-06-02"), None, None));
+        assert_eq!(messages.len(), 2);
+        assert!((covered[0] - 2.0).abs() < 1e-9, "{:?}", covered);
+        assert!(covered[1].abs() < 1e-9, "{:?}", covered);
+    }
+
+    #[test]
+    fn test_validate() {
+        let tier = FreeTier { provider: "google".to_string(), ..FreeTier::default() };
+        assert!(tier.validate().is_err());
+        assert!(FreeTier { tokens: Some(10), ..tier.clone() }.validate().is_ok());
+        assert!(FreeTier { provider: " ".to_string(), credits: Some(5.0), ..tier }.validate().is_err());
+    }
+}
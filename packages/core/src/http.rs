@@ -0,0 +1,350 @@
+//! Just enough HTTP/1.1 for the proxy and the server: read one request, write
+//! a response. Every response closes the connection, so there is no
+//! keep-alive handling.
+
+use std::time::Duration;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::time::timeout;
+
+/// Also bounds the trailers of a chunked body
+const MAX_HEAD_BYTES: usize = 64 * 1024;
+const MAX_HEADERS: usize = 100;
+const MAX_CHUNK_LINE_BYTES: usize = 1024;
+/// Request bodies are buffered to be forwarded; anything larger is refused
+pub const MAX_BODY_BYTES: usize = 32 * 1024 * 1024;
+/// A client slower than this is dropped, so it can't hold a connection open
+const HEAD_TIMEOUT: Duration = Duration::from_secs(30);
+const BODY_TIMEOUT: Duration = Duration::from_secs(120);
+
+#[derive(Debug)]
+pub struct Request {
+    pub method: String,
+    /// Path and query, e.g. "/v1/chat/completions"
+    pub target: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn path(&self) -> &str {
+        self.target.split('?').next().unwrap_or("")
+    }
+
+    /// Decoded query parameters, in order
+    pub fn query(&self) -> Vec<(String, String)> {
+        let Some((_, query)) = self.target.split_once('?') else {
+            return Vec::new();
+        };
+        query
+            .split('&')
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| {
+                let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+                (percent_decode(name), percent_decode(value))
+            })
+            .collect()
+    }
+
+    pub fn query_param(&self, name: &str) -> Option<String> {
+        self.query().into_iter().find(|(n, _)| n == name).map(|(_, v)| v)
+    }
+}
+
+pub fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => out.push(b' '),
+            b'%' if i + 2 < bytes.len() => match (hex_digit(bytes[i + 1]), hex_digit(bytes[i + 2])) {
+                (Some(high), Some(low)) => {
+                    out.push(high << 4 | low);
+                    i += 2;
+                }
+                _ => out.push(b'%'),
+            },
+            byte => out.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+fn hex_digit(byte: u8) -> Option<u8> {
+    (byte as char).to_digit(16).map(|d| d as u8)
+}
+
+/// Read one request. `Ok(None)` if the client closed the connection first.
+pub async fn read_request<R: AsyncBufRead + Unpin>(reader: &mut R) -> Result<Option<Request>, String> {
+    let head = match timeout(HEAD_TIMEOUT, read_head(reader)).await {
+        Ok(head) => head?,
+        Err(_) => return Err("timed out reading the request head".to_string()),
+    };
+    let Some(head) = head else {
+        return Ok(None);
+    };
+
+    let mut headers = [httparse::EMPTY_HEADER; MAX_HEADERS];
+    let mut parsed = httparse::Request::new(&mut headers);
+    match parsed.parse(&head).map_err(|e| e.to_string())? {
+        httparse::Status::Complete(_) => {}
+        httparse::Status::Partial => return Err("incomplete request head".to_string()),
+    }
+
+    let mut request = Request {
+        method: parsed.method.unwrap_or("GET").to_string(),
+        target: parsed.path.unwrap_or("/").to_string(),
+        headers: parsed
+            .headers
+            .iter()
+            .map(|h| (h.name.to_string(), String::from_utf8_lossy(h.value).into_owned()))
+            .collect(),
+        body: Vec::new(),
+    };
+
+    let chunked = request
+        .header("transfer-encoding")
+        .is_some_and(|v| v.to_ascii_lowercase().contains("chunked"));
+    let length = match request.header("content-length") {
+        Some(length) if !chunked => {
+            let length: usize = length.trim().parse().map_err(|_| "invalid content-length".to_string())?;
+            if length > MAX_BODY_BYTES {
+                return Err("request body too large".to_string());
+            }
+            Some(length)
+        }
+        _ => None,
+    };
+    let body = async {
+        if chunked {
+            read_chunked(reader).await
+        } else if let Some(length) = length {
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).await.map_err(|e| e.to_string())?;
+            Ok(body)
+        } else {
+            Ok(Vec::new())
+        }
+    };
+    request.body = match timeout(BODY_TIMEOUT, body).await {
+        Ok(body) => body?,
+        Err(_) => return Err("timed out reading the request body".to_string()),
+    };
+
+    Ok(Some(request))
+}
+
+/// Bytes up to the blank line ending the head; `None` if the connection
+/// closed before any arrived
+async fn read_head<R: AsyncBufRead + Unpin>(reader: &mut R) -> Result<Option<Vec<u8>>, String> {
+    let mut head = Vec::new();
+    loop {
+        // One byte over the limit tells a long head from one that just fits
+        let limit = (MAX_HEAD_BYTES + 1 - head.len()) as u64;
+        let read = (&mut *reader)
+            .take(limit)
+            .read_until(b'\n', &mut head)
+            .await
+            .map_err(|e| e.to_string())?;
+        if head.len() > MAX_HEAD_BYTES {
+            return Err("request head too large".to_string());
+        }
+        if read == 0 {
+            return if head.is_empty() { Ok(None) } else { Err("connection closed mid-request".to_string()) };
+        }
+        if head.ends_with(b"\r\n\r\n") || head.ends_with(b"\n\n") {
+            return Ok(Some(head));
+        }
+    }
+}
+
+/// Read one line of at most `limit` bytes into `line`, returning the bytes
+/// read (0 at the end of input)
+async fn read_line_limited<R: AsyncBufRead + Unpin>(reader: &mut R, line: &mut String, limit: usize) -> Result<usize, String> {
+    line.clear();
+    let read = (&mut *reader)
+        .take(limit as u64 + 1)
+        .read_line(line)
+        .await
+        .map_err(|e| e.to_string())?;
+    if read > limit {
+        return Err("request line too long".to_string());
+    }
+    Ok(read)
+}
+
+async fn read_chunked<R: AsyncBufRead + Unpin>(reader: &mut R) -> Result<Vec<u8>, String> {
+    let mut body = Vec::new();
+    let mut line = String::new();
+    loop {
+        read_line_limited(reader, &mut line, MAX_CHUNK_LINE_BYTES).await?;
+        let size = line.trim().split(';').next().unwrap_or("");
+        let size = usize::from_str_radix(size, 16).map_err(|_| "invalid chunk size".to_string())?;
+        if size == 0 {
+            // Skip trailers up to the blank line
+            let mut trailers = 0;
+            loop {
+                let read = read_line_limited(reader, &mut line, MAX_HEAD_BYTES - trailers).await?;
+                trailers += read;
+                if read == 0 || line.trim().is_empty() {
+                    return Ok(body);
+                }
+            }
+        }
+        let end = body.len().checked_add(size).filter(|&end| end <= MAX_BODY_BYTES);
+        let Some(end) = end else {
+            return Err("request body too large".to_string());
+        };
+        let start = body.len();
+        body.resize(end, 0);
+        reader.read_exact(&mut body[start..]).await.map_err(|e| e.to_string())?;
+        read_line_limited(reader, &mut line, MAX_CHUNK_LINE_BYTES).await?;
+    }
+}
+
+/// Write the status line and headers. With `chunked`, the body must follow as
+/// [`write_chunk`] calls and a final [`finish_chunks`].
+pub async fn write_head<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    status: u16,
+    reason: &str,
+    headers: &[(String, String)],
+    chunked: bool,
+) -> std::io::Result<()> {
+    let mut head = format!("HTTP/1.1 {} {}\r\n", status, reason);
+    for (name, value) in headers {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    if chunked {
+        head.push_str("transfer-encoding: chunked\r\n");
+    }
+    head.push_str("connection: close\r\n\r\n");
+    writer.write_all(head.as_bytes()).await
+}
+
+pub async fn write_chunk<W: AsyncWrite + Unpin>(writer: &mut W, data: &[u8]) -> std::io::Result<()> {
+    if data.is_empty() {
+        return Ok(());
+    }
+    writer.write_all(format!("{:x}\r\n", data.len()).as_bytes()).await?;
+    writer.write_all(data).await?;
+    writer.write_all(b"\r\n").await?;
+    writer.flush().await
+}
+
+pub async fn finish_chunks<W: AsyncWrite + Unpin>(writer: &mut W) -> std::io::Result<()> {
+    writer.write_all(b"0\r\n\r\n").await?;
+    writer.flush().await
+}
+
+/// Complete response with a fixed body
+pub async fn write_response<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    status: u16,
+    reason: &str,
+    content_type: &str,
+    body: &[u8],
+) -> std::io::Result<()> {
+    let headers = [
+        ("content-type".to_string(), content_type.to_string()),
+        ("content-length".to_string(), body.len().to_string()),
+    ];
+    write_head(writer, status, reason, &headers, false).await?;
+    writer.write_all(body).await?;
+    writer.flush().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap().block_on(future)
+    }
+
+    #[test]
+    fn test_read_request_with_body() {
+        let raw = b"POST /v1/chat/completions?x=1 HTTP/1.1\r\nHost: localhost\r\nContent-Length: 4\r\n\r\n{}{}";
+        let request = block_on(read_request(&mut &raw[..])).unwrap().unwrap();
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.path(), "/v1/chat/completions");
+        assert_eq!(request.header("HOST"), Some("localhost"));
+        assert_eq!(request.body, b"{}{}");
+    }
+
+    #[test]
+    fn test_query() {
+        let raw = b"GET /api/report?since=2025-01-01&sources=claude%2Ccodex&q=a+b&flag HTTP/1.1\r\n\r\n";
+        let request = block_on(read_request(&mut &raw[..])).unwrap().unwrap();
+        assert_eq!(request.path(), "/api/report");
+        assert_eq!(request.query_param("sources").as_deref(), Some("claude,codex"));
+        assert_eq!(request.query_param("q").as_deref(), Some("a b"));
+        assert_eq!(request.query_param("flag").as_deref(), Some(""));
+        assert_eq!(request.query_param("until"), None);
+        assert_eq!(percent_decode("100%"), "100%");
+        assert_eq!(percent_decode("%zz%4"), "%zz%4");
+        assert_eq!(percent_decode("%é"), "%é");
+    }
+
+    #[test]
+    fn test_read_chunked_request() {
+        let raw = b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n2;ext\r\nde\r\n0\r\n\r\n";
+        let request = block_on(read_request(&mut &raw[..])).unwrap().unwrap();
+        assert_eq!(request.body, b"abcde");
+    }
+
+    #[test]
+    fn test_read_request_eof() {
+        assert!(block_on(read_request(&mut &b""[..])).unwrap().is_none());
+        assert!(block_on(read_request(&mut &b"GET / HTTP/1.1\r\n"[..])).is_err());
+    }
+
+    #[test]
+    fn test_read_request_limits() {
+        let mut long_line = b"GET / HTTP/1.1\r\nX-Pad: ".to_vec();
+        long_line.extend(vec![b'a'; MAX_HEAD_BYTES]);
+        let error = block_on(read_request(&mut &long_line[..])).unwrap_err();
+        assert_eq!(error, "request head too large");
+
+        let mut long_size = b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n".to_vec();
+        long_size.extend(vec![b'0'; MAX_CHUNK_LINE_BYTES + 1]);
+        assert_eq!(block_on(read_request(&mut &long_size[..])).unwrap_err(), "request line too long");
+
+        let huge = b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n1\r\na\r\nffffffffffffffff\r\n";
+        assert_eq!(block_on(read_request(&mut &huge[..])).unwrap_err(), "request body too large");
+    }
+
+    #[test]
+    fn test_read_request_times_out() {
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().start_paused(true).build().unwrap();
+        let (client, server) = tokio::io::duplex(64);
+        let error = runtime.block_on(async move {
+            let _client = client;
+            read_request(&mut tokio::io::BufReader::new(server)).await.unwrap_err()
+        });
+        assert_eq!(error, "timed out reading the request head");
+    }
+
+    #[test]
+    fn test_write_chunked() {
+        let mut out = Vec::new();
+        block_on(async {
+            write_head(&mut out, 200, "OK", &[("content-type".to_string(), "text/plain".to_string())], true)
+                .await
+                .unwrap();
+            write_chunk(&mut out, b"hello").await.unwrap();
+            finish_chunks(&mut out).await.unwrap();
+        });
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.starts_with("HTTP/1.1 200 OK\r\ncontent-type: text/plain\r\ntransfer-encoding: chunked\r\n"));
+        assert!(text.ends_with("\r\n\r\n5\r\nhello\r\n0\r\n\r\n"));
+    }
+}
@@ -2,49 +2,137 @@
 //!
 //! This module provides parallel file scanning, SIMD JSON parsing, and efficient
 //! aggregation of token usage data from multiple AI coding assistant sessions.
+//! The Node.js bindings are the `tokscale-napi` crate; the `napi` feature
+//! makes the report types here napi objects for it.
 
 #![deny(clippy::all)]
 
+#[cfg(feature = "napi")]
 use napi_derive::napi;
 
-mod aggregator;
+pub mod activity;
+#[cfg(feature = "encryption")]
+pub mod age;
+pub mod aggregator;
+mod api;
+mod archive;
+pub mod arrow;
+pub mod budget;
+pub mod cache_gc;
+pub mod checks;
+pub mod chunked;
+mod clock;
+pub mod commits;
+pub mod config;
+pub mod config_schema;
+pub mod conformance;
+pub mod context_window;
+pub mod cost_drivers;
+pub mod cost_display;
+mod degradation;
+pub mod diagnostics;
+pub mod diff;
+pub mod downsample;
+pub mod effective_rates;
+pub mod efficiency;
+pub mod enforcement;
+pub mod families;
+mod free_tier;
+#[cfg(feature = "server")]
+mod http;
+pub mod model_glob;
+pub mod openrouter_import;
+pub mod parse_cache;
 mod parser;
-mod pricing;
-mod scanner;
-mod sessions;
+pub mod pipeline;
+pub mod pricing;
+pub mod provider_status;
+#[cfg(feature = "server")]
+pub mod proxy;
+pub mod recent;
+pub mod recorder;
+pub mod reconcile;
+pub mod run_summary;
+pub mod report_schema;
+mod response_usage;
+pub mod scanner;
+pub mod secrets;
+#[cfg(feature = "server")]
+pub mod serve;
+pub mod session_browser;
+#[cfg(feature = "encryption")]
+pub mod session_bundle;
+pub mod session_tags;
+pub mod sessions;
+pub mod shares;
+pub mod sources;
+pub mod statement;
+mod streaming;
+pub mod usage_mode;
+pub mod workspace;
+
+#[cfg(test)]
+mod testutil;
 
 pub use aggregator::*;
+pub use api::{Filter, PricingConfig, Tokscale, TokscaleBuilder, TokscaleError};
 pub use parser::*;
+pub use pricing::lookup::is_word_boundary_match;
+pub use pricing::{ModelPricing, PricingDataset, PricingProvider};
+#[cfg(feature = "server")]
+pub use proxy::{Proxy, ProxyConfig};
+pub use recorder::{IngestStats, Recorder};
+#[cfg(feature = "server")]
+pub use serve::tokens::{Scope, TokenStore};
+#[cfg(feature = "server")]
+pub use serve::{ServeConfig, Server};
+pub use response_usage::sse::{parse_sse, SseDecoder, SseEvent, StreamUsage};
+pub use response_usage::{ApiFormat, ResponseUsage};
+pub use report_schema::{
+    json_schema as report_json_schema, DateRange, ExportMeta, ExportYear, GraphExport, ReportKind,
+    SCHEMA_VERSION as REPORT_SCHEMA_VERSION,
+};
 pub use scanner::*;
+pub use sessions::{Provider, Source, UnifiedMessage};
+pub use streaming::MemoryLimits;
 
-/// Version of the native module
-#[napi]
-pub fn version() -> String {
-    env!("CARGO_PKG_VERSION").to_string()
-}
-
-/// Simple health check to verify the native module is working
-#[napi]
-pub fn health_check() -> String {
-    "tokscale-core is healthy!".to_string()
+/// What this build supports
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone)]
+pub struct Capabilities {
+    pub version: String,
+    pub features: Vec<String>,
+    /// Session sources that can be parsed
+    pub sources: Vec<String>,
+    /// Price sources `lookupPricing` can be forced to
+    pub pricing_providers: Vec<String>,
+    pub export_formats: Vec<String>,
+    /// Reports with a JSON Schema (see `getReportSchema`)
+    pub report_kinds: Vec<String>,
+    pub report_schema_version: String,
 }
 
 /// Token breakdown by type
-#[napi(object)]
-#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "napi", napi(object))]
+#[cfg_attr(test, derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct TokenBreakdown {
     pub input: i64,
     pub output: i64,
     pub cache_read: i64,
     pub cache_write: i64,
     pub reasoning: i64,
+    /// Fine-tuning training tokens, priced at the model's training rate
+    #[serde(default)]
+    pub training: i64,
 }
 
 // =============================================================================
 // Two-Phase Processing Types (for parallel execution optimization)
 // =============================================================================
 
-#[napi(object)]
+#[cfg_attr(feature = "napi", napi(object))]
 #[derive(Debug, Clone)]
 pub struct ParsedMessage {
     pub source: String,
@@ -58,11 +146,20 @@ pub struct ParsedMessage {
     pub cache_read: i64,
     pub cache_write: i64,
     pub reasoning: i64,
+    /// Fine-tuning training tokens
+    pub training: Option<i64>,
     pub agent: Option<String>,
+    /// Timestamp was synthesized rather than read from the session file
+    pub estimated_timestamp: Option<bool>,
+    /// Working directory, for sources that record it
+    pub cwd: Option<String>,
+    /// Cost the provider billed, kept instead of our pricing (imported
+    /// OpenRouter generations)
+    pub billed_cost: Option<f64>,
 }
 
 /// Result of parsing local sources (excludes Cursor - it's network-synced)
-#[napi(object)]
+#[cfg_attr(feature = "napi", napi(object))]
 #[derive(Debug, Clone)]
 pub struct ParsedMessages {
     pub messages: Vec<ParsedMessage>,
@@ -72,11 +169,18 @@ pub struct ParsedMessages {
     pub gemini_count: i32,
     pub amp_count: i32,
     pub droid_count: i32,
+    pub cline_count: i32,
+    pub roocode_count: i32,
+    pub recorded_count: i32,
     pub processing_time_ms: u32,
+    /// Session files per source scanned, for the run summary
+    pub files: Option<Vec<SourceFiles>>,
+    /// Messages dropped as duplicates
+    pub deduped: Option<i32>,
 }
 
 /// Options for parsing local sources only (no Cursor)
-#[napi(object)]
+#[cfg_attr(feature = "napi", napi(object))]
 #[derive(Debug, Clone)]
 pub struct LocalParseOptions {
     pub home_dir: Option<String>,
@@ -84,10 +188,12 @@ pub struct LocalParseOptions {
     pub since: Option<String>,
     pub until: Option<String>,
     pub year: Option<String>,
+    /// Drop messages whose timestamp was synthesized (e.g. older Amp threads)
+    pub exclude_estimated_timestamps: Option<bool>,
 }
 
 /// Options for finalizing report
-#[napi(object)]
+#[cfg_attr(feature = "napi", napi(object))]
 #[derive(Debug, Clone)]
 pub struct FinalizeReportOptions {
     pub home_dir: Option<String>,
@@ -96,11 +202,25 @@ pub struct FinalizeReportOptions {
     pub since: Option<String>,
     pub until: Option<String>,
     pub year: Option<String>,
+    /// Bucket width for recent usage: "hour" (default) or "15m"
+    pub recent_granularity: Option<String>,
+    /// Days of recent usage to bucket (default: `recentBucketDays` from
+    /// settings, else 2)
+    pub recent_days: Option<u32>,
+    /// Reproduce the report as of this date (YYYY-MM-DD): prices from the
+    /// price history, no messages after it. `finalizeReport` only.
+    pub as_of: Option<String>,
+    /// Only models matching these comma-separated globs (e.g. "claude-*,
+    /// gpt-4o"). `finalizeReportAndGraph` only.
+    pub model: Option<String>,
+    /// Also roll entries up by model family. `finalizeReport` only.
+    pub families: Option<bool>,
 }
 
 /// Daily contribution totals
-#[napi(object)]
-#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "napi", napi(object))]
+#[cfg_attr(test, derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
 pub struct DailyTotals {
     pub tokens: i64,
     pub cost: f64,
@@ -108,8 +228,10 @@ pub struct DailyTotals {
 }
 
 /// Source contribution for a specific day
-#[napi(object)]
-#[derive(Debug, Clone)]
+#[cfg_attr(feature = "napi", napi(object))]
+#[cfg_attr(test, derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct SourceContribution {
     pub source: String,
     pub model_id: String,
@@ -120,8 +242,10 @@ pub struct SourceContribution {
 }
 
 /// Daily contribution data
-#[napi(object)]
-#[derive(Debug, Clone)]
+#[cfg_attr(feature = "napi", napi(object))]
+#[cfg_attr(test, derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct DailyContribution {
     pub date: String,
     pub totals: DailyTotals,
@@ -131,8 +255,10 @@ pub struct DailyContribution {
 }
 
 /// Year summary
-#[napi(object)]
-#[derive(Debug, Clone)]
+#[cfg_attr(feature = "napi", napi(object))]
+#[cfg_attr(test, derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct YearSummary {
     pub year: String,
     pub total_tokens: i64,
@@ -142,8 +268,10 @@ pub struct YearSummary {
 }
 
 /// Data summary statistics
-#[napi(object)]
-#[derive(Debug, Clone)]
+#[cfg_attr(feature = "napi", napi(object))]
+#[cfg_attr(test, derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct DataSummary {
     pub total_tokens: i64,
     pub total_cost: f64,
@@ -156,8 +284,9 @@ pub struct DataSummary {
 }
 
 /// Metadata about the graph generation
-#[napi(object)]
-#[derive(Debug, Clone)]
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct GraphMeta {
     pub generated_at: String,
     pub version: String,
@@ -167,8 +296,9 @@ pub struct GraphMeta {
 }
 
 /// Complete graph result
-#[napi(object)]
-#[derive(Debug, Clone)]
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct GraphResult {
     pub meta: GraphMeta,
     pub summary: DataSummary,
@@ -181,19 +311,22 @@ pub struct GraphResult {
 // =============================================================================
 
 use rayon::prelude::*;
-use sessions::UnifiedMessage;
 use std::time::Instant;
 
-fn get_home_dir(home_dir_option: &Option<String>) -> napi::Result<String> {
-    home_dir_option
-        .clone()
-        .or_else(|| std::env::var("HOME").ok())
-        .or_else(|| dirs::home_dir().map(|p| p.to_string_lossy().into_owned()))
-        .ok_or_else(|| {
-            napi::Error::from_reason(
-                "HOME directory not specified and could not determine home directory",
-            )
-        })
+/// Home directory to read another user's data from (e.g. a backup) instead
+/// of your own; set by the CLI's `--home` flag
+pub const READ_ONLY_HOME_ENV: &str = "TOKSCALE_HOME";
+
+/// Most expensive first (NaN last), ties by source, provider and model so
+/// report rows come out in the same order on every run
+fn by_cost_desc(a: &ModelUsage, b: &ModelUsage) -> std::cmp::Ordering {
+    match (a.cost.is_nan(), b.cost.is_nan()) {
+        (true, true) => std::cmp::Ordering::Equal,
+        (true, false) => std::cmp::Ordering::Greater,
+        (false, true) => std::cmp::Ordering::Less,
+        (false, false) => b.cost.partial_cmp(&a.cost).unwrap_or(std::cmp::Ordering::Equal),
+    }
+    .then_with(|| (&a.source, &a.provider, &a.model).cmp(&(&b.source, &b.provider, &b.model)))
 }
 
 // =============================================================================
@@ -201,7 +334,7 @@ fn get_home_dir(home_dir_option: &Option<String>) -> napi::Result<String> {
 // =============================================================================
 
 /// Options for reports
-#[napi(object)]
+#[cfg_attr(feature = "napi", napi(object))]
 #[derive(Debug, Clone)]
 pub struct ReportOptions {
     pub home_dir: Option<String>,
@@ -209,11 +342,15 @@ pub struct ReportOptions {
     pub since: Option<String>,
     pub until: Option<String>,
     pub year: Option<String>,
+    /// Drop messages whose timestamp was synthesized (e.g. older Amp threads)
+    pub exclude_estimated_timestamps: Option<bool>,
 }
 
 /// Model usage summary for reports
-#[napi(object)]
-#[derive(Debug, Clone)]
+#[cfg_attr(feature = "napi", napi(object))]
+#[cfg_attr(test, derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct ModelUsage {
     pub source: String,
     pub model: String,
@@ -223,13 +360,28 @@ pub struct ModelUsage {
     pub cache_read: i64,
     pub cache_write: i64,
     pub reasoning: i64,
+    /// Fine-tuning training tokens
+    #[serde(default)]
+    pub training: i64,
     pub message_count: i32,
+    /// USD charged, after free tiers and credits
     pub cost: f64,
+    /// USD covered by free tiers and promotional credits
+    #[serde(default)]
+    pub covered: f64,
+    /// Fraction of the report's cost, 0 to 1 (see [`shares`])
+    #[serde(default)]
+    pub cost_share: f64,
+    /// Fraction of the report's tokens, 0 to 1
+    #[serde(default)]
+    pub token_share: f64,
 }
 
 /// Monthly usage summary
-#[napi(object)]
-#[derive(Debug, Clone)]
+#[cfg_attr(feature = "napi", napi(object))]
+#[cfg_attr(test, derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct MonthlyUsage {
     pub month: String,
     pub models: Vec<String>,
@@ -239,263 +391,318 @@ pub struct MonthlyUsage {
     pub cache_write: i64,
     pub message_count: i32,
     pub cost: f64,
+    /// Fraction of the report's cost, 0 to 1 (see [`shares`])
+    #[serde(default)]
+    pub cost_share: f64,
+    /// Fraction of the report's tokens, 0 to 1
+    #[serde(default)]
+    pub token_share: f64,
 }
 
 /// Model report result
-#[napi(object)]
-#[derive(Debug, Clone)]
+#[cfg_attr(feature = "napi", napi(object))]
+#[cfg_attr(test, derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct ModelReport {
     pub entries: Vec<ModelUsage>,
     pub total_input: i64,
     pub total_output: i64,
     pub total_cache_read: i64,
     pub total_cache_write: i64,
+    #[serde(default)]
+    pub total_reasoning: i64,
     pub total_messages: i32,
     pub total_cost: f64,
+    /// USD covered by free tiers and promotional credits
+    #[serde(default)]
+    pub total_covered: f64,
     pub processing_time_ms: u32,
+    /// What went into the report (see [`run_summary`])
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub summary: Option<RunSummary>,
+    /// Entries rolled up by model family, when asked for (see [`families`])
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub families: Option<Vec<ModelFamily>>,
+}
+
+/// Usage of one model family, with its model versions to drill down into
+#[cfg_attr(feature = "napi", napi(object))]
+#[cfg_attr(test, derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelFamily {
+    pub provider: String,
+    pub family: String,
+    /// Entries of the family's models, most expensive first
+    pub models: Vec<ModelUsage>,
+    pub input: i64,
+    pub output: i64,
+    pub cache_read: i64,
+    pub cache_write: i64,
+    pub reasoning: i64,
+    pub message_count: i32,
+    /// USD charged, after free tiers and credits
+    pub cost: f64,
+    /// USD covered by free tiers and promotional credits
+    pub covered: f64,
+    /// Fraction of the report's cost, 0 to 1
+    pub cost_share: f64,
+    /// Fraction of the report's tokens, 0 to 1
+    pub token_share: f64,
 }
 
 /// Monthly report result
-#[napi(object)]
-#[derive(Debug, Clone)]
+#[cfg_attr(feature = "napi", napi(object))]
+#[cfg_attr(test, derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct MonthlyReport {
     pub entries: Vec<MonthlyUsage>,
+    #[serde(default)]
+    pub total_input: i64,
+    #[serde(default)]
+    pub total_output: i64,
+    #[serde(default)]
+    pub total_cache_read: i64,
+    #[serde(default)]
+    pub total_cache_write: i64,
+    #[serde(default)]
+    pub total_messages: i32,
     pub total_cost: f64,
     pub processing_time_ms: u32,
+    /// What went into the report (see [`run_summary`])
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub summary: Option<RunSummary>,
+}
+
+/// Session files of one source
+#[cfg_attr(feature = "napi", napi(object))]
+#[cfg_attr(test, derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SourceFiles {
+    pub source: String,
+    pub files: u32,
+    /// Files that yielded messages
+    pub parsed: u32,
+    /// Files that yielded none: empty, unreadable or without usage
+    pub skipped: u32,
+    /// Asked for by name (`--claude`) rather than scanned by default
+    #[serde(default)]
+    pub named: bool,
+}
+
+/// How a report's numbers came about, so they can be judged
+#[cfg_attr(feature = "napi", napi(object))]
+#[cfg_attr(test, derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RunSummary {
+    /// Sources scanned and their session files
+    pub sources: Vec<SourceFiles>,
+    /// Messages in the report
+    pub messages: i64,
+    /// Messages dropped as duplicates of ones already counted
+    pub messages_deduped: i64,
+    /// Models without a list price, whose messages cost nothing unless
+    /// their source reported a cost
+    pub unknown_models: Vec<String>,
+    pub unknown_model_messages: i64,
+    /// When the price tables were fetched, in seconds since the epoch;
+    /// absent for archived prices (`--as-of`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pricing_fetched_at: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pricing_age_hours: Option<f64>,
+    /// Prices fetched more than a day ago
+    pub pricing_stale: bool,
+    /// USD billed by providers (imported usage), before free tiers
+    pub exact_cost: f64,
+    /// USD estimated from token counts and list prices, before free tiers
+    pub estimated_cost: f64,
+    /// Share of the cost that was billed, 0-1
+    pub exact_cost_share: f64,
+    /// What the report is missing (see [`degradation`])
+    #[serde(default)]
+    pub degradation: Degradation,
+}
+
+/// Gaps in a report that still rendered
+#[cfg_attr(feature = "napi", napi(object))]
+#[cfg_attr(test, derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Degradation {
+    /// Any of the gaps below
+    pub degraded: bool,
+    /// "fresh", "stale", "archived" (`--as-of`) or "unavailable"
+    pub pricing: String,
+    /// Why prices could not be loaded, when unavailable
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pricing_error: Option<String>,
+    /// Sources asked for by name that have no session files
+    pub missing_sources: Vec<String>,
+    /// Session files that yielded no usage: empty, unreadable or corrupt
+    pub skipped_files: u32,
+}
+
+/// Whether reports read another home directory via `TOKSCALE_HOME`
+fn read_only_home() -> bool {
+    std::env::var(READ_ONLY_HOME_ENV).is_ok_and(|h| !h.is_empty())
+}
+
+/// `Err` while `TOKSCALE_HOME` is set, so `what` doesn't write into the home
+/// being analyzed (recorded events, the enforcement log)
+pub fn check_writable_home(what: &str) -> Result<(), String> {
+    writable_home(std::env::var(READ_ONLY_HOME_ENV).ok().as_deref(), what)
+}
+
+fn writable_home(read_only_home: Option<&str>, what: &str) -> Result<(), String> {
+    match read_only_home.filter(|h| !h.is_empty()) {
+        Some(home) => Err(format!("{} isn't available while {} is set: {} is read-only", what, READ_ONLY_HOME_ENV, home)),
+        None => Ok(()),
+    }
+}
+
+/// Parse one session file, with its timestamps sanitized (see [`clock`])
+pub fn parse_file(session_type: SessionType, path: &std::path::Path) -> Vec<UnifiedMessage> {
+    let mut messages = read_session_file(session_type, path);
+    clock::sanitize(&mut messages);
+    messages
+}
+
+/// Messages as the source's parser reads them, which the parse cache keeps
+fn read_session_file(session_type: SessionType, path: &std::path::Path) -> Vec<UnifiedMessage> {
+    if archive::is_tarball(path) {
+        return archive::parse_tarball(path, session_type, read_session_file);
+    }
+    match session_type {
+        SessionType::OpenCode => sessions::opencode::parse_opencode_file(path).into_iter().collect(),
+        SessionType::Claude => sessions::claudecode::parse_claude_file(path),
+        SessionType::Codex => sessions::codex::parse_codex_file(path),
+        SessionType::Gemini => sessions::gemini::parse_gemini_file(path),
+        SessionType::Cursor => sessions::cursor::parse_cursor_file(path),
+        SessionType::Amp => sessions::amp::parse_amp_file(path),
+        SessionType::Droid => sessions::droid::parse_droid_file(path),
+        SessionType::Cline => sessions::cline::parse_cline_file(path),
+        SessionType::RooCode => sessions::cline::parse_roocode_file(path),
+        SessionType::Recorded => sessions::recorded::parse_recorded_file(path),
+    }
 }
 
-fn parse_all_messages_with_pricing(
-    home_dir: &str,
-    sources: &[String],
+/// Recalculate a parsed message's cost with our pricing data
+pub fn apply_pricing(
+    session_type: SessionType,
+    mut msg: UnifiedMessage,
     pricing: &pricing::PricingService,
-) -> Vec<UnifiedMessage> {
-    let scan_result = scanner::scan_all_sources(home_dir, sources);
-    let mut all_messages: Vec<UnifiedMessage> = Vec::new();
-
-    // Parse OpenCode files in parallel
-    let opencode_messages: Vec<UnifiedMessage> = scan_result
-        .opencode_files
-        .par_iter()
-        .filter_map(|path| {
-            let mut msg = sessions::opencode::parse_opencode_file(path)?;
-            // Recalculate cost using pricing data
-            msg.cost = pricing.calculate_cost(
-                &msg.model_id,
-                msg.tokens.input,
-                msg.tokens.output,
-                msg.tokens.cache_read,
-                msg.tokens.cache_write,
-                msg.tokens.reasoning,
-            );
-            Some(msg)
-        })
-        .collect();
-    all_messages.extend(opencode_messages);
-
-    // Parse Claude files in parallel
-    let claude_messages: Vec<UnifiedMessage> = scan_result
-        .claude_files
-        .par_iter()
-        .flat_map(|path| {
-            sessions::claudecode::parse_claude_file(path)
-                .into_iter()
-                .map(|mut msg| {
-                    msg.cost = pricing.calculate_cost(
-                        &msg.model_id,
-                        msg.tokens.input,
-                        msg.tokens.output,
-                        msg.tokens.cache_read,
-                        msg.tokens.cache_write,
-                        msg.tokens.reasoning,
-                    );
-                    msg
-                })
-                .collect::<Vec<_>>()
-        })
-        .collect();
-    all_messages.extend(claude_messages);
-
-    // Parse Codex files in parallel
-    let codex_messages: Vec<UnifiedMessage> = scan_result
-        .codex_files
-        .par_iter()
-        .flat_map(|path| {
-            sessions::codex::parse_codex_file(path)
-                .into_iter()
-                .map(|mut msg| {
-                    msg.cost = pricing.calculate_cost(
-                        &msg.model_id,
-                        msg.tokens.input,
-                        msg.tokens.output,
-                        msg.tokens.cache_read,
-                        msg.tokens.cache_write,
-                        msg.tokens.reasoning,
-                    );
-                    msg
-                })
-                .collect::<Vec<_>>()
-        })
-        .collect();
-    all_messages.extend(codex_messages);
-
-    // Parse Gemini files in parallel
-    let gemini_messages: Vec<UnifiedMessage> = scan_result
-        .gemini_files
-        .par_iter()
-        .flat_map(|path| {
-            sessions::gemini::parse_gemini_file(path)
-                .into_iter()
-                .map(|mut msg| {
-                    // Gemini: thoughts count as output for billing
-                    msg.cost = pricing.calculate_cost(
-                        &msg.model_id,
-                        msg.tokens.input,
-                        msg.tokens.output + msg.tokens.reasoning,
-                        0, // Gemini cached tokens are free
-                        0,
-                        0,
-                    );
-                    msg
-                })
-                .collect::<Vec<_>>()
-        })
-        .collect();
-    all_messages.extend(gemini_messages);
-
-    // Parse Cursor files in parallel
-    // Calculate cost using our pricing data for consistency with other providers
-    // Fall back to CSV cost only if no pricing is found
-    let cursor_messages: Vec<UnifiedMessage> = scan_result
-        .cursor_files
-        .par_iter()
-        .flat_map(|path| {
-            sessions::cursor::parse_cursor_file(path)
-                .into_iter()
-                .map(|mut msg| {
-                    let csv_cost = msg.cost; // Store original CSV cost
-                    let calculated_cost = pricing.calculate_cost(
-                        &msg.model_id,
-                        msg.tokens.input,
-                        msg.tokens.output,
-                        msg.tokens.cache_read,
-                        msg.tokens.cache_write,
-                        msg.tokens.reasoning,
-                    );
-                    // Use calculated cost if available, otherwise keep CSV cost
-                    msg.cost = if calculated_cost > 0.0 {
-                        calculated_cost
-                    } else {
-                        csv_cost
-                    };
-                    msg
-                })
-                .collect::<Vec<_>>()
-        })
-        .collect();
-    all_messages.extend(cursor_messages);
-
-    // Parse Amp files in parallel
-    // Calculate cost using our pricing data for consistency with other providers
-    // Fall back to credits (original cost) only if no pricing is found
-    let amp_messages: Vec<UnifiedMessage> = scan_result
-        .amp_files
-        .par_iter()
-        .flat_map(|path| {
-            sessions::amp::parse_amp_file(path)
-                .into_iter()
-                .map(|mut msg| {
-                    let credits = msg.cost; // Store original credits value
-                    let calculated_cost = pricing.calculate_cost(
-                        &msg.model_id,
-                        msg.tokens.input,
-                        msg.tokens.output,
-                        msg.tokens.cache_read,
-                        msg.tokens.cache_write,
-                        msg.tokens.reasoning,
-                    );
-                    // Use calculated cost if available, otherwise keep credits
-                    msg.cost = if calculated_cost > 0.0 {
-                        calculated_cost
-                    } else {
-                        credits
-                    };
-                    msg
-                })
-                .collect::<Vec<_>>()
-        })
-        .collect();
-    all_messages.extend(amp_messages);
-
-    // Parse Droid files in parallel
-    let droid_messages: Vec<UnifiedMessage> = scan_result
-        .droid_files
-        .par_iter()
-        .flat_map(|path| {
-            sessions::droid::parse_droid_file(path)
-                .into_iter()
-                .map(|mut msg| {
-                    msg.cost = pricing.calculate_cost(
-                        &msg.model_id,
-                        msg.tokens.input,
-                        msg.tokens.output,
-                        msg.tokens.cache_read,
-                        msg.tokens.cache_write,
-                        msg.tokens.reasoning,
-                    );
-                    msg
-                })
-                .collect::<Vec<_>>()
-        })
-        .collect();
-    all_messages.extend(droid_messages);
+) -> UnifiedMessage {
+    if msg.billed {
+        return msg;
+    }
+    let calculated_cost = match session_type {
+        // Gemini: thoughts count as output for billing, cached tokens are free
+        SessionType::Gemini => pricing.calculate_cost_with_provider(
+            &msg.model_id,
+            Some(msg.provider_id.as_str()),
+            msg.tokens.input,
+            msg.tokens.output + msg.tokens.reasoning,
+            0,
+            0,
+            0,
+        ),
+        _ => pricing.calculate_token_cost_at(&msg.model_id, Some(msg.provider_id.as_str()), &msg.tokens, msg.timestamp),
+    };
 
-    all_messages
+    msg.cost = match session_type {
+        // Cursor (CSV cost), Amp (credits), the cost Cline and Roo Code
+        // computed, and recorded events are kept only when no pricing is found
+        SessionType::Cursor | SessionType::Amp | SessionType::Cline | SessionType::RooCode | SessionType::Recorded
+            if calculated_cost <= 0.0 =>
+        {
+            msg.cost
+        }
+        _ => calculated_cost,
+    };
+    msg
 }
 
-/// Get model usage report with pricing calculation
-#[napi]
-pub async fn get_model_report(options: ReportOptions) -> napi::Result<ModelReport> {
-    let start = Instant::now();
+/// Aggregate the messages of `history` that `options` keep by (source,
+/// provider, model), sorted by cost, with display rounding applied. Free
+/// tiers are used up over all of `history` (see [`free_tier_coverage`]).
+pub fn build_model_report(history: Vec<UnifiedMessage>, options: &ReportOptions, start: Instant) -> ModelReport {
+    let mut messages = history;
+    let mut covered = free_tier_coverage(&messages);
+    retain_with_coverage(&mut messages, &mut covered, |m| report_includes(m, options));
+    let mut report = aggregate_covered_models(&messages, &covered, start);
+    cost_display::settings().apply_to_model_report(&mut report);
+    report
+}
 
-    let home_dir = get_home_dir(&options.home_dir)?;
+/// Aggregate priced messages by (source, provider, model), sorted by cost.
+/// Free tiers and credits from settings cover part of the cost, which is
+/// reported apart. The messages are taken as the whole history; for a
+/// filtered report see [`build_model_report`].
+pub fn aggregate_models(messages: Vec<UnifiedMessage>, start: Instant) -> ModelReport {
+    let covered = free_tier_coverage(&messages);
+    aggregate_covered_models(&messages, &covered, start)
+}
 
-    let sources = options.sources.clone().unwrap_or_else(|| {
-        vec![
-            "opencode".to_string(),
-            "claude".to_string(),
-            "codex".to_string(),
-            "gemini".to_string(),
-            "cursor".to_string(),
-            "amp".to_string(),
-            "droid".to_string(),
-        ]
-    });
+/// Aggregate messages of which free tiers cover `covered` (in the same order)
+pub fn aggregate_covered_models(messages: &[UnifiedMessage], covered: &[f64], start: Instant) -> ModelReport {
+    let mut totals = ModelTotals::default();
+    for (msg, &covered) in messages.iter().zip(covered) {
+        totals.add(msg, covered);
+    }
+    totals.finish(start)
+}
 
-    let pricing = pricing::PricingService::get_or_init()
-        .await
-        .map_err(|e| napi::Error::from_reason(e))?;
-    let all_messages = parse_all_messages_with_pricing(&home_dir, &sources, &pricing);
+/// How much of each message's cost the free tiers in settings cover.
+/// Allowances are used up in time order over all of `history`, so pass
+/// every message before report filters: a report starting mid-month, or
+/// after a credit ran out, then sees only what is left.
+pub fn free_tier_coverage(history: &[UnifiedMessage]) -> Vec<f64> {
+    free_tier::covered(history, &config::settings().free_tiers)
+}
 
-    // Apply date filters
-    let filtered = filter_messages_for_report(all_messages, &options);
+/// Keep the messages `keep` accepts, with their covered amounts
+pub fn retain_with_coverage(
+    messages: &mut Vec<UnifiedMessage>,
+    covered: &mut Vec<f64>,
+    keep: impl Fn(&UnifiedMessage) -> bool,
+) {
+    let kept: Vec<bool> = messages.iter().map(keep).collect();
+    let mut flags = kept.iter();
+    messages.retain(|_| *flags.next().unwrap_or(&false));
+    let mut flags = kept.iter();
+    covered.retain(|_| *flags.next().unwrap_or(&false));
+}
 
-    // Aggregate by model
-    let mut model_map: std::collections::HashMap<String, ModelUsage> =
-        std::collections::HashMap::new();
+/// Running per-model totals, so a report can be built one message at a time
+#[derive(Default)]
+pub(crate) struct ModelTotals {
+    models: std::collections::HashMap<(Source, Provider, String), ModelUsage>,
+}
 
-    for msg in filtered {
-        let key = format!("{}:{}:{}", msg.source, msg.provider_id, msg.model_id);
-        let entry = model_map.entry(key).or_insert_with(|| ModelUsage {
-            source: msg.source.clone(),
+impl ModelTotals {
+    /// Count `msg`, of whose cost a free tier covers `covered`
+    pub(crate) fn add(&mut self, msg: &UnifiedMessage, covered: f64) {
+        let key = (msg.source.clone(), msg.provider_id.clone(), msg.model_id.clone());
+        let entry = self.models.entry(key).or_insert_with(|| ModelUsage {
+            source: msg.source.to_string(),
             model: msg.model_id.clone(),
-            provider: msg.provider_id.clone(),
+            provider: msg.provider_id.to_string(),
             input: 0,
             output: 0,
             cache_read: 0,
             cache_write: 0,
             reasoning: 0,
+            training: 0,
             message_count: 0,
             cost: 0.0,
+            covered: 0.0,
+            cost_share: 0.0,
+            token_share: 0.0,
         });
 
         entry.input += msg.tokens.input;
@@ -503,193 +710,67 @@ pub async fn get_model_report(options: ReportOptions) -> napi::Result<ModelRepor
         entry.cache_read += msg.tokens.cache_read;
         entry.cache_write += msg.tokens.cache_write;
         entry.reasoning += msg.tokens.reasoning;
+        entry.training += msg.tokens.training;
         entry.message_count += 1;
-        entry.cost += msg.cost;
+        entry.cost += msg.cost - covered;
+        entry.covered += covered;
     }
 
-    let mut entries: Vec<ModelUsage> = model_map.into_values().collect();
-    // Sort by cost descending (NaN values sorted to the end)
-    entries.sort_by(|a, b| {
-        // Handle NaN: treat as smallest value so they sort to the end in descending order
-        match (a.cost.is_nan(), b.cost.is_nan()) {
-            (true, true) => std::cmp::Ordering::Equal,
-            (true, false) => std::cmp::Ordering::Greater, // NaN sorts after valid values
-            (false, true) => std::cmp::Ordering::Less,
-            (false, false) => b
-                .cost
-                .partial_cmp(&a.cost)
-                .unwrap_or(std::cmp::Ordering::Equal),
-        }
-    });
-
-    let total_input: i64 = entries.iter().map(|e| e.input).sum();
-    let total_output: i64 = entries.iter().map(|e| e.output).sum();
-    let total_cache_read: i64 = entries.iter().map(|e| e.cache_read).sum();
-    let total_cache_write: i64 = entries.iter().map(|e| e.cache_write).sum();
-    let total_messages: i32 = entries.iter().map(|e| e.message_count).sum();
-    let total_cost: f64 = entries.iter().map(|e| e.cost).sum();
-
-    Ok(ModelReport {
-        entries,
-        total_input,
-        total_output,
-        total_cache_read,
-        total_cache_write,
-        total_messages,
-        total_cost,
-        processing_time_ms: start.elapsed().as_millis() as u32,
-    })
-}
-
-/// Helper struct for aggregating monthly data (avoids clippy::type_complexity)
-#[derive(Default)]
-struct MonthAggregator {
-    models: std::collections::HashSet<String>,
-    input: i64,
-    output: i64,
-    cache_read: i64,
-    cache_write: i64,
-    message_count: i32,
-    cost: f64,
-}
-
-/// Get monthly usage report with pricing calculation
-#[napi]
-pub async fn get_monthly_report(options: ReportOptions) -> napi::Result<MonthlyReport> {
-    let start = Instant::now();
-
-    let home_dir = get_home_dir(&options.home_dir)?;
-
-    let sources = options.sources.clone().unwrap_or_else(|| {
-        vec![
-            "opencode".to_string(),
-            "claude".to_string(),
-            "codex".to_string(),
-            "gemini".to_string(),
-            "cursor".to_string(),
-            "amp".to_string(),
-            "droid".to_string(),
-        ]
-    });
-
-    let pricing = pricing::PricingService::get_or_init()
-        .await
-        .map_err(|e| napi::Error::from_reason(e))?;
-    let all_messages = parse_all_messages_with_pricing(&home_dir, &sources, &pricing);
-
-    // Apply date filters
-    let filtered = filter_messages_for_report(all_messages, &options);
-
-    // Aggregate by month
-    let mut month_map: std::collections::HashMap<String, MonthAggregator> =
-        std::collections::HashMap::new();
-
-    for msg in filtered {
-        // Extract month from date (YYYY-MM-DD -> YYYY-MM)
-        let month = if msg.date.len() >= 7 {
-            msg.date[..7].to_string()
-        } else {
-            continue;
+    /// Report sorted by cost, unrounded
+    pub(crate) fn finish(self, start: Instant) -> ModelReport {
+        let mut entries: Vec<ModelUsage> = self.models.into_values().collect();
+        entries.sort_by(by_cost_desc);
+
+        let total_input: i64 = entries.iter().map(|e| e.input).sum();
+        let total_output: i64 = entries.iter().map(|e| e.output).sum();
+        let total_cache_read: i64 = entries.iter().map(|e| e.cache_read).sum();
+        let total_cache_write: i64 = entries.iter().map(|e| e.cache_write).sum();
+        let total_reasoning: i64 = entries.iter().map(|e| e.reasoning).sum();
+        let total_messages: i32 = entries.iter().map(|e| e.message_count).sum();
+        let total_cost: f64 = entries.iter().map(|e| e.cost).sum();
+        let total_covered: f64 = entries.iter().map(|e| e.covered).sum();
+
+        let mut report = ModelReport {
+            entries,
+            total_input,
+            total_output,
+            total_cache_read,
+            total_cache_write,
+            total_reasoning,
+            total_messages,
+            total_cost,
+            total_covered,
+            processing_time_ms: start.elapsed().as_millis() as u32,
+            summary: None,
+            families: None,
         };
-
-        let entry = month_map.entry(month).or_default();
-
-        entry.models.insert(msg.model_id.clone());
-        entry.input += msg.tokens.input;
-        entry.output += msg.tokens.output;
-        entry.cache_read += msg.tokens.cache_read;
-        entry.cache_write += msg.tokens.cache_write;
-        entry.message_count += 1;
-        entry.cost += msg.cost;
+        shares::apply_to_model_report(&mut report);
+        report
     }
-
-    let mut entries: Vec<MonthlyUsage> = month_map
-        .into_iter()
-        .map(|(month, agg)| MonthlyUsage {
-            month,
-            models: agg.models.into_iter().collect(),
-            input: agg.input,
-            output: agg.output,
-            cache_read: agg.cache_read,
-            cache_write: agg.cache_write,
-            message_count: agg.message_count,
-            cost: agg.cost,
-        })
-        .collect();
-
-    // Sort by month ascending
-    entries.sort_by(|a, b| a.month.cmp(&b.month));
-
-    let total_cost: f64 = entries.iter().map(|e| e.cost).sum();
-
-    Ok(MonthlyReport {
-        entries,
-        total_cost,
-        processing_time_ms: start.elapsed().as_millis() as u32,
-    })
-}
-
-/// Generate graph data with pricing calculation
-#[napi]
-pub async fn generate_graph_with_pricing(options: ReportOptions) -> napi::Result<GraphResult> {
-    let start = Instant::now();
-
-    let home_dir = get_home_dir(&options.home_dir)?;
-
-    let sources = options.sources.clone().unwrap_or_else(|| {
-        vec![
-            "opencode".to_string(),
-            "claude".to_string(),
-            "codex".to_string(),
-            "gemini".to_string(),
-            "cursor".to_string(),
-            "amp".to_string(),
-            "droid".to_string(),
-        ]
-    });
-
-    let pricing = pricing::PricingService::get_or_init()
-        .await
-        .map_err(|e| napi::Error::from_reason(e))?;
-    let all_messages = parse_all_messages_with_pricing(&home_dir, &sources, &pricing);
-
-    // Apply date filters
-    let filtered = filter_messages_for_report(all_messages, &options);
-
-    // Aggregate by date
-    let contributions = aggregator::aggregate_by_date(filtered);
-
-    // Generate result
-    let processing_time_ms = start.elapsed().as_millis() as u32;
-    let result = aggregator::generate_graph_result(contributions, processing_time_ms);
-
-    Ok(result)
 }
 
 /// Filter messages by date range (for reports)
-fn filter_messages_for_report(
+pub fn filter_messages_for_report(
     messages: Vec<UnifiedMessage>,
     options: &ReportOptions,
 ) -> Vec<UnifiedMessage> {
     let mut filtered = messages;
+    filtered.retain(|m| report_includes(m, options));
+    filtered
+}
 
-    // Filter by year
-    if let Some(year) = &options.year {
-        let year_prefix = format!("{}-", year);
-        filtered.retain(|m| m.date.starts_with(&year_prefix));
-    }
-
-    // Filter by since date
-    if let Some(since) = &options.since {
-        filtered.retain(|m| m.date.as_str() >= since.as_str());
-    }
-
-    // Filter by until date
-    if let Some(until) = &options.until {
-        filtered.retain(|m| m.date.as_str() <= until.as_str());
-    }
+/// Whether `options` keep `msg` in a report
+pub fn report_includes(msg: &UnifiedMessage, options: &ReportOptions) -> bool {
+    !(options.exclude_estimated_timestamps.unwrap_or(false) && msg.estimated_timestamp)
+        && in_dates(&msg.date, options.since.as_deref(), options.until.as_deref(), options.year.as_deref())
+}
 
-    filtered
+/// Whether `date` (YYYY-MM-DD) is in `year` and between `since` and `until`,
+/// inclusive
+pub fn in_dates(date: &str, since: Option<&str>, until: Option<&str>, year: Option<&str>) -> bool {
+    year.is_none_or(|year| date.strip_prefix(year).is_some_and(|rest| rest.starts_with('-')))
+        && since.is_none_or(|since| date >= since)
+        && until.is_none_or(|until| date <= until)
 }
 
 // =============================================================================
@@ -698,12 +779,9 @@ fn filter_messages_for_report(
 
 /// Parse local sources only (OpenCode, Claude, Codex, Gemini - NO Cursor)
 /// This can run in parallel with network operations (Cursor sync, pricing fetch)
-#[napi]
-pub fn parse_local_sources(options: LocalParseOptions) -> napi::Result<ParsedMessages> {
+pub fn parse_local(home_dir: &str, options: &LocalParseOptions) -> ParsedMessages {
     let start = Instant::now();
 
-    let home_dir = get_home_dir(&options.home_dir)?;
-
     // Default to local sources only (no cursor)
     let sources = options.sources.clone().unwrap_or_else(|| {
         vec![
@@ -713,113 +791,102 @@ pub fn parse_local_sources(options: LocalParseOptions) -> napi::Result<ParsedMes
             "gemini".to_string(),
             "amp".to_string(),
             "droid".to_string(),
+            "cline".to_string(),
+            "roocode".to_string(),
         ]
     });
 
     // Filter out cursor if somehow included
     let local_sources: Vec<String> = sources.into_iter().filter(|s| s != "cursor").collect();
 
-    let scan_result = scanner::scan_all_sources(&home_dir, &local_sources);
+    let scan_result = scanner::scan_all_sources(home_dir, &local_sources);
+    let requested = |session_type: SessionType| {
+        local_sources.is_empty() || local_sources.iter().any(|s| s == session_type.name())
+    };
+    let mut files: Vec<SourceFiles> = Vec::new();
+    let mut parse_source = |session_type: SessionType, paths: &[std::path::PathBuf]| {
+        let per_file: Vec<Vec<UnifiedMessage>> =
+            paths.par_iter().map(|path| parse_file(session_type, path)).collect();
+        if requested(session_type) {
+            files.push(run_summary::count_files(session_type.name(), &per_file, options.sources.is_some()));
+        }
+        per_file.into_iter().flatten().collect::<Vec<_>>()
+    };
 
     let mut messages: Vec<ParsedMessage> = Vec::new();
 
-    // Parse OpenCode files in parallel
-    let opencode_msgs: Vec<ParsedMessage> = scan_result
-        .opencode_files
-        .par_iter()
-        .filter_map(|path| {
-            let msg = sessions::opencode::parse_opencode_file(path)?;
-            Some(unified_to_parsed(&msg))
-        })
-        .collect();
+    let opencode_msgs: Vec<ParsedMessage> =
+        parse_source(SessionType::OpenCode, &scan_result.opencode_files).iter().map(unified_to_parsed).collect();
     let opencode_count = opencode_msgs.len() as i32;
     messages.extend(opencode_msgs);
 
-    // Parse Claude files in parallel, then deduplicate globally
-    let claude_msgs_raw: Vec<(String, ParsedMessage)> = scan_result
-        .claude_files
-        .par_iter()
-        .flat_map(|path| {
-            sessions::claudecode::parse_claude_file(path)
-                .into_iter()
-                .map(|msg| {
-                    let dedup_key = msg.dedup_key.clone().unwrap_or_default();
-                    (dedup_key, unified_to_parsed(&msg))
-                })
-                .collect::<Vec<_>>()
-        })
-        .collect();
-
-    // Global deduplication across all Claude files
+    // Deduplicate Claude messages globally, across all Claude files
+    let claude_raw = parse_source(SessionType::Claude, &scan_result.claude_files);
+    let claude_parsed = claude_raw.len();
     let mut seen_keys: std::collections::HashSet<String> = std::collections::HashSet::new();
-    let claude_msgs: Vec<ParsedMessage> = claude_msgs_raw
-        .into_iter()
-        .filter(|(key, _)| key.is_empty() || seen_keys.insert(key.clone()))
-        .map(|(_, msg)| msg)
+    let claude_msgs: Vec<ParsedMessage> = claude_raw
+        .iter()
+        .filter(|msg| match &msg.dedup_key {
+            Some(key) if !key.is_empty() => seen_keys.insert(key.clone()),
+            _ => true,
+        })
+        .map(unified_to_parsed)
         .collect();
     let claude_count = claude_msgs.len() as i32;
     messages.extend(claude_msgs);
 
-    // Parse Codex files in parallel
-    let codex_msgs: Vec<ParsedMessage> = scan_result
-        .codex_files
-        .par_iter()
-        .flat_map(|path| {
-            sessions::codex::parse_codex_file(path)
-                .into_iter()
-                .map(|msg| unified_to_parsed(&msg))
-                .collect::<Vec<_>>()
-        })
-        .collect();
+    let codex_msgs: Vec<ParsedMessage> =
+        parse_source(SessionType::Codex, &scan_result.codex_files).iter().map(unified_to_parsed).collect();
     let codex_count = codex_msgs.len() as i32;
     messages.extend(codex_msgs);
 
-    // Parse Gemini files in parallel
-    let gemini_msgs: Vec<ParsedMessage> = scan_result
-        .gemini_files
-        .par_iter()
-        .flat_map(|path| {
-            sessions::gemini::parse_gemini_file(path)
-                .into_iter()
-                .map(|msg| unified_to_parsed(&msg))
-                .collect::<Vec<_>>()
-        })
-        .collect();
+    let gemini_msgs: Vec<ParsedMessage> =
+        parse_source(SessionType::Gemini, &scan_result.gemini_files).iter().map(unified_to_parsed).collect();
     let gemini_count = gemini_msgs.len() as i32;
     messages.extend(gemini_msgs);
 
-    // Parse Amp files in parallel
-    let amp_msgs: Vec<ParsedMessage> = scan_result
-        .amp_files
-        .par_iter()
-        .flat_map(|path| {
-            sessions::amp::parse_amp_file(path)
-                .into_iter()
-                .map(|msg| unified_to_parsed(&msg))
-                .collect::<Vec<_>>()
-        })
-        .collect();
+    let amp_msgs: Vec<ParsedMessage> =
+        parse_source(SessionType::Amp, &scan_result.amp_files).iter().map(unified_to_parsed).collect();
     let amp_count = amp_msgs.len() as i32;
     messages.extend(amp_msgs);
 
-    // Parse Droid files in parallel
-    let droid_msgs: Vec<ParsedMessage> = scan_result
-        .droid_files
-        .par_iter()
-        .flat_map(|path| {
-            sessions::droid::parse_droid_file(path)
-                .into_iter()
-                .map(|msg| unified_to_parsed(&msg))
-                .collect::<Vec<_>>()
-        })
-        .collect();
+    let droid_msgs: Vec<ParsedMessage> =
+        parse_source(SessionType::Droid, &scan_result.droid_files).iter().map(unified_to_parsed).collect();
     let droid_count = droid_msgs.len() as i32;
     messages.extend(droid_msgs);
 
+    let cline_msgs: Vec<ParsedMessage> =
+        parse_source(SessionType::Cline, &scan_result.cline_files).iter().map(unified_to_parsed).collect();
+    let cline_count = cline_msgs.len() as i32;
+    messages.extend(cline_msgs);
+
+    let roocode_msgs: Vec<ParsedMessage> =
+        parse_source(SessionType::RooCode, &scan_result.roocode_files).iter().map(unified_to_parsed).collect();
+    let roocode_count = roocode_msgs.len() as i32;
+    messages.extend(roocode_msgs);
+
+    // Drop events recorded twice under the same id
+    let recorded_raw = parse_source(SessionType::Recorded, &scan_result.recorded_files);
+    let recorded_parsed = recorded_raw.len();
+    let recorded_msgs: Vec<ParsedMessage> =
+        sessions::recorded::dedup(recorded_raw).iter().map(unified_to_parsed).collect();
+    let recorded_count = recorded_msgs.len() as i32;
+    messages.extend(recorded_msgs);
+    let deduped = (claude_parsed - claude_count as usize) + (recorded_parsed - recorded_count as usize);
+
+    // Imported OpenRouter generations replace the session messages they match
+    openrouter_import::drop_replaced(&mut messages, |msg| openrouter_import::Usage {
+        provider: &msg.provider_id,
+        model: &msg.model_id,
+        timestamp: msg.timestamp,
+        completion: msg.output + msg.reasoning,
+        billed: msg.billed_cost.is_some(),
+    });
+
     // Apply date filters
-    let filtered = filter_parsed_messages(messages, &options);
+    let filtered = filter_parsed_messages(messages, options);
 
-    Ok(ParsedMessages {
+    ParsedMessages {
         messages: filtered,
         opencode_count,
         claude_count,
@@ -827,15 +894,20 @@ pub fn parse_local_sources(options: LocalParseOptions) -> napi::Result<ParsedMes
         gemini_count,
         amp_count,
         droid_count,
+        cline_count,
+        roocode_count,
+        recorded_count,
         processing_time_ms: start.elapsed().as_millis() as u32,
-    })
+        files: Some(files),
+        deduped: Some(deduped as i32),
+    }
 }
 
 fn unified_to_parsed(msg: &UnifiedMessage) -> ParsedMessage {
     ParsedMessage {
-        source: msg.source.clone(),
+        source: msg.source.to_string(),
         model_id: msg.model_id.clone(),
-        provider_id: msg.provider_id.clone(),
+        provider_id: msg.provider_id.to_string(),
         session_id: msg.session_id.clone(),
         timestamp: msg.timestamp,
         date: msg.date.clone(),
@@ -844,7 +916,11 @@ fn unified_to_parsed(msg: &UnifiedMessage) -> ParsedMessage {
         cache_read: msg.tokens.cache_read,
         cache_write: msg.tokens.cache_write,
         reasoning: msg.tokens.reasoning,
+        training: Some(msg.tokens.training).filter(|&n| n > 0),
         agent: msg.agent.clone(),
+        estimated_timestamp: Some(msg.estimated_timestamp),
+        cwd: msg.cwd.clone(),
+        billed_cost: msg.billed.then_some(msg.cost),
     }
 }
 
@@ -855,27 +931,19 @@ fn filter_parsed_messages(
 ) -> Vec<ParsedMessage> {
     let mut filtered = messages;
 
-    if let Some(year) = &options.year {
-        let year_prefix = format!("{}-", year);
-        filtered.retain(|m| m.date.starts_with(&year_prefix));
-    }
-
-    if let Some(since) = &options.since {
-        filtered.retain(|m| m.date.as_str() >= since.as_str());
-    }
-
-    if let Some(until) = &options.until {
-        filtered.retain(|m| m.date.as_str() <= until.as_str());
+    if options.exclude_estimated_timestamps.unwrap_or(false) {
+        filtered.retain(|m| m.estimated_timestamp != Some(true));
     }
 
+    filtered.retain(|m| in_dates(&m.date, options.since.as_deref(), options.until.as_deref(), options.year.as_deref()));
     filtered
 }
 
 fn parsed_to_unified(msg: &ParsedMessage, cost: f64) -> UnifiedMessage {
     UnifiedMessage {
-        source: msg.source.clone(),
+        source: msg.source.as_str().into(),
         model_id: msg.model_id.clone(),
-        provider_id: msg.provider_id.clone(),
+        provider_id: msg.provider_id.as_str().into(),
         session_id: msg.session_id.clone(),
         timestamp: msg.timestamp,
         date: msg.date.clone(),
@@ -885,152 +953,51 @@ fn parsed_to_unified(msg: &ParsedMessage, cost: f64) -> UnifiedMessage {
             cache_read: msg.cache_read,
             cache_write: msg.cache_write,
             reasoning: msg.reasoning,
+            training: msg.training.unwrap_or(0),
         },
         cost,
         agent: msg.agent.clone(),
         dedup_key: None,
+        estimated_timestamp: msg.estimated_timestamp.unwrap_or(false),
+        naive_timestamp: false,
+        cwd: msg.cwd.clone(),
+        git_branch: None,
+        sidechain: false,
+        tool_call: false,
+        prompt_chars: None,
+        tool_calls: 0,
+        files_touched: None,
+        billed: msg.billed_cost.is_some(),
     }
 }
 
-/// Finalize model report: apply pricing to local messages, add Cursor, aggregate
-#[napi]
-pub async fn finalize_report(options: FinalizeReportOptions) -> napi::Result<ModelReport> {
-    let start = Instant::now();
-
-    let home_dir = get_home_dir(&options.home_dir)?;
-
-    let pricing = pricing::PricingService::get_or_init()
-        .await
-        .map_err(|e| napi::Error::from_reason(e))?;
-
-    // Convert local messages and apply pricing
-    let mut all_messages: Vec<UnifiedMessage> = options
-        .local_messages
-        .messages
-        .iter()
-        .map(|msg| {
-            let cost = pricing.calculate_cost(
-                &msg.model_id,
-                msg.input,
-                msg.output,
-                msg.cache_read,
-                msg.cache_write,
-                msg.reasoning,
-            );
-            parsed_to_unified(msg, cost)
-        })
-        .collect();
-
-    // Add Cursor messages if enabled
-    if options.include_cursor {
-        let cursor_cache_dir = format!("{}/.config/tokscale/cursor-cache", home_dir);
-        let cursor_files = scanner::scan_directory(&cursor_cache_dir, "*.csv");
-
-        let cursor_messages: Vec<UnifiedMessage> = cursor_files
-            .par_iter()
-            .flat_map(|path| {
-                sessions::cursor::parse_cursor_file(path)
-                    .into_iter()
-                    .map(|mut msg| {
-                        let csv_cost = msg.cost;
-                        let calculated_cost = pricing.calculate_cost(
-                            &msg.model_id,
-                            msg.tokens.input,
-                            msg.tokens.output,
-                            msg.tokens.cache_read,
-                            msg.tokens.cache_write,
-                            msg.tokens.reasoning,
-                        );
-                        msg.cost = if calculated_cost > 0.0 {
-                            calculated_cost
-                        } else {
-                            csv_cost
-                        };
-                        msg
-                    })
-                    .collect::<Vec<_>>()
-            })
-            .collect();
-
-        all_messages.extend(cursor_messages);
-    }
-
-    // Apply date filters to cursor messages (local already filtered)
-    if options.include_cursor {
-        if let Some(year) = &options.year {
-            let year_prefix = format!("{}-", year);
-            all_messages.retain(|m| m.date.starts_with(&year_prefix));
-        }
-        if let Some(since) = &options.since {
-            all_messages.retain(|m| m.date.as_str() >= since.as_str());
-        }
-        if let Some(until) = &options.until {
-            all_messages.retain(|m| m.date.as_str() <= until.as_str());
-        }
-    }
-
-    // Aggregate by model
-    let mut model_map: std::collections::HashMap<String, ModelUsage> =
-        std::collections::HashMap::new();
-
-    for msg in all_messages {
-        let key = format!("{}:{}:{}", msg.source, msg.provider_id, msg.model_id);
-        let entry = model_map.entry(key).or_insert_with(|| ModelUsage {
-            source: msg.source.clone(),
-            model: msg.model_id.clone(),
-            provider: msg.provider_id.clone(),
-            input: 0,
-            output: 0,
-            cache_read: 0,
-            cache_write: 0,
-            reasoning: 0,
-            message_count: 0,
-            cost: 0.0,
-        });
-
-        entry.input += msg.tokens.input;
-        entry.output += msg.tokens.output;
-        entry.cache_read += msg.tokens.cache_read;
-        entry.cache_write += msg.tokens.cache_write;
-        entry.reasoning += msg.tokens.reasoning;
-        entry.message_count += 1;
-        entry.cost += msg.cost;
+/// Convert a message handed back from JS and price it. Billed costs stay.
+pub fn price_parsed(msg: &ParsedMessage, pricing: &pricing::PricingService) -> UnifiedMessage {
+    if let Some(cost) = msg.billed_cost {
+        return parsed_to_unified(msg, cost);
     }
-
-    let mut entries: Vec<ModelUsage> = model_map.into_values().collect();
-    entries.sort_by(|a, b| match (a.cost.is_nan(), b.cost.is_nan()) {
-        (true, true) => std::cmp::Ordering::Equal,
-        (true, false) => std::cmp::Ordering::Greater,
-        (false, true) => std::cmp::Ordering::Less,
-        (false, false) => b
-            .cost
-            .partial_cmp(&a.cost)
-            .unwrap_or(std::cmp::Ordering::Equal),
-    });
-
-    let total_input: i64 = entries.iter().map(|e| e.input).sum();
-    let total_output: i64 = entries.iter().map(|e| e.output).sum();
-    let total_cache_read: i64 = entries.iter().map(|e| e.cache_read).sum();
-    let total_cache_write: i64 = entries.iter().map(|e| e.cache_write).sum();
-    let total_messages: i32 = entries.iter().map(|e| e.message_count).sum();
-    let total_cost: f64 = entries.iter().map(|e| e.cost).sum();
-
-    Ok(ModelReport {
-        entries,
-        total_input,
-        total_output,
-        total_cache_read,
-        total_cache_write,
-        total_messages,
-        total_cost,
-        processing_time_ms: start.elapsed().as_millis() as u32,
-    })
+    let mut msg = parsed_to_unified(msg, 0.0);
+    msg.cost = pricing.calculate_token_cost_at(&msg.model_id, Some(msg.provider_id.as_str()), &msg.tokens, msg.timestamp);
+    msg
 }
 
 /// Options for finalizing monthly report
-#[napi(object)]
+#[cfg_attr(feature = "napi", napi(object))]
 #[derive(Debug, Clone)]
 pub struct FinalizeMonthlyOptions {
+    pub home_dir: Option<String>,
+    pub local_messages: ParsedMessages,
+    pub include_cursor: bool,
+    pub since: Option<String>,
+    pub until: Option<String>,
+    pub year: Option<String>,    /// Reproduce the report as of this date (YYYY-MM-DD)
+    pub as_of: Option<String>,
+}
+
+/// Options for finalizing graph
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone)]
+pub struct FinalizeGraphOptions {
     pub home_dir: Option<String>,
     pub local_messages: ParsedMessages,
     pub include_cursor: bool,
@@ -1039,418 +1006,1340 @@ pub struct FinalizeMonthlyOptions {
     pub year: Option<String>,
 }
 
-/// Finalize monthly report
-#[napi]
-pub async fn finalize_monthly_report(options: FinalizeMonthlyOptions) -> napi::Result<MonthlyReport> {
-    let start = Instant::now();
+/// One session in the TUI session browser
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone)]
+pub struct SessionEntry {
+    pub source: String,
+    pub session_id: String,
+    /// Repository (or directory) name, for sources that record the working
+    /// directory
+    pub project: Option<String>,
+    /// Sorted
+    pub models: Vec<String>,
+    /// First and last message, Unix milliseconds
+    pub start: i64,
+    pub end: i64,
+    pub message_count: i32,
+    pub tokens: i64,
+    pub cost: f64,
+    /// Cost in equal slices of `start..=end`, oldest first
+    pub timeline: Vec<f64>,
+}
 
-    let home_dir = get_home_dir(&options.home_dir)?;
+/// One session, redacted for sharing (see [`session_bundle`])
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionBundle {
+    /// Always "tokscale-session-bundle"
+    pub format: String,
+    pub version: u32,
+    /// tokscale version that wrote the bundle
+    pub tokscale_version: String,
+    /// RFC 3339
+    pub created_at: String,
+    pub session: BundleSession,
+    /// Oldest first
+    pub messages: Vec<BundleMessage>,
+}
 
-    let pricing = pricing::PricingService::get_or_init()
-        .await
-        .map_err(|e| napi::Error::from_reason(e))?;
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BundleSession {
+    /// Hashed session ID
+    pub id: String,
+    pub source: String,
+    /// Sorted
+    pub models: Vec<String>,
+    /// First and last message, Unix milliseconds
+    pub start: i64,
+    pub end: i64,
+    pub message_count: u32,
+    pub tokens: TokenBreakdown,
+    pub cost: f64,
+}
 
-    // Convert local messages and apply pricing
-    let mut all_messages: Vec<UnifiedMessage> = options
-        .local_messages
-        .messages
-        .iter()
-        .map(|msg| {
-            let cost = pricing.calculate_cost(
-                &msg.model_id,
-                msg.input,
-                msg.output,
-                msg.cache_read,
-                msg.cache_write,
-                msg.reasoning,
-            );
-            parsed_to_unified(msg, cost)
-        })
-        .collect();
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BundleMessage {
+    /// Hashed message ID, for sources that record one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// Milliseconds since the session's first message
+    pub offset_ms: i64,
+    pub model: String,
+    pub provider: String,
+    pub tokens: TokenBreakdown,
+    pub cost: f64,
+    /// `cost` was billed by the provider rather than estimated
+    #[serde(default)]
+    pub billed: bool,
+    /// Written by a sub-agent
+    #[serde(default)]
+    pub sidechain: bool,
+    #[serde(default)]
+    pub tool_calls: u32,
+    /// Timestamp was synthesized rather than read from the session file
+    #[serde(default)]
+    pub estimated_timestamp: bool,
+}
 
-    // Add Cursor messages if enabled
-    if options.include_cursor {
-        let cursor_cache_dir = format!("{}/.config/tokscale/cursor-cache", home_dir);
-        let cursor_files = scanner::scan_directory(&cursor_cache_dir, "*.csv");
-
-        let cursor_messages: Vec<UnifiedMessage> = cursor_files
-            .par_iter()
-            .flat_map(|path| {
-                sessions::cursor::parse_cursor_file(path)
-                    .into_iter()
-                    .map(|mut msg| {
-                        let csv_cost = msg.cost;
-                        let calculated_cost = pricing.calculate_cost(
-                            &msg.model_id,
-                            msg.tokens.input,
-                            msg.tokens.output,
-                            msg.tokens.cache_read,
-                            msg.tokens.cache_write,
-                            msg.tokens.reasoning,
-                        );
-                        msg.cost = if calculated_cost > 0.0 {
-                            calculated_cost
-                        } else {
-                            csv_cost
-                        };
-                        msg
-                    })
-                    .collect::<Vec<_>>()
-            })
-            .collect();
-
-        all_messages.extend(cursor_messages);
-    }
+#[cfg_attr(feature = "napi", napi(object))]
+pub struct ShareSessionOptions {
+    pub home_dir: Option<String>,
+    pub sources: Option<Vec<String>>,
+    /// Session ID, or an unambiguous prefix of one
+    pub session_id: String,
+}
 
-    // Apply date filters
-    if let Some(year) = &options.year {
-        let year_prefix = format!("{}-", year);
-        all_messages.retain(|m| m.date.starts_with(&year_prefix));
-    }
-    if let Some(since) = &options.since {
-        all_messages.retain(|m| m.date.as_str() >= since.as_str());
-    }
-    if let Some(until) = &options.until {
-        all_messages.retain(|m| m.date.as_str() <= until.as_str());
-    }
+/// Combined result for report and graph (single pricing lookup)
+#[cfg_attr(feature = "napi", napi(object))]
+pub struct ReportAndGraph {
+    pub report: ModelReport,
+    pub graph: GraphResult,
+    /// Fine-grained buckets for the last few days, ignoring date filters
+    pub recent: RecentUsage,
+    /// Every session in the date range, most recent first
+    pub sessions: Vec<SessionEntry>,
+}
 
-    // Aggregate by month
-    let mut month_map: std::collections::HashMap<String, MonthAggregator> =
-        std::collections::HashMap::new();
+// =============================================================================
+// New Pricing API (Rust-native pricing fetching)
+// =============================================================================
 
-    for msg in all_messages {
-        let month = if msg.date.len() >= 7 {
-            msg.date[..7].to_string()
-        } else {
-            continue;
-        };
+#[cfg_attr(feature = "napi", napi(object))]
+pub struct NativePricing {
+    pub input_cost_per_token: f64,
+    pub output_cost_per_token: f64,
+    pub cache_read_input_token_cost: Option<f64>,
+    pub cache_creation_input_token_cost: Option<f64>,
+    pub training_cost_per_token: Option<f64>,
+    /// USD per million input tokens
+    pub input_per_million: f64,
+    /// USD per million output tokens
+    pub output_per_million: f64,
+    pub cache_read_per_million: Option<f64>,
+    pub cache_write_per_million: Option<f64>,
+    /// USD per million tokens at the default 3:1 input:output mix
+    pub blended_per_million: Option<f64>,
+}
 
-        let entry = month_map.entry(month).or_default();
-        entry.models.insert(msg.model_id.clone());
-        entry.input += msg.tokens.input;
-        entry.output += msg.tokens.output;
-        entry.cache_read += msg.tokens.cache_read;
-        entry.cache_write += msg.tokens.cache_write;
-        entry.message_count += 1;
-        entry.cost += msg.cost;
-    }
+#[cfg_attr(feature = "napi", napi(object))]
+pub struct PricingLookupResult {
+    pub model_id: String,
+    pub matched_key: String,
+    pub matched_prefix: Option<String>,
+    pub source: String,
+    pub pricing: NativePricing,
+}
 
-    let mut entries: Vec<MonthlyUsage> = month_map
-        .into_iter()
-        .map(|(month, agg)| MonthlyUsage {
-            month,
-            models: agg.models.into_iter().collect(),
-            input: agg.input,
-            output: agg.output,
-            cache_read: agg.cache_read,
-            cache_write: agg.cache_write,
-            message_count: agg.message_count,
-            cost: agg.cost,
-        })
-        .collect();
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone)]
+pub struct RateComparisonRow {
+    pub model_id: String,
+    /// Price table key the model resolved to
+    pub matched_key: String,
+    /// USD per million blended tokens
+    pub blended_per_million: f64,
+    /// Blended rate over the cheapest model's; 1 for the cheapest
+    pub relative: f64,
+}
+
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone)]
+pub struct RateComparison {
+    /// Cheapest first
+    pub rows: Vec<RateComparisonRow>,
+    /// Models with no input price
+    pub unpriced: Vec<String>,
+    /// The rows as an aligned text table
+    pub table: String,
+}
 
-    entries.sort_by(|a, b| a.month.cmp(&b.month));
-    let total_cost: f64 = entries.iter().map(|e| e.cost).sum();
+// =============================================================================
+// Pricing health
+// =============================================================================
 
-    Ok(MonthlyReport {
-        entries,
-        total_cost,
-        processing_time_ms: start.elapsed().as_millis() as u32,
-    })
+/// A provider's status page, or a probe of its API
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone)]
+pub struct ProviderStatus {
+    /// "GitHub", "OpenRouter", "Anthropic", "OpenAI"
+    pub name: String,
+    /// Pricing source this provider serves ("litellm", "openrouter"), if any
+    pub pricing_source: Option<String>,
+    pub url: String,
+    /// "operational", "degraded", "outage" or "unreachable"
+    pub state: String,
+    pub description: String,
 }
 
-/// Options for finalizing graph
-#[napi(object)]
+#[cfg_attr(feature = "napi", napi(object))]
 #[derive(Debug, Clone)]
-pub struct FinalizeGraphOptions {
+pub struct PricingSourceCheck {
+    /// "litellm" or "openrouter"
+    pub name: String,
+    /// Whether pricing is unavailable without it
+    pub required: bool,
+    pub ok: bool,
+    /// Models in the fetched table
+    pub model_count: u32,
+    pub error: Option<String>,
+    pub duration_ms: u32,
+    /// When the cached table in use was fetched (RFC 3339), if there is one
+    pub cached_at: Option<String>,
+    /// For a failed fetch with provider status checked: whose side it failed on
+    pub diagnosis: Option<String>,
+}
+
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone)]
+pub struct PricingHealth {
+    pub sources: Vec<PricingSourceCheck>,
+    /// Checked when a fetch failed or when asked for; empty otherwise
+    pub providers: Vec<ProviderStatus>,
+}
+
+/// Pricing fetch counters for one source, since the process started
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone)]
+pub struct PricingTelemetry {
+    pub source: String,
+    pub cache_hits: u32,
+    pub cache_misses: u32,
+    /// Tables fetched (or attempted) because the cache had none
+    pub fetches: u32,
+    pub failures: u32,
+    pub requests: u32,
+    pub retries: u32,
+    /// Response bytes downloaded
+    pub bytes: i64,
+    /// Wall-clock time of fetches
+    pub fetch_ms: u32,
+    /// Waiting on responses, summed over requests (which overlap for OpenRouter)
+    pub network_ms: u32,
+    /// Decoding JSON, summed over responses
+    pub parse_ms: u32,
+}
+
+// =============================================================================
+// Settings
+// =============================================================================
+
+/// Outcome of re-reading settings.json
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone)]
+pub struct SettingsReload {
+    /// "unchanged", "applied" or "rejected"
+    pub status: String,
+    /// Why the file was rejected; the previous settings stay in effect
+    pub error: Option<String>,
+}
+
+/// Problem found in settings.json
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone)]
+pub struct SettingsIssue {
+    /// "error" (the file is rejected) or "warning" (the file loads)
+    pub severity: String,
+    /// Dotted key path, e.g. "costDisplay.precision"; empty for malformed JSON
+    pub path: String,
+    /// 1-based; 0 when unknown
+    pub line: u32,
+    pub column: u32,
+    pub message: String,
+}
+
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone)]
+pub struct SettingsCheck {
+    pub path: String,
+    pub exists: bool,
+    /// In file order
+    pub issues: Vec<SettingsIssue>,
+}
+
+// =============================================================================
+// API keys
+// =============================================================================
+
+/// Where an API key was found, without the key itself
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone)]
+pub struct ApiKeyStatus {
+    pub name: String,
+    /// "keychain" or "env"; unset when no key was found
+    pub backend: Option<String>,
+    /// Environment variable checked when the keychain has no key
+    pub env_var: String,
+}
+
+// =============================================================================
+// Team encryption
+// =============================================================================
+
+/// A team key pair in age format
+#[cfg(feature = "encryption")]
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone)]
+pub struct TeamKey {
+    /// "age1..."; share this with everyone who uploads
+    pub public_key: String,
+    /// "AGE-SECRET-KEY-1..."; only the team's readers need it
+    pub secret_key: String,
+}
+
+// =============================================================================
+// Recorded usage
+// =============================================================================
+
+/// Rejected line from `recordEvents`
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone)]
+pub struct RecordError {
+    /// 1-based line number within the input
+    pub line: u32,
+    pub message: String,
+}
+
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone)]
+pub struct RecordResult {
+    pub recorded: u32,
+    pub errors: Vec<RecordError>,
+}
+
+/// Unreadable line in a recorded-events file
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone)]
+pub struct DamagedRecord {
+    pub file: String,
+    /// 1-based
+    pub line: u32,
+    pub reason: String,
+}
+
+/// Options for `importOpenrouterGenerations`
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone)]
+pub struct OpenRouterImportOptions {
     pub home_dir: Option<String>,
-    pub local_messages: ParsedMessages,
-    pub include_cursor: bool,
+    /// Generation IDs to import, besides those the proxy recorded
+    pub ids: Option<Vec<String>>,
+    /// Text to find generation IDs in, such as OpenRouter's activity CSV
+    pub ids_text: Option<String>,
+}
+
+/// Generation that couldn't be imported
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone)]
+pub struct ImportFailure {
+    pub id: String,
+    pub reason: String,
+}
+
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone)]
+pub struct OpenRouterImportResult {
+    pub imported: u32,
+    /// Imported before
+    pub skipped: u32,
+    pub failed: Vec<ImportFailure>,
+}
+
+// =============================================================================
+// Metering proxy
+// =============================================================================
+
+/// Options for `runProxy`
+#[cfg(feature = "server")]
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone)]
+pub struct ProxyOptions {
+    /// Interface to listen on (default: 127.0.0.1)
+    pub host: Option<String>,
+    /// Port to listen on (default: 8787)
+    pub port: Option<u32>,
+    /// Base URL for OpenAI-format requests (default: https://api.openai.com)
+    pub openai_upstream: Option<String>,
+    /// Base URL for Anthropic requests (default: https://api.anthropic.com)
+    pub anthropic_upstream: Option<String>,
+    pub home_dir: Option<String>,
+}
+
+// =============================================================================
+// Serve mode
+// =============================================================================
+
+/// Options for `runServer`
+#[cfg(feature = "server")]
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone)]
+pub struct ServeOptions {
+    /// Interface to listen on (default: 127.0.0.1); other addresses need a token
+    pub host: Option<String>,
+    /// Port to listen on (default: 8788)
+    pub port: Option<u32>,
+    /// Requests each client may make per minute (default: 120; 0 for no limit)
+    pub rate_limit_per_minute: Option<u32>,
+    /// Also write webhook events to stdout as NDJSON
+    pub emit_events: Option<bool>,
+    pub home_dir: Option<String>,
+}
+
+/// API token for serve mode, without its secret
+#[cfg(feature = "server")]
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone)]
+pub struct ServeToken {
+    pub name: String,
+    /// "read", "push" or "admin"
+    pub scope: String,
+    pub created_at: String,
+}
+
+/// Options for `pushUsage`
+#[cfg(feature = "server")]
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone)]
+pub struct PushOptions {
+    /// Base URL of a `tokscale serve` server, e.g. https://usage.example.com
+    pub server: String,
+    /// Push-scope token; defaults to the "team-server" secret
+    pub token: Option<String>,
+    /// Events per request (default 500)
+    pub batch_size: Option<u32>,
+    pub home_dir: Option<String>,
+    pub sources: Option<Vec<String>>,
+    pub since: Option<String>,
+}
+
+#[cfg(feature = "server")]
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone)]
+pub struct PushResult {
+    /// Events the server recorded
+    pub recorded: u32,
+    /// Events the server already had
+    pub skipped: u32,
+    /// Events older than the server's watermark, not sent
+    pub unsent: u32,
+    pub batches: u32,
+    /// Server watermark after the push (Unix milliseconds)
+    pub watermark: Option<f64>,
+}
+
+// =============================================================================
+// Chart series
+// =============================================================================
+
+/// Options for a downsampled chart series
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone)]
+pub struct ChartSeriesOptions {
+    pub home_dir: Option<String>,
+    pub sources: Option<Vec<String>>,
     pub since: Option<String>,
     pub until: Option<String>,
     pub year: Option<String>,
+    pub exclude_estimated_timestamps: Option<bool>,
+    /// Maximum number of points to return (default 500)
+    pub points: Option<u32>,
+    /// "tokens" (default) or "cost"
+    pub metric: Option<String>,
+    /// "lttb" (default) or "max" (largest value per time bucket)
+    pub method: Option<String>,
 }
 
-/// Finalize graph
-#[napi]
-pub async fn finalize_graph(options: FinalizeGraphOptions) -> napi::Result<GraphResult> {
-    let start = Instant::now();
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone)]
+pub struct ChartPoint {
+    /// Unix milliseconds
+    pub timestamp: i64,
+    pub value: f64,
+}
 
-    let home_dir = get_home_dir(&options.home_dir)?;
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone)]
+pub struct ChartSeries {
+    pub metric: String,
+    pub method: String,
+    pub points: Vec<ChartPoint>,
+    /// Number of points before downsampling (one per message)
+    pub total_points: u32,
+    pub processing_time_ms: u32,
+}
 
-    let pricing = pricing::PricingService::get_or_init()
-        .await
-        .map_err(|e| napi::Error::from_reason(e))?;
+// =============================================================================
+// Recent usage
+// =============================================================================
 
-    // Convert local messages and apply pricing
-    let mut all_messages: Vec<UnifiedMessage> = options
-        .local_messages
-        .messages
-        .iter()
-        .map(|msg| {
-            let cost = pricing.calculate_cost(
-                &msg.model_id,
-                msg.input,
-                msg.output,
-                msg.cache_read,
-                msg.cache_write,
-                msg.reasoning,
-            );
-            parsed_to_unified(msg, cost)
-        })
-        .collect();
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone)]
+pub struct RecentUsageOptions {
+    pub home_dir: Option<String>,
+    pub sources: Option<Vec<String>>,
+    /// "hour" (default) or "15m"
+    pub granularity: Option<String>,
+    /// Days to bucket, up to 14 (default: `recentBucketDays` from settings,
+    /// else 2)
+    pub days: Option<u32>,
+}
 
-    // Add Cursor messages if enabled
-    if options.include_cursor {
-        let cursor_cache_dir = format!("{}/.config/tokscale/cursor-cache", home_dir);
-        let cursor_files = scanner::scan_directory(&cursor_cache_dir, "*.csv");
-
-        let cursor_messages: Vec<UnifiedMessage> = cursor_files
-            .par_iter()
-            .flat_map(|path| {
-                sessions::cursor::parse_cursor_file(path)
-                    .into_iter()
-                    .map(|mut msg| {
-                        let csv_cost = msg.cost;
-                        let calculated_cost = pricing.calculate_cost(
-                            &msg.model_id,
-                            msg.tokens.input,
-                            msg.tokens.output,
-                            msg.tokens.cache_read,
-                            msg.tokens.cache_write,
-                            msg.tokens.reasoning,
-                        );
-                        msg.cost = if calculated_cost > 0.0 {
-                            calculated_cost
-                        } else {
-                            csv_cost
-                        };
-                        msg
-                    })
-                    .collect::<Vec<_>>()
-            })
-            .collect();
-
-        all_messages.extend(cursor_messages);
-    }
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone)]
+pub struct RecentBucket {
+    /// Bucket start, Unix milliseconds
+    pub start: i64,
+    pub tokens: i64,
+    pub message_count: i32,
+    pub cost: f64,
+}
 
-    // Apply date filters
-    if let Some(year) = &options.year {
-        let year_prefix = format!("{}-", year);
-        all_messages.retain(|m| m.date.starts_with(&year_prefix));
-    }
-    if let Some(since) = &options.since {
-        all_messages.retain(|m| m.date.as_str() >= since.as_str());
-    }
-    if let Some(until) = &options.until {
-        all_messages.retain(|m| m.date.as_str() <= until.as_str());
-    }
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone)]
+pub struct RecentUsage {
+    /// "hour" or "15m"
+    pub granularity: String,
+    pub bucket_minutes: u32,
+    pub days: u32,
+    /// Oldest first, empty buckets included; the last one holds now
+    pub buckets: Vec<RecentBucket>,
+    /// Cost over the last 60 minutes
+    pub cost_per_hour: f64,
+    /// Tokens over the last 60 minutes
+    pub tokens_per_hour: i64,
+    pub processing_time_ms: u32,
+}
 
-    // Aggregate by date
-    let contributions = aggregator::aggregate_by_date(all_messages);
+// =============================================================================
+// Effective rates
+// =============================================================================
 
-    // Generate result
-    let processing_time_ms = start.elapsed().as_millis() as u32;
-    let result = aggregator::generate_graph_result(contributions, processing_time_ms);
+/// What one model actually cost per million tokens over a period
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EffectiveRate {
+    pub model: String,
+    pub provider: String,
+    /// Sources the model was used from
+    pub sources: Vec<String>,
+    /// Input, cache read and cache write tokens
+    pub input_tokens: i64,
+    /// Output and reasoning tokens
+    pub output_tokens: i64,
+    pub training_tokens: i64,
+    /// Share of input tokens that were cache reads
+    pub cache_read_share: f64,
+    pub cost: f64,
+    /// Cost over all tokens, in USD per million
+    pub effective_per_million: f64,
+    /// Input-side cost over input tokens; unset when the model has no price
+    pub input_per_million: Option<f64>,
+    /// Output-side cost over output tokens; unset when the model has no price
+    pub output_per_million: Option<f64>,
+    pub list_input_per_million: Option<f64>,
+    pub list_output_per_million: Option<f64>,
+    pub message_count: i32,
+}
 
-    Ok(result)
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone)]
+pub struct EffectiveRateReport {
+    /// Most expensive first
+    pub entries: Vec<EffectiveRate>,
+    pub total_tokens: i64,
+    pub total_cost: f64,
+    pub effective_per_million: f64,
+    pub processing_time_ms: u32,
 }
 
-/// Combined result for report and graph (single pricing lookup)
-#[napi(object)]
-pub struct ReportAndGraph {
-    pub report: ModelReport,
-    pub graph: GraphResult,
+// =============================================================================
+// Session activity
+// =============================================================================
+
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone)]
+pub struct ActivityOptions {
+    pub home_dir: Option<String>,
+    pub sources: Option<Vec<String>>,
+    pub since: Option<String>,
+    pub until: Option<String>,
+    pub year: Option<String>,
+    /// Gaps between messages longer than this many minutes are idle time
+    /// (default: `idleThresholdMinutes` from settings, else 30)
+    pub idle_minutes: Option<f64>,
+    /// Only sessions with this tag ("expensive", "token-heavy",
+    /// "long-running" or "agent-heavy")
+    pub tag: Option<String>,
 }
 
-/// Finalize both report and graph in a single call with shared pricing
-/// This ensures consistent costs between report and graph data
-#[napi]
-pub async fn finalize_report_and_graph(options: FinalizeReportOptions) -> napi::Result<ReportAndGraph> {
-    let start = Instant::now();
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone)]
+pub struct SessionActivity {
+    pub source: String,
+    pub session_id: String,
+    /// First and last message, Unix milliseconds
+    pub start: i64,
+    pub end: i64,
+    /// `end - start`
+    pub wall_ms: i64,
+    /// Wall time minus idle gaps
+    pub active_ms: i64,
+    pub message_count: i32,
+    pub cost: f64,
+    /// All tokens, cache included
+    pub tokens: i64,
+    /// Messages written by sub-agents
+    pub sub_agent_messages: i32,
+    /// Outlier tags from the `sessionTags` thresholds in settings
+    pub tags: Vec<String>,
+}
 
-    let home_dir = get_home_dir(&options.home_dir)?;
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone)]
+pub struct ActivityReport {
+    /// Longest active time first
+    pub sessions: Vec<SessionActivity>,
+    pub session_count: i32,
+    pub active_hours: f64,
+    /// Hours from first to last message, idle gaps included
+    pub wall_hours: f64,
+    pub cost: f64,
+    pub cost_per_active_hour: f64,
+    pub idle_threshold_minutes: f64,
+    pub processing_time_ms: u32,
+}
 
-    // Single pricing lookup - shared by both report and graph
-    let pricing = pricing::PricingService::get_or_init()
-        .await
-        .map_err(|e| napi::Error::from_reason(e))?;
+// =============================================================================
+// Cost efficiency
+// =============================================================================
 
-    // Convert local messages and apply pricing (once)
-    let mut all_messages: Vec<UnifiedMessage> = options
-        .local_messages
-        .messages
-        .iter()
-        .map(|msg| {
-            let cost = pricing.calculate_cost(
-                &msg.model_id,
-                msg.input,
-                msg.output,
-                msg.cache_read,
-                msg.cache_write,
-                msg.reasoning,
-            );
-            parsed_to_unified(msg, cost)
-        })
-        .collect();
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone)]
+pub struct EfficiencyOptions {
+    pub home_dir: Option<String>,
+    pub sources: Option<Vec<String>>,
+    pub since: Option<String>,
+    pub until: Option<String>,
+    pub year: Option<String>,
+    /// Gaps between messages longer than this many minutes are idle time
+    /// (default: `idleThresholdMinutes` from settings, else 30)
+    pub idle_minutes: Option<f64>,
+    /// Sessions with fewer messages aren't compared (default 3)
+    pub min_messages: Option<u32>,
+    /// Sessions with less active time aren't compared (default 5)
+    pub min_active_minutes: Option<f64>,
+    /// Only compare usage of models in this family, e.g. "claude-sonnet"
+    pub family: Option<String>,
+}
 
-    // Add Cursor messages if enabled
-    if options.include_cursor {
-        let cursor_cache_dir = format!("{}/.config/tokscale/cursor-cache", home_dir);
-        let cursor_files = scanner::scan_directory(&cursor_cache_dir, "*.csv");
-
-        let cursor_messages: Vec<UnifiedMessage> = cursor_files
-            .par_iter()
-            .flat_map(|path| {
-                sessions::cursor::parse_cursor_file(path)
-                    .into_iter()
-                    .map(|mut msg| {
-                        let csv_cost = msg.cost;
-                        let calculated_cost = pricing.calculate_cost(
-                            &msg.model_id,
-                            msg.tokens.input,
-                            msg.tokens.output,
-                            msg.tokens.cache_read,
-                            msg.tokens.cache_write,
-                            msg.tokens.reasoning,
-                        );
-                        msg.cost = if calculated_cost > 0.0 {
-                            calculated_cost
-                        } else {
-                            csv_cost
-                        };
-                        msg
-                    })
-                    .collect::<Vec<_>>()
-            })
-            .collect();
-
-        all_messages.extend(cursor_messages);
-    }
+/// What a session and an hour of work cost in one source
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone)]
+pub struct SourceEfficiency {
+    pub source: String,
+    /// 1 for the lowest cost per active hour; unset without comparable sessions
+    pub rank: Option<u32>,
+    /// Comparable sessions
+    pub sessions: u32,
+    /// Sessions too short to compare
+    pub excluded_sessions: u32,
+    pub active_hours: f64,
+    pub cost: f64,
+    /// All tokens, cache included
+    pub tokens: i64,
+    pub cost_per_session: f64,
+    pub median_session_cost: f64,
+    pub cost_per_active_hour: f64,
+    pub cost_per_million_tokens: f64,
+}
 
-    // Apply date filters
-    if let Some(year) = &options.year {
-        let year_prefix = format!("{}-", year);
-        all_messages.retain(|m| m.date.starts_with(&year_prefix));
-    }
-    if let Some(since) = &options.since {
-        all_messages.retain(|m| m.date.as_str() >= since.as_str());
-    }
-    if let Some(until) = &options.until {
-        all_messages.retain(|m| m.date.as_str() <= until.as_str());
-    }
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone)]
+pub struct EfficiencyReport {
+    /// Cheapest per active hour first
+    pub entries: Vec<SourceEfficiency>,
+    pub idle_threshold_minutes: f64,
+    pub min_messages: u32,
+    pub min_active_minutes: f64,
+    pub family: Option<String>,
+    pub processing_time_ms: u32,
+}
 
-    // Clone messages for graph aggregation (report consumes for model aggregation)
-    let messages_for_graph = all_messages.clone();
+// =============================================================================
+// Usage modes
+// =============================================================================
 
-    // --- Generate Report ---
-    let mut model_map: std::collections::HashMap<String, ModelUsage> =
-        std::collections::HashMap::new();
+/// Usage from one source in one mode
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone)]
+pub struct UsageModeEntry {
+    pub source: String,
+    /// "interactive" or "agentic"
+    pub mode: String,
+    pub input: i64,
+    pub output: i64,
+    pub cache_read: i64,
+    pub cache_write: i64,
+    pub reasoning: i64,
+    pub message_count: i32,
+    pub cost: f64,
+}
 
-    for msg in all_messages {
-        let key = format!("{}:{}:{}", msg.source, msg.provider_id, msg.model_id);
-        let entry = model_map.entry(key).or_insert_with(|| ModelUsage {
-            source: msg.source.clone(),
-            model: msg.model_id.clone(),
-            provider: msg.provider_id.clone(),
-            input: 0,
-            output: 0,
-            cache_read: 0,
-            cache_write: 0,
-            reasoning: 0,
-            message_count: 0,
-            cost: 0.0,
-        });
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone)]
+pub struct UsageModeReport {
+    /// Most expensive first
+    pub entries: Vec<UsageModeEntry>,
+    pub interactive_cost: f64,
+    pub agentic_cost: f64,
+    /// Agentic share of total cost (0-1)
+    pub agentic_share: f64,
+    pub processing_time_ms: u32,
+}
 
-        entry.input += msg.tokens.input;
-        entry.output += msg.tokens.output;
-        entry.cache_read += msg.tokens.cache_read;
-        entry.cache_write += msg.tokens.cache_write;
-        entry.reasoning += msg.tokens.reasoning;
-        entry.message_count += 1;
-        entry.cost += msg.cost;
-    }
+// =============================================================================
+// Cost drivers
+// =============================================================================
 
-    let mut entries: Vec<ModelUsage> = model_map.into_values().collect();
-    entries.sort_by(|a, b| match (a.cost.is_nan(), b.cost.is_nan()) {
-        (true, true) => std::cmp::Ordering::Equal,
-        (true, false) => std::cmp::Ordering::Greater,
-        (false, true) => std::cmp::Ordering::Less,
-        (false, false) => b
-            .cost
-            .partial_cmp(&a.cost)
-            .unwrap_or(std::cmp::Ordering::Equal),
-    });
+/// Turns in one bucket of prompt length, tool calls or files touched
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone)]
+pub struct CostDriverBucket {
+    /// e.g. "500-1,999 chars", "3-9 calls", "1 file"
+    pub label: String,
+    pub turn_count: u32,
+    pub message_count: u32,
+    pub tokens: i64,
+    pub cost: f64,
+    /// Cost per turn
+    pub average_cost: f64,
+    /// Share of the cost of all bucketed turns (0-1)
+    pub share: f64,
+}
 
-    let total_input: i64 = entries.iter().map(|e| e.input).sum();
-    let total_output: i64 = entries.iter().map(|e| e.output).sum();
-    let total_cache_read: i64 = entries.iter().map(|e| e.cache_read).sum();
-    let total_cache_write: i64 = entries.iter().map(|e| e.cache_write).sum();
-    let total_messages: i32 = entries.iter().map(|e| e.message_count).sum();
-    let total_cost: f64 = entries.iter().map(|e| e.cost).sum();
-
-    let report = ModelReport {
-        entries,
-        total_input,
-        total_output,
-        total_cache_read,
-        total_cache_write,
-        total_messages,
-        total_cost,
-        processing_time_ms: start.elapsed().as_millis() as u32,
-    };
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone)]
+pub struct CostDriverReport {
+    /// Ascending; empty buckets are left out
+    pub prompt_length: Vec<CostDriverBucket>,
+    pub tool_calls: Vec<CostDriverBucket>,
+    /// Turns from sources that log file paths only
+    pub files_touched: Vec<CostDriverBucket>,
+    pub turn_count: u32,
+    pub attributed_cost: f64,
+    /// Messages outside any turn: sources that don't log prompts, and
+    /// responses before a session's first prompt
+    pub unattributed_messages: u32,
+    pub unattributed_cost: f64,
+    pub processing_time_ms: u32,
+}
+
+// =============================================================================
+// Context window pressure
+// =============================================================================
+
+#[cfg_attr(feature = "napi", napi(object))]
+pub struct ContextOptions {
+    pub home_dir: Option<String>,
+    pub sources: Option<Vec<String>>,
+    pub since: Option<String>,
+    pub until: Option<String>,
+    pub year: Option<String>,
+    /// Share of the context window from which a message counts as near the
+    /// limit (default: 0.9)
+    pub threshold: Option<f64>,
+}
 
-    // --- Generate Graph ---
-    let contributions = aggregator::aggregate_by_date(messages_for_graph);
-    let graph = aggregator::generate_graph_result(contributions, start.elapsed().as_millis() as u32);
+/// How close one model's requests run to its context window
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone)]
+pub struct ContextPressure {
+    pub model: String,
+    /// From the pricing catalog
+    pub context_window: i64,
+    pub message_count: u32,
+    /// Messages at or past the threshold, up to the window
+    pub near_limit_count: u32,
+    /// Messages past the window; usually a longer window than the catalog lists
+    pub over_limit_count: u32,
+    /// Share of messages near or over the limit (0-1)
+    pub near_limit_share: f64,
+    /// Cost of messages near or over the limit
+    pub near_limit_cost: f64,
+    /// Sessions with at least one message near or over the limit
+    pub sessions_affected: u32,
+    /// Largest context (input + cache read + cache write) of one message
+    pub peak_context: i64,
+    pub average_context: f64,
+}
 
-    Ok(ReportAndGraph { report, graph })
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone)]
+pub struct ContextPressureReport {
+    pub threshold: f64,
+    /// Most messages near or over the limit first
+    pub models: Vec<ContextPressure>,
+    /// Messages of models with a known context window
+    pub message_count: u32,
+    pub near_limit_count: u32,
+    pub over_limit_count: u32,
+    pub near_limit_cost: f64,
+    /// Models the pricing catalog lists no context window for
+    pub models_without_window: Vec<String>,
+    pub processing_time_ms: u32,
 }
 
 // =============================================================================
-// New Pricing API (Rust-native pricing fetching)
+// Projects
 // =============================================================================
 
-#[napi(object)]
-pub struct NativePricing {
-    pub input_cost_per_token: f64,
-    pub output_cost_per_token: f64,
-    pub cache_read_input_token_cost: Option<f64>,
-    pub cache_creation_input_token_cost: Option<f64>,
+/// Usage for one repository, or one directory outside any repository
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone)]
+pub struct ProjectUsage {
+    /// Repository root the sessions' working directories resolved to
+    pub project: String,
+    pub name: String,
+    /// Working directories recorded by the sessions
+    pub paths: Vec<String>,
+    pub sources: Vec<String>,
+    pub input: i64,
+    pub output: i64,
+    pub cache_read: i64,
+    pub cache_write: i64,
+    pub reasoning: i64,
+    pub message_count: i32,
+    pub cost: f64,
 }
 
-#[napi(object)]
-pub struct PricingLookupResult {
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone)]
+pub struct ProjectReport {
+    /// Most expensive first
+    pub entries: Vec<ProjectUsage>,
+    /// Messages from sources that don't record a working directory
+    pub unattributed_messages: i32,
+    pub unattributed_cost: f64,
+    pub total_cost: f64,
+    pub processing_time_ms: u32,
+}
+
+/// Usage on one branch of one project
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone)]
+pub struct BranchUsage {
+    pub branch: String,
+    /// Repository root, when the sessions recorded their working directory
+    pub project: Option<String>,
+    pub name: Option<String>,
+    pub sources: Vec<String>,
+    pub input: i64,
+    pub output: i64,
+    pub cache_read: i64,
+    pub cache_write: i64,
+    pub reasoning: i64,
+    pub message_count: i32,
+    pub session_count: i32,
+    /// First and last day with usage on the branch (YYYY-MM-DD)
+    pub first_date: String,
+    pub last_date: String,
+    pub cost: f64,
+}
+
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone)]
+pub struct BranchReport {
+    /// Most expensive first
+    pub entries: Vec<BranchUsage>,
+    /// Messages from sources that don't record a branch, or from a detached checkout
+    pub unattributed_messages: i32,
+    pub unattributed_cost: f64,
+    pub total_cost: f64,
+    pub processing_time_ms: u32,
+}
+
+// =============================================================================
+// Cost per commit
+// =============================================================================
+
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone)]
+pub struct CommitCostOptions {
+    pub home_dir: Option<String>,
+    pub sources: Option<Vec<String>>,
+    pub since: Option<String>,
+    pub until: Option<String>,
+    pub year: Option<String>,
+    /// How long before a commit a message may be and still be charged to it
+    /// (default: 24)
+    pub window_hours: Option<f64>,
+    /// Only consider commits whose author matches (as `git log --author`)
+    pub author: Option<String>,
+}
+
+/// Agent usage charged to one commit
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone)]
+pub struct CommitCost {
+    /// Repository root
+    pub project: String,
+    pub name: String,
+    pub hash: String,
+    pub author: String,
+    pub subject: String,
+    /// Commit time in Unix milliseconds
+    pub timestamp: i64,
+    pub message_count: i32,
+    pub session_count: i32,
+    pub cost: f64,
+}
+
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone)]
+pub struct CommitCostReport {
+    /// Newest first; commits no usage was charged to are left out
+    pub commits: Vec<CommitCost>,
+    /// Git repositories the usage ran in
+    pub repositories: i32,
+    pub committed_cost: f64,
+    pub mean_cost_per_commit: f64,
+    pub median_cost_per_commit: f64,
+    /// Usage in a repository with no commit within the window after it
+    pub uncommitted_messages: i32,
+    pub uncommitted_cost: f64,
+    /// Usage without a working directory, or outside any git repository
+    pub unattributed_messages: i32,
+    pub unattributed_cost: f64,
+    pub processing_time_ms: u32,
+}
+
+// =============================================================================
+// Invoice reconciliation
+// =============================================================================
+
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone)]
+pub struct ReconcileOptions {
+    /// Billing CSV exported from the provider's console
+    pub csv: String,
+    /// "anthropic" or "openai"; detected from the CSV header when unset
+    pub provider: Option<String>,
+    pub home_dir: Option<String>,
+    pub sources: Option<Vec<String>>,
+    /// Relative difference tolerated before a line is flagged (default 0.05)
+    pub tolerance: Option<f64>,
+    /// Differences up to this many USD are never flagged (default 0.01)
+    pub min_difference: Option<f64>,
+}
+
+/// Billed and computed cost for one (day, model)
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone)]
+pub struct ReconciliationLine {
+    /// UTC day, YYYY-MM-DD
+    pub date: String,
+    /// Model without its date suffix
+    pub model: String,
+    pub billed: f64,
+    pub computed: f64,
+    /// Computed minus billed; negative when tokscale under-counts
+    pub difference: f64,
+    pub mismatch: bool,
+}
+
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone)]
+pub struct ReconciliationReport {
+    pub provider: String,
+    /// First and last billed day
+    pub since: Option<String>,
+    pub until: Option<String>,
+    /// Ordered by date then model
+    pub lines: Vec<ReconciliationLine>,
+    pub total_billed: f64,
+    pub total_computed: f64,
+    pub mismatches: u32,
+}
+
+// =============================================================================
+// Budgets
+// =============================================================================
+
+/// Spend against one budget in its current period, projected to period end
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BudgetStatus {
+    pub name: String,
+    /// "day", "week" or "month"
+    pub period: String,
+    /// USD per period
+    pub amount: f64,
+    /// First and last day of the current period (YYYY-MM-DD, UTC)
+    pub period_start: String,
+    pub period_end: String,
+    pub spent: f64,
+    /// Burn rate: cost per hour over the last 24 hours
+    pub cost_per_hour: f64,
+    /// Spend at period end if the burn rate holds
+    pub projected_cost: f64,
+    /// When the budget will be exceeded at the burn rate (RFC 3339); unset
+    /// when that is after period end or it already is
+    pub projected_breach: Option<String>,
+    /// "ok", "projected" (will be exceeded before period end) or "exceeded"
+    pub state: String,
+}
+
+/// One run of a budget's `enforce` hook, as recorded in enforcements.jsonl
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EnforcementEvent {
+    pub budget: String,
+    /// First day of the period the cap was enforced for
+    pub period_start: String,
+    /// USD spent and the budget amount when the hook ran
+    pub spent: f64,
+    pub amount: f64,
+    pub command: String,
+    /// When the hook ran (RFC 3339)
+    pub ran_at: String,
+    /// Unset when the hook couldn't start, timed out or was killed
+    pub exit_code: Option<i32>,
+    /// End of the hook's stdout and stderr
+    pub output: String,
+    /// Why the run failed; unset when the hook exited with 0
+    pub error: Option<String>,
+}
+
+// =============================================================================
+// CI checks
+// =============================================================================
+
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone)]
+pub struct CheckOptions {
+    pub home_dir: Option<String>,
+    pub sources: Option<Vec<String>>,
+    pub since: Option<String>,
+    pub until: Option<String>,
+    pub year: Option<String>,
+    /// USD no day may cost more than
+    pub max_daily: Option<f64>,
+    /// Share of messages (0-1) that may use models without a list price
+    pub max_unknown_model_share: Option<f64>,
+}
+
+/// One limit and how usage measured up to it
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone)]
+pub struct ThresholdCheck {
+    /// "max-daily" or "max-unknown-model-share"
+    pub name: String,
+    pub passed: bool,
+    pub limit: f64,
+    /// Highest day's cost, or the share of messages without a list price
+    pub actual: f64,
+    /// Days over the limit, or the models without a list price
+    pub detail: Option<String>,
+}
+
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone)]
+pub struct CheckReport {
+    /// Every check passed
+    pub passed: bool,
+    pub checks: Vec<ThresholdCheck>,
+    /// Messages checked
+    pub messages: i64,
+}
+
+// =============================================================================
+// Monthly statements
+// =============================================================================
+
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone)]
+pub struct StatementOptions {
+    pub home_dir: Option<String>,
+    pub sources: Option<Vec<String>>,
+    pub since: Option<String>,
+    pub until: Option<String>,
+    pub year: Option<String>,
+    /// Only statements for this provider id, e.g. "anthropic"
+    pub provider: Option<String>,
+    pub exclude_estimated_timestamps: Option<bool>,
+}
+
+/// Usage of one model on a statement, with the list rates it is priced at
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone)]
+pub struct StatementLine {
+    pub model: String,
+    pub message_count: i32,
+    pub input: i64,
+    pub output: i64,
+    pub cache_read: i64,
+    pub cache_write: i64,
+    pub reasoning: i64,
+    /// List rates in USD per million tokens; unset when the model has no price
+    pub input_per_million: Option<f64>,
+    pub output_per_million: Option<f64>,
+    pub cache_read_per_million: Option<f64>,
+    pub cache_write_per_million: Option<f64>,
+    pub cost: f64,
+    /// Fraction of the statement's cost, 0 to 1
+    pub cost_share: f64,
+}
+
+/// One provider's usage in one calendar month
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone)]
+pub struct Statement {
+    pub provider: String,
+    /// YYYY-MM
+    pub month: String,
+    /// Most expensive first
+    pub lines: Vec<StatementLine>,
+    pub message_count: i32,
+    pub input: i64,
+    pub output: i64,
+    pub cache_read: i64,
+    pub cache_write: i64,
+    pub reasoning: i64,
+    pub total_cost: f64,
+}
+
+// =============================================================================
+// Usage diff
+// =============================================================================
+
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone)]
+pub struct DiffOptions {
+    pub home_dir: Option<String>,
+    pub sources: Option<Vec<String>>,
+    /// Year, month or day: "2025", "2025-05" or "2025-05-03"
+    pub from: String,
+    pub to: String,
+    pub exclude_estimated_timestamps: Option<bool>,
+}
+
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone)]
+pub struct DiffTotals {
+    pub cost: f64,
+    pub tokens: i64,
+    pub messages: i32,
+}
+
+/// One model or project on either side of a diff
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone)]
+pub struct DiffEntry {
+    /// Model id, or project root
+    pub key: String,
+    /// Model id, or the project's directory name
+    pub name: String,
+    pub from_cost: f64,
+    pub to_cost: f64,
+    pub cost_change: f64,
+    /// Share of that side's total cost (0-1)
+    pub from_share: f64,
+    pub to_share: f64,
+    pub from_tokens: i64,
+    pub to_tokens: i64,
+    pub from_messages: i32,
+    pub to_messages: i32,
+    /// "added", "removed", "changed" or "unchanged"
+    pub status: String,
+}
+
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone)]
+pub struct UsageDiff {
+    pub from_label: String,
+    pub to_label: String,
+    pub from: DiffTotals,
+    pub to: DiffTotals,
+    pub cost_change: f64,
+    /// Unset when the first side cost nothing
+    pub cost_change_percent: Option<f64>,
+    /// Largest cost change first; unset when a compared export has no models
+    pub models: Option<Vec<DiffEntry>>,
+    /// Largest cost change first; unset when a compared export has no projects
+    pub projects: Option<Vec<DiffEntry>>,
+}
+
+// =============================================================================
+// Cache maintenance
+// =============================================================================
+
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone)]
+pub struct CacheCleanOptions {
+    /// Report what would be removed without deleting anything
+    pub dry_run: Option<bool>,
+}
+
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone)]
+pub struct CacheCleanEntry {
+    pub path: String,
+    pub bytes: i64,
+    /// "orphaned-temp" or "expired"
+    pub reason: String,
+}
+
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone)]
+pub struct CacheCleanReport {
+    pub cache_dir: String,
+    pub dry_run: bool,
+    pub removed: Vec<CacheCleanEntry>,
+    pub bytes_freed: i64,
+    pub parse_entries_pruned: u32,
+    pub total_bytes: i64,
+}
+
+// =============================================================================
+// Parse diagnostics
+// =============================================================================
+
+/// A field whose type no longer matched what a session parser expects
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone)]
+pub struct ParseDiagnostic {
+    pub source: String,
+    /// Path of the field, e.g. "message/usage/input_tokens"
+    pub field: String,
+    pub expected: String,
+    pub found: String,
+    /// Number of occurrences
+    pub count: i64,
+}
+
+/// Files parsed as one detected format (e.g. Amp "usage-ledger")
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone)]
+pub struct ParseFormat {
+    pub source: String,
+    pub format: String,
+    /// Number of files
+    pub count: i64,
+}
+
+/// A model LiteLLM and OpenRouter price differently (USD per token)
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone)]
+pub struct PricingConflict {
     pub model_id: String,
-    pub matched_key: String,
+    pub litellm_key: String,
+    pub openrouter_key: String,
+    pub litellm_input: Option<f64>,
+    pub litellm_output: Option<f64>,
+    pub openrouter_input: Option<f64>,
+    pub openrouter_output: Option<f64>,
+    /// Source whose prices were used, per the `pricingMerge` setting
+    pub chosen: String,
+}
+
+/// Timestamps of one source corrected one way: "timezone" (read in the
+/// source's configured zone), "seconds" (scaled to milliseconds), "future"
+/// (moved back to now), or "no-offset" (no UTC offset and no zone set, so
+/// left as UTC)
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone)]
+pub struct TimestampCorrection {
     pub source: String,
-    pub pricing: NativePricing,
+    pub kind: String,
+    /// Number of messages
+    pub count: i64,
+}
+
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone)]
+pub struct ParseDiagnostics {
+    pub drift: Vec<ParseDiagnostic>,
+    pub formats: Vec<ParseFormat>,
+    pub pricing_conflicts: Vec<PricingConflict>,
+    pub timestamp_corrections: Vec<TimestampCorrection>,
+}
+
+// =============================================================================
+// Parser conformance
+// =============================================================================
+
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone)]
+pub struct ConformanceFinding {
+    /// "error" (tokscale loses or repairs usage) or "warning"
+    pub severity: String,
+    /// Stable identifier: "schema-drift", "no-usage", "missing-model",
+    /// "negative-tokens", "bad-timestamp", "estimated-timestamp",
+    /// "empty-usage", "missing-session" or "unreadable"
+    pub code: String,
+    /// File the finding is about; unset for the run as a whole
+    pub file: Option<String>,
+    pub message: String,
+    /// Messages (or fields) affected
+    pub count: i64,
 }
 
-#[napi]
-pub async fn lookup_pricing(model_id: String, provider: Option<String>) -> napi::Result<PricingLookupResult> {
-    let service = pricing::PricingService::get_or_init()
-        .await
-        .map_err(|e| napi::Error::from_reason(e))?;
-
-    let force_source = provider.as_deref();
-    
-    match service.lookup_with_source(&model_id, force_source) {
-        Some(result) => Ok(PricingLookupResult {
-            model_id,
-            matched_key: result.matched_key,
-            source: result.source,
-            pricing: NativePricing {
-                input_cost_per_token: result.pricing.input_cost_per_token.unwrap_or(0.0),
-                output_cost_per_token: result.pricing.output_cost_per_token.unwrap_or(0.0),
-                cache_read_input_token_cost: result.pricing.cache_read_input_token_cost,
-                cache_creation_input_token_cost: result.pricing.cache_creation_input_token_cost,
-            },
-        }),
-        None => Err(napi::Error::from_reason(format!(
-            "Model not found: {}{}",
-            model_id,
-            force_source.map(|s| format!(" (forced source: {})", s)).unwrap_or_default()
-        ))),
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone)]
+pub struct ConformanceReport {
+    pub source: String,
+    pub passed: bool,
+    pub file_count: u32,
+    pub message_count: u32,
+    /// Formats files were detected as, for sources with several
+    pub formats: Vec<String>,
+    pub findings: Vec<ConformanceFinding>,
+}
+
+// =============================================================================
+// Source metadata API
+// =============================================================================
+
+/// Options for scanning source metadata
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone)]
+pub struct SourceScanOptions {
+    pub home_dir: Option<String>,
+    pub sources: Option<Vec<String>>,
+}
+
+/// Session data found for one source (timestamps are file modification times)
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone)]
+pub struct SourceSummary {
+    pub source: String,
+    pub path: String,
+    pub exists: bool,
+    pub file_count: u32,
+    pub total_bytes: i64,
+    pub earliest_timestamp: Option<i64>,
+    pub latest_timestamp: Option<i64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_only_home_refuses_writes() {
+        let error = writable_home(Some("/backup"), "OpenRouter import").unwrap_err();
+        assert_eq!(error, "OpenRouter import isn't available while TOKSCALE_HOME is set: /backup is read-only");
+        assert!(writable_home(Some("/backup"), "Budget enforcement").unwrap_err().starts_with("Budget enforcement"));
+
+        assert!(writable_home(None, "OpenRouter import").is_ok());
+        assert!(writable_home(Some(""), "Budget enforcement").is_ok());
     }
 }
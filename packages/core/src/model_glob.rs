@@ -0,0 +1,82 @@
+//! Model globs
+//!
+//! Filters such as the TUI's model filter take a comma-separated list of
+//! patterns, matched case-insensitively against model IDs. `*` matches any
+//! run of characters and `?` any one; a pattern without either matches
+//! anywhere in the ID, so `sonnet` finds every Sonnet model while
+//! `claude-*-4` only matches whole IDs.
+
+/// Parsed model filter; matches when any pattern does
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModelGlob {
+    patterns: Vec<String>,
+}
+
+impl ModelGlob {
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let patterns: Vec<String> = spec
+            .split(',')
+            .map(|p| p.trim().to_lowercase())
+            .filter(|p| !p.is_empty())
+            .collect();
+        if patterns.is_empty() {
+            return Err(format!("model filter \"{}\" has no patterns", spec));
+        }
+        Ok(Self { patterns })
+    }
+
+    pub fn matches(&self, model: &str) -> bool {
+        let model = model.to_lowercase();
+        self.patterns.iter().any(|pattern| {
+            if pattern.contains(['*', '?']) {
+                glob_match(pattern.as_bytes(), model.as_bytes())
+            } else {
+                model.contains(pattern.as_str())
+            }
+        })
+    }
+}
+
+/// Whole-string match with `*` and `?`, backtracking to the last `*`
+fn glob_match(pattern: &[u8], text: &[u8]) -> bool {
+    let (mut p, mut t) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == b'?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == b'*' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = star {
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == b'*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_globs_and_substrings() {
+        let glob = ModelGlob::parse("claude-*-4, gpt-4?").unwrap();
+        assert!(glob.matches("claude-sonnet-4"));
+        assert!(glob.matches("Claude-Opus-4"));
+        assert!(!glob.matches("claude-sonnet-4-5"));
+        assert!(glob.matches("gpt-4o"));
+        assert!(!glob.matches("gpt-4"));
+
+        let substring = ModelGlob::parse("sonnet").unwrap();
+        assert!(substring.matches("claude-3-5-sonnet-20241022"));
+        assert!(!substring.matches("claude-opus-4"));
+
+        assert!(ModelGlob::parse("*").unwrap().matches("anything"));
+        assert!(ModelGlob::parse(" , ").is_err());
+    }
+}
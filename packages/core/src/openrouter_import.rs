@@ -0,0 +1,354 @@
+//! OpenRouter generation import
+//!
+//! OpenRouter's `/api/v1/generation?id=gen-...` returns what a generation
+//! actually used: native token counts from the upstream provider and the
+//! cost OpenRouter billed. There is no endpoint listing generations, so IDs
+//! come from what tokscale already has (responses recorded by the metering
+//! proxy) and from ID lists the user passes in, such as OpenRouter's activity
+//! CSV export.
+//!
+//! Each generation is recorded as a billed event (see
+//! [`crate::sessions::recorded`]) under its generation ID, so importing
+//! twice adds nothing and the billed cost replaces our pricing. Local copies
+//! of the same generation are dropped from reports by [`drop_replaced`]:
+//! the proxy's event shares its ID, and an OpenRouter message parsed from a
+//! session file matches on model, completion tokens and time.
+
+use crate::sessions::recorded::{EventTime, RecordedEvent};
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::path::Path;
+
+/// Secret name of the OpenRouter API key
+pub const SECRET: &str = "openrouter";
+pub const PROVIDER: &str = "openrouter";
+/// Session imported generations are recorded under
+pub const SESSION_ID: &str = "openrouter-import";
+
+/// A local message and a generation up to this far apart can be the same
+pub const MATCH_WINDOW_MS: i64 = 5 * 60 * 1000;
+
+#[cfg(feature = "network")]
+const GENERATION_URL: &str = "https://openrouter.ai/api/v1/generation";
+#[cfg(feature = "network")]
+const CONCURRENT_REQUESTS: usize = 4;
+#[cfg(feature = "network")]
+const REQUEST_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(20);
+
+/// The parts of a generation we keep
+#[derive(Debug, Clone, Deserialize)]
+pub struct Generation {
+    pub id: String,
+    pub model: String,
+    pub created_at: String,
+    #[serde(default)]
+    pub total_cost: f64,
+    #[serde(default)]
+    pub native_tokens_prompt: Option<i64>,
+    #[serde(default)]
+    pub native_tokens_completion: Option<i64>,
+    #[serde(default)]
+    pub native_tokens_reasoning: Option<i64>,
+    #[serde(default)]
+    pub native_tokens_cached: Option<i64>,
+    /// Normalized (GPT-4o tokenizer) counts, used when native ones are missing
+    #[serde(default)]
+    pub tokens_prompt: Option<i64>,
+    #[serde(default)]
+    pub tokens_completion: Option<i64>,
+}
+
+impl Generation {
+    /// Parse a `/generation` response body
+    pub fn from_response(body: &str) -> Result<Self, String> {
+        #[derive(Deserialize)]
+        struct Response {
+            data: Generation,
+        }
+        serde_json::from_str::<Response>(body)
+            .map(|r| r.data)
+            .map_err(|e| format!("unexpected response: {}", e))
+    }
+
+    /// The generation as a billed recorded event. Cached prompt tokens count
+    /// as cache reads and reasoning is split out of the completion, as for
+    /// OpenAI responses.
+    pub fn to_event(&self) -> Result<RecordedEvent, String> {
+        let timestamp = chrono::DateTime::parse_from_rfc3339(&self.created_at)
+            .map_err(|e| format!("invalid created_at \"{}\": {}", self.created_at, e))?
+            .timestamp_millis();
+        let prompt = self.native_tokens_prompt.or(self.tokens_prompt).unwrap_or(0);
+        let completion = self.native_tokens_completion.or(self.tokens_completion).unwrap_or(0);
+        let cached = self.native_tokens_cached.unwrap_or(0).clamp(0, prompt.max(0));
+        let reasoning = self.native_tokens_reasoning.unwrap_or(0).clamp(0, completion.max(0));
+        Ok(RecordedEvent {
+            id: Some(self.id.clone()),
+            timestamp: Some(EventTime::Millis(timestamp)),
+            model: self.model.clone(),
+            provider: Some(PROVIDER.to_string()),
+            session_id: Some(SESSION_ID.to_string()),
+            input: (prompt - cached).max(0),
+            output: (completion - reasoning).max(0),
+            cache_read: cached,
+            cache_write: 0,
+            reasoning,
+            training: 0,
+            cost: self.total_cost.max(0.0),
+            agent: None,
+            billed: true,
+        })
+    }
+}
+
+/// Generation IDs (`gen-...`) anywhere in `text`: one per line, or columns
+/// of a CSV export
+pub fn generation_ids(text: &str) -> Vec<String> {
+    let mut seen = HashSet::new();
+    text.split(|c: char| !(c.is_ascii_alphanumeric() || c == '-' || c == '_'))
+        .filter(|token| token.len() > 4 && token.starts_with("gen-"))
+        .filter(|token| seen.insert(token.to_string()))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Generation IDs in the recorded events under `dir`, as (already
+/// imported, recorded by the proxy but not imported)
+pub fn recorded_generation_ids(dir: &Path) -> (HashSet<String>, HashSet<String>) {
+    let mut billed = HashSet::new();
+    let mut unbilled = HashSet::new();
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return (billed, unbilled);
+    };
+    for entry in entries.flatten() {
+        let Ok(content) = std::fs::read_to_string(entry.path()) else {
+            continue;
+        };
+        for event in content.lines().filter_map(|line| RecordedEvent::parse(line).ok()) {
+            match event.id {
+                Some(id) if id.starts_with("gen-") => {
+                    if event.billed {
+                        billed.insert(id);
+                    } else {
+                        unbilled.insert(id);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    unbilled.retain(|id| !billed.contains(id));
+    (billed, unbilled)
+}
+
+/// Outcome of [`import`]
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ImportStats {
+    pub imported: usize,
+    /// Generations imported before
+    pub skipped: usize,
+    /// (generation ID, reason)
+    pub failed: Vec<(String, String)>,
+}
+
+/// Fetch one generation
+#[cfg(feature = "network")]
+async fn fetch(client: &reqwest::Client, api_key: &str, id: &str) -> Result<Generation, String> {
+    let response = client
+        .get(GENERATION_URL)
+        .query(&[("id", id)])
+        .bearer_auth(api_key)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    match response.status().as_u16() {
+        200 => {}
+        401 | 403 => return Err("API key rejected".to_string()),
+        404 => return Err("not found (only the key that made a generation can read it)".to_string()),
+        status => return Err(format!("HTTP {}", status)),
+    }
+    Generation::from_response(&response.text().await.map_err(|e| e.to_string())?)
+}
+
+/// Import `ids` plus every generation the proxy recorded, skipping those
+/// imported before
+#[cfg(feature = "network")]
+pub async fn import(home_dir: &str, api_key: &str, ids: Vec<String>) -> Result<ImportStats, String> {
+    use futures::StreamExt;
+
+    let recorder = crate::Recorder::open(home_dir)?;
+    let (imported, from_proxy) = recorded_generation_ids(recorder.dir());
+    let mut wanted: Vec<String> = ids.into_iter().chain(from_proxy).collect();
+    wanted.sort();
+    wanted.dedup();
+
+    let mut stats = ImportStats::default();
+    wanted.retain(|id| {
+        let new = !imported.contains(id);
+        stats.skipped += usize::from(!new);
+        new
+    });
+
+    let client = reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .user_agent(concat!("tokscale/", env!("CARGO_PKG_VERSION")))
+        .build()
+        .unwrap_or_default();
+    let mut results = futures::stream::iter(wanted.into_iter().map(|id| {
+        let client = &client;
+        async move {
+            let result = fetch(client, api_key, &id).await.and_then(|g| g.to_event());
+            (id, result)
+        }
+    }))
+    .buffer_unordered(CONCURRENT_REQUESTS);
+
+    while let Some((id, result)) = results.next().await {
+        match result {
+            Ok(event) => {
+                recorder.record(&event.into_message(0))?;
+                stats.imported += 1;
+            }
+            Err(reason) => stats.failed.push((id, reason)),
+        }
+    }
+    recorder.sync()?;
+    stats.failed.sort();
+    Ok(stats)
+}
+
+#[cfg(not(feature = "network"))]
+pub async fn import(_home_dir: &str, _api_key: &str, _ids: Vec<String>) -> Result<ImportStats, String> {
+    Err("OpenRouter import needs a build with network support".to_string())
+}
+
+/// What [`drop_replaced`] compares
+#[derive(Debug, Clone, Copy)]
+pub struct Usage<'a> {
+    pub provider: &'a str,
+    pub model: &'a str,
+    pub timestamp: i64,
+    /// Output plus reasoning tokens
+    pub completion: i64,
+    pub billed: bool,
+}
+
+/// Model without its vendor prefix, lowercased: "openai/gpt-4o" -> "gpt-4o"
+fn model_key(model: &str) -> String {
+    model.rsplit('/').next().unwrap_or(model).to_lowercase()
+}
+
+/// Drop OpenRouter messages that an imported (billed) generation replaces:
+/// same model and completion tokens, at most [`MATCH_WINDOW_MS`] apart.
+/// Each generation replaces at most one message, the closest in time.
+pub fn drop_replaced<T>(messages: &mut Vec<T>, usage: impl Fn(&T) -> Usage<'_>) {
+    let billed: Vec<usize> = (0..messages.len()).filter(|&i| usage(&messages[i]).billed).collect();
+    if billed.is_empty() {
+        return;
+    }
+
+    let mut replaced = vec![false; messages.len()];
+    for &g in &billed {
+        let generation = usage(&messages[g]);
+        let model = model_key(generation.model);
+        let closest = (0..messages.len())
+            .filter(|&i| !replaced[i])
+            .filter(|&i| {
+                let u = usage(&messages[i]);
+                !u.billed
+                    && u.provider.eq_ignore_ascii_case(PROVIDER)
+                    && u.completion == generation.completion
+                    && (u.timestamp - generation.timestamp).abs() <= MATCH_WINDOW_MS
+                    && model_key(u.model) == model
+            })
+            .min_by_key(|&i| (usage(&messages[i]).timestamp - generation.timestamp).abs());
+        if let Some(i) = closest {
+            replaced[i] = true;
+        }
+    }
+
+    let mut index = 0;
+    messages.retain(|_| {
+        let keep = !replaced[index];
+        index += 1;
+        keep
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sessions::UnifiedMessage;
+    use crate::testutil::{message, TestMessage};
+
+    const T0: i64 = 1_748_779_200_000;
+
+    #[test]
+    fn test_generation_to_event() {
+        let body = r#"{"data":{"id":"gen-abc","model":"openai/o3","created_at":"2025-06-01T12:00:00.000Z",
+            "total_cost":0.0123,"native_tokens_prompt":1000,"native_tokens_completion":400,
+            "native_tokens_reasoning":150,"native_tokens_cached":200,"tokens_prompt":980,"tokens_completion":390,
+            "provider_name":"OpenAI","streamed":true}}"#;
+        let event = Generation::from_response(body).unwrap().to_event().unwrap();
+        assert_eq!(event.id.as_deref(), Some("gen-abc"));
+        assert_eq!((event.input, event.cache_read, event.output, event.reasoning), (800, 200, 250, 150));
+        assert_eq!(event.timestamp, Some(EventTime::Millis(T0)));
+
+        let msg = event.into_message(0);
+        assert!(msg.billed);
+        assert_eq!(msg.cost, 0.0123);
+        assert_eq!(msg.provider_id, "openrouter");
+        assert_eq!(msg.dedup_key.as_deref(), Some("recorded:gen-abc"));
+
+        assert!(Generation::from_response(r#"{"error":{"code":404}}"#).is_err());
+    }
+
+    #[test]
+    fn test_generation_ids() {
+        let csv = "generation_id,created_at,model\ngen-1a2b,2025-06-01,openai/gpt-4o\n\"gen-3c4d\",2025-06-02,x\ngen-1a2b,,\n";
+        assert_eq!(generation_ids(csv), vec!["gen-1a2b", "gen-3c4d"]);
+        assert!(generation_ids("generation gen- nothing").is_empty());
+    }
+
+    fn usage(msg: &UnifiedMessage) -> Usage<'_> {
+        Usage {
+            provider: msg.provider_id.as_str(),
+            model: &msg.model_id,
+            timestamp: msg.timestamp,
+            completion: msg.tokens.output + msg.tokens.reasoning,
+            billed: msg.billed,
+        }
+    }
+
+    #[test]
+    fn test_drop_replaced() {
+        let mut messages = vec![
+            message("s1", T0 - 60_000, 0.01).with_model("gpt-4o").with_provider("openrouter").with_output(50),
+            message("s1", T0 - 10_000, 0.01).with_model("gpt-4o").with_provider("openrouter").with_output(50),
+            message("s1", T0 - 20_000, 0.01).with_model("gpt-4o").with_provider("openrouter").with_output(51),
+            message("s1", T0, 0.01).with_model("gpt-4o").with_provider("openai").with_output(50),
+            message("s1", T0 - MATCH_WINDOW_MS - 1, 0.01)
+                .with_model("gpt-4o")
+                .with_provider("openrouter")
+                .with_output(70),
+            message("s1", T0, 0.01)
+                .with_model("openai/gpt-4o")
+                .with_provider("openrouter")
+                .with_output(50)
+                .with_billed(true),
+            message("s1", T0, 0.01)
+                .with_model("openai/gpt-4o")
+                .with_provider("openrouter")
+                .with_output(70)
+                .with_billed(true),
+        ];
+        drop_replaced(&mut messages, usage);
+
+        // The closest matching OpenRouter message went; other providers,
+        // token counts and messages outside the window stay
+        let left: Vec<(i64, i64)> = messages.iter().map(|m| (m.timestamp - T0, m.tokens.output)).collect();
+        assert_eq!(
+            left,
+            vec![(-60_000, 50), (-20_000, 51), (0, 50), (-MATCH_WINDOW_MS - 1, 70), (0, 50), (0, 70)]
+        );
+    }
+}
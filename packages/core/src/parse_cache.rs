@@ -0,0 +1,579 @@
+//! Incremental parse cache
+//!
+//! Keeps the messages parsed from each session file, keyed by path and
+//! invalidated by size and modification time, so unchanged files are not
+//! re-parsed on every run. Costs are not cached; pricing is applied after.
+//!
+//! The cache is a compact binary file rather than JSON, since round-tripping
+//! hundreds of thousands of messages through serde_json dominated load time:
+//!
+//! ```text
+//! magic "TSPC" | format version u32 | payload length u64 | FNV-1a u64 | payload
+//! ```
+//!
+//! The payload starts with the crate version, so a new parser release also
+//! starts from a clean cache. Any mismatch or corruption drops the whole cache
+//! and files are simply re-parsed.
+//!
+//! The file is written as a zstd frame (see [`cache::compress`]); caches
+//! written uncompressed by older versions still load.
+
+use crate::pricing::cache;
+use crate::scanner::SessionType;
+use crate::sessions::UnifiedMessage;
+use crate::TokenBreakdown;
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+const MAGIC: &[u8; 4] = b"TSPC";
+/// Bump when the payload layout or `UnifiedMessage` fields change
+pub const FORMAT_VERSION: u32 = 9;
+const HEADER_LEN: usize = 4 + 4 + 8 + 8;
+pub(crate) const CACHE_FILE: &str = "parse-cache.bin";
+
+/// Size and modification time of a session file when it was parsed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FileStamp {
+    size: u64,
+    mtime_ms: u64,
+}
+
+impl FileStamp {
+    fn of(path: &Path) -> Option<Self> {
+        let metadata = fs::metadata(path).ok()?;
+        let mtime_ms = metadata
+            .modified()
+            .ok()?
+            .duration_since(UNIX_EPOCH)
+            .ok()?
+            .as_millis() as u64;
+        Some(Self { size: metadata.len(), mtime_ms })
+    }
+}
+
+struct CacheEntry {
+    stamp: FileStamp,
+    messages: Vec<UnifiedMessage>,
+}
+
+pub struct ParseCache {
+    path: PathBuf,
+    entries: HashMap<PathBuf, CacheEntry>,
+    dirty: bool,
+}
+
+impl ParseCache {
+    pub fn default_path() -> PathBuf {
+        cache::get_cache_path(CACHE_FILE)
+    }
+
+    /// Load the cache at `path`; a missing, outdated or corrupt file yields an
+    /// empty cache
+    pub fn load(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let entries = fs::read(&path)
+            .ok()
+            .and_then(cache::decompress)
+            .and_then(|data| decode(&data))
+            .unwrap_or_default();
+        Self { path, entries, dirty: false }
+    }
+
+    /// Parse `files`, reusing cached messages for unchanged files. Returns one
+    /// message list per file, in file order.
+    pub fn parse_files<F>(
+        &mut self,
+        files: &[(SessionType, PathBuf)],
+        parse: F,
+    ) -> Vec<Vec<UnifiedMessage>>
+    where
+        F: Fn(SessionType, &Path) -> Vec<UnifiedMessage> + Sync,
+    {
+        let results: Vec<(Option<FileStamp>, bool, Vec<UnifiedMessage>)> = files
+            .par_iter()
+            .map(|(session_type, path)| {
+                let stamp = FileStamp::of(path);
+                match self.entries.get(path) {
+                    Some(entry) if Some(entry.stamp) == stamp => (stamp, true, entry.messages.clone()),
+                    _ => (stamp, false, parse(*session_type, path)),
+                }
+            })
+            .collect();
+
+        files
+            .iter()
+            .zip(results)
+            .map(|((_, path), (stamp, hit, parsed))| {
+                if let (false, Some(stamp)) = (hit, stamp) {
+                    self.entries.insert(path.clone(), CacheEntry { stamp, messages: parsed.clone() });
+                    self.dirty = true;
+                }
+                parsed
+            })
+            .collect()
+    }
+
+    /// Drop entries for session files that no longer exist
+    pub fn prune_missing(&mut self) -> usize {
+        let before = self.entries.len();
+        self.entries.retain(|path, _| path.exists());
+        let pruned = before - self.entries.len();
+        if pruned > 0 {
+            self.dirty = true;
+        }
+        pruned
+    }
+
+    /// Write the cache if anything changed since it was loaded
+    pub fn save(&mut self) -> std::io::Result<()> {
+        if !self.dirty {
+            return Ok(());
+        }
+
+        let dir = self.path.parent().map(Path::to_path_buf).unwrap_or_default();
+        fs::create_dir_all(&dir)?;
+
+        let content = cache::compress(&encode(&self.entries));
+        let tmp_path = dir.join(format!(".{}.{}.tmp", CACHE_FILE, std::process::id()));
+        let write_result = (|| {
+            let mut file = fs::File::create(&tmp_path)?;
+            file.write_all(&content)?;
+            file.sync_all()?;
+            fs::rename(&tmp_path, &self.path)
+        })();
+
+        if write_result.is_err() {
+            let _ = fs::remove_file(&tmp_path);
+        } else {
+            self.dirty = false;
+        }
+
+        write_result
+    }
+}
+
+/// Fingerprint of the paths, sizes and modification times of `files`; it
+/// changes whenever parsing them could give different messages
+pub fn fingerprint(files: &[(SessionType, PathBuf)]) -> u64 {
+    // Directory listing order isn't guaranteed to be stable
+    let mut paths: Vec<&PathBuf> = files.iter().map(|(_, path)| path).collect();
+    paths.sort();
+    let mut data = Vec::new();
+    for path in paths {
+        data.extend_from_slice(path.as_os_str().as_encoded_bytes());
+        data.push(0);
+        let stamp = FileStamp::of(path).unwrap_or(FileStamp { size: u64::MAX, mtime_ms: 0 });
+        data.extend_from_slice(&stamp.size.to_le_bytes());
+        data.extend_from_slice(&stamp.mtime_ms.to_le_bytes());
+    }
+    fnv1a(&data)
+}
+
+fn fnv1a(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &b| {
+        (hash ^ b as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+fn encode(entries: &HashMap<PathBuf, CacheEntry>) -> Vec<u8> {
+    let mut payload = Encoder::default();
+    payload.str(env!("CARGO_PKG_VERSION"));
+    payload.u64(entries.len() as u64);
+    for (path, entry) in entries {
+        payload.str(&path.to_string_lossy());
+        payload.u64(entry.stamp.size);
+        payload.u64(entry.stamp.mtime_ms);
+        payload.u64(entry.messages.len() as u64);
+        for msg in &entry.messages {
+            payload.message(msg);
+        }
+    }
+
+    let payload = payload.buf;
+    let mut out = Vec::with_capacity(HEADER_LEN + payload.len());
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    out.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+    out.extend_from_slice(&fnv1a(&payload).to_le_bytes());
+    out.extend_from_slice(&payload);
+    out
+}
+
+fn decode(data: &[u8]) -> Option<HashMap<PathBuf, CacheEntry>> {
+    let mut header = Decoder { buf: data.get(..HEADER_LEN)? };
+    if header.bytes(4)? != MAGIC || header.u32()? != FORMAT_VERSION {
+        return None;
+    }
+    let len = header.u64()? as usize;
+    let checksum = header.u64()?;
+    let payload = &data[HEADER_LEN..];
+    if payload.len() != len || fnv1a(payload) != checksum {
+        return None;
+    }
+
+    let mut d = Decoder { buf: payload };
+    if d.str()? != env!("CARGO_PKG_VERSION") {
+        return None;
+    }
+
+    let count = d.u64()? as usize;
+    let mut entries = HashMap::with_capacity(count.min(payload.len()));
+    for _ in 0..count {
+        let path = PathBuf::from(d.str()?);
+        let stamp = FileStamp { size: d.u64()?, mtime_ms: d.u64()? };
+        let n = d.u64()? as usize;
+        let mut messages = Vec::with_capacity(n.min(payload.len()));
+        for _ in 0..n {
+            messages.push(d.message()?);
+        }
+        entries.insert(path, CacheEntry { stamp, messages });
+    }
+
+    d.buf.is_empty().then_some(entries)
+}
+
+#[derive(Default)]
+struct Encoder {
+    buf: Vec<u8>,
+}
+
+impl Encoder {
+    fn u64(&mut self, v: u64) {
+        self.buf.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn i64(&mut self, v: i64) {
+        self.buf.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn u32(&mut self, v: u32) {
+        self.buf.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn opt_u32(&mut self, v: Option<u32>) {
+        match v {
+            Some(v) => {
+                self.buf.push(1);
+                self.u32(v);
+            }
+            None => self.buf.push(0),
+        }
+    }
+
+    fn str(&mut self, s: &str) {
+        self.u64(s.len() as u64);
+        self.buf.extend_from_slice(s.as_bytes());
+    }
+
+    fn opt_str(&mut self, s: Option<&str>) {
+        match s {
+            Some(s) => {
+                self.buf.push(1);
+                self.str(s);
+            }
+            None => self.buf.push(0),
+        }
+    }
+
+    fn message(&mut self, msg: &UnifiedMessage) {
+        self.str(msg.source.as_str());
+        self.str(&msg.model_id);
+        self.str(msg.provider_id.as_str());
+        self.str(&msg.session_id);
+        self.i64(msg.timestamp);
+        self.str(&msg.date);
+        self.i64(msg.tokens.input);
+        self.i64(msg.tokens.output);
+        self.i64(msg.tokens.cache_read);
+        self.i64(msg.tokens.cache_write);
+        self.i64(msg.tokens.reasoning);
+        self.i64(msg.tokens.training);
+        self.u64(msg.cost.to_bits());
+        self.opt_str(msg.agent.as_deref());
+        self.opt_str(msg.dedup_key.as_deref());
+        self.buf.push(msg.estimated_timestamp as u8);
+        self.buf.push(msg.naive_timestamp as u8);
+        self.opt_str(msg.cwd.as_deref());
+        self.opt_str(msg.git_branch.as_deref());
+        self.buf.push(msg.sidechain as u8);
+        self.buf.push(msg.tool_call as u8);
+        self.opt_u32(msg.prompt_chars);
+        self.u32(msg.tool_calls);
+        self.opt_u32(msg.files_touched);
+        self.buf.push(msg.billed as u8);
+    }
+}
+
+struct Decoder<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Decoder<'a> {
+    fn bytes(&mut self, n: usize) -> Option<&'a [u8]> {
+        if self.buf.len() < n {
+            return None;
+        }
+        let (head, rest) = self.buf.split_at(n);
+        self.buf = rest;
+        Some(head)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.bytes(4)?.try_into().ok()?))
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        Some(u64::from_le_bytes(self.bytes(8)?.try_into().ok()?))
+    }
+
+    fn i64(&mut self) -> Option<i64> {
+        Some(i64::from_le_bytes(self.bytes(8)?.try_into().ok()?))
+    }
+
+    fn str(&mut self) -> Option<String> {
+        let len = self.u64()? as usize;
+        String::from_utf8(self.bytes(len)?.to_vec()).ok()
+    }
+
+    fn bool(&mut self) -> Option<bool> {
+        match self.bytes(1)?[0] {
+            0 => Some(false),
+            1 => Some(true),
+            _ => None,
+        }
+    }
+
+    fn opt_str(&mut self) -> Option<Option<String>> {
+        match self.bytes(1)?[0] {
+            0 => Some(None),
+            1 => Some(Some(self.str()?)),
+            _ => None,
+        }
+    }
+
+    fn opt_u32(&mut self) -> Option<Option<u32>> {
+        match self.bytes(1)?[0] {
+            0 => Some(None),
+            1 => Some(Some(self.u32()?)),
+            _ => None,
+        }
+    }
+
+    fn message(&mut self) -> Option<UnifiedMessage> {
+        Some(UnifiedMessage {
+            source: self.str()?.into(),
+            model_id: self.str()?,
+            provider_id: self.str()?.into(),
+            session_id: self.str()?,
+            timestamp: self.i64()?,
+            date: self.str()?,
+            tokens: TokenBreakdown {
+                input: self.i64()?,
+                output: self.i64()?,
+                cache_read: self.i64()?,
+                cache_write: self.i64()?,
+                reasoning: self.i64()?,
+                training: self.i64()?,
+            },
+            cost: f64::from_bits(self.u64()?),
+            agent: self.opt_str()?,
+            dedup_key: self.opt_str()?,
+            estimated_timestamp: self.bool()?,
+            naive_timestamp: self.bool()?,
+            cwd: self.opt_str()?,
+            git_branch: self.opt_str()?,
+            sidechain: self.bool()?,
+            tool_call: self.bool()?,
+            prompt_chars: self.opt_u32()?,
+            tool_calls: self.u32()?,
+            files_touched: self.opt_u32()?,
+            billed: self.bool()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tempfile::TempDir;
+
+    fn parse_lines(_: SessionType, path: &Path) -> Vec<UnifiedMessage> {
+        fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(|line| {
+                let mut msg = UnifiedMessage::new(
+                    "claude",
+                    line,
+                    "anthropic",
+                    "s1",
+                    1_700_000_000_000,
+                    TokenBreakdown { input: 10, output: 5, cache_read: 0, cache_write: 0, reasoning: 0, training: 0 },
+                    0.5,
+                );
+                msg.dedup_key = Some(format!("{}:{}", path.display(), line));
+                msg.estimated_timestamp = line.len() % 2 == 0;
+                msg
+            })
+            .collect()
+    }
+
+    fn setup(dir: &Path) -> Vec<(SessionType, PathBuf)> {
+        ["a.jsonl", "b.jsonl"]
+            .iter()
+            .map(|name| {
+                let path = dir.join(name);
+                fs::write(&path, "claude-sonnet-4\nclaude-opus-4-5\n").unwrap();
+                (SessionType::Claude, path)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_roundtrip_skips_reparse() {
+        let dir = TempDir::new().unwrap();
+        let files = setup(dir.path());
+        let cache_path = dir.path().join(CACHE_FILE);
+
+        let mut cache = ParseCache::load(&cache_path);
+        let first = cache.parse_files(&files, parse_lines).concat();
+        cache.save().unwrap();
+
+        let calls = AtomicUsize::new(0);
+        let mut cache = ParseCache::load(&cache_path);
+        let second = cache.parse_files(&files, |t, p| {
+            calls.fetch_add(1, Ordering::SeqCst);
+            parse_lines(t, p)
+        })
+        .concat();
+
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+        assert_eq!(second.len(), 4);
+        for (a, b) in first.iter().zip(&second) {
+            assert_eq!(a.model_id, b.model_id);
+            assert_eq!(a.date, b.date);
+            assert_eq!(a.cost, b.cost);
+            assert_eq!(a.agent, b.agent);
+            assert_eq!(a.dedup_key, b.dedup_key);
+            assert_eq!(a.estimated_timestamp, b.estimated_timestamp);
+        }
+    }
+
+    #[test]
+    fn test_loads_uncompressed_cache() {
+        let dir = TempDir::new().unwrap();
+        let files = setup(dir.path());
+        let cache_path = dir.path().join(CACHE_FILE);
+
+        let mut cache = ParseCache::load(&cache_path);
+        cache.parse_files(&files, parse_lines);
+        cache.save().unwrap();
+        let written = fs::read(&cache_path).unwrap();
+        assert!(!written.starts_with(MAGIC));
+
+        // As written before the cache was compressed
+        fs::write(&cache_path, encode(&cache.entries)).unwrap();
+        let calls = AtomicUsize::new(0);
+        let messages = ParseCache::load(&cache_path)
+            .parse_files(&files, |t, p| {
+                calls.fetch_add(1, Ordering::SeqCst);
+                parse_lines(t, p)
+            })
+            .concat();
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+        assert_eq!(messages.len(), 4);
+    }
+
+    #[test]
+    fn test_modified_file_reparsed() {
+        let dir = TempDir::new().unwrap();
+        let files = setup(dir.path());
+        let cache_path = dir.path().join(CACHE_FILE);
+
+        let mut cache = ParseCache::load(&cache_path);
+        cache.parse_files(&files, parse_lines);
+        cache.save().unwrap();
+
+        fs::write(&files[0].1, "gpt-4o\n").unwrap();
+
+        let calls = AtomicUsize::new(0);
+        let mut cache = ParseCache::load(&cache_path);
+        let messages = cache.parse_files(&files, |t, p| {
+            calls.fetch_add(1, Ordering::SeqCst);
+            parse_lines(t, p)
+        })
+        .concat();
+
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert_eq!(messages[0].model_id, "gpt-4o");
+        assert_eq!(messages.len(), 3);
+    }
+
+    #[test]
+    fn test_corrupt_cache_ignored() {
+        let dir = TempDir::new().unwrap();
+        let files = setup(dir.path());
+        let cache_path = dir.path().join(CACHE_FILE);
+
+        let mut cache = ParseCache::load(&cache_path);
+        cache.parse_files(&files, parse_lines);
+        cache.save().unwrap();
+
+        let mut data = cache::decompress(fs::read(&cache_path).unwrap()).unwrap();
+        let last = data.len() - 1;
+        data[last] ^= 0xFF;
+        fs::write(&cache_path, &data).unwrap();
+        assert!(ParseCache::load(&cache_path).entries.is_empty());
+
+        fs::write(&cache_path, &data[..HEADER_LEN + 3]).unwrap();
+        assert!(ParseCache::load(&cache_path).entries.is_empty());
+
+        fs::write(&cache_path, b"{\"not\": \"binary\"}").unwrap();
+        assert!(ParseCache::load(&cache_path).entries.is_empty());
+    }
+
+    #[test]
+    fn test_format_version_mismatch_ignored() {
+        let dir = TempDir::new().unwrap();
+        let files = setup(dir.path());
+        let cache_path = dir.path().join(CACHE_FILE);
+
+        let mut cache = ParseCache::load(&cache_path);
+        cache.parse_files(&files, parse_lines);
+        cache.save().unwrap();
+
+        let mut data = cache::decompress(fs::read(&cache_path).unwrap()).unwrap();
+        data[4..8].copy_from_slice(&(FORMAT_VERSION + 1).to_le_bytes());
+        fs::write(&cache_path, &data).unwrap();
+        assert!(ParseCache::load(&cache_path).entries.is_empty());
+    }
+
+    #[test]
+    fn test_prune_missing() {
+        let dir = TempDir::new().unwrap();
+        let files = setup(dir.path());
+        let mut cache = ParseCache::load(dir.path().join(CACHE_FILE));
+        cache.parse_files(&files, parse_lines);
+        cache.save().unwrap();
+
+        fs::remove_file(&files[1].1).unwrap();
+        assert_eq!(cache.prune_missing(), 1);
+        assert!(cache.entries.contains_key(&files[0].1));
+        assert_eq!(cache.prune_missing(), 0);
+    }
+
+    #[test]
+    fn test_unchanged_cache_not_rewritten() {
+        let dir = TempDir::new().unwrap();
+        let cache_path = dir.path().join(CACHE_FILE);
+        let mut cache = ParseCache::load(&cache_path);
+        cache.parse_files(&[], parse_lines);
+        cache.save().unwrap();
+        assert!(!cache_path.exists());
+    }
+}
@@ -1,16 +1,29 @@
 //! SIMD-accelerated JSON parser
 //!
-//! Uses simd-json for fast JSON parsing with SIMD instructions.
+//! Uses simd-json for fast JSON parsing with SIMD instructions, or serde_json
+//! in builds without the `simd` feature.
 
 use std::fs;
 use std::io::{BufRead, BufReader};
 use std::path::Path;
 
+/// Parse JSON in place; simd-json may overwrite `bytes`
+#[cfg(feature = "simd")]
+pub(crate) fn from_slice<T: serde::de::DeserializeOwned>(bytes: &mut [u8]) -> Result<T, String> {
+    simd_json::from_slice(bytes).map_err(|e| e.to_string())
+}
+
+/// Parse JSON in place; simd-json may overwrite `bytes`
+#[cfg(not(feature = "simd"))]
+pub(crate) fn from_slice<T: serde::de::DeserializeOwned>(bytes: &mut [u8]) -> Result<T, String> {
+    serde_json::from_slice(bytes).map_err(|e| e.to_string())
+}
+
 /// Parse a JSON file using SIMD-accelerated parsing
 pub fn parse_json_file<T: serde::de::DeserializeOwned>(path: &Path) -> Result<T, ParseError> {
     let mut data = fs::read(path).map_err(|e| ParseError::IoError(e.to_string()))?;
 
-    simd_json::from_slice(&mut data).map_err(|e| ParseError::JsonError(e.to_string()))
+    from_slice(&mut data).map_err(ParseError::JsonError)
 }
 
 /// Parse a JSONL file (one JSON object per line)
@@ -30,7 +43,7 @@ where
         }
 
         let mut bytes = trimmed.as_bytes().to_vec();
-        match simd_json::from_slice::<T>(&mut bytes) {
+        match from_slice::<T>(&mut bytes) {
             Ok(value) => process(value),
             Err(_) => continue, // Skip malformed lines (match TypeScript behavior)
         }
@@ -175,7 +188,7 @@ mod tests {
 
         let mut file = File::create(&file_path).unwrap();
         writeln!(file, r#"{{"name": "a", "value": 1}}"#).unwrap();
-        writeln!(file, "").unwrap(); // Empty line
+        writeln!(file).unwrap(); // Empty line
         writeln!(file, "   ").unwrap(); // Whitespace only
         writeln!(file, r#"{{"name": "b", "value": 2}}"#).unwrap();
 
@@ -262,4 +275,48 @@ mod tests {
         let json_error = ParseError::JsonError("unexpected token".to_string());
         assert!(json_error.to_string().contains("JSON parse error"));
     }
+
+    fn fuzz_line() -> impl proptest::strategy::Strategy<Value = String> {
+        use crate::testutil::string;
+        use proptest::prelude::*;
+        prop_oneof![
+            string(64),
+            (string(12), any::<i64>()).prop_map(|(name, value)| {
+                format!(r#"{{"name": "{}", "value": {}}}"#, name.replace(['"', '\\'], ""), value)
+            }),
+            (0..1000usize, string(4)).prop_map(|(name, value)| format!(r#"{{"name": {}, "value": "{}""#, name, value)),
+            Just(String::new()),
+        ]
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn prop_parse_jsonl_file_never_panics(lines in proptest::collection::vec(fuzz_line(), 0..20)) {
+            let dir = TempDir::new().unwrap();
+            let file_path = dir.path().join("fuzz.jsonl");
+            std::fs::write(&file_path, lines.join("\n")).unwrap();
+
+            let mut parsed = 0usize;
+            parse_jsonl_file(&file_path, |_: TestStruct| parsed += 1).unwrap();
+            proptest::prop_assert!(parsed <= lines.len());
+        }
+
+        #[test]
+        fn prop_parse_jsonl_file_keeps_valid_lines(
+            values in proptest::collection::vec(proptest::num::i32::ANY, 0..20),
+        ) {
+            let content: Vec<String> = values
+                .iter()
+                .map(|v| format!(r#"{{"name": "n", "value": {}}}"#, v))
+                .collect();
+
+            let dir = TempDir::new().unwrap();
+            let file_path = dir.path().join("valid.jsonl");
+            std::fs::write(&file_path, content.join("\n")).unwrap();
+
+            let mut parsed = Vec::new();
+            parse_jsonl_file(&file_path, |item: TestStruct| parsed.push(item.value)).unwrap();
+            proptest::prop_assert_eq!(parsed, values);
+        }
+    }
 }
@@ -0,0 +1,267 @@
+//! Concurrent ingest
+//!
+//! A cold run waits on the network for prices, on the disk to find session
+//! files and on the CPU to parse them. Nothing needs prices until messages
+//! are parsed, so [`ingest`] runs all of it as one group of tasks:
+//!
+//! - prices load on the calling task;
+//! - each source's directory is walked on its own thread, and the parse
+//!   cache loads on another;
+//! - a source's files are parsed as soon as its walk is done, on a pool of
+//!   `parseThreads` threads (settings.json; one per CPU by default), while
+//!   slower walks carry on.
+//!
+//! The tasks share a cancellation token. When prices fail to load, or the
+//! caller stops waiting and drops the future, the token is cancelled and
+//! parsing stops at the next file instead of finishing work nobody will
+//! read. A cancelled parse doesn't write the parse cache. Prices are only
+//! waited for when something was parsed.
+
+use crate::scanner::{self, SessionType};
+use crate::sessions::UnifiedMessage;
+use crate::{cache_gc, clock, config, parse_cache, read_only_home, read_session_file};
+use futures::future::{self, Either};
+use rayon::prelude::*;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+
+/// Parsed session files, and prices when there was anything to price
+pub struct Ingest<P> {
+    /// Messages of each file with its source type, in scan order
+    pub parsed: Vec<(SessionType, Vec<UnifiedMessage>)>,
+    /// `None` when no messages were parsed
+    pub prices: Option<P>,
+}
+
+/// The cancellation token an ingest's tasks share
+#[derive(Clone, Default)]
+struct Cancel(Arc<AtomicBool>);
+
+impl Cancel {
+    fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Cancels its token when dropped
+struct CancelOnDrop(Cancel);
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        self.0.cancel();
+    }
+}
+
+/// Find and parse the session files of `sources` while `prices` loads. With
+/// `use_cache`, unchanged files come from the parse cache. An error loading
+/// prices cancels the parse and is returned as is. Runs on any executor.
+pub async fn ingest<P, E, F>(home_dir: &str, sources: &[String], use_cache: bool, prices: F) -> Result<Ingest<P>, E>
+where
+    F: Future<Output = Result<P, E>>,
+{
+    let cancel = Cancel::default();
+    // Stops the parse however this returns, including by being dropped
+    let _cancel_on_drop = CancelOnDrop(cancel.clone());
+    let (home_dir, sources, threads) = (home_dir.to_string(), sources.to_vec(), parse_threads());
+    let parsing = std::pin::pin!(blocking(move || parse_sources(&home_dir, &sources, use_cache, threads, &cancel)));
+    let mut prices = std::pin::pin!(prices);
+
+    let (parsed, loaded) = match future::select(parsing, prices.as_mut()).await {
+        Either::Left((parsed, _)) => (parsed, None),
+        Either::Right((result, parsing)) => {
+            let loaded = result?;
+            (parsing.await, Some(loaded))
+        }
+    };
+    if parsed.iter().all(|(_, messages)| messages.is_empty()) {
+        return Ok(Ingest { parsed, prices: None });
+    }
+    let prices = match loaded {
+        Some(prices) => prices,
+        None => prices.await?,
+    };
+    Ok(Ingest { parsed, prices: Some(prices) })
+}
+
+/// Every session file of `sources`, found off the async runtime, in scan
+/// order; for runs that parse files themselves but can still look for them
+/// while prices load
+pub async fn discover(home_dir: &str, sources: &[String]) -> Vec<(SessionType, PathBuf)> {
+    let (home_dir, sources) = (home_dir.to_string(), sources.to_vec());
+    blocking(move || scanner::scan_all_sources(&home_dir, &sources).all_files()).await
+}
+
+/// Run `f` on a thread of its own, waiting for it without blocking the
+/// executor. A panic in `f` is resumed in the caller.
+pub(crate) async fn blocking<T, F>(f: F) -> T
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    let (done, finished) = futures::channel::oneshot::channel::<()>();
+    let handle = thread::spawn(move || {
+        let value = f();
+        let _ = done.send(());
+        value
+    });
+    // Resolves on a panic too, when `done` is dropped
+    let _ = finished.await;
+    handle.join().unwrap_or_else(|e| std::panic::resume_unwind(e))
+}
+
+/// Threads session files are parsed on
+fn parse_threads() -> usize {
+    config::settings()
+        .parse_threads
+        .unwrap_or_else(|| thread::available_parallelism().map_or(1, |n| n.get()))
+        .max(1)
+}
+
+/// Walk every source and parse its files on `threads` threads, returning
+/// messages per file with their source type, in the order
+/// [`scanner::ScanResult::all_files`] lists them. In read-only mode
+/// (`TOKSCALE_HOME`) the parse cache is skipped, so reading a backup leaves
+/// your own cache untouched. Returns nothing once `cancel` fires.
+fn parse_sources(
+    home_dir: &str,
+    sources: &[String],
+    use_cache: bool,
+    threads: usize,
+    cancel: &Cancel,
+) -> Vec<(SessionType, Vec<UnifiedMessage>)> {
+    let roots = scanner::source_roots(home_dir, sources);
+    let use_cache = use_cache && !read_only_home();
+    let pool = rayon::ThreadPoolBuilder::new().num_threads(threads).build();
+    let parse = |session_type, path: &Path| {
+        if cancel.is_cancelled() {
+            return Vec::new();
+        }
+        read_session_file(session_type, path)
+    };
+
+    let mut by_source: Vec<Vec<(SessionType, Vec<UnifiedMessage>)>> = roots.iter().map(|_| Vec::new()).collect();
+    let mut cache = None;
+    thread::scope(|scope| {
+        let loading = use_cache
+            .then(|| scope.spawn(|| parse_cache::ParseCache::load(parse_cache::ParseCache::default_path())));
+        let (tx, rx) = mpsc::channel();
+        for (index, (session_type, root, pattern)) in roots.iter().enumerate() {
+            let tx = tx.clone();
+            scope.spawn(move || {
+                if !cancel.is_cancelled() {
+                    let _ = tx.send((index, *session_type, scanner::scan_directory(root, pattern)));
+                }
+            });
+        }
+        drop(tx);
+        cache = loading.map(|loading| loading.join().unwrap_or_else(|e| std::panic::resume_unwind(e)));
+
+        // Walks finish in any order; each source keeps its slot
+        for (index, session_type, paths) in rx {
+            if cancel.is_cancelled() {
+                break;
+            }
+            let files: Vec<(SessionType, PathBuf)> = paths.into_iter().map(|path| (session_type, path)).collect();
+            let mut run = || {
+                let mut parsed = match &mut cache {
+                    Some(cache) => cache.parse_files(&files, parse),
+                    None => files.par_iter().map(|(session_type, path)| parse(*session_type, path)).collect(),
+                };
+                parsed.par_iter_mut().for_each(|messages| clock::sanitize(messages));
+                parsed
+            };
+            let parsed = match &pool {
+                Ok(pool) => pool.install(run),
+                // Couldn't start the threads; parse on the shared pool
+                Err(_) => run(),
+            };
+            by_source[index] = files.into_iter().map(|(session_type, _)| session_type).zip(parsed).collect();
+        }
+    });
+
+    if cancel.is_cancelled() {
+        return Vec::new();
+    }
+    if let Some(mut cache) = cache {
+        if let Err(e) = cache.save() {
+            eprintln!("[tokscale] failed to write parse cache: {}", e);
+        }
+        cache_gc::maybe_auto_collect();
+    }
+    by_source.into_iter().flatten().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+    use std::fs;
+
+    const LINE: &str = r#"{"type":"assistant","timestamp":"2024-12-01T10:00:00.000Z","requestId":"req_001","message":{"id":"msg_001","model":"claude-3-5-sonnet","usage":{"input_tokens":100,"output_tokens":50}}}"#;
+
+    /// A home directory with Claude sessions in two projects
+    fn home() -> tempfile::TempDir {
+        let home = tempfile::tempdir().unwrap();
+        for (project, session) in [("a", "s1"), ("a", "s2"), ("b", "s3")] {
+            let dir = home.path().join(".claude/projects").join(project);
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join(format!("{}.jsonl", session)), LINE).unwrap();
+        }
+        home
+    }
+
+    fn sources() -> Vec<String> {
+        vec!["claude".to_string(), "gemini".to_string()]
+    }
+
+    #[test]
+    fn test_parses_in_scan_order() {
+        let home = home();
+        let home_dir = home.path().to_string_lossy();
+        let files = scanner::scan_all_sources(&home_dir, &sources()).all_files();
+        let parsed = parse_sources(&home_dir, &sources(), false, 2, &Cancel::default());
+
+        assert_eq!(parsed.len(), files.len());
+        let sessions: Vec<&str> = parsed.iter().map(|(_, messages)| messages[0].session_id.as_str()).collect();
+        let stems: Vec<&str> = files.iter().map(|(_, path)| path.file_stem().unwrap().to_str().unwrap()).collect();
+        assert_eq!(sessions, stems);
+    }
+
+    #[test]
+    fn test_cancelled_parse_returns_nothing() {
+        let home = home();
+        let cancel = Cancel::default();
+        cancel.cancel();
+        assert!(parse_sources(&home.path().to_string_lossy(), &sources(), false, 2, &cancel).is_empty());
+    }
+
+    #[test]
+    fn test_prices_only_awaited_when_needed() {
+        let empty = tempfile::tempdir().unwrap();
+        // Never loads, and nothing to price
+        let never = std::future::pending::<Result<(), String>>();
+        let ingested = block_on(ingest(&empty.path().to_string_lossy(), &sources(), false, never)).unwrap();
+        assert!(ingested.parsed.is_empty() && ingested.prices.is_none());
+
+        let home = home();
+        let priced = async { Ok::<_, String>(42) };
+        let ingested = block_on(ingest(&home.path().to_string_lossy(), &sources(), false, priced)).unwrap();
+        assert_eq!((ingested.parsed.len(), ingested.prices), (3, Some(42)));
+    }
+
+    #[test]
+    fn test_price_error_returned() {
+        let home = home();
+        let failed = block_on(ingest(&home.path().to_string_lossy(), &sources(), false, async {
+            Err::<(), _>("offline".to_string())
+        }));
+        assert_eq!(failed.err().as_deref(), Some("offline"));
+    }
+}
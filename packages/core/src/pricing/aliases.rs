@@ -1,14 +1,68 @@
 use std::collections::HashMap;
 use once_cell::sync::Lazy;
 
+/// Names tools log for a model, grouped by provider, mapped to the id the
+/// price tables use
+const ALIAS_GROUPS: &[(&str, &[(&str, &str)])] = &[
+    (
+        "opencode",
+        &[("big-pickle", "glm-4.7"), ("big pickle", "glm-4.7"), ("bigpickle", "glm-4.7")],
+    ),
+    (
+        "xai",
+        &[
+            ("grok-4-latest", "grok-4"),
+            ("grok-4-fast", "grok-4-fast-reasoning"),
+            ("grok-3-latest", "grok-3"),
+            ("grok-3-beta", "grok-3"),
+            ("grok-3-mini-latest", "grok-3-mini"),
+            ("grok-3-mini-beta", "grok-3-mini"),
+            ("grok-2-latest", "grok-2-1212"),
+        ],
+    ),
+    (
+        "mistral",
+        &[
+            ("codestral", "codestral-latest"),
+            ("devstral", "devstral-medium-latest"),
+            ("devstral-medium", "devstral-medium-latest"),
+            ("devstral-small", "devstral-small-latest"),
+            ("magistral-medium", "magistral-medium-latest"),
+            ("magistral-small", "magistral-small-latest"),
+            ("mistral-large", "mistral-large-latest"),
+            ("mistral-medium", "mistral-medium-latest"),
+            ("mistral-small", "mistral-small-latest"),
+            ("pixtral-large", "pixtral-large-latest"),
+            ("mistral-nemo", "open-mistral-nemo"),
+        ],
+    ),
+];
+
 static MODEL_ALIASES: Lazy<HashMap<&'static str, &'static str>> = Lazy::new(|| {
-    let mut m = HashMap::new();
-    m.insert("big-pickle", "glm-4.7");
-    m.insert("big pickle", "glm-4.7");
-    m.insert("bigpickle", "glm-4.7");
-    m
+    ALIAS_GROUPS
+        .iter()
+        .flat_map(|(_, aliases)| aliases.iter().copied())
+        .collect()
 });
 
 pub fn resolve_alias(model_id: &str) -> Option<&'static str> {
     MODEL_ALIASES.get(model_id.to_lowercase().as_str()).copied()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_aliases_are_unique_and_resolved() {
+        let total: usize = ALIAS_GROUPS.iter().map(|(_, aliases)| aliases.len()).sum();
+        assert_eq!(MODEL_ALIASES.len(), total);
+        // An alias never points at another alias
+        for target in MODEL_ALIASES.values() {
+            assert!(!MODEL_ALIASES.contains_key(target), "{} is itself an alias", target);
+        }
+        assert_eq!(resolve_alias("Grok-4-Latest"), Some("grok-4"));
+        assert_eq!(resolve_alias("devstral"), Some("devstral-medium-latest"));
+        assert_eq!(resolve_alias("grok-4"), None);
+    }
+}
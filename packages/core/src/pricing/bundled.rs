@@ -0,0 +1,101 @@
+//! Bundled rates for providers LiteLLM lags on
+//!
+//! New xAI and Mistral models reach agent logs before LiteLLM lists them, and
+//! Mistral publishes no price API to fetch instead. This table is the last
+//! resort of a lookup, after LiteLLM, OpenRouter and registered providers, so
+//! those models cost their list price rather than $0 until the fetched tables
+//! catch up. Rates are USD per million tokens from the providers' pricing
+//! pages.
+
+use super::litellm::ModelPricing;
+
+pub const SOURCE: &str = "Bundled";
+
+/// (model family, input, output, cache read), USD per million tokens
+const RATES: &[(&str, f64, f64, Option<f64>)] = &[
+    // xAI
+    ("grok-4", 3.0, 15.0, Some(0.75)),
+    ("grok-4-fast-reasoning", 0.2, 0.5, Some(0.05)),
+    ("grok-4-fast-non-reasoning", 0.2, 0.5, Some(0.05)),
+    ("grok-code-fast-1", 0.2, 1.5, Some(0.02)),
+    ("grok-3", 3.0, 15.0, Some(0.75)),
+    ("grok-3-mini", 0.3, 0.5, Some(0.075)),
+    ("grok-2", 2.0, 10.0, None),
+    ("grok-2-vision", 2.0, 10.0, None),
+    // Mistral
+    ("mistral-large", 2.0, 6.0, None),
+    ("mistral-medium", 0.4, 2.0, None),
+    ("mistral-small", 0.1, 0.3, None),
+    ("codestral", 0.3, 0.9, None),
+    ("devstral-medium", 0.4, 2.0, None),
+    ("devstral-small", 0.1, 0.3, None),
+    ("magistral-medium", 2.0, 5.0, None),
+    ("magistral-small", 0.5, 1.5, None),
+    ("ministral-8b", 0.1, 0.1, None),
+    ("ministral-3b", 0.04, 0.04, None),
+    ("pixtral-large", 2.0, 6.0, None),
+    ("open-mistral-nemo", 0.15, 0.15, None),
+];
+
+const PROVIDER_PREFIXES: &[&str] = &["xai/", "x-ai/", "mistral/", "mistralai/"];
+
+/// Model family of a lowercased id: without the provider prefix, `-latest`
+/// or a release date ("mistral/mistral-large-2411" -> "mistral-large")
+fn family(model_id: &str) -> &str {
+    let id = PROVIDER_PREFIXES
+        .iter()
+        .find_map(|prefix| model_id.strip_prefix(prefix))
+        .unwrap_or(model_id);
+    if let Some(base) = id.strip_suffix("-latest") {
+        return base;
+    }
+    match id.rsplit_once('-') {
+        Some((base, date)) if date.len() == 4 && date.bytes().all(|b| b.is_ascii_digit()) => base,
+        _ => id,
+    }
+}
+
+/// Bundled price for a lowercased model id, with the family it matched
+pub fn lookup(model_id: &str) -> Option<(&'static str, ModelPricing)> {
+    let family = family(model_id);
+    let &(key, input, output, cache_read) = RATES.iter().find(|(key, ..)| *key == family)?;
+    let per_token = |rate: f64| rate / 1_000_000.0;
+    Some((
+        key,
+        ModelPricing {
+            input_cost_per_token: Some(per_token(input)),
+            output_cost_per_token: Some(per_token(output)),
+            cache_read_input_token_cost: cache_read.map(per_token),
+            mode: Some("chat".to_string()),
+            ..Default::default()
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_family() {
+        assert_eq!(family("mistral/mistral-large-2411"), "mistral-large");
+        assert_eq!(family("mistralai/codestral-latest"), "codestral");
+        assert_eq!(family("grok-4-0709"), "grok-4");
+        assert_eq!(family("grok-code-fast-1"), "grok-code-fast-1");
+        assert_eq!(family("ministral-8b-latest"), "ministral-8b");
+    }
+
+    #[test]
+    fn test_lookup() {
+        let (key, pricing) = lookup("x-ai/grok-code-fast-1").unwrap();
+        assert_eq!(key, "grok-code-fast-1");
+        assert!((pricing.output_per_million().unwrap() - 1.5).abs() < 1e-9);
+        assert!((pricing.cache_read_per_million().unwrap() - 0.02).abs() < 1e-9);
+
+        let (key, pricing) = lookup("devstral-medium-2507").unwrap();
+        assert_eq!(key, "devstral-medium");
+        assert_eq!(pricing.cache_read_input_token_cost, None);
+
+        assert!(lookup("gpt-4o").is_none());
+    }
+}
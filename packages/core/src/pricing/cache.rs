@@ -1,14 +1,50 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::fs;
 use std::time::SystemTime;
 use serde::{Serialize, Deserialize};
 
 const CACHE_TTL_SECS: u64 = 3600;
+/// First bytes of a zstd frame; caches written before compression are plain
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xB5, 0x2F, 0xFD];
 
+/// Overrides the cache root (sandboxes, tests, CI)
+pub const CACHE_DIR_ENV: &str = "TOKSCALE_CACHE_DIR";
+/// Isolates caches per profile under `<cache root>/profiles/<name>`
+pub const PROFILE_ENV: &str = "TOKSCALE_PROFILE";
+
+/// Cache directory, honoring `TOKSCALE_CACHE_DIR` and `TOKSCALE_PROFILE`
 pub fn get_cache_dir() -> PathBuf {
-    dirs::cache_dir()
-        .unwrap_or_else(|| PathBuf::from("/tmp"))
-        .join("tokscale")
+    resolve_cache_dir(
+        std::env::var(CACHE_DIR_ENV).ok(),
+        std::env::var(PROFILE_ENV).ok(),
+    )
+}
+
+fn resolve_cache_dir(override_dir: Option<String>, profile: Option<String>) -> PathBuf {
+    let root = override_dir
+        .filter(|dir| !dir.trim().is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(|| {
+            dirs::cache_dir()
+                .unwrap_or_else(|| PathBuf::from("/tmp"))
+                .join("tokscale")
+        });
+
+    match profile.as_deref().map(sanitize_profile) {
+        Some(name) if !name.is_empty() => root.join("profiles").join(name),
+        _ => root,
+    }
+}
+
+/// Keep profile names to a single safe path component
+fn sanitize_profile(profile: &str) -> String {
+    profile
+        .trim()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect::<String>()
+        .trim_matches('_')
+        .to_string()
 }
 
 pub fn get_cache_path(filename: &str) -> PathBuf {
@@ -21,10 +57,29 @@ pub struct CachedData<T> {
     pub data: T,
 }
 
-pub fn load_cache<T: for<'de> Deserialize<'de>>(filename: &str) -> Option<T> {
-    let path = get_cache_path(filename);
-    let content = fs::read_to_string(&path).ok()?;
-    let cached: CachedData<T> = serde_json::from_str(&content).ok()?;
+/// `data` as a zstd frame
+pub(crate) fn compress(data: &[u8]) -> Vec<u8> {
+    zstd::encode_all(data, 0).unwrap_or_else(|_| data.to_vec())
+}
+
+/// Contents of a cache file written by [`compress`], or by a version that
+/// predates it. `None` for a zstd frame that can't be decoded.
+pub(crate) fn decompress(data: Vec<u8>) -> Option<Vec<u8>> {
+    if !data.starts_with(&ZSTD_MAGIC) {
+        return Some(data);
+    }
+    zstd::decode_all(data.as_slice()).ok()
+}
+
+/// Cached data in the file at `path`, whatever its age
+pub(crate) fn read_cache_file<T: for<'de> Deserialize<'de>>(path: &Path) -> Option<CachedData<T>> {
+    let content = decompress(fs::read(path).ok()?)?;
+    serde_json::from_slice(&content).ok()
+}
+
+/// Cached data in the file at `path`, if saved within the TTL
+pub(crate) fn read_fresh_cache_file<T: for<'de> Deserialize<'de>>(path: &Path) -> Option<CachedData<T>> {
+    let cached: CachedData<T> = read_cache_file(path)?;
     
     let now = SystemTime::now()
         .duration_since(SystemTime::UNIX_EPOCH)
@@ -35,12 +90,20 @@ pub fn load_cache<T: for<'de> Deserialize<'de>>(filename: &str) -> Option<T> {
         return None;
     }
     
-    Some(cached.data)
+    Some(cached)
+}
+
+/// Cached data at any age, for when a fetch failed and old prices beat none
+pub fn load_stale_cache_entry<T: for<'de> Deserialize<'de>>(filename: &str) -> Option<CachedData<T>> {
+    read_cache_file(&get_cache_path(filename))
 }
 
 pub fn save_cache<T: Serialize>(filename: &str, data: &T) -> Result<(), std::io::Error> {
-    let dir = get_cache_dir();
-    fs::create_dir_all(&dir)?;
+    save_cache_in(&get_cache_dir(), filename, data)
+}
+
+fn save_cache_in<T: Serialize>(dir: &Path, filename: &str, data: &T) -> Result<(), std::io::Error> {
+    fs::create_dir_all(dir)?;
     
     let now = SystemTime::now()
         .duration_since(SystemTime::UNIX_EPOCH)
@@ -48,9 +111,9 @@ pub fn save_cache<T: Serialize>(filename: &str, data: &T) -> Result<(), std::io:
         .as_secs();
     
     let cached = CachedData { timestamp: now, data };
-    let content = serde_json::to_string(&cached)?;
+    let content = compress(&serde_json::to_vec(&cached)?);
     
-    let final_path = get_cache_path(filename);
+    let final_path = dir.join(filename);
     let nanos = std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)
         .map(|d| d.as_nanos() as u64)
@@ -61,7 +124,7 @@ pub fn save_cache<T: Serialize>(filename: &str, data: &T) -> Result<(), std::io:
     use std::io::Write;
     let write_result = (|| {
         let mut file = fs::File::create(&tmp_path)?;
-        file.write_all(content.as_bytes())?;
+        file.write_all(&content)?;
         file.sync_all()?;
         fs::rename(&tmp_path, &final_path)
     })();
@@ -72,3 +135,63 @@ pub fn save_cache<T: Serialize>(filename: &str, data: &T) -> Result<(), std::io:
     
     write_result
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_cache_dir() {
+        let dir = resolve_cache_dir(None, None);
+        assert!(dir.ends_with("tokscale"));
+    }
+
+    #[test]
+    fn test_cache_dir_override() {
+        assert_eq!(
+            resolve_cache_dir(Some("/sandbox/cache".into()), None),
+            PathBuf::from("/sandbox/cache")
+        );
+        assert!(resolve_cache_dir(Some("  ".into()), None).ends_with("tokscale"));
+    }
+
+    #[test]
+    fn test_profile_subdirectory() {
+        assert_eq!(
+            resolve_cache_dir(Some("/c".into()), Some("work".into())),
+            PathBuf::from("/c/profiles/work")
+        );
+        assert_eq!(
+            resolve_cache_dir(Some("/c".into()), Some("../etc".into())),
+            PathBuf::from("/c/profiles/etc")
+        );
+        assert_eq!(resolve_cache_dir(Some("/c".into()), Some("".into())), PathBuf::from("/c"));
+        assert_eq!(resolve_cache_dir(Some("/c".into()), Some("..".into())), PathBuf::from("/c"));
+    }
+
+    #[test]
+    fn test_cache_file_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let prices = std::collections::HashMap::from([("gpt-5".to_string(), 1.25)]);
+        save_cache_in(dir.path(), "pricing-test.json", &prices).unwrap();
+
+        let path = dir.path().join("pricing-test.json");
+        assert!(fs::read(&path).unwrap().starts_with(&ZSTD_MAGIC));
+        let cached: CachedData<std::collections::HashMap<String, f64>> = read_cache_file(&path).unwrap();
+        assert_eq!(cached.data, prices);
+        assert!(cached.timestamp > 0);
+    }
+
+    #[test]
+    fn test_reads_uncompressed_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("pricing-test.json");
+        fs::write(&path, r#"{"timestamp":1,"data":[1,2]}"#).unwrap();
+
+        let cached: CachedData<Vec<u32>> = read_cache_file(&path).unwrap();
+        assert_eq!(cached.data, vec![1, 2]);
+
+        fs::write(&path, [&ZSTD_MAGIC[..], b"garbage"].concat()).unwrap();
+        assert!(read_cache_file::<Vec<u32>>(&path).is_none());
+    }
+}
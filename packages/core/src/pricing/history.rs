@@ -0,0 +1,244 @@
+//! Price history
+//!
+//! The pricing cache only holds the latest tables. To reproduce a report as
+//! it would have been generated on a past date, each fetched LiteLLM and
+//! OpenRouter table is also archived under
+//! `pricing-history/<source>/<YYYY-MM-DD>.json` (local date of the fetch).
+//! A table is only written when it differs from the newest one archived, so
+//! the archive grows with price changes rather than with every fetch; the
+//! last change of a day wins.
+//!
+//! Cache garbage collection leaves the archive alone. Prices from before the
+//! first archived fetch are not known.
+//!
+//! OpenRouter's prices move with the endpoints serving a model, so messages
+//! priced from OpenRouter are charged the rate in effect when they were sent
+//! rather than today's (see [`PriceSeries`]). Each archived table counts as
+//! observed at noon UTC on its date, and the current table at its fetch time.
+//! Between two observations each rate is interpolated linearly, since the
+//! change happened at some unknown moment between them; before the first and
+//! after the last the nearest observation holds.
+
+use super::cache::get_cache_dir;
+use super::litellm::{ModelPricing, PricingDataset};
+use chrono::NaiveDate;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const HISTORY_DIR: &str = "pricing-history";
+
+fn source_dir(root: &Path, source: &str) -> PathBuf {
+    root.join(HISTORY_DIR).join(source)
+}
+
+/// Archived dates for `source`, oldest first
+fn dates(root: &Path, source: &str) -> Vec<NaiveDate> {
+    let Ok(entries) = fs::read_dir(source_dir(root, source)) else {
+        return Vec::new();
+    };
+    let mut dates: Vec<NaiveDate> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name();
+            let stem = name.to_str()?.strip_suffix(".json")?;
+            NaiveDate::parse_from_str(stem, "%Y-%m-%d").ok()
+        })
+        .collect();
+    dates.sort();
+    dates
+}
+
+fn path(root: &Path, source: &str, date: NaiveDate) -> PathBuf {
+    source_dir(root, source).join(format!("{}.json", date.format("%Y-%m-%d")))
+}
+
+fn archive_in(root: &Path, source: &str, table: &PricingDataset, date: NaiveDate) -> std::io::Result<bool> {
+    // Sorted keys, so an unchanged table serializes to the same bytes
+    let sorted: BTreeMap<&String, _> = table.iter().collect();
+    let content = serde_json::to_vec(&sorted)?;
+    if let Some(newest) = dates(root, source).last() {
+        if fs::read(path(root, source, *newest)).is_ok_and(|previous| previous == content) {
+            return Ok(false);
+        }
+    }
+
+    let dir = source_dir(root, source);
+    fs::create_dir_all(&dir)?;
+    let tmp = dir.join(format!(".{}.{}.tmp", date.format("%Y-%m-%d"), std::process::id()));
+    fs::write(&tmp, &content)?;
+    fs::rename(&tmp, path(root, source, date))?;
+    Ok(true)
+}
+
+/// Archive a freshly fetched table under today's date, unless it matches
+/// the newest archived one. Failures are reported and otherwise ignored.
+pub fn archive(source: &str, table: &PricingDataset) {
+    if table.is_empty() {
+        return;
+    }
+    let today = chrono::Local::now().date_naive();
+    if let Err(e) = archive_in(&get_cache_dir(), source, table, today) {
+        eprintln!("[tokscale] failed to archive {} prices: {}", source, e);
+    }
+}
+
+fn load_in(root: &Path, source: &str, as_of: NaiveDate) -> Option<PricingDataset> {
+    let date = dates(root, source).into_iter().rev().find(|d| *d <= as_of)?;
+    serde_json::from_slice(&fs::read(path(root, source, date)).ok()?).ok()
+}
+
+pub fn parse_date(date: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map_err(|_| format!("expected a date like 2025-04-30, got \"{}\"", date))
+}
+
+/// Tables in effect on `as_of`: the newest archived on or before it. LiteLLM
+/// is required; OpenRouter is empty if none was archived by then.
+pub fn tables_as_of(as_of: NaiveDate) -> Result<(PricingDataset, PricingDataset), String> {
+    tables_as_of_in(&get_cache_dir(), as_of)
+}
+
+fn tables_as_of_in(root: &Path, as_of: NaiveDate) -> Result<(PricingDataset, PricingDataset), String> {
+    let Some(litellm) = load_in(root, "litellm", as_of) else {
+        return Err(match dates(root, "litellm").first() {
+            Some(first) => format!("no prices archived on or before {}; the price history starts on {}", as_of, first),
+            None => "no price history yet; prices are archived as they are fetched".to_string(),
+        });
+    };
+    let openrouter = load_in(root, "openrouter", as_of).unwrap_or_default();
+    Ok((litellm, openrouter))
+}
+
+/// Hour of the day (UTC) an archived table counts as observed at
+const OBSERVED_HOUR: u32 = 12;
+
+/// Archived rates of one source by model, oldest first, for pricing each
+/// message at the rates in effect when it was sent
+#[derive(Debug, Default)]
+pub struct PriceSeries {
+    /// Model key → (Unix milliseconds, pricing)
+    models: HashMap<String, Vec<(i64, ModelPricing)>>,
+}
+
+impl PriceSeries {
+    /// `tables` observed at the given Unix milliseconds, in any order
+    pub fn new(mut tables: Vec<(i64, PricingDataset)>) -> Self {
+        tables.sort_by_key(|(at, _)| *at);
+        let mut models: HashMap<String, Vec<(i64, ModelPricing)>> = HashMap::new();
+        for (at, table) in tables {
+            for (key, pricing) in table {
+                models.entry(key).or_default().push((at, pricing));
+            }
+        }
+        Self { models }
+    }
+
+    /// `key`'s rates at `timestamp_ms`; `None` if it was never archived
+    pub fn at(&self, key: &str, timestamp_ms: i64) -> Option<ModelPricing> {
+        let points = self.models.get(key)?;
+        let after = points.partition_point(|(at, _)| *at <= timestamp_ms);
+        let (before, after) = match (after.checked_sub(1).map(|i| &points[i]), points.get(after)) {
+            (Some(before), Some(after)) => (before, after),
+            (Some((_, only)), None) | (None, Some((_, only))) => return Some(only.clone()),
+            (None, None) => return None,
+        };
+        let weight = (timestamp_ms - before.0) as f64 / (after.0 - before.0) as f64;
+        let lerp = |from: Option<f64>, to: Option<f64>| match (from, to) {
+            (Some(from), Some(to)) => Some(from + (to - from) * weight),
+            (from, to) => from.or(to),
+        };
+        let (from, to) = (&before.1, &after.1);
+        Some(ModelPricing {
+            input_cost_per_token: lerp(from.input_cost_per_token, to.input_cost_per_token),
+            output_cost_per_token: lerp(from.output_cost_per_token, to.output_cost_per_token),
+            cache_creation_input_token_cost: lerp(
+                from.cache_creation_input_token_cost,
+                to.cache_creation_input_token_cost,
+            ),
+            cache_read_input_token_cost: lerp(from.cache_read_input_token_cost, to.cache_read_input_token_cost),
+            training_cost_per_token: lerp(from.training_cost_per_token, to.training_cost_per_token),
+            ..from.clone()
+        })
+    }
+}
+
+fn observed_at(date: NaiveDate) -> i64 {
+    date.and_hms_opt(OBSERVED_HOUR, 0, 0).map_or(0, |at| at.and_utc().timestamp_millis())
+}
+
+/// Every archived table of `source`, plus `current` fetched at `fetched_at`
+/// (seconds since the epoch; 0 if unknown)
+pub fn series_in(root: &Path, source: &str, current: &PricingDataset, fetched_at: u64) -> PriceSeries {
+    let mut tables: Vec<(i64, PricingDataset)> = dates(root, source)
+        .into_iter()
+        .filter_map(|date| {
+            let table = serde_json::from_slice(&fs::read(path(root, source, date)).ok()?).ok()?;
+            Some((observed_at(date), table))
+        })
+        .collect();
+    if fetched_at > 0 && !current.is_empty() {
+        tables.push((fetched_at as i64 * 1000, current.clone()));
+    }
+    PriceSeries::new(tables)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pricing::ModelPricing;
+    use tempfile::TempDir;
+
+    fn table(price: f64) -> PricingDataset {
+        PricingDataset::from([(
+            "claude-sonnet-4".to_string(),
+            ModelPricing { input_cost_per_token: Some(price), ..Default::default() },
+        )])
+    }
+
+    fn day(date: &str) -> NaiveDate {
+        parse_date(date).unwrap()
+    }
+
+    #[test]
+    fn test_archives_changes_and_loads_as_of() {
+        let root = TempDir::new().unwrap();
+        assert!(archive_in(root.path(), "litellm", &table(3e-6), day("2025-03-01")).unwrap());
+        assert!(!archive_in(root.path(), "litellm", &table(3e-6), day("2025-03-15")).unwrap(), "unchanged");
+        assert!(archive_in(root.path(), "litellm", &table(2e-6), day("2025-05-01")).unwrap());
+
+        let price = |date: &str| {
+            let (litellm, openrouter) = tables_as_of_in(root.path(), day(date)).unwrap();
+            assert!(openrouter.is_empty());
+            litellm["claude-sonnet-4"].input_cost_per_token
+        };
+        assert_eq!(price("2025-04-30"), Some(3e-6));
+        assert_eq!(price("2025-05-01"), Some(2e-6));
+
+        let err = tables_as_of_in(root.path(), day("2025-02-01")).unwrap_err();
+        assert!(err.contains("starts on 2025-03-01"), "{}", err);
+        assert!(tables_as_of_in(TempDir::new().unwrap().path(), day("2025-02-01")).is_err());
+        assert!(parse_date("04/30/2025").is_err());
+    }
+
+    #[test]
+    fn test_series_interpolates_between_snapshots() {
+        let root = TempDir::new().unwrap();
+        archive_in(root.path(), "openrouter", &table(2e-6), day("2025-03-01")).unwrap();
+        archive_in(root.path(), "openrouter", &table(4e-6), day("2025-03-03")).unwrap();
+        let current = table(1e-6);
+        let fetched_at = observed_at(day("2025-03-05")) as u64 / 1000;
+        let series = series_in(root.path(), "openrouter", &current, fetched_at);
+
+        let price = |date: &str, hour: u32| {
+            let at = day(date).and_hms_opt(hour, 0, 0).unwrap().and_utc().timestamp_millis();
+            series.at("claude-sonnet-4", at).unwrap().input_cost_per_token.unwrap()
+        };
+        assert!((price("2025-02-01", 0) - 2e-6).abs() < 1e-12, "before the first snapshot");
+        assert!((price("2025-03-01", 12) - 2e-6).abs() < 1e-12);
+        assert!((price("2025-03-02", 12) - 3e-6).abs() < 1e-12, "halfway between snapshots");
+        assert!((price("2025-03-04", 12) - 2.5e-6).abs() < 1e-12, "towards the current table");
+        assert!((price("2025-06-01", 0) - 1e-6).abs() < 1e-12, "after the current table");
+        assert!(series.at("unknown-model", 0).is_none());
+    }
+}
@@ -1,10 +1,17 @@
-use super::cache;
+use super::provider::PricingProvider;
+use super::windows::PriceWindow;
+#[cfg(feature = "network")]
+use super::telemetry;
+use futures::future::BoxFuture;
 use std::collections::HashMap;
 use serde::{Serialize, Deserialize};
 
-const CACHE_FILENAME: &str = "pricing-litellm.json";
+const SOURCE: &str = "litellm";
+#[cfg(feature = "network")]
 const PRICING_URL: &str = "https://raw.githubusercontent.com/BerriAI/litellm/main/model_prices_and_context_window.json";
+#[cfg(feature = "network")]
 const MAX_RETRIES: u32 = 3;
+#[cfg(feature = "network")]
 const INITIAL_BACKOFF_MS: u64 = 200;
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -13,34 +20,71 @@ pub struct ModelPricing {
     pub output_cost_per_token: Option<f64>,
     pub cache_creation_input_token_cost: Option<f64>,
     pub cache_read_input_token_cost: Option<f64>,
+    /// What LiteLLM lists the model as: "chat", "embedding", ...
+    pub mode: Option<String>,
+    /// Per fine-tuning training token
+    pub training_cost_per_token: Option<f64>,
+    /// Context window: the most input (cached or not) one request can carry
+    #[serde(default, deserialize_with = "token_limit")]
+    pub max_input_tokens: Option<u64>,
+    /// Hours at which the rates are scaled (see [`super::windows`])
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub time_windows: Vec<PriceWindow>,
+}
+
+/// A positive token count, or `None` for anything else. LiteLLM's
+/// `sample_spec` entry documents fields with strings in place of numbers.
+fn token_limit<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Option<u64>, D::Error> {
+    let value = serde_json::Value::deserialize(deserializer)?;
+    Ok(value.as_f64().filter(|n| n.is_finite() && *n >= 1.0).map(|n| n as u64))
 }
 
 pub type PricingDataset = HashMap<String, ModelPricing>;
 
-pub fn load_cached() -> Option<PricingDataset> {
-    cache::load_cache(CACHE_FILENAME)
-}
+/// LiteLLM's community-maintained price table
+pub struct LiteLlm;
 
-pub async fn fetch() -> Result<PricingDataset, reqwest::Error> {
-    if let Some(cached) = load_cached() {
-        return Ok(cached);
+impl PricingProvider for LiteLlm {
+    fn name(&self) -> &str {
+        SOURCE
     }
-    
+
+    fn required(&self) -> bool {
+        true
+    }
+
+    fn fetch(&self) -> BoxFuture<'_, Result<PricingDataset, String>> {
+        Box::pin(fetch())
+    }
+}
+
+#[cfg(not(feature = "network"))]
+async fn fetch() -> Result<PricingDataset, String> {
+    Err(super::OFFLINE.to_string())
+}
+
+#[cfg(feature = "network")]
+async fn fetch() -> Result<PricingDataset, String> {
     let client = reqwest::Client::builder()
         .timeout(std::time::Duration::from_secs(30))
         .connect_timeout(std::time::Duration::from_secs(10))
-        .build()?;
+        .build()
+        .map_err(|e| e.to_string())?;
     
-    let mut last_error: Option<reqwest::Error> = None;
+    let mut last_error: Option<String> = None;
     
     for attempt in 0..MAX_RETRIES {
-        match client.get(PRICING_URL).send().await {
+        if attempt > 0 {
+            telemetry::record(SOURCE, |t| t.retries += 1);
+        }
+        match telemetry::send(SOURCE, client.get(PRICING_URL)).await {
             Ok(response) => {
                 let status = response.status();
                 
                 if status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS {
                     eprintln!("[tokscale] LiteLLM HTTP {} (attempt {}/{})", status, attempt + 1, MAX_RETRIES);
                     let _ = response.bytes().await;
+                    last_error = Some(format!("HTTP {}", status));
                     if attempt < MAX_RETRIES - 1 {
                         tokio::time::sleep(std::time::Duration::from_millis(
                             INITIAL_BACKOFF_MS * (1 << attempt)
@@ -51,14 +95,11 @@ pub async fn fetch() -> Result<PricingDataset, reqwest::Error> {
                 
                 if !status.is_success() {
                     eprintln!("[tokscale] LiteLLM HTTP {}", status);
-                    return Err(response.error_for_status().unwrap_err());
+                    return Err(response.error_for_status().unwrap_err().to_string());
                 }
                 
-                match response.json::<PricingDataset>().await {
-                    Ok(data) => {
-                        let _ = cache::save_cache(CACHE_FILENAME, &data);
-                        return Ok(data);
-                    }
+                match telemetry::read_json::<PricingDataset>(SOURCE, response).await {
+                    Ok(data) => return Ok(data),
                     Err(e) => {
                         eprintln!("[tokscale] LiteLLM JSON parse failed: {}", e);
                         return Err(e);
@@ -67,7 +108,7 @@ pub async fn fetch() -> Result<PricingDataset, reqwest::Error> {
             }
             Err(e) => {
                 eprintln!("[tokscale] LiteLLM network error (attempt {}/{}): {}", attempt + 1, MAX_RETRIES, e);
-                last_error = Some(e);
+                last_error = Some(e.to_string());
                 if attempt < MAX_RETRIES - 1 {
                     tokio::time::sleep(std::time::Duration::from_millis(
                         INITIAL_BACKOFF_MS * (1 << attempt)
@@ -79,3 +120,23 @@ pub async fn fetch() -> Result<PricingDataset, reqwest::Error> {
     
     Err(last_error.expect("should have error after retries"))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_context_window_tolerates_placeholders() {
+        let data: PricingDataset = serde_json::from_str(
+            r#"{
+                "sample_spec": {"max_input_tokens": "max input tokens, if the provider specifies it", "mode": "one of: chat, embedding"},
+                "gpt-4o": {"input_cost_per_token": 2.5e-6, "max_input_tokens": 128000},
+                "tts-1": {"input_cost_per_token": 1.5e-5, "max_input_tokens": 0}
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(data["sample_spec"].max_input_tokens, None);
+        assert_eq!(data["gpt-4o"].max_input_tokens, Some(128_000));
+        assert_eq!(data["tts-1"].max_input_tokens, None);
+    }
+}
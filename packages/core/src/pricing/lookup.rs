@@ -1,7 +1,14 @@
-use super::{aliases, litellm::ModelPricing};
+use super::{aliases, bundled, litellm::ModelPricing, normalize::NormalizeRules, windows};
+use super::history::{self, PriceSeries};
+use super::merge::{self, Candidate, MergePolicy, Side};
+use crate::diagnostics::{self, PriceConflict};
+use crate::TokenBreakdown;
 use std::collections::HashMap;
-use std::sync::RwLock;
+use std::path::PathBuf;
+use std::sync::{OnceLock, RwLock};
 
+/// Default provider prefixes tried for bare model names, highest priority first.
+/// Overridable with `providerPrefixes` in settings.
 const PROVIDER_PREFIXES: &[&str] = &[
     "openai/",
     "anthropic/",
@@ -13,6 +20,8 @@ const PROVIDER_PREFIXES: &[&str] = &[
     "cohere/",
     "perplexity/",
     "x-ai/",
+    "xai/",
+    "mistral/",
 ];
 
 const ORIGINAL_PROVIDER_PREFIXES: &[&str] = &[
@@ -23,6 +32,7 @@ const ORIGINAL_PROVIDER_PREFIXES: &[&str] = &[
     "google/",
     "meta-llama/",
     "mistralai/",
+    "mistral/",
     "deepseek/",
     "z-ai/",
     "qwen/",
@@ -47,6 +57,25 @@ const FUZZY_BLOCKLIST: &[&str] = &["auto", "mini", "chat", "base"];
 
 const MIN_FUZZY_MATCH_LEN: usize = 5;
 
+/// Provider ids reported by tools, mapped to the LiteLLM key scope holding that
+/// backend's prices (e.g. Gemini on Vertex AI vs the Gemini API / AI Studio)
+const PROVIDER_SCOPES: &[(&str, &str)] = &[
+    ("vertex_ai", "vertex_ai/"),
+    ("vertex", "vertex_ai/"),
+    ("google-vertex", "vertex_ai/"),
+    ("google-vertex-anthropic", "vertex_ai/"),
+    ("gemini", "gemini/"),
+    ("google", "gemini/"),
+    ("google-ai-studio", "gemini/"),
+    ("bedrock", "bedrock/"),
+    ("amazon-bedrock", "bedrock/"),
+    ("azure", "azure/"),
+    ("xai", "xai/"),
+    ("x-ai", "xai/"),
+    ("mistral", "mistral/"),
+    ("mistralai", "mistral/"),
+];
+
 /// Quality/speed tier suffixes that should be stripped for pricing lookup
 /// These indicate provider-specific routing but don't affect the base model pricing
 /// Note: OpenCode Zen uses -xhigh suffix for extra-high quality tier
@@ -69,6 +98,7 @@ struct CachedResult {
     pricing: ModelPricing,
     source: String,
     matched_key: String,
+    matched_prefix: Option<String>,
 }
 
 pub struct PricingLookup {
@@ -79,13 +109,46 @@ pub struct PricingLookup {
     litellm_lower: HashMap<String, String>,
     openrouter_lower: HashMap<String, String>,
     openrouter_model_part: HashMap<String, String>,
+    normalize_rules: NormalizeRules,
+    provider_prefixes: Vec<String>,
+    derived_aliases: HashMap<String, String>,
+    /// Tables from registered providers, consulted before LiteLLM/OpenRouter
+    custom: Vec<CustomTable>,
+    merge_policy: MergePolicy,
+    /// When the LiteLLM and OpenRouter tables were fetched (0 if unknown)
+    snapshot_times: (u64, u64),
+    /// Cache directory holding the OpenRouter price history, to price dated
+    /// messages at the rates in effect when they were sent
+    openrouter_history_root: Option<PathBuf>,
+    /// Loaded when first needed
+    openrouter_history: OnceLock<PriceSeries>,
     lookup_cache: RwLock<HashMap<String, Option<CachedResult>>>,
 }
 
+struct CustomTable {
+    name: String,
+    /// Lowercased model id → (original key, pricing)
+    prices: HashMap<String, (String, ModelPricing)>,
+}
+
+impl CustomTable {
+    fn exact_match(&self, model_id: &str) -> Option<LookupResult> {
+        let (key, pricing) = self.prices.get(model_id)?;
+        Some(LookupResult {
+            pricing: pricing.clone(),
+            source: self.name.clone(),
+            matched_key: key.clone(),
+            matched_prefix: None,
+        })
+    }
+}
+
 pub struct LookupResult {
     pub pricing: ModelPricing,
     pub source: String,
     pub matched_key: String,
+    /// Provider prefix that resolved a bare model name (prefix matches only)
+    pub matched_prefix: Option<String>,
 }
 
 impl PricingLookup {
@@ -94,10 +157,10 @@ impl PricingLookup {
         openrouter: HashMap<String, ModelPricing>,
     ) -> Self {
         let mut litellm_keys: Vec<String> = litellm.keys().cloned().collect();
-        litellm_keys.sort_by(|a, b| b.len().cmp(&a.len()));
+        litellm_keys.sort_by_key(|k| std::cmp::Reverse(k.len()));
 
         let mut openrouter_keys: Vec<String> = openrouter.keys().cloned().collect();
-        openrouter_keys.sort_by(|a, b| b.len().cmp(&a.len()));
+        openrouter_keys.sort_by_key(|k| std::cmp::Reverse(k.len()));
 
         let mut litellm_lower = HashMap::with_capacity(litellm.len());
         for key in &litellm_keys {
@@ -109,13 +172,15 @@ impl PricingLookup {
         for key in &openrouter_keys {
             let lower = key.to_lowercase();
             openrouter_lower.insert(lower.clone(), key.clone());
-            if let Some(model_part) = lower.split('/').last() {
+            if let Some(model_part) = lower.split('/').next_back() {
                 if model_part != lower {
                     openrouter_model_part.insert(model_part.to_string(), key.clone());
                 }
             }
         }
 
+        let derived_aliases = derive_aliases(&litellm_keys);
+
         Self {
             litellm,
             openrouter,
@@ -124,10 +189,81 @@ impl PricingLookup {
             litellm_lower,
             openrouter_lower,
             openrouter_model_part,
+            normalize_rules: NormalizeRules::load(),
+            provider_prefixes: resolve_provider_prefixes(
+                crate::config::settings().provider_prefixes.as_deref(),
+            ),
+            derived_aliases,
+            custom: Vec::new(),
+            merge_policy: crate::config::settings().pricing_merge.clone(),
+            snapshot_times: (0, 0),
+            openrouter_history_root: None,
+            openrouter_history: OnceLock::new(),
             lookup_cache: RwLock::new(HashMap::with_capacity(64)),
         }
     }
 
+    /// Replace the policy for models both sources price differently
+    #[cfg(test)]
+    pub fn with_merge_policy(mut self, policy: MergePolicy) -> Self {
+        self.merge_policy = policy;
+        self
+    }
+
+    /// When the LiteLLM and OpenRouter tables were fetched, for `prefer: "newer"`
+    pub fn with_snapshot_times(mut self, litellm: u64, openrouter: u64) -> Self {
+        self.snapshot_times = (litellm, openrouter);
+        self
+    }
+
+    /// Price dated messages matched in OpenRouter at the rates archived
+    /// under `root` around their time (see [`history`])
+    pub fn with_openrouter_history(mut self, root: PathBuf) -> Self {
+        self.openrouter_history_root = Some(root);
+        self
+    }
+
+    /// `result`'s pricing at `timestamp_ms`, from the OpenRouter history for
+    /// OpenRouter matches
+    fn pricing_at(&self, result: &LookupResult, timestamp_ms: i64) -> Option<ModelPricing> {
+        let root = self.openrouter_history_root.as_deref().filter(|_| result.source == "OpenRouter")?;
+        self.openrouter_history
+            .get_or_init(|| history::series_in(root, "openrouter", &self.openrouter, self.snapshot_times.1))
+            .at(&result.matched_key, timestamp_ms)
+    }
+
+    /// When the older of the fetched LiteLLM and OpenRouter tables was
+    /// fetched, in seconds since the epoch
+    pub fn fetched_at(&self) -> Option<u64> {
+        let (litellm, openrouter) = self.snapshot_times;
+        [litellm, openrouter].into_iter().filter(|&t| t > 0).min()
+    }
+
+    /// Add a provider's table. Its exact matches win over LiteLLM and
+    /// OpenRouter; earlier tables win over later ones.
+    pub fn with_custom_source(mut self, name: &str, data: HashMap<String, ModelPricing>) -> Self {
+        let prices = data
+            .into_iter()
+            .map(|(key, pricing)| (key.to_lowercase(), (key, pricing)))
+            .collect();
+        self.custom.push(CustomTable { name: name.to_string(), prices });
+        self
+    }
+
+    /// Replace the provider prefix priority used for bare model names
+    #[cfg(test)]
+    pub fn with_provider_prefixes(mut self, prefixes: &[String]) -> Self {
+        self.provider_prefixes = resolve_provider_prefixes(Some(prefixes));
+        self
+    }
+
+    /// Replace the normalization rules (defaults to user settings + bundled)
+    #[cfg(test)]
+    pub fn with_normalize_rules(mut self, rules: NormalizeRules) -> Self {
+        self.normalize_rules = rules;
+        self
+    }
+
     pub fn lookup(&self, model_id: &str) -> Option<LookupResult> {
         if let Some(cached) = self
             .lookup_cache
@@ -139,6 +275,7 @@ impl PricingLookup {
                 pricing: c.pricing,
                 source: c.source,
                 matched_key: c.matched_key,
+                matched_prefix: c.matched_prefix,
             });
         }
 
@@ -151,6 +288,55 @@ impl PricingLookup {
                     pricing: r.pricing.clone(),
                     source: r.source.clone(),
                     matched_key: r.matched_key.clone(),
+                    matched_prefix: r.matched_prefix.clone(),
+                }),
+            );
+        }
+
+        result
+    }
+
+    /// Look up pricing, preferring the LiteLLM key scoped to `provider`'s
+    /// backend (`vertex_ai/gemini-2.5-pro` for provider `vertex_ai`) and
+    /// falling back to the regular lookup when no scoped key exists.
+    pub fn lookup_with_provider(
+        &self,
+        model_id: &str,
+        provider: Option<&str>,
+    ) -> Option<LookupResult> {
+        let Some(scope) = provider.and_then(provider_scope) else {
+            return self.lookup(model_id);
+        };
+
+        let cache_key = format!("{}\0{}", scope, model_id);
+        if let Some(cached) = self
+            .lookup_cache
+            .read()
+            .ok()
+            .and_then(|c| c.get(&cache_key).cloned())
+        {
+            return cached.map(|c| LookupResult {
+                pricing: c.pricing,
+                source: c.source,
+                matched_key: c.matched_key,
+                matched_prefix: c.matched_prefix,
+            });
+        }
+
+        let result = self
+            .lookup(model_id)
+            .filter(|r| self.custom.iter().any(|t| t.name == r.source))
+            .or_else(|| self.scoped_match_litellm(model_id, scope))
+            .or_else(|| self.lookup(model_id));
+
+        if let Ok(mut cache) = self.lookup_cache.write() {
+            cache.insert(
+                cache_key,
+                result.as_ref().map(|r| CachedResult {
+                    pricing: r.pricing.clone(),
+                    source: r.source.clone(),
+                    matched_key: r.matched_key.clone(),
+                    matched_prefix: r.matched_prefix.clone(),
                 }),
             );
         }
@@ -158,6 +344,27 @@ impl PricingLookup {
         result
     }
 
+    fn scoped_match_litellm(&self, model_id: &str, scope: &str) -> Option<LookupResult> {
+        let prefix_stripped = strip_routing_prefix(model_id);
+        let canonical = aliases::resolve_alias(prefix_stripped).unwrap_or(prefix_stripped);
+        let lower = canonical.to_lowercase();
+        let bare = lower.strip_prefix(scope).unwrap_or(&lower);
+
+        let mut candidates = vec![bare.to_string()];
+        candidates.extend(normalize_version_separator(bare));
+        candidates.extend(strip_tier_suffix(bare).map(String::from));
+
+        candidates.iter().find_map(|id| {
+            let key = self.litellm_lower.get(&format!("{}{}", scope, id))?;
+            Some(LookupResult {
+                pricing: self.litellm.get(key).unwrap().clone(),
+                source: "LiteLLM".into(),
+                matched_key: key.clone(),
+                matched_prefix: Some(scope.to_string()),
+            })
+        })
+    }
+
     pub fn lookup_with_source(
         &self,
         model_id: &str,
@@ -171,7 +378,17 @@ impl PricingLookup {
         let do_lookup = |id: &str| match force_source {
             Some("litellm") => self.lookup_litellm_only(id),
             Some("openrouter") => self.lookup_openrouter_only(id),
-            _ => self.lookup_auto(id),
+            Some(name) if name.eq_ignore_ascii_case(bundled::SOURCE) => None,
+            Some(name) if self.custom.iter().any(|t| t.name == name) => self
+                .custom
+                .iter()
+                .filter(|t| t.name == name)
+                .find_map(|t| t.exact_match(id)),
+            _ => self
+                .custom
+                .iter()
+                .find_map(|t| t.exact_match(id))
+                .or_else(|| self.lookup_auto(id)),
         };
 
         // Try direct lookup
@@ -205,32 +422,35 @@ impl PricingLookup {
             }
         }
 
+        // Last resort: list prices for providers the fetched tables lag on
+        if force_source.is_none_or(|source| source.eq_ignore_ascii_case(bundled::SOURCE)) {
+            let bundled = bundled::lookup(&lower).or_else(|| strip_tier_suffix(&lower).and_then(bundled::lookup));
+            if let Some((key, pricing)) = bundled {
+                return Some(LookupResult {
+                    pricing,
+                    source: bundled::SOURCE.into(),
+                    matched_key: key.to_string(),
+                    matched_prefix: None,
+                });
+            }
+        }
+
         None
     }
 
     fn lookup_auto(&self, model_id: &str) -> Option<LookupResult> {
-        if let Some(result) = self.exact_match_litellm(model_id) {
-            return Some(result);
-        }
-
-        if let Some(result) = self.exact_match_openrouter(model_id) {
+        if let Some(result) = self.exact_match_merged(model_id) {
             return Some(result);
         }
 
         if let Some(version_normalized) = normalize_version_separator(model_id) {
-            if let Some(result) = self.exact_match_litellm(&version_normalized) {
-                return Some(result);
-            }
-            if let Some(result) = self.exact_match_openrouter(&version_normalized) {
+            if let Some(result) = self.exact_match_merged(&version_normalized) {
                 return Some(result);
             }
         }
 
-        if let Some(normalized) = normalize_model_name(model_id) {
-            if let Some(result) = self.exact_match_litellm(&normalized) {
-                return Some(result);
-            }
-            if let Some(result) = self.exact_match_openrouter(&normalized) {
+        if let Some(normalized) = self.normalize_rules.apply(model_id) {
+            if let Some(result) = self.exact_match_merged(&normalized) {
                 return Some(result);
             }
         }
@@ -251,6 +471,10 @@ impl PricingLookup {
             }
         }
 
+        if let Some(result) = self.derived_alias_match(model_id) {
+            return Some(result);
+        }
+
         if !is_fuzzy_eligible(model_id) {
             return None;
         }
@@ -294,7 +518,7 @@ impl PricingLookup {
                 return Some(result);
             }
         }
-        if let Some(normalized) = normalize_model_name(model_id) {
+        if let Some(normalized) = self.normalize_rules.apply(model_id) {
             if let Some(result) = self.exact_match_litellm(&normalized) {
                 return Some(result);
             }
@@ -307,6 +531,9 @@ impl PricingLookup {
                 return Some(result);
             }
         }
+        if let Some(result) = self.derived_alias_match(model_id) {
+            return Some(result);
+        }
         if is_fuzzy_eligible(model_id) {
             if let Some(result) = self.fuzzy_match_litellm(model_id) {
                 return Some(result);
@@ -324,7 +551,7 @@ impl PricingLookup {
                 return Some(result);
             }
         }
-        if let Some(normalized) = normalize_model_name(model_id) {
+        if let Some(normalized) = self.normalize_rules.apply(model_id) {
             if let Some(result) = self.exact_match_openrouter(&normalized) {
                 return Some(result);
             }
@@ -345,12 +572,53 @@ impl PricingLookup {
         None
     }
 
+    /// Exact match in either source; when both match with different prices,
+    /// the merge policy picks one and the conflict is recorded
+    fn exact_match_merged(&self, model_id: &str) -> Option<LookupResult> {
+        let (litellm, openrouter) = match (self.exact_match_litellm(model_id), self.exact_match_openrouter(model_id)) {
+            (Some(l), Some(o)) => (l, o),
+            (l, o) => return l.or(o),
+        };
+        if !merge::conflicts(&litellm.pricing, &openrouter.pricing) {
+            return Some(litellm);
+        }
+
+        let side = self.merge_policy.choose(
+            Candidate { pricing: &litellm.pricing, fetched_at: self.snapshot_times.0 },
+            Candidate { pricing: &openrouter.pricing, fetched_at: self.snapshot_times.1 },
+        );
+        let chosen = match side {
+            Side::Litellm => "LiteLLM",
+            Side::Openrouter => "OpenRouter",
+        };
+        if self.merge_policy.flag_conflicts {
+            eprintln!(
+                "[tokscale] Price conflict for {}: LiteLLM {} vs OpenRouter {}, using {}",
+                model_id, litellm.matched_key, openrouter.matched_key, chosen
+            );
+        }
+        diagnostics::record_price_conflict(PriceConflict {
+            model_id: model_id.to_string(),
+            litellm_key: litellm.matched_key.clone(),
+            openrouter_key: openrouter.matched_key.clone(),
+            litellm: (litellm.pricing.input_cost_per_token, litellm.pricing.output_cost_per_token),
+            openrouter: (openrouter.pricing.input_cost_per_token, openrouter.pricing.output_cost_per_token),
+            chosen,
+        });
+
+        Some(match side {
+            Side::Litellm => litellm,
+            Side::Openrouter => openrouter,
+        })
+    }
+
     fn exact_match_litellm(&self, model_id: &str) -> Option<LookupResult> {
         if let Some(key) = self.litellm_lower.get(model_id) {
             return Some(LookupResult {
                 pricing: self.litellm.get(key).unwrap().clone(),
                 source: "LiteLLM".into(),
                 matched_key: key.clone(),
+                matched_prefix: None,
             });
         }
         None
@@ -362,6 +630,7 @@ impl PricingLookup {
                 pricing: self.openrouter.get(key).unwrap().clone(),
                 source: "OpenRouter".into(),
                 matched_key: key.clone(),
+                matched_prefix: None,
             });
         }
         if let Some(key) = self.openrouter_model_part.get(model_id) {
@@ -369,19 +638,21 @@ impl PricingLookup {
                 pricing: self.openrouter.get(key).unwrap().clone(),
                 source: "OpenRouter".into(),
                 matched_key: key.clone(),
+                matched_prefix: None,
             });
         }
         None
     }
 
     fn prefix_match_litellm(&self, model_id: &str) -> Option<LookupResult> {
-        for prefix in PROVIDER_PREFIXES {
+        for prefix in &self.provider_prefixes {
             let key = format!("{}{}", prefix, model_id);
             if let Some(litellm_key) = self.litellm_lower.get(&key) {
                 return Some(LookupResult {
                     pricing: self.litellm.get(litellm_key).unwrap().clone(),
                     source: "LiteLLM".into(),
                     matched_key: litellm_key.clone(),
+                    matched_prefix: Some(prefix.clone()),
                 });
             }
         }
@@ -389,26 +660,39 @@ impl PricingLookup {
     }
 
     fn prefix_match_openrouter(&self, model_id: &str) -> Option<LookupResult> {
-        for prefix in PROVIDER_PREFIXES {
+        for prefix in &self.provider_prefixes {
             let key = format!("{}{}", prefix, model_id);
             if let Some(or_key) = self.openrouter_lower.get(&key) {
                 return Some(LookupResult {
                     pricing: self.openrouter.get(or_key).unwrap().clone(),
                     source: "OpenRouter".into(),
                     matched_key: or_key.clone(),
+                    matched_prefix: Some(prefix.clone()),
                 });
             }
         }
         None
     }
 
+    fn derived_alias_match(&self, model_id: &str) -> Option<LookupResult> {
+        let key = self.derived_aliases.get(model_id).or_else(|| {
+            normalize_version_separator(model_id).and_then(|v| self.derived_aliases.get(&v))
+        })?;
+        Some(LookupResult {
+            pricing: self.litellm.get(key).unwrap().clone(),
+            source: "LiteLLM".into(),
+            matched_key: key.clone(),
+            matched_prefix: None,
+        })
+    }
+
     fn fuzzy_match_litellm(&self, model_id: &str) -> Option<LookupResult> {
         let family = extract_model_family(model_id);
         let mut family_matches_list: Vec<&String> = Vec::new();
 
         for key in &self.litellm_keys {
             let lower_key = key.to_lowercase();
-            if family_matches(&lower_key, &family) && contains_model_id(&lower_key, model_id) {
+            if family_matches(&lower_key, &family) && is_word_boundary_match(&lower_key, model_id) {
                 family_matches_list.push(key);
             }
         }
@@ -420,7 +704,7 @@ impl PricingLookup {
         let mut all_matches: Vec<&String> = Vec::new();
         for key in &self.litellm_keys {
             let lower_key = key.to_lowercase();
-            if contains_model_id(&lower_key, model_id) {
+            if is_word_boundary_match(&lower_key, model_id) {
                 all_matches.push(key);
             }
         }
@@ -434,8 +718,8 @@ impl PricingLookup {
 
         for key in &self.openrouter_keys {
             let lower_key = key.to_lowercase();
-            let model_part = lower_key.split('/').last().unwrap_or(&lower_key);
-            if family_matches(model_part, &family) && contains_model_id(model_part, model_id) {
+            let model_part = lower_key.split('/').next_back().unwrap_or(&lower_key);
+            if family_matches(model_part, &family) && is_word_boundary_match(model_part, model_id) {
                 family_matches_list.push(key);
             }
         }
@@ -449,8 +733,8 @@ impl PricingLookup {
         let mut all_matches: Vec<&String> = Vec::new();
         for key in &self.openrouter_keys {
             let lower_key = key.to_lowercase();
-            let model_part = lower_key.split('/').last().unwrap_or(&lower_key);
-            if contains_model_id(model_part, model_id) {
+            let model_part = lower_key.split('/').next_back().unwrap_or(&lower_key);
+            if is_word_boundary_match(model_part, model_id) {
                 all_matches.push(key);
             }
         }
@@ -458,30 +742,60 @@ impl PricingLookup {
         select_best_match(&all_matches, &self.openrouter, "OpenRouter")
     }
 
-    pub fn calculate_cost(
+    #[allow(clippy::too_many_arguments)]
+    pub fn calculate_cost_with_provider(
         &self,
         model_id: &str,
+        provider: Option<&str>,
         input: i64,
         output: i64,
         cache_read: i64,
         cache_write: i64,
         reasoning: i64,
     ) -> f64 {
-        let result = match self.lookup(model_id) {
+        let tokens = TokenBreakdown { input, output, cache_read, cache_write, reasoning, training: 0 };
+        self.calculate_token_cost(model_id, provider, &tokens)
+    }
+
+    /// Cost of `tokens`, including fine-tuning training tokens
+    pub fn calculate_token_cost(&self, model_id: &str, provider: Option<&str>, tokens: &TokenBreakdown) -> f64 {
+        self.calculate_token_cost_at(model_id, provider, tokens, None)
+    }
+
+    /// Cost of `tokens`; with the message's `timestamp_ms`, scaled by the
+    /// price window it falls in (DeepSeek off-peak hours)
+    pub fn calculate_token_cost_at(
+        &self,
+        model_id: &str,
+        provider: Option<&str>,
+        tokens: &TokenBreakdown,
+        timestamp_ms: Option<i64>,
+    ) -> f64 {
+        let result = match self.lookup_with_provider(model_id, provider) {
             Some(r) => r,
             None => return 0.0,
         };
 
-        let p = &result.pricing;
+        let dated = timestamp_ms.and_then(|timestamp_ms| self.pricing_at(&result, timestamp_ms));
+        let p = dated.as_ref().unwrap_or(&result.pricing);
         let safe_price =
             |opt: Option<f64>| opt.filter(|v| v.is_finite() && *v >= 0.0).unwrap_or(0.0);
 
-        let input_cost = input as f64 * safe_price(p.input_cost_per_token);
-        let output_cost = (output + reasoning) as f64 * safe_price(p.output_cost_per_token);
-        let cache_read_cost = cache_read as f64 * safe_price(p.cache_read_input_token_cost);
-        let cache_write_cost = cache_write as f64 * safe_price(p.cache_creation_input_token_cost);
+        let input_cost = tokens.input as f64 * safe_price(p.input_cost_per_token);
+        let output_cost = (tokens.output + tokens.reasoning) as f64 * safe_price(p.output_cost_per_token);
+        let cache_read_cost = tokens.cache_read as f64 * safe_price(p.cache_read_input_token_cost);
+        let cache_write_cost = tokens.cache_write as f64 * safe_price(p.cache_creation_input_token_cost);
+        let training_cost = tokens.training as f64 * safe_price(p.training_cost_per_token);
+
+        let multiplier = timestamp_ms.map_or(1.0, |timestamp_ms| {
+            if p.time_windows.is_empty() {
+                windows::multiplier_at(&windows::builtin(&result.matched_key, &result.source, provider), timestamp_ms)
+            } else {
+                windows::multiplier_at(&p.time_windows, timestamp_ms)
+            }
+        });
 
-        input_cost + output_cost + cache_read_cost + cache_write_cost
+        (input_cost + output_cost + cache_read_cost + cache_write_cost + training_cost) * multiplier
     }
 }
 
@@ -547,7 +861,7 @@ fn extract_model_family(model_id: &str) -> String {
     }
 
     lower
-        .split(|c: char| c == '-' || c == '_' || c == '.')
+        .split(['-', '_', '.'])
         .next()
         .unwrap_or(&lower)
         .to_string()
@@ -560,48 +874,31 @@ fn family_matches(key: &str, family: &str) -> bool {
     key.contains(family)
 }
 
-fn contains_model_id(key: &str, model_id: &str) -> bool {
-    if let Some(pos) = key.find(model_id) {
-        let before_ok = pos == 0 || !key[..pos].chars().last().unwrap().is_alphanumeric();
-        let after_pos = pos + model_id.len();
-        let after_ok =
-            after_pos == key.len() || !key[after_pos..].chars().next().unwrap().is_alphanumeric();
-        before_ok && after_ok
-    } else {
-        false
+/// Returns true if `needle` occurs in `haystack` as a whole word, i.e. the match
+/// is not directly preceded or followed by an alphanumeric character.
+///
+/// Every occurrence is checked (not just the first), and boundaries are read as
+/// chars rather than bytes, so multi-byte neighbours such as `é` or `日` are
+/// classified correctly and never split.
+pub fn is_word_boundary_match(haystack: &str, needle: &str) -> bool {
+    if needle.is_empty() {
+        return false;
     }
-}
 
-fn normalize_model_name(model_id: &str) -> Option<String> {
-    let lower = model_id.to_lowercase();
-
-    if lower.contains("opus") {
-        if lower.contains("4.5") || lower.contains("4-5") {
-            return Some("claude-opus-4-5".into());
-        } else if lower.contains("4") {
-            return Some("claude-opus-4".into());
-        }
-    }
-    if lower.contains("sonnet") {
-        if lower.contains("4.5") || lower.contains("4-5") {
-            return Some("claude-sonnet-4-5".into());
-        } else if lower.contains("4") && !lower.contains("3.") && !lower.contains("3-") {
-            return Some("claude-sonnet-4".into());
-        } else if lower.contains("3.7") || lower.contains("3-7") {
-            return Some("claude-3-7-sonnet".into());
-        } else if lower.contains("3.5") || lower.contains("3-5") {
-            return Some("claude-3.5-sonnet".into());
+    haystack.char_indices().any(|(pos, _)| {
+        if !haystack[pos..].starts_with(needle) {
+            return false;
         }
-    }
-    if lower.contains("haiku") {
-        if lower.contains("4.5") || lower.contains("4-5") {
-            return Some("claude-haiku-4-5".into());
-        } else if lower.contains("3.5") || lower.contains("3-5") {
-            return Some("claude-3.5-haiku".into());
-        }
-    }
-
-    None
+        let before_ok = haystack[..pos]
+            .chars()
+            .next_back()
+            .is_none_or(|c| !c.is_alphanumeric());
+        let after_ok = haystack[pos + needle.len()..]
+            .chars()
+            .next()
+            .is_none_or(|c| !c.is_alphanumeric());
+        before_ok && after_ok
+    })
 }
 
 fn normalize_version_separator(model_id: &str) -> Option<String> {
@@ -642,13 +939,13 @@ fn is_fuzzy_eligible(model_id: &str) -> bool {
     if model_id.len() < MIN_FUZZY_MATCH_LEN {
         return false;
     }
-    !FUZZY_BLOCKLIST.iter().any(|blocked| model_id == *blocked)
+    !FUZZY_BLOCKLIST.contains(&model_id)
 }
 
 fn strip_tier_suffix(model_id: &str) -> Option<&str> {
     for suffix in TIER_SUFFIXES {
-        if model_id.ends_with(suffix) {
-            return Some(&model_id[..model_id.len() - suffix.len()]);
+        if let Some(stripped) = model_id.strip_suffix(suffix) {
+            return Some(stripped);
         }
     }
     None
@@ -661,11 +958,11 @@ fn strip_fallback_suffix(model_id: &str) -> Option<&str> {
     // FALLBACK_SUFFIXES should be ordered with longer suffixes first,
     // but we sort by length descending to be safe
     let mut suffixes: Vec<&str> = FALLBACK_SUFFIXES.to_vec();
-    suffixes.sort_by(|a, b| b.len().cmp(&a.len()));
+    suffixes.sort_by_key(|s| std::cmp::Reverse(s.len()));
 
     for suffix in suffixes {
-        if model_id.ends_with(suffix) {
-            return Some(&model_id[..model_id.len() - suffix.len()]);
+        if let Some(stripped) = model_id.strip_suffix(suffix) {
+            return Some(stripped);
         }
     }
     None
@@ -695,8 +992,109 @@ fn is_reseller_provider(key: &str) -> bool {
         .any(|prefix| lower.starts_with(prefix))
 }
 
-fn select_best_match<'a>(
-    matches: &[&'a String],
+fn provider_scope(provider: &str) -> Option<&'static str> {
+    let lower = provider.trim().to_lowercase();
+    PROVIDER_SCOPES
+        .iter()
+        .find(|(id, _)| *id == lower)
+        .map(|(_, scope)| *scope)
+}
+
+/// Configured prefixes (lowercased, `/`-terminated, deduplicated) or the defaults
+fn resolve_provider_prefixes(configured: Option<&[String]>) -> Vec<String> {
+    let Some(configured) = configured else {
+        return PROVIDER_PREFIXES.iter().map(|p| p.to_string()).collect();
+    };
+
+    let mut prefixes: Vec<String> = Vec::with_capacity(configured.len());
+    for prefix in configured {
+        let trimmed = prefix.trim().trim_end_matches('/').to_lowercase();
+        if trimmed.is_empty() {
+            continue;
+        }
+        let prefix = format!("{}/", trimmed);
+        if !prefixes.contains(&prefix) {
+            prefixes.push(prefix);
+        }
+    }
+    prefixes
+}
+
+/// Build a synthetic alias index from dataset keys.
+///
+/// Each key contributes the spellings users actually send: without its
+/// provider prefix (`vertex_ai/gemini-2.5-pro`), without a release date
+/// (`claude-3-5-sonnet-20241022`, `claude-3-5-sonnet@20240620`) and without
+/// `-latest`. Aliases that are already real keys are skipped. When several keys
+/// derive the same alias, unprefixed keys win over original providers, which
+/// win over non-resellers; ties go to the greatest key, i.e. the newest date.
+fn derive_aliases(keys: &[String]) -> HashMap<String, String> {
+    let lower_keys: std::collections::HashSet<String> =
+        keys.iter().map(|k| k.to_lowercase()).collect();
+    let rank = |key: &str| (!key.contains('/'), is_original_provider(key), !is_reseller_provider(key));
+
+    let mut aliases: HashMap<String, String> = HashMap::new();
+    for key in keys {
+        for alias in alias_candidates(&key.to_lowercase()) {
+            if lower_keys.contains(&alias) {
+                continue;
+            }
+            let replace = match aliases.get(&alias) {
+                Some(current) => (rank(key), key) > (rank(current), current),
+                None => true,
+            };
+            if replace {
+                aliases.insert(alias, key.clone());
+            }
+        }
+    }
+    aliases
+}
+
+fn alias_candidates(key: &str) -> Vec<String> {
+    let mut bases = vec![key];
+    if let Some(model_part) = key.rsplit('/').next().filter(|m| *m != key && !m.is_empty()) {
+        bases.push(model_part);
+    }
+
+    let mut candidates = Vec::new();
+    for base in bases {
+        let undated = strip_date_suffix(base);
+        let unlatest = base.strip_suffix("-latest");
+        for candidate in [Some(base), undated, unlatest].into_iter().flatten() {
+            if candidate != key && !candidate.is_empty() && !candidates.iter().any(|c| c == candidate) {
+                candidates.push(candidate.to_string());
+            }
+        }
+    }
+    candidates
+}
+
+/// Strip a trailing release date: `-20241022`, `-2024-10-22` or `@20240620`
+fn strip_date_suffix(model_id: &str) -> Option<&str> {
+    let is_digits = |s: &str, n: usize| s.len() == n && s.bytes().all(|b| b.is_ascii_digit());
+
+    if let Some((base, date)) = model_id.rsplit_once(['-', '@']) {
+        if is_digits(date, 8) && !base.is_empty() {
+            return Some(base);
+        }
+    }
+    if model_id.len() > 11 && model_id.is_char_boundary(model_id.len() - 11) {
+        let (base, date) = model_id.split_at(model_id.len() - 11);
+        let parts: Vec<&str> = date.strip_prefix('-')?.split('-').collect();
+        if parts.len() == 3
+            && is_digits(parts[0], 4)
+            && is_digits(parts[1], 2)
+            && is_digits(parts[2], 2)
+        {
+            return Some(base);
+        }
+    }
+    None
+}
+
+fn select_best_match(
+    matches: &[&String],
     dataset: &HashMap<String, ModelPricing>,
     source: &str,
 ) -> Option<LookupResult> {
@@ -709,6 +1107,7 @@ fn select_best_match<'a>(
             pricing: dataset.get(*key).unwrap().clone(),
             source: source.into(),
             matched_key: (*key).clone(),
+            matched_prefix: None,
         });
     }
 
@@ -717,6 +1116,7 @@ fn select_best_match<'a>(
             pricing: dataset.get(*key).unwrap().clone(),
             source: source.into(),
             matched_key: (*key).clone(),
+            matched_prefix: None,
         });
     }
 
@@ -725,6 +1125,7 @@ fn select_best_match<'a>(
         pricing: dataset.get(key).unwrap().clone(),
         source: source.into(),
         matched_key: key.clone(),
+        matched_prefix: None,
     })
 }
 
@@ -744,6 +1145,7 @@ mod tests {
                 output_cost_per_token: Some(0.00001),
                 cache_read_input_token_cost: Some(0.00000125),
                 cache_creation_input_token_cost: None,
+                ..Default::default()
             },
         );
         m.insert(
@@ -753,6 +1155,7 @@ mod tests {
                 output_cost_per_token: Some(0.00003),
                 cache_read_input_token_cost: None,
                 cache_creation_input_token_cost: None,
+                ..Default::default()
             },
         );
 
@@ -764,6 +1167,7 @@ mod tests {
                 output_cost_per_token: Some(0.000014),
                 cache_read_input_token_cost: Some(1.75e-7),
                 cache_creation_input_token_cost: None,
+                ..Default::default()
             },
         );
         m.insert(
@@ -773,6 +1177,7 @@ mod tests {
                 output_cost_per_token: Some(0.00001),
                 cache_read_input_token_cost: Some(1.25e-7),
                 cache_creation_input_token_cost: None,
+                ..Default::default()
             },
         );
         m.insert(
@@ -782,6 +1187,7 @@ mod tests {
                 output_cost_per_token: Some(0.00001),
                 cache_read_input_token_cost: Some(1.25e-7),
                 cache_creation_input_token_cost: None,
+                ..Default::default()
             },
         );
         m.insert(
@@ -791,6 +1197,7 @@ mod tests {
                 output_cost_per_token: Some(0.00001),
                 cache_read_input_token_cost: Some(1.25e-7),
                 cache_creation_input_token_cost: None,
+                ..Default::default()
             },
         );
         m.insert(
@@ -800,6 +1207,7 @@ mod tests {
                 output_cost_per_token: Some(0.00001),
                 cache_read_input_token_cost: Some(1.25e-7),
                 cache_creation_input_token_cost: None,
+                ..Default::default()
             },
         );
         m.insert(
@@ -809,6 +1217,7 @@ mod tests {
                 output_cost_per_token: Some(0.00001),
                 cache_read_input_token_cost: Some(1.25e-7),
                 cache_creation_input_token_cost: None,
+                ..Default::default()
             },
         );
         m.insert(
@@ -818,6 +1227,7 @@ mod tests {
                 output_cost_per_token: Some(4e-7),
                 cache_read_input_token_cost: Some(5e-9),
                 cache_creation_input_token_cost: None,
+                ..Default::default()
             },
         );
 
@@ -829,6 +1239,7 @@ mod tests {
                 output_cost_per_token: Some(0.000015),
                 cache_read_input_token_cost: Some(0.0000003),
                 cache_creation_input_token_cost: Some(0.00000375),
+                ..Default::default()
             },
         );
         m.insert(
@@ -838,6 +1249,7 @@ mod tests {
                 output_cost_per_token: Some(0.000015),
                 cache_read_input_token_cost: Some(3e-7),
                 cache_creation_input_token_cost: Some(0.00000375),
+                ..Default::default()
             },
         );
         m.insert(
@@ -847,6 +1259,7 @@ mod tests {
                 output_cost_per_token: Some(0.000005),
                 cache_read_input_token_cost: Some(1e-7),
                 cache_creation_input_token_cost: Some(0.00000125),
+                ..Default::default()
             },
         );
         m.insert(
@@ -856,6 +1269,7 @@ mod tests {
                 output_cost_per_token: Some(0.000004),
                 cache_read_input_token_cost: Some(8e-8),
                 cache_creation_input_token_cost: Some(0.000001),
+                ..Default::default()
             },
         );
         m.insert(
@@ -865,6 +1279,7 @@ mod tests {
                 output_cost_per_token: Some(0.000025),
                 cache_read_input_token_cost: Some(5e-7),
                 cache_creation_input_token_cost: Some(0.00000625),
+                ..Default::default()
             },
         );
         m.insert(
@@ -874,6 +1289,7 @@ mod tests {
                 output_cost_per_token: Some(0.000075),
                 cache_read_input_token_cost: Some(0.0000015),
                 cache_creation_input_token_cost: Some(0.00001875),
+                ..Default::default()
             },
         );
 
@@ -885,6 +1301,7 @@ mod tests {
                 output_cost_per_token: Some(0.000012),
                 cache_read_input_token_cost: Some(2e-7),
                 cache_creation_input_token_cost: None,
+                ..Default::default()
             },
         );
         m.insert(
@@ -894,6 +1311,7 @@ mod tests {
                 output_cost_per_token: Some(0.000003),
                 cache_read_input_token_cost: Some(5e-8),
                 cache_creation_input_token_cost: None,
+                ..Default::default()
             },
         );
 
@@ -905,6 +1323,7 @@ mod tests {
                 output_cost_per_token: Some(0.0000015),
                 cache_read_input_token_cost: Some(2e-8),
                 cache_creation_input_token_cost: None,
+                ..Default::default()
             },
         );
 
@@ -915,6 +1334,7 @@ mod tests {
                 output_cost_per_token: Some(0.0000175),
                 cache_read_input_token_cost: None,
                 cache_creation_input_token_cost: None,
+                ..Default::default()
             },
         );
         m.insert(
@@ -924,6 +1344,7 @@ mod tests {
                 output_cost_per_token: Some(0.000015),
                 cache_read_input_token_cost: Some(3e-7),
                 cache_creation_input_token_cost: Some(0.00000375),
+                ..Default::default()
             },
         );
         m.insert(
@@ -933,6 +1354,7 @@ mod tests {
                 output_cost_per_token: Some(0.000005),
                 cache_read_input_token_cost: None,
                 cache_creation_input_token_cost: None,
+                ..Default::default()
             },
         );
         m.insert(
@@ -942,6 +1364,7 @@ mod tests {
                 output_cost_per_token: Some(0.000005),
                 cache_read_input_token_cost: None,
                 cache_creation_input_token_cost: None,
+                ..Default::default()
             },
         );
 
@@ -960,6 +1383,7 @@ mod tests {
                 output_cost_per_token: Some(0.00001),
                 cache_read_input_token_cost: Some(0.00000125),
                 cache_creation_input_token_cost: None,
+                ..Default::default()
             },
         );
 
@@ -971,6 +1395,7 @@ mod tests {
                 output_cost_per_token: Some(0.000015),
                 cache_read_input_token_cost: Some(3e-7),
                 cache_creation_input_token_cost: Some(0.00000375),
+                ..Default::default()
             },
         );
         m.insert(
@@ -980,6 +1405,7 @@ mod tests {
                 output_cost_per_token: Some(0.000025),
                 cache_read_input_token_cost: Some(0.0000005),
                 cache_creation_input_token_cost: Some(0.00000625),
+                ..Default::default()
             },
         );
         m.insert(
@@ -989,6 +1415,7 @@ mod tests {
                 output_cost_per_token: Some(0.000004),
                 cache_read_input_token_cost: Some(8e-8),
                 cache_creation_input_token_cost: Some(0.000001),
+                ..Default::default()
             },
         );
 
@@ -1000,6 +1427,7 @@ mod tests {
                 output_cost_per_token: Some(0.0000015),
                 cache_read_input_token_cost: None,
                 cache_creation_input_token_cost: None,
+                ..Default::default()
             },
         );
         m.insert(
@@ -1009,6 +1437,7 @@ mod tests {
                 output_cost_per_token: Some(0.0000019),
                 cache_read_input_token_cost: None,
                 cache_creation_input_token_cost: None,
+                ..Default::default()
             },
         );
 
@@ -1020,6 +1449,7 @@ mod tests {
                 output_cost_per_token: Some(0.00000184),
                 cache_read_input_token_cost: None,
                 cache_creation_input_token_cost: None,
+                ..Default::default()
             },
         );
         m.insert(
@@ -1029,6 +1459,7 @@ mod tests {
                 output_cost_per_token: Some(0.00000175),
                 cache_read_input_token_cost: None,
                 cache_creation_input_token_cost: None,
+                ..Default::default()
             },
         );
 
@@ -1040,6 +1471,7 @@ mod tests {
                 output_cost_per_token: Some(9.5e-7),
                 cache_read_input_token_cost: None,
                 cache_creation_input_token_cost: None,
+                ..Default::default()
             },
         );
 
@@ -1048,6 +1480,23 @@ mod tests {
 
     fn create_lookup() -> PricingLookup {
         PricingLookup::new(mock_litellm(), mock_openrouter())
+            .with_normalize_rules(NormalizeRules::bundled())
+    }
+
+    /// LiteLLM-only lookup over `(key, input, output)` per-token prices
+    fn litellm_lookup(prices: &[(&str, f64, f64)]) -> PricingLookup {
+        let litellm = prices
+            .iter()
+            .map(|&(key, input, output)| {
+                let pricing = ModelPricing {
+                    input_cost_per_token: Some(input),
+                    output_cost_per_token: Some(output),
+                    ..Default::default()
+                };
+                (key.to_string(), pricing)
+            })
+            .collect();
+        PricingLookup::new(litellm, HashMap::new())
     }
 
     // =========================================================================
@@ -1355,6 +1804,27 @@ mod tests {
         assert_eq!(result.source, "LiteLLM");
     }
 
+    #[test]
+    fn test_normalize_user_rule_extends_bundled() {
+        use super::super::normalize::{NormalizeRule, NormalizeRuleSet};
+
+        let user = NormalizeRuleSet {
+            version: 1,
+            rules: vec![NormalizeRule {
+                all: vec!["pickle".into()],
+                canonical: "glm-4.6".into(),
+                ..Default::default()
+            }],
+        };
+        let bundled = serde_json::from_str(include_str!("normalize_rules.json")).unwrap();
+        let rules = NormalizeRules::from_sets(&[user, bundled]);
+        let lookup =
+            PricingLookup::new(mock_litellm(), mock_openrouter()).with_normalize_rules(rules);
+
+        assert_eq!(lookup.lookup("small-pickle-v2").unwrap().matched_key, "z-ai/glm-4.6");
+        assert_eq!(lookup.lookup("opus-4-5").unwrap().matched_key, "claude-opus-4-5");
+    }
+
     #[test]
     fn test_blocklist_auto() {
         let lookup = create_lookup();
@@ -1465,6 +1935,7 @@ mod tests {
                 output_cost_per_token: Some(0.00001),
                 cache_read_input_token_cost: Some(1.25e-7),
                 cache_creation_input_token_cost: None,
+                ..Default::default()
             },
         );
         // Note: gpt-5-codex is NOT in the pricing data
@@ -1493,6 +1964,7 @@ mod tests {
                 output_cost_per_token: Some(0.00001),
                 cache_read_input_token_cost: Some(1.25e-7),
                 cache_creation_input_token_cost: None,
+                ..Default::default()
             },
         );
 
@@ -1520,6 +1992,7 @@ mod tests {
                 output_cost_per_token: Some(0.00001),
                 cache_read_input_token_cost: None,
                 cache_creation_input_token_cost: None,
+                ..Default::default()
             },
         );
         litellm.insert(
@@ -1529,6 +2002,7 @@ mod tests {
                 output_cost_per_token: Some(0.000015),
                 cache_read_input_token_cost: None,
                 cache_creation_input_token_cost: None,
+                ..Default::default()
             },
         );
 
@@ -1622,6 +2096,7 @@ mod tests {
                 output_cost_per_token: Some(0.0000175), // $17.50/1M tokens
                 cache_read_input_token_cost: None,
                 cache_creation_input_token_cost: None,
+                ..Default::default()
             },
         );
 
@@ -1633,6 +2108,7 @@ mod tests {
                 output_cost_per_token: Some(0.0000015), // $1.50/1M tokens
                 cache_read_input_token_cost: Some(0.00000002),
                 cache_creation_input_token_cost: None,
+                ..Default::default()
             },
         );
 
@@ -1706,7 +2182,7 @@ mod tests {
     fn test_calculate_cost_gpt_5_2() {
         let lookup = create_lookup();
         // 1M input, 500K output tokens
-        let cost = lookup.calculate_cost("gpt-5.2", 1_000_000, 500_000, 0, 0, 0);
+        let cost = lookup.calculate_cost_with_provider("gpt-5.2", None, 1_000_000, 500_000, 0, 0, 0);
         // input: 1M * 0.00000175 = 1.75, output: 500K * 0.000014 = 7.0
         assert!((cost - 8.75).abs() < 0.001);
     }
@@ -1715,7 +2191,7 @@ mod tests {
     fn test_calculate_cost_claude_sonnet_4_5() {
         let lookup = create_lookup();
         // 100K input, 50K output, 200K cache read
-        let cost = lookup.calculate_cost("claude-sonnet-4-5", 100_000, 50_000, 200_000, 0, 0);
+        let cost = lookup.calculate_cost_with_provider("claude-sonnet-4-5", None, 100_000, 50_000, 200_000, 0, 0);
         // input: 100K * 0.000003 = 0.30, output: 50K * 0.000015 = 0.75, cache: 200K * 3e-7 = 0.06
         assert!((cost - 1.11).abs() < 0.001);
     }
@@ -1723,10 +2199,29 @@ mod tests {
     #[test]
     fn test_calculate_cost_unknown_model() {
         let lookup = create_lookup();
-        let cost = lookup.calculate_cost("nonexistent-model", 1_000_000, 500_000, 0, 0, 0);
+        let cost = lookup.calculate_cost_with_provider("nonexistent-model", None, 1_000_000, 500_000, 0, 0, 0);
         assert_eq!(cost, 0.0);
     }
 
+    #[test]
+    fn test_calculate_token_cost_training() {
+        let mut litellm = HashMap::new();
+        litellm.insert(
+            "gpt-4o-mini-2024-07-18".into(),
+            ModelPricing {
+                input_cost_per_token: Some(1.5e-7),
+                output_cost_per_token: Some(6e-7),
+                training_cost_per_token: Some(3e-6),
+                ..Default::default()
+            },
+        );
+        let lookup = PricingLookup::new(litellm, HashMap::new());
+        let tokens = TokenBreakdown { input: 1_000_000, training: 100_000, ..Default::default() };
+        // input: 1M * 1.5e-7 = 0.15, training: 100K * 3e-6 = 0.30
+        let cost = lookup.calculate_token_cost("gpt-4o-mini-2024-07-18", None, &tokens);
+        assert!((cost - 0.45).abs() < 0.001);
+    }
+
     // =========================================================================
     // ROUTING PREFIX TESTS (e.g., antigravity-auth plugin)
     // =========================================================================
@@ -1797,9 +2292,396 @@ mod tests {
     fn test_antigravity_cost_calculation() {
         let lookup = create_lookup();
         let cost_with_prefix =
-            lookup.calculate_cost("antigravity-gpt-5.2", 1_000_000, 500_000, 0, 0, 0);
-        let cost_without_prefix = lookup.calculate_cost("gpt-5.2", 1_000_000, 500_000, 0, 0, 0);
+            lookup.calculate_cost_with_provider("antigravity-gpt-5.2", None, 1_000_000, 500_000, 0, 0, 0);
+        let cost_without_prefix = lookup.calculate_cost_with_provider("gpt-5.2", None, 1_000_000, 500_000, 0, 0, 0);
         assert!((cost_with_prefix - cost_without_prefix).abs() < 0.001);
         assert!(cost_with_prefix > 0.0);
     }
+
+    // =========================================================================
+    // PROVIDER PREFIX ORDER
+    // =========================================================================
+
+    fn command_r_lookup() -> PricingLookup {
+        litellm_lookup(&[
+            ("cohere/command-r", 0.00000015, 0.0000006),
+            ("perplexity/command-r", 0.000001, 0.000004),
+        ])
+    }
+
+    #[test]
+    fn test_non_prefix_match_has_no_prefix() {
+        let lookup = create_lookup();
+        assert_eq!(lookup.lookup("gpt-5").unwrap().matched_prefix, None);
+        assert_eq!(lookup.lookup("claude-3.5-haiku").unwrap().matched_prefix, None);
+    }
+
+    #[test]
+    fn test_prefix_order_default() {
+        let defaults: Vec<String> = PROVIDER_PREFIXES.iter().map(|p| p.to_string()).collect();
+        let lookup = command_r_lookup().with_provider_prefixes(&defaults);
+        let result = lookup.lookup("command-r").unwrap();
+        assert_eq!(result.matched_key, "cohere/command-r");
+        assert_eq!(result.matched_prefix.as_deref(), Some("cohere/"));
+    }
+
+    #[test]
+    fn test_prefix_order_configured() {
+        let lookup = command_r_lookup().with_provider_prefixes(&keys(&["Perplexity", "cohere/"]));
+        let result = lookup.lookup("command-r").unwrap();
+        assert_eq!(result.matched_key, "perplexity/command-r");
+        assert_eq!(result.matched_prefix.as_deref(), Some("perplexity/"));
+    }
+
+    #[test]
+    fn test_resolve_provider_prefixes() {
+        assert_eq!(resolve_provider_prefixes(None).len(), PROVIDER_PREFIXES.len());
+        assert_eq!(
+            resolve_provider_prefixes(Some(&keys(&[" OpenAI ", "openai/", "", "x-ai//"]))),
+            vec!["openai/".to_string(), "x-ai/".to_string()]
+        );
+    }
+
+    // =========================================================================
+    // PROVIDER-SCOPED LOOKUP (Vertex AI vs Gemini API)
+    // =========================================================================
+
+    fn google_lookup() -> PricingLookup {
+        litellm_lookup(&[
+            ("gemini-2.5-pro", 0.00000125, 0.00001),
+            ("gemini/gemini-2.5-pro", 0.000001, 0.00001),
+            ("vertex_ai/gemini-2.5-pro", 0.0000015, 0.00001),
+        ])
+    }
+
+    #[test]
+    fn test_lookup_with_provider_vertex() {
+        let lookup = google_lookup();
+        let result = lookup.lookup_with_provider("gemini-2.5-pro", Some("vertex_ai")).unwrap();
+        assert_eq!(result.matched_key, "vertex_ai/gemini-2.5-pro");
+        assert_eq!(result.matched_prefix.as_deref(), Some("vertex_ai/"));
+
+        let result = lookup
+            .lookup_with_provider("gemini-2.5-pro-high", Some("google-vertex"))
+            .unwrap();
+        assert_eq!(result.matched_key, "vertex_ai/gemini-2.5-pro");
+    }
+
+    #[test]
+    fn test_custom_source_takes_precedence() {
+        let rate_card = HashMap::from([(
+            "GPT-5.2".to_string(),
+            ModelPricing { input_cost_per_token: Some(1e-7), ..Default::default() },
+        )]);
+        let lookup = create_lookup().with_custom_source("ratecard", rate_card);
+
+        let result = lookup.lookup("gpt-5.2-high").unwrap();
+        assert_eq!(result.source, "ratecard");
+        assert_eq!(result.matched_key, "GPT-5.2");
+        assert_eq!(lookup.lookup_with_provider("gpt-5.2", Some("azure")).unwrap().source, "ratecard");
+
+        // Models the rate card doesn't list, and forced built-in sources, are unaffected
+        assert_eq!(lookup.lookup("gpt-5.1").unwrap().source, "LiteLLM");
+        assert_eq!(lookup.lookup_with_source("gpt-5.2", Some("litellm")).unwrap().source, "LiteLLM");
+        assert!(lookup.lookup_with_source("gpt-5.1", Some("ratecard")).is_none());
+    }
+
+    #[test]
+    fn test_merge_policy_on_conflicting_sources() {
+        use crate::pricing::merge::Prefer;
+
+        let price = |input: f64| ModelPricing {
+            input_cost_per_token: Some(input),
+            output_cost_per_token: Some(1e-5),
+            ..Default::default()
+        };
+        let lookup = |prefer| {
+            PricingLookup::new(
+                HashMap::from([("conflict-test-model".into(), price(3e-6)), ("agreed-test-model".into(), price(1e-6))]),
+                HashMap::from([("conflict-test-model".into(), price(2e-6)), ("agreed-test-model".into(), price(1e-6))]),
+            )
+            .with_merge_policy(MergePolicy { prefer, flag_conflicts: false })
+            .with_snapshot_times(100, 200)
+        };
+
+        assert_eq!(lookup(Prefer::Litellm).lookup("conflict-test-model").unwrap().source, "LiteLLM");
+        assert_eq!(lookup(Prefer::Openrouter).lookup("conflict-test-model").unwrap().source, "OpenRouter");
+        assert_eq!(lookup(Prefer::Cheaper).lookup("conflict-test-model").unwrap().source, "OpenRouter");
+        assert_eq!(lookup(Prefer::Newer).lookup("conflict-test-model").unwrap().source, "OpenRouter");
+        assert_eq!(lookup(Prefer::Openrouter).lookup("agreed-test-model").unwrap().source, "LiteLLM");
+
+        let conflicts = diagnostics::take_price_conflicts();
+        let conflict = conflicts.iter().find(|c| c.model_id == "conflict-test-model").unwrap();
+        assert_eq!(conflict.litellm, (Some(3e-6), Some(1e-5)));
+        assert_eq!(conflict.openrouter.0, Some(2e-6));
+        assert!(!conflicts.iter().any(|c| c.model_id == "agreed-test-model"));
+    }
+
+    #[test]
+    fn test_openrouter_prices_from_history() {
+        let price = |input: f64| ModelPricing { input_cost_per_token: Some(input), ..Default::default() };
+        let root = tempfile::tempdir().unwrap();
+        let dir = root.path().join(history::HISTORY_DIR).join("openrouter");
+        std::fs::create_dir_all(&dir).unwrap();
+        let archived = HashMap::from([("vendor/router-only-model".to_string(), price(4e-6))]);
+        std::fs::write(dir.join("2025-03-01.json"), serde_json::to_vec(&archived).unwrap()).unwrap();
+
+        const MARCH_1_NOON: i64 = 1_740_830_400_000;
+        const DAY: i64 = 86_400_000;
+        let lookup = PricingLookup::new(
+            HashMap::from([("claude-sonnet-4".to_string(), price(3e-6))]),
+            HashMap::from([("vendor/router-only-model".to_string(), price(2e-6))]),
+        )
+        .with_snapshot_times(0, ((MARCH_1_NOON + 2 * DAY) / 1000) as u64)
+        .with_openrouter_history(root.path().to_path_buf());
+
+        let tokens = TokenBreakdown { input: 1_000_000, ..Default::default() };
+        let cost = |model: &str, at: Option<i64>| lookup.calculate_token_cost_at(model, None, &tokens, at);
+        assert!((cost("vendor/router-only-model", None) - 2.0).abs() < 1e-9, "undated: current rate");
+        assert!((cost("vendor/router-only-model", Some(MARCH_1_NOON)) - 4.0).abs() < 1e-9);
+        assert!((cost("vendor/router-only-model", Some(MARCH_1_NOON + DAY)) - 3.0).abs() < 1e-9, "interpolated");
+        assert!((cost("claude-sonnet-4", Some(MARCH_1_NOON)) - 3.0).abs() < 1e-9, "LiteLLM prices stay current");
+    }
+
+    #[test]
+    fn test_lookup_with_provider_gemini_api() {
+        let lookup = google_lookup();
+        let result = lookup.lookup_with_provider("gemini-2.5-pro", Some("google")).unwrap();
+        assert_eq!(result.matched_key, "gemini/gemini-2.5-pro");
+        let result = lookup.lookup_with_provider("Gemini-2.5-Pro", Some("Gemini")).unwrap();
+        assert_eq!(result.matched_key, "gemini/gemini-2.5-pro");
+    }
+
+    #[test]
+    fn test_lookup_with_provider_falls_back() {
+        let lookup = google_lookup();
+        for provider in [None, Some("anthropic"), Some("bedrock")] {
+            let result = lookup.lookup_with_provider("gemini-2.5-pro", provider).unwrap();
+            assert_eq!(result.matched_key, "gemini-2.5-pro", "{:?}", provider);
+        }
+        assert!(lookup.lookup_with_provider("unknown-model-xyz", Some("vertex_ai")).is_none());
+    }
+
+    #[test]
+    fn test_calculate_cost_with_provider() {
+        let lookup = google_lookup();
+        let cost = |provider| {
+            lookup.calculate_cost_with_provider("gemini-2.5-pro", provider, 1_000_000, 0, 0, 0, 0)
+        };
+        let vertex = cost(Some("vertex_ai"));
+        let studio = cost(Some("gemini"));
+        let plain = lookup.calculate_cost_with_provider("gemini-2.5-pro", None, 1_000_000, 0, 0, 0, 0);
+        assert!((vertex - 1.5).abs() < 1e-9);
+        assert!((studio - 1.0).abs() < 1e-9);
+        assert!((plain - 1.25).abs() < 1e-9);
+    }
+
+    // =========================================================================
+    // xAI AND MISTRAL
+    // =========================================================================
+
+    #[test]
+    fn test_xai_and_mistral_prefixes() {
+        let mut litellm = HashMap::new();
+        for key in ["xai/grok-4", "mistral/devstral-medium-latest"] {
+            litellm.insert(key.to_string(), ModelPricing { input_cost_per_token: Some(1e-6), ..Default::default() });
+        }
+        let lookup = PricingLookup::new(litellm, HashMap::new());
+        let result = lookup.lookup("grok-4-latest").unwrap();
+        assert_eq!(result.matched_key, "xai/grok-4");
+        assert_eq!(result.matched_prefix.as_deref(), Some("xai/"));
+        assert_eq!(lookup.lookup("devstral").unwrap().matched_key, "mistral/devstral-medium-latest");
+    }
+
+    #[test]
+    fn test_bundled_fallback() {
+        let lookup = PricingLookup::new(HashMap::new(), HashMap::new());
+        let result = lookup.lookup("mistral-medium-2508").unwrap();
+        assert_eq!(result.source, "Bundled");
+        assert_eq!(result.matched_key, "mistral-medium");
+        let cost = lookup.calculate_cost_with_provider("grok-code-fast-1", None, 1_000_000, 1_000_000, 0, 0, 0);
+        assert!((cost - 1.7).abs() < 1e-9);
+
+        // Fetched tables win, and a forced source skips the bundled rates
+        let mut litellm = HashMap::new();
+        litellm.insert("xai/grok-code-fast-1".to_string(), ModelPricing { input_cost_per_token: Some(1e-7), ..Default::default() });
+        let lookup = PricingLookup::new(litellm, HashMap::new());
+        assert_eq!(lookup.lookup("grok-code-fast-1").unwrap().source, "LiteLLM");
+        assert!(lookup.lookup_with_source("mistral-small-latest", Some("openrouter")).is_none());
+        assert_eq!(lookup.lookup_with_source("mistral-small-latest", Some("bundled")).unwrap().matched_key, "mistral-small");
+    }
+
+    // =========================================================================
+    // DERIVED ALIASES
+    // =========================================================================
+
+    fn keys(list: &[&str]) -> Vec<String> {
+        list.iter().map(|k| k.to_string()).collect()
+    }
+
+    #[test]
+    fn test_strip_date_suffix() {
+        assert_eq!(strip_date_suffix("claude-3-5-sonnet-20241022"), Some("claude-3-5-sonnet"));
+        assert_eq!(strip_date_suffix("claude-3-5-sonnet@20240620"), Some("claude-3-5-sonnet"));
+        assert_eq!(strip_date_suffix("gpt-4o-2024-08-06"), Some("gpt-4o"));
+        assert_eq!(strip_date_suffix("grok-code-fast-1-0825"), None);
+        assert_eq!(strip_date_suffix("gpt-4o"), None);
+        assert_eq!(strip_date_suffix("-20241022"), None);
+        assert_eq!(strip_date_suffix("日本語日本語日本語"), None);
+    }
+
+    #[test]
+    fn test_derive_aliases_prefix_date_latest() {
+        let aliases = derive_aliases(&keys(&[
+            "vertex_ai/gemini-2.5-pro",
+            "claude-3-5-sonnet-20241022",
+            "mistral/mistral-large-latest",
+        ]));
+        assert_eq!(aliases["gemini-2.5-pro"], "vertex_ai/gemini-2.5-pro");
+        assert_eq!(aliases["claude-3-5-sonnet"], "claude-3-5-sonnet-20241022");
+        assert_eq!(aliases["mistral-large"], "mistral/mistral-large-latest");
+        assert_eq!(aliases["mistral/mistral-large"], "mistral/mistral-large-latest");
+    }
+
+    #[test]
+    fn test_derive_aliases_skips_real_keys() {
+        let aliases = derive_aliases(&keys(&["gpt-4o", "openai/gpt-4o", "azure/gpt-4o"]));
+        assert!(!aliases.contains_key("gpt-4o"));
+    }
+
+    #[test]
+    fn test_derive_aliases_conflict_resolution() {
+        let aliases = derive_aliases(&keys(&[
+            "azure/claude-3-5-sonnet",
+            "anthropic/claude-3-5-sonnet",
+            "claude-3-5-sonnet-20240620",
+            "claude-3-5-sonnet-20241022",
+        ]));
+        // Unprefixed beats providers; newest date wins among unprefixed
+        assert_eq!(aliases["claude-3-5-sonnet"], "claude-3-5-sonnet-20241022");
+
+        let aliases = derive_aliases(&keys(&["azure/gemini-x", "google/gemini-x"]));
+        assert_eq!(aliases["gemini-x"], "google/gemini-x");
+    }
+
+    #[test]
+    fn test_lookup_uses_derived_alias() {
+        let lookup = litellm_lookup(&[
+            ("claude-3-5-sonnet-20240620", 0.000003, 0.000015),
+            ("claude-3-5-sonnet-20241022", 0.000003, 0.000015),
+        ]);
+
+        let result = lookup.lookup("claude-3-5-sonnet").unwrap();
+        assert_eq!(result.matched_key, "claude-3-5-sonnet-20241022");
+        let result = lookup.lookup_with_source("claude-3-5-sonnet", Some("litellm")).unwrap();
+        assert_eq!(result.matched_key, "claude-3-5-sonnet-20241022");
+    }
+
+    // =========================================================================
+    // WORD BOUNDARY MATCHING
+    // =========================================================================
+
+    #[test]
+    fn test_word_boundary_match_basic() {
+        assert!(is_word_boundary_match("gpt-4o", "gpt-4o"));
+        assert!(is_word_boundary_match("openai/gpt-4o", "gpt-4o"));
+        assert!(is_word_boundary_match("azure/gpt-4o-2024-08-06", "gpt-4o"));
+        assert!(!is_word_boundary_match("gpt-4o-mini", "gpt-4"));
+        assert!(!is_word_boundary_match("xgpt-4o", "gpt-4o"));
+        assert!(!is_word_boundary_match("gpt-4o", ""));
+        assert!(!is_word_boundary_match("", "gpt-4o"));
+    }
+
+    #[test]
+    fn test_word_boundary_match_checks_every_occurrence() {
+        // First occurrence is embedded in a word, the second stands alone
+        assert!(is_word_boundary_match("provider/r", "r"));
+        assert!(is_word_boundary_match("minimax/mini", "mini"));
+        // Overlapping candidates
+        assert!(!is_word_boundary_match("aa-a", "a-a"));
+        assert!(is_word_boundary_match("a-a-a", "a-a"));
+    }
+
+    #[test]
+    fn test_word_boundary_match_non_ascii_neighbours() {
+        // Multi-byte letters are alphanumeric and block a match
+        assert!(!is_word_boundary_match("éclaude-sonnet", "claude-sonnet"));
+        assert!(!is_word_boundary_match("claude-sonnetü", "claude-sonnet"));
+        assert!(!is_word_boundary_match("日claude", "claude"));
+        // Multi-byte punctuation is a separator
+        assert!(is_word_boundary_match("模型—claude-sonnet—v2", "claude-sonnet"));
+        assert!(is_word_boundary_match("🦀/gemini-2.5-pro", "gemini-2.5-pro"));
+    }
+
+    #[test]
+    fn test_word_boundary_match_non_ascii_needle() {
+        assert!(is_word_boundary_match("vendor/modèle-large", "modèle-large"));
+        assert!(is_word_boundary_match("日本語/モデル", "モデル"));
+        assert!(!is_word_boundary_match("日本語モデル", "モデル"));
+    }
+
+    #[test]
+    fn test_lookup_non_ascii_model_ids() {
+        let lookup = create_lookup();
+        assert!(lookup.lookup("modèle-inconnu-é").is_none());
+        assert!(lookup.lookup("日本語モデル").is_none());
+        assert!(lookup.calculate_cost_with_provider("🦀-claude", None, 1_000, 1_000, 0, 0, 0) >= 0.0);
+    }
+
+    // =========================================================================
+    // PROPERTY TESTS - lookup and normalization must never panic
+    // =========================================================================
+
+    use crate::testutil::{model_id, model_id_or_string, string};
+    use proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn prop_normalize_rules_never_panic(id in model_id(), noise in string(24)) {
+            let rules = NormalizeRules::bundled();
+            let _ = rules.apply(&id);
+            let _ = rules.apply(&noise);
+        }
+
+        #[test]
+        fn prop_normalize_version_separator_preserves_length(id in model_id()) {
+            if let Some(normalized) = normalize_version_separator(&id) {
+                prop_assert_eq!(normalized.chars().count(), id.chars().count(), "{:?}", id);
+            }
+        }
+
+        #[test]
+        fn prop_word_boundary_match_never_panics(key in string(32), id in string(8)) {
+            let _ = is_word_boundary_match(&key, &id);
+        }
+
+        #[test]
+        fn prop_word_boundary_match_whole_key(id in model_id()) {
+            prop_assume!(!id.is_empty());
+            prop_assert!(is_word_boundary_match(&id, &id), "{:?}", id);
+            prop_assert!(is_word_boundary_match(&format!("provider/{}", id), &id), "{:?}", id);
+            prop_assert!(is_word_boundary_match(&format!("日本/{}@", id), &id), "{:?}", id);
+        }
+
+        #[test]
+        fn prop_strip_helpers_never_panic(id in model_id(), noise in string(16)) {
+            let _ = strip_routing_prefix(&id);
+            let _ = strip_tier_suffix(&id);
+            let _ = strip_fallback_suffix(&id);
+            let _ = extract_model_family(&id);
+            let _ = strip_date_suffix(&id);
+            let _ = strip_date_suffix(&noise);
+            let _ = alias_candidates(&id);
+        }
+
+        #[test]
+        fn prop_lookup_never_panics(id in model_id_or_string(24)) {
+            let lookup = create_lookup();
+            let _ = lookup.lookup(&id);
+            let _ = lookup.lookup_with_source(&id, Some("litellm"));
+            let _ = lookup.lookup_with_source(&id, Some("openrouter"));
+            let cost = lookup.calculate_cost_with_provider(&id, None, 1_000, 500, 100, 50, 10);
+            prop_assert!(cost.is_finite() && cost >= 0.0, "{:?} -> {}", id, cost);
+        }
+    }
 }
@@ -0,0 +1,127 @@
+//! Merge policy for disagreeing price sources
+//!
+//! When LiteLLM and OpenRouter both have an exact entry for a model and their
+//! prices differ, `pricingMerge` in settings decides which one is used:
+//!
+//! ```json
+//! "pricingMerge": { "prefer": "cheaper", "flagConflicts": true }
+//! ```
+//!
+//! `prefer` is "litellm" (default), "openrouter", "cheaper" (lower input +
+//! output price) or "newer" (the more recently fetched snapshot). Every
+//! conflict is recorded in diagnostics; `flagConflicts` also logs each one to
+//! stderr. Prefix and fuzzy matches are not merged, since the two sources may
+//! have matched different models.
+
+use super::ModelPricing;
+use serde::{Deserialize, Serialize};
+
+/// Prices within this relative difference are treated as the same
+const TOLERANCE: f64 = 0.005;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Prefer {
+    #[default]
+    Litellm,
+    Openrouter,
+    Cheaper,
+    Newer,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct MergePolicy {
+    pub prefer: Prefer,
+    pub flag_conflicts: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Litellm,
+    Openrouter,
+}
+
+/// One source's entry for a model, with when its table was fetched
+/// (seconds since the epoch; 0 if unknown)
+pub struct Candidate<'a> {
+    pub pricing: &'a ModelPricing,
+    pub fetched_at: u64,
+}
+
+/// Whether the input or output prices differ beyond rounding
+pub fn conflicts(a: &ModelPricing, b: &ModelPricing) -> bool {
+    let differs = |x: Option<f64>, y: Option<f64>| match (x, y) {
+        (Some(x), Some(y)) => (x - y).abs() > TOLERANCE * x.abs().max(y.abs()),
+        _ => false,
+    };
+    differs(a.input_cost_per_token, b.input_cost_per_token) || differs(a.output_cost_per_token, b.output_cost_per_token)
+}
+
+impl MergePolicy {
+    /// Which entry to use when both sources price a model. Ties go to LiteLLM.
+    pub fn choose(&self, litellm: Candidate<'_>, openrouter: Candidate<'_>) -> Side {
+        let prefer_openrouter = match self.prefer {
+            Prefer::Litellm => false,
+            Prefer::Openrouter => true,
+            Prefer::Cheaper => per_token(openrouter.pricing) < per_token(litellm.pricing),
+            Prefer::Newer => openrouter.fetched_at > litellm.fetched_at,
+        };
+        if prefer_openrouter {
+            Side::Openrouter
+        } else {
+            Side::Litellm
+        }
+    }
+}
+
+fn per_token(pricing: &ModelPricing) -> f64 {
+    pricing.input_cost_per_token.unwrap_or(0.0) + pricing.output_cost_per_token.unwrap_or(0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn price(input: f64, output: f64) -> ModelPricing {
+        ModelPricing {
+            input_cost_per_token: Some(input),
+            output_cost_per_token: Some(output),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_conflicts_ignores_rounding() {
+        assert!(!conflicts(&price(3e-6, 1.5e-5), &price(0.000003, 0.000015)));
+        assert!(!conflicts(&price(3e-6, 1.5e-5), &price(3.001e-6, 1.5e-5)));
+        assert!(conflicts(&price(3e-6, 1.5e-5), &price(3e-6, 1.2e-5)));
+        assert!(!conflicts(&price(3e-6, 1.5e-5), &ModelPricing::default()));
+    }
+
+    #[test]
+    fn test_choose() {
+        let (expensive, cheap) = (price(3e-6, 1.5e-5), price(2e-6, 1e-5));
+        let pick = |prefer, litellm_time, openrouter_time| {
+            MergePolicy { prefer, flag_conflicts: false }.choose(
+                Candidate { pricing: &expensive, fetched_at: litellm_time },
+                Candidate { pricing: &cheap, fetched_at: openrouter_time },
+            )
+        };
+
+        assert_eq!(pick(Prefer::Litellm, 0, 0), Side::Litellm);
+        assert_eq!(pick(Prefer::Openrouter, 0, 0), Side::Openrouter);
+        assert_eq!(pick(Prefer::Cheaper, 0, 0), Side::Openrouter);
+        assert_eq!(pick(Prefer::Newer, 200, 100), Side::Litellm);
+        assert_eq!(pick(Prefer::Newer, 100, 200), Side::Openrouter);
+        assert_eq!(pick(Prefer::Newer, 100, 100), Side::Litellm);
+    }
+
+    #[test]
+    fn test_settings_shape() {
+        let policy: MergePolicy = serde_json::from_str(r#"{"prefer":"newer","flagConflicts":true}"#).unwrap();
+        assert_eq!(policy, MergePolicy { prefer: Prefer::Newer, flag_conflicts: true });
+        assert_eq!(serde_json::from_str::<MergePolicy>("{}").unwrap(), MergePolicy::default());
+        assert!(serde_json::from_str::<MergePolicy>(r#"{"prefer":"anthropic"}"#).is_err());
+    }
+}
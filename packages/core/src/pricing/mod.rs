@@ -1,51 +1,213 @@
 pub mod aliases;
+pub mod bundled;
 pub mod cache;
+pub mod history;
 pub mod litellm;
 pub mod lookup;
+pub mod merge;
+pub mod normalize;
 pub mod openrouter;
+pub mod provider;
+pub mod rates;
+pub mod telemetry;
+pub mod windows;
+pub mod xai;
 
 use lookup::{PricingLookup, LookupResult};
+use crate::TokenBreakdown;
 use std::collections::HashMap;
+use once_cell::sync::Lazy;
+use std::future::Future;
 use std::sync::Arc;
-use tokio::sync::OnceCell;
 
-pub use litellm::ModelPricing;
+pub use litellm::{ModelPricing, PricingDataset};
+pub use provider::PricingProvider;
 
-static PRICING_SERVICE: OnceCell<Arc<PricingService>> = OnceCell::const_new();
+/// Price sources a lookup can be forced to
+pub const SOURCES: &[&str] = &["litellm", "openrouter"];
+
+/// Why a fetch fails in builds without the `network` feature
+#[cfg(not(feature = "network"))]
+pub const OFFLINE: &str = "built without the network feature; prices come from the disk cache only";
+
+static PRICING_SERVICE: Lazy<ServiceCell> = Lazy::new(ServiceCell::default);
+
+/// A service built on first use. Callers arriving while it's built wait for
+/// it; after a failed build the next caller tries again.
+#[derive(Default)]
+pub(crate) struct ServiceCell(futures::lock::Mutex<Option<Arc<PricingService>>>);
+
+impl ServiceCell {
+    pub(crate) async fn get_or_try_init<F, Fut>(&self, init: F) -> Result<Arc<PricingService>, String>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<PricingService, String>>,
+    {
+        let mut service = self.0.lock().await;
+        if let Some(service) = service.as_ref() {
+            return Ok(Arc::clone(service));
+        }
+        let built = Arc::new(init().await?);
+        *service = Some(Arc::clone(&built));
+        Ok(built)
+    }
+}
 
 pub struct PricingService {
     lookup: PricingLookup,
+    /// Why prices could not be loaded, for a service standing in without them
+    unavailable: Option<String>,
 }
 
 impl PricingService {
     pub fn new(litellm_data: HashMap<String, ModelPricing>, openrouter_data: HashMap<String, ModelPricing>) -> Self {
         Self {
             lookup: PricingLookup::new(litellm_data, openrouter_data),
+            unavailable: None,
         }
     }
+
+    /// No fetched prices, because of `reason`. Only bundled rates and costs
+    /// reported by sources remain, so reports still render.
+    pub fn unavailable(reason: impl Into<String>) -> Self {
+        Self { unavailable: Some(reason.into()), ..Self::new(HashMap::new(), HashMap::new()) }
+    }
+
+    /// Tables as if fetched at `at`, in seconds since the epoch
+    #[cfg(test)]
+    pub fn with_fetched_at(self, at: u64) -> Self {
+        Self { lookup: self.lookup.with_snapshot_times(at, at), ..self }
+    }
     
-    async fn fetch_inner() -> Result<Self, String> {
-        let (litellm_result, openrouter_data) = tokio::join!(
-            litellm::fetch(),
-            openrouter::fetch_all_mapped()
+    /// Fetch LiteLLM and OpenRouter plus `extra` providers, concurrently.
+    /// Extra providers' prices take precedence, in the order given.
+    pub async fn with_providers(extra: Vec<Arc<dyn PricingProvider>>) -> Result<Self, String> {
+        let (litellm, openrouter, extra_tables) = futures::join!(
+            provider::load(&litellm::LiteLlm),
+            provider::load(&openrouter::OpenRouter),
+            futures::future::join_all(extra.iter().map(|p| provider::load(p.as_ref())))
         );
-        
-        let litellm_data = litellm_result.map_err(|e| e.to_string())?;
-        
-        Ok(Self::new(litellm_data, openrouter_data))
+
+        let (litellm, litellm_time) = accept(&litellm::LiteLlm, litellm)?;
+        let (openrouter, openrouter_time) = accept(&openrouter::OpenRouter, openrouter)?;
+        let mut lookup = PricingLookup::new(litellm, openrouter)
+            .with_snapshot_times(litellm_time, openrouter_time)
+            .with_openrouter_history(cache::get_cache_dir());
+        for (provider, table) in extra.iter().zip(extra_tables) {
+            lookup = lookup.with_custom_source(provider.name(), accept(provider.as_ref(), table)?.0);
+        }
+        telemetry::log_if_verbose();
+        Ok(Self { lookup, unavailable: None })
     }
-    
+
+    /// Prices as archived on or before `date` (YYYY-MM-DD), for reproducing
+    /// a past report; see [`history`]
+    pub fn as_of(date: &str) -> Result<Self, String> {
+        let (litellm, openrouter) = history::tables_as_of(history::parse_date(date)?)?;
+        Ok(Self::new(litellm, openrouter))
+    }
+
     pub async fn get_or_init() -> Result<Arc<PricingService>, String> {
         PRICING_SERVICE.get_or_try_init(|| async {
-            Self::fetch_inner().await.map(Arc::new)
-        }).await.map(Arc::clone)
+            // xAI's own list, when there's a key to read it with
+            let extra: Vec<Arc<dyn PricingProvider>> = xai::XAi::from_secret()
+                .into_iter()
+                .map(|provider| Arc::new(provider) as Arc<dyn PricingProvider>)
+                .collect();
+            Self::with_providers(extra).await
+        }).await
+    }
+
+    /// The shared service, or one without prices when they can't be loaded.
+    /// Loading is tried again on the next call.
+    pub async fn get_or_unavailable() -> Arc<PricingService> {
+        match Self::get_or_init().await {
+            Ok(service) => service,
+            Err(e) => {
+                eprintln!("[tokscale] Pricing unavailable ({}); costs are only those sources reported", e);
+                Arc::new(Self::unavailable(e))
+            }
+        }
+    }
+
+    /// Why prices could not be loaded, if they couldn't
+    pub fn unavailable_reason(&self) -> Option<&str> {
+        self.unavailable.as_deref()
+    }
+
+    /// When the price tables in use were fetched (the older of the two), in
+    /// seconds since the epoch; `None` for archived or hand-built tables
+    pub fn fetched_at(&self) -> Option<u64> {
+        self.lookup.fetched_at()
     }
 
     pub fn lookup_with_source(&self, model_id: &str, force_source: Option<&str>) -> Option<LookupResult> {
         self.lookup.lookup_with_source(model_id, force_source)
     }
     
-    pub fn calculate_cost(&self, model_id: &str, input: i64, output: i64, cache_read: i64, cache_write: i64, reasoning: i64) -> f64 {
-        self.lookup.calculate_cost(model_id, input, output, cache_read, cache_write, reasoning)
+    pub fn lookup_with_provider(&self, model_id: &str, provider: Option<&str>) -> Option<LookupResult> {
+        self.lookup.lookup_with_provider(model_id, provider)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn calculate_cost_with_provider(&self, model_id: &str, provider: Option<&str>, input: i64, output: i64, cache_read: i64, cache_write: i64, reasoning: i64) -> f64 {
+        self.lookup.calculate_cost_with_provider(model_id, provider, input, output, cache_read, cache_write, reasoning)
+    }
+
+    pub fn calculate_token_cost(&self, model_id: &str, provider: Option<&str>, tokens: &TokenBreakdown) -> f64 {
+        self.lookup.calculate_token_cost(model_id, provider, tokens)
+    }
+
+    /// Cost of `tokens` in a message sent at `timestamp_ms`, for models whose
+    /// rates depend on the time of day
+    pub fn calculate_token_cost_at(&self, model_id: &str, provider: Option<&str>, tokens: &TokenBreakdown, timestamp_ms: i64) -> f64 {
+        self.lookup.calculate_token_cost_at(model_id, provider, tokens, Some(timestamp_ms))
+    }
+}
+
+/// Fetching one built-in table, bypassing the cache
+pub struct SourceCheck {
+    pub name: String,
+    pub required: bool,
+    /// Models in the fetched table
+    pub result: Result<usize, String>,
+    pub elapsed: std::time::Duration,
+    /// When the cached table was fetched, in seconds since the epoch, if one
+    /// is fresh enough to be used
+    pub cached_at: Option<u64>,
+}
+
+async fn check_source(provider: &dyn PricingProvider) -> SourceCheck {
+    let cached_at = provider.cache_key().and_then(|key| provider::cached(&key)).map(|cached| cached.timestamp);
+    let start = std::time::Instant::now();
+    let result = provider.fetch().await.map(|table| table.len());
+    SourceCheck {
+        name: provider.name().to_string(),
+        required: provider.required(),
+        result,
+        elapsed: start.elapsed(),
+        cached_at,
+    }
+}
+
+/// Fetch LiteLLM and OpenRouter afresh, concurrently, to see whether they can
+/// be reached from here
+pub async fn check_sources() -> Vec<SourceCheck> {
+    let (litellm, openrouter) = futures::join!(check_source(&litellm::LiteLlm), check_source(&openrouter::OpenRouter));
+    vec![litellm, openrouter]
+}
+
+/// A fetched table, or an empty one when an optional provider failed
+fn accept(
+    provider: &dyn PricingProvider,
+    table: Result<(PricingDataset, u64), String>,
+) -> Result<(PricingDataset, u64), String> {
+    match table {
+        Ok(table) => Ok(table),
+        Err(e) if provider.required() => Err(format!("{}: {}", provider.name(), e)),
+        Err(e) => {
+            eprintln!("[tokscale] Skipping pricing provider {}: {}", provider.name(), e);
+            Ok((PricingDataset::new(), 0))
+        }
     }
 }
@@ -0,0 +1,257 @@
+//! Data-driven model name normalization
+//!
+//! Maps loose model ids ("opus-4-5", "Sonnet 3.7") to the canonical pricing key
+//! for their family. Rules ship in `normalize_rules.json` and users can prepend
+//! their own via `normalizeRules` in `~/.config/tokscale/settings.json`, so a
+//! new model generation doesn't need a release.
+//!
+//! Rules are tried in order and the first match wins; user rules run before the
+//! bundled ones. All patterns are plain case-insensitive substrings.
+//!
+//! The same table places models in the catalog's hierarchy of provider →
+//! family → model version, so reports can roll "every Sonnet" up into one
+//! line. A rule with a `family` (and optionally the `provider` the family
+//! belongs to) names the family of the ids it matches; a rule may have a
+//! `canonical` key, a `family`, or both. The first matching rule with a
+//! family decides it, independently of the first with a canonical key.
+
+use serde::{Deserialize, Serialize};
+
+/// Highest rule set version this build understands
+pub const RULES_VERSION: u32 = 1;
+
+const BUNDLED_RULES: &str = include_str!("normalize_rules.json");
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NormalizeRule {
+    /// Every pattern must occur in the model id
+    pub all: Vec<String>,
+    /// At least one pattern must occur (ignored when empty)
+    pub any: Vec<String>,
+    /// No pattern may occur
+    pub none: Vec<String>,
+    /// Pricing key returned on match
+    pub canonical: String,
+    /// Provider the family belongs to, e.g. "anthropic"
+    pub provider: String,
+    /// Family of the matching models, e.g. "claude-sonnet"
+    pub family: String,
+}
+
+impl NormalizeRule {
+    fn matches(&self, lower: &str) -> bool {
+        self.all.iter().all(|p| lower.contains(p.as_str()))
+            && (self.any.is_empty() || self.any.iter().any(|p| lower.contains(p.as_str())))
+            && !self.none.iter().any(|p| lower.contains(p.as_str()))
+    }
+
+    fn to_lowercase(&self) -> Self {
+        let lower = |v: &[String]| v.iter().map(|p| p.to_lowercase()).collect();
+        Self {
+            all: lower(&self.all),
+            any: lower(&self.any),
+            none: lower(&self.none),
+            canonical: self.canonical.clone(),
+            provider: self.provider.clone(),
+            family: self.family.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NormalizeRuleSet {
+    pub version: u32,
+    pub rules: Vec<NormalizeRule>,
+}
+
+/// Ordered, pre-lowercased rules ready for matching
+#[derive(Debug, Clone, Default)]
+pub struct NormalizeRules {
+    rules: Vec<NormalizeRule>,
+}
+
+impl NormalizeRules {
+    /// Rules shipped with this build
+    pub fn bundled() -> Self {
+        let set: NormalizeRuleSet =
+            serde_json::from_str(BUNDLED_RULES).expect("bundled normalize_rules.json is valid");
+        Self::from_sets(&[set])
+    }
+
+    /// User rules from settings followed by the bundled rules
+    pub fn load() -> Self {
+        let mut rules = Self::default();
+        if let Some(user) = &crate::config::settings().normalize_rules {
+            rules.extend(user);
+        }
+        rules.extend_bundled();
+        rules
+    }
+
+    /// Concatenate rule sets in priority order. Sets with an unsupported
+    /// version are skipped rather than half-applied.
+    pub fn from_sets(sets: &[NormalizeRuleSet]) -> Self {
+        let mut rules = Self::default();
+        for set in sets {
+            rules.extend(set);
+        }
+        rules
+    }
+
+    fn extend(&mut self, set: &NormalizeRuleSet) {
+        if set.version > RULES_VERSION {
+            eprintln!(
+                "[tokscale] ignoring normalize rules v{} (supported: v{})",
+                set.version, RULES_VERSION
+            );
+            return;
+        }
+        self.rules.extend(
+            set.rules
+                .iter()
+                .filter(|r| !r.canonical.is_empty() || !r.family.is_empty())
+                .map(NormalizeRule::to_lowercase),
+        );
+    }
+
+    fn extend_bundled(&mut self) {
+        self.rules.extend(Self::bundled().rules);
+    }
+
+    /// Canonical key for `model_id`, if any rule matches
+    pub fn apply(&self, model_id: &str) -> Option<String> {
+        let lower = model_id.to_lowercase();
+        self.rules
+            .iter()
+            .find(|r| !r.canonical.is_empty() && r.matches(&lower))
+            .map(|r| r.canonical.clone())
+    }
+
+    /// Family of `model_id` and the provider it belongs to (empty when the
+    /// rule doesn't say), if any rule with a family matches
+    pub fn family(&self, model_id: &str) -> Option<(&str, &str)> {
+        let lower = model_id.to_lowercase();
+        self.rules
+            .iter()
+            .find(|r| !r.family.is_empty() && r.matches(&lower))
+            .map(|r| (r.provider.as_str(), r.family.as_str()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(all: &[&str], any: &[&str], none: &[&str], canonical: &str) -> NormalizeRule {
+        let v = |s: &[&str]| s.iter().map(|p| p.to_string()).collect();
+        NormalizeRule {
+            all: v(all),
+            any: v(any),
+            none: v(none),
+            canonical: canonical.into(),
+            ..NormalizeRule::default()
+        }
+    }
+
+    #[test]
+    fn test_bundled_rules_parse() {
+        let rules = NormalizeRules::bundled();
+        assert!(!rules.rules.is_empty());
+    }
+
+    #[test]
+    fn test_bundled_claude_families() {
+        let rules = NormalizeRules::bundled();
+        assert_eq!(rules.apply("opus-4-5").as_deref(), Some("claude-opus-4-5"));
+        assert_eq!(rules.apply("Claude-Opus-4.5").as_deref(), Some("claude-opus-4-5"));
+        assert_eq!(rules.apply("opus-4").as_deref(), Some("claude-opus-4"));
+        assert_eq!(rules.apply("sonnet-4").as_deref(), Some("claude-sonnet-4"));
+        assert_eq!(rules.apply("sonnet-4.5").as_deref(), Some("claude-sonnet-4-5"));
+        assert_eq!(rules.apply("sonnet-3.7").as_deref(), Some("claude-3-7-sonnet"));
+        assert_eq!(rules.apply("sonnet-3-5-v4").as_deref(), Some("claude-3.5-sonnet"));
+        assert_eq!(rules.apply("haiku-3.5").as_deref(), Some("claude-3.5-haiku"));
+        assert_eq!(rules.apply("haiku-4-5").as_deref(), Some("claude-haiku-4-5"));
+        assert_eq!(rules.apply("gpt-4o"), None);
+        assert_eq!(rules.apply("haiku"), None);
+    }
+
+    #[test]
+    fn test_bundled_families() {
+        let rules = NormalizeRules::bundled();
+        assert_eq!(rules.family("claude-sonnet-4-5-20250929"), Some(("anthropic", "claude-sonnet")));
+        assert_eq!(rules.family("claude-3-5-sonnet-20241022"), Some(("anthropic", "claude-sonnet")));
+        assert_eq!(rules.family("gpt-4o-mini"), Some(("openai", "gpt-4")));
+        assert_eq!(rules.family("gemini-2.5-flash"), Some(("google", "gemini-flash")));
+        assert_eq!(rules.family("glm-4.6"), None);
+        // Family-only rules don't rename
+        assert_eq!(rules.apply("gpt-4o"), None);
+    }
+
+    #[test]
+    fn test_first_match_wins() {
+        let set = NormalizeRuleSet {
+            version: 1,
+            rules: vec![
+                rule(&["opus", "5"], &[], &[], "claude-opus-5"),
+                rule(&["opus"], &[], &[], "claude-opus-4"),
+            ],
+        };
+        let rules = NormalizeRules::from_sets(&[set]);
+        assert_eq!(rules.apply("opus-5").as_deref(), Some("claude-opus-5"));
+        assert_eq!(rules.apply("opus").as_deref(), Some("claude-opus-4"));
+    }
+
+    #[test]
+    fn test_user_rules_take_precedence() {
+        let user = NormalizeRuleSet {
+            version: 1,
+            rules: vec![rule(&["OPUS"], &["4.5"], &[], "my-opus")],
+        };
+        let mut rules = NormalizeRules::from_sets(&[user]);
+        rules.extend_bundled();
+        assert_eq!(rules.apply("opus-4.5").as_deref(), Some("my-opus"));
+        assert_eq!(rules.apply("opus-4-5").as_deref(), Some("claude-opus-4-5"));
+    }
+
+    #[test]
+    fn test_unsupported_version_skipped() {
+        let future = NormalizeRuleSet {
+            version: RULES_VERSION + 1,
+            rules: vec![rule(&["opus"], &[], &[], "future-opus")],
+        };
+        assert!(NormalizeRules::from_sets(&[future]).rules.is_empty());
+    }
+
+    #[test]
+    fn test_rule_without_canonical_skipped() {
+        let set = NormalizeRuleSet {
+            version: 1,
+            rules: vec![rule(&["opus"], &[], &[], "")],
+        };
+        assert!(NormalizeRules::from_sets(&[set]).rules.is_empty());
+    }
+
+    #[test]
+    fn test_user_family_rules() {
+        let set: NormalizeRuleSet = serde_json::from_str(
+            r#"{"version": 1, "rules": [{"all": ["glm"], "provider": "zai", "family": "glm"}]}"#,
+        )
+        .unwrap();
+        let rules = NormalizeRules::from_sets(&[set]);
+        assert_eq!(rules.family("GLM-4.6"), Some(("zai", "glm")));
+        assert_eq!(rules.apply("GLM-4.6"), None);
+    }
+
+    #[test]
+    fn test_rule_set_from_json() {
+        let set: NormalizeRuleSet = serde_json::from_str(
+            r#"{"version": 1, "rules": [{"all": ["glm"], "any": ["5"], "canonical": "glm-5"}]}"#,
+        )
+        .unwrap();
+        let rules = NormalizeRules::from_sets(&[set]);
+        assert_eq!(rules.apply("GLM-5-Air").as_deref(), Some("glm-5"));
+        assert_eq!(rules.apply("glm-4.7"), None);
+    }
+}
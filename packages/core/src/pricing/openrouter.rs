@@ -1,30 +1,43 @@
-use super::cache;
-use super::litellm::ModelPricing;
+use super::litellm::{ModelPricing, PricingDataset};
+use super::provider::PricingProvider;
+#[cfg(feature = "network")]
+use super::telemetry;
+use futures::future::BoxFuture;
 use std::collections::HashMap;
+#[cfg(feature = "network")]
 use std::sync::Arc;
+#[cfg(feature = "network")]
 use serde::Deserialize;
+#[cfg(feature = "network")]
 use tokio::sync::Semaphore;
 
-const CACHE_FILENAME: &str = "pricing-openrouter.json";
+const SOURCE: &str = "openrouter";
+#[cfg(feature = "network")]
 const MODELS_URL: &str = "https://openrouter.ai/api/v1/models";
+#[cfg(feature = "network")]
 const MAX_RETRIES: u32 = 3;
+#[cfg(feature = "network")]
 const INITIAL_BACKOFF_MS: u64 = 200;
+#[cfg(feature = "network")]
 const MAX_CONCURRENT_REQUESTS: usize = 10;
 
-/// Structs for `/api/v1/models` endpoint (list all models).
+// Structs for `/api/v1/models` endpoint (list all models).
 
+#[cfg(feature = "network")]
 #[derive(Deserialize)]
 struct ModelListItem {
     id: String,
 }
 
+#[cfg(feature = "network")]
 #[derive(Deserialize)]
 struct ModelsListResponse {
     data: Vec<ModelListItem>,
 }
 
-/// Structs for `/api/v1/models/{id}/endpoints` endpoint (author pricing).
+// Structs for `/api/v1/models/{id}/endpoints` endpoint (author pricing).
 
+#[cfg(feature = "network")]
 #[derive(Deserialize)]
 struct EndpointPricing {
     prompt: String,
@@ -35,12 +48,16 @@ struct EndpointPricing {
     input_cache_write: Option<String>,
 }
 
+#[cfg(feature = "network")]
 #[derive(Deserialize)]
 struct Endpoint {
     provider_name: String,
     pricing: EndpointPricing,
+    #[serde(default)]
+    context_length: Option<u64>,
 }
 
+#[cfg(feature = "network")]
 #[derive(Deserialize)]
 struct EndpointData {
     #[allow(dead_code)]
@@ -48,6 +65,7 @@ struct EndpointData {
     endpoints: Vec<Endpoint>,
 }
 
+#[cfg(feature = "network")]
 #[derive(Deserialize)]
 struct EndpointsResponse {
     data: EndpointData,
@@ -57,6 +75,7 @@ struct EndpointsResponse {
 ///
 /// Translates model ID prefixes like `z-ai` to their corresponding
 /// provider names in the endpoints API, such as `Z.AI`.
+#[cfg(feature = "network")]
 fn get_author_provider_name(model_id: &str) -> Option<&'static str> {
     let prefix = model_id.split('/').next()?;
     
@@ -77,15 +96,27 @@ fn get_author_provider_name(model_id: &str) -> Option<&'static str> {
     }
 }
 
-pub fn load_cached() -> Option<HashMap<String, ModelPricing>> {
-    cache::load_cache(CACHE_FILENAME)
+/// Author-endpoint prices from OpenRouter's models API. Fetch failures yield
+/// an empty table rather than an error.
+pub struct OpenRouter;
+
+impl PricingProvider for OpenRouter {
+    fn name(&self) -> &str {
+        SOURCE
+    }
+
+    fn fetch(&self) -> BoxFuture<'_, Result<PricingDataset, String>> {
+        Box::pin(async { Ok(fetch_all_models().await) })
+    }
 }
 
+#[cfg(feature = "network")]
 fn parse_price(s: &str) -> Option<f64> {
     s.trim().parse::<f64>().ok().filter(|v| v.is_finite() && *v >= 0.0)
 }
 
 /// Fetch author pricing for a specific model using the /endpoints API
+#[cfg(feature = "network")]
 async fn fetch_author_pricing(
     client: Arc<reqwest::Client>, 
     model_id: String,
@@ -97,23 +128,21 @@ async fn fetch_author_pricing(
     
     let url = format!("https://openrouter.ai/api/v1/models/{}/endpoints", model_id);
     
-    let response = match client.get(&url)
-        .header("Content-Type", "application/json")
-        .send()
-        .await {
-            Ok(r) => r,
-            Err(e) => {
-                eprintln!("[tokscale] endpoints fetch failed for {}: {}", model_id, e);
-                return None;
-            }
-        };
+    let request = client.get(&url).header("Content-Type", "application/json");
+    let response = match telemetry::send(SOURCE, request).await {
+        Ok(r) => r,
+        Err(e) => {
+            eprintln!("[tokscale] endpoints fetch failed for {}: {}", model_id, e);
+            return None;
+        }
+    };
     
     if !response.status().is_success() {
         eprintln!("[tokscale] endpoints API returned {} for {}", response.status(), model_id);
         return None;
     }
     
-    let data: EndpointsResponse = match response.json().await {
+    let data: EndpointsResponse = match telemetry::read_json(SOURCE, response).await {
         Ok(d) => d,
         Err(e) => {
             eprintln!("[tokscale] endpoints JSON parse failed for {}: {}", model_id, e);
@@ -143,17 +172,21 @@ async fn fetch_author_pricing(
         cache_creation_input_token_cost: author_endpoint.pricing.input_cache_write
             .as_ref()
             .and_then(|s| parse_price(s)),
+        max_input_tokens: author_endpoint.context_length.filter(|&n| n > 0),
+        ..Default::default()
     };
     
     Some((model_id, pricing))
 }
 
+#[cfg(not(feature = "network"))]
+async fn fetch_all_models() -> HashMap<String, ModelPricing> {
+    HashMap::new()
+}
+
 /// Fetch all models and get author pricing for each
-pub async fn fetch_all_models() -> HashMap<String, ModelPricing> {
-    if let Some(cached) = load_cached() {
-        return cached;
-    }
-    
+#[cfg(feature = "network")]
+async fn fetch_all_models() -> HashMap<String, ModelPricing> {
     let client = Arc::new(reqwest::Client::builder()
         .timeout(std::time::Duration::from_secs(30))
         .connect_timeout(std::time::Duration::from_secs(10))
@@ -165,21 +198,22 @@ pub async fn fetch_all_models() -> HashMap<String, ModelPricing> {
     // First, get the list of all models
     let model_ids: Vec<String> = 'retry: {
         for attempt in 0..MAX_RETRIES {
-            let response = match client.get(MODELS_URL)
-                .header("Content-Type", "application/json")
-                .send()
-                .await {
-                    Ok(r) => r,
-                    Err(e) => {
-                        last_error = Some(format!("network error: {}", e));
-                        if attempt < MAX_RETRIES - 1 {
-                            tokio::time::sleep(std::time::Duration::from_millis(
-                                INITIAL_BACKOFF_MS * (1 << attempt)
-                            )).await;
-                        }
-                        continue;
+            if attempt > 0 {
+                telemetry::record(SOURCE, |t| t.retries += 1);
+            }
+            let request = client.get(MODELS_URL).header("Content-Type", "application/json");
+            let response = match telemetry::send(SOURCE, request).await {
+                Ok(r) => r,
+                Err(e) => {
+                    last_error = Some(format!("network error: {}", e));
+                    if attempt < MAX_RETRIES - 1 {
+                        tokio::time::sleep(std::time::Duration::from_millis(
+                            INITIAL_BACKOFF_MS * (1 << attempt)
+                        )).await;
                     }
-                };
+                    continue;
+                }
+            };
             
             let status = response.status();
             if status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS {
@@ -198,7 +232,7 @@ pub async fn fetch_all_models() -> HashMap<String, ModelPricing> {
                 break 'retry Vec::new();
             }
             
-            let data: ModelsListResponse = match response.json().await {
+            let data: ModelsListResponse = match telemetry::read_json(SOURCE, response).await {
                 Ok(d) => d,
                 Err(e) => {
                     eprintln!("[tokscale] OpenRouter models JSON parse failed: {}", e);
@@ -249,13 +283,5 @@ pub async fn fetch_all_models() -> HashMap<String, ModelPricing> {
         }
     }
     
-    if !result.is_empty() {
-        let _ = cache::save_cache(CACHE_FILENAME, &result);
-    }
-    
     result
 }
-
-pub async fn fetch_all_mapped() -> HashMap<String, ModelPricing> {
-    fetch_all_models().await
-}
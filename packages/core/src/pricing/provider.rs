@@ -0,0 +1,188 @@
+//! Pricing providers
+//!
+//! A provider fetches one price table. LiteLLM and OpenRouter are built in;
+//! more can be registered when a [`PricingService`](super::PricingService) is
+//! constructed, e.g. a company's internal rate card service. Prices from
+//! registered providers take precedence over the built-in ones.
+
+use super::cache;
+use super::telemetry;
+use super::litellm::PricingDataset;
+use futures::future::BoxFuture;
+use std::path::Path;
+use std::time::SystemTime;
+
+pub trait PricingProvider: Send + Sync {
+    /// Short identifier ("litellm"); also accepted as a forced lookup source
+    fn name(&self) -> &str;
+
+    /// File the fetched table is cached under, or `None` to fetch every time
+    fn cache_key(&self) -> Option<String> {
+        Some(format!("pricing-{}.json", cache_safe(self.name())))
+    }
+
+    /// Whether pricing is unavailable without this table. Optional providers
+    /// that fail are skipped with a warning.
+    fn required(&self) -> bool {
+        false
+    }
+
+    fn fetch(&self) -> BoxFuture<'_, Result<PricingDataset, String>>;
+}
+
+/// Cached table if [usable](cached), otherwise a fetched one (cached when non-empty),
+/// with when it was fetched in seconds since the epoch. When the fetch fails,
+/// the cached table is used at any age; its fetch time makes reports flag
+/// it as stale.
+pub(super) async fn load(provider: &dyn PricingProvider) -> Result<(PricingDataset, u64), String> {
+    let key = provider.cache_key();
+    if let Some(key) = key.as_deref() {
+        let cached = cached(key);
+        let hit = cached.is_some();
+        telemetry::record(provider.name(), |t| if hit { t.cache_hits += 1 } else { t.cache_misses += 1 });
+        if let Some(cached) = cached {
+            return Ok((cached.data, cached.timestamp));
+        }
+    }
+
+    let start = std::time::Instant::now();
+    let fetched = provider.fetch().await;
+    telemetry::record(provider.name(), |t| {
+        t.fetches += 1;
+        t.failures += fetched.is_err() as u64;
+        t.fetch_time += start.elapsed();
+    });
+    let data = match fetched {
+        Ok(data) => data,
+        Err(e) => {
+            let stale = key.as_deref().and_then(cache::load_stale_cache_entry::<PricingDataset>);
+            return or_stale(provider.name(), e, stale);
+        }
+    };
+    if let Some(key) = key.filter(|_| !data.is_empty()) {
+        let _ = cache::save_cache(&key, &data);
+    }
+    if super::SOURCES.contains(&provider.name()) {
+        super::history::archive(provider.name(), &data);
+    }
+    let now = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    Ok((data, now))
+}
+
+/// The table cached under `key` if it can be used without fetching. Builds
+/// without the `network` feature can't fetch, so any age will do.
+pub(super) fn cached(key: &str) -> Option<cache::CachedData<PricingDataset>> {
+    cached_in(&cache::get_cache_dir(), key)
+}
+
+fn cached_in(dir: &Path, key: &str) -> Option<cache::CachedData<PricingDataset>> {
+    let path = dir.join(key);
+    if cfg!(feature = "network") {
+        cache::read_fresh_cache_file(&path)
+    } else {
+        cache::read_cache_file(&path)
+    }
+}
+
+fn or_stale(
+    name: &str,
+    error: String,
+    stale: Option<cache::CachedData<PricingDataset>>,
+) -> Result<(PricingDataset, u64), String> {
+    match stale {
+        Some(cached) if !cached.data.is_empty() => {
+            eprintln!("[tokscale] {} unavailable ({}); using the prices cached before", name, error);
+            Ok((cached.data, cached.timestamp))
+        }
+        _ => Err(error),
+    }
+}
+
+fn cache_safe(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c.to_ascii_lowercase() } else { '_' })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pricing::ModelPricing;
+    use futures::executor::block_on;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct RateCard {
+        fetches: AtomicUsize,
+    }
+
+    impl PricingProvider for RateCard {
+        fn name(&self) -> &str {
+            "Internal Rate/Card"
+        }
+
+        fn fetch(&self) -> BoxFuture<'_, Result<PricingDataset, String>> {
+            self.fetches.fetch_add(1, Ordering::SeqCst);
+            Box::pin(async {
+                let price = ModelPricing { input_cost_per_token: Some(0.000001), ..Default::default() };
+                Ok(PricingDataset::from([("claude-sonnet-4".to_string(), price)]))
+            })
+        }
+    }
+
+    #[test]
+    fn test_default_cache_key() {
+        let provider = RateCard { fetches: AtomicUsize::new(0) };
+        assert_eq!(provider.cache_key().as_deref(), Some("pricing-internal_rate_card.json"));
+        assert!(!provider.required());
+    }
+
+    struct Uncached(RateCard);
+
+    impl PricingProvider for Uncached {
+        fn name(&self) -> &str {
+            self.0.name()
+        }
+
+        fn cache_key(&self) -> Option<String> {
+            None
+        }
+
+        fn fetch(&self) -> BoxFuture<'_, Result<PricingDataset, String>> {
+            self.0.fetch()
+        }
+    }
+
+    #[test]
+    fn test_load_without_cache_fetches_each_time() {
+        let provider = Uncached(RateCard { fetches: AtomicUsize::new(0) });
+        let (data, fetched_at) = block_on(load(&provider)).unwrap();
+        block_on(load(&provider)).unwrap();
+        assert_eq!(data["claude-sonnet-4"].input_cost_per_token, Some(0.000001));
+        assert_eq!(provider.0.fetches.load(Ordering::SeqCst), 2);
+        assert!(fetched_at > 0);
+    }
+
+    #[test]
+    fn test_failed_fetch_falls_back_to_stale_cache() {
+        let price = ModelPricing { input_cost_per_token: Some(0.000001), ..Default::default() };
+        let cached = cache::CachedData { timestamp: 1_740_787_200, data: PricingDataset::from([("gpt-5".to_string(), price)]) };
+        let (data, fetched_at) = or_stale("litellm", "HTTP 503".to_string(), Some(cached)).unwrap();
+        assert_eq!((data.len(), fetched_at), (1, 1_740_787_200));
+
+        let empty = cache::CachedData { timestamp: 1_740_787_200, data: PricingDataset::new() };
+        assert_eq!(or_stale("litellm", "HTTP 503".to_string(), Some(empty)).unwrap_err(), "HTTP 503");
+        assert!(or_stale("litellm", "HTTP 503".to_string(), None).is_err());
+    }
+
+    #[test]
+    fn test_offline_build_uses_cache_at_any_age() {
+        let dir = tempfile::tempdir().unwrap();
+        let price = ModelPricing { input_cost_per_token: Some(0.000001), ..Default::default() };
+        let cached = cache::CachedData { timestamp: 1_740_787_200, data: PricingDataset::from([("gpt-5".to_string(), price)]) };
+        std::fs::write(dir.path().join("pricing-litellm.json"), serde_json::to_vec(&cached).unwrap()).unwrap();
+
+        let found = cached_in(dir.path(), "pricing-litellm.json");
+        assert_eq!(found.map(|cached| cached.timestamp), (!cfg!(feature = "network")).then_some(1_740_787_200));
+        assert!(cached_in(dir.path(), "pricing-openrouter.json").is_none());
+    }
+}
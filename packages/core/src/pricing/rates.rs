@@ -0,0 +1,138 @@
+//! Per-million-token rates
+//!
+//! Price tables store USD per token, but people compare models in USD per
+//! million tokens. The conversion and the blended input/output rate live here
+//! so every frontend shows the same numbers.
+
+use super::litellm::ModelPricing;
+
+pub const TOKENS_PER_MILLION: f64 = 1_000_000.0;
+
+/// Share of input tokens assumed when blending, the 3:1 input:output mix
+/// most comparison sites use
+pub const DEFAULT_INPUT_RATIO: f64 = 0.75;
+
+fn per_million(cost_per_token: Option<f64>) -> Option<f64> {
+    cost_per_token.map(|cost| cost * TOKENS_PER_MILLION)
+}
+
+impl ModelPricing {
+    pub fn input_per_million(&self) -> Option<f64> {
+        per_million(self.input_cost_per_token)
+    }
+
+    pub fn output_per_million(&self) -> Option<f64> {
+        per_million(self.output_cost_per_token)
+    }
+
+    pub fn cache_read_per_million(&self) -> Option<f64> {
+        per_million(self.cache_read_input_token_cost)
+    }
+
+    pub fn cache_write_per_million(&self) -> Option<f64> {
+        per_million(self.cache_creation_input_token_cost)
+    }
+
+    /// USD per million tokens when `input_ratio` (clamped to 0..=1) of them
+    /// are input and the rest output. A missing output price counts as free,
+    /// as for embedding models; a missing input price gives `None`.
+    pub fn blended_rate(&self, input_ratio: f64) -> Option<f64> {
+        let ratio = if input_ratio.is_nan() { DEFAULT_INPUT_RATIO } else { input_ratio.clamp(0.0, 1.0) };
+        let input = self.input_per_million()?;
+        let output = self.output_per_million().unwrap_or(0.0);
+        Some(input * ratio + output * (1.0 - ratio))
+    }
+}
+
+/// "$3.00 / 1M tokens"; sub-cent rates keep enough digits to tell apart
+pub fn format_per_million(rate: f64) -> String {
+    let digits = if rate != 0.0 && rate.abs() < 0.01 { 4 } else { 2 };
+    format!("${:.*} / 1M tokens", digits, rate)
+}
+
+/// One model's line in a blended-rate comparison
+#[derive(Debug, Clone, PartialEq)]
+pub struct RateComparison {
+    pub model: String,
+    /// USD per million blended tokens
+    pub blended: f64,
+    /// `blended` over the cheapest model's; 1.0 for the cheapest
+    pub relative: f64,
+}
+
+/// Compare `models` at the same input ratio, cheapest first. Models without
+/// an input price are left out.
+pub fn compare(models: &[(String, ModelPricing)], input_ratio: f64) -> Vec<RateComparison> {
+    let mut rows: Vec<RateComparison> = models
+        .iter()
+        .filter_map(|(model, pricing)| {
+            pricing.blended_rate(input_ratio).map(|blended| RateComparison { model: model.clone(), blended, relative: 1.0 })
+        })
+        .collect();
+    rows.sort_by(|a, b| a.blended.total_cmp(&b.blended).then_with(|| a.model.cmp(&b.model)));
+
+    let cheapest = rows.first().map(|row| row.blended).unwrap_or(0.0);
+    for row in &mut rows {
+        row.relative = if cheapest > 0.0 { row.blended / cheapest } else if row.blended > 0.0 { f64::INFINITY } else { 1.0 };
+    }
+    rows
+}
+
+/// Aligned text table of a comparison, one model per line
+pub fn format_comparison(rows: &[RateComparison]) -> String {
+    let width = rows.iter().map(|row| row.model.chars().count()).max().unwrap_or(0);
+    rows.iter()
+        .map(|row| {
+            let relative = if row.relative.is_finite() { format!("{:.2}x", row.relative) } else { "-".to_string() };
+            format!("{:<width$}  {}  {}", row.model, format_per_million(row.blended), relative, width = width)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pricing(input: f64, output: Option<f64>) -> ModelPricing {
+        ModelPricing { input_cost_per_token: Some(input), output_cost_per_token: output, ..Default::default() }
+    }
+
+    #[test]
+    fn test_per_million_and_blended() {
+        let sonnet = pricing(3e-6, Some(1.5e-5));
+        assert!((sonnet.input_per_million().unwrap() - 3.0).abs() < 1e-9);
+        assert!((sonnet.output_per_million().unwrap() - 15.0).abs() < 1e-9);
+        assert_eq!(sonnet.cache_read_per_million(), None);
+        // 0.75 * 3 + 0.25 * 15
+        assert!((sonnet.blended_rate(DEFAULT_INPUT_RATIO).unwrap() - 6.0).abs() < 1e-9);
+        assert!((sonnet.blended_rate(2.0).unwrap() - 3.0).abs() < 1e-9);
+
+        let embedding = pricing(2e-8, None);
+        assert!((embedding.blended_rate(0.5).unwrap() - 0.01).abs() < 1e-9);
+        assert_eq!(ModelPricing::default().blended_rate(0.5), None);
+    }
+
+    #[test]
+    fn test_format_per_million() {
+        assert_eq!(format_per_million(3.0), "$3.00 / 1M tokens");
+        assert_eq!(format_per_million(0.0), "$0.00 / 1M tokens");
+        assert_eq!(format_per_million(0.002), "$0.0020 / 1M tokens");
+    }
+
+    #[test]
+    fn test_compare_cheapest_first() {
+        let models = vec![
+            ("claude-sonnet-4".to_string(), pricing(3e-6, Some(1.5e-5))),
+            ("unpriced".to_string(), ModelPricing::default()),
+            ("gpt-4o-mini".to_string(), pricing(1.5e-7, Some(6e-7))),
+        ];
+        let rows = compare(&models, DEFAULT_INPUT_RATIO);
+        assert_eq!(rows.iter().map(|r| r.model.as_str()).collect::<Vec<_>>(), ["gpt-4o-mini", "claude-sonnet-4"]);
+        assert_eq!(rows[0].relative, 1.0);
+        assert!((rows[1].relative - 6.0 / 0.2625).abs() < 1e-6);
+
+        let table = format_comparison(&rows);
+        assert_eq!(table.lines().next().unwrap(), "gpt-4o-mini      $0.26 / 1M tokens  1.00x");
+    }
+}
@@ -0,0 +1,159 @@
+//! Pricing fetch telemetry
+//!
+//! Startup is slow when prices have to be fetched, and it is rarely obvious
+//! why: a cold cache, a slow network, retries, or decoding a large table.
+//! Each price source counts its cache hits and misses, fetches, HTTP requests
+//! and retries, and how long it spent waiting on the network versus decoding
+//! JSON, for the life of the process.
+//!
+//! With `TOKSCALE_VERBOSE` set (`tokscale --verbose`), a line per source is
+//! printed to stderr once prices are loaded.
+
+use once_cell::sync::Lazy;
+#[cfg(feature = "network")]
+use serde::de::DeserializeOwned;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::Duration;
+#[cfg(feature = "network")]
+use std::time::Instant;
+
+pub const VERBOSE_ENV: &str = "TOKSCALE_VERBOSE";
+
+/// Counters for one price source
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SourceTelemetry {
+    pub cache_hits: u64,
+    pub cache_misses: u64,
+    /// Tables fetched (or attempted) because the cache had none
+    pub fetches: u64,
+    pub failures: u64,
+    pub requests: u64,
+    pub retries: u64,
+    pub bytes: u64,
+    /// Wall-clock time of fetches
+    pub fetch_time: Duration,
+    /// Waiting on responses, summed over requests. OpenRouter's run
+    /// concurrently, so this can exceed `fetch_time`.
+    pub network_time: Duration,
+    /// Decoding JSON, summed over responses
+    pub parse_time: Duration,
+}
+
+static TELEMETRY: Lazy<Mutex<BTreeMap<String, SourceTelemetry>>> = Lazy::new(|| Mutex::new(BTreeMap::new()));
+
+pub fn record(source: &str, update: impl FnOnce(&mut SourceTelemetry)) {
+    let mut telemetry = TELEMETRY.lock().unwrap_or_else(|e| e.into_inner());
+    update(telemetry.entry(source.to_string()).or_default());
+}
+
+/// Counters so far, by source name
+pub fn snapshot() -> Vec<(String, SourceTelemetry)> {
+    let telemetry = TELEMETRY.lock().unwrap_or_else(|e| e.into_inner());
+    telemetry.iter().map(|(source, t)| (source.clone(), t.clone())).collect()
+}
+
+/// Send `request`, counting it and the wait for the response headers
+#[cfg(feature = "network")]
+pub async fn send(source: &str, request: reqwest::RequestBuilder) -> Result<reqwest::Response, reqwest::Error> {
+    let start = Instant::now();
+    let response = request.send().await;
+    record(source, |t| {
+        t.requests += 1;
+        t.network_time += start.elapsed();
+    });
+    response
+}
+
+/// Read and decode a JSON body, timing the download apart from decoding
+#[cfg(feature = "network")]
+pub async fn read_json<T: DeserializeOwned>(source: &str, response: reqwest::Response) -> Result<T, String> {
+    let start = Instant::now();
+    let body = response.bytes().await;
+    let downloaded = start.elapsed();
+    record(source, |t| {
+        t.network_time += downloaded;
+        t.bytes += body.as_ref().map_or(0, |b| b.len() as u64);
+    });
+    let body = body.map_err(|e| e.to_string())?;
+
+    let start = Instant::now();
+    let parsed = serde_json::from_slice(&body).map_err(|e| e.to_string());
+    record(source, |t| t.parse_time += start.elapsed());
+    parsed
+}
+
+pub fn verbose() -> bool {
+    std::env::var(VERBOSE_ENV).is_ok_and(|v| !v.is_empty() && v != "0" && !v.eq_ignore_ascii_case("false"))
+}
+
+fn ms(duration: Duration) -> u128 {
+    duration.as_millis()
+}
+
+/// One line per source, e.g. "litellm: cache miss, 1 fetch, 2 requests
+/// (1 retry), 1.2 MB in 840 ms (network 790 ms, parse 45 ms)"
+pub fn summary_lines() -> Vec<String> {
+    snapshot()
+        .into_iter()
+        .map(|(source, t)| {
+            let cache = match (t.cache_hits, t.cache_misses) {
+                (0, 0) => "not cached".to_string(),
+                (hits, 0) => format!("cache hit{}", if hits > 1 { format!(" x{}", hits) } else { String::new() }),
+                (hits, misses) => format!("{} cache hit(s), {} miss(es)", hits, misses),
+            };
+            if t.fetches == 0 {
+                return format!("{}: {}", source, cache);
+            }
+            format!(
+                "{}: {}, {} fetch(es){}, {} request(s) ({} retries), {:.1} MB in {} ms (network {} ms, parse {} ms)",
+                source,
+                cache,
+                t.fetches,
+                if t.failures > 0 { format!(" ({} failed)", t.failures) } else { String::new() },
+                t.requests,
+                t.retries,
+                t.bytes as f64 / 1_000_000.0,
+                ms(t.fetch_time),
+                ms(t.network_time),
+                ms(t.parse_time),
+            )
+        })
+        .collect()
+}
+
+/// Print [`summary_lines`] to stderr when verbose
+pub fn log_if_verbose() {
+    if verbose() {
+        for line in summary_lines() {
+            eprintln!("[tokscale] pricing {}", line);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counters_accumulate_per_source() {
+        let source = "telemetry-test";
+        record(source, |t| t.cache_misses += 1);
+        record(source, |t| {
+            t.fetches += 1;
+            t.requests += 2;
+            t.retries += 1;
+            t.bytes = 1_500_000;
+            t.fetch_time = Duration::from_millis(840);
+        });
+
+        let (_, counters) = snapshot().into_iter().find(|(name, _)| name == source).unwrap();
+        assert_eq!((counters.cache_misses, counters.requests, counters.retries), (1, 2, 1));
+        let line = summary_lines().into_iter().find(|line| line.starts_with(source)).unwrap();
+        assert_eq!(
+            line,
+            "telemetry-test: 0 cache hit(s), 1 miss(es), 1 fetch(es), 2 request(s) (1 retries), 1.5 MB in 840 ms \
+             (network 0 ms, parse 0 ms)"
+        );
+    }
+}
@@ -0,0 +1,151 @@
+//! Time-of-day pricing
+//!
+//! Some providers charge less at certain hours: DeepSeek's API billed
+//! 50% (V3) or 25% (R1) of list price between 16:30 and 00:30 UTC until
+//! 2025-09-05. A [`PriceWindow`] scales a model's rates for messages sent
+//! within its hours. Windows come with a price table entry
+//! ([`ModelPricing::time_windows`](super::ModelPricing)), so registered
+//! providers can ship their own; DeepSeek's are built in, because neither
+//! LiteLLM nor OpenRouter lists them.
+
+use serde::{Deserialize, Serialize};
+
+const MINUTES_PER_DAY: i64 = 24 * 60;
+
+/// Hours of the day at which a model's rates are scaled
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PriceWindow {
+    /// Minutes after midnight UTC the window opens
+    pub start_minute: u32,
+    /// Minutes after midnight UTC it closes (exclusive); before `start_minute`
+    /// for windows spanning midnight
+    pub end_minute: u32,
+    /// Applied to every rate inside the window
+    pub multiplier: f64,
+    /// Unix milliseconds from which the window no longer applies
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub until: Option<i64>,
+}
+
+impl PriceWindow {
+    pub fn contains(&self, timestamp_ms: i64) -> bool {
+        if self.until.is_some_and(|until| timestamp_ms >= until) {
+            return false;
+        }
+        let minute = timestamp_ms.div_euclid(60_000).rem_euclid(MINUTES_PER_DAY) as u32;
+        if self.start_minute <= self.end_minute {
+            (self.start_minute..self.end_minute).contains(&minute)
+        } else {
+            minute >= self.start_minute || minute < self.end_minute
+        }
+    }
+}
+
+/// Multiplier for a message sent at `timestamp_ms`: the first window that
+/// contains it, or 1
+pub fn multiplier_at(windows: &[PriceWindow], timestamp_ms: i64) -> f64 {
+    windows
+        .iter()
+        .find(|window| window.contains(timestamp_ms))
+        .map(|window| window.multiplier)
+        .filter(|m| m.is_finite() && *m >= 0.0)
+        .unwrap_or(1.0)
+}
+
+/// 2025-09-05T16:00:00Z, when DeepSeek ended off-peak discounts
+const DEEPSEEK_OFF_PEAK_UNTIL: i64 = 1_757_088_000_000;
+
+fn deepseek_off_peak(multiplier: f64) -> Vec<PriceWindow> {
+    vec![PriceWindow {
+        start_minute: 16 * 60 + 30,
+        end_minute: 30,
+        multiplier,
+        until: Some(DEEPSEEK_OFF_PEAK_UNTIL),
+    }]
+}
+
+/// Built-in windows for a price key matched in `source`'s table. Only
+/// DeepSeek's own API had them, so they apply when `provider` is DeepSeek,
+/// or, without a provider, when the key prices DeepSeek's API rather than a
+/// reseller's listing (resellers don't pass provider discounts on).
+pub fn builtin(matched_key: &str, source: &str, provider: Option<&str>) -> Vec<PriceWindow> {
+    let official = match provider {
+        Some(provider) => provider.eq_ignore_ascii_case("deepseek"),
+        None => is_deepseek_api_key(matched_key, source),
+    };
+    if !official || !matched_key.to_lowercase().contains("deepseek") {
+        return Vec::new();
+    }
+    let model = matched_key.rsplit('/').next().unwrap_or(matched_key).to_lowercase();
+    if model.contains("reasoner") || model.contains("r1") {
+        deepseek_off_peak(0.25)
+    } else if model.contains("chat") || model.contains("v3") {
+        deepseek_off_peak(0.5)
+    } else {
+        Vec::new()
+    }
+}
+
+/// Whether `key` is LiteLLM's entry for DeepSeek's API (`deepseek-chat`,
+/// `deepseek/deepseek-chat`), not a reseller's (`openrouter/deepseek/...`,
+/// `together_ai/deepseek-ai/...`). OpenRouter's own table lists its prices
+/// under `deepseek/...` too, so nothing from it counts.
+fn is_deepseek_api_key(key: &str, source: &str) -> bool {
+    if source.eq_ignore_ascii_case("openrouter") {
+        return false;
+    }
+    let key = key.to_lowercase();
+    let model = key.strip_prefix("deepseek/").unwrap_or(&key);
+    model.starts_with("deepseek-") && !model.contains('/')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 2025-06-01T00:00:00Z
+    const DAY: i64 = 1_748_736_000_000;
+    const MINUTE: i64 = 60_000;
+
+    #[test]
+    fn test_window_spanning_midnight() {
+        let windows = deepseek_off_peak(0.5);
+        assert_eq!(multiplier_at(&windows, DAY + (16 * 60 + 29) * MINUTE), 1.0);
+        assert_eq!(multiplier_at(&windows, DAY + (16 * 60 + 30) * MINUTE), 0.5);
+        assert_eq!(multiplier_at(&windows, DAY + 23 * 60 * MINUTE), 0.5);
+        assert_eq!(multiplier_at(&windows, DAY + 29 * MINUTE), 0.5);
+        assert_eq!(multiplier_at(&windows, DAY + 30 * MINUTE), 1.0);
+        // Discounts ended
+        assert_eq!(multiplier_at(&windows, DEEPSEEK_OFF_PEAK_UNTIL + 4 * 60 * MINUTE), 1.0);
+    }
+
+    #[test]
+    fn test_window_within_day() {
+        let window = PriceWindow { start_minute: 60, end_minute: 120, multiplier: 0.8, until: None };
+        assert!(!window.contains(DAY + 59 * MINUTE));
+        assert!(window.contains(DAY + 60 * MINUTE));
+        assert!(!window.contains(DAY + 120 * MINUTE));
+        assert!(window.contains(DAY - MINUTES_PER_DAY * MINUTE + 90 * MINUTE));
+    }
+
+    #[test]
+    fn test_builtin() {
+        assert_eq!(builtin("deepseek/deepseek-chat", "LiteLLM", None)[0].multiplier, 0.5);
+        assert_eq!(builtin("deepseek-chat", "LiteLLM", None)[0].multiplier, 0.5);
+        assert_eq!(builtin("deepseek/deepseek-reasoner", "LiteLLM", Some("DeepSeek"))[0].multiplier, 0.25);
+        assert_eq!(builtin("deepseek/deepseek-chat", "OpenRouter", Some("deepseek"))[0].multiplier, 0.5);
+        assert!(builtin("deepseek/deepseek-chat", "LiteLLM", Some("openrouter")).is_empty());
+        assert!(builtin("gpt-4o", "LiteLLM", None).is_empty());
+        assert!(builtin("gpt-4o", "LiteLLM", Some("deepseek")).is_empty());
+    }
+
+    #[test]
+    fn test_builtin_skips_resellers_without_provider() {
+        assert!(builtin("deepseek/deepseek-chat", "OpenRouter", None).is_empty());
+        assert!(builtin("openrouter/deepseek/deepseek-chat", "LiteLLM", None).is_empty());
+        assert!(builtin("together_ai/deepseek-ai/DeepSeek-V3", "LiteLLM", None).is_empty());
+        assert!(builtin("deepseek-ai/DeepSeek-R1", "LiteLLM", None).is_empty());
+        assert!(builtin("fireworks_ai/accounts/fireworks/models/deepseek-v3", "LiteLLM", None).is_empty());
+    }
+}
@@ -0,0 +1,135 @@
+//! xAI's own price list
+//!
+//! `api.x.ai/v1/language-models` lists every Grok model the key can use,
+//! with its aliases and prices. It is registered as a pricing provider when
+//! an "xai" API key is stored (`tokscale secrets set xai`), so new Grok
+//! models are priced the day they ship instead of when LiteLLM adds them.
+
+#[cfg(any(feature = "network", test))]
+use super::litellm::ModelPricing;
+use super::litellm::PricingDataset;
+use super::provider::PricingProvider;
+use futures::future::BoxFuture;
+#[cfg(any(feature = "network", test))]
+use serde::Deserialize;
+
+const SOURCE: &str = "xai";
+/// Secret name of the xAI API key
+pub const SECRET: &str = "xai";
+#[cfg(feature = "network")]
+const MODELS_URL: &str = "https://api.x.ai/v1/language-models";
+
+/// Listed prices are in US cents per 100 million tokens
+#[cfg(any(feature = "network", test))]
+const PRICE_UNITS_PER_USD_TOKEN: f64 = 100.0 * 100_000_000.0;
+
+#[cfg(any(feature = "network", test))]
+#[derive(Debug, Deserialize)]
+struct LanguageModel {
+    id: String,
+    #[serde(default)]
+    aliases: Vec<String>,
+    #[serde(default)]
+    prompt_text_token_price: Option<f64>,
+    #[serde(default)]
+    cached_prompt_text_token_price: Option<f64>,
+    #[serde(default)]
+    completion_text_token_price: Option<f64>,
+}
+
+#[cfg(any(feature = "network", test))]
+#[derive(Debug, Deserialize)]
+struct LanguageModelsResponse {
+    models: Vec<LanguageModel>,
+}
+
+/// Prices from xAI's API, for the key it was created with
+pub struct XAi {
+    api_key: String,
+}
+
+impl XAi {
+    /// The provider, if an xAI API key is stored
+    pub fn from_secret() -> Option<Self> {
+        crate::secrets::resolve(SECRET).map(|(api_key, _)| Self { api_key })
+    }
+}
+
+impl PricingProvider for XAi {
+    fn name(&self) -> &str {
+        SOURCE
+    }
+
+    fn fetch(&self) -> BoxFuture<'_, Result<PricingDataset, String>> {
+        Box::pin(fetch(&self.api_key))
+    }
+}
+
+/// Table keyed by each model id and alias, bare and under `xai/`
+#[cfg(any(feature = "network", test))]
+fn to_dataset(response: LanguageModelsResponse) -> PricingDataset {
+    let per_token = |price: Option<f64>| price.filter(|p| p.is_finite() && *p >= 0.0).map(|p| p / PRICE_UNITS_PER_USD_TOKEN);
+    let mut dataset = PricingDataset::new();
+    for model in response.models {
+        let pricing = ModelPricing {
+            input_cost_per_token: per_token(model.prompt_text_token_price),
+            output_cost_per_token: per_token(model.completion_text_token_price),
+            cache_read_input_token_cost: per_token(model.cached_prompt_text_token_price),
+            mode: Some("chat".to_string()),
+            ..Default::default()
+        };
+        if pricing.input_cost_per_token.is_none() {
+            continue;
+        }
+        for name in std::iter::once(&model.id).chain(&model.aliases) {
+            dataset.insert(format!("xai/{}", name), pricing.clone());
+            dataset.insert(name.clone(), pricing.clone());
+        }
+    }
+    dataset
+}
+
+#[cfg(not(feature = "network"))]
+async fn fetch(_api_key: &str) -> Result<PricingDataset, String> {
+    Err(super::OFFLINE.to_string())
+}
+
+#[cfg(feature = "network")]
+async fn fetch(api_key: &str) -> Result<PricingDataset, String> {
+    let client = reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(20))
+        .build()
+        .map_err(|e| e.to_string())?;
+    let response = super::telemetry::send(SOURCE, client.get(MODELS_URL).bearer_auth(api_key))
+        .await
+        .map_err(|e| e.to_string())?;
+    if !response.status().is_success() {
+        return Err(format!("HTTP {}", response.status()));
+    }
+    let data: LanguageModelsResponse = super::telemetry::read_json(SOURCE, response).await?;
+    Ok(to_dataset(data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_dataset() {
+        let response: LanguageModelsResponse = serde_json::from_str(
+            r#"{"models": [
+                {"id": "grok-4-0709", "aliases": ["grok-4", "grok-4-latest"], "prompt_text_token_price": 30000,
+                 "cached_prompt_text_token_price": 7500, "completion_text_token_price": 150000, "owned_by": "xai"},
+                {"id": "grok-2-image-1212", "aliases": [], "image_price": 700000000}
+            ]}"#,
+        )
+        .unwrap();
+        let dataset = to_dataset(response);
+        assert_eq!(dataset.len(), 6);
+        let grok = &dataset["xai/grok-4-latest"];
+        assert!((grok.input_per_million().unwrap() - 3.0).abs() < 1e-9);
+        assert!((grok.output_per_million().unwrap() - 15.0).abs() < 1e-9);
+        assert!((grok.cache_read_per_million().unwrap() - 0.75).abs() < 1e-9);
+        assert!(!dataset.contains_key("grok-2-image-1212"));
+    }
+}
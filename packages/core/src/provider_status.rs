@@ -0,0 +1,234 @@
+//! Provider status
+//!
+//! When prices can't be fetched, the cause is either on this machine (no
+//! network, a proxy, a firewall) or at the other end. Anthropic, OpenAI and
+//! GitHub (which serves the LiteLLM price table) publish Statuspage status
+//! APIs; OpenRouter's own API is probed instead. Reading them lets `tokscale
+//! doctor` and `tokscale serve` webhooks say which of the two it is.
+//!
+//! Nothing here runs unless asked: the doctor checks status pages when a
+//! fetch failed or with `--provider-status`, and the server only when pricing
+//! fails.
+
+use crate::ProviderStatus;
+#[cfg(any(feature = "network", test))]
+use serde::Deserialize;
+#[cfg(feature = "network")]
+use std::time::Duration;
+
+pub const OPERATIONAL: &str = "operational";
+pub const DEGRADED: &str = "degraded";
+pub const OUTAGE: &str = "outage";
+/// The status page itself could not be read
+pub const UNREACHABLE: &str = "unreachable";
+
+#[cfg(feature = "network")]
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[cfg(feature = "network")]
+enum Check {
+    /// A Statuspage `/api/v2/status.json`
+    Statuspage,
+    /// Any response other than a server error means up
+    Probe,
+}
+
+struct Page {
+    name: &'static str,
+    /// Pricing source whose table this provider serves
+    pricing_source: Option<&'static str>,
+    url: &'static str,
+    #[cfg(feature = "network")]
+    check: Check,
+}
+
+const PAGES: &[Page] = &[
+    Page {
+        name: "GitHub",
+        pricing_source: Some("litellm"),
+        url: "https://www.githubstatus.com/api/v2/status.json",
+        #[cfg(feature = "network")]
+        check: Check::Statuspage,
+    },
+    Page {
+        name: "OpenRouter",
+        pricing_source: Some("openrouter"),
+        url: "https://openrouter.ai/api/v1/models",
+        #[cfg(feature = "network")]
+        check: Check::Probe,
+    },
+    Page {
+        name: "Anthropic",
+        pricing_source: None,
+        url: "https://status.anthropic.com/api/v2/status.json",
+        #[cfg(feature = "network")]
+        check: Check::Statuspage,
+    },
+    Page {
+        name: "OpenAI",
+        pricing_source: None,
+        url: "https://status.openai.com/api/v2/status.json",
+        #[cfg(feature = "network")]
+        check: Check::Statuspage,
+    },
+];
+
+#[cfg(any(feature = "network", test))]
+#[derive(Deserialize)]
+struct StatuspageBody {
+    status: StatuspageStatus,
+}
+
+#[cfg(any(feature = "network", test))]
+#[derive(Deserialize)]
+struct StatuspageStatus {
+    /// "none", "minor", "major", "critical" or "maintenance"
+    indicator: String,
+    description: String,
+}
+
+/// State and description from a Statuspage `status.json` body
+#[cfg(any(feature = "network", test))]
+fn parse_statuspage(body: &str) -> Result<(&'static str, String), String> {
+    let body: StatuspageBody = serde_json::from_str(body).map_err(|e| format!("unexpected response: {}", e))?;
+    let state = match body.status.indicator.as_str() {
+        "none" => OPERATIONAL,
+        "major" | "critical" => OUTAGE,
+        _ => DEGRADED,
+    };
+    Ok((state, body.status.description))
+}
+
+#[cfg(feature = "network")]
+async fn check_page(client: &reqwest::Client, page: &Page) -> ProviderStatus {
+    let result = match client.get(page.url).send().await {
+        Err(e) => Err(e.to_string()),
+        Ok(response) => match page.check {
+            Check::Probe if response.status().is_server_error() => {
+                Ok((OUTAGE, format!("API returned HTTP {}", response.status())))
+            }
+            Check::Probe => Ok((OPERATIONAL, "API is responding".to_string())),
+            Check::Statuspage => match response.error_for_status() {
+                Ok(response) => match response.text().await {
+                    Ok(body) => parse_statuspage(&body),
+                    Err(e) => Err(e.to_string()),
+                },
+                Err(e) => Err(e.to_string()),
+            },
+        },
+    };
+    let (state, description) = result.unwrap_or_else(|e| (UNREACHABLE, e));
+    ProviderStatus {
+        name: page.name.to_string(),
+        pricing_source: page.pricing_source.map(str::to_string),
+        url: page.url.to_string(),
+        state: state.to_string(),
+        description,
+    }
+}
+
+/// Every provider's current status, checked concurrently
+#[cfg(feature = "network")]
+pub async fn check() -> Vec<ProviderStatus> {
+    let client = reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .user_agent(concat!("tokscale/", env!("CARGO_PKG_VERSION")))
+        .build()
+        .unwrap_or_default();
+    futures::future::join_all(PAGES.iter().map(|page| check_page(&client, page))).await
+}
+
+#[cfg(not(feature = "network"))]
+pub async fn check() -> Vec<ProviderStatus> {
+    PAGES
+        .iter()
+        .map(|page| ProviderStatus {
+            name: page.name.to_string(),
+            pricing_source: page.pricing_source.map(str::to_string),
+            url: page.url.to_string(),
+            state: UNREACHABLE.to_string(),
+            description: "built without the network feature".to_string(),
+        })
+        .collect()
+}
+
+/// Why fetching `source`'s prices failed, going by the providers' status.
+/// With no `source`, any provider serving prices counts.
+pub fn diagnose(source: Option<&str>, statuses: &[ProviderStatus]) -> String {
+    let serving: Vec<&ProviderStatus> = statuses
+        .iter()
+        .filter(|s| s.pricing_source.is_some() && (source.is_none() || s.pricing_source.as_deref() == source))
+        .collect();
+    if let Some(down) = serving.iter().find(|s| s.state == OUTAGE || s.state == DEGRADED) {
+        return format!("{} reports a problem on their side: {}", down.name, down.description);
+    }
+    if !statuses.is_empty() && statuses.iter().all(|s| s.state == UNREACHABLE) {
+        return "No status page is reachable either; this machine looks offline or its proxy blocks requests"
+            .to_string();
+    }
+    if !serving.is_empty() && serving.iter().all(|s| s.state == OPERATIONAL) {
+        let names: Vec<&str> = serving.iter().map(|s| s.name.as_str()).collect();
+        return format!(
+            "{} reports no problems; the fetch likely failed on this side (network, proxy or firewall)",
+            names.join(" and ")
+        );
+    }
+    "Provider status is unknown".to_string()
+}
+
+/// Pricing source named by a pricing error ("litellm: ..."), if any
+pub fn failed_source(error: &str) -> Option<&'static str> {
+    crate::pricing::SOURCES
+        .iter()
+        .copied()
+        .find(|source| error.strip_prefix(source).is_some_and(|rest| rest.starts_with(':')))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(name: &str, source: Option<&str>, state: &str) -> ProviderStatus {
+        ProviderStatus {
+            name: name.to_string(),
+            pricing_source: source.map(str::to_string),
+            url: String::new(),
+            state: state.to_string(),
+            description: format!("{} is {}", name, state),
+        }
+    }
+
+    #[test]
+    fn test_parse_statuspage() {
+        let body = r#"{"page":{"id":"x","name":"GitHub"},"status":{"indicator":"major","description":"Partial System Outage"}}"#;
+        assert_eq!(parse_statuspage(body).unwrap(), (OUTAGE, "Partial System Outage".to_string()));
+        let body = r#"{"status":{"indicator":"none","description":"All Systems Operational"}}"#;
+        assert_eq!(parse_statuspage(body).unwrap().0, OPERATIONAL);
+        let body = r#"{"status":{"indicator":"maintenance","description":"Service Under Maintenance"}}"#;
+        assert_eq!(parse_statuspage(body).unwrap().0, DEGRADED);
+        assert!(parse_statuspage("<html>").is_err());
+    }
+
+    #[test]
+    fn test_diagnose() {
+        let statuses = vec![
+            status("GitHub", Some("litellm"), OPERATIONAL),
+            status("OpenRouter", Some("openrouter"), OUTAGE),
+            status("Anthropic", None, DEGRADED),
+        ];
+        assert!(diagnose(Some("openrouter"), &statuses).starts_with("OpenRouter reports a problem"));
+        assert!(diagnose(Some("litellm"), &statuses).contains("failed on this side"));
+        assert!(diagnose(None, &statuses).starts_with("OpenRouter"));
+
+        let offline = vec![status("GitHub", Some("litellm"), UNREACHABLE), status("Anthropic", None, UNREACHABLE)];
+        assert!(diagnose(Some("litellm"), &offline).contains("offline"));
+        assert_eq!(diagnose(Some("litellm"), &[]), "Provider status is unknown");
+    }
+
+    #[test]
+    fn test_failed_source() {
+        assert_eq!(failed_source("litellm: error sending request"), Some("litellm"));
+        assert_eq!(failed_source("openrouterx: nope"), None);
+        assert_eq!(failed_source("timeout"), None);
+    }
+}
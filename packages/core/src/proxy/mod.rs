@@ -0,0 +1,395 @@
+//! Metering proxy
+//!
+//! A local reverse proxy for the OpenAI and Anthropic APIs. Clients pointed
+//! at it (`OPENAI_BASE_URL=http://127.0.0.1:8787/v1`,
+//! `ANTHROPIC_BASE_URL=http://127.0.0.1:8787`) talk to the real API as before,
+//! while the usage reported in each response, streamed or not, is recorded
+//! through the [`Recorder`] and shows up as the "recorded" source.
+//!
+//! `/v1/messages` goes to the Anthropic upstream, every other `/v1/` path to
+//! the OpenAI one (or any OpenAI-compatible API). Streamed Chat Completions
+//! requests get `stream_options.include_usage` so the final chunk carries
+//! usage. Two optional request headers label what is recorded and are not
+//! forwarded: `x-tokscale-session` and `x-tokscale-agent`.
+
+mod usage;
+
+use crate::http;
+use crate::recorder::Recorder;
+use crate::response_usage::{ApiFormat, ResponseUsage};
+use crate::sessions::recorded::RecordedEvent;
+use serde_json::Value;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncWrite, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use usage::UsageMeter;
+
+pub const DEFAULT_PORT: u16 = 8787;
+pub const OPENAI_UPSTREAM: &str = "https://api.openai.com";
+pub const ANTHROPIC_UPSTREAM: &str = "https://api.anthropic.com";
+
+const SESSION_HEADER: &str = "x-tokscale-session";
+const AGENT_HEADER: &str = "x-tokscale-agent";
+
+/// Headers that describe one connection rather than the request, plus
+/// `accept-encoding` so responses arrive uncompressed and can be metered
+const NOT_FORWARDED: &[&str] = &[
+    "host",
+    "connection",
+    "keep-alive",
+    "proxy-connection",
+    "transfer-encoding",
+    "te",
+    "trailer",
+    "upgrade",
+    "content-length",
+    "accept-encoding",
+    SESSION_HEADER,
+    AGENT_HEADER,
+];
+
+#[derive(Debug, Clone)]
+pub struct ProxyConfig {
+    pub listen: SocketAddr,
+    pub openai_upstream: String,
+    pub anthropic_upstream: String,
+}
+
+impl Default for ProxyConfig {
+    fn default() -> Self {
+        Self {
+            listen: SocketAddr::from(([127, 0, 0, 1], DEFAULT_PORT)),
+            openai_upstream: OPENAI_UPSTREAM.to_string(),
+            anthropic_upstream: ANTHROPIC_UPSTREAM.to_string(),
+        }
+    }
+}
+
+pub struct Proxy {
+    listener: TcpListener,
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    config: ProxyConfig,
+    client: reqwest::Client,
+    recorder: Recorder,
+}
+
+impl Proxy {
+    pub async fn bind(config: ProxyConfig, recorder: Recorder) -> Result<Self, String> {
+        let listener = TcpListener::bind(config.listen)
+            .await
+            .map_err(|e| format!("Failed to listen on {}: {}", config.listen, e))?;
+        // No overall timeout: streamed responses can run for minutes
+        let client = reqwest::Client::builder()
+            .connect_timeout(Duration::from_secs(10))
+            .build()
+            .map_err(|e| e.to_string())?;
+        Ok(Self {
+            listener,
+            shared: Arc::new(Shared { config, client, recorder }),
+        })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr, String> {
+        self.listener.local_addr().map_err(|e| e.to_string())
+    }
+
+    /// Accept connections until the task is dropped
+    pub async fn serve(self) -> Result<(), String> {
+        loop {
+            let (stream, _) = self.listener.accept().await.map_err(|e| e.to_string())?;
+            let shared = Arc::clone(&self.shared);
+            tokio::spawn(async move { handle_connection(stream, &shared).await });
+        }
+    }
+}
+
+fn route(path: &str) -> Option<ApiFormat> {
+    if path == "/v1/messages" || path.starts_with("/v1/messages/") {
+        Some(ApiFormat::Anthropic)
+    } else if path.starts_with("/v1/") {
+        Some(ApiFormat::OpenAi)
+    } else {
+        None
+    }
+}
+
+async fn handle_connection(stream: TcpStream, shared: &Shared) {
+    let (read, mut write) = stream.into_split();
+    let mut reader = BufReader::new(read);
+
+    let request = match http::read_request(&mut reader).await {
+        Ok(Some(request)) => request,
+        Ok(None) => return,
+        Err(e) => {
+            let _ = write_error(&mut write, 400, "Bad Request", &e).await;
+            return;
+        }
+    };
+    let Some(format) = route(request.path()) else {
+        let message = format!("tokscale proxy only forwards /v1/ API paths, not {}", request.path());
+        let _ = write_error(&mut write, 404, "Not Found", &message).await;
+        return;
+    };
+
+    if let Err(e) = forward(request, format, shared, &mut write).await {
+        eprintln!("[tokscale] proxy: {}", e);
+    }
+}
+
+async fn forward<W: AsyncWrite + Unpin>(
+    request: http::Request,
+    format: ApiFormat,
+    shared: &Shared,
+    writer: &mut W,
+) -> Result<(), String> {
+    let base = match format {
+        ApiFormat::OpenAi => &shared.config.openai_upstream,
+        ApiFormat::Anthropic => &shared.config.anthropic_upstream,
+    };
+    let url = format!("{}{}", base.trim_end_matches('/'), request.target);
+    let mut body_json: Option<Value> = serde_json::from_slice(&request.body).ok();
+    let rewritten = format == ApiFormat::OpenAi
+        && body_json.as_mut().is_some_and(|json| request_stream_usage(request.path(), json));
+    let body = match &body_json {
+        Some(json) if rewritten => serde_json::to_vec(json).map_err(|e| e.to_string())?,
+        _ => request.body.clone(),
+    };
+
+    let method = reqwest::Method::from_bytes(request.method.as_bytes()).map_err(|e| e.to_string())?;
+    let is_head = method == reqwest::Method::HEAD;
+    let mut upstream = shared.client.request(method, &url).body(body);
+    for (name, value) in &request.headers {
+        if !NOT_FORWARDED.iter().any(|h| name.eq_ignore_ascii_case(h)) {
+            upstream = upstream.header(name.as_str(), value.as_str());
+        }
+    }
+
+    let mut response = match upstream.send().await {
+        Ok(response) => response,
+        Err(e) => {
+            let message = format!("Upstream request to {} failed: {}", url, e);
+            let _ = write_error(writer, 502, "Bad Gateway", &message).await;
+            return Err(message);
+        }
+    };
+
+    let status = response.status();
+    let content_type = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .map(|v| String::from_utf8_lossy(v.as_bytes()).into_owned());
+    let headers: Vec<(String, String)> = response
+        .headers()
+        .iter()
+        .filter(|(name, _)| !NOT_FORWARDED.contains(&name.as_str()))
+        .map(|(name, value)| (name.to_string(), String::from_utf8_lossy(value.as_bytes()).into_owned()))
+        .collect();
+    let has_body = !is_head && status != reqwest::StatusCode::NO_CONTENT && status != reqwest::StatusCode::NOT_MODIFIED;
+
+    http::write_head(writer, status.as_u16(), status.canonical_reason().unwrap_or(""), &headers, has_body)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let mut meter = status.is_success().then(|| UsageMeter::new(format, content_type.as_deref()));
+    while let Some(chunk) = response.chunk().await.map_err(|e| format!("Upstream stream failed: {}", e))? {
+        if let Some(meter) = meter.as_mut() {
+            meter.feed(&chunk);
+        }
+        // Client gone: stop reading, which also cancels the upstream request
+        if http::write_chunk(writer, &chunk).await.is_err() {
+            break;
+        }
+    }
+    if has_body {
+        let _ = http::finish_chunks(writer).await;
+    }
+
+    if let Some(usage) = meter.and_then(UsageMeter::finish) {
+        let model = body_json.as_ref().and_then(|b| b.get("model")).and_then(Value::as_str);
+        record(shared, &request, usage, model)?;
+    }
+    Ok(())
+}
+
+/// Ask for usage in streamed Chat Completions / Completions responses, which
+/// otherwise carry none. Returns whether the body was changed.
+fn request_stream_usage(path: &str, body: &mut Value) -> bool {
+    if !(path.ends_with("/chat/completions") || path.ends_with("/completions")) {
+        return false;
+    }
+    if body.get("stream").and_then(Value::as_bool) != Some(true) {
+        return false;
+    }
+    let Some(object) = body.as_object_mut() else {
+        return false;
+    };
+    let options = object
+        .entry("stream_options")
+        .or_insert_with(|| Value::Object(Default::default()));
+    match options.as_object_mut() {
+        Some(options) if !options.contains_key("include_usage") => {
+            options.insert("include_usage".to_string(), Value::Bool(true));
+            true
+        }
+        _ => false,
+    }
+}
+
+fn record(shared: &Shared, request: &http::Request, usage: ResponseUsage, request_model: Option<&str>) -> Result<(), String> {
+    let model = usage
+        .model
+        .or_else(|| request_model.map(str::to_string))
+        .unwrap_or_else(|| "unknown".to_string());
+    let tokens = usage.tokens;
+    eprintln!(
+        "[tokscale] proxy: {} {} in, {} out",
+        model,
+        tokens.input + tokens.cache_read + tokens.cache_write,
+        tokens.output + tokens.reasoning
+    );
+
+    let event = RecordedEvent {
+        id: usage.id,
+        timestamp: None,
+        model,
+        // Inferred from the model, which also covers OpenAI-compatible
+        // upstreams serving other providers' models
+        provider: None,
+        session_id: Some(request.header(SESSION_HEADER).unwrap_or("proxy").to_string()),
+        input: tokens.input,
+        output: tokens.output,
+        cache_read: tokens.cache_read,
+        cache_write: tokens.cache_write,
+        reasoning: tokens.reasoning,
+        training: tokens.training,
+        cost: 0.0,
+        billed: false,
+        agent: request.header(AGENT_HEADER).map(str::to_string),
+    };
+    shared
+        .recorder
+        .record(&event.into_message(chrono::Utc::now().timestamp_millis()))
+}
+
+async fn write_error<W: AsyncWrite + Unpin>(writer: &mut W, status: u16, reason: &str, message: &str) -> std::io::Result<()> {
+    let body = serde_json::json!({ "error": { "type": "tokscale_proxy_error", "message": message } });
+    http::write_response(writer, status, reason, "application/json", body.to_string().as_bytes()).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sessions::recorded::parse_recorded_file;
+    use std::path::Path;
+    use tokio::sync::mpsc;
+
+    #[test]
+    fn test_route() {
+        assert_eq!(route("/v1/messages"), Some(ApiFormat::Anthropic));
+        assert_eq!(route("/v1/chat/completions"), Some(ApiFormat::OpenAi));
+        assert_eq!(route("/v1/responses"), Some(ApiFormat::OpenAi));
+        assert_eq!(route("/health"), None);
+    }
+
+    #[test]
+    fn test_request_stream_usage() {
+        let mut body = serde_json::json!({ "model": "gpt-4o", "stream": true });
+        assert!(request_stream_usage("/v1/chat/completions", &mut body));
+        assert_eq!(body["stream_options"]["include_usage"], true);
+        assert!(!request_stream_usage("/v1/chat/completions", &mut body));
+
+        let mut body = serde_json::json!({ "stream": true, "stream_options": { "include_usage": false } });
+        assert!(!request_stream_usage("/v1/chat/completions", &mut body));
+        let mut body = serde_json::json!({ "model": "gpt-4o" });
+        assert!(!request_stream_usage("/v1/chat/completions", &mut body));
+        let mut body = serde_json::json!({ "stream": true });
+        assert!(!request_stream_usage("/v1/responses", &mut body));
+    }
+
+    /// Upstream that answers every request with `response` and reports each
+    /// request body it received
+    async fn fake_upstream(response: &'static str) -> (String, mpsc::UnboundedReceiver<(String, Vec<u8>)>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (tx, rx) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let (read, mut write) = stream.into_split();
+                let request = http::read_request(&mut BufReader::new(read)).await.unwrap().unwrap();
+                let auth = request.header("authorization").unwrap_or("").to_string();
+                tx.send((auth, request.body)).unwrap();
+                let headers = [("content-type".to_string(), "text/event-stream".to_string())];
+                http::write_head(&mut write, 200, "OK", &headers, true).await.unwrap();
+                for part in response.split_inclusive("\n\n") {
+                    http::write_chunk(&mut write, part.as_bytes()).await.unwrap();
+                }
+                http::finish_chunks(&mut write).await.unwrap();
+            }
+        });
+        (format!("http://{}", addr), rx)
+    }
+
+    fn recorded_messages(dir: &Path) -> Vec<crate::UnifiedMessage> {
+        std::fs::read_dir(dir)
+            .unwrap()
+            .flat_map(|entry| parse_recorded_file(&entry.unwrap().path()))
+            .collect()
+    }
+
+    #[test]
+    fn test_proxy_meters_streamed_response() {
+        let stream = concat!(
+            "data: {\"id\":\"chatcmpl-9\",\"model\":\"gpt-4o\",\"choices\":[{\"delta\":{\"content\":\"Hi\"}}]}\n\n",
+            "data: {\"id\":\"chatcmpl-9\",\"model\":\"gpt-4o\",\"choices\":[],\"usage\":{\"prompt_tokens\":12,\"completion_tokens\":3}}\n\n",
+            "data: [DONE]\n\n",
+        );
+        let dir = tempfile::tempdir().unwrap();
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+
+        let body = runtime.block_on(async {
+            let (upstream, mut requests) = fake_upstream(stream).await;
+            let config = ProxyConfig {
+                listen: SocketAddr::from(([127, 0, 0, 1], 0)),
+                openai_upstream: upstream,
+                anthropic_upstream: ANTHROPIC_UPSTREAM.to_string(),
+            };
+            let proxy = Proxy::bind(config, Recorder::in_dir(dir.path().to_path_buf()).unwrap()).await.unwrap();
+            let addr = proxy.local_addr().unwrap();
+            tokio::spawn(proxy.serve());
+
+            let body = reqwest::Client::new()
+                .post(format!("http://{}/v1/chat/completions", addr))
+                .header("authorization", "Bearer sk-test")
+                .header(SESSION_HEADER, "nightly-eval")
+                .body(r#"{"model":"gpt-4o","stream":true,"messages":[]}"#)
+                .send()
+                .await
+                .unwrap()
+                .text()
+                .await
+                .unwrap();
+
+            let (auth, forwarded) = requests.recv().await.unwrap();
+            assert_eq!(auth, "Bearer sk-test");
+            let forwarded: Value = serde_json::from_slice(&forwarded).unwrap();
+            assert_eq!(forwarded["stream_options"]["include_usage"], true);
+            body
+        });
+
+        assert_eq!(body, stream);
+        let messages = recorded_messages(dir.path());
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].model_id, "gpt-4o");
+        assert_eq!(messages[0].provider_id, "openai");
+        assert_eq!(messages[0].session_id, "nightly-eval");
+        assert_eq!(messages[0].tokens.input, 12);
+        assert_eq!(messages[0].tokens.output, 3);
+        assert_eq!(messages[0].dedup_key.as_deref(), Some("recorded:chatcmpl-9"));
+    }
+}
@@ -0,0 +1,105 @@
+//! Metering of response bodies as they pass through the proxy
+
+use crate::response_usage::sse::{SseDecoder, StreamUsage};
+use crate::response_usage::{from_json, ApiFormat, ResponseUsage};
+use serde_json::Value;
+
+/// Non-streamed bodies larger than this are passed through unmetered
+const MAX_BUFFERED_BODY: usize = 16 * 1024 * 1024;
+
+/// Collects a response body as it passes through and reports its usage at
+/// the end. Streams are decoded as they arrive, so only a partial line is
+/// held in memory; other bodies are buffered up to a limit.
+pub struct UsageMeter {
+    format: ApiFormat,
+    body: Body,
+}
+
+enum Body {
+    Stream(SseDecoder, StreamUsage),
+    Buffered(Vec<u8>),
+    /// Outgrew the buffer; passed through unmetered
+    Overflowed,
+}
+
+impl UsageMeter {
+    pub fn new(format: ApiFormat, content_type: Option<&str>) -> Self {
+        let body = if content_type.is_some_and(|ct| ct.starts_with("text/event-stream")) {
+            Body::Stream(SseDecoder::new(), StreamUsage::new(Some(format)))
+        } else {
+            Body::Buffered(Vec::new())
+        };
+        Self { format, body }
+    }
+
+    pub fn feed(&mut self, data: &[u8]) {
+        match &mut self.body {
+            Body::Stream(decoder, usage) => {
+                for event in decoder.feed(data) {
+                    usage.push(&event);
+                }
+            }
+            Body::Buffered(buffer) if buffer.len() + data.len() > MAX_BUFFERED_BODY => self.body = Body::Overflowed,
+            Body::Buffered(buffer) => buffer.extend_from_slice(data),
+            Body::Overflowed => {}
+        }
+    }
+
+    /// Usage of the response; a stream is expected to hold exactly one
+    pub fn finish(self) -> Option<ResponseUsage> {
+        match self.body {
+            Body::Stream(mut decoder, mut usage) => {
+                if let Some(event) = decoder.finish() {
+                    usage.push(&event);
+                }
+                usage.finish().pop()
+            }
+            Body::Buffered(buffer) => {
+                let body: Value = serde_json::from_slice(&buffer).ok()?;
+                from_json(self.format, &body)
+            }
+            Body::Overflowed => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_meter_json_in_pieces() {
+        let body = br#"{"id":"msg_1","model":"claude-sonnet-4","usage":{"input_tokens":10,"output_tokens":5,"cache_read_input_tokens":3}}"#;
+        let mut meter = UsageMeter::new(ApiFormat::Anthropic, Some("application/json"));
+        for piece in body.chunks(7) {
+            meter.feed(piece);
+        }
+        let usage = meter.finish().unwrap();
+        assert_eq!(usage.tokens.input, 10);
+        assert_eq!(usage.tokens.cache_read, 3);
+    }
+
+    #[test]
+    fn test_meter_stream_in_pieces() {
+        let stream = concat!(
+            "data: {\"id\":\"chatcmpl-2\",\"model\":\"gpt-4o\",\"choices\":[{\"delta\":{\"content\":\"Hi\"}}],\"usage\":null}\n\n",
+            "data: {\"id\":\"chatcmpl-2\",\"model\":\"gpt-4o\",\"choices\":[],\"usage\":{\"prompt_tokens\":9,\"completion_tokens\":2}}\n\n",
+            "data: [DONE]\n\n",
+        );
+        let mut meter = UsageMeter::new(ApiFormat::OpenAi, Some("text/event-stream; charset=utf-8"));
+        for piece in stream.as_bytes().chunks(5) {
+            meter.feed(piece);
+        }
+        let usage = meter.finish().unwrap();
+        assert_eq!(usage.id.as_deref(), Some("chatcmpl-2"));
+        assert_eq!(usage.tokens.input, 9);
+        assert_eq!(usage.tokens.output, 2);
+    }
+
+    #[test]
+    fn test_meter_error_body() {
+        let mut meter = UsageMeter::new(ApiFormat::OpenAi, Some("application/json"));
+        meter.feed(br#"{"error":{"message":"rate limited"}}"#);
+        assert!(meter.finish().is_none());
+    }
+}
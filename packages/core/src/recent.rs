@@ -0,0 +1,189 @@
+//! Hourly and 15-minute buckets for recent usage
+//!
+//! Reports roll usage up by day, which is too coarse to see what is happening
+//! right now. The last few days are bucketed finer for the live TUI and the
+//! burn rate; anything older stays daily, so the number of buckets depends on
+//! the window, not on how much history there is. Buckets are computed from
+//! the messages on each call and never stored.
+//!
+//! Buckets are aligned to UTC. With 15-minute buckets that is also aligned to
+//! every local time zone; hourly buckets are off by half an hour in zones
+//! such as UTC+5:30.
+
+use crate::sessions::UnifiedMessage;
+use crate::{RecentBucket, RecentUsage};
+
+/// Days bucketed when neither the caller nor settings set it
+pub const DEFAULT_RECENT_DAYS: u32 = 2;
+/// Upper bound on the window: 14 days of 15-minute buckets is 1344 buckets
+pub const MAX_RECENT_DAYS: u32 = 14;
+
+const MS_PER_MINUTE: i64 = 60_000;
+const MS_PER_HOUR: i64 = 3_600_000;
+const MS_PER_DAY: i64 = 86_400_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Granularity {
+    Hour,
+    QuarterHour,
+}
+
+impl Granularity {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "hour" | "1h" => Some(Granularity::Hour),
+            "15m" => Some(Granularity::QuarterHour),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Granularity::Hour => "hour",
+            Granularity::QuarterHour => "15m",
+        }
+    }
+
+    pub fn minutes(self) -> i64 {
+        match self {
+            Granularity::Hour => 60,
+            Granularity::QuarterHour => 15,
+        }
+    }
+}
+
+/// Days to bucket: the caller's, else `recentBucketDays` from settings, else
+/// the default, capped at [`MAX_RECENT_DAYS`]. Zero is ignored.
+pub fn recent_days(requested: Option<u32>) -> u32 {
+    requested
+        .filter(|d| *d > 0)
+        .or_else(|| crate::config::settings().recent_bucket_days.filter(|d| *d > 0))
+        .unwrap_or(DEFAULT_RECENT_DAYS)
+        .min(MAX_RECENT_DAYS)
+}
+
+fn total_tokens(msg: &UnifiedMessage) -> i64 {
+    msg.tokens
+        .input
+        .saturating_add(msg.tokens.output)
+        .saturating_add(msg.tokens.cache_read)
+        .saturating_add(msg.tokens.cache_write)
+        .saturating_add(msg.tokens.reasoning)
+        .saturating_add(msg.tokens.training)
+}
+
+/// Usage over the `days` days up to `now_ms`, one bucket per period, oldest
+/// first. Empty periods are included so the buckets can be charted as is.
+pub fn buckets(messages: &[UnifiedMessage], granularity: Granularity, days: u32, now_ms: i64) -> Vec<RecentBucket> {
+    let width = granularity.minutes() * MS_PER_MINUTE;
+    // The bucket holding `now_ms` is the last one
+    let end = now_ms - now_ms.rem_euclid(width) + width;
+    let start = end - days as i64 * MS_PER_DAY;
+
+    let mut buckets: Vec<RecentBucket> = (start..end)
+        .step_by(width as usize)
+        .map(|bucket_start| RecentBucket {
+            start: bucket_start,
+            tokens: 0,
+            message_count: 0,
+            cost: 0.0,
+        })
+        .collect();
+
+    for msg in messages.iter().filter(|m| m.timestamp >= start && m.timestamp < end) {
+        let bucket = &mut buckets[((msg.timestamp - start) / width) as usize];
+        bucket.tokens = bucket.tokens.saturating_add(total_tokens(msg));
+        bucket.message_count += 1;
+        bucket.cost += msg.cost;
+    }
+    buckets
+}
+
+/// Buckets plus the burn rate: cost and tokens over the hour before `now_ms`
+pub fn report(
+    messages: &[UnifiedMessage],
+    granularity: Granularity,
+    days: u32,
+    now_ms: i64,
+    processing_time_ms: u32,
+) -> RecentUsage {
+    let last_hour = messages
+        .iter()
+        .filter(|m| m.timestamp > now_ms - MS_PER_HOUR && m.timestamp <= now_ms);
+    let (cost_per_hour, tokens_per_hour) =
+        last_hour.fold((0.0, 0i64), |(cost, tokens), m| (cost + m.cost, tokens.saturating_add(total_tokens(m))));
+
+    RecentUsage {
+        granularity: granularity.as_str().to_string(),
+        bucket_minutes: granularity.minutes() as u32,
+        days,
+        buckets: buckets(messages, granularity, days, now_ms),
+        cost_per_hour,
+        tokens_per_hour,
+        processing_time_ms,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{message, TestMessage};
+
+    // 2025-03-01T10:00:00Z
+    const T0: i64 = 1_740_823_200_000;
+
+    #[test]
+    fn test_quarter_hour_buckets() {
+        let now = T0 + 20 * MS_PER_MINUTE;
+        let messages = vec![
+            message("s1", T0 - 1, 0.1).with_output(10),
+            message("s1", T0, 0.2).with_output(20),
+            message("s1", T0 + 14 * MS_PER_MINUTE, 0.3).with_output(30),
+            message("s1", T0 + 15 * MS_PER_MINUTE, 0.4).with_output(40),
+            // Before the window
+            message("s1", now - 2 * MS_PER_DAY, 0.5).with_output(50),
+        ];
+
+        let buckets = buckets(&messages, Granularity::QuarterHour, 1, now);
+        assert_eq!(buckets.len(), 96);
+        let last = &buckets[95];
+        assert_eq!(last.start, T0 + 15 * MS_PER_MINUTE);
+        assert_eq!((last.tokens, last.message_count), (40, 1));
+        assert_eq!(buckets[94].start, T0);
+        assert_eq!((buckets[94].tokens, buckets[94].message_count), (50, 2));
+        assert_eq!(buckets[93].tokens, 10);
+        assert_eq!(buckets.iter().map(|b| b.message_count).sum::<i32>(), 4);
+    }
+
+    #[test]
+    fn test_hourly_buckets_are_contiguous() {
+        let buckets = buckets(&[], Granularity::Hour, 2, T0 + 1);
+        assert_eq!(buckets.len(), 48);
+        assert_eq!(buckets[47].start, T0);
+        assert!(buckets.windows(2).all(|w| w[1].start - w[0].start == MS_PER_HOUR));
+    }
+
+    #[test]
+    fn test_burn_rate_covers_last_hour() {
+        let now = T0 + 90 * MS_PER_MINUTE;
+        let messages = vec![
+            message("s1", now - MS_PER_HOUR, 1.0).with_output(100),
+            message("s1", now - MS_PER_HOUR + 1, 2.0).with_output(200),
+            message("s1", now, 3.0).with_output(300),
+        ];
+
+        let report = report(&messages, Granularity::Hour, 1, now, 0);
+        assert_eq!(report.tokens_per_hour, 500);
+        assert!((report.cost_per_hour - 5.0).abs() < 1e-9);
+        assert_eq!((report.granularity.as_str(), report.bucket_minutes), ("hour", 60));
+    }
+
+    #[test]
+    fn test_granularity_parse_round_trip() {
+        for granularity in [Granularity::Hour, Granularity::QuarterHour] {
+            assert_eq!(Granularity::parse(granularity.as_str()), Some(granularity));
+        }
+        assert_eq!(Granularity::parse("1h"), Some(Granularity::Hour));
+        assert_eq!(Granularity::parse("day"), None);
+    }
+}
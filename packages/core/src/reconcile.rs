@@ -0,0 +1,343 @@
+//! Invoice reconciliation
+//!
+//! Lines up what tokscale computed against what a provider billed, per UTC
+//! day and model, from the provider's CSV export:
+//!
+//! - Anthropic Console cost export: `usage_date_utc`, `model`, `cost_usd`
+//! - OpenAI cost export: `start_time` (Unix seconds or a date), `line_item`
+//!   ("gpt-4o-2024-08-06, input"), `amount_value`
+//!
+//! Other exports work as long as they have a date, a model and a USD cost
+//! column under one of the names in [`DATE_COLUMNS`], [`MODEL_COLUMNS`] and
+//! [`COST_COLUMNS`]. Models are matched without their date suffix, since
+//! invoices and session logs rarely agree on it.
+
+use crate::sessions::UnifiedMessage;
+use std::collections::BTreeMap;
+
+pub const DATE_COLUMNS: &[&str] = &["usage_date_utc", "date", "start_time", "day", "usage_date"];
+pub const MODEL_COLUMNS: &[&str] = &["model", "line_item", "model_name", "model_id"];
+pub const COST_COLUMNS: &[&str] = &["cost_usd", "amount_value", "cost", "amount", "amount_usd"];
+const CURRENCY_COLUMNS: &[&str] = &["amount_currency", "currency"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvoiceProvider {
+    Anthropic,
+    OpenAi,
+}
+
+impl InvoiceProvider {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "anthropic" => Some(InvoiceProvider::Anthropic),
+            "openai" => Some(InvoiceProvider::OpenAi),
+            _ => None,
+        }
+    }
+
+    /// The provider id messages billed by this provider carry
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            InvoiceProvider::Anthropic => "anthropic",
+            InvoiceProvider::OpenAi => "openai",
+        }
+    }
+
+    /// Guess from the header row; only the two known exports are recognized
+    fn detect(headers: &[String]) -> Option<Self> {
+        let has = |name: &str| headers.iter().any(|h| h == name);
+        if has("usage_date_utc") || has("cost_usd") {
+            Some(InvoiceProvider::Anthropic)
+        } else if has("line_item") || has("amount_value") {
+            Some(InvoiceProvider::OpenAi)
+        } else {
+            None
+        }
+    }
+}
+
+/// One billed row
+#[derive(Debug, Clone, PartialEq)]
+pub struct BilledItem {
+    /// UTC day, YYYY-MM-DD
+    pub date: String,
+    pub model: String,
+    pub cost: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct Invoice {
+    pub provider: InvoiceProvider,
+    pub items: Vec<BilledItem>,
+}
+
+impl Invoice {
+    /// Parse a billing CSV. `provider` is detected from the header when not
+    /// given. Rows without a date or a cost (totals, blank lines) are skipped.
+    pub fn parse_csv(text: &str, provider: Option<InvoiceProvider>) -> Result<Self, String> {
+        let mut rows = csv_records(text.trim_start_matches('\u{feff}')).into_iter();
+        let headers: Vec<String> = rows
+            .next()
+            .ok_or("Invoice is empty")?
+            .iter()
+            .map(|h| h.trim().to_ascii_lowercase())
+            .collect();
+
+        let provider = match provider.or_else(|| InvoiceProvider::detect(&headers)) {
+            Some(provider) => provider,
+            None => return Err("Can't tell which provider this invoice is from; pass the provider".to_string()),
+        };
+        let column = |names: &[&str], what: &str| {
+            names
+                .iter()
+                .find_map(|name| headers.iter().position(|h| h == name))
+                .ok_or_else(|| format!("Invoice has no {} column (expected one of: {})", what, names.join(", ")))
+        };
+        let (date_col, model_col, cost_col) = (column(DATE_COLUMNS, "date")?, column(MODEL_COLUMNS, "model")?, column(COST_COLUMNS, "cost")?);
+        let currency_col = CURRENCY_COLUMNS.iter().find_map(|name| headers.iter().position(|h| h == name));
+
+        let mut items = Vec::new();
+        for (i, row) in rows.enumerate() {
+            let line = i + 2;
+            let field = |col: usize| row.get(col).map(|s| s.trim()).unwrap_or("");
+            let Some(cost) = parse_amount(field(cost_col)) else { continue };
+            if field(date_col).is_empty() {
+                continue;
+            }
+            if let Some(currency) = currency_col.map(field).filter(|c| !c.is_empty()) {
+                if !currency.eq_ignore_ascii_case("usd") {
+                    return Err(format!("Line {}: only USD invoices are supported, found {}", line, currency));
+                }
+            }
+            let date = parse_date(field(date_col)).ok_or_else(|| format!("Line {}: invalid date \"{}\"", line, field(date_col)))?;
+            // OpenAI line items read "gpt-4o-2024-08-06, input"
+            let model = field(model_col).split(',').next().unwrap_or("").trim();
+            if model.is_empty() {
+                return Err(format!("Line {}: missing model", line));
+            }
+            items.push(BilledItem { date, model: model.to_string(), cost });
+        }
+
+        Ok(Self { provider, items })
+    }
+
+    /// First and last billed day
+    pub fn date_range(&self) -> Option<(&str, &str)> {
+        let min = self.items.iter().map(|i| i.date.as_str()).min()?;
+        let max = self.items.iter().map(|i| i.date.as_str()).max()?;
+        Some((min, max))
+    }
+}
+
+/// How far computed and billed may drift before a line is flagged: by more
+/// than `relative` of the larger amount and by more than `absolute` USD
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tolerance {
+    pub relative: f64,
+    pub absolute: f64,
+}
+
+impl Default for Tolerance {
+    fn default() -> Self {
+        Self { relative: 0.05, absolute: 0.01 }
+    }
+}
+
+/// Billed and computed cost for one (day, model)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Line {
+    pub date: String,
+    /// Model without its date suffix
+    pub model: String,
+    pub billed: f64,
+    pub computed: f64,
+    pub mismatch: bool,
+}
+
+impl Line {
+    /// Computed minus billed; negative when tokscale under-counts
+    pub fn difference(&self) -> f64 {
+        self.computed - self.billed
+    }
+}
+
+/// Compare `invoice` with the messages billed by its provider over the days
+/// it covers, ordered by date then model
+pub fn reconcile(invoice: &Invoice, messages: &[UnifiedMessage], tolerance: Tolerance) -> Vec<Line> {
+    let Some((first, last)) = invoice.date_range() else { return Vec::new() };
+
+    let mut lines: BTreeMap<(String, String), (f64, f64)> = BTreeMap::new();
+    for item in &invoice.items {
+        lines.entry((item.date.clone(), model_key(&item.model))).or_default().0 += item.cost;
+    }
+    let provider = invoice.provider.as_str();
+    for msg in messages {
+        if msg.provider_id.as_str() != provider || msg.date.as_str() < first || msg.date.as_str() > last {
+            continue;
+        }
+        lines.entry((msg.date.clone(), model_key(&msg.model_id))).or_default().1 += msg.cost;
+    }
+
+    lines
+        .into_iter()
+        .map(|((date, model), (billed, computed))| {
+            let difference = (computed - billed).abs();
+            let mismatch = difference > tolerance.absolute && difference > tolerance.relative * billed.max(computed);
+            Line { date, model, billed, computed, mismatch }
+        })
+        .collect()
+}
+
+/// Lowercase, without a routing prefix or a trailing date, with dots as
+/// dashes: "anthropic/claude-3.5-sonnet-20241022" -> "claude-3-5-sonnet"
+fn model_key(model: &str) -> String {
+    let model = model.rsplit('/').next().unwrap_or(model).to_ascii_lowercase().replace('.', "-");
+    let digits = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+    let parts: Vec<&str> = model.split('-').collect();
+    let n = parts.len();
+    let keep = if n > 1 && parts[n - 1].len() == 8 && digits(parts[n - 1]) {
+        // -20241022
+        n - 1
+    } else if n > 3 && parts[n - 3].len() == 4 && parts[n - 2].len() == 2 && parts[n - 1].len() == 2
+        && [parts[n - 3], parts[n - 2], parts[n - 1]].iter().all(|p| digits(p))
+    {
+        // -2024-08-06
+        n - 3
+    } else {
+        n
+    };
+    parts[..keep].join("-")
+}
+
+/// "$1,234.50" -> 1234.5; `None` for blank or non-numeric cells
+fn parse_amount(text: &str) -> Option<f64> {
+    let cleaned: String = text.chars().filter(|c| !matches!(c, '$' | ',' | ' ')).collect();
+    cleaned.parse::<f64>().ok().filter(|v| v.is_finite())
+}
+
+/// YYYY-MM-DD from a date, an RFC 3339 time or Unix seconds/milliseconds
+fn parse_date(text: &str) -> Option<String> {
+    if let Ok(date) = chrono::NaiveDate::parse_from_str(text, "%Y-%m-%d") {
+        return Some(date.format("%Y-%m-%d").to_string());
+    }
+    if let Ok(dt) = chrono::DateTime::parse_from_rfc3339(text) {
+        return Some(dt.with_timezone(&chrono::Utc).format("%Y-%m-%d").to_string());
+    }
+    let number: i64 = text.parse().ok()?;
+    let millis = if number > 100_000_000_000 { number } else { number * 1000 };
+    chrono::DateTime::from_timestamp_millis(millis).map(|dt| dt.format("%Y-%m-%d").to_string())
+}
+
+/// Records of a CSV document, with quoted fields (including commas, doubled
+/// quotes and line breaks inside quotes) unescaped
+fn csv_records(text: &str) -> Vec<Vec<String>> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => record.push(std::mem::take(&mut field)),
+            '\n' if !in_quotes => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            }
+            '\r' if !in_quotes => {}
+            _ => field.push(c),
+        }
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    records.retain(|r| r.iter().any(|f| !f.trim().is_empty()));
+    records
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{message, noon_utc, TestMessage};
+
+    #[test]
+    fn test_parse_anthropic_export() {
+        let csv = "\u{feff}usage_date_utc,model,workspace,token_type,cost_usd\n\
+                   2025-03-01,claude-sonnet-4-20250514,Default,input,1.20\n\
+                   2025-03-01,claude-sonnet-4-20250514,Default,output,\"3.00\"\n\
+                   ,,,Total,4.20\n";
+        let invoice = Invoice::parse_csv(csv, None).unwrap();
+        assert_eq!(invoice.provider, InvoiceProvider::Anthropic);
+        // The total row has no date and isn't counted twice
+        assert_eq!(invoice.items.len(), 2);
+        assert_eq!(invoice.items[1].cost, 3.0);
+    }
+
+    #[test]
+    fn test_parse_openai_export() {
+        let csv = "start_time,end_time,amount_value,amount_currency,line_item,project_id\n\
+                   1740787200,1740873600,0.75,usd,\"gpt-4o-2024-08-06, input\",proj_1\n\
+                   1740787200,1740873600,\"1,000.25\",usd,\"gpt-4o-2024-08-06, output\",proj_1\n";
+        let invoice = Invoice::parse_csv(csv, None).unwrap();
+        assert_eq!(invoice.provider, InvoiceProvider::OpenAi);
+        assert_eq!(invoice.items[0], BilledItem { date: "2025-03-01".into(), model: "gpt-4o-2024-08-06".into(), cost: 0.75 });
+        assert_eq!(invoice.items[1].cost, 1000.25);
+
+        let eur = csv.replace("usd", "eur");
+        assert!(Invoice::parse_csv(&eur, None).unwrap_err().contains("only USD"));
+        assert!(Invoice::parse_csv("day,what,cost\n2025-03-01,x,1\n", None).is_err());
+        assert!(Invoice::parse_csv("day,what,cost\n2025-03-01,x,1\n", Some(InvoiceProvider::OpenAi))
+            .unwrap_err()
+            .contains("no model column"));
+    }
+
+    #[test]
+    fn test_model_key() {
+        assert_eq!(model_key("claude-sonnet-4-20250514"), "claude-sonnet-4");
+        assert_eq!(model_key("anthropic/claude-3.5-sonnet-20241022"), "claude-3-5-sonnet");
+        assert_eq!(model_key("gpt-4o-2024-08-06"), "gpt-4o");
+        assert_eq!(model_key("gpt-4o"), "gpt-4o");
+        assert_eq!(model_key("o3"), "o3");
+    }
+
+    #[test]
+    fn test_reconcile_flags_mismatches() {
+        let invoice = Invoice {
+            provider: InvoiceProvider::Anthropic,
+            items: vec![
+                BilledItem { date: "2025-03-01".into(), model: "claude-sonnet-4-20250514".into(), cost: 4.0 },
+                BilledItem { date: "2025-03-02".into(), model: "claude-sonnet-4-20250514".into(), cost: 10.0 },
+                BilledItem { date: "2025-03-02".into(), model: "claude-opus-4-20250514".into(), cost: 0.005 },
+            ],
+        };
+        let messages = vec![
+            message("s1", noon_utc("2025-03-01"), 3.9).with_model("claude-sonnet-4-20250514"),
+            message("s1", noon_utc("2025-03-02"), 7.0),
+            // Outside the invoice's days, or billed by someone else
+            message("s1", noon_utc("2025-03-05"), 100.0),
+            message("s1", noon_utc("2025-03-01"), 100.0).with_provider("bedrock"),
+        ];
+
+        let lines = reconcile(&invoice, &messages, Tolerance::default());
+        assert_eq!(lines.len(), 3);
+        assert_eq!((lines[0].date.as_str(), lines[0].model.as_str()), ("2025-03-01", "claude-sonnet-4"));
+        assert!(!lines[0].mismatch);
+        // Billed but not seen locally, yet under a cent
+        assert_eq!(lines[1].model, "claude-opus-4");
+        assert!(!lines[1].mismatch);
+        assert!(lines[2].mismatch);
+        assert!((lines[2].difference() + 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_csv_records() {
+        let records = csv_records("a,\"b, c\",\"say \"\"hi\"\"\"\r\n\n1,\"two\nlines\",3");
+        assert_eq!(records, vec![vec!["a", "b, c", "say \"hi\""], vec!["1", "two\nlines", "3"]]);
+    }
+}
@@ -0,0 +1,269 @@
+//! Recording usage from outside the supported tools
+//!
+//! Scripts, gateways and in-house agents that call models directly have no
+//! session files for tokscale to scan. They can record usage instead, either
+//! through [`Recorder::record`] or by piping NDJSON events into
+//! `tokscale record` (see [`crate::sessions::recorded`] for the event shape).
+//!
+//! Events are appended to `~/.config/tokscale/recorded/YYYY-MM-DD.jsonl`, one
+//! file per UTC day, and show up in reports as the "recorded" source.
+//!
+//! The proxy and serve mode append here for as long as they run, so a crash
+//! must not damage what is already recorded. Each event is a single write, a
+//! line torn by a crash is sealed off with a newline before the next append
+//! (readers skip it instead of it swallowing the next event), and every
+//! [`Recorder::ingest`] batch is synced to disk before it returns. A day's
+//! file is synced when writing moves on to the next day, so a batch that
+//! crosses midnight leaves no unsynced file behind.
+//! [`check`] reports lines that no longer parse.
+
+use crate::sessions::recorded::RecordedEvent;
+use crate::sessions::UnifiedMessage;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Directory recorded events are stored in
+pub fn recorded_dir(home_dir: &str) -> PathBuf {
+    Path::new(home_dir).join(".config").join("tokscale").join("recorded")
+}
+
+/// Appends usage events to the recorded-events directory
+pub struct Recorder {
+    dir: PathBuf,
+    /// Open file for the day last written to
+    current: Mutex<Option<(String, File)>>,
+}
+
+/// Outcome of [`Recorder::ingest`]
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct IngestStats {
+    pub recorded: usize,
+    /// Rejected lines as (1-based line number, reason)
+    pub errors: Vec<(usize, String)>,
+}
+
+impl Recorder {
+    /// Recorder for the recorded-events directory under `home_dir`
+    pub fn open(home_dir: &str) -> Result<Self, String> {
+        Self::in_dir(recorded_dir(home_dir))
+    }
+
+    pub fn in_dir(dir: PathBuf) -> Result<Self, String> {
+        std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        Ok(Self { dir, current: Mutex::new(None) })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Append one message. The source is stored as "recorded" whatever the
+    /// message says, since that is where it will be read back from.
+    pub fn record(&self, message: &UnifiedMessage) -> Result<(), String> {
+        self.append(&RecordedEvent::from_message(message), &message.date)
+    }
+
+    /// Record every valid NDJSON event from `reader`. Events without a
+    /// timestamp are stamped with the time they are read; invalid lines are
+    /// reported and skipped.
+    pub fn ingest(&self, reader: impl BufRead) -> Result<IngestStats, String> {
+        let mut stats = IngestStats::default();
+        for (index, line) in reader.lines().enumerate() {
+            let line = line.map_err(|e| format!("Failed to read input: {}", e))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match RecordedEvent::parse(&line) {
+                Ok(event) => {
+                    let message = event.into_message(chrono::Utc::now().timestamp_millis());
+                    self.record(&message)?;
+                    stats.recorded += 1;
+                }
+                Err(e) => stats.errors.push((index + 1, e)),
+            }
+        }
+        self.sync()?;
+        Ok(stats)
+    }
+
+    /// Flush the file last written to disk. Batches call this once rather
+    /// than syncing every event.
+    pub fn sync(&self) -> Result<(), String> {
+        let current = self.current.lock().unwrap_or_else(|e| e.into_inner());
+        match current.as_ref() {
+            Some((day, file)) => file.sync_data().map_err(|e| format!("Failed to sync {}.jsonl: {}", day, e)),
+            None => Ok(()),
+        }
+    }
+
+    fn append(&self, event: &RecordedEvent, date: &str) -> Result<(), String> {
+        let mut line = serde_json::to_string(event).map_err(|e| e.to_string())?;
+        line.push('\n');
+
+        let mut current = self.current.lock().unwrap_or_else(|e| e.into_inner());
+        if current.as_ref().map(|(day, _)| day.as_str()) != Some(date) {
+            let path = self.dir.join(format!("{}.jsonl", date));
+            let mut file = OpenOptions::new()
+                .create(true)
+                .read(true)
+                .append(true)
+                .open(&path)
+                .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+            seal_torn_tail(&mut file).map_err(|e| format!("Failed to repair {}: {}", path.display(), e))?;
+            if let Some((day, previous)) = current.replace((date.to_string(), file)) {
+                previous.sync_data().map_err(|e| format!("Failed to sync {}.jsonl: {}", day, e))?;
+            }
+        }
+        let (_, file) = current.as_mut().expect("file opened above");
+        // One write per line so concurrent recorders don't interleave events
+        file.write_all(line.as_bytes()).map_err(|e| format!("Failed to write event: {}", e))
+    }
+}
+
+/// End a partial last line, left by a crash mid-write, so the next event
+/// starts on a line of its own
+fn seal_torn_tail(file: &mut File) -> std::io::Result<()> {
+    if file.metadata()?.len() == 0 {
+        return Ok(());
+    }
+    let mut last = [0u8; 1];
+    file.seek(SeekFrom::End(-1))?;
+    file.read_exact(&mut last)?;
+    if last[0] != b'\n' {
+        file.write_all(b"\n")?;
+    }
+    Ok(())
+}
+
+/// Line in a recorded-events file that isn't a valid event
+#[derive(Debug, Clone, PartialEq)]
+pub struct DamagedLine {
+    pub file: PathBuf,
+    /// 1-based
+    pub line: usize,
+    pub reason: String,
+}
+
+/// Every unreadable line in the recorded-events files in `dir`, oldest file
+/// first. Reports already skip these lines; this says where they are.
+pub fn check(dir: &Path) -> Result<Vec<DamagedLine>, String> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("Failed to read {}: {}", dir.display(), e)),
+    };
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "jsonl"))
+        .collect();
+    files.sort();
+
+    let mut damaged = Vec::new();
+    for file in files {
+        let content = std::fs::read(&file).map_err(|e| format!("Failed to read {}: {}", file.display(), e))?;
+        for (index, line) in content.split(|&b| b == b'\n').enumerate() {
+            if line.iter().all(u8::is_ascii_whitespace) {
+                continue;
+            }
+            let reason = match std::str::from_utf8(line) {
+                Ok(text) => RecordedEvent::parse(text).err(),
+                Err(_) => Some("not valid UTF-8".to_string()),
+            };
+            if let Some(reason) = reason {
+                damaged.push(DamagedLine { file: file.clone(), line: index + 1, reason });
+            }
+        }
+    }
+    Ok(damaged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sessions::recorded::parse_recorded_file;
+    use crate::TokenBreakdown;
+
+    #[test]
+    fn test_record_appends_per_day() {
+        let dir = tempfile::tempdir().unwrap();
+        let recorder = Recorder::in_dir(dir.path().join("recorded")).unwrap();
+        let tokens = TokenBreakdown { input: 100, output: 20, cache_read: 0, cache_write: 0, reasoning: 0, training: 0 };
+
+        for timestamp in [1733011200000, 1733014800000, 1733097600000] {
+            let msg = UnifiedMessage::new("claude", "claude-sonnet-4", "anthropic", "s1", timestamp, tokens.clone(), 0.0);
+            recorder.record(&msg).unwrap();
+        }
+
+        let first = parse_recorded_file(&recorder.dir().join("2024-12-01.jsonl"));
+        let second = parse_recorded_file(&recorder.dir().join("2024-12-02.jsonl"));
+        assert_eq!(first.len(), 2);
+        assert_eq!(second.len(), 1);
+        assert_eq!(first[0].source, "recorded");
+        assert_eq!(first[0].session_id, "s1");
+        assert_eq!(first[1].tokens.input, 100);
+    }
+
+    #[test]
+    fn test_ingest_across_midnight() {
+        let dir = tempfile::tempdir().unwrap();
+        let recorder = Recorder::in_dir(dir.path().to_path_buf()).unwrap();
+        let input = concat!(
+            r#"{"timestamp":"2024-12-01T23:59:30Z","model":"o3","output":1}"#,
+            "\n",
+            r#"{"timestamp":"2024-12-02T00:00:10Z","model":"o3","output":2}"#,
+            "\n",
+            r#"{"timestamp":"2024-12-02T00:00:20Z","model":"o3","output":3}"#,
+            "\n",
+        );
+
+        let stats = recorder.ingest(input.as_bytes()).unwrap();
+        assert_eq!(stats.recorded, 3);
+        let current = recorder.current.lock().unwrap();
+        assert_eq!(current.as_ref().map(|(day, _)| day.as_str()), Some("2024-12-02"), "only the last day stays open");
+        drop(current);
+        assert_eq!(parse_recorded_file(&dir.path().join("2024-12-01.jsonl")).len(), 1);
+        let second = parse_recorded_file(&dir.path().join("2024-12-02.jsonl"));
+        assert_eq!(second.iter().map(|m| m.tokens.output).collect::<Vec<_>>(), vec![2, 3]);
+    }
+
+    #[test]
+    fn test_ingest_reports_bad_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let recorder = Recorder::in_dir(dir.path().to_path_buf()).unwrap();
+        let input = concat!(
+            r#"{"timestamp":"2024-12-01T10:00:00Z","model":"gpt-4o","input":5}"#,
+            "\n\n",
+            "garbage\n",
+            r#"{"timestamp":1733011200000,"model":"o3","output":9}"#,
+            "\n",
+        );
+
+        let stats = recorder.ingest(input.as_bytes()).unwrap();
+        assert_eq!(stats.recorded, 2);
+        assert_eq!(stats.errors.len(), 1);
+        assert_eq!(stats.errors[0].0, 3);
+        assert_eq!(parse_recorded_file(&dir.path().join("2024-12-01.jsonl")).len(), 2);
+    }
+
+    #[test]
+    fn test_torn_line_is_sealed_before_append() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("2024-12-01.jsonl");
+        std::fs::write(&path, concat!(r#"{"timestamp":1733011200000,"model":"o3","output":9}"#, "\n", r#"{"timestamp":17330"#))
+            .unwrap();
+
+        let recorder = Recorder::in_dir(dir.path().to_path_buf()).unwrap();
+        recorder.ingest(r#"{"timestamp":1733014800000,"model":"o3","output":4}"#.as_bytes()).unwrap();
+
+        let messages = parse_recorded_file(&path);
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[1].tokens.output, 4);
+
+        let damaged = check(dir.path()).unwrap();
+        assert_eq!(damaged.len(), 1);
+        assert_eq!(damaged[0].line, 2);
+        assert!(check(&dir.path().join("missing")).unwrap().is_empty());
+    }
+}
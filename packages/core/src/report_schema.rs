@@ -0,0 +1,916 @@
+//! JSON report schema
+//!
+//! What the CLI prints with `--json` (`models`, `monthly`) and writes with
+//! `graph` is an interface scripts depend on. Its shape is pinned here as Rust
+//! types plus a JSON Schema, versioned with semver in [`SCHEMA_VERSION`]:
+//!
+//! - patch: descriptions and other non-structural edits
+//! - minor: new fields, optional in the schema so older documents still validate
+//! - major: anything that removes, renames or retypes a field
+//!
+//! The released schemas are committed under `tests/schema/` and the tests fail
+//! when the generated schema drifts from them, or when its fields, required
+//! fields and types differ from a schema derived from the Rust types with
+//! `schemars`. After an intentional change,
+//! bump `SCHEMA_VERSION` and regenerate:
+//! `UPDATE_GOLDEN=1 cargo test -p tokscale-core report_schema`
+//! Regeneration still refuses breaking changes unless the major version moved.
+
+use crate::{DailyContribution, DataSummary, GraphResult};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+
+pub const SCHEMA_VERSION: &str = "1.6.0";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportKind {
+    Models,
+    Monthly,
+    Graph,
+}
+
+impl ReportKind {
+    pub const ALL: [ReportKind; 3] = [ReportKind::Models, ReportKind::Monthly, ReportKind::Graph];
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "models" => Some(ReportKind::Models),
+            "monthly" => Some(ReportKind::Monthly),
+            "graph" => Some(ReportKind::Graph),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ReportKind::Models => "models",
+            ReportKind::Monthly => "monthly",
+            ReportKind::Graph => "graph",
+        }
+    }
+}
+
+/// `tokscale graph` output: a [`GraphResult`] with its date ranges nested
+#[cfg_attr(test, derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GraphExport {
+    pub meta: ExportMeta,
+    pub summary: DataSummary,
+    pub years: Vec<ExportYear>,
+    pub contributions: Vec<DailyContribution>,
+}
+
+#[cfg_attr(test, derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportMeta {
+    pub generated_at: String,
+    pub version: String,
+    pub date_range: DateRange,
+}
+
+#[cfg_attr(test, derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportYear {
+    pub year: String,
+    pub total_tokens: i64,
+    pub total_cost: f64,
+    pub range: DateRange,
+}
+
+#[cfg_attr(test, derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DateRange {
+    pub start: String,
+    pub end: String,
+}
+
+impl From<GraphResult> for GraphExport {
+    fn from(graph: GraphResult) -> Self {
+        GraphExport {
+            meta: ExportMeta {
+                generated_at: graph.meta.generated_at,
+                version: graph.meta.version,
+                date_range: DateRange {
+                    start: graph.meta.date_range_start,
+                    end: graph.meta.date_range_end,
+                },
+            },
+            summary: graph.summary,
+            years: graph
+                .years
+                .into_iter()
+                .map(|year| ExportYear {
+                    year: year.year,
+                    total_tokens: year.total_tokens,
+                    total_cost: year.total_cost,
+                    range: DateRange { start: year.range_start, end: year.range_end },
+                })
+                .collect(),
+            contributions: graph.contributions,
+        }
+    }
+}
+
+/// JSON Schema (draft 2020-12) for one report kind
+pub fn json_schema(kind: ReportKind) -> Value {
+    let (description, fields, defs) = match kind {
+        ReportKind::Models => (
+            "Usage and cost per source, provider and model (`tokscale models --json`)",
+            vec![
+                ("entries", array(reference("modelUsage"), "One row per (source, provider, model), most expensive first")),
+                ("totalInput", integer("Input tokens across all entries")),
+                ("totalOutput", integer("Output tokens across all entries")),
+                ("totalCacheRead", integer("Cache read tokens across all entries")),
+                ("totalCacheWrite", integer("Cache write tokens across all entries")),
+                ("totalMessages", integer("Messages across all entries")),
+                ("totalCost", number("USD cost across all entries")),
+                ("processingTimeMs", integer("Time spent building the report")),
+            ],
+            vec![("modelUsage", model_usage())],
+        ),
+        ReportKind::Monthly => (
+            "Usage and cost per calendar month (`tokscale monthly --json`)",
+            vec![
+                ("entries", array(reference("monthlyUsage"), "One row per month, oldest first")),
+                ("totalCost", number("USD cost across all months")),
+                ("processingTimeMs", integer("Time spent building the report")),
+            ],
+            vec![("monthlyUsage", monthly_usage())],
+        ),
+        ReportKind::Graph => (
+            "Daily contribution graph data (`tokscale graph`)",
+            vec![
+                ("meta", reference("meta")),
+                ("summary", reference("summary")),
+                ("years", array(reference("yearSummary"), "One entry per calendar year with activity")),
+                ("contributions", array(reference("dailyContribution"), "One entry per active day, oldest first")),
+            ],
+            vec![
+                ("meta", graph_meta()),
+                ("summary", graph_summary()),
+                ("yearSummary", year_summary()),
+                ("dailyContribution", daily_contribution()),
+                ("sourceContribution", source_contribution()),
+                ("tokenBreakdown", token_breakdown()),
+                ("dateRange", date_range()),
+            ],
+        ),
+    };
+
+    let mut schema = object(description, &fields);
+    schema["$schema"] = json!("https://json-schema.org/draft/2020-12/schema");
+    schema["title"] = json!(format!("tokscale {} report", kind.as_str()));
+    schema["x-schema-version"] = json!(SCHEMA_VERSION);
+    // Written by the CLI but not by older versions, so never required
+    schema["properties"]["schemaVersion"] = string("Version of this schema the document conforms to");
+    schema["$defs"] = Value::Object(defs.into_iter().map(|(name, def)| (name.to_string(), def)).collect());
+    if kind == ReportKind::Models {
+        // Added in 1.2
+        schema["properties"]["totalCovered"] = number("USD covered by free tiers and promotional credits across all entries");
+        // Added in 1.4
+        schema["properties"]["totalReasoning"] = integer("Reasoning tokens across all entries");
+        // Added in 1.5
+        schema["properties"]["families"] =
+            array(reference("modelFamily"), "Entries rolled up by model family, most expensive first (`--by-family`)");
+        schema["$defs"]["modelFamily"] = model_family();
+    }
+    if kind != ReportKind::Graph {
+        // Added in 1.3
+        schema["properties"]["summary"] = reference("runSummary");
+        schema["$defs"]["runSummary"] = run_summary();
+        schema["$defs"]["sourceFiles"] = source_files();
+        // Added in 1.6
+        schema["$defs"]["degradation"] = degradation();
+    }
+    if kind == ReportKind::Monthly {
+        // Added in 1.4
+        schema["properties"]["totalInput"] = integer("Input tokens across all months");
+        schema["properties"]["totalOutput"] = integer("Output tokens across all months");
+        schema["properties"]["totalCacheRead"] = integer("Cache read tokens across all months");
+        schema["properties"]["totalCacheWrite"] = integer("Cache write tokens across all months");
+        schema["properties"]["totalMessages"] = integer("Messages across all months");
+    }
+    schema
+}
+
+/// Share-of-total properties of a table row
+fn add_shares(schema: &mut Value) {
+    // Added in 1.4
+    schema["properties"]["costShare"] = fraction("Share of the report's cost");
+    schema["properties"]["tokenShare"] = fraction("Share of the report's input, output and cache tokens");
+}
+
+fn model_usage() -> Value {
+    let mut schema = object(
+        "Usage for one (source, provider, model)",
+        &[
+            ("source", string("Tool the usage came from, e.g. \"claude\"")),
+            ("model", string("Model id as recorded by the source")),
+            ("provider", string("Model provider, e.g. \"anthropic\"")),
+            ("input", integer("Input tokens")),
+            ("output", integer("Output tokens")),
+            ("cacheRead", integer("Cache read tokens")),
+            ("cacheWrite", integer("Cache write tokens")),
+            ("reasoning", integer("Reasoning tokens")),
+            ("messageCount", integer("Assistant messages")),
+            ("cost", number("USD cost")),
+        ],
+    );
+    // Added in 1.1
+    schema["properties"]["training"] = integer("Fine-tuning training tokens");
+    // Added in 1.2
+    schema["properties"]["covered"] = number("USD covered by free tiers and promotional credits, not included in cost");
+    add_shares(&mut schema);
+    schema
+}
+
+fn model_family() -> Value {
+    object(
+        "Usage for one model family, e.g. every Claude Sonnet version",
+        &[
+            ("provider", string("Provider the family belongs to, e.g. \"anthropic\"")),
+            ("family", string("Family name from the normalization rules, or the model id when no rule names one")),
+            ("models", array(reference("modelUsage"), "Entries of the family's models, most expensive first")),
+            ("input", integer("Input tokens")),
+            ("output", integer("Output tokens")),
+            ("cacheRead", integer("Cache read tokens")),
+            ("cacheWrite", integer("Cache write tokens")),
+            ("reasoning", integer("Reasoning tokens")),
+            ("messageCount", integer("Assistant messages")),
+            ("cost", number("USD cost")),
+            ("covered", number("USD covered by free tiers and promotional credits, not included in cost")),
+            ("costShare", fraction("Share of the report's cost")),
+            ("tokenShare", fraction("Share of the report's input, output and cache tokens")),
+        ],
+    )
+}
+
+fn monthly_usage() -> Value {
+    let mut schema = object(
+        "Usage for one calendar month",
+        &[
+            ("month", string("Month as YYYY-MM")),
+            ("models", array(string("Model id"), "Models used during the month")),
+            ("input", integer("Input tokens")),
+            ("output", integer("Output tokens")),
+            ("cacheRead", integer("Cache read tokens")),
+            ("cacheWrite", integer("Cache write tokens")),
+            ("messageCount", integer("Assistant messages")),
+            ("cost", number("USD cost")),
+        ],
+    );
+    add_shares(&mut schema);
+    schema
+}
+
+fn run_summary() -> Value {
+    let mut schema = object(
+        "What went into the report, for judging how far to trust it",
+        &[
+            ("sources", array(reference("sourceFiles"), "Sources scanned, with their session files")),
+            ("messages", integer("Messages in the report")),
+            ("messagesDeduped", integer("Messages dropped as duplicates of ones already counted")),
+            ("unknownModels", array(string("Model id"), "Models without a list price")),
+            ("unknownModelMessages", integer("Messages of models without a list price")),
+            ("pricingStale", boolean("Prices were fetched more than a day ago")),
+            ("exactCost", number("USD billed by providers, before free tiers")),
+            ("estimatedCost", number("USD estimated from token counts and list prices, before free tiers")),
+            ("exactCostShare", fraction("Share of the cost billed by providers")),
+        ],
+    );
+    // Absent for archived prices (`--as-of`)
+    schema["properties"]["pricingFetchedAt"] = integer("When the price tables were fetched, in seconds since the epoch");
+    schema["properties"]["pricingAgeHours"] = number("Hours since the price tables were fetched");
+    // Added in 1.6
+    schema["properties"]["degradation"] = reference("degradation");
+    schema
+}
+
+fn source_files() -> Value {
+    let mut schema = object(
+        "Session files of one source",
+        &[
+            ("source", string("Tool the files belong to, e.g. \"claude\"")),
+            ("files", integer("Session files found")),
+            ("parsed", integer("Files that yielded messages")),
+            ("skipped", integer("Files that yielded none: empty, unreadable or without usage")),
+        ],
+    );
+    // Added in 1.6
+    schema["properties"]["named"] = boolean("Asked for by name rather than scanned by default");
+    schema
+}
+
+fn degradation() -> Value {
+    let mut schema = object(
+        "Gaps in a report that still rendered",
+        &[
+            ("degraded", boolean("Any of the gaps below")),
+            ("pricing", string("\"fresh\", \"stale\", \"archived\" (--as-of) or \"unavailable\"")),
+            ("missingSources", array(string("Source name"), "Sources asked for by name that have no session files")),
+            ("skippedFiles", integer("Session files that yielded no usage: empty, unreadable or corrupt")),
+        ],
+    );
+    schema["properties"]["pricingError"] = string("Why prices could not be loaded, when unavailable");
+    schema
+}
+
+fn graph_meta() -> Value {
+    object(
+        "How and when the data was generated",
+        &[
+            ("generatedAt", string("RFC 3339 generation time")),
+            ("version", string("tokscale version that generated the data")),
+            ("dateRange", reference("dateRange")),
+        ],
+    )
+}
+
+fn graph_summary() -> Value {
+    object(
+        "Totals over the whole range",
+        &[
+            ("totalTokens", integer("Tokens of every kind")),
+            ("totalCost", number("USD cost")),
+            ("totalDays", integer("Days in the range")),
+            ("activeDays", integer("Days with any usage")),
+            ("averagePerDay", number("USD cost per active day")),
+            ("maxCostInSingleDay", number("Highest USD cost of any day")),
+            ("sources", array(string("Source name"), "Sources with any usage")),
+            ("models", array(string("Model id"), "Models with any usage")),
+        ],
+    )
+}
+
+fn year_summary() -> Value {
+    object(
+        "Totals for one calendar year",
+        &[
+            ("year", string("Year as YYYY")),
+            ("totalTokens", integer("Tokens of every kind")),
+            ("totalCost", number("USD cost")),
+            ("range", reference("dateRange")),
+        ],
+    )
+}
+
+fn daily_contribution() -> Value {
+    let totals = object(
+        "Totals for the day",
+        &[
+            ("tokens", integer("Tokens of every kind")),
+            ("cost", number("USD cost")),
+            ("messages", integer("Assistant messages")),
+        ],
+    );
+    let mut intensity = integer("Activity level relative to the busiest day, 0-4");
+    intensity["minimum"] = json!(0);
+    intensity["maximum"] = json!(4);
+
+    object(
+        "Usage for one day",
+        &[
+            ("date", string("Day as YYYY-MM-DD")),
+            ("totals", totals),
+            ("intensity", intensity),
+            ("tokenBreakdown", reference("tokenBreakdown")),
+            ("sources", array(reference("sourceContribution"), "Usage per source and model")),
+        ],
+    )
+}
+
+fn source_contribution() -> Value {
+    object(
+        "Usage for one source and model on a day",
+        &[
+            ("source", string("Tool the usage came from, e.g. \"claude\"")),
+            ("modelId", string("Model id as recorded by the source")),
+            ("providerId", string("Model provider, e.g. \"anthropic\"")),
+            ("tokens", reference("tokenBreakdown")),
+            ("cost", number("USD cost")),
+            ("messages", integer("Assistant messages")),
+        ],
+    )
+}
+
+fn token_breakdown() -> Value {
+    let mut schema = object(
+        "Tokens by kind",
+        &[
+            ("input", integer("Input tokens")),
+            ("output", integer("Output tokens")),
+            ("cacheRead", integer("Cache read tokens")),
+            ("cacheWrite", integer("Cache write tokens")),
+            ("reasoning", integer("Reasoning tokens")),
+        ],
+    );
+    // Added in 1.1
+    schema["properties"]["training"] = integer("Fine-tuning training tokens");
+    schema
+}
+
+fn date_range() -> Value {
+    object(
+        "Inclusive range of days",
+        &[("start", string("First day as YYYY-MM-DD")), ("end", string("Last day as YYYY-MM-DD"))],
+    )
+}
+
+/// Object whose listed fields are all required
+fn object(description: &str, fields: &[(&str, Value)]) -> Value {
+    let properties: Map<String, Value> = fields.iter().map(|(name, schema)| (name.to_string(), schema.clone())).collect();
+    let required: Vec<&str> = fields.iter().map(|(name, _)| *name).collect();
+    json!({
+        "type": "object",
+        "description": description,
+        "properties": properties,
+        "required": required,
+    })
+}
+
+fn array(items: Value, description: &str) -> Value {
+    json!({ "type": "array", "description": description, "items": items })
+}
+
+fn reference(def: &str) -> Value {
+    json!({ "$ref": format!("#/$defs/{}", def) })
+}
+
+fn string(description: &str) -> Value {
+    json!({ "type": "string", "description": description })
+}
+
+fn integer(description: &str) -> Value {
+    json!({ "type": "integer", "description": description })
+}
+
+fn number(description: &str) -> Value {
+    json!({ "type": "number", "description": description })
+}
+
+/// A number from 0 to 1
+fn fraction(description: &str) -> Value {
+    json!({ "type": "number", "description": format!("{}, 0-1", description), "minimum": 0, "maximum": 1 })
+}
+
+fn boolean(description: &str) -> Value {
+    json!({ "type": "boolean", "description": description })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        DailyTotals, Degradation, GraphMeta, ModelReport, ModelUsage, MonthlyReport, MonthlyUsage, RunSummary, SourceContribution,
+        SourceFiles, TokenBreakdown, YearSummary,
+    };
+    use serde::de::DeserializeOwned;
+    use std::fs;
+    use std::path::PathBuf;
+
+    fn schema_dir() -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/schema")
+    }
+
+    fn resolve<'a>(root: &'a Value, node: &'a Value) -> &'a Value {
+        match node.get("$ref").and_then(Value::as_str) {
+            Some(reference) => {
+                let name = reference.trim_start_matches("#/$defs/");
+                root["$defs"].get(name).unwrap_or_else(|| panic!("dangling $ref {}", reference))
+            }
+            None => node,
+        }
+    }
+
+    /// Checks the subset of JSON Schema used above. `strict` also rejects
+    /// properties the schema doesn't list.
+    fn validate(root: &Value, node: &Value, value: &Value, path: &str, strict: bool, errors: &mut Vec<String>) {
+        let node = resolve(root, node);
+        let type_ok = match node.get("type").and_then(Value::as_str) {
+            Some("object") => value.is_object(),
+            Some("array") => value.is_array(),
+            Some("string") => value.is_string(),
+            Some("integer") => value.is_i64() || value.is_u64(),
+            Some("number") => value.is_number(),
+            _ => true,
+        };
+        if !type_ok {
+            errors.push(format!("{}: expected {}, got {}", path, node["type"], value));
+            return;
+        }
+
+        if let Some(n) = value.as_f64() {
+            if node.get("minimum").and_then(Value::as_f64).is_some_and(|min| n < min)
+                || node.get("maximum").and_then(Value::as_f64).is_some_and(|max| n > max)
+            {
+                errors.push(format!("{}: {} out of range", path, n));
+            }
+        }
+        if let Some(object) = value.as_object() {
+            let properties = node["properties"].as_object().cloned().unwrap_or_default();
+            for required in node["required"].as_array().into_iter().flatten().filter_map(Value::as_str) {
+                if !object.contains_key(required) {
+                    errors.push(format!("{}: missing {}", path, required));
+                }
+            }
+            for (key, field) in object {
+                match properties.get(key) {
+                    Some(schema) => validate(root, schema, field, &format!("{}.{}", path, key), strict, errors),
+                    None if strict => errors.push(format!("{}: unexpected {}", path, key)),
+                    None => {}
+                }
+            }
+        }
+        if let (Some(items), Some(array)) = (node.get("items"), value.as_array()) {
+            for (i, item) in array.iter().enumerate() {
+                validate(root, items, item, &format!("{}[{}]", path, i), strict, errors);
+            }
+        }
+    }
+
+    fn validation_errors(kind: ReportKind, value: &Value, strict: bool) -> Vec<String> {
+        let schema = json_schema(kind);
+        let mut errors = Vec::new();
+        validate(&schema, &schema, value, "$", strict, &mut errors);
+        errors
+    }
+
+    /// Changes from `old` to `new` that would break a reader or an old document
+    fn breaking_changes(old_root: &Value, old: &Value, new_root: &Value, new: &Value, path: &str, out: &mut Vec<String>) {
+        let (old, new) = (resolve(old_root, old), resolve(new_root, new));
+        if old.get("type") != new.get("type") {
+            out.push(format!("{}: type changed from {} to {}", path, old["type"], new["type"]));
+            return;
+        }
+
+        let required = |node: &Value| -> Vec<String> {
+            node["required"].as_array().into_iter().flatten().filter_map(|r| r.as_str().map(String::from)).collect()
+        };
+        let (old_required, new_required) = (required(old), required(new));
+        for name in &new_required {
+            if !old_required.contains(name) {
+                out.push(format!("{}: new field {} is required", path, name));
+            }
+        }
+        if let Some(old_properties) = old["properties"].as_object() {
+            for (name, old_field) in old_properties {
+                let Some(new_field) = new["properties"].get(name) else {
+                    out.push(format!("{}: field {} removed", path, name));
+                    continue;
+                };
+                if old_required.contains(name) && !new_required.contains(name) {
+                    out.push(format!("{}: field {} no longer required", path, name));
+                }
+                breaking_changes(old_root, old_field, new_root, new_field, &format!("{}.{}", path, name), out);
+            }
+        }
+        if let (Some(old_items), Some(new_items)) = (old.get("items"), new.get("items")) {
+            breaking_changes(old_root, old_items, new_root, new_items, &format!("{}[]", path), out);
+        }
+    }
+
+    /// Schema type and node, seeing through `$ref`s and the null branch
+    /// schemars adds for `Option`
+    fn shape<'a>(root: &'a Value, node: &'a Value) -> (String, &'a Value) {
+        let node = resolve(root, node);
+        if let Some(branch) = node["anyOf"].as_array().and_then(|branches| {
+            branches.iter().find(|branch| branch["type"] != "null")
+        }) {
+            return shape(root, branch);
+        }
+        let ty = match &node["type"] {
+            Value::Array(types) => types.iter().filter_map(Value::as_str).find(|ty| *ty != "null").unwrap_or_default(),
+            ty => ty.as_str().unwrap_or_default(),
+        };
+        (ty.to_string(), node)
+    }
+
+    /// Where the committed schema and the one derived from the Rust types
+    /// disagree on fields, required fields or types
+    fn structural_differences(
+        committed_root: &Value,
+        committed: &Value,
+        derived_root: &Value,
+        derived: &Value,
+        path: &str,
+        out: &mut Vec<String>,
+    ) {
+        let (committed_type, committed) = shape(committed_root, committed);
+        let (derived_type, derived) = shape(derived_root, derived);
+        if committed_type != derived_type {
+            out.push(format!("{}: schema says {}, Rust type is {}", path, committed_type, derived_type));
+            return;
+        }
+        if committed_type == "array" {
+            let path = format!("{}[]", path);
+            structural_differences(committed_root, &committed["items"], derived_root, &derived["items"], &path, out);
+            return;
+        }
+        if committed_type != "object" {
+            return;
+        }
+
+        let keys = |node: &Value, list: &str| -> Vec<String> {
+            let mut keys: Vec<String> = match &node[list] {
+                Value::Object(properties) => properties.keys().cloned().collect(),
+                Value::Array(names) => names.iter().filter_map(Value::as_str).map(str::to_string).collect(),
+                _ => Vec::new(),
+            };
+            keys.sort();
+            keys
+        };
+        let (committed_fields, derived_fields) = (keys(committed, "properties"), keys(derived, "properties"));
+        for field in committed_fields.iter().filter(|f| !derived_fields.contains(f)) {
+            out.push(format!("{}: {} is in the schema but not the Rust type", path, field));
+        }
+        for field in derived_fields.iter().filter(|f| !committed_fields.contains(f)) {
+            out.push(format!("{}: {} is in the Rust type but not the schema", path, field));
+        }
+        if keys(committed, "required") != keys(derived, "required") {
+            out.push(format!(
+                "{}: schema requires {:?}, Rust type requires {:?}",
+                path,
+                keys(committed, "required"),
+                keys(derived, "required")
+            ));
+        }
+        for field in committed_fields.iter().filter(|f| derived_fields.contains(f)) {
+            structural_differences(
+                committed_root,
+                &committed["properties"][field],
+                derived_root,
+                &derived["properties"][field],
+                &format!("{}.{}", path, field),
+                out,
+            );
+        }
+    }
+
+    fn major(version: &str) -> &str {
+        version.split('.').next().unwrap_or(version)
+    }
+
+    fn tokens() -> TokenBreakdown {
+        TokenBreakdown { input: 1200, output: 340, cache_read: 5000, cache_write: 800, reasoning: 60, training: 0 }
+    }
+
+    fn model_report() -> ModelReport {
+        ModelReport {
+            entries: vec![ModelUsage {
+                source: "claude".into(),
+                model: "claude-sonnet-4".into(),
+                provider: "anthropic".into(),
+                input: 1200,
+                output: 340,
+                cache_read: 5000,
+                cache_write: 800,
+                reasoning: 60,
+                training: 0,
+                message_count: 3,
+                cost: 0.0123,
+                covered: 0.0,
+                cost_share: 1.0,
+                token_share: 1.0,
+            }],
+            total_input: 1200,
+            total_output: 340,
+            total_cache_read: 5000,
+            total_cache_write: 800,
+            total_reasoning: 60,
+            total_messages: 3,
+            total_cost: 0.0123,
+            total_covered: 0.0,
+            processing_time_ms: 12,
+            summary: Some(RunSummary {
+                sources: vec![SourceFiles { source: "claude".into(), files: 2, parsed: 1, skipped: 1, named: false }],
+                messages: 3,
+                messages_deduped: 1,
+                unknown_models: vec!["in-house-model".into()],
+                unknown_model_messages: 1,
+                pricing_fetched_at: Some(1_740_787_200),
+                pricing_age_hours: Some(0.5),
+                pricing_stale: false,
+                exact_cost: 0.0,
+                estimated_cost: 0.0123,
+                exact_cost_share: 0.0,
+                degradation: Degradation {
+                    degraded: true,
+                    pricing: "fresh".into(),
+                    pricing_error: None,
+                    missing_sources: Vec::new(),
+                    skipped_files: 1,
+                },
+            }),
+            families: None,
+        }
+    }
+
+    fn monthly_report() -> MonthlyReport {
+        MonthlyReport {
+            entries: vec![MonthlyUsage {
+                month: "2025-03".into(),
+                models: vec!["claude-sonnet-4".into(), "gpt-5".into()],
+                input: 1200,
+                output: 340,
+                cache_read: 5000,
+                cache_write: 800,
+                message_count: 3,
+                cost: 0.0123,
+                cost_share: 1.0,
+                token_share: 1.0,
+            }],
+            total_input: 1200,
+            total_output: 340,
+            total_cache_read: 5000,
+            total_cache_write: 800,
+            total_messages: 3,
+            total_cost: 0.0123,
+            processing_time_ms: 7,
+            summary: None,
+        }
+    }
+
+    fn graph_export() -> GraphExport {
+        GraphExport::from(GraphResult {
+            meta: GraphMeta {
+                generated_at: "2025-03-02T00:00:00Z".into(),
+                version: "1.0.0".into(),
+                date_range_start: "2025-03-01".into(),
+                date_range_end: "2025-03-01".into(),
+                processing_time_ms: 5,
+            },
+            summary: DataSummary {
+                total_tokens: 7400,
+                total_cost: 0.0123,
+                total_days: 1,
+                active_days: 1,
+                average_per_day: 0.0123,
+                max_cost_in_single_day: 0.0123,
+                sources: vec!["claude".into()],
+                models: vec!["claude-sonnet-4".into()],
+            },
+            years: vec![YearSummary {
+                year: "2025".into(),
+                total_tokens: 7400,
+                total_cost: 0.0123,
+                range_start: "2025-03-01".into(),
+                range_end: "2025-03-01".into(),
+            }],
+            contributions: vec![DailyContribution {
+                date: "2025-03-01".into(),
+                totals: DailyTotals { tokens: 7400, cost: 0.0123, messages: 3 },
+                intensity: 4,
+                token_breakdown: tokens(),
+                sources: vec![SourceContribution {
+                    source: "claude".into(),
+                    model_id: "claude-sonnet-4".into(),
+                    provider_id: "anthropic".into(),
+                    tokens: tokens(),
+                    cost: 0.0123,
+                    messages: 3,
+                }],
+            }],
+        })
+    }
+
+    fn assert_round_trip<T: Serialize + DeserializeOwned>(kind: ReportKind, report: &T) {
+        let value = serde_json::to_value(report).unwrap();
+        let errors = validation_errors(kind, &value, true);
+        assert!(errors.is_empty(), "{} report doesn't match its schema: {:?}", kind.as_str(), errors);
+
+        let parsed: T = serde_json::from_value(value.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&parsed).unwrap(), value);
+    }
+
+    #[test]
+    fn test_kind_round_trip() {
+        for kind in ReportKind::ALL {
+            assert_eq!(ReportKind::parse(kind.as_str()), Some(kind));
+        }
+        assert_eq!(ReportKind::parse("daily"), None);
+    }
+
+    #[test]
+    fn test_reports_match_schema() {
+        assert_round_trip(ReportKind::Models, &model_report());
+        assert_round_trip(ReportKind::Monthly, &monthly_report());
+        assert_round_trip(ReportKind::Graph, &graph_export());
+    }
+
+    #[test]
+    fn test_validation_errors() {
+        let mut value = serde_json::to_value(model_report()).unwrap();
+        value["entries"][0]["cost"] = json!("free");
+        value.as_object_mut().unwrap().remove("totalCost");
+        let errors = validation_errors(ReportKind::Models, &value, false);
+        assert_eq!(errors, vec!["$: missing totalCost", "$.entries[0].cost: expected \"number\", got \"free\""]);
+
+        let mut graph = serde_json::to_value(graph_export()).unwrap();
+        graph["contributions"][0]["intensity"] = json!(5);
+        assert_eq!(validation_errors(ReportKind::Graph, &graph, false).len(), 1);
+    }
+
+    #[test]
+    fn test_breaking_changes_detected() {
+        let old = json_schema(ReportKind::Models);
+        let mut new = old.clone();
+        new["$defs"]["modelUsage"]["properties"]
+            .as_object_mut()
+            .unwrap()
+            .remove("reasoning");
+        new["properties"]["totalCost"]["type"] = json!("string");
+        new["properties"]["totalCacheHits"] = integer("Added optional field");
+
+        let mut changes = Vec::new();
+        breaking_changes(&old, &old, &new, &new, "$", &mut changes);
+        assert_eq!(
+            changes,
+            vec![
+                "$.entries[]: field reasoning removed",
+                "$.families[].models[]: field reasoning removed",
+                "$.totalCost: type changed from \"number\" to \"string\"",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_report_schema_matches_committed() {
+        let update = std::env::var_os("UPDATE_GOLDEN").is_some();
+        let mut failures = Vec::new();
+
+        for kind in ReportKind::ALL {
+            let path = schema_dir().join(format!("{}.schema.json", kind.as_str()));
+            let generated = json_schema(kind);
+            let committed: Option<Value> = fs::read(&path).ok().map(|bytes| serde_json::from_slice(&bytes).unwrap());
+
+            if let Some(committed) = &committed {
+                let committed_version = committed["x-schema-version"].as_str().unwrap_or_default();
+                if *committed == generated {
+                    continue;
+                }
+                if committed_version == SCHEMA_VERSION {
+                    failures.push(format!("{}: schema changed without bumping SCHEMA_VERSION", kind.as_str()));
+                    continue;
+                }
+                if major(committed_version) == major(SCHEMA_VERSION) {
+                    let mut changes = Vec::new();
+                    breaking_changes(committed, committed, &generated, &generated, "$", &mut changes);
+                    if !changes.is_empty() {
+                        failures.push(format!("{}: breaking changes need a major version: {:?}", kind.as_str(), changes));
+                        continue;
+                    }
+                }
+            }
+
+            if update {
+                fs::create_dir_all(schema_dir()).unwrap();
+                fs::write(&path, serde_json::to_string_pretty(&generated).unwrap() + "\n").unwrap();
+            } else {
+                failures.push(format!(
+                    "{}: generated schema differs from {}; rerun with UPDATE_GOLDEN=1",
+                    kind.as_str(),
+                    path.display()
+                ));
+            }
+        }
+
+        assert!(failures.is_empty(), "{}", failures.join("\n"));
+    }
+
+    #[test]
+    fn test_report_schema_matches_rust_types() {
+        let derived = [
+            (ReportKind::Models, schemars::schema_for!(ModelReport)),
+            (ReportKind::Monthly, schemars::schema_for!(MonthlyReport)),
+            (ReportKind::Graph, schemars::schema_for!(GraphExport)),
+        ];
+        let mut differences = Vec::new();
+        for (kind, derived) in derived {
+            let path = schema_dir().join(format!("{}.schema.json", kind.as_str()));
+            let committed: Value = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+            let mut derived = serde_json::to_value(derived).unwrap();
+            // Added by the CLI around the Rust report
+            derived["properties"]["schemaVersion"] = json!({ "type": "string" });
+            structural_differences(&committed, &committed, &derived, &derived, kind.as_str(), &mut differences);
+        }
+        assert!(differences.is_empty(), "{}", differences.join("\n"));
+    }
+
+    #[test]
+    fn test_v1_documents_still_accepted() {
+        fn check<T: DeserializeOwned>(kind: ReportKind) {
+            let path = schema_dir().join(format!("samples/v1/{}.json", kind.as_str()));
+            let value: Value = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+            let errors = validation_errors(kind, &value, false);
+            assert!(errors.is_empty(), "{}: {:?}", path.display(), errors);
+            serde_json::from_value::<T>(value).unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+        }
+
+        check::<ModelReport>(ReportKind::Models);
+        check::<MonthlyReport>(ReportKind::Monthly);
+        check::<GraphExport>(ReportKind::Graph);
+    }
+}
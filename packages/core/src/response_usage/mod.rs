@@ -0,0 +1,175 @@
+//! Token usage from OpenAI- and Anthropic-format API responses
+//!
+//! Complete JSON bodies are read with [`from_json`]; streamed
+//! (`text/event-stream`) responses, live or stored in logs, go through
+//! [`sse`]. Used by the metering proxy and by importers of raw API logs.
+
+pub mod sse;
+
+use crate::TokenBreakdown;
+use serde_json::Value;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApiFormat {
+    /// Chat Completions, Completions, Responses and embeddings, from OpenAI
+    /// or any OpenAI-compatible API
+    OpenAi,
+    Anthropic,
+}
+
+impl ApiFormat {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "openai" => Some(ApiFormat::OpenAi),
+            "anthropic" => Some(ApiFormat::Anthropic),
+            _ => None,
+        }
+    }
+
+    /// Guess the format of a streamed event from its `type`
+    pub fn detect(event: &Value) -> Self {
+        match event.get("type").and_then(Value::as_str) {
+            Some(t) if t.starts_with("message_") || t.starts_with("content_block_") || t == "ping" => {
+                ApiFormat::Anthropic
+            }
+            _ => ApiFormat::OpenAi,
+        }
+    }
+}
+
+/// What one response consumed
+#[derive(Debug, Clone, Default)]
+pub struct ResponseUsage {
+    /// Response id ("chatcmpl-...", "msg_..."), used to drop duplicates
+    pub id: Option<String>,
+    pub model: Option<String>,
+    pub tokens: TokenBreakdown,
+}
+
+/// Usage from a complete (non-streamed) response body. An OpenAI
+/// fine-tuning job counts its `trained_tokens` once it has finished.
+pub fn from_json(format: ApiFormat, body: &Value) -> Option<ResponseUsage> {
+    if format == ApiFormat::OpenAi && body.get("object").and_then(Value::as_str) == Some("fine_tuning.job") {
+        return fine_tuning_job(body);
+    }
+    let usage = body.get("usage").filter(|u| u.is_object())?;
+    Some(ResponseUsage {
+        id: str_field(body, "id"),
+        model: str_field(body, "model"),
+        tokens: match format {
+            ApiFormat::OpenAi => openai_tokens(usage),
+            ApiFormat::Anthropic => anthropic_tokens(usage),
+        },
+    })
+}
+
+/// OpenAI counts cached tokens inside the prompt and reasoning tokens inside
+/// the completion; both are split out so they are not priced twice.
+/// Covers Chat Completions (`prompt_tokens`), Responses (`input_tokens`) and
+/// embeddings (prompt only).
+fn openai_tokens(usage: &Value) -> TokenBreakdown {
+    let input = int(usage, "/prompt_tokens").or_else(|| int(usage, "/input_tokens")).unwrap_or(0);
+    let output = int(usage, "/completion_tokens").or_else(|| int(usage, "/output_tokens")).unwrap_or(0);
+    let cached = int(usage, "/prompt_tokens_details/cached_tokens")
+        .or_else(|| int(usage, "/input_tokens_details/cached_tokens"))
+        .unwrap_or(0);
+    let reasoning = int(usage, "/completion_tokens_details/reasoning_tokens")
+        .or_else(|| int(usage, "/output_tokens_details/reasoning_tokens"))
+        .unwrap_or(0);
+
+    TokenBreakdown {
+        input: (input - cached).max(0),
+        output: (output - reasoning).max(0),
+        cache_read: cached.min(input),
+        cache_write: 0,
+        reasoning: reasoning.min(output),
+        training: 0,
+    }
+}
+
+fn anthropic_tokens(usage: &Value) -> TokenBreakdown {
+    TokenBreakdown {
+        input: int(usage, "/input_tokens").unwrap_or(0),
+        output: int(usage, "/output_tokens").unwrap_or(0),
+        cache_read: int(usage, "/cache_read_input_tokens").unwrap_or(0),
+        cache_write: int(usage, "/cache_creation_input_tokens").unwrap_or(0),
+        reasoning: 0,
+        training: 0,
+    }
+}
+
+/// Training tokens are billed against the base model
+fn fine_tuning_job(job: &Value) -> Option<ResponseUsage> {
+    let trained = int(job, "/trained_tokens")?;
+    Some(ResponseUsage {
+        id: str_field(job, "id"),
+        model: str_field(job, "model"),
+        tokens: TokenBreakdown { training: trained, ..TokenBreakdown::default() },
+    })
+}
+
+fn int(value: &Value, pointer: &str) -> Option<i64> {
+    value.pointer(pointer).and_then(Value::as_i64).map(|n| n.max(0))
+}
+
+fn str_field(value: &Value, key: &str) -> Option<String> {
+    value.get(key).and_then(Value::as_str).map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_openai_chat_completion() {
+        let body = json!({
+            "id": "chatcmpl-1",
+            "model": "gpt-4o-2024-08-06",
+            "usage": {
+                "prompt_tokens": 1200,
+                "completion_tokens": 300,
+                "prompt_tokens_details": { "cached_tokens": 200 },
+                "completion_tokens_details": { "reasoning_tokens": 100 }
+            }
+        });
+        let usage = from_json(ApiFormat::OpenAi, &body).unwrap();
+        assert_eq!(usage.id.as_deref(), Some("chatcmpl-1"));
+        assert_eq!(usage.model.as_deref(), Some("gpt-4o-2024-08-06"));
+        assert_eq!(usage.tokens.input, 1000);
+        assert_eq!(usage.tokens.cache_read, 200);
+        assert_eq!(usage.tokens.output, 200);
+        assert_eq!(usage.tokens.reasoning, 100);
+    }
+
+    #[test]
+    fn test_openai_embeddings_and_missing_usage() {
+        let body = json!({ "model": "text-embedding-3-small", "usage": { "prompt_tokens": 8, "total_tokens": 8 } });
+        let usage = from_json(ApiFormat::OpenAi, &body).unwrap();
+        assert_eq!(usage.tokens.input, 8);
+        assert_eq!(usage.tokens.output, 0);
+
+        assert!(from_json(ApiFormat::OpenAi, &json!({ "error": { "message": "bad key" } })).is_none());
+    }
+
+    #[test]
+    fn test_openai_fine_tuning_job() {
+        let running = json!({ "object": "fine_tuning.job", "id": "ftjob-1", "model": "gpt-4o-mini-2024-07-18", "status": "running", "trained_tokens": null });
+        assert!(from_json(ApiFormat::OpenAi, &running).is_none());
+
+        let done = json!({ "object": "fine_tuning.job", "id": "ftjob-1", "model": "gpt-4o-mini-2024-07-18", "status": "succeeded", "trained_tokens": 5768 });
+        let usage = from_json(ApiFormat::OpenAi, &done).unwrap();
+        assert_eq!(usage.id.as_deref(), Some("ftjob-1"));
+        assert_eq!(usage.tokens.training, 5768);
+        assert_eq!(usage.tokens.input, 0);
+    }
+
+    #[test]
+    fn test_detect_format() {
+        assert_eq!(ApiFormat::detect(&json!({ "type": "message_start" })), ApiFormat::Anthropic);
+        assert_eq!(ApiFormat::detect(&json!({ "type": "response.completed" })), ApiFormat::OpenAi);
+        assert_eq!(ApiFormat::detect(&json!({ "object": "chat.completion.chunk" })), ApiFormat::OpenAi);
+        assert_eq!(ApiFormat::parse("Anthropic"), Some(ApiFormat::Anthropic));
+        assert_eq!(ApiFormat::parse("gemini"), None);
+    }
+}
@@ -0,0 +1,277 @@
+//! Usage from streamed (server-sent events) responses
+//!
+//! [`SseDecoder`] turns bytes into events as they arrive, however the stream
+//! is split into chunks. [`StreamUsage`] folds events into per-response usage:
+//!
+//! - OpenAI Chat Completions: the last chunk carries `usage` when the request
+//!   set `stream_options.include_usage`
+//! - OpenAI Responses: the `response.completed` event carries the response
+//! - Anthropic: `message_start` has the input and cache counts,
+//!   `message_delta` the running output count
+//!
+//! A stored log may hold several responses back to back; each one is reported
+//! separately.
+
+use super::{anthropic_tokens, from_json, ApiFormat, ResponseUsage};
+use serde_json::Value;
+
+/// One dispatched event
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SseEvent {
+    /// `event:` field, if any
+    pub event: Option<String>,
+    /// `data:` lines joined with newlines
+    pub data: String,
+}
+
+/// Incremental event-stream parser
+#[derive(Debug, Default)]
+pub struct SseDecoder {
+    /// Bytes after the last complete line
+    partial: Vec<u8>,
+    event: Option<String>,
+    data: Option<String>,
+}
+
+impl SseDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Events completed by `bytes`
+    pub fn feed(&mut self, bytes: &[u8]) -> Vec<SseEvent> {
+        self.partial.extend_from_slice(bytes);
+        let mut events = Vec::new();
+        while let Some(end) = self.partial.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.partial.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line[..end]);
+            if let Some(event) = self.line(line.strip_suffix('\r').unwrap_or(&line)) {
+                events.push(event);
+            }
+        }
+        events
+    }
+
+    /// The last event, for streams that end without a blank line (common in
+    /// saved logs)
+    pub fn finish(&mut self) -> Option<SseEvent> {
+        let rest = std::mem::take(&mut self.partial);
+        let rest = String::from_utf8_lossy(&rest);
+        let line = rest.strip_suffix('\r').unwrap_or(&rest);
+        self.line(line).or_else(|| self.line(""))
+    }
+
+    fn line(&mut self, line: &str) -> Option<SseEvent> {
+        if line.is_empty() {
+            let event = self.event.take();
+            return self.data.take().map(|data| SseEvent { event, data });
+        }
+        if line.starts_with(':') {
+            return None; // comment / keep-alive
+        }
+
+        let (field, value) = line.split_once(':').unwrap_or((line, ""));
+        let value = value.strip_prefix(' ').unwrap_or(value);
+        match field {
+            "event" => self.event = Some(value.to_string()),
+            "data" => match self.data.as_mut() {
+                Some(data) => {
+                    data.push('\n');
+                    data.push_str(value);
+                }
+                None => self.data = Some(value.to_string()),
+            },
+            _ => {} // id, retry and unknown fields don't affect usage
+        }
+        None
+    }
+}
+
+/// Accumulates usage across the events of one or more streamed responses
+#[derive(Debug, Default)]
+pub struct StreamUsage {
+    /// `None` to detect from the first JSON event
+    format: Option<ApiFormat>,
+    current: Option<ResponseUsage>,
+    finished: Vec<ResponseUsage>,
+}
+
+impl StreamUsage {
+    pub fn new(format: Option<ApiFormat>) -> Self {
+        Self { format, ..Default::default() }
+    }
+
+    pub fn push(&mut self, event: &SseEvent) {
+        // "[DONE]" and other non-JSON payloads carry no usage
+        if let Ok(value) = serde_json::from_str::<Value>(&event.data) {
+            self.push_json(&value);
+        }
+    }
+
+    /// Add one event's JSON payload, for logs that store events already decoded
+    pub fn push_json(&mut self, event: &Value) {
+        let format = *self.format.get_or_insert_with(|| ApiFormat::detect(event));
+        match format {
+            ApiFormat::OpenAi => self.openai_event(event),
+            ApiFormat::Anthropic => self.anthropic_event(event),
+        }
+    }
+
+    /// Usage of the response in progress (or the last one)
+    pub fn current(&self) -> Option<&ResponseUsage> {
+        self.current.as_ref()
+    }
+
+    /// Usage of every response seen, in order
+    pub fn finish(mut self) -> Vec<ResponseUsage> {
+        self.finished.extend(self.current.take());
+        self.finished
+    }
+
+    fn start(&mut self, usage: ResponseUsage) {
+        self.finished.extend(self.current.replace(usage));
+    }
+
+    fn openai_event(&mut self, event: &Value) {
+        let body = event.get("response").filter(|r| r.is_object()).unwrap_or(event);
+        let Some(usage) = from_json(ApiFormat::OpenAi, body) else {
+            return;
+        };
+        match &mut self.current {
+            // Later usage for the same response supersedes earlier usage
+            Some(current) if current.id == usage.id => *current = usage,
+            _ => self.start(usage),
+        }
+    }
+
+    fn anthropic_event(&mut self, event: &Value) {
+        match event.get("type").and_then(Value::as_str) {
+            Some("message_start") => {
+                if let Some(usage) = event.get("message").and_then(|m| from_json(ApiFormat::Anthropic, m)) {
+                    self.start(usage);
+                }
+            }
+            // Counts so far; any field present replaces the earlier value
+            Some("message_delta") => {
+                let (Some(current), Some(delta)) = (self.current.as_mut(), event.get("usage")) else {
+                    return;
+                };
+                let update = anthropic_tokens(delta);
+                for (value, pointer, new) in [
+                    (&mut current.tokens.output, "/output_tokens", update.output),
+                    (&mut current.tokens.input, "/input_tokens", update.input),
+                    (&mut current.tokens.cache_read, "/cache_read_input_tokens", update.cache_read),
+                    (&mut current.tokens.cache_write, "/cache_creation_input_tokens", update.cache_write),
+                ] {
+                    if delta.pointer(pointer).is_some() {
+                        *value = new;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Usage of every response in a complete event stream, e.g. a stored log
+pub fn parse_sse(text: &str, format: Option<ApiFormat>) -> Vec<ResponseUsage> {
+    let mut decoder = SseDecoder::new();
+    let mut usage = StreamUsage::new(format);
+    for event in decoder.feed(text.as_bytes()).iter().chain(decoder.finish().iter()) {
+        usage.push(event);
+    }
+    usage.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ANTHROPIC_STREAM: &str = concat!(
+        "event: message_start\n",
+        "data: {\"type\":\"message_start\",\"message\":{\"id\":\"msg_3\",\"model\":\"claude-sonnet-4\",\"usage\":{\"input_tokens\":25,\"cache_creation_input_tokens\":40,\"output_tokens\":1}}}\n\n",
+        "event: ping\n",
+        "data: {\"type\":\"ping\"}\n\n",
+        "event: content_block_delta\n",
+        "data: {\"type\":\"content_block_delta\",\"delta\":{\"text\":\"Hello\"}}\n\n",
+        "event: message_delta\n",
+        "data: {\"type\":\"message_delta\",\"usage\":{\"output_tokens\":15}}\n\n",
+        "event: message_stop\n",
+        "data: {\"type\":\"message_stop\"}\n\n",
+    );
+
+    #[test]
+    fn test_decoder_framing() {
+        let stream = b": keep-alive\r\nevent: update\r\ndata: first\r\ndata: second\r\n\r\ndata:no-space\n\nid: 7\n\n";
+        let mut decoder = SseDecoder::new();
+        let mut events = Vec::new();
+        for piece in stream.chunks(3) {
+            events.extend(decoder.feed(piece));
+        }
+        assert_eq!(
+            events,
+            vec![
+                SseEvent { event: Some("update".to_string()), data: "first\nsecond".to_string() },
+                SseEvent { event: None, data: "no-space".to_string() },
+            ]
+        );
+        assert_eq!(decoder.finish(), None);
+    }
+
+    #[test]
+    fn test_decoder_finish_without_blank_line() {
+        let mut decoder = SseDecoder::new();
+        assert!(decoder.feed(b"data: {\"a\":1}\ndata: tail").is_empty());
+        assert_eq!(decoder.finish().unwrap().data, "{\"a\":1}\ntail");
+    }
+
+    #[test]
+    fn test_anthropic_stream() {
+        let usage = parse_sse(ANTHROPIC_STREAM, Some(ApiFormat::Anthropic));
+        assert_eq!(usage.len(), 1);
+        assert_eq!(usage[0].id.as_deref(), Some("msg_3"));
+        assert_eq!(usage[0].tokens.input, 25);
+        assert_eq!(usage[0].tokens.cache_write, 40);
+        assert_eq!(usage[0].tokens.output, 15);
+    }
+
+    #[test]
+    fn test_log_with_several_responses() {
+        let log = format!("{}{}", ANTHROPIC_STREAM, ANTHROPIC_STREAM.replace("msg_3", "msg_4"));
+        let usage = parse_sse(&log, None);
+        assert_eq!(usage.len(), 2);
+        assert_eq!(usage[1].id.as_deref(), Some("msg_4"));
+        assert_eq!(usage[1].tokens.output, 15);
+    }
+
+    #[test]
+    fn test_openai_streams() {
+        let chat = concat!(
+            "data: {\"id\":\"chatcmpl-2\",\"object\":\"chat.completion.chunk\",\"model\":\"gpt-4o\",\"choices\":[{\"delta\":{\"content\":\"Hi\"}}],\"usage\":null}\n\n",
+            "data: {\"id\":\"chatcmpl-2\",\"object\":\"chat.completion.chunk\",\"model\":\"gpt-4o\",\"choices\":[],\"usage\":{\"prompt_tokens\":9,\"completion_tokens\":2}}\n\n",
+            "data: [DONE]\n\n",
+        );
+        let usage = parse_sse(chat, None);
+        assert_eq!(usage.len(), 1);
+        assert_eq!(usage[0].tokens.input, 9);
+        assert_eq!(usage[0].tokens.output, 2);
+
+        let responses = concat!(
+            "event: response.created\n",
+            "data: {\"type\":\"response.created\",\"response\":{\"id\":\"resp_1\",\"model\":\"o3\",\"usage\":null}}\n\n",
+            "event: response.completed\n",
+            "data: {\"type\":\"response.completed\",\"response\":{\"id\":\"resp_1\",\"model\":\"o3\",\"usage\":{\"input_tokens\":50,\"output_tokens\":30,\"output_tokens_details\":{\"reasoning_tokens\":20}}}}\n\n",
+        );
+        let usage = parse_sse(responses, Some(ApiFormat::OpenAi));
+        assert_eq!(usage.len(), 1);
+        assert_eq!(usage[0].id.as_deref(), Some("resp_1"));
+        assert_eq!(usage[0].tokens.output, 10);
+        assert_eq!(usage[0].tokens.reasoning, 20);
+    }
+
+    #[test]
+    fn test_stream_without_usage() {
+        let stream = "data: {\"id\":\"chatcmpl-5\",\"choices\":[{\"delta\":{}}]}\n\ndata: [DONE]\n\n";
+        assert!(parse_sse(stream, None).is_empty());
+    }
+}
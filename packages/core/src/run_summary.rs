@@ -0,0 +1,136 @@
+//! Run summary
+//!
+//! Model and monthly reports end with a summary of what went into them, so
+//! the numbers can be judged before they are trusted:
+//!
+//! - each source scanned, its session files, and how many of them yielded
+//!   usage (the rest were empty, unreadable or held none);
+//! - messages dropped as duplicates of ones already counted;
+//! - models without a list price, whose messages cost nothing unless their
+//!   source reported a cost;
+//! - how long ago the price tables were fetched;
+//! - how much of the cost providers billed, against how much was estimated
+//!   from token counts and list prices;
+//! - what the report is missing (see [`crate::degradation`]).
+
+use crate::degradation;
+use crate::pricing::PricingService;
+use crate::sessions::UnifiedMessage;
+use crate::{RunSummary, SourceFiles};
+use std::collections::{BTreeSet, HashMap};
+
+/// Price tables fetched longer ago than this are flagged as stale
+pub const STALE_AFTER_HOURS: f64 = 24.0;
+
+/// Files of one source, given the messages each of them yielded. `named`
+/// when the source was asked for by name.
+pub fn count_files(source: &str, per_file: &[Vec<UnifiedMessage>], named: bool) -> SourceFiles {
+    let parsed = per_file.iter().filter(|messages| !messages.is_empty()).count() as u32;
+    SourceFiles {
+        source: source.to_string(),
+        files: per_file.len() as u32,
+        parsed,
+        skipped: per_file.len() as u32 - parsed,
+        named,
+    }
+}
+
+/// Models in `messages` without a list price in `pricing`, and how many
+/// messages used them. Messages with a billed cost don't need one.
+pub fn unknown_models(messages: &[UnifiedMessage], pricing: &PricingService) -> (Vec<String>, i64) {
+    let mut listed: HashMap<(&str, &str), bool> = HashMap::new();
+    let mut unknown_models = BTreeSet::new();
+    let mut unknown_model_messages = 0;
+    for msg in messages.iter().filter(|m| !m.billed) {
+        let key = (msg.model_id.as_str(), msg.provider_id.as_str());
+        let has_price = *listed
+            .entry(key)
+            .or_insert_with(|| pricing.lookup_with_provider(key.0, Some(key.1)).is_some());
+        if !has_price {
+            unknown_models.insert(msg.model_id.clone());
+            unknown_model_messages += 1;
+        }
+    }
+    (unknown_models.into_iter().collect(), unknown_model_messages)
+}
+
+/// Summary of a report over `messages` priced with `pricing`, whose source
+/// files are `files` and from which `deduped` duplicates were dropped.
+/// Costs are before free tiers and credits.
+pub fn build(
+    messages: &[UnifiedMessage],
+    files: Vec<SourceFiles>,
+    deduped: i64,
+    pricing: &PricingService,
+    now_secs: u64,
+) -> RunSummary {
+    let mut exact_cost = 0.0;
+    let mut estimated_cost = 0.0;
+    for msg in messages {
+        if msg.billed {
+            exact_cost += msg.cost;
+        } else {
+            estimated_cost += msg.cost;
+        }
+    }
+    let (unknown_models, unknown_model_messages) = unknown_models(messages, pricing);
+
+    let fetched_at = pricing.fetched_at();
+    let age_hours = fetched_at.map(|at| now_secs.saturating_sub(at) as f64 / 3600.0);
+    let total = exact_cost + estimated_cost;
+    let pricing_stale = age_hours.is_some_and(|hours| hours > STALE_AFTER_HOURS);
+    let degradation = degradation::assess(&files, pricing, pricing_stale);
+    RunSummary {
+        sources: files,
+        messages: messages.len() as i64,
+        messages_deduped: deduped,
+        unknown_models,
+        unknown_model_messages,
+        pricing_fetched_at: fetched_at.map(|at| at as i64),
+        pricing_age_hours: age_hours,
+        pricing_stale,
+        exact_cost,
+        estimated_cost,
+        exact_cost_share: if total > 0.0 { exact_cost / total } else { 0.0 },
+        degradation,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pricing::ModelPricing;
+    use crate::testutil::{message, TestMessage};
+
+    const T0: i64 = 1_748_779_200_000;
+
+    #[test]
+    fn test_count_files() {
+        let per_file = vec![vec![message("s1", T0, 0.0)], Vec::new(), Vec::new()];
+        let files = count_files("claude", &per_file, false);
+        assert_eq!((files.files, files.parsed, files.skipped), (3, 1, 2));
+    }
+
+    #[test]
+    fn test_build() {
+        let sonnet = ModelPricing { input_cost_per_token: Some(3e-6), ..ModelPricing::default() };
+        let pricing = PricingService::new(HashMap::from([("claude-sonnet-4".to_string(), sonnet)]), HashMap::new());
+        let messages = vec![
+            message("s1", T0, 3.0),
+            message("s1", T0, 0.0).with_model("in-house-model-x"),
+            message("s1", T0, 0.0).with_model("in-house-model-x"),
+            // Billed cost counts as exact whether or not there's a list price
+            message("s1", T0, 1.0).with_model("in-house-model-y").with_billed(true),
+        ];
+        let summary = build(&messages, Vec::new(), 5, &pricing, 1_748_779_200);
+
+        assert_eq!(summary.messages, 4);
+        assert_eq!(summary.messages_deduped, 5);
+        assert_eq!(summary.unknown_models, ["in-house-model-x"]);
+        assert_eq!(summary.unknown_model_messages, 2);
+        assert_eq!((summary.exact_cost, summary.estimated_cost), (1.0, 3.0));
+        assert_eq!(summary.exact_cost_share, 0.25);
+        // Hand-built tables were never fetched
+        assert_eq!((summary.pricing_age_hours, summary.pricing_stale), (None, false));
+    }
+}
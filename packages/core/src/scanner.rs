@@ -2,6 +2,7 @@
 //!
 //! Uses walkdir with rayon for parallel directory traversal.
 
+use crate::archive;
 use rayon::prelude::*;
 use std::path::PathBuf;
 use walkdir::WalkDir;
@@ -16,6 +17,56 @@ pub enum SessionType {
     Cursor,
     Amp,
     Droid,
+    Cline,
+    RooCode,
+    Recorded,
+}
+
+impl SessionType {
+    /// Every supported source, in scan order
+    pub const ALL: [SessionType; 10] = [
+        SessionType::OpenCode,
+        SessionType::Claude,
+        SessionType::Codex,
+        SessionType::Gemini,
+        SessionType::Cursor,
+        SessionType::Amp,
+        SessionType::Droid,
+        SessionType::Cline,
+        SessionType::RooCode,
+        SessionType::Recorded,
+    ];
+
+    /// Source name as used in `sources` filters and `UnifiedMessage::source`
+    pub fn name(&self) -> &'static str {
+        match self {
+            SessionType::OpenCode => "opencode",
+            SessionType::Claude => "claude",
+            SessionType::Codex => "codex",
+            SessionType::Gemini => "gemini",
+            SessionType::Cursor => "cursor",
+            SessionType::Amp => "amp",
+            SessionType::Droid => "droid",
+            SessionType::Cline => "cline",
+            SessionType::RooCode => "roocode",
+            SessionType::Recorded => "recorded",
+        }
+    }
+
+    /// Pattern of session file names, as taken by [`scan_directory`]
+    pub fn file_pattern(&self) -> &'static str {
+        match self {
+            SessionType::OpenCode => "*.json",
+            SessionType::Claude => "*.jsonl",
+            SessionType::Codex => "*.jsonl",
+            SessionType::Gemini => "session-*.json",
+            SessionType::Cursor => "*.csv",
+            SessionType::Amp => "T-*.json",
+            SessionType::Droid => "*.settings.json",
+            SessionType::Cline | SessionType::RooCode => "ui_messages.json",
+            SessionType::Recorded => "*.jsonl",
+        }
+    }
 }
 
 /// Result of scanning all session directories
@@ -28,6 +79,9 @@ pub struct ScanResult {
     pub cursor_files: Vec<PathBuf>,
     pub amp_files: Vec<PathBuf>,
     pub droid_files: Vec<PathBuf>,
+    pub cline_files: Vec<PathBuf>,
+    pub roocode_files: Vec<PathBuf>,
+    pub recorded_files: Vec<PathBuf>,
 }
 
 impl ScanResult {
@@ -40,6 +94,9 @@ impl ScanResult {
             + self.cursor_files.len()
             + self.amp_files.len()
             + self.droid_files.len()
+            + self.cline_files.len()
+            + self.roocode_files.len()
+            + self.recorded_files.len()
     }
 
     /// Get all files as a single vector
@@ -67,12 +124,22 @@ impl ScanResult {
         for path in &self.droid_files {
             result.push((SessionType::Droid, path.clone()));
         }
+        for path in &self.cline_files {
+            result.push((SessionType::Cline, path.clone()));
+        }
+        for path in &self.roocode_files {
+            result.push((SessionType::RooCode, path.clone()));
+        }
+        for path in &self.recorded_files {
+            result.push((SessionType::Recorded, path.clone()));
+        }
 
         result
     }
 }
 
-/// Scan a single directory for session files
+/// Scan a single directory for session files, compressed or not, and
+/// tarballs (see [`crate::archive`])
 pub fn scan_directory(root: &str, pattern: &str) -> Vec<PathBuf> {
     if !std::path::Path::new(root).exists() {
         return Vec::new();
@@ -88,7 +155,10 @@ pub fn scan_directory(root: &str, pattern: &str) -> Vec<PathBuf> {
                 return false;
             }
 
-            let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+            if archive::is_tarball(path) {
+                return true;
+            }
+            let file_name = archive::strip(path.file_name().and_then(|n| n.to_str()).unwrap_or(""));
 
             match pattern {
                 "*.json" => file_name.ends_with(".json"),
@@ -101,6 +171,7 @@ pub fn scan_directory(root: &str, pattern: &str) -> Vec<PathBuf> {
                     file_name.starts_with("T-") && file_name.ends_with(".json")
                 }
                 "*.settings.json" => file_name.ends_with(".settings.json"),
+                "ui_messages.json" => file_name == "ui_messages.json",
                 _ => false,
             }
         })
@@ -108,10 +179,45 @@ pub fn scan_directory(root: &str, pattern: &str) -> Vec<PathBuf> {
         .collect()
 }
 
-/// Scan all session source directories in parallel
-pub fn scan_all_sources(home_dir: &str, sources: &[String]) -> ScanResult {
-    let mut result = ScanResult::default();
+/// VS Code and the editors forked from it, by their user data directory name
+const VSCODE_EDITORS: &[&str] = &["Code", "Code - Insiders", "VSCodium", "Cursor", "Windsurf"];
+
+/// Platform directory VS Code-based editors keep their user data in
+fn editor_config_dir(home_dir: &str) -> String {
+    if cfg!(target_os = "macos") {
+        format!("{}/Library/Application Support", home_dir)
+    } else if cfg!(windows) {
+        std::env::var("APPDATA").unwrap_or_else(|_| format!("{}/AppData/Roaming", home_dir))
+    } else {
+        std::env::var("XDG_CONFIG_HOME").unwrap_or_else(|_| format!("{}/.config", home_dir))
+    }
+}
+
+/// Task directories of a VS Code extension source, one per editor it was
+/// used in; VS Code's own when it was used in none, so the source still has
+/// a root to report as missing
+fn extension_task_dirs(home_dir: &str, session_type: SessionType) -> Vec<String> {
+    let extension = match session_type {
+        SessionType::Cline => "saoudrizwan.claude-dev",
+        SessionType::RooCode => "rooveterinaryinc.roo-cline",
+        _ => return Vec::new(),
+    };
+    let config_dir = editor_config_dir(home_dir);
+    let dirs: Vec<String> = VSCODE_EDITORS
+        .iter()
+        .map(|editor| format!("{}/{}/User/globalStorage/{}/tasks", config_dir, editor, extension))
+        .collect();
+    let used: Vec<String> = dirs.iter().filter(|dir| std::path::Path::new(dir).is_dir()).cloned().collect();
+    if used.is_empty() {
+        dirs.into_iter().take(1).collect()
+    } else {
+        used
+    }
+}
 
+/// Session directory and file pattern for each requested source
+/// (all sources when `sources` is empty)
+pub fn source_roots(home_dir: &str, sources: &[String]) -> Vec<(SessionType, String, &'static str)> {
     let include_all = sources.is_empty();
     let include_opencode = include_all || sources.iter().any(|s| s == "opencode");
     let include_claude = include_all || sources.iter().any(|s| s == "claude");
@@ -120,22 +226,25 @@ pub fn scan_all_sources(home_dir: &str, sources: &[String]) -> ScanResult {
     let include_cursor = include_all || sources.iter().any(|s| s == "cursor");
     let include_amp = include_all || sources.iter().any(|s| s == "amp");
     let include_droid = include_all || sources.iter().any(|s| s == "droid");
+    let include_cline = include_all || sources.iter().any(|s| s == "cline");
+    let include_roocode = include_all || sources.iter().any(|s| s == "roocode");
+    let include_recorded = include_all || sources.iter().any(|s| s == "recorded");
 
     // Define scan tasks
-    let mut tasks: Vec<(SessionType, String, &str)> = Vec::new();
+    let mut tasks: Vec<(SessionType, String, &'static str)> = Vec::new();
 
     if include_opencode {
         // OpenCode: ~/.local/share/opencode/storage/message/*/*.json
         let xdg_data =
             std::env::var("XDG_DATA_HOME").unwrap_or_else(|_| format!("{}/.local/share", home_dir));
         let opencode_path = format!("{}/opencode/storage/message", xdg_data);
-        tasks.push((SessionType::OpenCode, opencode_path, "*.json"));
+        tasks.push((SessionType::OpenCode, opencode_path, SessionType::OpenCode.file_pattern()));
     }
 
     if include_claude {
         // Claude: ~/.claude/projects/**/*.jsonl
         let claude_path = format!("{}/.claude/projects", home_dir);
-        tasks.push((SessionType::Claude, claude_path, "*.jsonl"));
+        tasks.push((SessionType::Claude, claude_path, SessionType::Claude.file_pattern()));
     }
 
     if include_codex {
@@ -143,19 +252,19 @@ pub fn scan_all_sources(home_dir: &str, sources: &[String]) -> ScanResult {
         let codex_home =
             std::env::var("CODEX_HOME").unwrap_or_else(|_| format!("{}/.codex", home_dir));
         let codex_path = format!("{}/sessions", codex_home);
-        tasks.push((SessionType::Codex, codex_path, "*.jsonl"));
+        tasks.push((SessionType::Codex, codex_path, SessionType::Codex.file_pattern()));
     }
 
     if include_gemini {
         // Gemini: ~/.gemini/tmp/*/chats/session-*.json
         let gemini_path = format!("{}/.gemini/tmp", home_dir);
-        tasks.push((SessionType::Gemini, gemini_path, "session-*.json"));
+        tasks.push((SessionType::Gemini, gemini_path, SessionType::Gemini.file_pattern()));
     }
 
     if include_cursor {
         // Cursor: ~/.config/tokscale/cursor-cache/*.csv (migrated from ~/.tokscale)
         let cursor_path = format!("{}/.config/tokscale/cursor-cache", home_dir);
-        tasks.push((SessionType::Cursor, cursor_path, "*.csv"));
+        tasks.push((SessionType::Cursor, cursor_path, SessionType::Cursor.file_pattern()));
     }
 
     if include_amp {
@@ -163,15 +272,38 @@ pub fn scan_all_sources(home_dir: &str, sources: &[String]) -> ScanResult {
         let xdg_data =
             std::env::var("XDG_DATA_HOME").unwrap_or_else(|_| format!("{}/.local/share", home_dir));
         let amp_path = format!("{}/amp/threads", xdg_data);
-        tasks.push((SessionType::Amp, amp_path, "T-*.json"));
+        tasks.push((SessionType::Amp, amp_path, SessionType::Amp.file_pattern()));
     }
 
     if include_droid {
         // Droid: ~/.factory/sessions/*.settings.json
         let droid_path = format!("{}/.factory/sessions", home_dir);
-        tasks.push((SessionType::Droid, droid_path, "*.settings.json"));
+        tasks.push((SessionType::Droid, droid_path, SessionType::Droid.file_pattern()));
     }
 
+    for (include, session_type) in [(include_cline, SessionType::Cline), (include_roocode, SessionType::RooCode)] {
+        if include {
+            // Cline / Roo Code: <editor>/User/globalStorage/<extension>/tasks/<task>/ui_messages.json
+            for tasks_path in extension_task_dirs(home_dir, session_type) {
+                tasks.push((session_type, tasks_path, session_type.file_pattern()));
+            }
+        }
+    }
+
+    if include_recorded {
+        // Recorded events: ~/.config/tokscale/recorded/*.jsonl
+        let recorded_path = crate::recorder::recorded_dir(home_dir);
+        tasks.push((SessionType::Recorded, recorded_path.to_string_lossy().into_owned(), SessionType::Recorded.file_pattern()));
+    }
+
+    tasks
+}
+
+/// Scan all session source directories in parallel
+pub fn scan_all_sources(home_dir: &str, sources: &[String]) -> ScanResult {
+    let mut result = ScanResult::default();
+    let tasks = source_roots(home_dir, sources);
+
     // Execute scans in parallel
     let scan_results: Vec<(SessionType, Vec<PathBuf>)> = tasks
         .into_par_iter()
@@ -191,6 +323,10 @@ pub fn scan_all_sources(home_dir: &str, sources: &[String]) -> ScanResult {
             SessionType::Cursor => result.cursor_files = files,
             SessionType::Amp => result.amp_files = files,
             SessionType::Droid => result.droid_files = files,
+            // One root per editor the extension is installed in
+            SessionType::Cline => result.cline_files.extend(files),
+            SessionType::RooCode => result.roocode_files.extend(files),
+            SessionType::Recorded => result.recorded_files = files,
         }
     }
 
@@ -214,6 +350,9 @@ mod tests {
             cursor_files: vec![],
             amp_files: vec![],
             droid_files: vec![],
+            cline_files: vec![],
+            roocode_files: vec![],
+            recorded_files: vec![],
         };
         assert_eq!(result.total_files(), 4);
     }
@@ -228,6 +367,9 @@ mod tests {
             cursor_files: vec![PathBuf::from("e.csv")],
             amp_files: vec![],
             droid_files: vec![],
+            cline_files: vec![],
+            roocode_files: vec![],
+            recorded_files: vec![],
         };
 
         let all = result.all_files();
@@ -445,4 +587,27 @@ mod tests {
         let result = scan_all_sources(home.to_str().unwrap(), &["codex".to_string()]);
         assert_eq!(result.codex_files.len(), 1);
     }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_scan_all_sources_cline_in_each_editor() {
+        let dir = TempDir::new().unwrap();
+        let home = dir.path();
+        std::env::set_var("XDG_CONFIG_HOME", home.join(".config"));
+        for editor in ["Code", "Cursor"] {
+            let task =
+                home.join(".config").join(editor).join("User/globalStorage/saoudrizwan.claude-dev/tasks/1750075190000");
+            fs::create_dir_all(&task).unwrap();
+            File::create(task.join("ui_messages.json")).unwrap().write_all(b"[]").unwrap();
+            File::create(task.join("api_conversation_history.json")).unwrap().write_all(b"[]").unwrap();
+        }
+
+        let result = scan_all_sources(home.to_str().unwrap(), &["cline".to_string(), "roocode".to_string()]);
+        assert_eq!(result.cline_files.len(), 2);
+        assert!(result.roocode_files.is_empty());
+
+        let roots = source_roots(home.to_str().unwrap(), &["roocode".to_string()]);
+        assert_eq!(roots.len(), 1, "an unused extension still has VS Code's root");
+        assert!(roots[0].1.ends_with("Code/User/globalStorage/rooveterinaryinc.roo-cline/tasks"));
+    }
 }
@@ -0,0 +1,201 @@
+//! API key storage
+//!
+//! Keys for remote importers (OpenRouter, Anthropic admin, OpenAI) stay out of
+//! settings.json. Settings only say where a key lives, by name:
+//!
+//! ```json
+//! "apiKeys": { "openrouter": "keychain:openrouter", "openai": "env:OPENAI_API_KEY" }
+//! ```
+//!
+//! `keychain:` entries are read from the OS keychain (see [`SystemKeychain`])
+//! under the service [`SERVICE`]. A key with no reference is looked up in the
+//! keychain under its own name. Either way, `TOKSCALE_<NAME>_API_KEY` is the
+//! fallback when the keychain has nothing.
+
+/// Keychain service the entries are stored under
+pub const SERVICE: &str = "tokscale";
+
+/// Where settings say a key lives
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SecretRef {
+    /// Keychain entry (account name under [`SERVICE`])
+    Keychain(String),
+    /// Environment variable
+    Env(String),
+}
+
+impl SecretRef {
+    pub fn parse(reference: &str) -> Result<Self, String> {
+        let (scheme, name) = reference
+            .split_once(':')
+            .ok_or_else(|| "expected \"keychain:<name>\" or \"env:<VAR>\", got a plain value".to_string())?;
+        if name.is_empty() {
+            return Err(format!("empty name in \"{}\"", reference));
+        }
+        match scheme {
+            "keychain" => Ok(SecretRef::Keychain(name.to_string())),
+            "env" => Ok(SecretRef::Env(name.to_string())),
+            other => Err(format!("unknown secret backend \"{}\"", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    Keychain,
+    Env,
+}
+
+impl Backend {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Backend::Keychain => "keychain",
+            Backend::Env => "env",
+        }
+    }
+}
+
+pub trait Keychain {
+    fn get(&self, account: &str) -> Option<String>;
+    fn set(&self, account: &str, secret: &str) -> Result<(), String>;
+}
+
+/// The OS keychain: Keychain Services on macOS, Credential Manager on
+/// Windows and the Secret Service (GNOME Keyring, KWallet) on Linux
+pub struct SystemKeychain;
+
+#[cfg(any(target_os = "macos", target_os = "windows", target_os = "linux"))]
+impl Keychain for SystemKeychain {
+    fn get(&self, account: &str) -> Option<String> {
+        let entry = keyring::Entry::new(SERVICE, account).ok()?;
+        entry.get_password().ok().filter(|secret| !secret.is_empty())
+    }
+
+    fn set(&self, account: &str, secret: &str) -> Result<(), String> {
+        keyring::Entry::new(SERVICE, account)
+            .and_then(|entry| entry.set_password(secret))
+            .map_err(|e| format!("could not store the key in the keychain: {}", e))
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
+impl Keychain for SystemKeychain {
+    fn get(&self, _account: &str) -> Option<String> {
+        None
+    }
+
+    fn set(&self, account: &str, _secret: &str) -> Result<(), String> {
+        Err(format!(
+            "no keychain support on this platform; set {} instead",
+            fallback_env_var(account)
+        ))
+    }
+}
+
+/// Environment variable checked when the keychain has no key for `name`
+pub fn fallback_env_var(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+        .collect();
+    format!("TOKSCALE_{}_API_KEY", name)
+}
+
+/// API key for `name` (e.g. "openrouter") and where it was found
+pub fn resolve(name: &str) -> Option<(String, Backend)> {
+    let settings = crate::config::settings();
+    resolve_with(name, settings.api_keys.get(name).map(String::as_str), &SystemKeychain, |var| {
+        std::env::var(var).ok()
+    })
+}
+
+/// Store `secret` in the OS keychain under `name`
+pub fn store(name: &str, secret: &str) -> Result<(), String> {
+    SystemKeychain.set(name, secret)
+}
+
+fn resolve_with(
+    name: &str,
+    reference: Option<&str>,
+    keychain: &dyn Keychain,
+    env: impl Fn(&str) -> Option<String>,
+) -> Option<(String, Backend)> {
+    let reference = match reference.map(SecretRef::parse) {
+        Some(Ok(reference)) => reference,
+        Some(Err(reason)) => {
+            eprintln!("[tokscale] Ignoring apiKeys.{} in settings: {}", name, reason);
+            SecretRef::Keychain(name.to_string())
+        }
+        None => SecretRef::Keychain(name.to_string()),
+    };
+
+    let found = match &reference {
+        SecretRef::Keychain(account) => keychain.get(account).map(|key| (key, Backend::Keychain)),
+        SecretRef::Env(var) => env(var).map(|key| (key, Backend::Env)),
+    };
+    found
+        .or_else(|| env(&fallback_env_var(name)).map(|key| (key, Backend::Env)))
+        .filter(|(key, _)| !key.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    struct FakeKeychain(HashMap<&'static str, &'static str>);
+
+    impl Keychain for FakeKeychain {
+        fn get(&self, account: &str) -> Option<String> {
+            self.0.get(account).map(|s| s.to_string())
+        }
+
+        fn set(&self, _account: &str, _secret: &str) -> Result<(), String> {
+            Ok(())
+        }
+    }
+
+    fn env(var: &str) -> Option<String> {
+        match var {
+            "OPENAI_API_KEY" => Some("sk-env".to_string()),
+            "TOKSCALE_ANTHROPIC_ADMIN_API_KEY" => Some("sk-ant-fallback".to_string()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_parse_reference() {
+        assert_eq!(SecretRef::parse("keychain:openrouter"), Ok(SecretRef::Keychain("openrouter".into())));
+        assert_eq!(SecretRef::parse("env:OPENAI_API_KEY"), Ok(SecretRef::Env("OPENAI_API_KEY".into())));
+        assert!(SecretRef::parse("sk-live-1234").is_err());
+        assert!(SecretRef::parse("vault:openai").is_err());
+        assert!(SecretRef::parse("env:").is_err());
+    }
+
+    #[test]
+    fn test_fallback_env_var() {
+        assert_eq!(fallback_env_var("openrouter"), "TOKSCALE_OPENROUTER_API_KEY");
+        assert_eq!(fallback_env_var("anthropic-admin"), "TOKSCALE_ANTHROPIC_ADMIN_API_KEY");
+    }
+
+    #[test]
+    fn test_resolve() {
+        let keychain = FakeKeychain(HashMap::from([("openrouter", "sk-or-keychain"), ("work", "sk-work")]));
+
+        let resolved = resolve_with("openrouter", None, &keychain, env);
+        assert_eq!(resolved, Some(("sk-or-keychain".into(), Backend::Keychain)));
+
+        let resolved = resolve_with("openrouter", Some("keychain:work"), &keychain, env);
+        assert_eq!(resolved, Some(("sk-work".into(), Backend::Keychain)));
+
+        let resolved = resolve_with("openai", Some("env:OPENAI_API_KEY"), &keychain, env);
+        assert_eq!(resolved, Some(("sk-env".into(), Backend::Env)));
+
+        // Keychain miss falls back to TOKSCALE_<NAME>_API_KEY
+        let resolved = resolve_with("anthropic-admin", Some("keychain:anthropic-admin"), &keychain, env);
+        assert_eq!(resolved, Some(("sk-ant-fallback".into(), Backend::Env)));
+
+        // A plaintext key in settings is never used as the key
+        assert_eq!(resolve_with("openai", Some("sk-plaintext"), &keychain, env), None);
+    }
+}
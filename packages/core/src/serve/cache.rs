@@ -0,0 +1,121 @@
+//! Rendered responses of the read endpoints
+//!
+//! Dashboards tend to ask for the same few reports over and over. Each entry
+//! remembers the data version it was computed from (see
+//! [`crate::Tokscale::data_version`]) and the ingest generation, so it is
+//! only served while the session files are unchanged and nothing has been
+//! pushed since.
+
+use crate::http;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Responses kept; the least recently used goes first
+const MAX_ENTRIES: usize = 64;
+
+#[derive(Default)]
+pub struct ReportCache {
+    entries: Mutex<HashMap<String, Entry>>,
+    generation: AtomicU64,
+    /// Recency counter for eviction
+    clock: AtomicU64,
+}
+
+struct Entry {
+    generation: u64,
+    data_version: u64,
+    body: Arc<str>,
+    used: u64,
+}
+
+/// Endpoint plus its query parameters in a canonical order, so
+/// `?year=2025&sources=claude` and `?sources=claude&year=2025` share an entry
+pub fn key(request: &http::Request) -> String {
+    let mut params = request.query();
+    params.sort();
+    let query: Vec<String> = params.iter().map(|(name, value)| format!("{}={}", name, value)).collect();
+    format!("{}?{}", request.path(), query.join("&"))
+}
+
+impl ReportCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Current ingest generation; take it before reading any data
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::SeqCst)
+    }
+
+    pub fn get(&self, key: &str, generation: u64, data_version: u64) -> Option<Arc<str>> {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let entry = entries.get_mut(key)?;
+        if entry.generation != generation || entry.data_version != data_version {
+            return None;
+        }
+        entry.used = self.clock.fetch_add(1, Ordering::Relaxed);
+        Some(Arc::clone(&entry.body))
+    }
+
+    /// Store a response computed from data read at `generation`; dropped if
+    /// there has been an ingest since
+    pub fn insert(&self, key: String, generation: u64, data_version: u64, body: Arc<str>) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        if generation != self.generation() {
+            return;
+        }
+        if entries.len() >= MAX_ENTRIES && !entries.contains_key(&key) {
+            if let Some(oldest) = entries.iter().min_by_key(|(_, e)| e.used).map(|(k, _)| k.clone()) {
+                entries.remove(&oldest);
+            }
+        }
+        let used = self.clock.fetch_add(1, Ordering::Relaxed);
+        entries.insert(key, Entry { generation, data_version, body, used });
+    }
+
+    /// Drop everything, after new events were recorded
+    pub fn invalidate(&self) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        entries.clear();
+    }
+
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner()).len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_versions_and_invalidation() {
+        let cache = ReportCache::new();
+        let generation = cache.generation();
+        cache.insert("/api/report?".to_string(), generation, 1, Arc::from("{}"));
+        assert_eq!(cache.get("/api/report?", generation, 1).as_deref(), Some("{}"));
+        assert!(cache.get("/api/report?", generation, 2).is_none());
+
+        // A response computed before an ingest is not stored after it
+        cache.invalidate();
+        assert_eq!(cache.len(), 0);
+        cache.insert("/api/graph?".to_string(), generation, 1, Arc::from("{}"));
+        assert_eq!(cache.len(), 0);
+    }
+
+    #[test]
+    fn test_least_recently_used_evicted() {
+        let cache = ReportCache::new();
+        for i in 0..MAX_ENTRIES {
+            cache.insert(format!("/api/report?year={}", i), 0, 1, Arc::from("{}"));
+        }
+        assert!(cache.get("/api/report?year=0", 0, 1).is_some());
+        cache.insert("/api/graph?".to_string(), 0, 1, Arc::from("{}"));
+        assert_eq!(cache.len(), MAX_ENTRIES);
+        assert!(cache.get("/api/report?year=0", 0, 1).is_some());
+        assert!(cache.get("/api/report?year=1", 0, 1).is_none());
+    }
+}
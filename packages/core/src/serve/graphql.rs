@@ -0,0 +1,1226 @@
+//! GraphQL queries over the same data as the REST endpoints
+//!
+//! `POST /api/graphql` (`{"query", "variables", "operationName"}`) or
+//! `GET /api/graphql?query=...` lets a dashboard fetch several views, shaped
+//! as it needs them, in one request. This is the query subset dashboards use:
+//! operations, aliases, arguments, variables and fragments (named and inline);
+//! directives and introspection (other than `__typename`) are not supported.
+//!
+//! ```graphql
+//! type Query {
+//!   usage(since: String, until: String, year: String, sources: [String!],
+//!         groupBy: [UsageKey!], limit: Int): [UsageGroup!]!
+//!   messages(since, until, year, sources, limit: Int, cursor: String): MessagePage!
+//!   sessions(since, until, year, sources, limit: Int, cursor: String): SessionPage!
+//!   models(since, until, year, sources): ModelReport!
+//!   pricing(since, until, year, sources, model: String): [Rate!]!
+//! }
+//! enum UsageKey { SOURCE PROVIDER MODEL AGENT SESSION DAY MONTH }
+//! ```
+//!
+//! `usage` totals messages per combination of the `groupBy` keys (one total
+//! without any), most expensive first; keys not grouped by are null. The other
+//! types are listed in [`TYPES`] and match the REST responses.
+
+use super::page::{self, Cursor};
+use super::ServeConfig;
+use crate::api::{Filter, Tokscale};
+use crate::sessions::{Source, UnifiedMessage};
+use crate::TokenBreakdown;
+use serde_json::{json, Map, Value};
+use std::collections::{HashMap, HashSet};
+
+/// Fields of each object type, with their GraphQL types. `Long` is a 64-bit
+/// integer (token counts, Unix milliseconds).
+pub const TYPES: &[(&str, &[(&str, &str)])] = &[
+    (
+        "Query",
+        &[
+            ("usage", "[UsageGroup!]!"),
+            ("messages", "MessagePage!"),
+            ("sessions", "SessionPage!"),
+            ("models", "ModelReport!"),
+            ("pricing", "[Rate!]!"),
+        ],
+    ),
+    (
+        "UsageGroup",
+        &[
+            ("source", "String"),
+            ("provider", "String"),
+            ("model", "String"),
+            ("agent", "String"),
+            ("sessionId", "String"),
+            ("date", "String"),
+            ("month", "String"),
+            ("tokens", "Tokens!"),
+            ("cost", "Float!"),
+            ("messageCount", "Int!"),
+        ],
+    ),
+    (
+        "Tokens",
+        &[
+            ("input", "Long!"),
+            ("output", "Long!"),
+            ("cacheRead", "Long!"),
+            ("cacheWrite", "Long!"),
+            ("reasoning", "Long!"),
+            ("training", "Long!"),
+        ],
+    ),
+    ("MessagePage", &[("messages", "[Message!]!"), ("nextCursor", "String")]),
+    (
+        "Message",
+        &[
+            ("id", "String!"),
+            ("source", "String!"),
+            ("modelId", "String!"),
+            ("providerId", "String!"),
+            ("sessionId", "String!"),
+            ("timestamp", "Long!"),
+            ("date", "String!"),
+            ("tokens", "Tokens!"),
+            ("cost", "Float!"),
+            ("agent", "String"),
+        ],
+    ),
+    ("SessionPage", &[("sessions", "[Session!]!"), ("nextCursor", "String")]),
+    (
+        "Session",
+        &[
+            ("id", "String!"),
+            ("source", "String!"),
+            ("sessionId", "String!"),
+            ("firstTimestamp", "Long!"),
+            ("lastTimestamp", "Long!"),
+            ("messageCount", "Int!"),
+            ("models", "[String!]!"),
+            ("tokens", "Tokens!"),
+            ("cost", "Float!"),
+        ],
+    ),
+    (
+        "ModelReport",
+        &[
+            ("entries", "[ModelUsage!]!"),
+            ("totalInput", "Long!"),
+            ("totalOutput", "Long!"),
+            ("totalCacheRead", "Long!"),
+            ("totalCacheWrite", "Long!"),
+            ("totalReasoning", "Long!"),
+            ("totalMessages", "Int!"),
+            ("totalCost", "Float!"),
+            ("processingTimeMs", "Int!"),
+        ],
+    ),
+    (
+        "ModelUsage",
+        &[
+            ("source", "String!"),
+            ("model", "String!"),
+            ("provider", "String!"),
+            ("input", "Long!"),
+            ("output", "Long!"),
+            ("cacheRead", "Long!"),
+            ("cacheWrite", "Long!"),
+            ("reasoning", "Long!"),
+            ("training", "Long!"),
+            ("messageCount", "Int!"),
+            ("cost", "Float!"),
+            ("costShare", "Float!"),
+            ("tokenShare", "Float!"),
+        ],
+    ),
+    (
+        "Rate",
+        &[
+            ("model", "String!"),
+            ("provider", "String!"),
+            ("sources", "[String!]!"),
+            ("inputTokens", "Long!"),
+            ("outputTokens", "Long!"),
+            ("trainingTokens", "Long!"),
+            ("cacheReadShare", "Float!"),
+            ("cost", "Float!"),
+            ("effectivePerMillion", "Float!"),
+            ("inputPerMillion", "Float"),
+            ("outputPerMillion", "Float"),
+            ("listInputPerMillion", "Float"),
+            ("listOutputPerMillion", "Float"),
+            ("messageCount", "Int!"),
+        ],
+    ),
+];
+
+const FILTER_ARGS: &[&str] = &["since", "until", "year", "sources"];
+
+/// Arguments each root field takes besides the filter ones
+const ROOT_ARGS: &[(&str, &[&str])] = &[
+    ("usage", &["groupBy", "limit"]),
+    ("messages", &["limit", "cursor"]),
+    ("sessions", &["limit", "cursor"]),
+    ("models", &[]),
+    ("pricing", &["model"]),
+];
+
+fn fields_of(ty: &str) -> Option<&'static [(&'static str, &'static str)]> {
+    TYPES.iter().find(|(name, _)| *name == ty).map(|(_, fields)| *fields)
+}
+
+/// Named type under list and non-null wrappers, e.g. `Message` for `[Message!]!`
+fn named_type(ty: &str) -> &str {
+    ty.trim_matches(|c| c == '[' || c == ']' || c == '!')
+}
+
+// =============================================================================
+// Parsing
+// =============================================================================
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Punct(char),
+    Spread,
+    Name(String),
+    Number(String),
+    Str(String),
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            // Commas are insignificant in GraphQL
+            c if c.is_whitespace() || c == ',' || c == '\u{feff}' => i += 1,
+            '#' => {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+            }
+            '.' if chars[i..].starts_with(&['.', '.', '.']) => {
+                tokens.push(Token::Spread);
+                i += 3;
+            }
+            '{' | '}' | '(' | ')' | '[' | ']' | ':' | '!' | '$' | '=' | '@' | '|' | '&' => {
+                tokens.push(Token::Punct(c));
+                i += 1;
+            }
+            c if c == '_' || c.is_ascii_alphabetic() => {
+                let start = i;
+                while i < chars.len() && (chars[i] == '_' || chars[i].is_ascii_alphanumeric()) {
+                    i += 1;
+                }
+                tokens.push(Token::Name(chars[start..i].iter().collect()));
+            }
+            c if c == '-' || c.is_ascii_digit() => {
+                let start = i;
+                i += 1;
+                while i < chars.len() && (chars[i].is_ascii_digit() || matches!(chars[i], '.' | 'e' | 'E' | '+' | '-')) {
+                    i += 1;
+                }
+                tokens.push(Token::Number(chars[start..i].iter().collect()));
+            }
+            '"' => {
+                if chars[i..].starts_with(&['"', '"', '"']) {
+                    return Err("Block strings are not supported".to_string());
+                }
+                i += 1;
+                let mut text = String::new();
+                loop {
+                    match chars.get(i) {
+                        None | Some('\n') => return Err("Unterminated string".to_string()),
+                        Some('"') => break,
+                        Some('\\') => {
+                            let escaped = match chars.get(i + 1) {
+                                Some('n') => '\n',
+                                Some('t') => '\t',
+                                Some('r') => '\r',
+                                Some('b') => '\u{8}',
+                                Some('f') => '\u{c}',
+                                Some('u') => {
+                                    let hex: String = chars.iter().skip(i + 2).take(4).collect();
+                                    let code = u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32);
+                                    i += 4;
+                                    code.ok_or_else(|| format!("Invalid escape \\u{}", hex))?
+                                }
+                                Some(c @ ('"' | '\\' | '/')) => *c,
+                                _ => return Err("Invalid escape in string".to_string()),
+                            };
+                            text.push(escaped);
+                            i += 2;
+                        }
+                        Some(c) => {
+                            text.push(*c);
+                            i += 1;
+                        }
+                    }
+                }
+                tokens.push(Token::Str(text));
+                i += 1;
+            }
+            c => return Err(format!("Unexpected character '{}'", c)),
+        }
+    }
+    Ok(tokens)
+}
+
+/// One selected field
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field {
+    pub alias: Option<String>,
+    pub name: String,
+    pub arguments: Vec<(String, Value)>,
+    pub selection: Vec<Field>,
+}
+
+impl Field {
+    /// Key of this field in the response
+    pub fn key(&self) -> &str {
+        self.alias.as_deref().unwrap_or(&self.name)
+    }
+
+    fn argument(&self, name: &str) -> Option<&Value> {
+        self.arguments.iter().find(|(n, _)| n == name).map(|(_, v)| v).filter(|v| !v.is_null())
+    }
+}
+
+struct Parser<'a> {
+    tokens: Vec<Token>,
+    pos: usize,
+    supplied: &'a Map<String, Value>,
+    variables: Map<String, Value>,
+    /// Fragment name -> (type condition, position of its selection set)
+    fragments: HashMap<String, (String, usize)>,
+    /// Fragments being expanded, to catch cycles
+    spreading: Vec<String>,
+    used: HashSet<String>,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn advance(&mut self) -> Result<Token, String> {
+        let token = self.tokens.get(self.pos).cloned().ok_or("Unexpected end of query")?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(&Token::Punct(c)) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), String> {
+        match self.advance()? {
+            Token::Punct(p) if p == c => Ok(()),
+            other => Err(format!("Expected '{}', found {}", c, describe(&other))),
+        }
+    }
+
+    fn name(&mut self) -> Result<String, String> {
+        match self.advance()? {
+            Token::Name(name) => Ok(name),
+            other => Err(format!("Expected a name, found {}", describe(&other))),
+        }
+    }
+
+    /// `query Name($var: Type = default) { ... }` or `{ ... }`, as
+    /// (name, selection)
+    fn operation(&mut self) -> Result<(Option<String>, Vec<Field>), String> {
+        self.variables = self.supplied.clone();
+        let mut name = None;
+        match self.peek() {
+            Some(Token::Punct('{')) => {}
+            Some(Token::Name(keyword)) if keyword == "query" => {
+                self.pos += 1;
+                if let Some(Token::Name(_)) = self.peek() {
+                    name = Some(self.name()?);
+                }
+                if self.eat('(') {
+                    while !self.eat(')') {
+                        self.variable_definition()?;
+                    }
+                }
+            }
+            Some(Token::Name(keyword)) if keyword == "mutation" || keyword == "subscription" => {
+                return Err(format!("Only queries are supported, not {}s", keyword));
+            }
+            Some(other) => return Err(format!("Expected an operation, found {}", describe(other))),
+            None => return Err("Unexpected end of query".to_string()),
+        }
+        if self.peek() == Some(&Token::Punct('@')) {
+            return Err("Directives are not supported".to_string());
+        }
+        Ok((name, self.selection_set("Query")?))
+    }
+
+    /// `fragment Name on Type { ... }`, as (name, type condition). Leaves the
+    /// parser at the selection set.
+    fn fragment_header(&mut self) -> Result<(String, String), String> {
+        self.pos += 1;
+        let name = self.name()?;
+        if name == "on" {
+            return Err("Fragment can't be named \"on\"".to_string());
+        }
+        match self.advance()? {
+            Token::Name(on) if on == "on" => {}
+            other => return Err(format!("Expected \"on\", found {}", describe(&other))),
+        }
+        let ty = self.name()?;
+        if fields_of(&ty).is_none() {
+            return Err(format!("Unknown type \"{}\"", ty));
+        }
+        if self.peek() == Some(&Token::Punct('@')) {
+            return Err("Directives are not supported".to_string());
+        }
+        Ok((name, ty))
+    }
+
+    /// Move past a `{ ... }` without interpreting it
+    fn skip_selection_set(&mut self) -> Result<(), String> {
+        self.expect('{')?;
+        let mut depth = 1;
+        loop {
+            match self.advance()? {
+                Token::Punct('{') => depth += 1,
+                Token::Punct('}') => depth -= 1,
+                _ => {}
+            }
+            if depth == 0 {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Record every fragment definition so operations can spread fragments
+    /// defined after them
+    fn collect_fragments(&mut self) -> Result<(), String> {
+        let (mut braces, mut parens) = (0i32, 0i32);
+        while let Some(token) = self.peek() {
+            match token {
+                Token::Name(keyword) if keyword == "fragment" && braces == 0 && parens == 0 => {
+                    let (name, ty) = self.fragment_header()?;
+                    if self.fragments.insert(name.clone(), (ty, self.pos)).is_some() {
+                        return Err(format!("There can be only one fragment named \"{}\"", name));
+                    }
+                    self.skip_selection_set()?;
+                    continue;
+                }
+                Token::Punct('{') => braces += 1,
+                Token::Punct('}') => braces -= 1,
+                Token::Punct('(') => parens += 1,
+                Token::Punct(')') => parens -= 1,
+                _ => {}
+            }
+            self.pos += 1;
+        }
+        self.pos = 0;
+        Ok(())
+    }
+
+    /// Fields of `...Name` or `... on Type { ... }` on an object of type
+    /// `parent`
+    fn fragment_spread(&mut self, parent: &str) -> Result<Vec<Field>, String> {
+        self.pos += 1;
+        let condition = match self.peek() {
+            Some(Token::Name(on)) if on == "on" => {
+                self.pos += 1;
+                Some(self.name()?)
+            }
+            Some(Token::Name(_)) => {
+                let name = self.name()?;
+                if self.peek() == Some(&Token::Punct('@')) {
+                    return Err("Directives are not supported".to_string());
+                }
+                let (ty, start) = self
+                    .fragments
+                    .get(&name)
+                    .cloned()
+                    .ok_or_else(|| format!("Unknown fragment \"{}\"", name))?;
+                if self.spreading.contains(&name) {
+                    return Err(format!("Cannot spread fragment \"{}\" within itself", name));
+                }
+                check_condition(&format!("Fragment \"{}\"", name), &ty, parent)?;
+                self.used.insert(name.clone());
+
+                let resume = self.pos;
+                self.pos = start;
+                self.spreading.push(name);
+                let fields = self.selection_set(&ty);
+                self.spreading.pop();
+                self.pos = resume;
+                return fields;
+            }
+            _ => None,
+        };
+        if self.peek() == Some(&Token::Punct('@')) {
+            return Err("Directives are not supported".to_string());
+        }
+        if let Some(ty) = &condition {
+            if fields_of(ty).is_none() {
+                return Err(format!("Unknown type \"{}\"", ty));
+            }
+            check_condition("Fragment", ty, parent)?;
+        }
+        self.selection_set(condition.as_deref().unwrap_or(parent))
+    }
+
+    fn variable_definition(&mut self) -> Result<(), String> {
+        self.expect('$')?;
+        let name = self.name()?;
+        self.expect(':')?;
+        self.skip_type()?;
+        if self.eat('=') {
+            let default = self.value()?;
+            self.variables.entry(name).or_insert(default);
+        }
+        Ok(())
+    }
+
+    fn skip_type(&mut self) -> Result<(), String> {
+        if self.eat('[') {
+            self.skip_type()?;
+            self.expect(']')?;
+        } else {
+            self.name()?;
+        }
+        self.eat('!');
+        Ok(())
+    }
+
+    /// Fields selected on an object of type `ty`, with fragments expanded and
+    /// repeated response keys merged
+    fn selection_set(&mut self, ty: &str) -> Result<Vec<Field>, String> {
+        self.expect('{')?;
+        let mut fields = Vec::new();
+        while !self.eat('}') {
+            if self.peek() == Some(&Token::Spread) {
+                for field in self.fragment_spread(ty)? {
+                    merge_field(&mut fields, field)?;
+                }
+            } else {
+                let field = self.field(ty)?;
+                merge_field(&mut fields, field)?;
+            }
+        }
+        if fields.is_empty() {
+            return Err("Empty selection".to_string());
+        }
+        Ok(fields)
+    }
+
+    fn field(&mut self, parent: &str) -> Result<Field, String> {
+        let mut name = self.name()?;
+        let mut alias = None;
+        if self.eat(':') {
+            alias = Some(name);
+            name = self.name()?;
+        }
+        let mut arguments = Vec::new();
+        if self.eat('(') {
+            while !self.eat(')') {
+                let argument = self.name()?;
+                self.expect(':')?;
+                arguments.push((argument, self.value()?));
+            }
+        }
+        if self.peek() == Some(&Token::Punct('@')) {
+            return Err("Directives are not supported".to_string());
+        }
+        let ty = fields_of(parent)
+            .and_then(|fields| fields.iter().find(|(field, _)| *field == name))
+            .map(|(_, ty)| named_type(ty))
+            .unwrap_or_default();
+        let selection = match self.peek() {
+            Some(Token::Punct('{')) => self.selection_set(ty)?,
+            _ => Vec::new(),
+        };
+        Ok(Field { alias, name, arguments, selection })
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        match self.advance()? {
+            Token::Punct('$') => {
+                let name = self.name()?;
+                Ok(self.variables.get(&name).cloned().unwrap_or(Value::Null))
+            }
+            Token::Number(text) => {
+                let number = match text.parse::<i64>() {
+                    Ok(int) => Some(int.into()),
+                    Err(_) => text.parse::<f64>().ok().and_then(serde_json::Number::from_f64),
+                };
+                number.map(Value::Number).ok_or_else(|| format!("Invalid number {}", text))
+            }
+            Token::Str(text) => Ok(Value::String(text)),
+            Token::Name(name) => Ok(match name.as_str() {
+                "true" => Value::Bool(true),
+                "false" => Value::Bool(false),
+                "null" => Value::Null,
+                // Enum values
+                _ => Value::String(name),
+            }),
+            Token::Punct('[') => {
+                let mut items = Vec::new();
+                while !self.eat(']') {
+                    items.push(self.value()?);
+                }
+                Ok(Value::Array(items))
+            }
+            Token::Punct('{') => {
+                let mut object = Map::new();
+                while !self.eat('}') {
+                    let key = self.name()?;
+                    self.expect(':')?;
+                    object.insert(key, self.value()?);
+                }
+                Ok(Value::Object(object))
+            }
+            other => Err(format!("Expected a value, found {}", describe(&other))),
+        }
+    }
+}
+
+/// Every type here is a concrete object type, so a fragment applies only to
+/// its own type. `parent` is empty under a field that doesn't exist, which
+/// `validate` reports instead.
+fn check_condition(what: &str, ty: &str, parent: &str) -> Result<(), String> {
+    if parent.is_empty() || ty == parent {
+        return Ok(());
+    }
+    Err(format!(
+        "{} cannot be spread here as objects of type \"{}\" can never be of type \"{}\"",
+        what, parent, ty
+    ))
+}
+
+/// Add `field` to a selection, merging it into an earlier field with the same
+/// response key
+fn merge_field(fields: &mut Vec<Field>, field: Field) -> Result<(), String> {
+    let Some(existing) = fields.iter_mut().find(|f| f.key() == field.key()) else {
+        fields.push(field);
+        return Ok(());
+    };
+    if existing.name != field.name || existing.arguments != field.arguments {
+        return Err(format!(
+            "Fields \"{}\" conflict because they select different fields or arguments",
+            field.key()
+        ));
+    }
+    for sub in field.selection {
+        merge_field(&mut existing.selection, sub)?;
+    }
+    Ok(())
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Punct(c) => format!("'{}'", c),
+        Token::Spread => "'...'".to_string(),
+        Token::Name(name) => format!("\"{}\"", name),
+        Token::Number(text) => text.clone(),
+        Token::Str(text) => format!("string \"{}\"", text),
+    }
+}
+
+/// Root selection of the operation to run, with variables substituted
+pub fn parse(query: &str, variables: &Map<String, Value>, operation_name: Option<&str>) -> Result<Vec<Field>, String> {
+    let mut parser = Parser {
+        tokens: tokenize(query)?,
+        pos: 0,
+        supplied: variables,
+        variables: Map::new(),
+        fragments: HashMap::new(),
+        spreading: Vec::new(),
+        used: HashSet::new(),
+    };
+    parser.collect_fragments()?;
+    let mut operations = Vec::new();
+    while let Some(token) = parser.peek() {
+        if matches!(token, Token::Name(keyword) if keyword == "fragment") {
+            parser.fragment_header()?;
+            parser.skip_selection_set()?;
+        } else {
+            operations.push(parser.operation()?);
+        }
+    }
+    if let Some(unused) = parser.fragments.keys().filter(|name| !parser.used.contains(*name)).min() {
+        return Err(format!("Fragment \"{}\" is never used", unused));
+    }
+    let chosen = match operation_name {
+        Some(wanted) => operations.into_iter().find(|(name, _)| name.as_deref() == Some(wanted)),
+        None if operations.len() > 1 => {
+            return Err("operationName is required when the document has several operations".to_string());
+        }
+        None => operations.into_iter().next(),
+    };
+    match chosen {
+        Some((_, selection)) => Ok(selection),
+        None => Err(match operation_name {
+            Some(name) => format!("No operation named \"{}\"", name),
+            None => "No operation in query".to_string(),
+        }),
+    }
+}
+
+/// Check selections against [`TYPES`] before anything is read
+fn validate(ty: &str, selection: &[Field]) -> Result<(), String> {
+    let ty = named_type(ty);
+    let Some(fields) = fields_of(ty) else {
+        if selection.is_empty() {
+            return Ok(());
+        }
+        return Err(format!("{} has no subfields", ty));
+    };
+    for field in selection {
+        if field.name == "__typename" {
+            continue;
+        }
+        let Some((_, field_type)) = fields.iter().find(|(name, _)| *name == field.name) else {
+            return Err(format!("Cannot query field \"{}\" on type {}", field.name, ty));
+        };
+        if ty == "Query" {
+            let extra = ROOT_ARGS.iter().find(|(name, _)| *name == field.name).map(|(_, args)| *args).unwrap_or(&[]);
+            if let Some((unknown, _)) = field.arguments.iter().find(|(arg, _)| {
+                !FILTER_ARGS.contains(&arg.as_str()) && !extra.contains(&arg.as_str())
+            }) {
+                return Err(format!("Unknown argument \"{}\" on field {}", unknown, field.name));
+            }
+        } else if let Some((argument, _)) = field.arguments.first() {
+            return Err(format!("Unknown argument \"{}\" on field {}.{}", argument, ty, field.name));
+        }
+        if fields_of(named_type(field_type)).is_some() && field.selection.is_empty() {
+            return Err(format!("Field \"{}\" of type {} needs a selection of subfields", field.name, field_type));
+        }
+        validate(field_type, &field.selection).map_err(|e| {
+            if field.selection.is_empty() {
+                e
+            } else {
+                format!("{}: {}", field.key(), e)
+            }
+        })?;
+    }
+    Ok(())
+}
+
+/// The parts of `value` the selection asks for, in selection order
+fn project(value: &Value, ty: &str, selection: &[Field]) -> Value {
+    let ty = ty.trim_end_matches('!');
+    if value.is_null() || selection.is_empty() {
+        return value.clone();
+    }
+    if let Some(item_type) = ty.strip_prefix('[').and_then(|t| t.strip_suffix(']')) {
+        let items = value.as_array().map(Vec::as_slice).unwrap_or_default();
+        return Value::Array(items.iter().map(|item| project(item, item_type, selection)).collect());
+    }
+    let fields = fields_of(ty).unwrap_or_default();
+    let mut out = Map::new();
+    for field in selection {
+        let projected = if field.name == "__typename" {
+            json!(ty)
+        } else {
+            let field_type = fields.iter().find(|(name, _)| *name == field.name).map(|(_, t)| *t).unwrap_or("");
+            project(value.get(&field.name).unwrap_or(&Value::Null), field_type, &field.selection)
+        };
+        out.insert(field.key().to_string(), projected);
+    }
+    Value::Object(out)
+}
+
+// =============================================================================
+// Execution
+// =============================================================================
+
+/// Body of `POST /api/graphql`
+pub struct GraphQlRequest {
+    pub query: String,
+    pub variables: Map<String, Value>,
+    pub operation_name: Option<String>,
+}
+
+impl GraphQlRequest {
+    /// From a JSON request body
+    pub fn from_json(body: &[u8]) -> Result<Self, String> {
+        let body: Value =
+            serde_json::from_slice(body).map_err(|e| format!("Expected {{\"query\", \"variables\"}}: {}", e))?;
+        let variables = match &body["variables"] {
+            Value::Object(variables) => variables.clone(),
+            Value::Null => Map::new(),
+            _ => return Err("\"variables\" must be an object".to_string()),
+        };
+        Self::new(body["query"].as_str(), variables, body["operationName"].as_str())
+    }
+
+    /// From `query`, `variables` (JSON) and `operationName` query parameters
+    pub fn from_params(
+        query: Option<String>,
+        variables: Option<String>,
+        operation_name: Option<String>,
+    ) -> Result<Self, String> {
+        let variables = match variables.filter(|v| !v.is_empty()) {
+            Some(text) => match serde_json::from_str(&text) {
+                Ok(Value::Object(variables)) => variables,
+                _ => return Err("\"variables\" must be a JSON object".to_string()),
+            },
+            None => Map::new(),
+        };
+        Self::new(query.as_deref(), variables, operation_name.as_deref())
+    }
+
+    fn new(query: Option<&str>, variables: Map<String, Value>, operation_name: Option<&str>) -> Result<Self, String> {
+        match query {
+            Some(query) if !query.trim().is_empty() => Ok(Self {
+                query: query.to_string(),
+                variables,
+                operation_name: operation_name.map(str::to_string),
+            }),
+            _ => Err("Missing \"query\"".to_string()),
+        }
+    }
+}
+
+/// `{"data", "errors"}` for a query. `Err` if the document itself can't run
+/// (syntax, unknown fields); errors reading data are reported per field,
+/// which is then null.
+pub async fn execute(request: &GraphQlRequest, config: &ServeConfig) -> Result<Value, String> {
+    let selection = parse(&request.query, &request.variables, request.operation_name.as_deref())?;
+    validate("Query", &selection)?;
+
+    let root = fields_of("Query").unwrap_or_default();
+    let mut data = Map::new();
+    let mut errors = Vec::new();
+    for field in &selection {
+        if field.name == "__typename" {
+            data.insert(field.key().to_string(), json!("Query"));
+            continue;
+        }
+        let field_type = root.iter().find(|(name, _)| *name == field.name).map(|(_, t)| *t).unwrap_or("");
+        match resolve(field, config).await {
+            Ok(value) => {
+                data.insert(field.key().to_string(), project(&value, field_type, &field.selection));
+            }
+            Err(message) => {
+                data.insert(field.key().to_string(), Value::Null);
+                errors.push(json!({ "message": message, "path": [field.key()] }));
+            }
+        }
+    }
+
+    let mut response = json!({ "data": data });
+    if !errors.is_empty() {
+        response["errors"] = Value::Array(errors);
+    }
+    Ok(response)
+}
+
+fn string_argument(field: &Field, name: &str) -> Result<Option<String>, String> {
+    match field.argument(name) {
+        None => Ok(None),
+        Some(Value::String(text)) => Ok(Some(text.clone())),
+        // Years are often written as numbers
+        Some(Value::Number(number)) if name == "year" => Ok(Some(number.to_string())),
+        Some(other) => Err(format!("Argument \"{}\" must be a string, not {}", name, other)),
+    }
+}
+
+fn list_argument(field: &Field, name: &str) -> Result<Vec<String>, String> {
+    match field.argument(name) {
+        None => Ok(Vec::new()),
+        Some(Value::Array(items)) => items
+            .iter()
+            .map(|item| item.as_str().map(str::to_string))
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| format!("Argument \"{}\" must be a list of strings", name)),
+        // A single value where a list is expected is coerced to a list of one
+        Some(Value::String(text)) => Ok(vec![text.clone()]),
+        Some(other) => Err(format!("Argument \"{}\" must be a list of strings, not {}", name, other)),
+    }
+}
+
+fn limit_argument(field: &Field) -> Result<Option<usize>, String> {
+    match field.argument("limit") {
+        None => Ok(None),
+        Some(value) => value
+            .as_u64()
+            .map(|limit| Some(limit as usize))
+            .ok_or_else(|| "Argument \"limit\" must be a non-negative integer".to_string()),
+    }
+}
+
+/// Reader and filter for a root field's `sources`, `since`, `until` and `year`
+fn target(field: &Field, config: &ServeConfig) -> Result<(Tokscale, Filter), String> {
+    let mut builder = Tokscale::builder().home_dir(&config.home_dir).pricing(config.pricing.clone());
+    let sources = list_argument(field, "sources")?;
+    if !sources.is_empty() {
+        builder = builder.sources(sources.iter().map(|s| Source::from(s.as_str())));
+    }
+    let tokscale = builder.build().map_err(|e| e.to_string())?;
+    let filter = Filter {
+        since: string_argument(field, "since")?,
+        until: string_argument(field, "until")?,
+        year: string_argument(field, "year")?,
+        ..Filter::default()
+    };
+    Ok((tokscale, filter))
+}
+
+async fn resolve(field: &Field, config: &ServeConfig) -> Result<Value, String> {
+    let (tokscale, filter) = target(field, config)?;
+    let value = match field.name.as_str() {
+        "usage" => {
+            let keys = list_argument(field, "groupBy")?
+                .iter()
+                .map(|key| UsageKey::parse(key))
+                .collect::<Result<Vec<_>, _>>()?;
+            let limit = limit_argument(field)?;
+            let messages = tokscale.messages(filter).await.map_err(|e| e.to_string())?;
+            let mut groups = usage_groups(&messages, &keys);
+            if let Some(limit) = limit {
+                groups.truncate(limit);
+            }
+            Value::Array(groups)
+        }
+        "messages" | "sessions" => {
+            let cursor = match string_argument(field, "cursor")? {
+                Some(cursor) => Some(Cursor::decode(&cursor)?),
+                None => None,
+            };
+            let limit = page::parse_limit(limit_argument(field)?.map(|l| l.to_string()).as_deref())?;
+            let messages = tokscale.messages(filter).await.map_err(|e| e.to_string())?;
+            if field.name == "messages" {
+                let page = page::paginate(page::message_items(messages), page::MessageItem::key, cursor.as_ref(), limit);
+                json!({ "messages": page.items, "nextCursor": page.next_cursor })
+            } else {
+                let page = page::paginate(page::session_items(&messages), page::SessionItem::key, cursor.as_ref(), limit);
+                json!({ "sessions": page.items, "nextCursor": page.next_cursor })
+            }
+        }
+        "models" => {
+            let report = tokscale.report(filter).await.map_err(|e| e.to_string())?;
+            serde_json::to_value(report).map_err(|e| e.to_string())?
+        }
+        "pricing" => {
+            let model = string_argument(field, "model")?;
+            let report = tokscale.effective_rates(filter).await.map_err(|e| e.to_string())?;
+            let rates: Vec<_> = report
+                .entries
+                .into_iter()
+                .filter(|rate| model.as_ref().is_none_or(|model| rate.model.eq_ignore_ascii_case(model)))
+                .collect();
+            serde_json::to_value(rates).map_err(|e| e.to_string())?
+        }
+        other => return Err(format!("Cannot query field \"{}\" on type Query", other)),
+    };
+    Ok(value)
+}
+
+/// `groupBy` keys of `usage`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum UsageKey {
+    Source,
+    Provider,
+    Model,
+    Agent,
+    Session,
+    Day,
+    Month,
+}
+
+impl UsageKey {
+    const ALL: [UsageKey; 7] = [
+        UsageKey::Source,
+        UsageKey::Provider,
+        UsageKey::Model,
+        UsageKey::Agent,
+        UsageKey::Session,
+        UsageKey::Day,
+        UsageKey::Month,
+    ];
+
+    fn parse(name: &str) -> Result<Self, String> {
+        Self::ALL
+            .into_iter()
+            .find(|key| key.as_str().eq_ignore_ascii_case(name))
+            .ok_or_else(|| format!("Unknown groupBy key \"{}\" (expected SOURCE, PROVIDER, MODEL, AGENT, SESSION, DAY or MONTH)", name))
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            UsageKey::Source => "SOURCE",
+            UsageKey::Provider => "PROVIDER",
+            UsageKey::Model => "MODEL",
+            UsageKey::Agent => "AGENT",
+            UsageKey::Session => "SESSION",
+            UsageKey::Day => "DAY",
+            UsageKey::Month => "MONTH",
+        }
+    }
+
+    /// Response field holding this key
+    fn field(self) -> &'static str {
+        match self {
+            UsageKey::Source => "source",
+            UsageKey::Provider => "provider",
+            UsageKey::Model => "model",
+            UsageKey::Agent => "agent",
+            UsageKey::Session => "sessionId",
+            UsageKey::Day => "date",
+            UsageKey::Month => "month",
+        }
+    }
+
+    fn value(self, message: &UnifiedMessage) -> Option<String> {
+        match self {
+            UsageKey::Source => Some(message.source.to_string()),
+            UsageKey::Provider => Some(message.provider_id.to_string()),
+            UsageKey::Model => Some(message.model_id.clone()),
+            UsageKey::Agent => message.agent.clone(),
+            // Session ids are only unique within a source
+            UsageKey::Session => Some(format!("{}:{}", message.source, message.session_id)),
+            UsageKey::Day => Some(message.date.clone()),
+            UsageKey::Month => Some(message.date.chars().take(7).collect()),
+        }
+    }
+}
+
+#[derive(Default)]
+struct UsageGroup {
+    tokens: TokenBreakdown,
+    cost: f64,
+    message_count: u64,
+}
+
+/// Totals per combination of `keys`, most expensive first
+fn usage_groups(messages: &[UnifiedMessage], keys: &[UsageKey]) -> Vec<Value> {
+    let mut groups: HashMap<Vec<Option<String>>, UsageGroup> = HashMap::new();
+    for message in messages {
+        let key = keys.iter().map(|k| k.value(message)).collect();
+        let group = groups.entry(key).or_default();
+        group.tokens.input += message.tokens.input;
+        group.tokens.output += message.tokens.output;
+        group.tokens.cache_read += message.tokens.cache_read;
+        group.tokens.cache_write += message.tokens.cache_write;
+        group.tokens.reasoning += message.tokens.reasoning;
+        group.tokens.training += message.tokens.training;
+        group.cost += message.cost;
+        group.message_count += 1;
+    }
+
+    let mut groups: Vec<_> = groups.into_iter().collect();
+    groups.sort_by(|(a_key, a), (b_key, b)| b.cost.total_cmp(&a.cost).then_with(|| a_key.cmp(b_key)));
+    groups
+        .into_iter()
+        .map(|(values, group)| {
+            let mut entry = json!({
+                "source": null,
+                "provider": null,
+                "model": null,
+                "agent": null,
+                "sessionId": null,
+                "date": null,
+                "month": null,
+                "tokens": group.tokens,
+                "cost": group.cost,
+                "messageCount": group.message_count,
+            });
+            for (key, value) in keys.iter().zip(values) {
+                entry[key.field()] = json!(value);
+            }
+            entry
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_query(query: &str, variables: Value) -> Result<Vec<Field>, String> {
+        parse(query, variables.as_object().unwrap(), None)
+    }
+
+    #[test]
+    fn test_parse_aliases_arguments_and_variables() {
+        let query = r#"
+            query Dashboard($since: String = "2025-01-01", $keys: [UsageKey!]) {
+              byModel: usage(since: $since, groupBy: $keys, limit: 5) { model cost }
+              sessions(sources: ["claude", "codex"]) { nextCursor }
+            }
+        "#;
+        let fields = parse_query(query, json!({ "keys": ["MODEL"] })).unwrap();
+        assert_eq!(fields.len(), 2);
+        assert_eq!(fields[0].key(), "byModel");
+        assert_eq!(fields[0].name, "usage");
+        assert_eq!(fields[0].argument("since"), Some(&json!("2025-01-01")));
+        assert_eq!(fields[0].argument("groupBy"), Some(&json!(["MODEL"])));
+        assert_eq!(fields[0].argument("limit"), Some(&json!(5)));
+        assert_eq!(fields[0].selection.iter().map(Field::key).collect::<Vec<_>>(), ["model", "cost"]);
+        assert_eq!(fields[1].argument("sources"), Some(&json!(["claude", "codex"])));
+        validate("Query", &fields).unwrap();
+    }
+
+    #[test]
+    fn test_rejects_unsupported_and_unknown() {
+        let error = |query: &str| {
+            parse_query(query, json!({})).and_then(|fields| validate("Query", &fields)).unwrap_err()
+        };
+        assert!(error("mutation { usage { cost } }").contains("Only queries"));
+        assert!(error("{ usage @skip(if: true) { cost } }").contains("Directives"));
+        assert!(error("{ usage { price } }").contains("Cannot query field \"price\" on type UsageGroup"));
+        assert!(error("{ usage(colour: 1) { cost } }").contains("Unknown argument \"colour\""));
+        assert!(error("{ models }").contains("needs a selection"));
+        assert!(error("{ usage { cost { x } } }").contains("Float"));
+        assert!(error("{ usage { cost }").contains("end of query"));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = |query: &str| parse_query(query, json!({})).unwrap_err();
+        assert_eq!(error(""), "No operation in query");
+        assert_eq!(error("{ }"), "Empty selection");
+        assert_eq!(error("{ usage(limit: ) { cost } }"), "Expected a value, found ')'");
+        assert_eq!(error("{ usage { cost } } }"), "Expected an operation, found '}'");
+        assert_eq!(error("query Q($x String) { models { totalCost } }"), "Expected ':', found \"String\"");
+        assert_eq!(error("{ usage(since: \"2025) { cost } }"), "Unterminated string");
+        assert_eq!(error("{ usage(since: \"\\q\") { cost } }"), "Invalid escape in string");
+        assert_eq!(error("{ usage(since: \"\"\"x\"\"\") { cost } }"), "Block strings are not supported");
+        assert_eq!(error("{ usage { cost; } }"), "Unexpected character ';'");
+        assert_eq!(error("{ models { totalCost }"), "Unexpected end of query");
+
+        let two = "query A { models { totalCost } } query B { usage { cost } }";
+        assert!(error(two).contains("operationName is required"));
+        assert_eq!(parse(two, &Map::new(), Some("B")).unwrap()[0].name, "usage");
+        assert_eq!(parse(two, &Map::new(), Some("C")).unwrap_err(), "No operation named \"C\"");
+    }
+
+    #[test]
+    fn test_variables() {
+        let query = r#"
+            query Q($since: String = "2025-01-01", $limit: Int, $sources: [String!] = ["claude"]) {
+              usage(since: $since, limit: $limit, sources: $sources) { cost }
+            }
+        "#;
+        let fields = parse_query(query, json!({})).unwrap();
+        assert_eq!(fields[0].argument("since"), Some(&json!("2025-01-01")));
+        assert_eq!(fields[0].argument("sources"), Some(&json!(["claude"])));
+        // Unset variables without a default leave the argument out
+        assert_eq!(fields[0].argument("limit"), None);
+
+        let fields = parse_query(query, json!({ "since": "2025-06-01", "limit": 3, "sources": null })).unwrap();
+        assert_eq!(fields[0].argument("since"), Some(&json!("2025-06-01")));
+        assert_eq!(fields[0].argument("limit"), Some(&json!(3)));
+        assert_eq!(fields[0].argument("sources"), None);
+
+        // Defaults belong to their own operation
+        let query = r#"
+            query A($since: String = "a") { usage(since: $since) { cost } }
+            query B($since: String = "b") { usage(since: $since) { cost } }
+        "#;
+        let fields = parse(query, &Map::new(), Some("B")).unwrap();
+        assert_eq!(fields[0].argument("since"), Some(&json!("b")));
+    }
+
+    #[test]
+    fn test_fragments() {
+        let query = r#"
+            query Q($model: String) {
+              sessions { ...Page }
+              pricing(model: $model) { ... on Rate { model } }
+              models { ... { totalCost } }
+            }
+            fragment Page on SessionPage {
+              nextCursor
+              sessions { ...Tokens sessionId }
+              sessions { tokens { output } }
+            }
+            fragment Tokens on Session { tokens { input } }
+        "#;
+        let fields = parse_query(query, json!({ "model": "gpt-4o" })).unwrap();
+        validate("Query", &fields).unwrap();
+        fn keys(fields: &[Field]) -> Vec<&str> {
+            fields.iter().map(Field::key).collect()
+        }
+
+        assert_eq!(keys(&fields[0].selection), ["nextCursor", "sessions"]);
+        // Both `sessions` selections merge, including the nested `tokens`
+        let sessions = &fields[0].selection[1].selection;
+        assert_eq!(keys(sessions), ["tokens", "sessionId"]);
+        assert_eq!(keys(&sessions[0].selection), ["input", "output"]);
+        assert_eq!(fields[1].argument("model"), Some(&json!("gpt-4o")));
+        assert_eq!(keys(&fields[1].selection), ["model"]);
+        assert_eq!(keys(&fields[2].selection), ["totalCost"]);
+
+        let error = |query: &str| parse_query(query, json!({})).unwrap_err();
+        assert_eq!(error("{ usage { ...F } }"), "Unknown fragment \"F\"");
+        assert_eq!(error("{ usage { cost } } fragment F on UsageGroup { cost }"), "Fragment \"F\" is never used");
+        assert_eq!(
+            error("{ usage { ...F } } fragment F on UsageGroup { cost } fragment F on UsageGroup { model }"),
+            "There can be only one fragment named \"F\""
+        );
+        assert_eq!(error("{ usage { ...F } } fragment F on Nope { cost }"), "Unknown type \"Nope\"");
+        assert_eq!(
+            error("{ usage { ...F } } fragment F on Rate { model }"),
+            "Fragment \"F\" cannot be spread here as objects of type \"UsageGroup\" can never be of type \"Rate\""
+        );
+        assert!(error("{ usage { ... on Rate { model } } }").contains("can never be of type \"Rate\""));
+        assert_eq!(
+            error("{ models { ...A } } fragment A on ModelReport { ...B } fragment B on ModelReport { ...A }"),
+            "Cannot spread fragment \"A\" within itself"
+        );
+        assert_eq!(
+            error("{ usage { x: cost ...F } } fragment F on UsageGroup { x: model }"),
+            "Fields \"x\" conflict because they select different fields or arguments"
+        );
+        assert_eq!(
+            error("{ usage(limit: 1) { cost } usage(limit: 2) { model } }"),
+            "Fields \"usage\" conflict because they select different fields or arguments"
+        );
+    }
+
+    #[test]
+    fn test_project_follows_selection() {
+        let fields = parse_query("{ messages { page: nextCursor messages { __typename tokens { input } } } }", json!({}))
+            .unwrap();
+        let value = json!({
+            "messages": [{ "id": "a", "tokens": { "input": 3, "output": 4 } }],
+            "nextCursor": null,
+        });
+        let projected = project(&value, "MessagePage!", &fields[0].selection);
+        assert_eq!(
+            projected,
+            json!({ "page": null, "messages": [{ "__typename": "Message", "tokens": { "input": 3 } }] })
+        );
+    }
+
+    #[test]
+    fn test_usage_groups_by_keys() {
+        let message = |model: &str, date: &str, cost: f64| UnifiedMessage {
+            date: date.to_string(),
+            ..UnifiedMessage::new("claude", model, "anthropic", "s1", 0, TokenBreakdown { input: 10, ..Default::default() }, cost)
+        };
+        let messages = vec![
+            message("sonnet", "2025-03-01", 1.0),
+            message("sonnet", "2025-04-02", 2.0),
+            message("opus", "2025-04-03", 5.0),
+        ];
+
+        let groups = usage_groups(&messages, &[UsageKey::Model]);
+        assert_eq!(groups.len(), 2);
+        assert_eq!((groups[0]["model"].as_str(), groups[0]["cost"].as_f64()), (Some("opus"), Some(5.0)));
+        assert_eq!(groups[1]["messageCount"], 2);
+        assert_eq!(groups[1]["month"], Value::Null);
+
+        let groups = usage_groups(&messages, &[UsageKey::Month]);
+        assert_eq!(groups[0]["month"], "2025-04");
+        assert_eq!(groups[0]["tokens"]["input"], 20);
+        assert_eq!(usage_groups(&messages, &[]).len(), 1);
+    }
+}
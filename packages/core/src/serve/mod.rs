@@ -0,0 +1,1013 @@
+//! HTTP server for sharing usage with dashboards and CI jobs
+//!
+//! `tokscale serve` exposes reports over HTTP and accepts recorded events:
+//!
+//! | Endpoint | Scope |
+//! |----------|-------|
+//! | `GET /api/health` | none |
+//! | `GET /api/report`, `GET /api/graph` (`since`, `until`, `year`, `sources`) | read |
+//! | `GET /api/messages`, `GET /api/sessions` (also `limit`, `cursor`; see [`page`]) | read |
+//! | `POST /api/events` (NDJSON, see [`crate::sessions::recorded`]) | push |
+//! | `GET /api/graphql`, `POST /api/graphql` (see [`graphql`]) | read |
+//! | `GET /api/sync`, `POST /api/sync` (watermarked NDJSON, see [`sync`]) | push |
+//! | `GET /api/tokens`, `POST /api/tokens`, `DELETE /api/tokens/<name>` | admin |
+//! | `GET /openapi.json`, `GET /docs` (see [`openapi`]) | none |
+//!
+//! Requests authenticate with `Authorization: Bearer <token>` (see
+//! [`tokens`]). A server with no tokens serves read endpoints without one,
+//! so it only starts on a loopback address; push and admin endpoints always
+//! need a token. A loopback server also refuses requests addressed to
+//! another host name or sent from another site's page, so web pages can't
+//! reach it through the browser (by DNS rebinding or cross-site requests).
+//!
+//! Each client is limited to `rate_limit_per_minute` requests (see
+//! [`rate_limit`]); beyond that it gets 429 with `Retry-After`. Read
+//! endpoints are answered from [`cache`] while the underlying data is
+//! unchanged; `X-Tokscale-Cache` says whether a response was. With
+//! `memory.streaming` set, reports, graphs and message pages are aggregated
+//! while files are parsed (see [`crate::streaming`]).
+//!
+//! Webhooks in settings.json are notified of new sessions and sessions
+//! crossing a cost threshold (see [`webhooks`]); other event sinks get the
+//! same events as NDJSON or OpenTelemetry logs (see [`sinks`]).
+
+pub mod cache;
+pub mod graphql;
+pub mod openapi;
+pub mod page;
+pub mod rate_limit;
+pub mod sinks;
+pub mod sync;
+pub mod tokens;
+pub mod webhooks;
+
+use crate::api::{Filter, PricingConfig, Tokscale, TokscaleError};
+use crate::http;
+use crate::recorder::Recorder;
+use crate::sessions::Source;
+use serde_json::{json, Value};
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use cache::ReportCache;
+use page::Cursor;
+use rate_limit::RateLimiter;
+use sync::SyncStore;
+use tokens::{Scope, TokenStore};
+use tokio::io::{AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+pub const DEFAULT_PORT: u16 = 8788;
+pub const DEFAULT_RATE_LIMIT_PER_MINUTE: u32 = 120;
+
+#[derive(Clone)]
+pub struct ServeConfig {
+    pub listen: SocketAddr,
+    /// Home directory whose usage is served and where pushed events are recorded
+    pub home_dir: PathBuf,
+    pub pricing: PricingConfig,
+    /// Requests each client may make per minute; `None` for no limit
+    pub rate_limit_per_minute: Option<u32>,
+    /// Webhooks to notify; `None` to follow `webhooks` in settings.json
+    pub webhooks: Option<Vec<webhooks::Webhook>>,
+    /// Other sinks for events; `None` to follow `eventSinks` in settings.json
+    pub event_sinks: Option<Vec<sinks::SinkConfig>>,
+    /// Sinks besides those configured, for programs embedding the server
+    pub sinks: Vec<Arc<dyn sinks::EventSink>>,
+    /// How often to check for session events
+    pub webhook_interval: Duration,
+}
+
+impl ServeConfig {
+    pub fn new(home_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            listen: SocketAddr::from(([127, 0, 0, 1], DEFAULT_PORT)),
+            home_dir: home_dir.into(),
+            pricing: PricingConfig::default(),
+            rate_limit_per_minute: Some(DEFAULT_RATE_LIMIT_PER_MINUTE),
+            webhooks: None,
+            event_sinks: None,
+            sinks: Vec::new(),
+            webhook_interval: webhooks::DEFAULT_INTERVAL,
+        }
+    }
+}
+
+pub struct Server {
+    listener: TcpListener,
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    config: ServeConfig,
+    recorder: Recorder,
+    tokens: Mutex<TokenStore>,
+    limiter: Option<RateLimiter>,
+    cache: ReportCache,
+    sync: Mutex<SyncStore>,
+}
+
+impl Server {
+    pub async fn bind(config: ServeConfig) -> Result<Self, String> {
+        let home_dir = config.home_dir.to_string_lossy().into_owned();
+        let tokens = TokenStore::load(tokens::tokens_path(&home_dir))?;
+        if tokens.is_empty() && !config.listen.ip().is_loopback() {
+            return Err(format!(
+                "Refusing to serve on {} without API tokens; create one with 'tokscale serve token create <name> --scope read'",
+                config.listen
+            ));
+        }
+        let recorder = Recorder::open(&home_dir)?;
+        let sync = SyncStore::load(sync::sync_path(&home_dir))?;
+        let listener = TcpListener::bind(config.listen)
+            .await
+            .map_err(|e| format!("Failed to listen on {}: {}", config.listen, e))?;
+        let limiter = config.rate_limit_per_minute.filter(|&n| n > 0).map(RateLimiter::new);
+        Ok(Self {
+            listener,
+            shared: Arc::new(Shared {
+                config,
+                recorder,
+                tokens: Mutex::new(tokens),
+                limiter,
+                cache: ReportCache::new(),
+                sync: Mutex::new(sync),
+            }),
+        })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr, String> {
+        self.listener.local_addr().map_err(|e| e.to_string())
+    }
+
+    /// Accept connections until the task is dropped
+    pub async fn serve(self) -> Result<(), String> {
+        let config = &self.shared.config;
+        let no_sinks = config.webhooks.as_ref().is_some_and(Vec::is_empty)
+            && config.event_sinks.as_ref().is_some_and(Vec::is_empty)
+            && config.sinks.is_empty();
+        let _webhooks = if no_sinks { None } else { Some(AbortOnDrop(tokio::spawn(webhooks::run(config.clone())))) };
+        loop {
+            let (stream, peer) = self.listener.accept().await.map_err(|e| e.to_string())?;
+            let shared = Arc::clone(&self.shared);
+            tokio::spawn(async move { handle_connection(stream, peer, shared).await });
+        }
+    }
+}
+
+/// Stops a background task along with the server
+struct AbortOnDrop(tokio::task::JoinHandle<()>);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+struct Response {
+    status: u16,
+    /// Rendered JSON, unless `content_type` says otherwise
+    body: Option<Arc<str>>,
+    content_type: &'static str,
+    headers: Vec<(String, String)>,
+}
+
+impl Response {
+    fn json(status: u16, body: Value) -> Self {
+        Self::rendered(status, Arc::from(body.to_string()))
+    }
+
+    fn rendered(status: u16, body: Arc<str>) -> Self {
+        Self { status, body: Some(body), content_type: "application/json", headers: Vec::new() }
+    }
+
+    fn html(body: &str) -> Self {
+        Self { content_type: "text/html; charset=utf-8", ..Self::rendered(200, Arc::from(body)) }
+    }
+
+    fn error(status: u16, message: impl Into<String>) -> Self {
+        Self::json(status, json!({ "error": message.into() }))
+    }
+
+    fn no_content() -> Self {
+        Self { status: 204, body: None, content_type: "application/json", headers: Vec::new() }
+    }
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        201 => "Created",
+        204 => "No Content",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        429 => "Too Many Requests",
+        _ => "Internal Server Error",
+    }
+}
+
+async fn handle_connection(stream: TcpStream, peer: SocketAddr, shared: Arc<Shared>) {
+    let (read, mut write) = stream.into_split();
+    let response = match http::read_request(&mut BufReader::new(read)).await {
+        Ok(Some(request)) => handle(Arc::new(request), peer, &shared).await,
+        Ok(None) => return,
+        Err(e) => Response::error(400, e),
+    };
+    let _ = send(&mut write, response).await;
+}
+
+async fn send<W: AsyncWrite + Unpin>(writer: &mut W, response: Response) -> std::io::Result<()> {
+    let body = response.body.as_deref().unwrap_or("");
+    let mut headers = response.headers;
+    if response.status != 204 {
+        headers.push(("content-type".to_string(), response.content_type.to_string()));
+        headers.push(("content-length".to_string(), body.len().to_string()));
+    }
+    if response.status == 401 {
+        headers.push(("www-authenticate".to_string(), "Bearer".to_string()));
+    }
+    http::write_head(writer, response.status, reason(response.status), &headers, false).await?;
+    writer.write_all(body.as_bytes()).await?;
+    writer.flush().await
+}
+
+enum Route {
+    Report,
+    Graph,
+    Messages,
+    Sessions,
+    GraphQl,
+    Events,
+    SyncState,
+    SyncUpload,
+    ListTokens,
+    CreateToken,
+    RevokeToken(String),
+}
+
+/// Every path the server answers, with sub-paths (`/api/tokens/{name}`);
+/// anything else is a 404 before routing
+const PATHS: &[&str] = &[
+    "/api/health",
+    "/api/report",
+    "/api/graph",
+    "/api/messages",
+    "/api/sessions",
+    "/api/graphql",
+    "/api/events",
+    "/api/sync",
+    "/api/tokens",
+    "/openapi.json",
+    "/docs",
+];
+
+/// The scope and route for a request, or the response for public pages and
+/// unknown paths or methods
+fn route(method: &str, path: &str) -> Result<(Scope, Route), Response> {
+    let known = PATHS.iter().any(|known| {
+        path.strip_prefix(known).is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+    });
+    if !known {
+        return Err(Response::error(404, format!("No endpoint at {}", path)));
+    }
+    Ok(match (method, path) {
+        ("GET", "/api/health") => return Err(Response::json(200, json!({ "status": "ok" }))),
+        ("GET", "/openapi.json") => return Err(Response::json(200, openapi::document())),
+        ("GET", "/docs") => return Err(Response::html(openapi::DOCS_PAGE)),
+        ("GET", "/api/report") => (Scope::Read, Route::Report),
+        ("GET", "/api/graph") => (Scope::Read, Route::Graph),
+        ("GET", "/api/messages") => (Scope::Read, Route::Messages),
+        ("GET", "/api/sessions") => (Scope::Read, Route::Sessions),
+        ("GET" | "POST", "/api/graphql") => (Scope::Read, Route::GraphQl),
+        ("POST", "/api/events") => (Scope::Push, Route::Events),
+        ("GET", "/api/sync") => (Scope::Push, Route::SyncState),
+        ("POST", "/api/sync") => (Scope::Push, Route::SyncUpload),
+        ("GET", "/api/tokens") => (Scope::Admin, Route::ListTokens),
+        ("POST", "/api/tokens") => (Scope::Admin, Route::CreateToken),
+        ("DELETE", path) if path.starts_with("/api/tokens/") => {
+            let name = http::percent_decode(&path["/api/tokens/".len()..]);
+            (Scope::Admin, Route::RevokeToken(name))
+        }
+        (_, path) if PATHS.contains(&path) => {
+            return Err(Response::error(405, format!("{} is not supported here", method)));
+        }
+        (_, path) => return Err(Response::error(404, format!("No endpoint at {}", path))),
+    })
+}
+
+async fn handle(request: Arc<http::Request>, peer: SocketAddr, shared: &Arc<Shared>) -> Response {
+    if shared.config.listen.ip().is_loopback() {
+        if let Err(response) = check_local(&request) {
+            return response;
+        }
+    }
+    let (needed, route) = match route(request.method.as_str(), request.path()) {
+        Ok(routed) => routed,
+        Err(response) => return response,
+    };
+
+    // Requests without a known token (including on an open server) are
+    // limited by address, so guessing tokens is slow too
+    let (client, access) = authorize(&request, shared, needed).await;
+    // Watermarks are kept per token, which push endpoints always have
+    let sync_client = client.clone().unwrap_or_default();
+    let client = client.unwrap_or_else(|| format!("address {}", peer.ip()));
+    if let Some(limiter) = &shared.limiter {
+        if let Err(wait) = limiter.check(&client, Instant::now()) {
+            let mut response = Response::error(429, "Too many requests");
+            let seconds = wait.as_secs() + u64::from(wait.subsec_nanos() > 0);
+            response.headers.push(("retry-after".to_string(), seconds.to_string()));
+            return response;
+        }
+    }
+    if let Err(response) = access {
+        return response;
+    }
+
+    match route {
+        Route::Report | Route::Graph | Route::Messages | Route::Sessions => read(&route, &request, shared).await,
+        Route::GraphQl => graphql_query(&request, shared).await,
+        Route::Events => blocking(shared, move |shared| record_events(&request, shared)).await,
+        Route::SyncState => {
+            blocking(shared, move |shared| {
+                let store = shared.sync.lock().unwrap_or_else(|e| e.into_inner());
+                let watermark = store.watermark(&sync_client).map(|mark| mark.timestamp);
+                Response::json(200, json!({ "client": sync_client, "watermark": watermark }))
+            })
+            .await
+        }
+        Route::SyncUpload => blocking(shared, move |shared| sync_events(&request, shared, &sync_client)).await,
+        Route::ListTokens => blocking(shared, |shared| with_tokens(shared, |store| {
+            let tokens: Vec<Value> = store
+                .entries()
+                .iter()
+                .map(|e| json!({ "name": e.name, "scope": e.scope, "createdAt": e.created_at }))
+                .collect();
+            Response::json(200, Value::Array(tokens))
+        }))
+        .await,
+        Route::CreateToken => blocking(shared, move |shared| create_token(&request, shared)).await,
+        Route::RevokeToken(name) => blocking(shared, move |shared| {
+            with_tokens(shared, |store| match store.revoke(&name) {
+                Ok(true) => Response::no_content(),
+                Ok(false) => Response::error(404, format!("No token named \"{}\"", name)),
+                Err(e) => Response::error(500, e),
+            })
+        })
+        .await,
+    }
+}
+
+/// Run `f` on a blocking thread, since it reads files or waits on locks. A
+/// panic in `f` is resumed in the caller.
+async fn blocking<T, F>(shared: &Arc<Shared>, f: F) -> T
+where
+    T: Send + 'static,
+    F: FnOnce(&Shared) -> T + Send + 'static,
+{
+    let shared = Arc::clone(shared);
+    tokio::task::spawn_blocking(move || f(&shared))
+        .await
+        .unwrap_or_else(|e| std::panic::resume_unwind(e.into_panic()))
+}
+
+/// Refuse requests to a loopback server that name another host (as after
+/// DNS rebinding) or come from a page on another site
+fn check_local(request: &http::Request) -> Result<(), Response> {
+    let host = request.header("host").unwrap_or("");
+    if !is_loopback_host(host) {
+        return Err(Response::error(403, format!("Host \"{}\" is not a loopback address", host)));
+    }
+    if let Some(origin) = request.header("origin") {
+        let authority = origin.strip_prefix("http://").or_else(|| origin.strip_prefix("https://"));
+        if !authority.is_some_and(is_loopback_host) {
+            return Err(Response::error(403, format!("Requests from {} are not allowed", origin)));
+        }
+    }
+    Ok(())
+}
+
+/// Whether a `host[:port]` authority names this machine
+fn is_loopback_host(authority: &str) -> bool {
+    let host = match authority.strip_prefix('[') {
+        Some(bracketed) => bracketed.split_once(']').map_or("", |(host, _)| host),
+        None => authority.rsplit_once(':').map_or(authority, |(host, _)| host),
+    };
+    host.eq_ignore_ascii_case("localhost") || host.parse::<IpAddr>().is_ok_and(|ip| ip.is_loopback())
+}
+
+/// Whether `request` may do what `needed` covers, and the client it comes
+/// from if its token is known
+async fn authorize(request: &http::Request, shared: &Arc<Shared>, needed: Scope) -> (Option<String>, Result<(), Response>) {
+    let token = request
+        .header("authorization")
+        .and_then(|value| value.split_once(' '))
+        .filter(|(scheme, _)| scheme.eq_ignore_ascii_case("bearer"))
+        .map(|(_, token)| token.trim().to_string());
+    blocking(shared, move |shared| check_token(shared, token.as_deref(), needed)).await
+}
+
+fn check_token(shared: &Shared, token: Option<&str>, needed: Scope) -> (Option<String>, Result<(), Response>) {
+    let mut store = shared.tokens.lock().unwrap_or_else(|e| e.into_inner());
+    if let Err(e) = store.refresh() {
+        return (None, Err(Response::error(500, e)));
+    }
+    if store.is_empty() {
+        // Without tokens only reads are open, and only on loopback
+        return if needed != Scope::Read {
+            let message = format!(
+                "This needs a token with {} scope; create one with 'tokscale serve token create <name> --scope {}'",
+                needed.as_str(),
+                needed.as_str()
+            );
+            (None, Err(Response::error(401, message)))
+        } else if shared.config.listen.ip().is_loopback() {
+            (None, Ok(()))
+        } else {
+            (None, Err(Response::error(401, "No API tokens are configured")))
+        };
+    }
+
+    let Some(token) = token else {
+        return (None, Err(Response::error(401, "Missing bearer token")));
+    };
+    let Some(entry) = store.authenticate(token) else {
+        return (None, Err(Response::error(401, "Invalid token")));
+    };
+    let client = Some(format!("token {}", entry.name));
+    if entry.scope.allows(needed) {
+        (client, Ok(()))
+    } else {
+        let message = format!("Token \"{}\" has {} scope; this needs {}", entry.name, entry.scope.as_str(), needed.as_str());
+        (client, Err(Response::error(403, message)))
+    }
+}
+
+fn with_tokens(shared: &Shared, f: impl FnOnce(&mut TokenStore) -> Response) -> Response {
+    let mut store = shared.tokens.lock().unwrap_or_else(|e| e.into_inner());
+    f(&mut store)
+}
+
+/// Reader and filter for the `sources`, `since`, `until` and `year` parameters
+fn query_target(request: &http::Request, shared: &Shared) -> Result<(Tokscale, Filter), Response> {
+    let mut builder = Tokscale::builder()
+        .home_dir(&shared.config.home_dir)
+        .pricing(shared.config.pricing.clone());
+    if let Some(sources) = request.query_param("sources") {
+        builder = builder.sources(sources.split(',').filter(|s| !s.is_empty()).map(Source::from));
+    }
+    let tokscale = builder.build().map_err(|e| Response::error(400, e.to_string()))?;
+
+    let filter = Filter {
+        since: request.query_param("since"),
+        until: request.query_param("until"),
+        year: request.query_param("year"),
+        ..Filter::default()
+    };
+    Ok((tokscale, filter))
+}
+
+/// `cursor` and `limit` parameters of the listing endpoints
+fn page_params(request: &http::Request) -> Result<(Option<Cursor>, usize), Response> {
+    let cursor = match request.query_param("cursor") {
+        Some(cursor) => Some(Cursor::decode(&cursor).map_err(|e| Response::error(400, e))?),
+        None => None,
+    };
+    let limit = page::parse_limit(request.query_param("limit").as_deref()).map_err(|e| Response::error(400, e))?;
+    Ok((cursor, limit))
+}
+
+/// Read endpoints, answered from the cache while the data they cover is
+/// unchanged
+async fn read(route: &Route, request: &http::Request, shared: &Arc<Shared>) -> Response {
+    let (tokscale, filter) = match query_target(request, shared) {
+        Ok(target) => target,
+        Err(response) => return response,
+    };
+    let (cursor, limit) = match page_params(request) {
+        Ok(params) => params,
+        Err(response) => return response,
+    };
+
+    let tokscale = Arc::new(tokscale);
+    let key = cache::key(request);
+    let generation = shared.cache.generation();
+    let data_version = {
+        let tokscale = Arc::clone(&tokscale);
+        blocking(shared, move |_| tokscale.data_version()).await
+    };
+    if let Some(body) = shared.cache.get(&key, generation, data_version) {
+        let mut response = Response::rendered(200, body);
+        response.headers.push(("x-tokscale-cache".to_string(), "hit".to_string()));
+        return response;
+    }
+
+    let mut response = match route {
+        Route::Report => result(tokscale.report(filter).await),
+        Route::Graph => result(tokscale.graph(filter).await),
+        Route::Messages if tokscale.memory_limits().is_some() => {
+            match page::stream_message_page(&tokscale, filter, cursor, limit).await {
+                Ok(page) => listing("messages", page),
+                Err(e) => Response::error(500, e.to_string()),
+            }
+        }
+        Route::Messages => match tokscale.messages(filter).await {
+            Ok(messages) => {
+                let page = page::paginate(page::message_items(messages), page::MessageItem::key, cursor.as_ref(), limit);
+                listing("messages", page)
+            }
+            Err(e) => Response::error(500, e.to_string()),
+        },
+        Route::Sessions => match tokscale.messages(filter).await {
+            Ok(messages) => {
+                let page = page::paginate(page::session_items(&messages), page::SessionItem::key, cursor.as_ref(), limit);
+                listing("sessions", page)
+            }
+            Err(e) => Response::error(500, e.to_string()),
+        },
+        _ => unreachable!("not a read endpoint"),
+    };
+    if let (200, Some(body)) = (response.status, &response.body) {
+        shared.cache.insert(key, generation, data_version, Arc::clone(body));
+        response.headers.push(("x-tokscale-cache".to_string(), "miss".to_string()));
+    }
+    response
+}
+
+fn listing<T: serde::Serialize>(name: &str, page: page::Page<T>) -> Response {
+    match serde_json::to_value(&page.items) {
+        Ok(items) => Response::json(200, json!({ name: items, "nextCursor": page.next_cursor })),
+        Err(e) => Response::error(500, e.to_string()),
+    }
+}
+
+fn result<T: serde::Serialize>(result: Result<T, TokscaleError>) -> Response {
+    match result.map(|value| serde_json::to_value(value)) {
+        Ok(Ok(value)) => Response::json(200, value),
+        Ok(Err(e)) => Response::error(500, e.to_string()),
+        Err(e) => Response::error(500, e.to_string()),
+    }
+}
+
+async fn graphql_query(request: &http::Request, shared: &Shared) -> Response {
+    let query = match request.method.as_str() {
+        "GET" => graphql::GraphQlRequest::from_params(
+            request.query_param("query"),
+            request.query_param("variables"),
+            request.query_param("operationName"),
+        ),
+        _ => graphql::GraphQlRequest::from_json(&request.body),
+    };
+    // GraphQL clients read errors from "errors", not "error"
+    match query {
+        Ok(query) => match graphql::execute(&query, &shared.config).await {
+            Ok(body) => Response::json(200, body),
+            Err(e) => Response::json(400, json!({ "errors": [{ "message": e }] })),
+        },
+        Err(e) => Response::json(400, json!({ "errors": [{ "message": e }] })),
+    }
+}
+
+fn record_events(request: &http::Request, shared: &Shared) -> Response {
+    if request.body.is_empty() {
+        return Response::error(400, "Expected NDJSON events in the request body");
+    }
+    match shared.recorder.ingest(&request.body[..]) {
+        Ok(stats) => {
+            if stats.recorded > 0 {
+                shared.cache.invalidate();
+            }
+            let errors: Vec<Value> = stats
+                .errors
+                .into_iter()
+                .map(|(line, message)| json!({ "line": line, "message": message }))
+                .collect();
+            Response::json(200, json!({ "recorded": stats.recorded, "errors": errors }))
+        }
+        Err(e) => Response::error(500, e),
+    }
+}
+
+/// Record the events in an upload the client hasn't sent before, then move
+/// its watermark past them
+fn sync_events(request: &http::Request, shared: &Shared, client: &str) -> Response {
+    let mut store = shared.sync.lock().unwrap_or_else(|e| e.into_inner());
+    let batch = sync::split_batch(&request.body, store.watermark(client));
+
+    if batch.accepted_count > 0 {
+        match shared.recorder.ingest(batch.accepted.as_bytes()) {
+            Ok(_) => shared.cache.invalidate(),
+            Err(e) => return Response::error(500, e),
+        }
+    }
+    if let Some(watermark) = batch.watermark.clone() {
+        if store.watermark(client) != Some(&watermark) {
+            if let Err(e) = store.set(client, watermark) {
+                return Response::error(500, e);
+            }
+        }
+    }
+
+    let errors: Vec<Value> = batch
+        .errors
+        .into_iter()
+        .map(|(line, message)| json!({ "line": line, "message": message }))
+        .collect();
+    let watermark = batch.watermark.map(|mark| mark.timestamp);
+    Response::json(
+        200,
+        json!({ "recorded": batch.accepted_count, "skipped": batch.skipped, "errors": errors, "watermark": watermark }),
+    )
+}
+
+fn create_token(request: &http::Request, shared: &Shared) -> Response {
+    let body: Value = match serde_json::from_slice(&request.body) {
+        Ok(body) => body,
+        Err(e) => return Response::error(400, format!("Expected {{\"name\", \"scope\"}}: {}", e)),
+    };
+    let name = body.get("name").and_then(Value::as_str).unwrap_or("");
+    let scope = match Scope::parse(body.get("scope").and_then(Value::as_str).unwrap_or("")) {
+        Ok(scope) => scope,
+        Err(e) => return Response::error(400, e),
+    };
+    with_tokens(shared, |store| match store.create(name, scope) {
+        Ok(token) => Response::json(201, json!({ "name": name.trim(), "scope": scope, "token": token })),
+        Err(e) => Response::error(400, e),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::StatusCode;
+    use tempfile::TempDir;
+
+    struct TestServer {
+        home: TempDir,
+        base: String,
+        client: reqwest::Client,
+        runtime: tokio::runtime::Runtime,
+    }
+
+    impl TestServer {
+        fn start(listen: SocketAddr, tokens: &[(&str, Scope)]) -> (Self, Vec<String>) {
+            Self::start_limited(listen, tokens, None)
+        }
+
+        fn start_limited(listen: SocketAddr, tokens: &[(&str, Scope)], rate_limit: Option<u32>) -> (Self, Vec<String>) {
+            let home = TempDir::new().unwrap();
+            let home_dir = home.path().to_string_lossy().into_owned();
+            let mut store = TokenStore::load(tokens::tokens_path(&home_dir)).unwrap();
+            let secrets = tokens.iter().map(|(name, scope)| store.create(name, *scope).unwrap()).collect();
+
+            let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+            let config = ServeConfig {
+                listen,
+                pricing: PricingConfig::reported(),
+                rate_limit_per_minute: rate_limit,
+                webhooks: Some(Vec::new()),
+                event_sinks: Some(Vec::new()),
+                ..ServeConfig::new(home.path())
+            };
+            let base = runtime.block_on(async {
+                let server = Server::bind(config).await.unwrap();
+                let addr = server.local_addr().unwrap();
+                tokio::spawn(server.serve());
+                format!("http://{}", addr)
+            });
+            (Self { home, base, client: reqwest::Client::new(), runtime }, secrets)
+        }
+
+        fn request(&self, method: &str, path: &str, token: Option<&str>, body: &str) -> (StatusCode, Value) {
+            let (status, _, body) = self.request_with_headers(method, path, token, body);
+            (status, body)
+        }
+
+        fn request_with_headers(
+            &self,
+            method: &str,
+            path: &str,
+            token: Option<&str>,
+            body: &str,
+        ) -> (StatusCode, reqwest::header::HeaderMap, Value) {
+            self.runtime.block_on(async {
+                let method = reqwest::Method::from_bytes(method.as_bytes()).unwrap();
+                let mut request = self.client.request(method, format!("{}{}", self.base, path)).body(body.to_string());
+                if let Some(token) = token {
+                    request = request.bearer_auth(token);
+                }
+                let response = request.send().await.unwrap();
+                let status = response.status();
+                let headers = response.headers().clone();
+                let text = response.text().await.unwrap();
+                (status, headers, serde_json::from_str(&text).unwrap_or(Value::Null))
+            })
+        }
+
+        fn request_from(&self, path: &str, headers: &[(&str, &str)]) -> StatusCode {
+            self.runtime.block_on(async {
+                let mut request = self.client.get(format!("{}{}", self.base, path));
+                for (name, value) in headers {
+                    request = request.header(*name, *value);
+                }
+                request.send().await.unwrap().status()
+            })
+        }
+
+        /// Record events as `tokscale record` would, without the server
+        fn record(&self, events: &str) {
+            let recorder = Recorder::open(&self.home.path().to_string_lossy()).unwrap();
+            assert!(recorder.ingest(events.as_bytes()).unwrap().errors.is_empty());
+        }
+    }
+
+    fn loopback() -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], 0))
+    }
+
+    #[test]
+    fn test_scoped_tokens() {
+        let (server, tokens) = TestServer::start(loopback(), &[("dashboard", Scope::Read), ("ci", Scope::Push)]);
+        let (read, push) = (tokens[0].as_str(), tokens[1].as_str());
+
+        assert_eq!(server.request("GET", "/api/health", None, "").0, StatusCode::OK);
+        assert_eq!(server.request("GET", "/api/report", None, "").0, StatusCode::UNAUTHORIZED);
+        assert_eq!(server.request("GET", "/api/report", Some("tks_wrong"), "").0, StatusCode::UNAUTHORIZED);
+        assert_eq!(server.request("GET", "/api/report", Some(push), "").0, StatusCode::FORBIDDEN);
+        assert_eq!(server.request("GET", "/api/tokens", Some(read), "").0, StatusCode::FORBIDDEN);
+
+        let event = r#"{"timestamp":"2025-03-01T10:00:00Z","model":"gpt-4o","input":100,"output":10}"#;
+        let (status, body) = server.request("POST", "/api/events", Some(push), event);
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["recorded"], 1);
+
+        let (status, report) = server.request("GET", "/api/report?sources=recorded&year=2025", Some(read), "");
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(report["totalMessages"], 1);
+        assert_eq!(report["entries"][0]["source"], "recorded");
+
+        let (status, graph) = server.request("GET", "/api/graph?since=2025-03-02", Some(read), "");
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(graph["contributions"].as_array().unwrap().len(), 0);
+
+        assert_eq!(server.request("GET", "/api/report?sources=zed", Some(read), "").0, StatusCode::BAD_REQUEST);
+        assert_eq!(server.request("PUT", "/api/report", Some(read), "").0, StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(server.request("GET", "/api/nope", Some(read), "").0, StatusCode::NOT_FOUND);
+        assert!(server.home.path().join(".config/tokscale/recorded").is_dir());
+    }
+
+    #[test]
+    fn test_admin_manages_tokens() {
+        let (server, tokens) = TestServer::start(loopback(), &[("root", Scope::Admin)]);
+        let admin = tokens[0].as_str();
+
+        let (status, created) = server.request("POST", "/api/tokens", Some(admin), r#"{"name":"grafana","scope":"read"}"#);
+        assert_eq!(status, StatusCode::CREATED);
+        let grafana = created["token"].as_str().unwrap().to_string();
+        assert_eq!(server.request("GET", "/api/report", Some(&grafana), "").0, StatusCode::OK);
+        assert_eq!(
+            server.request("POST", "/api/tokens", Some(admin), r#"{"name":"x","scope":"owner"}"#).0,
+            StatusCode::BAD_REQUEST
+        );
+
+        let (_, list) = server.request("GET", "/api/tokens", Some(admin), "");
+        let names: Vec<&str> = list.as_array().unwrap().iter().map(|t| t["name"].as_str().unwrap()).collect();
+        assert_eq!(names, ["root", "grafana"]);
+        assert!(list[0].get("hash").is_none());
+
+        assert_eq!(server.request("DELETE", "/api/tokens/grafana", Some(admin), "").0, StatusCode::NO_CONTENT);
+        assert_eq!(server.request("DELETE", "/api/tokens/grafana", Some(admin), "").0, StatusCode::NOT_FOUND);
+        assert_eq!(server.request("GET", "/api/report", Some(&grafana), "").0, StatusCode::UNAUTHORIZED);
+    }
+
+    #[test]
+    fn test_paginated_listings() {
+        let (server, _) = TestServer::start(loopback(), &[]);
+        let events: Vec<String> = (1..=5)
+            .map(|i| {
+                format!(
+                    r#"{{"id":"e{i}","timestamp":"2025-03-0{i}T10:00:00Z","model":"gpt-4o","sessionId":"s{}","input":{i}}}"#,
+                    i % 2
+                )
+            })
+            .collect();
+        server.record(&events.join("\n"));
+
+        let mut ids = Vec::new();
+        let mut path = "/api/messages?sources=recorded&limit=2".to_string();
+        loop {
+            let (status, page) = server.request("GET", &path, None, "");
+            assert_eq!(status, StatusCode::OK);
+            ids.extend(page["messages"].as_array().unwrap().iter().map(|m| m["tokens"]["input"].as_i64().unwrap()));
+            match page["nextCursor"].as_str() {
+                Some(cursor) => path = format!("/api/messages?sources=recorded&limit=2&cursor={}", cursor),
+                None => break,
+            }
+        }
+        assert_eq!(ids, [5, 4, 3, 2, 1]);
+
+        let (_, sessions) = server.request("GET", "/api/sessions?sources=recorded", None, "");
+        let sessions = sessions["sessions"].as_array().unwrap();
+        assert_eq!(sessions.len(), 2);
+        assert_eq!(sessions[0]["sessionId"], "s1");
+        assert_eq!(sessions[0]["messageCount"], 3);
+        assert_eq!(sessions[0]["tokens"]["input"], 9);
+
+        assert_eq!(server.request("GET", "/api/messages?cursor=bogus", None, "").0, StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn test_rate_limit_per_client() {
+        let (server, tokens) =
+            TestServer::start_limited(loopback(), &[("a", Scope::Read), ("b", Scope::Read)], Some(2));
+        let (a, b) = (tokens[0].as_str(), tokens[1].as_str());
+
+        assert_eq!(server.request("GET", "/api/tokens", Some(a), "").0, StatusCode::FORBIDDEN);
+        assert_eq!(server.request("GET", "/api/report", Some(a), "").0, StatusCode::OK);
+        let (status, headers, _) = server.request_with_headers("GET", "/api/report", Some(a), "");
+        assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(headers["retry-after"], "30");
+        assert_eq!(server.request("GET", "/api/report", Some(b), "").0, StatusCode::OK);
+
+        // Failed authentication is limited by address
+        assert_eq!(server.request("GET", "/api/report", Some("tks_guess"), "").0, StatusCode::UNAUTHORIZED);
+        assert_eq!(server.request("GET", "/api/report", Some("tks_guess"), "").0, StatusCode::UNAUTHORIZED);
+        assert_eq!(server.request("GET", "/api/report", Some("tks_guess"), "").0, StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(server.request("GET", "/api/health", None, "").0, StatusCode::OK);
+    }
+
+    #[test]
+    fn test_reports_cached_until_ingest() {
+        let (server, tokens) = TestServer::start(loopback(), &[("dashboard", Scope::Read), ("ci", Scope::Push)]);
+        let (read, push) = (tokens[0].as_str(), tokens[1].as_str());
+        let cache_header = |path: &str| {
+            let (status, headers, body) = server.request_with_headers("GET", path, Some(read), "");
+            assert_eq!(status, StatusCode::OK);
+            (headers["x-tokscale-cache"].to_str().unwrap().to_string(), body)
+        };
+        let event = |id: &str| format!(r#"{{"id":"{}","timestamp":"2025-03-01T10:00:00Z","model":"gpt-4o","input":10}}"#, id);
+
+        server.request("POST", "/api/events", Some(push), &event("a"));
+        assert_eq!(cache_header("/api/report?sources=recorded&year=2025").0, "miss");
+        let (hit, report) = cache_header("/api/report?year=2025&sources=recorded");
+        assert_eq!(hit, "hit");
+        assert_eq!(report["totalMessages"], 1);
+        assert_eq!(cache_header("/api/report?sources=recorded").0, "miss");
+
+        server.request("POST", "/api/events", Some(push), &event("b"));
+        let (status, report) = cache_header("/api/report?sources=recorded&year=2025");
+        assert_eq!(status, "miss");
+        assert_eq!(report["totalMessages"], 2);
+
+        // Files changed behind the server's back are noticed too
+        server.record(&event("c"));
+        let (status, report) = cache_header("/api/report?sources=recorded&year=2025");
+        assert_eq!(status, "miss");
+        assert_eq!(report["totalMessages"], 3);
+    }
+
+    #[test]
+    fn test_open_only_on_loopback() {
+        let (server, _) = TestServer::start(loopback(), &[]);
+        assert_eq!(server.request("GET", "/api/report", None, "").0, StatusCode::OK);
+        // Pushing and managing tokens always need a token
+        let event = r#"{"timestamp":"2025-03-01T10:00:00Z","model":"gpt-4o","input":100}"#;
+        assert_eq!(server.request("POST", "/api/events", None, event).0, StatusCode::UNAUTHORIZED);
+        assert_eq!(server.request("POST", "/api/sync", None, event).0, StatusCode::UNAUTHORIZED);
+        assert_eq!(server.request("GET", "/api/tokens", None, "").0, StatusCode::UNAUTHORIZED);
+        let (status, body) = server.request("POST", "/api/tokens", None, r#"{"name":"x","scope":"admin"}"#);
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert!(body["error"].as_str().unwrap().contains("admin scope"));
+        assert!(!tokens::tokens_path(&server.home.path().to_string_lossy()).exists());
+
+        let home = TempDir::new().unwrap();
+        let config = ServeConfig {
+            listen: SocketAddr::from(([0, 0, 0, 0], 0)),
+            ..ServeConfig::new(home.path())
+        };
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        let error = runtime.block_on(Server::bind(config)).err().unwrap();
+        assert!(error.contains("without API tokens"));
+    }
+
+    #[test]
+    fn test_refuses_other_hosts_and_sites() {
+        let (server, _) = TestServer::start(loopback(), &[]);
+        assert_eq!(server.request_from("/api/report", &[("origin", "http://localhost:3000")]), StatusCode::OK);
+        assert_eq!(server.request_from("/api/health", &[("host", "localhost:8788")]), StatusCode::OK);
+        assert_eq!(server.request_from("/api/health", &[("host", "[::1]:8788")]), StatusCode::OK);
+
+        // A rebound name still resolves here, but the Host header gives it away
+        assert_eq!(server.request_from("/api/report", &[("host", "attacker.example:8788")]), StatusCode::FORBIDDEN);
+        assert_eq!(server.request_from("/api/report", &[("host", "127.0.0.1.nip.io")]), StatusCode::FORBIDDEN);
+        assert_eq!(server.request_from("/api/report", &[("origin", "https://attacker.example")]), StatusCode::FORBIDDEN);
+        assert_eq!(server.request_from("/api/report", &[("origin", "null")]), StatusCode::FORBIDDEN);
+
+        assert!(is_loopback_host("127.0.0.1"));
+        assert!(is_loopback_host("LOCALHOST:80"));
+        assert!(!is_loopback_host(""));
+        assert!(!is_loopback_host("[::1"));
+    }
+
+    #[test]
+    fn test_sync_resumes_from_watermark() {
+        let (server, tokens) = TestServer::start(loopback(), &[("laptop", Scope::Push), ("desktop", Scope::Push)]);
+        let (laptop, desktop) = (tokens[0].as_str(), tokens[1].as_str());
+        let event = |id: &str, ms: i64| format!(r#"{{"id":"{}","timestamp":{},"model":"gpt-4o","input":10}}"#, id, ms);
+
+        let (status, state) = server.request("GET", "/api/sync", Some(laptop), "");
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(state["watermark"], Value::Null);
+
+        let batch = format!("{}\n{}", event("a", 1740823200000), event("b", 1740826800000));
+        let (_, body) = server.request("POST", "/api/sync", Some(laptop), &batch);
+        assert_eq!(body["recorded"], 2);
+        assert_eq!(body["watermark"], 1740826800000i64);
+
+        // A retried batch records nothing; the next one continues
+        let (_, body) = server.request("POST", "/api/sync", Some(laptop), &batch);
+        assert_eq!((body["recorded"].as_u64(), body["skipped"].as_u64()), (Some(0), Some(2)));
+        let (_, body) = server.request("POST", "/api/sync", Some(laptop), &event("c", 1740830400000));
+        assert_eq!(body["recorded"], 1);
+
+        // Watermarks are per token
+        assert_eq!(server.request("GET", "/api/sync", Some(desktop), "").1["watermark"], Value::Null);
+        assert_eq!(server.request("GET", "/api/sync", Some(laptop), "").1["watermark"], 1740830400000i64);
+        let recorded = crate::sessions::recorded::parse_recorded_file(
+            &server.home.path().join(".config/tokscale/recorded/2025-03-01.jsonl"),
+        );
+        assert_eq!(recorded.len(), 3);
+    }
+
+    #[test]
+    fn test_graphql_query() {
+        let (server, _) = TestServer::start(loopback(), &[]);
+        let events = [
+            r#"{"timestamp":"2025-03-01T10:00:00Z","model":"gpt-4o","input":100,"cost":0.5}"#,
+            r#"{"timestamp":"2025-03-02T10:00:00Z","model":"gpt-4o","input":50,"cost":0.25}"#,
+            r#"{"timestamp":"2025-03-02T11:00:00Z","model":"o3","input":10,"cost":2}"#,
+        ];
+        server.record(&events.join("\n"));
+
+        let body = json!({
+            "query": "query($keys: [UsageKey!]) { byModel: usage(sources: \"recorded\", groupBy: $keys) { model cost tokens { input } } \
+                      sessions(sources: [\"recorded\"], limit: 1) { sessions { messageCount } } }",
+            "variables": { "keys": ["MODEL"] },
+        });
+        let (status, result) = server.request("POST", "/api/graphql", None, &body.to_string());
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(result["data"]["byModel"][0], json!({ "model": "o3", "cost": 2.0, "tokens": { "input": 10 } }));
+        assert_eq!(result["data"]["byModel"][1]["tokens"]["input"], 150);
+        assert_eq!(result["data"]["sessions"]["sessions"].as_array().unwrap().len(), 1);
+        assert!(result.get("errors").is_none());
+
+        let (status, result) = server.request("GET", "/api/graphql?query=%7B+models+%7B+totalMessages+%7D+%7D", None, "");
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(result["data"]["models"]["totalMessages"], 3);
+
+        let (status, result) = server.request("POST", "/api/graphql", None, r#"{"query":"{ usage { price } }"}"#);
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(result["errors"][0]["message"].as_str().unwrap().contains("price"));
+    }
+
+    #[test]
+    fn test_openapi_covers_routes() {
+        let (server, tokens) = TestServer::start(loopback(), &[("admin", Scope::Admin)]);
+        let admin = tokens[0].as_str();
+
+        let (status, doc) = server.request("GET", "/openapi.json", None, "");
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(doc["openapi"], "3.1.0");
+        for endpoint in openapi::ENDPOINTS {
+            let path = endpoint.path.replace("{name}", "nobody");
+            let (status, body) = server.request(&endpoint.method.to_uppercase(), &path, Some(admin), "");
+            assert_ne!(status, StatusCode::METHOD_NOT_ALLOWED, "{} {}", endpoint.method, path);
+            let error = body["error"].as_str().unwrap_or("");
+            assert!(!error.starts_with("No endpoint"), "{} {} is documented but not routed", endpoint.method, path);
+        }
+
+        // Every path and method `route` accepts is documented, with its scope
+        for candidate in PATHS.iter().flat_map(|path| [path.to_string(), format!("{}/nobody", path)]) {
+            for method in ["GET", "HEAD", "POST", "PUT", "PATCH", "DELETE", "OPTIONS"] {
+                let scope = match route(method, &candidate) {
+                    Ok((scope, _)) => Some(scope.as_str()),
+                    Err(response) if response.status == 200 => None,
+                    Err(_) => continue,
+                };
+                let documented = openapi::ENDPOINTS.iter().find(|endpoint| {
+                    endpoint.method.eq_ignore_ascii_case(method) && endpoint.path.replace("{name}", "nobody") == candidate
+                });
+                let endpoint = documented.unwrap_or_else(|| panic!("{} {} is routed but not documented", method, candidate));
+                assert_eq!(endpoint.scope, scope, "{} {}", method, candidate);
+            }
+        }
+
+        let (status, headers, _) = server.request_with_headers("GET", "/docs", None, "");
+        assert_eq!(status, StatusCode::OK);
+        assert!(headers["content-type"].to_str().unwrap().starts_with("text/html"));
+    }
+}
@@ -0,0 +1,504 @@
+//! OpenAPI description of the server
+//!
+//! `GET /openapi.json` returns an OpenAPI 3.1 document for every endpoint,
+//! built from [`ENDPOINTS`], so integrators can generate clients; `GET /docs`
+//! renders it with Swagger UI. The report response reuses the `--json` report
+//! schema from [`crate::report_schema`], so both stay in step.
+
+use crate::report_schema::{self, ReportKind};
+use serde_json::{json, Map, Value};
+
+/// One documented operation
+pub struct Endpoint {
+    pub method: &'static str,
+    /// OpenAPI path, with `{name}` parameters
+    pub path: &'static str,
+    /// Token scope required; `None` for public endpoints
+    pub scope: Option<&'static str>,
+    pub summary: &'static str,
+    pub query: &'static [Param],
+    pub request: Option<Body>,
+    pub response: Response,
+}
+
+pub struct Param {
+    pub name: &'static str,
+    pub description: &'static str,
+}
+
+pub enum Body {
+    Ndjson(&'static str),
+    Json(fn() -> Value),
+}
+
+pub enum Response {
+    /// 200 with a schema from `components/schemas`
+    Schema(&'static str),
+    Json(fn() -> Value),
+    Html,
+    NoContent,
+}
+
+const REPORT_FILTERS: &[Param] = &[
+    Param { name: "since", description: "Start date (YYYY-MM-DD), inclusive" },
+    Param { name: "until", description: "End date (YYYY-MM-DD), inclusive" },
+    Param { name: "year", description: "Calendar year, e.g. 2025" },
+    Param { name: "sources", description: "Comma-separated sources, e.g. claude,codex,recorded" },
+];
+
+const LISTING_PARAMS: &[Param] = &[
+    Param { name: "since", description: "Start date (YYYY-MM-DD), inclusive" },
+    Param { name: "until", description: "End date (YYYY-MM-DD), inclusive" },
+    Param { name: "year", description: "Calendar year, e.g. 2025" },
+    Param { name: "sources", description: "Comma-separated sources, e.g. claude,codex,recorded" },
+    Param { name: "limit", description: "Items per page (default 100, at most 1000)" },
+    Param { name: "cursor", description: "`nextCursor` from the previous page" },
+];
+
+const GRAPHQL_PARAMS: &[Param] = &[
+    Param { name: "query", description: "GraphQL query document" },
+    Param { name: "variables", description: "Variables as a JSON object" },
+    Param { name: "operationName", description: "Operation to run when the document has several" },
+];
+
+pub const ENDPOINTS: &[Endpoint] = &[
+    Endpoint {
+        method: "get",
+        path: "/api/health",
+        scope: None,
+        summary: "Liveness check",
+        query: &[],
+        request: None,
+        response: Response::Json(|| object(&[("status", json!({ "type": "string", "const": "ok" }))])),
+    },
+    Endpoint {
+        method: "get",
+        path: "/api/report",
+        scope: Some("read"),
+        summary: "Usage and cost per source, provider and model",
+        query: REPORT_FILTERS,
+        request: None,
+        response: Response::Schema("ModelReport"),
+    },
+    Endpoint {
+        method: "get",
+        path: "/api/graph",
+        scope: Some("read"),
+        summary: "Daily contribution graph data: meta, summary, years and contributions",
+        query: REPORT_FILTERS,
+        request: None,
+        response: Response::Json(|| json!({ "type": "object" })),
+    },
+    Endpoint {
+        method: "get",
+        path: "/api/messages",
+        scope: Some("read"),
+        summary: "Individual messages, newest first",
+        query: LISTING_PARAMS,
+        request: None,
+        response: Response::Json(|| listing("messages", "Message")),
+    },
+    Endpoint {
+        method: "get",
+        path: "/api/sessions",
+        scope: Some("read"),
+        summary: "Per-session totals, most recently active first",
+        query: LISTING_PARAMS,
+        request: None,
+        response: Response::Json(|| listing("sessions", "Session")),
+    },
+    Endpoint {
+        method: "get",
+        path: "/api/graphql",
+        scope: Some("read"),
+        summary: "Run a GraphQL query given as parameters",
+        query: GRAPHQL_PARAMS,
+        request: None,
+        response: Response::Schema("GraphQlResult"),
+    },
+    Endpoint {
+        method: "post",
+        path: "/api/graphql",
+        scope: Some("read"),
+        summary: "Run a GraphQL query over usage, messages, sessions, models and pricing",
+        query: &[],
+        request: Some(Body::Json(|| {
+            json!({
+                "type": "object",
+                "properties": {
+                    "query": { "type": "string" },
+                    "variables": { "type": "object" },
+                    "operationName": { "type": "string" },
+                },
+                "required": ["query"],
+            })
+        })),
+        response: Response::Schema("GraphQlResult"),
+    },
+    Endpoint {
+        method: "post",
+        path: "/api/events",
+        scope: Some("push"),
+        summary: "Record usage events",
+        query: &[],
+        request: Some(Body::Ndjson("One usage event per line, as for `tokscale record`")),
+        response: Response::Schema("IngestResult"),
+    },
+    Endpoint {
+        method: "get",
+        path: "/api/sync",
+        scope: Some("push"),
+        summary: "Newest event acknowledged for this token",
+        query: &[],
+        request: None,
+        response: Response::Json(|| {
+            object(&[
+                ("client", json!({ "type": "string" })),
+                ("watermark", json!({ "type": ["integer", "null"], "description": "Unix milliseconds" })),
+            ])
+        }),
+    },
+    Endpoint {
+        method: "post",
+        path: "/api/sync",
+        scope: Some("push"),
+        summary: "Record events newer than this token's watermark and advance it",
+        query: &[],
+        request: Some(Body::Ndjson("Usage events with timestamps, oldest first")),
+        response: Response::Schema("SyncResult"),
+    },
+    Endpoint {
+        method: "get",
+        path: "/api/tokens",
+        scope: Some("admin"),
+        summary: "List API tokens (names and scopes only)",
+        query: &[],
+        request: None,
+        response: Response::Json(|| json!({ "type": "array", "items": { "$ref": "#/components/schemas/Token" } })),
+    },
+    Endpoint {
+        method: "post",
+        path: "/api/tokens",
+        scope: Some("admin"),
+        summary: "Create an API token; the response is the only time it is shown",
+        query: &[],
+        request: Some(Body::Json(|| object(&[("name", json!({ "type": "string" })), ("scope", scope_schema())]))),
+        response: Response::Json(|| {
+            object(&[
+                ("name", json!({ "type": "string" })),
+                ("scope", scope_schema()),
+                ("token", json!({ "type": "string" })),
+            ])
+        }),
+    },
+    Endpoint {
+        method: "delete",
+        path: "/api/tokens/{name}",
+        scope: Some("admin"),
+        summary: "Revoke an API token",
+        query: &[],
+        request: None,
+        response: Response::NoContent,
+    },
+    Endpoint {
+        method: "get",
+        path: "/openapi.json",
+        scope: None,
+        summary: "This document",
+        query: &[],
+        request: None,
+        response: Response::Json(|| json!({ "type": "object" })),
+    },
+    Endpoint {
+        method: "get",
+        path: "/docs",
+        scope: None,
+        summary: "Interactive API documentation (Swagger UI)",
+        query: &[],
+        request: None,
+        response: Response::Html,
+    },
+];
+
+fn object(fields: &[(&str, Value)]) -> Value {
+    let properties: Map<String, Value> = fields.iter().map(|(name, schema)| (name.to_string(), schema.clone())).collect();
+    let required: Vec<&str> = fields.iter().map(|(name, _)| *name).collect();
+    json!({ "type": "object", "properties": properties, "required": required })
+}
+
+fn scope_schema() -> Value {
+    json!({ "type": "string", "enum": ["read", "push", "admin"] })
+}
+
+fn listing(name: &str, item: &str) -> Value {
+    object(&[
+        (name, json!({ "type": "array", "items": { "$ref": format!("#/components/schemas/{}", item) } })),
+        ("nextCursor", json!({ "type": ["string", "null"], "description": "Pass as `cursor` for the next page" })),
+    ])
+}
+
+fn line_errors() -> Value {
+    json!({
+        "type": "array",
+        "description": "Rejected lines",
+        "items": object(&[("line", json!({ "type": "integer" })), ("message", json!({ "type": "string" }))]),
+    })
+}
+
+fn token_breakdown() -> Value {
+    let count = json!({ "type": "integer" });
+    object(&[
+        ("input", count.clone()),
+        ("output", count.clone()),
+        ("cacheRead", count.clone()),
+        ("cacheWrite", count.clone()),
+        ("reasoning", count.clone()),
+        ("training", count),
+    ])
+}
+
+/// Schemas shared by several operations
+fn components() -> Map<String, Value> {
+    let mut schemas = Map::new();
+
+    // The report schema keeps its definitions under $defs; OpenAPI resolves
+    // references from the document root, so they move to components
+    let mut report = report_schema::json_schema(ReportKind::Models);
+    if let Some(Value::Object(defs)) = report.as_object_mut().and_then(|r| r.remove("$defs")) {
+        for (name, def) in defs {
+            schemas.insert(name, def);
+        }
+    }
+    if let Some(report) = report.as_object_mut() {
+        report.remove("$schema");
+    }
+    schemas.insert("ModelReport".to_string(), report);
+
+    let string = json!({ "type": "string" });
+    let integer = json!({ "type": "integer" });
+    schemas.insert(
+        "Message".to_string(),
+        object(&[
+            ("id", string.clone()),
+            ("source", string.clone()),
+            ("modelId", string.clone()),
+            ("providerId", string.clone()),
+            ("sessionId", string.clone()),
+            ("timestamp", integer.clone()),
+            ("date", string.clone()),
+            ("tokens", token_breakdown()),
+            ("cost", json!({ "type": "number" })),
+            ("agent", json!({ "type": ["string", "null"] })),
+        ]),
+    );
+    schemas.insert(
+        "Session".to_string(),
+        object(&[
+            ("id", string.clone()),
+            ("source", string.clone()),
+            ("sessionId", string.clone()),
+            ("firstTimestamp", integer.clone()),
+            ("lastTimestamp", integer.clone()),
+            ("messageCount", integer.clone()),
+            ("models", json!({ "type": "array", "items": string })),
+            ("tokens", token_breakdown()),
+            ("cost", json!({ "type": "number" })),
+        ]),
+    );
+    schemas.insert("IngestResult".to_string(), object(&[("recorded", integer.clone()), ("errors", line_errors())]));
+    schemas.insert(
+        "SyncResult".to_string(),
+        object(&[
+            ("recorded", integer.clone()),
+            ("skipped", integer),
+            ("errors", line_errors()),
+            ("watermark", json!({ "type": ["integer", "null"], "description": "Unix milliseconds" })),
+        ]),
+    );
+    schemas.insert(
+        "Token".to_string(),
+        object(&[("name", string.clone()), ("scope", scope_schema()), ("createdAt", string)]),
+    );
+    schemas.insert(
+        "GraphQlResult".to_string(),
+        json!({
+            "type": "object",
+            "description": "Query result; a query that can't run is a 400 with `errors` only",
+            "properties": {
+                "data": { "type": ["object", "null"] },
+                "errors": {
+                    "type": "array",
+                    "items": object(&[("message", json!({ "type": "string" }))]),
+                },
+            },
+        }),
+    );
+    schemas.insert("Error".to_string(), object(&[("error", json!({ "type": "string" }))]));
+
+    rewrite_refs(&mut schemas);
+    schemas
+}
+
+fn rewrite_refs(schemas: &mut Map<String, Value>) {
+    fn walk(value: &mut Value) {
+        match value {
+            Value::Object(map) => {
+                if let Some(Value::String(target)) = map.get_mut("$ref") {
+                    if let Some(name) = target.strip_prefix("#/$defs/") {
+                        *target = format!("#/components/schemas/{}", name);
+                    }
+                }
+                map.values_mut().for_each(walk);
+            }
+            Value::Array(items) => items.iter_mut().for_each(walk),
+            _ => {}
+        }
+    }
+    schemas.values_mut().for_each(walk);
+}
+
+fn operation(endpoint: &Endpoint) -> Value {
+    let mut op = json!({ "summary": endpoint.summary, "responses": {} });
+
+    let mut parameters: Vec<Value> = endpoint
+        .query
+        .iter()
+        .map(|p| json!({ "name": p.name, "in": "query", "required": false, "description": p.description, "schema": { "type": "string" } }))
+        .collect();
+    if endpoint.path.contains("{name}") {
+        parameters.push(json!({ "name": "name", "in": "path", "required": true, "schema": { "type": "string" } }));
+    }
+    if !parameters.is_empty() {
+        op["parameters"] = Value::Array(parameters);
+    }
+
+    match &endpoint.request {
+        Some(Body::Ndjson(description)) => {
+            op["requestBody"] = json!({
+                "required": true,
+                "description": description,
+                "content": { "application/x-ndjson": { "schema": { "type": "string" } } },
+            });
+        }
+        Some(Body::Json(schema)) => {
+            op["requestBody"] = json!({ "required": true, "content": { "application/json": { "schema": schema() } } });
+        }
+        None => {}
+    }
+
+    let ok = match &endpoint.response {
+        Response::Schema(name) => json!({
+            "description": "OK",
+            "content": { "application/json": { "schema": { "$ref": format!("#/components/schemas/{}", name) } } },
+        }),
+        Response::Json(schema) => json!({ "description": "OK", "content": { "application/json": { "schema": schema() } } }),
+        Response::Html => json!({ "description": "OK", "content": { "text/html": {} } }),
+        Response::NoContent => json!({ "description": "Done" }),
+    };
+    let status = match (&endpoint.response, endpoint.method) {
+        (Response::NoContent, _) => "204",
+        (_, "post") if endpoint.path == "/api/tokens" => "201",
+        _ => "200",
+    };
+    op["responses"][status] = ok;
+
+    let error = json!({ "$ref": "#/components/responses/Error" });
+    if let Some(scope) = endpoint.scope {
+        op["security"] = json!([{ "bearer": [] }]);
+        op["x-scope"] = json!(scope);
+        op["responses"]["401"] = error.clone();
+        op["responses"]["403"] = error.clone();
+        op["responses"]["429"] = error.clone();
+    }
+    if endpoint.query.iter().any(|p| p.name == "cursor" || p.name == "query") || endpoint.request.is_some() {
+        op["responses"]["400"] = error;
+    }
+    op
+}
+
+/// The OpenAPI document served at `/openapi.json`
+pub fn document() -> Value {
+    let mut paths = Map::new();
+    for endpoint in ENDPOINTS {
+        let entry = paths.entry(endpoint.path.to_string()).or_insert_with(|| json!({}));
+        entry[endpoint.method] = operation(endpoint);
+    }
+
+    json!({
+        "openapi": "3.1.0",
+        "info": {
+            "title": "tokscale serve",
+            "version": env!("CARGO_PKG_VERSION"),
+            "description": "Usage reports and event ingestion. Endpoints with a scope need `Authorization: Bearer <token>` \
+                            with that scope (`x-scope`) or admin; a server without tokens serves read endpoints on loopback only.",
+        },
+        "paths": paths,
+        "components": {
+            "schemas": components(),
+            "responses": {
+                "Error": {
+                    "description": "Error",
+                    "content": { "application/json": { "schema": { "$ref": "#/components/schemas/Error" } } },
+                },
+            },
+            "securitySchemes": { "bearer": { "type": "http", "scheme": "bearer" } },
+        },
+    })
+}
+
+/// Swagger UI page for `/docs`. The UI itself loads from a CDN, so the page
+/// needs network access in the browser; the document is always local.
+pub const DOCS_PAGE: &str = r##"<!doctype html>
+<html>
+<head>
+  <meta charset="utf-8">
+  <title>tokscale serve API</title>
+  <link rel="stylesheet" href="https://unpkg.com/swagger-ui-dist@5/swagger-ui.css">
+</head>
+<body>
+  <div id="swagger-ui"></div>
+  <script src="https://unpkg.com/swagger-ui-dist@5/swagger-ui-bundle.js"></script>
+  <script>SwaggerUIBundle({ url: "/openapi.json", dom_id: "#swagger-ui" });</script>
+</body>
+</html>
+"##;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn collect_refs(value: &Value, refs: &mut Vec<String>) {
+        match value {
+            Value::Object(map) => {
+                if let Some(Value::String(target)) = map.get("$ref") {
+                    refs.push(target.clone());
+                }
+                map.values().for_each(|v| collect_refs(v, refs));
+            }
+            Value::Array(items) => items.iter().for_each(|v| collect_refs(v, refs)),
+            _ => {}
+        }
+    }
+
+    #[test]
+    fn test_every_reference_resolves() {
+        let doc = document();
+        let mut refs = Vec::new();
+        collect_refs(&doc, &mut refs);
+        assert!(!refs.is_empty());
+        for target in refs {
+            let pointer = target.strip_prefix('#').expect("local reference");
+            assert!(doc.pointer(pointer).is_some(), "unresolved {}", target);
+        }
+    }
+
+    #[test]
+    fn test_documents_scoped_operations() {
+        let doc = document();
+        assert_eq!(doc["paths"]["/api/report"]["get"]["x-scope"], "read");
+        assert_eq!(doc["paths"]["/api/sync"]["post"]["x-scope"], "push");
+        assert!(doc["paths"]["/api/health"]["get"].get("security").is_none());
+        assert!(doc["paths"]["/api/tokens/{name}"]["delete"]["responses"]["204"].is_object());
+        assert_eq!(doc["paths"]["/api/tokens"]["post"]["responses"]["201"]["description"], "OK");
+    }
+}
@@ -0,0 +1,314 @@
+//! Cursor pagination for the listing endpoints
+//!
+//! Listings are ordered newest first by a `(timestamp, id)` key. A cursor
+//! holds the key of the last item returned, so the next page starts right
+//! after it even when new messages arrive in between (offsets would shift).
+//! With memory limits set, message pages are built while messages stream in
+//! ([`stream_message_page`]).
+
+use crate::api::{Filter, Tokscale, TokscaleError};
+use crate::sessions::UnifiedMessage;
+use crate::streaming::TopN;
+use crate::TokenBreakdown;
+use base64::Engine;
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+pub const DEFAULT_LIMIT: usize = 100;
+pub const MAX_LIMIT: usize = 1000;
+
+/// Position after the last item of a page
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Cursor {
+    pub timestamp: i64,
+    pub id: String,
+}
+
+impl Cursor {
+    pub fn encode(&self) -> String {
+        base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(format!("{}\n{}", self.timestamp, self.id))
+    }
+
+    pub fn decode(text: &str) -> Result<Self, String> {
+        let invalid = || format!("Invalid cursor \"{}\"", text);
+        let bytes = base64::engine::general_purpose::URL_SAFE_NO_PAD
+            .decode(text)
+            .map_err(|_| invalid())?;
+        let text = String::from_utf8(bytes).map_err(|_| invalid())?;
+        let (timestamp, id) = text.split_once('\n').ok_or_else(invalid)?;
+        Ok(Self {
+            timestamp: timestamp.parse().map_err(|_| invalid())?,
+            id: id.to_string(),
+        })
+    }
+}
+
+/// `limit` query parameter, clamped to `1..=MAX_LIMIT`
+pub fn parse_limit(value: Option<&str>) -> Result<usize, String> {
+    match value {
+        None => Ok(DEFAULT_LIMIT),
+        Some(value) => value
+            .parse::<usize>()
+            .map(|limit| limit.clamp(1, MAX_LIMIT))
+            .map_err(|_| format!("Invalid limit \"{}\"", value)),
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// Pass as `cursor` to get the next page; `None` on the last page
+    pub next_cursor: Option<String>,
+}
+
+/// Up to `limit` items after `cursor`, newest first
+pub fn paginate<T>(mut items: Vec<T>, key: impl Fn(&T) -> Cursor, cursor: Option<&Cursor>, limit: usize) -> Page<T> {
+    items.sort_by_cached_key(|item| Reverse(key(item)));
+    let start = match cursor {
+        Some(cursor) => items.partition_point(|item| key(item) >= *cursor),
+        None => 0,
+    };
+    first_page(items.into_iter().skip(start).collect(), key, limit)
+}
+
+/// Up to `limit` of `items`, which are sorted newest first and start after
+/// the cursor
+fn first_page<T>(mut items: Vec<T>, key: impl Fn(&T) -> Cursor, limit: usize) -> Page<T> {
+    let next_cursor = if items.len() > limit {
+        items.truncate(limit);
+        items.last().map(|item| key(item).encode())
+    } else {
+        None
+    };
+    Page { items, next_cursor }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageItem {
+    pub id: String,
+    pub source: String,
+    pub model_id: String,
+    pub provider_id: String,
+    pub session_id: String,
+    pub timestamp: i64,
+    pub date: String,
+    pub tokens: TokenBreakdown,
+    pub cost: f64,
+    pub agent: Option<String>,
+}
+
+impl MessageItem {
+    pub fn key(&self) -> Cursor {
+        Cursor { timestamp: self.timestamp, id: self.id.clone() }
+    }
+}
+
+/// Listing entries for `messages` (see [`MessageIds`])
+pub fn message_items(messages: Vec<UnifiedMessage>) -> Vec<MessageItem> {
+    let mut ids = MessageIds::default();
+    messages.into_iter().map(|msg| ids.item(msg)).collect()
+}
+
+/// Gives listing entries their ids, from the source's dedup key where it has
+/// one; otherwise from the message's position in its session, which is
+/// stable as long as the session is only appended to. Messages must come in
+/// scan order.
+#[derive(Default)]
+pub struct MessageIds {
+    seen: HashMap<(String, String), usize>,
+}
+
+impl MessageIds {
+    pub fn item(&mut self, msg: UnifiedMessage) -> MessageItem {
+        let source = msg.source.to_string();
+        let id = match msg.dedup_key {
+            Some(key) => format!("{}:{}", source, key),
+            None => {
+                let n = self.seen.entry((source.clone(), msg.session_id.clone())).or_default();
+                *n += 1;
+                format!("{}:{}#{}", source, msg.session_id, n)
+            }
+        };
+        MessageItem {
+            id,
+            source,
+            model_id: msg.model_id,
+            provider_id: msg.provider_id.to_string(),
+            session_id: msg.session_id,
+            timestamp: msg.timestamp,
+            date: msg.date,
+            tokens: msg.tokens,
+            cost: msg.cost,
+            agent: msg.agent,
+        }
+    }
+}
+
+/// The page [`paginate`] would give of `tokscale`'s messages, keeping only
+/// the candidates for it while they stream in
+pub async fn stream_message_page(
+    tokscale: &Tokscale,
+    filter: Filter,
+    cursor: Option<Cursor>,
+    limit: usize,
+) -> Result<Page<MessageItem>, TokscaleError> {
+    let sort_chunk = tokscale.memory_limits().unwrap_or_default().sort_chunk;
+    // One more than the page, to know whether another follows
+    let init = (MessageIds::default(), TopN::new(limit + 1, sort_chunk));
+    let (_, top) = tokscale
+        .fold_messages(filter, init, move |(ids, top): &mut (MessageIds, TopN<Cursor, MessageItem>), messages| {
+            for msg in messages {
+                let item = ids.item(msg);
+                let key = item.key();
+                if cursor.as_ref().is_none_or(|cursor| key < *cursor) {
+                    top.push(key, item);
+                }
+            }
+        })
+        .await?;
+    Ok(first_page(top.into_sorted(), MessageItem::key, limit))
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionItem {
+    pub id: String,
+    pub source: String,
+    pub session_id: String,
+    pub first_timestamp: i64,
+    pub last_timestamp: i64,
+    pub message_count: u32,
+    pub models: Vec<String>,
+    pub tokens: TokenBreakdown,
+    pub cost: f64,
+}
+
+impl SessionItem {
+    /// Sessions are ordered by their latest activity
+    pub fn key(&self) -> Cursor {
+        Cursor { timestamp: self.last_timestamp, id: self.id.clone() }
+    }
+}
+
+/// Per-session totals of `messages`
+pub fn session_items(messages: &[UnifiedMessage]) -> Vec<SessionItem> {
+    let mut sessions: HashMap<(String, &str), SessionItem> = HashMap::new();
+    for msg in messages {
+        let source = msg.source.to_string();
+        let session = sessions
+            .entry((source.clone(), msg.session_id.as_str()))
+            .or_insert_with(|| SessionItem {
+                id: format!("{}:{}", source, msg.session_id),
+                source,
+                session_id: msg.session_id.clone(),
+                first_timestamp: msg.timestamp,
+                last_timestamp: msg.timestamp,
+                message_count: 0,
+                models: Vec::new(),
+                tokens: TokenBreakdown::default(),
+                cost: 0.0,
+            });
+        session.first_timestamp = session.first_timestamp.min(msg.timestamp);
+        session.last_timestamp = session.last_timestamp.max(msg.timestamp);
+        session.message_count += 1;
+        if !session.models.contains(&msg.model_id) {
+            session.models.push(msg.model_id.clone());
+        }
+        session.tokens.input += msg.tokens.input;
+        session.tokens.output += msg.tokens.output;
+        session.tokens.cache_read += msg.tokens.cache_read;
+        session.tokens.cache_write += msg.tokens.cache_write;
+        session.tokens.reasoning += msg.tokens.reasoning;
+        session.tokens.training += msg.tokens.training;
+        session.cost += msg.cost;
+    }
+    sessions.into_values().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(item: &(i64, &str)) -> Cursor {
+        Cursor { timestamp: item.0, id: item.1.to_string() }
+    }
+
+    #[test]
+    fn test_cursor_roundtrip() {
+        let cursor = Cursor { timestamp: 1_740_823_200_000, id: "claude:req_1:msg_1".to_string() };
+        assert_eq!(Cursor::decode(&cursor.encode()).unwrap(), cursor);
+        assert!(Cursor::decode("not a cursor!").is_err());
+        assert!(Cursor::decode(&base64::engine::general_purpose::URL_SAFE_NO_PAD.encode("x\ny")).is_err());
+    }
+
+    #[test]
+    fn test_pages_cover_everything_once() {
+        let items = vec![(3, "a"), (1, "a"), (2, "b"), (2, "a"), (5, "c")];
+        let first = paginate(items.clone(), key, None, 2);
+        assert_eq!(first.items, [(5, "c"), (3, "a")]);
+
+        let cursor = Cursor::decode(first.next_cursor.as_deref().unwrap()).unwrap();
+        let second = paginate(items.clone(), key, Some(&cursor), 2);
+        assert_eq!(second.items, [(2, "b"), (2, "a")]);
+
+        // Newer items arriving between pages don't shift later pages
+        let mut grown = items;
+        grown.push((9, "d"));
+        let cursor = Cursor::decode(second.next_cursor.as_deref().unwrap()).unwrap();
+        let last = paginate(grown, key, Some(&cursor), 2);
+        assert_eq!(last.items, [(1, "a")]);
+        assert!(last.next_cursor.is_none());
+    }
+
+    #[test]
+    fn test_streamed_pages_match() {
+        use crate::api::PricingConfig;
+        use crate::MemoryLimits;
+
+        let home = tempfile::TempDir::new().unwrap();
+        let builder = || {
+            Tokscale::builder()
+                .home_dir(home.path())
+                .sources(["recorded"])
+                .pricing(PricingConfig::reported())
+                .without_parse_cache()
+        };
+        let whole = builder().build().unwrap();
+        let streamed = builder().memory_limits(MemoryLimits { files_in_flight: 1, sort_chunk: 1 }).build().unwrap();
+        let recorder = whole.recorder().unwrap();
+        for (session, timestamp) in [("a", 3), ("b", 1), ("a", 5), ("c", 5), ("b", 2)] {
+            let tokens = TokenBreakdown { input: timestamp, ..TokenBreakdown::default() };
+            let msg = UnifiedMessage::new("claude", "sonnet", "anthropic", session, 1_740_787_200_000 + timestamp, tokens, 0.0);
+            recorder.record(&msg).unwrap();
+        }
+
+        let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        let mut cursor = None;
+        let mut pages = 0;
+        loop {
+            let messages = runtime.block_on(whole.messages(Filter::default())).unwrap();
+            let expected = paginate(message_items(messages), MessageItem::key, cursor.as_ref(), 2);
+            let page = runtime.block_on(stream_message_page(&streamed, Filter::default(), cursor.clone(), 2)).unwrap();
+            let ids = |page: &Page<MessageItem>| page.items.iter().map(|item| item.id.clone()).collect::<Vec<_>>();
+            assert_eq!(ids(&page), ids(&expected));
+            assert_eq!(page.next_cursor, expected.next_cursor);
+            pages += 1;
+            match page.next_cursor {
+                Some(next) => cursor = Some(Cursor::decode(&next).unwrap()),
+                None => break,
+            }
+        }
+        assert_eq!(pages, 3);
+    }
+
+    #[test]
+    fn test_parse_limit() {
+        assert_eq!(parse_limit(None), Ok(DEFAULT_LIMIT));
+        assert_eq!(parse_limit(Some("0")), Ok(1));
+        assert_eq!(parse_limit(Some("50000")), Ok(MAX_LIMIT));
+        assert!(parse_limit(Some("ten")).is_err());
+    }
+}
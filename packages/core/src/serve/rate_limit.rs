@@ -0,0 +1,80 @@
+//! Per-client rate limiting
+//!
+//! Each client (a token name, or an address for requests without a valid
+//! token) has a bucket of `per_minute` requests that refills continuously.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Buckets kept before full ones are dropped; a full bucket is the same as none
+const MAX_CLIENTS: usize = 10_000;
+
+pub struct RateLimiter {
+    capacity: f64,
+    /// Requests regained per second
+    refill: f64,
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+struct Bucket {
+    available: f64,
+    updated: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(per_minute: u32) -> Self {
+        Self {
+            capacity: per_minute as f64,
+            refill: per_minute as f64 / 60.0,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Take one request from `client`'s bucket, or say how long until one is
+    /// available
+    pub fn check(&self, client: &str, now: Instant) -> Result<(), Duration> {
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        if buckets.len() >= MAX_CLIENTS && !buckets.contains_key(client) {
+            buckets.retain(|_, bucket| self.refilled(bucket, now) < self.capacity);
+        }
+
+        let bucket = buckets
+            .entry(client.to_string())
+            .or_insert(Bucket { available: self.capacity, updated: now });
+        bucket.available = self.refilled(bucket, now);
+        bucket.updated = now;
+        if bucket.available >= 1.0 {
+            bucket.available -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - bucket.available) / self.refill))
+        }
+    }
+
+    fn refilled(&self, bucket: &Bucket, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        (bucket.available + elapsed * self.refill).min(self.capacity)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket_per_client() {
+        let limiter = RateLimiter::new(60);
+        let start = Instant::now();
+        for _ in 0..60 {
+            assert!(limiter.check("dashboard", start).is_ok());
+        }
+        let wait = limiter.check("dashboard", start).unwrap_err();
+        assert_eq!(wait, Duration::from_secs(1));
+        assert!(limiter.check("ci", start).is_ok());
+
+        // One request per second comes back
+        assert!(limiter.check("dashboard", start + Duration::from_secs(1)).is_ok());
+        assert!(limiter.check("dashboard", start + Duration::from_secs(1)).is_err());
+    }
+}
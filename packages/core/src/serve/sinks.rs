@@ -0,0 +1,280 @@
+//! Event sinks
+//!
+//! The events `tokscale serve` checks for (see [`super::webhooks`]) go to
+//! every sink that wants them. Webhooks are one kind; `eventSinks` in
+//! settings.json adds others, for consumers of the live event stream that
+//! would rather not poll the HTTP API:
+//!
+//! ```json
+//! "eventSinks": [
+//!   { "type": "stdout" },
+//!   { "type": "file", "path": "~/tokscale/events.ndjson", "events": ["budget.exceeded"] },
+//!   { "type": "otlp", "url": "http://localhost:4318", "events": ["usage.ingested", "budget.projected"] }
+//! ]
+//! ```
+//!
+//! - `stdout`: each event as a line of JSON (NDJSON); the CLI keeps its own
+//!   messages on stderr
+//! - `file`: appended as NDJSON to `path`; `~/` is the home directory
+//! - `otlp`: an OpenTelemetry log record per event, POSTed as OTLP/HTTP JSON
+//!   to `url` + `/v1/logs`. The record's body is the event's `text`,
+//!   `event.name` its event, and `tokscale.event` the whole event as JSON.
+//!
+//! `events` and `costThreshold` work as for webhooks. Programs embedding the
+//! server implement [`EventSink`] and add theirs to
+//! [`ServeConfig::sinks`](super::ServeConfig::sinks).
+
+use super::webhooks::{self, Subscription};
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+pub const STDOUT: &str = "stdout";
+pub const FILE: &str = "file";
+pub const OTLP: &str = "otlp";
+pub const KINDS: &[&str] = &[STDOUT, FILE, OTLP];
+
+/// Where serve-mode events go
+pub trait EventSink: Subscription + Send + Sync {
+    /// The sink in log messages, e.g. "file /var/log/tokscale.ndjson"
+    fn describe(&self) -> String;
+    /// Send one event; failures are logged, not retried
+    fn emit<'a>(&'a self, body: &'a Value) -> BoxFuture<'a, Result<(), String>>;
+}
+
+impl Subscription for Arc<dyn EventSink> {
+    fn wants(&self, event: &str) -> bool {
+        (**self).wants(event)
+    }
+
+    fn cost_threshold(&self) -> Option<f64> {
+        (**self).cost_threshold()
+    }
+}
+
+/// One entry of `eventSinks` in settings.json
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct SinkConfig {
+    /// "stdout", "file" or "otlp"
+    #[serde(rename = "type")]
+    pub kind: String,
+    /// File to append to, for "file"
+    pub path: Option<String>,
+    /// Collector base URL, for "otlp"
+    pub url: Option<String>,
+    /// Events to send; empty for all but `usage.ingested`
+    pub events: Vec<String>,
+    /// USD cost at which `session.threshold` fires
+    pub cost_threshold: Option<f64>,
+}
+
+impl SinkConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        match self.kind.as_str() {
+            STDOUT => {}
+            FILE if self.path.as_deref().is_none_or(str::is_empty) => return Err("file needs a path".to_string()),
+            FILE => {}
+            OTLP => match self.url.as_deref() {
+                Some(url) if url.starts_with("http://") || url.starts_with("https://") => {}
+                Some(url) => return Err(format!("url must be http:// or https://, got \"{}\"", url)),
+                None => return Err("otlp needs a url".to_string()),
+            },
+            kind => return Err(format!("unknown type \"{}\" (expected one of {})", kind, KINDS.join(", "))),
+        }
+        webhooks::validate_subscription(&self.events, self.cost_threshold)
+    }
+}
+
+enum Target {
+    Stdout,
+    File(PathBuf),
+    Otlp { client: reqwest::Client, endpoint: String },
+}
+
+/// A sink from `eventSinks`
+pub struct ConfiguredSink {
+    config: SinkConfig,
+    target: Target,
+}
+
+impl ConfiguredSink {
+    /// Open a validated `config`
+    pub fn open(config: &SinkConfig, home_dir: &str, client: &reqwest::Client) -> Self {
+        let target = match config.kind.as_str() {
+            FILE => {
+                let path = config.path.as_deref().unwrap_or_default();
+                Target::File(match path.strip_prefix("~/") {
+                    Some(rest) => Path::new(home_dir).join(rest),
+                    None => PathBuf::from(path),
+                })
+            }
+            OTLP => Target::Otlp {
+                client: client.clone(),
+                endpoint: format!("{}/v1/logs", config.url.as_deref().unwrap_or_default().trim_end_matches('/')),
+            },
+            _ => Target::Stdout,
+        };
+        Self { config: config.clone(), target }
+    }
+
+    /// NDJSON on stdout, for `tokscale serve --emit-events`
+    pub fn stdout() -> Self {
+        Self { config: SinkConfig { kind: STDOUT.to_string(), ..SinkConfig::default() }, target: Target::Stdout }
+    }
+}
+
+impl Subscription for ConfiguredSink {
+    fn wants(&self, event: &str) -> bool {
+        webhooks::wants(&self.config.events, event)
+    }
+
+    fn cost_threshold(&self) -> Option<f64> {
+        self.config.cost_threshold
+    }
+}
+
+impl EventSink for ConfiguredSink {
+    fn describe(&self) -> String {
+        match &self.target {
+            Target::Stdout => STDOUT.to_string(),
+            Target::File(path) => format!("file {}", path.display()),
+            Target::Otlp { endpoint, .. } => format!("otlp {}", endpoint),
+        }
+    }
+
+    fn emit<'a>(&'a self, body: &'a Value) -> BoxFuture<'a, Result<(), String>> {
+        Box::pin(async move {
+            match &self.target {
+                Target::Stdout => {
+                    // One write per line, so concurrent events don't interleave
+                    let line = format!("{}\n", body);
+                    std::io::stdout().lock().write_all(line.as_bytes()).map_err(|e| e.to_string())
+                }
+                Target::File(path) => append(path, body),
+                Target::Otlp { client, endpoint } => {
+                    let response = client
+                        .post(endpoint)
+                        .header("content-type", "application/json")
+                        .body(otlp_body(body).to_string())
+                        .send()
+                        .await
+                        .map_err(|e| e.to_string())?;
+                    if response.status().is_success() {
+                        Ok(())
+                    } else {
+                        Err(format!("HTTP {}", response.status()))
+                    }
+                }
+            }
+        })
+    }
+}
+
+fn append(path: &Path, body: &Value) -> Result<(), String> {
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    }
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut file| file.write_all(format!("{}\n", body).as_bytes()))
+        .map_err(|e| format!("Failed to append to {}: {}", path.display(), e))
+}
+
+fn string_value(value: &str) -> Value {
+    json!({ "stringValue": value })
+}
+
+/// `body` as an OTLP/HTTP JSON logs export request
+fn otlp_body(body: &Value) -> Value {
+    let event = body["event"].as_str().unwrap_or_default();
+    // Budget and pricing alerts are warnings, the rest informational
+    let (severity_number, severity_text) =
+        if event.starts_with("budget.") || event == webhooks::PRICING_UNAVAILABLE { (13, "WARN") } else { (9, "INFO") };
+    let time_unix_nano = body["createdAt"]
+        .as_str()
+        .and_then(|at| chrono::DateTime::parse_from_rfc3339(at).ok())
+        .and_then(|at| at.timestamp_nanos_opt())
+        .unwrap_or_default();
+    json!({
+        "resourceLogs": [{
+            "resource": {
+                "attributes": [{ "key": "service.name", "value": string_value("tokscale") }]
+            },
+            "scopeLogs": [{
+                "scope": { "name": "tokscale", "version": env!("CARGO_PKG_VERSION") },
+                "logRecords": [{
+                    // 64-bit integers are strings in OTLP JSON
+                    "timeUnixNano": time_unix_nano.to_string(),
+                    "severityNumber": severity_number,
+                    "severityText": severity_text,
+                    "body": string_value(body["text"].as_str().unwrap_or_default()),
+                    "attributes": [
+                        { "key": "event.name", "value": string_value(event) },
+                        { "key": "tokscale.event", "value": string_value(&body.to_string()) }
+                    ]
+                }]
+            }]
+        }]
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        let sink = |kind: &str, path: Option<&str>, url: Option<&str>| SinkConfig {
+            kind: kind.to_string(),
+            path: path.map(str::to_string),
+            url: url.map(str::to_string),
+            ..SinkConfig::default()
+        };
+        assert!(sink(STDOUT, None, None).validate().is_ok());
+        assert!(sink(FILE, None, None).validate().unwrap_err().contains("path"));
+        assert!(sink(OTLP, None, Some("localhost:4318")).validate().unwrap_err().contains("http://"));
+        assert!(sink("kafka", None, None).validate().unwrap_err().contains("unknown type"));
+        let threshold = SinkConfig { events: vec![webhooks::SESSION_THRESHOLD.to_string()], ..sink(STDOUT, None, None) };
+        assert!(threshold.validate().unwrap_err().contains("costThreshold"));
+    }
+
+    #[test]
+    fn test_file_sink_appends_ndjson() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = SinkConfig { kind: FILE.to_string(), path: Some("~/logs/events.ndjson".to_string()), ..SinkConfig::default() };
+        let sink = ConfiguredSink::open(&config, &dir.path().to_string_lossy(), &reqwest::Client::new());
+        assert!(!sink.wants(webhooks::USAGE_INGESTED), "opt-in events need listing");
+
+        let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        runtime.block_on(async {
+            sink.emit(&json!({ "event": webhooks::SESSION_CREATED })).await.unwrap();
+            sink.emit(&json!({ "event": webhooks::BUDGET_EXCEEDED })).await.unwrap();
+        });
+        let content = fs::read_to_string(dir.path().join("logs/events.ndjson")).unwrap();
+        let events: Vec<Value> = content.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[1]["event"], webhooks::BUDGET_EXCEEDED);
+    }
+
+    #[test]
+    fn test_otlp_log_record() {
+        let body = json!({
+            "event": webhooks::BUDGET_EXCEEDED,
+            "createdAt": "2025-05-10T12:00:00+00:00",
+            "text": "Budget \"team\" exceeded",
+        });
+        let export = otlp_body(&body);
+        let record = &export["resourceLogs"][0]["scopeLogs"][0]["logRecords"][0];
+        assert_eq!(record["timeUnixNano"], "1746878400000000000");
+        assert_eq!(record["severityText"], "WARN");
+        assert_eq!(record["body"]["stringValue"], "Budget \"team\" exceeded");
+        assert_eq!(record["attributes"][0]["value"]["stringValue"], webhooks::BUDGET_EXCEEDED);
+    }
+}
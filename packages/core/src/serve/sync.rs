@@ -0,0 +1,291 @@
+//! Differential sync for push clients
+//!
+//! `POST /api/sync` takes the same NDJSON as `POST /api/events`, but the
+//! server remembers, per push token, the newest event it has acknowledged:
+//! the watermark, kept in `~/.config/tokscale/serve-sync.json`. A client asks
+//! for it with `GET /api/sync` and uploads only what is newer, oldest first,
+//! in batches. Each batch moves the watermark, so an interrupted push resumes
+//! where it stopped. Events older than the watermark are skipped, and events
+//! at the watermark are recognised by id (or by content when they have none),
+//! so resending a batch whose response was lost records nothing twice.
+//!
+//! [`push`] is the client side: it sends local usage to a server this way.
+
+use crate::sessions::recorded::{EventTime, RecordedEvent};
+use crate::sessions::UnifiedMessage;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const SYNC_FILE: &str = "serve-sync.json";
+pub const DEFAULT_BATCH_SIZE: usize = 500;
+/// Secret name for the push client's token (`tokscale secrets set team-server`)
+pub const TOKEN_SECRET: &str = "team-server";
+
+/// Newest event acknowledged for one client
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Watermark {
+    /// Unix milliseconds
+    pub timestamp: i64,
+    /// Fingerprints of the events recorded at exactly `timestamp`
+    pub edge: Vec<String>,
+}
+
+pub fn sync_path(home_dir: &str) -> PathBuf {
+    Path::new(home_dir).join(".config").join("tokscale").join(SYNC_FILE)
+}
+
+/// Watermarks by client (push token name)
+#[derive(Debug, Default)]
+pub struct SyncStore {
+    path: PathBuf,
+    clients: HashMap<String, Watermark>,
+}
+
+impl SyncStore {
+    /// Watermarks from `path`; a missing file means no client has synced
+    pub fn load(path: PathBuf) -> Result<Self, String> {
+        let clients = match fs::read_to_string(&path) {
+            Ok(content) => {
+                serde_json::from_str(&content).map_err(|e| format!("Invalid {}: {}", path.display(), e))?
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+        };
+        Ok(Self { path, clients })
+    }
+
+    pub fn watermark(&self, client: &str) -> Option<&Watermark> {
+        self.clients.get(client)
+    }
+
+    pub fn set(&mut self, client: &str, watermark: Watermark) -> Result<(), String> {
+        self.clients.insert(client.to_string(), watermark);
+        let dir = self.path.parent().map(Path::to_path_buf).unwrap_or_default();
+        fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+        let content = serde_json::to_string_pretty(&self.clients).map_err(|e| e.to_string())?;
+        let tmp_path = dir.join(format!(".{}.{}.tmp", SYNC_FILE, std::process::id()));
+        fs::write(&tmp_path, content)
+            .and_then(|_| fs::rename(&tmp_path, &self.path))
+            .map_err(|e| {
+                let _ = fs::remove_file(&tmp_path);
+                format!("Failed to write {}: {}", self.path.display(), e)
+            })
+    }
+}
+
+/// An uploaded batch split against a client's watermark
+#[derive(Debug, Default)]
+pub struct SyncBatch {
+    /// Events to record, as NDJSON
+    pub accepted: String,
+    pub accepted_count: usize,
+    /// Events at or before the watermark that were already acknowledged
+    pub skipped: usize,
+    /// Rejected lines as (1-based line number, reason)
+    pub errors: Vec<(usize, String)>,
+    /// Watermark once `accepted` is recorded
+    pub watermark: Option<Watermark>,
+}
+
+fn fingerprint(event: &RecordedEvent) -> String {
+    match &event.id {
+        Some(id) => format!("id:{}", id),
+        None => format!("event:{}", serde_json::to_string(event).unwrap_or_default()),
+    }
+}
+
+/// Check an uploaded batch against `watermark`. Sync events need a
+/// timestamp, since that is what the watermark orders them by.
+pub fn split_batch(body: &[u8], watermark: Option<&Watermark>) -> SyncBatch {
+    let mut batch = SyncBatch::default();
+    let mut next = watermark.cloned();
+
+    for (index, line) in String::from_utf8_lossy(body).lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let event = match RecordedEvent::parse(line) {
+            Ok(event) => event,
+            Err(e) => {
+                batch.errors.push((index + 1, e));
+                continue;
+            }
+        };
+        let Ok(Some(timestamp)) = event.timestamp_ms() else {
+            batch.errors.push((index + 1, "sync events need a timestamp".to_string()));
+            continue;
+        };
+
+        let fingerprint = fingerprint(&event);
+        if let Some(mark) = watermark {
+            if timestamp < mark.timestamp || (timestamp == mark.timestamp && mark.edge.contains(&fingerprint)) {
+                batch.skipped += 1;
+                continue;
+            }
+        }
+
+        match &mut next {
+            Some(mark) if timestamp < mark.timestamp => {}
+            Some(mark) if timestamp == mark.timestamp => {
+                if !mark.edge.contains(&fingerprint) {
+                    mark.edge.push(fingerprint);
+                }
+            }
+            _ => next = Some(Watermark { timestamp, edge: vec![fingerprint] }),
+        }
+        if let Ok(json) = serde_json::to_string(&event) {
+            batch.accepted.push_str(&json);
+            batch.accepted.push('\n');
+            batch.accepted_count += 1;
+        }
+    }
+    batch.watermark = next;
+    batch
+}
+
+// =============================================================================
+// Client
+// =============================================================================
+
+/// Outcome of [`push`]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PushStats {
+    /// Events the server recorded
+    pub recorded: usize,
+    /// Events the server already had
+    pub skipped: usize,
+    /// Events not sent because they were older than the server's watermark
+    pub unsent: usize,
+    pub batches: usize,
+    /// Server watermark after the push, in Unix milliseconds
+    pub watermark: Option<i64>,
+}
+
+/// Recorded-event form of a local message. Session ids are prefixed with the
+/// source so sessions from different tools stay apart on the server, and
+/// messages without a dedup key get an id from where they came from, so every
+/// pushed event can be matched.
+pub fn event_for(message: &UnifiedMessage) -> RecordedEvent {
+    let mut event = RecordedEvent::from_message(message);
+    event.session_id = Some(format!("{}:{}", message.source, message.session_id));
+    if event.id.is_none() {
+        event.id = Some(format!(
+            "{}:{}:{}:{}",
+            message.source, message.session_id, message.timestamp, message.model_id
+        ));
+    }
+    event
+}
+
+async fn send(request: reqwest::RequestBuilder, token: Option<&str>) -> Result<serde_json::Value, String> {
+    let request = match token {
+        Some(token) => request.bearer_auth(token),
+        None => request,
+    };
+    let response = request.send().await.map_err(|e| e.to_string())?;
+    let status = response.status();
+    let body: serde_json::Value = response.json().await.unwrap_or_default();
+    if !status.is_success() {
+        let message = body["error"].as_str().unwrap_or("request failed");
+        return Err(format!("{} ({})", message, status));
+    }
+    Ok(body)
+}
+
+/// Upload the messages the server at `base_url` doesn't have yet, oldest
+/// first, `batch_size` events per request. If a batch fails, the error says
+/// how far the push got; running it again continues from there.
+pub async fn push(
+    base_url: &str,
+    token: Option<&str>,
+    messages: &[UnifiedMessage],
+    batch_size: usize,
+) -> Result<PushStats, String> {
+    let base_url = base_url.trim_end_matches('/');
+    let client = reqwest::Client::new();
+    let state = send(client.get(format!("{}/api/sync", base_url)), token)
+        .await
+        .map_err(|e| format!("Failed to read the sync watermark from {}: {}", base_url, e))?;
+    let watermark = state["watermark"].as_i64();
+
+    let mut events: Vec<RecordedEvent> = messages.iter().map(event_for).collect();
+    events.sort_by_key(|event| match event.timestamp {
+        Some(EventTime::Millis(ms)) => ms,
+        _ => 0,
+    });
+    let mut stats = PushStats { watermark, ..PushStats::default() };
+    let first_unsent = events.partition_point(|event| match (&event.timestamp, watermark) {
+        (Some(EventTime::Millis(ms)), Some(mark)) => *ms < mark,
+        _ => false,
+    });
+    stats.unsent = first_unsent;
+
+    for chunk in events[first_unsent..].chunks(batch_size.max(1)) {
+        let body: String = chunk
+            .iter()
+            .filter_map(|event| serde_json::to_string(event).ok())
+            .map(|line| line + "\n")
+            .collect();
+        let result = send(client.post(format!("{}/api/sync", base_url)).body(body), token)
+            .await
+            .map_err(|e| {
+                format!(
+                    "Push stopped after {} of {} events: {}; run it again to continue",
+                    stats.recorded + stats.skipped,
+                    events.len() - first_unsent,
+                    e
+                )
+            })?;
+        stats.recorded += result["recorded"].as_u64().unwrap_or(0) as usize;
+        stats.skipped += result["skipped"].as_u64().unwrap_or(0) as usize;
+        stats.watermark = result["watermark"].as_i64().or(stats.watermark);
+        stats.batches += 1;
+    }
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(id: Option<&str>, timestamp: i64) -> String {
+        match id {
+            Some(id) => format!(r#"{{"id":"{}","timestamp":{},"model":"gpt-4o","input":1}}"#, id, timestamp),
+            None => format!(r#"{{"timestamp":{},"model":"gpt-4o","input":1}}"#, timestamp),
+        }
+    }
+
+    #[test]
+    fn test_split_batch_skips_acknowledged_events() {
+        let body = [line(Some("a"), 100), line(Some("b"), 200), line(None, 200)].join("\n");
+        let first = split_batch(body.as_bytes(), None);
+        assert_eq!(first.accepted_count, 3);
+        let mark = first.watermark.unwrap();
+        assert_eq!(mark.timestamp, 200);
+        assert_eq!(mark.edge.len(), 2);
+
+        // The same batch again, plus one newer event and one without a timestamp
+        let body = format!("{}\n{}\n{}", body, line(Some("c"), 200), r#"{"model":"o3"}"#);
+        let second = split_batch(body.as_bytes(), Some(&mark));
+        assert_eq!(second.skipped, 3);
+        assert_eq!(second.accepted_count, 1);
+        assert!(second.accepted.contains(r#""id":"c""#));
+        assert_eq!(second.errors, vec![(5, "sync events need a timestamp".to_string())]);
+        assert_eq!(second.watermark.unwrap().edge.len(), 3);
+    }
+
+    #[test]
+    fn test_store_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(SYNC_FILE);
+        let mut store = SyncStore::load(path.clone()).unwrap();
+        assert!(store.watermark("laptop").is_none());
+        store.set("laptop", Watermark { timestamp: 5, edge: vec!["id:a".to_string()] }).unwrap();
+
+        let store = SyncStore::load(path).unwrap();
+        assert_eq!(store.watermark("laptop").unwrap().timestamp, 5);
+    }
+}
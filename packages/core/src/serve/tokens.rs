@@ -0,0 +1,235 @@
+//! API tokens for the server
+//!
+//! Each token has a name and one scope: `read` (reports), `push` (recording
+//! events) or `admin` (everything, including managing tokens). Only a SHA-256
+//! hash of each token is stored, in `~/.config/tokscale/serve-tokens.json`;
+//! the token itself is shown once, when it is created.
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+const TOKENS_FILE: &str = "serve-tokens.json";
+const TOKEN_PREFIX: &str = "tks_";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+    Read,
+    Push,
+    Admin,
+}
+
+impl Scope {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "read" => Ok(Scope::Read),
+            "push" => Ok(Scope::Push),
+            "admin" => Ok(Scope::Admin),
+            other => Err(format!("Unknown scope \"{}\" (expected read, push or admin)", other)),
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Scope::Read => "read",
+            Scope::Push => "push",
+            Scope::Admin => "admin",
+        }
+    }
+
+    /// Whether a token with this scope may do what `needed` covers
+    pub fn allows(self, needed: Scope) -> bool {
+        self == Scope::Admin || self == needed
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenEntry {
+    pub name: String,
+    pub scope: Scope,
+    /// Hex SHA-256 of the token
+    hash: String,
+    pub created_at: String,
+}
+
+#[derive(Debug, Default)]
+pub struct TokenStore {
+    path: PathBuf,
+    entries: Vec<TokenEntry>,
+    /// Modification time of the file when it was read, to pick up tokens
+    /// created by other processes
+    loaded_mtime: Option<SystemTime>,
+}
+
+pub fn tokens_path(home_dir: &str) -> PathBuf {
+    Path::new(home_dir).join(".config").join("tokscale").join(TOKENS_FILE)
+}
+
+impl TokenStore {
+    /// Tokens from `path`; a missing file means no tokens
+    pub fn load(path: PathBuf) -> Result<Self, String> {
+        let mut store = Self { path, ..Default::default() };
+        store.read()?;
+        Ok(store)
+    }
+
+    pub fn entries(&self) -> &[TokenEntry] {
+        &self.entries
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Re-read the file if it changed since it was loaded
+    pub fn refresh(&mut self) -> Result<(), String> {
+        if mtime(&self.path) != self.loaded_mtime {
+            self.read()?;
+        }
+        Ok(())
+    }
+
+    /// Create a token and return it; this is the only time it is available
+    pub fn create(&mut self, name: &str, scope: Scope) -> Result<String, String> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err("Token name is empty".to_string());
+        }
+        if self.entries.iter().any(|e| e.name == name) {
+            return Err(format!("A token named \"{}\" already exists", name));
+        }
+
+        let mut secret = [0u8; 32];
+        openssl::rand::rand_bytes(&mut secret).map_err(|e| e.to_string())?;
+        use base64::Engine;
+        let token = format!("{}{}", TOKEN_PREFIX, base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(secret));
+
+        self.entries.push(TokenEntry {
+            name: name.to_string(),
+            scope,
+            hash: hash(&token),
+            created_at: chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+        });
+        self.save()?;
+        Ok(token)
+    }
+
+    /// Remove a token; `false` if there was none by that name
+    pub fn revoke(&mut self, name: &str) -> Result<bool, String> {
+        let before = self.entries.len();
+        self.entries.retain(|e| e.name != name);
+        if self.entries.len() == before {
+            return Ok(false);
+        }
+        self.save()?;
+        Ok(true)
+    }
+
+    /// The entry `token` belongs to
+    pub fn authenticate(&self, token: &str) -> Option<&TokenEntry> {
+        let presented = hash(token);
+        // Compare every entry in constant time so timing reveals nothing
+        self.entries
+            .iter()
+            .filter(|e| e.hash.len() == presented.len())
+            .fold(None, |found, e| {
+                let matches = openssl::memcmp::eq(e.hash.as_bytes(), presented.as_bytes());
+                found.or(matches.then_some(e))
+            })
+    }
+
+    fn read(&mut self) -> Result<(), String> {
+        self.loaded_mtime = mtime(&self.path);
+        self.entries = match fs::read_to_string(&self.path) {
+            Ok(content) => serde_json::from_str(&content)
+                .map_err(|e| format!("Invalid {}: {}", self.path.display(), e))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(format!("Failed to read {}: {}", self.path.display(), e)),
+        };
+        Ok(())
+    }
+
+    fn save(&mut self) -> Result<(), String> {
+        let dir = self.path.parent().map(Path::to_path_buf).unwrap_or_default();
+        fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+
+        let content = serde_json::to_string_pretty(&self.entries).map_err(|e| e.to_string())?;
+        let tmp_path = dir.join(format!(".{}.{}.tmp", TOKENS_FILE, std::process::id()));
+        let write_result = (|| {
+            let mut options = fs::OpenOptions::new();
+            options.write(true).create(true).truncate(true);
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::OpenOptionsExt;
+                options.mode(0o600);
+            }
+            let mut file = options.open(&tmp_path)?;
+            file.write_all(content.as_bytes())?;
+            file.sync_all()?;
+            fs::rename(&tmp_path, &self.path)
+        })();
+
+        if let Err(e) = write_result {
+            let _ = fs::remove_file(&tmp_path);
+            return Err(format!("Failed to write {}: {}", self.path.display(), e));
+        }
+        self.loaded_mtime = mtime(&self.path);
+        Ok(())
+    }
+}
+
+fn hash(token: &str) -> String {
+    openssl::sha::sha256(token.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+fn mtime(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scopes() {
+        assert!(Scope::Admin.allows(Scope::Push));
+        assert!(Scope::Read.allows(Scope::Read));
+        assert!(!Scope::Read.allows(Scope::Push));
+        assert!(!Scope::Push.allows(Scope::Admin));
+        assert_eq!(Scope::parse("push"), Ok(Scope::Push));
+        assert!(Scope::parse("write").is_err());
+    }
+
+    #[test]
+    fn test_create_authenticate_revoke() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(TOKENS_FILE);
+        let mut store = TokenStore::load(path.clone()).unwrap();
+        assert!(store.is_empty());
+
+        let dashboard = store.create("dashboard", Scope::Read).unwrap();
+        let ci = store.create("ci", Scope::Push).unwrap();
+        assert!(dashboard.starts_with(TOKEN_PREFIX));
+        assert!(store.create("ci", Scope::Admin).is_err());
+
+        // Only hashes are written
+        let saved = fs::read_to_string(&path).unwrap();
+        assert!(!saved.contains(&dashboard));
+
+        let reloaded = TokenStore::load(path).unwrap();
+        assert_eq!(reloaded.authenticate(&dashboard).unwrap().name, "dashboard");
+        assert_eq!(reloaded.authenticate(&ci).unwrap().scope, Scope::Push);
+        assert!(reloaded.authenticate("tks_guess").is_none());
+
+        assert!(store.revoke("ci").unwrap());
+        assert!(!store.revoke("ci").unwrap());
+        assert!(store.authenticate(&ci).is_none());
+    }
+}
@@ -0,0 +1,867 @@
+//! Webhooks for session activity, budgets and pricing outages
+//!
+//! While `tokscale serve` runs, it checks the usage it serves (local session
+//! files and pushed events) whenever it changes, at most every
+//! [`DEFAULT_INTERVAL`], and POSTs JSON to each webhook in settings.json.
+//! Webhooks are one kind of [`EventSink`]; the same events can go to stdout,
+//! a file or an OpenTelemetry collector (see [`super::sinks`]):
+//!
+//! ```json
+//! "webhooks": [
+//!   { "url": "https://hooks.slack.com/services/...", "events": ["session.threshold"],
+//!     "costThreshold": 25, "secret": "team-hook" }
+//! ]
+//! ```
+//!
+//! - `session.created`: a session the server hasn't seen before
+//! - `session.threshold`: a session's cost reached `costThreshold` USD
+//! - `budget.projected`: at the current burn rate, a budget in settings will
+//!   be exceeded before its period ends; `projectedBreach` says when
+//! - `budget.exceeded`: a budget's spend reached its amount
+//! - `budget.enforced`: the `enforce` hook of an exceeded budget ran (see
+//!   [`crate::enforcement`]); `enforcement` has its exit code and output
+//! - `pricing.unavailable`: prices could not be fetched, so nothing could be
+//!   checked. Provider status pages are read then, and `diagnosis` says
+//!   whether a provider reports an outage or the failure is local. Fires once
+//!   until pricing works again.
+//! - `usage.ingested`: new messages were read or pushed since the last
+//!   check; `messages` and `cost` are what they added
+//!
+//! `events` defaults to all of them but `usage.ingested`, which fires on
+//! nearly every check and has to be asked for. Sessions already present when webhooks
+//! are first set up don't fire, and each budget alert fires once per period;
+//! what has been seen is kept in `~/.config/tokscale/serve-webhooks.json`, so
+//! restarts don't fire again.
+//!
+//! The body has a Slack-style `text` summary next to the event, its session
+//! and threshold. With `secret` (a name for `tokscale secrets set`), requests
+//! carry `X-Tokscale-Signature: sha256=<hex>`, the HMAC-SHA256 of the body
+//! under that secret. Failed deliveries (network errors, 429 and 5xx) are
+//! retried after 1, 5 and 30 seconds.
+
+use super::page::{self, SessionItem};
+use super::sinks::{self, EventSink};
+use super::ServeConfig;
+use crate::api::{Filter, Tokscale, TokscaleError};
+use crate::budget;
+use crate::enforcement;
+use crate::provider_status;
+use crate::sessions::UnifiedMessage;
+use crate::{BudgetStatus, EnforcementEvent, ProviderStatus};
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+pub const SESSION_CREATED: &str = "session.created";
+pub const SESSION_THRESHOLD: &str = "session.threshold";
+pub const BUDGET_PROJECTED: &str = "budget.projected";
+pub const BUDGET_EXCEEDED: &str = "budget.exceeded";
+pub const BUDGET_ENFORCED: &str = "budget.enforced";
+pub const PRICING_UNAVAILABLE: &str = "pricing.unavailable";
+pub const USAGE_INGESTED: &str = "usage.ingested";
+pub const EVENTS: &[&str] = &[
+    SESSION_CREATED,
+    SESSION_THRESHOLD,
+    BUDGET_PROJECTED,
+    BUDGET_EXCEEDED,
+    BUDGET_ENFORCED,
+    PRICING_UNAVAILABLE,
+    USAGE_INGESTED,
+];
+/// Events sent only to sinks that list them
+const OPT_IN_EVENTS: &[&str] = &[USAGE_INGESTED];
+
+pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(30);
+const RETRY_DELAYS: [Duration; 3] = [Duration::from_secs(1), Duration::from_secs(5), Duration::from_secs(30)];
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const STATE_FILE: &str = "serve-webhooks.json";
+
+/// One entry of `webhooks` in settings.json
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Webhook {
+    pub url: String,
+    /// Events to send; empty for all
+    pub events: Vec<String>,
+    /// USD cost at which `session.threshold` fires
+    pub cost_threshold: Option<f64>,
+    /// Secret name whose value signs the requests
+    pub secret: Option<String>,
+}
+
+impl Webhook {
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.url.starts_with("http://") && !self.url.starts_with("https://") {
+            return Err(format!("url must be http:// or https://, got \"{}\"", self.url));
+        }
+        validate_subscription(&self.events, self.cost_threshold)
+    }
+}
+
+/// Which events a webhook or other sink is sent
+pub trait Subscription {
+    fn wants(&self, event: &str) -> bool;
+    /// USD cost at which `session.threshold` fires; `None` for never
+    fn cost_threshold(&self) -> Option<f64>;
+}
+
+impl Subscription for Webhook {
+    fn wants(&self, event: &str) -> bool {
+        wants(&self.events, event)
+    }
+
+    fn cost_threshold(&self) -> Option<f64> {
+        self.cost_threshold
+    }
+}
+
+/// Whether a sink listing `events` is sent `event`
+pub fn wants(events: &[String], event: &str) -> bool {
+    if events.is_empty() {
+        !OPT_IN_EVENTS.contains(&event)
+    } else {
+        events.iter().any(|e| e == event)
+    }
+}
+
+/// Check `events` and `costThreshold` of a webhook or other sink
+pub fn validate_subscription(events: &[String], cost_threshold: Option<f64>) -> Result<(), String> {
+    if let Some(event) = events.iter().find(|e| !EVENTS.contains(&e.as_str())) {
+        return Err(format!("unknown event \"{}\" (expected one of {})", event, EVENTS.join(", ")));
+    }
+    match cost_threshold {
+        Some(threshold) if threshold.is_nan() || threshold <= 0.0 => {
+            Err(format!("costThreshold must be positive, got {}", threshold))
+        }
+        None if events.iter().any(|e| e == SESSION_THRESHOLD) => {
+            Err(format!("{} needs a costThreshold", SESSION_THRESHOLD))
+        }
+        _ => Ok(()),
+    }
+}
+
+pub fn state_path(home_dir: &str) -> PathBuf {
+    Path::new(home_dir).join(".config").join("tokscale").join(STATE_FILE)
+}
+
+/// Most severe budget state alerted in a period
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BudgetAlert {
+    period_start: String,
+    state: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct SavedState {
+    /// Last seen cost by session id
+    sessions: HashMap<String, f64>,
+    /// By budget name
+    #[serde(default)]
+    budgets: HashMap<String, BudgetAlert>,
+}
+
+/// Sessions and budget alerts seen so far, to tell what changed
+#[derive(Debug)]
+pub struct Watcher {
+    path: PathBuf,
+    /// `None` until the first check, which only records what is there
+    costs: Option<HashMap<String, f64>>,
+    budgets: HashMap<String, BudgetAlert>,
+    /// Whether the last check failed to get prices
+    pricing_failed: bool,
+    /// Messages and their cost at the last check; `None` until the first
+    ingested: Option<(usize, f64)>,
+}
+
+fn severity(state: &str) -> u8 {
+    match state {
+        budget::EXCEEDED => 2,
+        budget::PROJECTED => 1,
+        _ => 0,
+    }
+}
+
+impl Watcher {
+    pub fn load(path: PathBuf) -> Result<Self, String> {
+        let state = match fs::read_to_string(&path) {
+            Ok(content) => Some(
+                serde_json::from_str::<SavedState>(&content)
+                    .map_err(|e| format!("Invalid {}: {}", path.display(), e))?,
+            ),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+        };
+        let (costs, budgets) = match state {
+            Some(state) => (Some(state.sessions), state.budgets),
+            None => (None, HashMap::new()),
+        };
+        Ok(Self { path, costs, budgets, pricing_failed: false, ingested: None })
+    }
+
+    /// Events to send, as (index into `hooks`, body)
+    pub fn update<S: Subscription>(&mut self, sessions: &[SessionItem], hooks: &[S]) -> Vec<(usize, Value)> {
+        let baseline = self.costs.is_none();
+        let known = self.costs.get_or_insert_with(HashMap::new);
+        let mut events = Vec::new();
+        for session in sessions {
+            let previous = known.insert(session.id.clone(), session.cost);
+            if baseline {
+                continue;
+            }
+            for (index, hook) in hooks.iter().enumerate() {
+                if previous.is_none() && hook.wants(SESSION_CREATED) {
+                    events.push((index, payload(SESSION_CREATED, session, None)));
+                }
+                if let Some(threshold) = hook.cost_threshold() {
+                    let crossed = previous.unwrap_or(0.0) < threshold && session.cost >= threshold;
+                    if crossed && hook.wants(SESSION_THRESHOLD) {
+                        events.push((index, payload(SESSION_THRESHOLD, session, Some(threshold))));
+                    }
+                }
+            }
+        }
+        events
+    }
+
+    /// Budget alerts to send, as (index into `hooks`, body). A budget alerts
+    /// once per period for each state it gets worse to.
+    pub fn update_budgets<S: Subscription>(&mut self, statuses: &[BudgetStatus], hooks: &[S]) -> Vec<(usize, Value)> {
+        let mut events = Vec::new();
+        for status in statuses {
+            let alerted = self
+                .budgets
+                .get(&status.name)
+                .filter(|alert| alert.period_start == status.period_start)
+                .map_or(0, |alert| severity(&alert.state));
+            if severity(&status.state) <= alerted {
+                continue;
+            }
+            self.budgets.insert(
+                status.name.clone(),
+                BudgetAlert { period_start: status.period_start.clone(), state: status.state.clone() },
+            );
+            let event = if status.state == budget::EXCEEDED { BUDGET_EXCEEDED } else { BUDGET_PROJECTED };
+            for (index, hook) in hooks.iter().enumerate() {
+                if hook.wants(event) {
+                    events.push((index, budget_payload(event, status)));
+                }
+            }
+        }
+        events
+    }
+
+    /// `usage.ingested` when there are more messages than at the last check,
+    /// as (index into `hooks`, body)
+    pub fn update_ingested<S: Subscription>(&mut self, messages: &[UnifiedMessage], hooks: &[S]) -> Vec<(usize, Value)> {
+        let count = messages.len();
+        let cost: f64 = messages.iter().map(|m| m.cost).sum();
+        let previous = self.ingested.replace((count, cost));
+        let (added, added_cost) = match previous {
+            Some((before, before_cost)) if count > before => (count - before, cost - before_cost),
+            _ => return Vec::new(),
+        };
+        hooks
+            .iter()
+            .enumerate()
+            .filter(|(_, hook)| hook.wants(USAGE_INGESTED))
+            .map(|(index, _)| {
+                let body = json!({
+                    "id": delivery_id(),
+                    "event": USAGE_INGESTED,
+                    "createdAt": chrono::Utc::now().to_rfc3339(),
+                    "text": format!("{} new messages (${:.2}); {} in total (${:.2})", added, added_cost, count, cost),
+                    "messages": added,
+                    "cost": added_cost,
+                    "totalMessages": count,
+                    "totalCost": cost,
+                });
+                (index, body)
+            })
+            .collect()
+    }
+
+    /// Record whether pricing worked this check; true when it just stopped
+    pub fn update_pricing(&mut self, failed: bool) -> bool {
+        let started = failed && !self.pricing_failed;
+        self.pricing_failed = failed;
+        started
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let state = SavedState { sessions: self.costs.clone().unwrap_or_default(), budgets: self.budgets.clone() };
+        let dir = self.path.parent().map(Path::to_path_buf).unwrap_or_default();
+        fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+        let content = serde_json::to_string(&state).map_err(|e| e.to_string())?;
+        let tmp_path = dir.join(format!(".{}.{}.tmp", STATE_FILE, std::process::id()));
+        fs::write(&tmp_path, content)
+            .and_then(|_| fs::rename(&tmp_path, &self.path))
+            .map_err(|e| {
+                let _ = fs::remove_file(&tmp_path);
+                format!("Failed to write {}: {}", self.path.display(), e)
+            })
+    }
+}
+
+fn delivery_id() -> String {
+    let mut bytes = [0u8; 16];
+    let _ = openssl::rand::rand_bytes(&mut bytes);
+    hex(&bytes)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn payload(event: &str, session: &SessionItem, threshold: Option<f64>) -> Value {
+    let text = match threshold {
+        Some(threshold) => format!(
+            "{} session {} reached ${:.2} (now ${:.2}, {} messages)",
+            session.source, session.session_id, threshold, session.cost, session.message_count
+        ),
+        None => format!(
+            "New {} session {} ({})",
+            session.source,
+            session.session_id,
+            session.models.join(", ")
+        ),
+    };
+    json!({
+        "id": delivery_id(),
+        "event": event,
+        "createdAt": chrono::Utc::now().to_rfc3339(),
+        "text": text,
+        "session": session,
+        "threshold": threshold,
+    })
+}
+
+fn budget_payload(event: &str, status: &BudgetStatus) -> Value {
+    let limit = format!("\"{}\" (${:.2} per {})", status.name, status.amount, status.period);
+    let text = match &status.projected_breach {
+        Some(breach) if event == BUDGET_PROJECTED => format!(
+            "Budget {} will be exceeded around {} at the current burn rate (${:.2} spent, ${:.2}/hour)",
+            limit, breach, status.spent, status.cost_per_hour
+        ),
+        _ => format!("Budget {} exceeded: ${:.2} spent since {}", limit, status.spent, status.period_start),
+    };
+    json!({
+        "id": delivery_id(),
+        "event": event,
+        "createdAt": chrono::Utc::now().to_rfc3339(),
+        "text": text,
+        "budget": status,
+        "projectedBreach": status.projected_breach,
+    })
+}
+
+/// `budget.enforced` for each run and hook that wants it, as (index into
+/// `hooks`, body)
+pub fn enforcement_alerts<S: Subscription>(runs: &[EnforcementEvent], hooks: &[S]) -> Vec<(usize, Value)> {
+    let mut events = Vec::new();
+    for run in runs {
+        let limit = format!("\"{}\" (${:.2} spent of ${:.2})", run.budget, run.spent, run.amount);
+        let text = match &run.error {
+            Some(error) => format!("Budget {} hit its cap, but its enforce hook failed: {}", limit, error),
+            None => format!("Budget {} hit its cap; its enforce hook ran", limit),
+        };
+        for (index, hook) in hooks.iter().enumerate() {
+            if hook.wants(BUDGET_ENFORCED) {
+                let body = json!({
+                    "id": delivery_id(),
+                    "event": BUDGET_ENFORCED,
+                    "createdAt": chrono::Utc::now().to_rfc3339(),
+                    "text": text,
+                    "enforcement": run,
+                });
+                events.push((index, body));
+            }
+        }
+    }
+    events
+}
+
+/// `pricing.unavailable` for each hook that wants it, as (index into `hooks`, body)
+pub fn pricing_alerts<S: Subscription>(
+    error: &str,
+    diagnosis: &str,
+    providers: &[ProviderStatus],
+    hooks: &[S],
+) -> Vec<(usize, Value)> {
+    let providers: Vec<Value> = providers
+        .iter()
+        .map(|p| json!({ "name": p.name, "state": p.state, "description": p.description, "url": p.url }))
+        .collect();
+    let body = json!({
+        "event": PRICING_UNAVAILABLE,
+        "createdAt": chrono::Utc::now().to_rfc3339(),
+        "text": format!("tokscale could not fetch prices: {}. {}", error, diagnosis),
+        "error": error,
+        "diagnosis": diagnosis,
+        "providers": providers,
+    });
+    hooks
+        .iter()
+        .enumerate()
+        .filter(|(_, hook)| hook.wants(PRICING_UNAVAILABLE))
+        .map(|(index, _)| {
+            let mut body = body.clone();
+            body["id"] = json!(delivery_id());
+            (index, body)
+        })
+        .collect()
+}
+
+/// `X-Tokscale-Signature` value for `body`
+pub fn sign(secret: &str, body: &[u8]) -> Result<String, String> {
+    Ok(format!("sha256={}", hex(&hmac_sha256(secret.as_bytes(), body).map_err(|e| e.to_string())?)))
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Result<Vec<u8>, openssl::error::ErrorStack> {
+    let key = openssl::pkey::PKey::hmac(key)?;
+    let mut signer = openssl::sign::Signer::new(openssl::hash::MessageDigest::sha256(), &key)?;
+    signer.update(data)?;
+    signer.sign_to_vec()
+}
+
+/// POST `body` to `hook`, retrying after each of `delays`. Returns the number
+/// of attempts it took.
+pub async fn deliver(
+    client: &reqwest::Client,
+    hook: &Webhook,
+    secret: Option<&str>,
+    body: &Value,
+    delays: &[Duration],
+) -> Result<usize, String> {
+    let text = body.to_string();
+    let signature = secret.map(|secret| sign(secret, text.as_bytes())).transpose()?;
+    let mut attempt = 0;
+    loop {
+        let mut request = client
+            .post(&hook.url)
+            .header("content-type", "application/json")
+            .header("user-agent", concat!("tokscale/", env!("CARGO_PKG_VERSION")))
+            .header("x-tokscale-event", body["event"].as_str().unwrap_or(""))
+            .header("x-tokscale-delivery", body["id"].as_str().unwrap_or(""))
+            .body(text.clone());
+        if let Some(signature) = &signature {
+            request = request.header("x-tokscale-signature", signature);
+        }
+        let (error, retry) = match request.send().await {
+            Ok(response) if response.status().is_success() => return Ok(attempt + 1),
+            Ok(response) => {
+                let status = response.status();
+                (format!("HTTP {}", status), status.is_server_error() || status.as_u16() == 429)
+            }
+            Err(e) => (e.to_string(), true),
+        };
+        match delays.get(attempt) {
+            Some(delay) if retry => {
+                tokio::time::sleep(*delay).await;
+                attempt += 1;
+            }
+            _ => return Err(format!("{} after {} attempt(s)", error, attempt + 1)),
+        }
+    }
+}
+
+/// A webhook from settings.json as an [`EventSink`]
+pub struct WebhookSink {
+    client: reqwest::Client,
+    hook: Webhook,
+}
+
+impl WebhookSink {
+    pub fn new(client: reqwest::Client, hook: Webhook) -> Self {
+        Self { client, hook }
+    }
+}
+
+impl Subscription for WebhookSink {
+    fn wants(&self, event: &str) -> bool {
+        self.hook.wants(event)
+    }
+
+    fn cost_threshold(&self) -> Option<f64> {
+        self.hook.cost_threshold
+    }
+}
+
+impl EventSink for WebhookSink {
+    fn describe(&self) -> String {
+        format!("webhook {}", self.hook.url)
+    }
+
+    fn emit<'a>(&'a self, body: &'a Value) -> BoxFuture<'a, Result<(), String>> {
+        Box::pin(async move {
+            let secret = match &self.hook.secret {
+                Some(name) => match crate::secrets::resolve(name) {
+                    Some((secret, _)) => Some(secret),
+                    None => {
+                        return Err(format!("secret \"{}\" is not set (tokscale secrets set {})", name, name));
+                    }
+                },
+                None => None,
+            };
+            deliver(&self.client, &self.hook, secret.as_deref(), body, &RETRY_DELAYS).await.map(|_| ())
+        })
+    }
+}
+
+/// Webhooks and other sinks events go to, from `config` or settings.json.
+/// Invalid entries are left out; `tokscale config check` reports them.
+fn open_sinks(config: &ServeConfig, client: &reqwest::Client) -> Vec<Arc<dyn EventSink>> {
+    let settings = crate::config::settings();
+    let hooks = config.webhooks.as_ref().unwrap_or(&settings.webhooks);
+    let configured = config.event_sinks.as_ref().unwrap_or(&settings.event_sinks);
+    let mut opened: Vec<Arc<dyn EventSink>> = hooks
+        .iter()
+        .filter(|hook| hook.validate().is_ok())
+        .map(|hook| Arc::new(WebhookSink::new(client.clone(), hook.clone())) as Arc<dyn EventSink>)
+        .collect();
+    let home_dir = config.home_dir.to_string_lossy();
+    opened.extend(
+        configured
+            .iter()
+            .filter(|sink| sink.validate().is_ok())
+            .map(|sink| Arc::new(sinks::ConfiguredSink::open(sink, &home_dir, client)) as Arc<dyn EventSink>),
+    );
+    opened.extend(config.sinks.iter().cloned());
+    opened
+}
+
+/// Send each event to its sink in the background
+fn dispatch(sinks: &[Arc<dyn EventSink>], events: Vec<(usize, Value)>) {
+    for (index, body) in events {
+        let sink = Arc::clone(&sinks[index]);
+        tokio::spawn(async move {
+            if let Err(e) = sink.emit(&body).await {
+                eprintln!("[tokscale] Sending {} to {} failed: {}", body["event"], sink.describe(), e);
+            }
+        });
+    }
+}
+
+/// Check for session and budget events every `config.webhook_interval`
+/// until dropped
+pub async fn run(config: ServeConfig) {
+    let home_dir = config.home_dir.to_string_lossy().into_owned();
+    let mut watcher = match Watcher::load(state_path(&home_dir)) {
+        Ok(watcher) => watcher,
+        Err(e) => {
+            eprintln!("[tokscale] Webhooks disabled: {}", e);
+            return;
+        }
+    };
+    let client = reqwest::Client::builder().timeout(REQUEST_TIMEOUT).build().unwrap_or_default();
+    let mut interval = tokio::time::interval(config.webhook_interval);
+    let mut checked_version = None;
+    loop {
+        interval.tick().await;
+        // Opened on every check, so edits to settings.json apply without a restart
+        let hooks = open_sinks(&config, &client);
+        let capped = crate::config::settings().budgets.iter().any(|b| b.enforce.is_some());
+        if hooks.is_empty() && !capped {
+            continue;
+        }
+
+        let tokscale = match Tokscale::builder().home_dir(&config.home_dir).pricing(config.pricing.clone()).build() {
+            Ok(tokscale) => Arc::new(tokscale),
+            Err(e) => {
+                eprintln!("[tokscale] Webhooks disabled: {}", e);
+                return;
+            }
+        };
+        let version = match tokio::task::spawn_blocking({
+            let tokscale = Arc::clone(&tokscale);
+            move || tokscale.data_version()
+        })
+        .await
+        {
+            Ok(version) => version,
+            Err(e) => std::panic::resume_unwind(e.into_panic()),
+        };
+        if checked_version == Some(version) {
+            continue;
+        }
+        let messages = match tokscale.messages(Filter::default()).await {
+            Ok(messages) => messages,
+            Err(TokscaleError::Pricing(e)) => {
+                if watcher.update_pricing(true) {
+                    let providers = provider_status::check().await;
+                    let diagnosis = provider_status::diagnose(provider_status::failed_source(&e), &providers);
+                    eprintln!("[tokscale] Webhook check failed: pricing unavailable: {}. {}", e, diagnosis);
+                    dispatch(&hooks, pricing_alerts(&e, &diagnosis, &providers, &hooks));
+                }
+                continue;
+            }
+            Err(e) => {
+                eprintln!("[tokscale] Webhook check failed: {}", e);
+                continue;
+            }
+        };
+        watcher.update_pricing(false);
+        checked_version = Some(version);
+
+        let now = chrono::Utc::now();
+        let budgets: Vec<BudgetStatus> = crate::config::settings()
+            .budgets
+            .iter()
+            .filter(|b| b.validate().is_ok())
+            .map(|b| budget::status(b, &messages, now))
+            .collect();
+        let mut events = watcher.update_ingested(&messages, &hooks);
+        events.extend(watcher.update(&page::session_items(&messages), &hooks));
+        events.extend(watcher.update_budgets(&budgets, &hooks));
+        if capped {
+            let caps = crate::config::settings().budgets.clone();
+            let path = enforcement::log_path(&home_dir);
+            match tokio::task::spawn_blocking(move || enforcement::enforce(&caps, &messages, now, &path)).await {
+                Ok(Ok(runs)) => {
+                    for run in &runs {
+                        match &run.error {
+                            Some(error) => eprintln!("[tokscale] Enforcing budget \"{}\" failed: {}", run.budget, error),
+                            None => eprintln!("[tokscale] Enforced budget \"{}\"", run.budget),
+                        }
+                    }
+                    events.extend(enforcement_alerts(&runs, &hooks));
+                }
+                Ok(Err(e)) => eprintln!("[tokscale] {}", e),
+                Err(e) => eprintln!("[tokscale] Budget enforcement failed: {}", e),
+            }
+        }
+        dispatch(&hooks, events);
+        if let Err(e) = watcher.save() {
+            eprintln!("[tokscale] {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http;
+    use crate::sessions::UnifiedMessage;
+    use crate::TokenBreakdown;
+    use std::sync::{Arc, Mutex};
+    use tokio::io::{AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+
+    fn sessions(costs: &[(&str, f64)]) -> Vec<SessionItem> {
+        let messages: Vec<UnifiedMessage> = costs
+            .iter()
+            .map(|(session, cost)| UnifiedMessage::new("claude", "sonnet", "anthropic", *session, 0, TokenBreakdown::default(), *cost))
+            .collect();
+        page::session_items(&messages)
+    }
+
+    #[test]
+    fn test_fires_for_new_sessions_and_thresholds() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(STATE_FILE);
+        let hooks = [
+            Webhook { url: "http://a".to_string(), cost_threshold: Some(20.0), ..Webhook::default() },
+            Webhook {
+                url: "http://b".to_string(),
+                events: vec![SESSION_THRESHOLD.to_string()],
+                cost_threshold: Some(25.0),
+                secret: None,
+            },
+        ];
+
+        // Whatever exists at the first check is the baseline
+        let mut watcher = Watcher::load(path.clone()).unwrap();
+        assert!(watcher.update(&sessions(&[("old", 30.0)]), &hooks).is_empty());
+        watcher.save().unwrap();
+
+        let mut watcher = Watcher::load(path).unwrap();
+        let events = watcher.update(&sessions(&[("old", 40.0), ("new", 10.0)]), &hooks);
+        assert_eq!(events.len(), 1);
+        assert_eq!((events[0].0, events[0].1["event"].as_str()), (0, Some(SESSION_CREATED)));
+        assert_eq!(events[0].1["session"]["sessionId"], "new");
+
+        let events = watcher.update(&sessions(&[("old", 40.0), ("new", 26.0)]), &hooks);
+        let fired: Vec<(usize, &str)> = events.iter().map(|(i, body)| (*i, body["event"].as_str().unwrap())).collect();
+        assert_eq!(fired, [(0, SESSION_THRESHOLD), (1, SESSION_THRESHOLD)]);
+        assert_eq!(events[1].1["threshold"], 25.0);
+        assert!(events[1].1["text"].as_str().unwrap().contains("reached $25.00"));
+
+        assert!(watcher.update(&sessions(&[("old", 40.0), ("new", 30.0)]), &hooks).is_empty());
+    }
+
+    #[test]
+    fn test_budget_alerts_once_per_state_and_period() {
+        let dir = tempfile::tempdir().unwrap();
+        let hooks = [
+            Webhook { url: "http://a".to_string(), ..Webhook::default() },
+            Webhook { url: "http://b".to_string(), events: vec![BUDGET_EXCEEDED.to_string()], ..Webhook::default() },
+        ];
+        let status = |period_start: &str, state: &str, breach: Option<&str>| BudgetStatus {
+            name: "team".to_string(),
+            period: "month".to_string(),
+            amount: 100.0,
+            period_start: period_start.to_string(),
+            period_end: "2025-05-31".to_string(),
+            spent: 80.0,
+            cost_per_hour: 2.0,
+            projected_cost: 300.0,
+            projected_breach: breach.map(str::to_string),
+            state: state.to_string(),
+        };
+        let projected = status("2025-05-01", budget::PROJECTED, Some("2025-05-10T22:00:00Z"));
+
+        let mut watcher = Watcher::load(dir.path().join(STATE_FILE)).unwrap();
+        let events = watcher.update_budgets(std::slice::from_ref(&projected), &hooks);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].1["event"], BUDGET_PROJECTED);
+        assert_eq!(events[0].1["projectedBreach"], "2025-05-10T22:00:00Z");
+        assert_eq!(events[0].1["budget"]["periodEnd"], "2025-05-31");
+        assert!(watcher.update_budgets(std::slice::from_ref(&projected), &hooks).is_empty());
+        watcher.save().unwrap();
+
+        // Survives a restart; a worse state still alerts, to both hooks
+        let mut watcher = Watcher::load(dir.path().join(STATE_FILE)).unwrap();
+        let exceeded = status("2025-05-01", budget::EXCEEDED, None);
+        let fired: Vec<usize> = watcher.update_budgets(&[exceeded], &hooks).iter().map(|(i, _)| *i).collect();
+        assert_eq!(fired, [0, 1]);
+        assert!(watcher.update_budgets(std::slice::from_ref(&projected), &hooks).is_empty());
+
+        // A new period starts over
+        let next = status("2025-06-01", budget::PROJECTED, Some("2025-06-03T10:00:00Z"));
+        assert_eq!(watcher.update_budgets(&[next], &hooks).len(), 1);
+    }
+
+    #[test]
+    fn test_enforcement_alerts() {
+        let hooks = [
+            Webhook { url: "http://a".to_string(), events: vec![BUDGET_EXCEEDED.to_string()], ..Webhook::default() },
+            Webhook { url: "http://b".to_string(), ..Webhook::default() },
+        ];
+        let run = EnforcementEvent {
+            budget: "agents".to_string(),
+            period_start: "2025-05-10".to_string(),
+            spent: 41.5,
+            amount: 40.0,
+            command: "revoke-key.sh".to_string(),
+            ran_at: "2025-05-10T12:00:00Z".to_string(),
+            exit_code: Some(1),
+            output: String::new(),
+            error: Some("Hook exited with code 1".to_string()),
+        };
+        let events = enforcement_alerts(&[run], &hooks);
+        assert_eq!(events.len(), 1);
+        assert_eq!((events[0].0, events[0].1["event"].as_str()), (1, Some(BUDGET_ENFORCED)));
+        assert_eq!(events[0].1["enforcement"]["exitCode"], 1);
+        assert!(events[0].1["text"].as_str().unwrap().contains("failed"));
+    }
+
+    #[test]
+    fn test_pricing_alert_once_until_recovered() {
+        let dir = tempfile::tempdir().unwrap();
+        let hooks = [
+            Webhook { url: "http://a".to_string(), ..Webhook::default() },
+            Webhook { url: "http://b".to_string(), events: vec![SESSION_CREATED.to_string()], ..Webhook::default() },
+        ];
+        let mut watcher = Watcher::load(dir.path().join(STATE_FILE)).unwrap();
+        assert!(watcher.update_pricing(true));
+        assert!(!watcher.update_pricing(true));
+        assert!(!watcher.update_pricing(false));
+        assert!(watcher.update_pricing(true));
+
+        let providers = [ProviderStatus {
+            name: "GitHub".to_string(),
+            pricing_source: Some("litellm".to_string()),
+            url: "https://www.githubstatus.com/api/v2/status.json".to_string(),
+            state: provider_status::OUTAGE.to_string(),
+            description: "Partial System Outage".to_string(),
+        }];
+        let diagnosis = provider_status::diagnose(Some("litellm"), &providers);
+        let events = pricing_alerts("litellm: HTTP 503", &diagnosis, &providers, &hooks);
+        assert_eq!(events.len(), 1);
+        assert_eq!((events[0].0, events[0].1["event"].as_str()), (0, Some(PRICING_UNAVAILABLE)));
+        assert_eq!(events[0].1["providers"][0]["state"], "outage");
+        assert!(events[0].1["text"].as_str().unwrap().contains("GitHub reports a problem on their side"));
+    }
+
+    #[test]
+    fn test_ingested_only_where_listed() {
+        let dir = tempfile::tempdir().unwrap();
+        let hooks = [
+            Webhook { url: "http://a".to_string(), ..Webhook::default() },
+            Webhook { url: "http://b".to_string(), events: vec![USAGE_INGESTED.to_string()], ..Webhook::default() },
+        ];
+        let message = |cost: f64| UnifiedMessage::new("claude", "sonnet", "anthropic", "s1", 0, TokenBreakdown::default(), cost);
+
+        let mut watcher = Watcher::load(dir.path().join(STATE_FILE)).unwrap();
+        assert!(watcher.update_ingested(&[message(1.0)], &hooks).is_empty(), "the first check is the baseline");
+        let events = watcher.update_ingested(&[message(1.0), message(0.5), message(0.25)], &hooks);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].0, 1);
+        assert_eq!((events[0].1["messages"].as_u64(), events[0].1["cost"].as_f64()), (Some(2), Some(0.75)));
+        assert_eq!(events[0].1["totalMessages"], 3);
+        assert!(watcher.update_ingested(&[message(1.0)], &hooks).is_empty(), "fewer messages aren't ingested");
+    }
+
+    #[test]
+    fn test_validate() {
+        let hook = |events: &[&str], threshold: Option<f64>| Webhook {
+            url: "https://example.com/hook".to_string(),
+            events: events.iter().map(|e| e.to_string()).collect(),
+            cost_threshold: threshold,
+            secret: None,
+        };
+        assert!(hook(&[], None).validate().is_ok());
+        assert!(hook(&[SESSION_THRESHOLD], None).validate().unwrap_err().contains("costThreshold"));
+        assert!(hook(&["session.ended"], None).validate().unwrap_err().contains("unknown event"));
+        assert!(hook(&[], Some(0.0)).validate().is_err());
+        assert!(Webhook { url: "ftp://x".to_string(), ..Webhook::default() }.validate().is_err());
+    }
+
+    #[test]
+    fn test_sign() {
+        assert_eq!(
+            sign("key", b"The quick brown fox jumps over the lazy dog").unwrap(),
+            "sha256=f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8"
+        );
+    }
+
+    #[test]
+    fn test_deliver_signs_and_retries() {
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        runtime.block_on(async {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let url = format!("http://{}/hook", listener.local_addr().unwrap());
+            let received = Arc::new(Mutex::new(Vec::new()));
+            let log = Arc::clone(&received);
+            tokio::spawn(async move {
+                for status in [503, 200] {
+                    let (stream, _) = listener.accept().await.unwrap();
+                    let (read, mut write) = stream.into_split();
+                    let request = http::read_request(&mut BufReader::new(read)).await.unwrap().unwrap();
+                    log.lock().unwrap().push(request);
+                    let headers = [("content-length".to_string(), "0".to_string())];
+                    http::write_head(&mut write, status, "", &headers, false).await.unwrap();
+                    write.flush().await.unwrap();
+                }
+            });
+
+            let hook = Webhook { url, ..Webhook::default() };
+            let body = json!({ "id": "d1", "event": SESSION_CREATED });
+            let client = reqwest::Client::new();
+            let attempts = deliver(&client, &hook, Some("s3cret"), &body, &[Duration::ZERO]).await.unwrap();
+            assert_eq!(attempts, 2);
+
+            let received = received.lock().unwrap();
+            let request = &received[1];
+            assert_eq!(request.header("x-tokscale-event"), Some(SESSION_CREATED));
+            let expected = sign("s3cret", &request.body).unwrap();
+            assert_eq!(request.header("x-tokscale-signature"), Some(expected.as_str()));
+            assert_eq!(expected.len(), "sha256=".len() + 64);
+        });
+    }
+}
@@ -0,0 +1,104 @@
+//! Sessions for the TUI session browser
+//!
+//! One entry per session with what the browser searches on (session ID,
+//! project, models) and sorts by, plus a cost timeline for the detail pane:
+//! the session's span cut into [`TIMELINE_POINTS`] equal slices, each holding
+//! the cost of the messages in it. A session with a single message (or all
+//! messages at one instant) has a one-point timeline.
+
+use crate::sessions::UnifiedMessage;
+use crate::workspace::WorkspaceResolver;
+use crate::SessionEntry;
+use std::collections::{BTreeSet, HashMap};
+
+/// Slices in a session's cost timeline
+pub const TIMELINE_POINTS: usize = 24;
+
+fn total_tokens(msg: &UnifiedMessage) -> i64 {
+    let t = &msg.tokens;
+    t.input + t.output + t.cache_read + t.cache_write + t.reasoning + t.training
+}
+
+/// Cost per slice of `[start, end]`
+fn timeline(messages: &[&UnifiedMessage], start: i64, end: i64) -> Vec<f64> {
+    let span = end - start;
+    if span <= 0 {
+        return vec![messages.iter().map(|m| m.cost).sum()];
+    }
+    let mut points = vec![0.0; TIMELINE_POINTS];
+    for msg in messages {
+        let slice = ((msg.timestamp - start) as i128 * TIMELINE_POINTS as i128 / span as i128) as usize;
+        points[slice.min(TIMELINE_POINTS - 1)] += msg.cost;
+    }
+    points
+}
+
+/// Every session in `messages`, most recent first
+pub fn sessions(messages: &[UnifiedMessage]) -> Vec<SessionEntry> {
+    let mut grouped: HashMap<(&str, &str), Vec<&UnifiedMessage>> = HashMap::new();
+    for msg in messages.iter().filter(|m| !m.session_id.is_empty()) {
+        grouped.entry((msg.source.as_str(), msg.session_id.as_str())).or_default().push(msg);
+    }
+
+    let mut resolver = WorkspaceResolver::new();
+    let mut entries: Vec<SessionEntry> = grouped
+        .into_iter()
+        .map(|((source, session_id), messages)| {
+            let start = messages.iter().map(|m| m.timestamp).min().unwrap_or_default();
+            let end = messages.iter().map(|m| m.timestamp).max().unwrap_or_default();
+            let models: BTreeSet<&str> = messages.iter().map(|m| m.model_id.as_str()).collect();
+            let project = messages.iter().find_map(|m| m.cwd.as_deref()).map(|cwd| resolver.resolve(cwd).name);
+            SessionEntry {
+                source: source.to_string(),
+                session_id: session_id.to_string(),
+                project,
+                models: models.into_iter().map(str::to_string).collect(),
+                start,
+                end,
+                message_count: messages.len() as i32,
+                tokens: messages.iter().map(|m| total_tokens(m)).sum(),
+                cost: messages.iter().map(|m| m.cost).sum(),
+                timeline: timeline(&messages, start, end),
+            }
+        })
+        .collect();
+    entries.sort_by(|a, b| {
+        b.end
+            .cmp(&a.end)
+            .then_with(|| a.source.cmp(&b.source))
+            .then_with(|| a.session_id.cmp(&b.session_id))
+    });
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{message, TestMessage};
+
+    const MINUTE: i64 = 60_000;
+    const T0: i64 = 1_740_823_200_000;
+
+    #[test]
+    fn test_sessions_with_timeline() {
+        let messages = vec![
+            message("s1", T0, 1.0).with_input(100).with_output(50),
+            message("s1", T0 + 12 * MINUTE, 2.0).with_model("claude-opus-4").with_input(100).with_output(50),
+            message("s1", T0 + 24 * MINUTE, 0.5).with_input(100).with_output(50),
+            message("s2", T0 + 60 * MINUTE, 0.25).with_input(100).with_output(50),
+            message("", T0, 9.0).with_input(100).with_output(50),
+        ];
+
+        let sessions = sessions(&messages);
+        assert_eq!(sessions.len(), 2);
+        assert_eq!(sessions[0].session_id, "s2", "most recent first");
+        assert_eq!(sessions[0].timeline, vec![0.25]);
+
+        let s1 = &sessions[1];
+        assert_eq!(s1.models, vec!["claude-opus-4", "claude-sonnet-4"]);
+        assert_eq!((s1.message_count, s1.tokens, s1.cost), (3, 450, 3.5));
+        assert_eq!(s1.project, None);
+        assert_eq!(s1.timeline.len(), TIMELINE_POINTS);
+        assert_eq!((s1.timeline[0], s1.timeline[12], s1.timeline[23]), (1.0, 2.0, 0.5));
+    }
+}
@@ -0,0 +1,198 @@
+//! Shareable session bundles
+//!
+//! `tokscale share-session <id>` writes one session as a JSON bundle that can
+//! be attached to a bug report or sent to a teammate, and `tokscale inspect`
+//! reads one back. A bundle holds what the numbers need and nothing a session
+//! says or where it ran:
+//!
+//! - kept: source, models, providers, token counts, costs, timestamps (the
+//!   session's start and end, each message as an offset from the start),
+//!   tool call counts and whether a message came from a sub-agent
+//! - hashed: the session ID and message IDs, as the first 16 hex digits of
+//!   their SHA-256, so the sender can tell which session a bundle came from
+//!   and duplicates still show, without the IDs themselves
+//! - dropped: prompts and responses (never parsed), working directory, git
+//!   branch, agent names and prompt lengths
+//!
+//! A session is picked by its full ID or an unambiguous prefix, as shown by
+//! the TUI session browser.
+
+use crate::sessions::UnifiedMessage;
+use crate::{BundleMessage, BundleSession, SessionBundle, TokenBreakdown};
+use std::collections::BTreeSet;
+
+/// `format` of every bundle
+pub const FORMAT: &str = "tokscale-session-bundle";
+/// Newest bundle version this build writes and reads
+pub const VERSION: u32 = 1;
+
+/// Hex digits kept of a hashed ID
+const HASH_DIGITS: usize = 16;
+
+/// `id` as it appears in a bundle
+pub fn hash_id(id: &str) -> String {
+    openssl::sha::sha256(id.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<String>()[..HASH_DIGITS]
+        .to_string()
+}
+
+fn add_tokens(total: &mut TokenBreakdown, tokens: &TokenBreakdown) {
+    total.input += tokens.input;
+    total.output += tokens.output;
+    total.cache_read += tokens.cache_read;
+    total.cache_write += tokens.cache_write;
+    total.reasoning += tokens.reasoning;
+    total.training += tokens.training;
+}
+
+/// The session in `messages` whose ID is `id`, or starts with it
+fn find<'a>(messages: &'a [UnifiedMessage], id: &str) -> Result<(&'a str, &'a str), String> {
+    if id.is_empty() {
+        return Err("No session ID given".to_string());
+    }
+    let sessions: BTreeSet<(&str, &str)> = messages
+        .iter()
+        .filter(|m| !m.session_id.is_empty())
+        .map(|m| (m.source.as_str(), m.session_id.as_str()))
+        .collect();
+    if let Some(exact) = sessions.iter().find(|(_, session)| *session == id) {
+        return Ok(*exact);
+    }
+    let matches: Vec<_> = sessions.iter().filter(|(_, session)| session.starts_with(id)).collect();
+    match matches.as_slice() {
+        [] => Err(format!("No session with ID \"{}\"", id)),
+        [only] => Ok(**only),
+        _ => Err(format!(
+            "\"{}\" matches {} sessions ({}); give more of the ID",
+            id,
+            matches.len(),
+            matches.iter().take(3).map(|(_, s)| *s).collect::<Vec<_>>().join(", ")
+        )),
+    }
+}
+
+/// A redacted bundle of session `id`, written at `created_at` (RFC 3339)
+pub fn bundle(messages: &[UnifiedMessage], id: &str, created_at: String) -> Result<SessionBundle, String> {
+    let (source, session_id) = find(messages, id)?;
+    let mut session_messages: Vec<&UnifiedMessage> =
+        messages.iter().filter(|m| m.source.as_str() == source && m.session_id == session_id).collect();
+    session_messages.sort_by_key(|m| m.timestamp);
+
+    let start = session_messages.first().map(|m| m.timestamp).unwrap_or_default();
+    let end = session_messages.last().map(|m| m.timestamp).unwrap_or_default();
+    let mut tokens = TokenBreakdown::default();
+    for msg in &session_messages {
+        add_tokens(&mut tokens, &msg.tokens);
+    }
+    let models: BTreeSet<&str> = session_messages.iter().map(|m| m.model_id.as_str()).collect();
+
+    Ok(SessionBundle {
+        format: FORMAT.to_string(),
+        version: VERSION,
+        tokscale_version: env!("CARGO_PKG_VERSION").to_string(),
+        created_at,
+        session: BundleSession {
+            id: hash_id(session_id),
+            source: source.to_string(),
+            models: models.into_iter().map(str::to_string).collect(),
+            start,
+            end,
+            message_count: session_messages.len() as u32,
+            tokens,
+            cost: session_messages.iter().map(|m| m.cost).sum(),
+        },
+        messages: session_messages
+            .iter()
+            .map(|msg| BundleMessage {
+                id: msg.dedup_key.as_deref().map(hash_id),
+                offset_ms: msg.timestamp - start,
+                model: msg.model_id.clone(),
+                provider: msg.provider_id.as_str().to_string(),
+                tokens: msg.tokens.clone(),
+                cost: msg.cost,
+                billed: msg.billed,
+                sidechain: msg.sidechain,
+                tool_calls: msg.tool_calls,
+                estimated_timestamp: msg.estimated_timestamp,
+            })
+            .collect(),
+    })
+}
+
+/// A bundle read back from its JSON
+pub fn read(json: &str) -> Result<SessionBundle, String> {
+    let value: serde_json::Value = serde_json::from_str(json).map_err(|e| format!("Not valid JSON: {}", e))?;
+    if value.get("format").and_then(|f| f.as_str()) != Some(FORMAT) {
+        return Err("Not a tokscale session bundle (see 'tokscale share-session')".to_string());
+    }
+    match value.get("version").and_then(|v| v.as_u64()) {
+        Some(version) if (1..=VERSION as u64).contains(&version) => {}
+        Some(version) => return Err(format!("Bundle version {} is newer than this tokscale reads ({})", version, VERSION)),
+        None => return Err("Bundle has no version".to_string()),
+    }
+    serde_json::from_value(value).map_err(|e| format!("Malformed bundle: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{message, TestMessage};
+
+    const MINUTE: i64 = 60_000;
+    const T0: i64 = 1_740_823_200_000;
+
+    /// A message carrying everything a bundle must not leak
+    fn private_message(session: &str, timestamp: i64, cost: f64) -> UnifiedMessage {
+        let tokens = TokenBreakdown { input: 100, output: 50, cache_read: 1000, ..TokenBreakdown::default() };
+        let mut msg = message(session, timestamp, cost).with_tokens(tokens);
+        msg.dedup_key = Some(format!("msg_{}", timestamp));
+        msg.cwd = Some("/home/alice/secret-project".to_string());
+        msg.git_branch = Some("alice/acquisition".to_string());
+        msg.agent = Some("Acquisition planner".to_string());
+        msg.prompt_chars = Some(420);
+        msg
+    }
+
+    #[test]
+    fn test_bundle_is_redacted() {
+        let messages = vec![
+            private_message("5f1c9a2e-session", T0 + 10 * MINUTE, 0.5),
+            private_message("5f1c9a2e-session", T0, 1.0),
+            private_message("7b04d3aa-other", T0, 9.0),
+        ];
+        let bundle = bundle(&messages, "5f1c", "2025-03-01T12:00:00Z".to_string()).unwrap();
+
+        assert_eq!(bundle.session.id, hash_id("5f1c9a2e-session"));
+        assert_eq!(bundle.session.message_count, 2);
+        assert_eq!(bundle.session.tokens.cache_read, 2000);
+        assert!((bundle.session.cost - 1.5).abs() < 1e-9);
+        assert_eq!((bundle.session.start, bundle.session.end), (T0, T0 + 10 * MINUTE));
+        let offsets: Vec<i64> = bundle.messages.iter().map(|m| m.offset_ms).collect();
+        assert_eq!(offsets, vec![0, 10 * MINUTE]);
+
+        let json = serde_json::to_string(&bundle).unwrap();
+        for secret in ["5f1c9a2e", "msg_", "alice", "secret-project", "Acquisition", "prompt"] {
+            assert!(!json.contains(secret), "{} leaked into {}", secret, json);
+        }
+        assert_eq!(read(&json).unwrap().session.id, bundle.session.id);
+    }
+
+    #[test]
+    fn test_find_by_prefix() {
+        let messages = vec![message("abc-1", T0, 1.0), message("abc-2", T0, 1.0), message("abc", T0, 1.0)];
+        assert_eq!(find(&messages, "abc").unwrap().1, "abc", "an exact ID wins over prefixes");
+        assert_eq!(find(&messages, "abc-2").unwrap().1, "abc-2");
+        assert!(find(&messages, "abc-").unwrap_err().contains("matches 2 sessions"));
+        assert!(find(&messages, "xyz").unwrap_err().contains("No session"));
+    }
+
+    #[test]
+    fn test_read_rejects_other_json() {
+        assert!(read("{\"entries\": []}").unwrap_err().contains("Not a tokscale session bundle"));
+        let future = format!("{{\"format\": \"{}\", \"version\": {}}}", FORMAT, VERSION + 1);
+        assert!(read(&future).unwrap_err().contains("newer"));
+        assert!(read("not json").unwrap_err().contains("Not valid JSON"));
+    }
+}
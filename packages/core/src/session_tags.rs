@@ -0,0 +1,135 @@
+//! Outlier session tags
+//!
+//! Reviewing every session is tedious; the ones worth a look are usually the
+//! outliers. Sessions over a threshold get a tag, set in settings.json:
+//!
+//! ```json
+//! "sessionTags": { "cost": 20, "tokens": 5000000, "activeMinutes": 120, "subAgentMessages": 50 }
+//! ```
+//!
+//! Each threshold has a default, so tags work without any settings; setting
+//! one to 0 turns its tag off. Tags are recomputed on every report rather
+//! than stored, so changing a threshold retags past sessions too.
+
+use crate::SessionActivity;
+use serde::{Deserialize, Serialize};
+
+pub const EXPENSIVE: &str = "expensive";
+pub const TOKEN_HEAVY: &str = "token-heavy";
+pub const LONG_RUNNING: &str = "long-running";
+pub const AGENT_HEAVY: &str = "agent-heavy";
+
+/// Every tag, in the order they are listed on a session
+pub const TAGS: &[&str] = &[EXPENSIVE, TOKEN_HEAVY, LONG_RUNNING, AGENT_HEAVY];
+
+pub const DEFAULT_COST: f64 = 10.0;
+pub const DEFAULT_TOKENS: i64 = 10_000_000;
+pub const DEFAULT_ACTIVE_MINUTES: f64 = 120.0;
+pub const DEFAULT_SUB_AGENT_MESSAGES: u32 = 100;
+
+const MS_PER_MINUTE: f64 = 60_000.0;
+
+/// `sessionTags` in settings.json. A session is tagged when it reaches a
+/// threshold; unset ones use the defaults above.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct TagThresholds {
+    /// USD per session, for `expensive`
+    pub cost: Option<f64>,
+    /// All tokens including cache, for `token-heavy`
+    pub tokens: Option<i64>,
+    /// Active time with idle gaps trimmed, for `long-running`
+    pub active_minutes: Option<f64>,
+    /// Messages written by sub-agents, for `agent-heavy`
+    pub sub_agent_messages: Option<u32>,
+}
+
+impl TagThresholds {
+    pub fn validate(&self) -> Result<(), String> {
+        for (name, value) in [("cost", self.cost), ("activeMinutes", self.active_minutes)] {
+            if value.is_some_and(|v| v.is_nan() || v < 0.0) {
+                return Err(format!("{} must not be negative, got {}", name, value.unwrap_or_default()));
+            }
+        }
+        if let Some(tokens) = self.tokens.filter(|t| *t < 0) {
+            return Err(format!("tokens must not be negative, got {}", tokens));
+        }
+        Ok(())
+    }
+
+    /// Tags `session` reaches, in [`TAGS`] order
+    pub fn tags(&self, session: &SessionActivity) -> Vec<String> {
+        let cost = self.cost.unwrap_or(DEFAULT_COST);
+        let tokens = self.tokens.unwrap_or(DEFAULT_TOKENS);
+        let active_ms = self.active_minutes.unwrap_or(DEFAULT_ACTIVE_MINUTES) * MS_PER_MINUTE;
+        let sub_agent = self.sub_agent_messages.unwrap_or(DEFAULT_SUB_AGENT_MESSAGES);
+
+        let mut tags = Vec::new();
+        if cost > 0.0 && session.cost >= cost {
+            tags.push(EXPENSIVE.to_string());
+        }
+        if tokens > 0 && session.tokens >= tokens {
+            tags.push(TOKEN_HEAVY.to_string());
+        }
+        if active_ms > 0.0 && session.active_ms as f64 >= active_ms {
+            tags.push(LONG_RUNNING.to_string());
+        }
+        if sub_agent > 0 && session.sub_agent_messages as u32 >= sub_agent {
+            tags.push(AGENT_HEAVY.to_string());
+        }
+        tags
+    }
+}
+
+/// Error unless `tag` is one of [`TAGS`]
+pub fn validate_tag(tag: &str) -> Result<(), String> {
+    if TAGS.contains(&tag) {
+        Ok(())
+    } else {
+        Err(format!("unknown session tag \"{}\", expected one of: {}", tag, TAGS.join(", ")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(cost: f64, tokens: i64, active_minutes: i64, sub_agent_messages: i32) -> SessionActivity {
+        SessionActivity {
+            source: "claude".to_string(),
+            session_id: "s1".to_string(),
+            start: 0,
+            end: active_minutes * 60_000,
+            wall_ms: active_minutes * 60_000,
+            active_ms: active_minutes * 60_000,
+            message_count: 10,
+            cost,
+            tokens,
+            sub_agent_messages,
+            tags: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_tags_sessions_over_thresholds() {
+        let defaults = TagThresholds::default();
+        assert!(defaults.tags(&session(1.0, 1_000, 10, 0)).is_empty());
+        assert_eq!(
+            defaults.tags(&session(10.0, 20_000_000, 180, 100)),
+            vec![EXPENSIVE, TOKEN_HEAVY, LONG_RUNNING, AGENT_HEAVY]
+        );
+
+        let custom = TagThresholds { cost: Some(0.0), active_minutes: Some(30.0), ..Default::default() };
+        assert_eq!(custom.tags(&session(500.0, 1_000, 45, 0)), vec![LONG_RUNNING], "cost tag turned off");
+    }
+
+    #[test]
+    fn test_validation() {
+        assert!(TagThresholds { cost: Some(-1.0), ..Default::default() }.validate().is_err());
+        assert!(TagThresholds { tokens: Some(-5), ..Default::default() }.validate().is_err());
+        assert!(TagThresholds { active_minutes: Some(f64::NAN), ..Default::default() }.validate().is_err());
+        assert!(TagThresholds { cost: Some(0.0), ..Default::default() }.validate().is_ok());
+        assert!(validate_tag("long-running").is_ok());
+        assert!(validate_tag("slow").unwrap_err().contains("expensive, token-heavy"));
+    }
+}
@@ -1,9 +1,18 @@
 //! Amp (Sourcegraph) session parser
 //!
 //! Parses JSON files from ~/.local/share/amp/threads/
+//!
+//! Threads come in two formats: older ones carry usage on each assistant
+//! message, newer ones keep a usage ledger. The format is detected up front and
+//! each is parsed with its own structs; the detected format is recorded in
+//! parse diagnostics.
 
-use super::UnifiedMessage;
+use super::lenient::{self, FieldSpec, Kind};
+use super::provider::infer_provider;
+use super::{Source, UnifiedMessage};
+use crate::diagnostics::{record_drift, record_format};
 use crate::TokenBreakdown;
+use serde::de::IgnoredAny;
 use serde::Deserialize;
 use std::path::Path;
 
@@ -41,6 +50,15 @@ pub struct AmpMessageUsage {
     #[serde(rename = "cacheCreationInputTokens")]
     pub cache_creation_input_tokens: Option<i64>,
     pub credits: Option<f64>,
+    /// RFC 3339 completion time, written by newer Amp versions
+    pub timestamp: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AmpMessageMeta {
+    /// Unix milliseconds
+    #[serde(rename = "sentAt")]
+    pub sent_at: Option<i64>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -49,6 +67,7 @@ pub struct AmpMessage {
     #[serde(rename = "messageId")]
     pub message_id: Option<i64>,
     pub usage: Option<AmpMessageUsage>,
+    pub meta: Option<AmpMessageMeta>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -56,149 +75,325 @@ pub struct AmpUsageLedger {
     pub events: Option<Vec<AmpUsageEvent>>,
 }
 
+/// Thread written before the usage ledger existed: usage is attached to each
+/// assistant message
 #[derive(Debug, Deserialize)]
-pub struct AmpThread {
+pub struct AmpMessageThread {
     pub id: Option<String>,
     pub created: Option<i64>,
     pub messages: Option<Vec<AmpMessage>>,
+}
+
+/// Thread with a usage ledger: one event per billed operation
+#[derive(Debug, Deserialize)]
+pub struct AmpLedgerThread {
+    pub id: Option<String>,
     #[serde(rename = "usageLedger")]
     pub usage_ledger: Option<AmpUsageLedger>,
 }
 
-/// Get provider from model name
-fn get_provider_from_model(model: &str) -> &'static str {
-    let model_lower = model.to_lowercase();
-    if model_lower.contains("claude")
-        || model_lower.contains("opus")
-        || model_lower.contains("sonnet")
-        || model_lower.contains("haiku")
-    {
-        return "anthropic";
-    }
-    if model_lower.contains("gpt") || model_lower.contains("o1") || model_lower.contains("o3") {
-        return "openai";
-    }
-    if model_lower.contains("gemini") {
-        return "google";
+/// Just enough of a thread to tell its format apart
+#[derive(Debug, Deserialize)]
+struct AmpProbe {
+    #[serde(rename = "usageLedger")]
+    usage_ledger: Option<AmpLedgerProbe>,
+    messages: Option<IgnoredAny>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AmpLedgerProbe {
+    events: Option<Vec<IgnoredAny>>,
+}
+
+/// Amp thread file format, oldest first
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AmpFormat {
+    /// Per-message `usage` only
+    MessageUsage,
+    /// `usageLedger.events`
+    UsageLedger,
+}
+
+impl AmpFormat {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AmpFormat::MessageUsage => "message-usage",
+            AmpFormat::UsageLedger => "usage-ledger",
+        }
     }
-    if model_lower.contains("grok") {
-        return "xai";
+
+    /// Detect the format of a thread file. A ledger with events takes
+    /// precedence over message usage, which newer threads still carry.
+    pub fn detect(content: &[u8]) -> Option<AmpFormat> {
+        let mut bytes = content.to_vec();
+        let probe: AmpProbe = crate::parser::from_slice(&mut bytes).ok()?;
+
+        let has_events = probe
+            .usage_ledger
+            .and_then(|ledger| ledger.events)
+            .is_some_and(|events| !events.is_empty());
+        if has_events {
+            Some(AmpFormat::UsageLedger)
+        } else if probe.messages.is_some() {
+            Some(AmpFormat::MessageUsage)
+        } else {
+            None
+        }
     }
-    "anthropic" // Default for Amp
 }
 
+/// Fields checked and repaired when a message-usage thread drifts
+const MESSAGE_SCHEMA: &[FieldSpec] = &[
+    ("/id", Kind::Str),
+    ("/created", Kind::Int),
+    ("/messages", Kind::Arr),
+    ("/messages/*/role", Kind::Str),
+    ("/messages/*/messageId", Kind::Int),
+    ("/messages/*/usage", Kind::Obj),
+    ("/messages/*/usage/model", Kind::Str),
+    ("/messages/*/usage/inputTokens", Kind::Int),
+    ("/messages/*/usage/outputTokens", Kind::Int),
+    ("/messages/*/usage/cacheReadInputTokens", Kind::Int),
+    ("/messages/*/usage/cacheCreationInputTokens", Kind::Int),
+    ("/messages/*/usage/credits", Kind::Num),
+    ("/messages/*/usage/timestamp", Kind::Str),
+    ("/messages/*/meta", Kind::Obj),
+    ("/messages/*/meta/sentAt", Kind::Int),
+];
+
+/// Fields checked and repaired when a usage-ledger thread drifts
+const LEDGER_SCHEMA: &[FieldSpec] = &[
+    ("/id", Kind::Str),
+    ("/usageLedger", Kind::Obj),
+    ("/usageLedger/events", Kind::Arr),
+    ("/usageLedger/events/*/timestamp", Kind::Str),
+    ("/usageLedger/events/*/model", Kind::Str),
+    ("/usageLedger/events/*/credits", Kind::Num),
+    ("/usageLedger/events/*/operationType", Kind::Str),
+    ("/usageLedger/events/*/tokens", Kind::Obj),
+    ("/usageLedger/events/*/tokens/input", Kind::Int),
+    ("/usageLedger/events/*/tokens/output", Kind::Int),
+    ("/usageLedger/events/*/tokens/cacheReadInputTokens", Kind::Int),
+    ("/usageLedger/events/*/tokens/cacheCreationInputTokens", Kind::Int),
+];
+
 /// Parse an Amp thread JSON file
 pub fn parse_amp_file(path: &Path) -> Vec<UnifiedMessage> {
-    let content = match std::fs::read(path) {
+    let content = match crate::archive::read(path) {
         Ok(c) => c,
         Err(_) => return Vec::new(),
     };
 
-    let mut bytes = content;
-    let thread: AmpThread = match simd_json::from_slice(&mut bytes) {
-        Ok(t) => t,
-        Err(_) => return Vec::new(),
+    let Some(format) = AmpFormat::detect(&content) else {
+        // Valid JSON without a known layout is a format we don't understand yet
+        if serde_json::from_slice::<IgnoredAny>(&content).is_ok() {
+            record_drift("amp", "(format)", "usageLedger or messages", "unrecognized thread");
+        }
+        return Vec::new();
     };
+    record_format("amp", format.as_str());
 
-    let thread_id = thread
-        .id
-        .clone()
-        .unwrap_or_else(|| {
-            path.file_stem()
-                .and_then(|s| s.to_str())
-                .unwrap_or("unknown")
-                .to_string()
-        });
+    let fallback_id = || {
+        crate::archive::file_stem(path).unwrap_or("unknown").to_string()
+    };
 
+    match format {
+        AmpFormat::UsageLedger => lenient::from_slice("amp", &content, LEDGER_SCHEMA)
+            .map(|thread: AmpLedgerThread| {
+                let thread_id = thread.id.unwrap_or_else(fallback_id);
+                parse_ledger(thread_id, thread.usage_ledger)
+            })
+            .unwrap_or_default(),
+        AmpFormat::MessageUsage => lenient::from_slice("amp", &content, MESSAGE_SCHEMA)
+            .map(|thread: AmpMessageThread| {
+                let thread_id = thread.id.unwrap_or_else(fallback_id);
+                parse_message_usage(thread_id, thread.created, thread.messages)
+            })
+            .unwrap_or_default(),
+    }
+}
+
+fn parse_ledger(thread_id: String, ledger: Option<AmpUsageLedger>) -> Vec<UnifiedMessage> {
     let mut messages = Vec::new();
+    let events = ledger.and_then(|l| l.events).unwrap_or_default();
+
+    for event in events {
+        let model = match event.model {
+            Some(m) => m,
+            None => continue,
+        };
 
-    // Prefer usageLedger.events (cleaner aggregated data)
-    if let Some(ledger) = thread.usage_ledger {
-        if let Some(events) = ledger.events {
-            for event in events {
-                let model = match event.model {
-                    Some(m) => m,
-                    None => continue,
-                };
-
-                let timestamp = event
-                    .timestamp
-                    .and_then(|ts| chrono::DateTime::parse_from_rfc3339(&ts).ok())
-                    .map(|dt| dt.timestamp_millis())
-                    .unwrap_or(0);
-
-                if timestamp == 0 {
-                    continue;
-                }
-
-                let tokens = event.tokens.unwrap_or(AmpTokens {
-                    input: Some(0),
-                    output: Some(0),
-                    cache_read_input_tokens: Some(0),
-                    cache_creation_input_tokens: Some(0),
-                });
-
-                messages.push(UnifiedMessage::new(
-                    "amp",
-                    &model,
-                    get_provider_from_model(&model),
-                    thread_id.clone(),
-                    timestamp,
-                    TokenBreakdown {
-                        input: tokens.input.unwrap_or(0),
-                        output: tokens.output.unwrap_or(0),
-                        cache_read: tokens.cache_read_input_tokens.unwrap_or(0),
-                        cache_write: tokens.cache_creation_input_tokens.unwrap_or(0),
-                        reasoning: 0,
-                    },
-                    event.credits.unwrap_or(0.0),
-                ));
-            }
-            if !messages.is_empty() {
-                return messages;
-            }
+        let (timestamp, naive) = event
+            .timestamp
+            .as_deref()
+            .and_then(crate::clock::parse_timestamp)
+            .unwrap_or((0, false));
+
+        if timestamp == 0 {
+            continue;
         }
+
+        let tokens = event.tokens.unwrap_or(AmpTokens {
+            input: Some(0),
+            output: Some(0),
+            cache_read_input_tokens: Some(0),
+            cache_creation_input_tokens: Some(0),
+        });
+
+        messages.push(UnifiedMessage::new(
+            Source::Amp,
+            &model,
+            infer_provider(&Source::Amp, &model),
+            thread_id.clone(),
+            timestamp,
+            TokenBreakdown {
+                input: tokens.input.unwrap_or(0),
+                output: tokens.output.unwrap_or(0),
+                cache_read: tokens.cache_read_input_tokens.unwrap_or(0),
+                cache_write: tokens.cache_creation_input_tokens.unwrap_or(0),
+                reasoning: 0,
+                training: 0,
+            },
+            event.credits.unwrap_or(0.0),
+        )
+        .with_naive_timestamp(naive));
     }
 
-    // Fallback: parse from individual message usage
-    let created = thread.created.unwrap_or(0);
-    if let Some(thread_messages) = thread.messages {
-        for msg in thread_messages {
-            if msg.role.as_deref() != Some("assistant") {
-                continue;
-            }
-
-            let usage = match msg.usage {
-                Some(u) => u,
-                None => continue,
-            };
-
-            let model = match usage.model {
-                Some(m) => m,
-                None => continue,
-            };
-
-            // Approximate timestamp from created + messageId offset
-            let message_id = msg.message_id.unwrap_or(0);
-            let timestamp = created + (message_id * 1000);
-
-            messages.push(UnifiedMessage::new(
-                "amp",
-                &model,
-                get_provider_from_model(&model),
-                thread_id.clone(),
-                timestamp,
-                TokenBreakdown {
-                    input: usage.input_tokens.unwrap_or(0),
-                    output: usage.output_tokens.unwrap_or(0),
-                    cache_read: usage.cache_read_input_tokens.unwrap_or(0),
-                    cache_write: usage.cache_creation_input_tokens.unwrap_or(0),
-                    reasoning: 0,
-                },
-                usage.credits.unwrap_or(0.0),
-            ));
+    messages
+}
+
+fn parse_message_usage(
+    thread_id: String,
+    created: Option<i64>,
+    thread_messages: Option<Vec<AmpMessage>>,
+) -> Vec<UnifiedMessage> {
+    let mut messages = Vec::new();
+    let created = created.unwrap_or(0);
+
+    for msg in thread_messages.unwrap_or_default() {
+        if msg.role.as_deref() != Some("assistant") {
+            continue;
         }
+
+        let usage = match msg.usage {
+            Some(u) => u,
+            None => continue,
+        };
+
+        let model = match usage.model {
+            Some(m) => m,
+            None => continue,
+        };
+
+        let recorded = usage
+            .timestamp
+            .as_deref()
+            .and_then(|ts| chrono::DateTime::parse_from_rfc3339(ts).ok())
+            .map(|dt| dt.timestamp_millis())
+            .or(msg.meta.and_then(|meta| meta.sent_at))
+            .filter(|&ts| ts > 0);
+
+        // Older threads have no per-message time: approximate it from
+        // created + messageId offset
+        let (timestamp, estimated) = match recorded {
+            Some(ts) => (ts, false),
+            None => (created + msg.message_id.unwrap_or(0) * 1000, true),
+        };
+
+        let message = UnifiedMessage::new(
+            Source::Amp,
+            &model,
+            infer_provider(&Source::Amp, &model),
+            thread_id.clone(),
+            timestamp,
+            TokenBreakdown {
+                input: usage.input_tokens.unwrap_or(0),
+                output: usage.output_tokens.unwrap_or(0),
+                cache_read: usage.cache_read_input_tokens.unwrap_or(0),
+                cache_write: usage.cache_creation_input_tokens.unwrap_or(0),
+                reasoning: 0,
+                training: 0,
+            },
+            usage.credits.unwrap_or(0.0),
+        );
+
+        messages.push(if estimated {
+            message.with_estimated_timestamp()
+        } else {
+            message
+        });
     }
 
     messages
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostics::{take_drift_for, take_formats_for};
+
+    #[test]
+    fn test_detect_format() {
+        let ledger = br#"{"messages": [], "usageLedger": {"events": [{"model": "m"}]}}"#;
+        assert_eq!(AmpFormat::detect(ledger), Some(AmpFormat::UsageLedger));
+
+        let empty_ledger = br#"{"messages": [], "usageLedger": {"events": []}}"#;
+        assert_eq!(AmpFormat::detect(empty_ledger), Some(AmpFormat::MessageUsage));
+
+        let messages = br#"{"id": "T-1", "messages": [{"role": "user"}]}"#;
+        assert_eq!(AmpFormat::detect(messages), Some(AmpFormat::MessageUsage));
+
+        assert_eq!(AmpFormat::detect(br#"{"id": "T-1", "turns": []}"#), None);
+        assert_eq!(AmpFormat::detect(b"not json"), None);
+    }
+
+    #[test]
+    fn test_message_timestamps() {
+        let thread: AmpMessageThread = serde_json::from_str(
+            r#"{"created": 1750075200000, "messages": [
+                {"role": "assistant", "messageId": 1, "usage": {"model": "m", "timestamp": "2025-06-17T09:30:00.000Z"}},
+                {"role": "assistant", "messageId": 2, "meta": {"sentAt": 1750152000000}, "usage": {"model": "m"}},
+                {"role": "assistant", "messageId": 3, "usage": {"model": "m"}}
+            ]}"#,
+        )
+        .unwrap();
+
+        let messages = parse_message_usage("T-1".into(), thread.created, thread.messages);
+        assert_eq!(messages.len(), 3);
+
+        assert_eq!(messages[0].date, "2025-06-17");
+        assert!(!messages[0].estimated_timestamp);
+        assert_eq!(messages[1].timestamp, 1750152000000);
+        assert!(!messages[1].estimated_timestamp);
+        assert_eq!(messages[2].timestamp, 1750075203000);
+        assert!(messages[2].estimated_timestamp);
+    }
+
+    #[test]
+    fn test_unrecognized_thread_recorded() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("T-unknown.json");
+        std::fs::write(&path, br#"{"id": "T-unknown", "turns": []}"#).unwrap();
+
+        assert!(parse_amp_file(&path).is_empty());
+        let drift = take_drift_for("amp");
+        assert!(drift.iter().any(|d| d.field == "(format)"));
+    }
+
+    #[test]
+    fn test_detected_format_recorded() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("T-ledger.json");
+        let thread = br#"{"id": "T-ledger", "usageLedger": {"events": [
+            {"timestamp": "2025-06-16T08:00:00.000Z", "model": "gpt-5", "credits": 0.4, "tokens": {"input": 7, "output": 1}}
+        ]}}"#;
+        std::fs::write(&path, thread).unwrap();
+
+        let messages = parse_amp_file(&path);
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].session_id, "T-ledger");
+
+        let formats = take_formats_for("amp");
+        assert!(formats.iter().any(|f| f.format == "usage-ledger" && f.count >= 1));
+    }
+}
@@ -1,11 +1,16 @@
 //! Claude Code session parser
 //!
 //! Parses JSONL files from ~/.claude/projects/
+//!
+//! User prompts and tool calls are read only for their derived stats: the
+//! prompt's length, and the number of tool calls and files they name. The
+//! text and paths themselves are dropped as soon as they are counted.
 
-use super::UnifiedMessage;
+use super::lenient::{self, FieldSpec, Kind};
+use super::{Source, UnifiedMessage};
 use crate::TokenBreakdown;
 use serde::Deserialize;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::io::{BufRead, BufReader};
 use std::path::Path;
 
@@ -19,6 +24,14 @@ pub struct ClaudeEntry {
     /// Request ID for deduplication (used with message.id)
     #[serde(rename = "requestId")]
     pub request_id: Option<String>,
+    /// Directory Claude Code was started in
+    pub cwd: Option<String>,
+    /// Branch checked out in `cwd` when the entry was written
+    #[serde(rename = "gitBranch")]
+    pub git_branch: Option<String>,
+    /// Entry belongs to a sub-agent's conversation
+    #[serde(rename = "isSidechain")]
+    pub is_sidechain: Option<bool>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -27,6 +40,102 @@ pub struct ClaudeMessage {
     pub usage: Option<ClaudeUsage>,
     /// Message ID for deduplication (used with requestId)
     pub id: Option<String>,
+    /// "tool_use" when the response asks to run a tool
+    pub stop_reason: Option<String>,
+    pub content: Option<ClaudeContent>,
+}
+
+/// A typed prompt is a plain string; everything else is a list of blocks
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum ClaudeContent {
+    Text(String),
+    Blocks(Vec<ClaudeContentBlock>),
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ClaudeContentBlock {
+    #[serde(rename = "type")]
+    pub block_type: Option<String>,
+    /// Tool call id, on `tool_use` blocks
+    pub id: Option<String>,
+    /// Prompt or reply text; only its length is used
+    pub text: Option<String>,
+    /// Tool call arguments, on `tool_use` blocks
+    pub input: Option<ClaudeToolInput>,
+}
+
+/// The tool call arguments that name a file
+#[derive(Debug, Deserialize)]
+pub struct ClaudeToolInput {
+    /// Read, Write, Edit and MultiEdit
+    pub file_path: Option<String>,
+    /// NotebookEdit
+    pub notebook_path: Option<String>,
+}
+
+impl ClaudeMessage {
+    fn blocks(&self) -> &[ClaudeContentBlock] {
+        match &self.content {
+            Some(ClaudeContent::Blocks(blocks)) => blocks,
+            _ => &[],
+        }
+    }
+
+    fn tool_uses(&self) -> impl Iterator<Item = &ClaudeContentBlock> {
+        self.blocks().iter().filter(|b| b.block_type.as_deref() == Some("tool_use"))
+    }
+
+    fn calls_tool(&self) -> bool {
+        self.stop_reason.as_deref() == Some("tool_use") || self.tool_uses().next().is_some()
+    }
+
+    /// Characters typed in a user entry; `None` for entries that only carry
+    /// tool results
+    fn prompt_chars(&self) -> Option<u32> {
+        let chars = match self.content.as_ref()? {
+            ClaudeContent::Text(text) => text.chars().count(),
+            ClaudeContent::Blocks(blocks) => {
+                let texts: Vec<&str> = blocks
+                    .iter()
+                    .filter(|b| b.block_type.as_deref() == Some("text"))
+                    .filter_map(|b| b.text.as_deref())
+                    .collect();
+                if texts.is_empty() {
+                    return None;
+                }
+                texts.iter().map(|t| t.chars().count()).sum()
+            }
+        };
+        Some(chars.min(u32::MAX as usize) as u32)
+    }
+}
+
+/// Tool calls of one kept message, gathered across the lines it spans
+#[derive(Default)]
+struct ToolUses {
+    /// Ids seen, so a repeated line isn't counted twice
+    ids: HashSet<String>,
+    /// Calls without an id
+    unnamed: u32,
+    files: HashSet<String>,
+}
+
+impl ToolUses {
+    fn add(&mut self, message: &ClaudeMessage) {
+        for block in message.tool_uses() {
+            match &block.id {
+                Some(id) => {
+                    self.ids.insert(id.clone());
+                }
+                None => self.unnamed += 1,
+            }
+            let input = block.input.as_ref();
+            if let Some(path) = input.and_then(|i| i.file_path.as_ref().or(i.notebook_path.as_ref())) {
+                self.files.insert(path.clone());
+            }
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -37,22 +146,50 @@ pub struct ClaudeUsage {
     pub cache_creation_input_tokens: Option<i64>,
 }
 
+/// Fields checked and repaired when an entry no longer matches the structs above
+const SCHEMA: &[FieldSpec] = &[
+    ("/type", Kind::Str),
+    ("/timestamp", Kind::Str),
+    ("/requestId", Kind::Str),
+    ("/cwd", Kind::Str),
+    ("/gitBranch", Kind::Str),
+    ("/isSidechain", Kind::Bool),
+    ("/message", Kind::Obj),
+    ("/message/model", Kind::Str),
+    ("/message/id", Kind::Str),
+    ("/message/stop_reason", Kind::Str),
+    ("/message/content/*/type", Kind::Str),
+    ("/message/content/*/id", Kind::Str),
+    ("/message/content/*/text", Kind::Str),
+    ("/message/content/*/input", Kind::Obj),
+    ("/message/content/*/input/file_path", Kind::Str),
+    ("/message/content/*/input/notebook_path", Kind::Str),
+    ("/message/usage", Kind::Obj),
+    ("/message/usage/input_tokens", Kind::Int),
+    ("/message/usage/output_tokens", Kind::Int),
+    ("/message/usage/cache_read_input_tokens", Kind::Int),
+    ("/message/usage/cache_creation_input_tokens", Kind::Int),
+];
+
 /// Parse a Claude Code JSONL file
 pub fn parse_claude_file(path: &Path) -> Vec<UnifiedMessage> {
-    let file = match std::fs::File::open(path) {
+    let file = match crate::archive::open(path) {
         Ok(f) => f,
         Err(_) => return Vec::new(),
     };
 
-    let session_id = path
-        .file_stem()
-        .and_then(|s| s.to_str())
+    let session_id = crate::archive::file_stem(path)
         .unwrap_or("unknown")
         .to_string();
 
     let reader = BufReader::new(file);
-    let mut messages = Vec::new();
-    let mut processed_hashes: HashSet<String> = HashSet::new();
+    let mut messages: Vec<UnifiedMessage> = Vec::new();
+    // Dedup key -> index in `messages`, if the first entry was kept
+    let mut processed_hashes: HashMap<String, Option<usize>> = HashMap::new();
+    // Tool calls per message in `messages`
+    let mut tool_uses: Vec<ToolUses> = Vec::new();
+    // Length of the last prompt, until a response to it is kept
+    let mut pending_prompt: Option<u32> = None;
 
     for line in reader.lines() {
         let line = match line {
@@ -65,13 +202,19 @@ pub fn parse_claude_file(path: &Path) -> Vec<UnifiedMessage> {
             continue;
         }
 
-        let mut bytes = trimmed.as_bytes().to_vec();
-        let entry: ClaudeEntry = match simd_json::from_slice(&mut bytes) {
-            Ok(e) => e,
-            Err(_) => continue,
+        let entry: ClaudeEntry = match lenient::from_slice("claude", trimmed.as_bytes(), SCHEMA) {
+            Some(e) => e,
+            None => continue,
         };
 
-        // Only process assistant messages with usage data
+        if entry.entry_type == "user" {
+            if let Some(chars) = entry.message.as_ref().and_then(ClaudeMessage::prompt_chars) {
+                pending_prompt = Some(chars);
+            }
+            continue;
+        }
+
+        // Otherwise only assistant messages carry usage data
         if entry.entry_type != "assistant" {
             continue;
         }
@@ -85,14 +228,24 @@ pub fn parse_claude_file(path: &Path) -> Vec<UnifiedMessage> {
         let dedup_key = match (&message.id, &entry.request_id) {
             (Some(msg_id), Some(req_id)) => {
                 let hash = format!("{}:{}", msg_id, req_id);
-                if !processed_hashes.insert(hash.clone()) {
+                if let Some(kept) = processed_hashes.get(&hash) {
+                    // Each content block is its own line; a tool call may
+                    // come after the line that was kept
+                    if let Some(index) = *kept {
+                        messages[index].tool_call |= message.calls_tool();
+                        tool_uses[index].add(&message);
+                    }
                     continue;
                 }
+                processed_hashes.insert(hash.clone(), None);
                 Some(hash)
             }
             _ => None,
         };
 
+        let tool_call = message.calls_tool();
+        let mut tools = ToolUses::default();
+        tools.add(&message);
         let usage = match message.usage {
             Some(u) => u,
             None => continue,
@@ -103,18 +256,23 @@ pub fn parse_claude_file(path: &Path) -> Vec<UnifiedMessage> {
             None => continue,
         };
 
-        let timestamp = entry
+        let (timestamp, naive) = entry
             .timestamp
-            .and_then(|ts| chrono::DateTime::parse_from_rfc3339(&ts).ok())
-            .map(|dt| dt.timestamp_millis())
-            .unwrap_or(0);
+            .as_deref()
+            .and_then(crate::clock::parse_timestamp)
+            .unwrap_or((0, false));
 
         if timestamp == 0 {
             continue;
         }
 
+        if let Some(hash) = &dedup_key {
+            processed_hashes.insert(hash.clone(), Some(messages.len()));
+        }
+        tool_uses.push(tools);
+
         messages.push(UnifiedMessage::new_with_dedup(
-            "claude",
+            Source::Claude,
             model,
             "anthropic",
             session_id.clone(),
@@ -125,12 +283,23 @@ pub fn parse_claude_file(path: &Path) -> Vec<UnifiedMessage> {
                 cache_read: usage.cache_read_input_tokens.unwrap_or(0),
                 cache_write: usage.cache_creation_input_tokens.unwrap_or(0),
                 reasoning: 0,
+                training: 0,
             },
             0.0,
             dedup_key,
-        ));
+        )
+        .with_cwd(entry.cwd)
+        .with_git_branch(entry.git_branch)
+        .with_sidechain(entry.is_sidechain.unwrap_or(false))
+        .with_tool_call(tool_call)
+        .with_prompt_chars(pending_prompt.take())
+        .with_naive_timestamp(naive));
     }
 
+    for (msg, tools) in messages.iter_mut().zip(tool_uses) {
+        msg.tool_calls = tools.ids.len() as u32 + tools.unnamed;
+        msg.files_touched = Some(tools.files.len() as u32);
+    }
     messages
 }
 
@@ -183,6 +352,87 @@ mod tests {
         assert_eq!(messages.len(), 2, "Entries without messageId/requestId should still be processed");
     }
 
+    #[test]
+    fn test_working_directory_kept() {
+        let content = r#"{"type":"assistant","cwd":"/home/dev/app/packages/web","timestamp":"2024-12-01T10:00:00.000Z","message":{"model":"claude-3-5-sonnet","usage":{"input_tokens":100,"output_tokens":50}}}
+{"type":"assistant","timestamp":"2024-12-01T10:00:01.000Z","message":{"model":"claude-3-5-sonnet","usage":{"input_tokens":200,"output_tokens":100}}}"#;
+
+        let file = create_test_file(content);
+        let messages = parse_claude_file(file.path());
+
+        assert_eq!(messages[0].cwd.as_deref(), Some("/home/dev/app/packages/web"));
+        assert_eq!(messages[1].cwd, None);
+    }
+
+    #[test]
+    fn test_sub_agent_and_tool_call_signals() {
+        let content = r#"{"type":"assistant","timestamp":"2024-12-01T10:00:00.000Z","requestId":"req_1","message":{"id":"msg_1","model":"claude-3-5-sonnet","content":[{"type":"text","text":"Let me look"}],"usage":{"input_tokens":100,"output_tokens":50}}}
+{"type":"assistant","timestamp":"2024-12-01T10:00:00.000Z","requestId":"req_1","message":{"id":"msg_1","model":"claude-3-5-sonnet","content":[{"type":"tool_use","name":"Read"}],"usage":{"input_tokens":100,"output_tokens":50}}}
+{"type":"assistant","isSidechain":"true","timestamp":"2024-12-01T10:00:05.000Z","requestId":"req_2","message":{"id":"msg_2","model":"claude-3-5-sonnet","stop_reason":"end_turn","usage":{"input_tokens":10,"output_tokens":5}}}"#;
+
+        let file = create_test_file(content);
+        let messages = parse_claude_file(file.path());
+
+        assert_eq!(messages.len(), 2);
+        assert!(messages[0].tool_call, "tool call in a later line of the same message");
+        assert!(!messages[0].sidechain);
+        assert!(messages[1].sidechain, "drifted string flag is coerced");
+        assert!(!messages[1].tool_call);
+    }
+
+    #[test]
+    fn test_content_stats() {
+        let content = r#"{"type":"user","timestamp":"2024-12-01T10:00:00.000Z","message":{"role":"user","content":"Fix the bug in été"}}
+{"type":"assistant","timestamp":"2024-12-01T10:00:01.000Z","requestId":"req_1","message":{"id":"msg_1","model":"claude-3-5-sonnet","content":[{"type":"tool_use","id":"toolu_1","name":"Read","input":{"file_path":"/src/a.rs"}}],"usage":{"input_tokens":100,"output_tokens":50}}}
+{"type":"assistant","timestamp":"2024-12-01T10:00:01.000Z","requestId":"req_1","message":{"id":"msg_1","model":"claude-3-5-sonnet","content":[{"type":"tool_use","id":"toolu_2","name":"Edit","input":{"file_path":"/src/b.rs","old_string":"x"}}],"usage":{"input_tokens":100,"output_tokens":50}}}
+{"type":"assistant","timestamp":"2024-12-01T10:00:01.000Z","requestId":"req_1","message":{"id":"msg_1","model":"claude-3-5-sonnet","content":[{"type":"tool_use","id":"toolu_2","name":"Edit","input":{"file_path":"/src/b.rs","old_string":"x"}}],"usage":{"input_tokens":100,"output_tokens":50}}}
+{"type":"user","timestamp":"2024-12-01T10:00:02.000Z","message":{"role":"user","content":[{"type":"tool_result","tool_use_id":"toolu_1","content":"fn main() {}"}]}}
+{"type":"assistant","timestamp":"2024-12-01T10:00:03.000Z","requestId":"req_2","message":{"id":"msg_2","model":"claude-3-5-sonnet","content":[{"type":"text","text":"Done"}],"usage":{"input_tokens":200,"output_tokens":10}}}
+{"type":"user","timestamp":"2024-12-01T10:01:00.000Z","message":{"role":"user","content":[{"type":"text","text":"Thanks"},{"type":"image"}]}}
+{"type":"assistant","timestamp":"2024-12-01T10:01:01.000Z","requestId":"req_3","message":{"id":"msg_3","model":"claude-3-5-sonnet","usage":{"input_tokens":200,"output_tokens":10}}}"#;
+
+        let file = create_test_file(content);
+        let (messages, drift, _) = crate::diagnostics::capture(|| parse_claude_file(file.path()));
+        assert!(drift.is_empty(), "string and block content parse without drift: {:?}", drift);
+        assert_eq!(messages.len(), 3);
+
+        assert_eq!(messages[0].prompt_chars, Some(18), "counted in characters, not bytes");
+        assert_eq!(messages[0].tool_calls, 2, "the repeated line is counted once");
+        assert_eq!(messages[0].files_touched, Some(2));
+
+        assert_eq!(messages[1].prompt_chars, None, "tool results aren't prompts");
+        assert_eq!((messages[1].tool_calls, messages[1].files_touched), (0, Some(0)));
+        assert_eq!(messages[2].prompt_chars, Some(6));
+    }
+
+    #[test]
+    fn test_git_branch_kept() {
+        let content = r#"{"type":"assistant","gitBranch":"feature/export","timestamp":"2024-12-01T10:00:00.000Z","message":{"model":"claude-3-5-sonnet","usage":{"input_tokens":100,"output_tokens":50}}}
+{"type":"assistant","gitBranch":"HEAD","timestamp":"2024-12-01T10:00:01.000Z","message":{"model":"claude-3-5-sonnet","usage":{"input_tokens":200,"output_tokens":100}}}
+{"type":"assistant","gitBranch":"","timestamp":"2024-12-01T10:00:02.000Z","message":{"model":"claude-3-5-sonnet","usage":{"input_tokens":200,"output_tokens":100}}}"#;
+
+        let file = create_test_file(content);
+        let messages = parse_claude_file(file.path());
+
+        assert_eq!(messages[0].git_branch.as_deref(), Some("feature/export"));
+        assert_eq!(messages[1].git_branch, None, "a detached HEAD isn't a branch");
+        assert_eq!(messages[2].git_branch, None);
+    }
+
+    #[test]
+    fn test_timestamp_without_offset_kept() {
+        let content = r#"{"type":"assistant","timestamp":"2024-12-01T10:00:00.000Z","message":{"model":"claude-3-5-sonnet","usage":{"input_tokens":100,"output_tokens":50}}}
+{"type":"assistant","timestamp":"2024-12-01T10:00:01","message":{"model":"claude-3-5-sonnet","usage":{"input_tokens":200,"output_tokens":100}}}"#;
+
+        let file = create_test_file(content);
+        let messages = parse_claude_file(file.path());
+
+        assert_eq!(messages.len(), 2);
+        assert!(!messages[0].naive_timestamp);
+        assert!(messages[1].naive_timestamp, "read as UTC until a timezone is applied");
+        assert_eq!(messages[1].timestamp - messages[0].timestamp, 1000);
+    }
+
     #[test]
     fn test_user_messages_ignored() {
         let content = r#"{"type":"user","timestamp":"2024-12-01T10:00:00.000Z","message":{"content":"Hello"}}
@@ -209,4 +459,57 @@ mod tests {
         assert_eq!(messages[0].tokens.cache_write, 100);
         assert_eq!(messages[0].tokens.reasoning, 0);
     }
+
+    #[test]
+    fn test_model_kept_per_message() {
+        // `/model` switches the model mid-session; each message is priced as what it ran on
+        let content = r#"{"type":"assistant","timestamp":"2024-12-01T10:00:00.000Z","requestId":"req_1","message":{"id":"msg_1","model":"claude-sonnet-4-5-20250929","usage":{"input_tokens":100,"output_tokens":50,"cache_creation_input_tokens":4000}}}
+{"type":"assistant","timestamp":"2024-12-01T10:05:00.000Z","requestId":"req_2","message":{"id":"msg_2","model":"claude-opus-4-1-20250805","usage":{"input_tokens":10,"output_tokens":500,"cache_read_input_tokens":4000}}}
+{"type":"assistant","timestamp":"2024-12-01T10:06:00.000Z","requestId":"req_3","message":{"id":"msg_3","usage":{"input_tokens":10,"output_tokens":5}}}"#;
+
+        let file = create_test_file(content);
+        let messages = parse_claude_file(file.path());
+
+        let models: Vec<&str> = messages.iter().map(|m| m.model_id.as_str()).collect();
+        assert_eq!(models, ["claude-sonnet-4-5-20250929", "claude-opus-4-1-20250805"], "no model, no message");
+        assert_eq!((messages[0].tokens.cache_read, messages[0].tokens.cache_write), (0, 4000));
+        assert_eq!((messages[1].tokens.cache_read, messages[1].tokens.cache_write), (4000, 0));
+        assert_eq!(messages[0].session_id, messages[1].session_id);
+    }
+
+    #[test]
+    fn test_drifted_entry_kept() {
+        let content = r#"{"type":"assistant","timestamp":"2024-12-01T10:00:00.000Z","requestId":"req_001","message":{"id":"msg_001","model":"claude-3-5-sonnet","usage":{"input_tokens":"100","output_tokens":50,"cache_read_input_tokens":{"ephemeral":20}}}}"#;
+
+        let file = create_test_file(content);
+        let messages = parse_claude_file(file.path());
+
+        assert_eq!(messages.len(), 1, "Drifted fields should not drop the entry");
+        assert_eq!(messages[0].tokens.input, 100);
+        assert_eq!(messages[0].tokens.output, 50);
+        assert_eq!(messages[0].tokens.cache_read, 0);
+    }
+
+    fn fuzz_line() -> impl proptest::strategy::Strategy<Value = String> {
+        use crate::testutil::{model_id, string};
+        use proptest::prelude::*;
+        prop_oneof![
+            string(80),
+            (string(24), model_id(), any::<i64>()).prop_map(|(timestamp, model, input)| format!(
+                r#"{{"type":"assistant","timestamp":"{}","message":{{"model":"{}","usage":{{"input_tokens":{}}}}}}}"#,
+                timestamp.replace(['"', '\\'], ""),
+                model.replace(['"', '\\'], ""),
+                input,
+            )),
+        ]
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn prop_parse_claude_file_never_panics(lines in proptest::collection::vec(fuzz_line(), 0..12)) {
+            let file = create_test_file(&lines.join("\n"));
+            let messages = parse_claude_file(file.path());
+            proptest::prop_assert!(messages.len() <= lines.len());
+        }
+    }
 }
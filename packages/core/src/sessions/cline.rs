@@ -0,0 +1,343 @@
+//! Cline and Roo Code session parser
+//!
+//! Both VS Code extensions (Roo Code is a fork of Cline) keep one directory
+//! per task under the editor's globalStorage:
+//!
+//! - Cline: `<editor>/User/globalStorage/saoudrizwan.claude-dev/tasks/<task>/`
+//! - Roo Code: `<editor>/User/globalStorage/rooveterinaryinc.roo-cline/tasks/<task>/`
+//!
+//! Usage is read from `ui_messages.json`: every API request is logged as an
+//! `api_req_started` message whose `text` is a JSON string with the tokens
+//! and the cost the extension computed (`deleted_api_reqs` sums requests the
+//! user deleted from the task). Newer Cline versions record the model on the
+//! message itself; otherwise it is taken from `modelInfo` on the assistant
+//! messages of the sibling `api_conversation_history.json`, carrying the last
+//! known model forward. A request is answered by the first assistant message
+//! saved after it started and before the next one did; histories without
+//! timestamps pair the nth request with the nth assistant message. Deleted
+//! requests have no history left, so they take the last known model. The
+//! task directory name is the session ID.
+
+use super::lenient::{self, FieldSpec, Kind};
+use super::provider::infer_provider;
+use super::{Source, UnifiedMessage};
+use crate::TokenBreakdown;
+use serde::Deserialize;
+use std::path::Path;
+
+/// A `ui_messages.json` entry
+#[derive(Debug, Deserialize)]
+pub struct ClineUiMessage {
+    /// Unix milliseconds
+    pub ts: Option<i64>,
+    pub say: Option<String>,
+    pub text: Option<String>,
+    #[serde(rename = "modelInfo")]
+    pub model_info: Option<ClineModelInfo>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ClineModelInfo {
+    #[serde(rename = "modelId")]
+    pub model_id: Option<String>,
+}
+
+/// `text` of an `api_req_started` message
+#[derive(Debug, Deserialize)]
+pub struct ClineApiReqInfo {
+    #[serde(rename = "tokensIn")]
+    pub tokens_in: Option<i64>,
+    #[serde(rename = "tokensOut")]
+    pub tokens_out: Option<i64>,
+    #[serde(rename = "cacheWrites")]
+    pub cache_writes: Option<i64>,
+    #[serde(rename = "cacheReads")]
+    pub cache_reads: Option<i64>,
+    pub cost: Option<f64>,
+}
+
+/// An `api_conversation_history.json` entry, without its content
+#[derive(Debug, Deserialize)]
+pub struct ClineApiMessage {
+    /// Unix milliseconds; missing in older versions
+    pub ts: Option<i64>,
+    pub role: Option<String>,
+    #[serde(rename = "modelInfo")]
+    pub model_info: Option<ClineModelInfo>,
+}
+
+/// Fields checked and repaired when messages no longer match the structs above
+const SCHEMA: &[FieldSpec] = &[
+    ("/*/ts", Kind::Int),
+    ("/*/say", Kind::Str),
+    ("/*/text", Kind::Str),
+    ("/*/modelInfo", Kind::Obj),
+    ("/*/modelInfo/modelId", Kind::Str),
+    ("/*/modelInfo/providerId", Kind::Str),
+];
+
+const REQ_SCHEMA: &[FieldSpec] = &[
+    ("/tokensIn", Kind::Int),
+    ("/tokensOut", Kind::Int),
+    ("/cacheWrites", Kind::Int),
+    ("/cacheReads", Kind::Int),
+    ("/cost", Kind::Num),
+];
+
+const HISTORY_SCHEMA: &[FieldSpec] = &[
+    ("/*/ts", Kind::Int),
+    ("/*/role", Kind::Str),
+    ("/*/modelInfo", Kind::Obj),
+    ("/*/modelInfo/modelId", Kind::Str),
+];
+
+/// Timestamps and models of the assistant messages in the task's API
+/// history, in order
+fn history_models(source: &'static str, ui_messages: &Path) -> Vec<(Option<i64>, Option<String>)> {
+    let Some(name) = ui_messages.file_name().and_then(|n| n.to_str()) else {
+        return Vec::new();
+    };
+    let history = ui_messages.with_file_name(name.replacen("ui_messages", "api_conversation_history", 1));
+    let Ok(data) = crate::archive::read(&history) else {
+        return Vec::new();
+    };
+    let messages: Vec<ClineApiMessage> = lenient::from_slice(source, &data, HISTORY_SCHEMA).unwrap_or_default();
+    messages
+        .into_iter()
+        .filter(|msg| msg.role.as_deref() == Some("assistant"))
+        .map(|msg| (msg.ts, msg.model_info.and_then(|info| info.model_id).filter(|m| !m.is_empty())))
+        .collect()
+}
+
+/// Model from `history` answering each request started at `starts`
+fn request_models(history: Vec<(Option<i64>, Option<String>)>, starts: &[Option<i64>]) -> Vec<Option<String>> {
+    if history.is_empty() || history.iter().any(|(ts, _)| ts.is_none()) {
+        let mut models = history.into_iter().map(|(_, model)| model);
+        return starts.iter().map(|_| models.next().flatten()).collect();
+    }
+    starts
+        .iter()
+        .enumerate()
+        .map(|(i, start)| {
+            let start = (*start)?;
+            let next = starts[i + 1..].iter().flatten().next().copied().unwrap_or(i64::MAX);
+            history
+                .iter()
+                .find(|(ts, _)| ts.is_some_and(|ts| ts >= start && ts < next))
+                .and_then(|(_, model)| model.clone())
+        })
+        .collect()
+}
+
+fn parse_task(source: Source, name: &'static str, path: &Path) -> Vec<UnifiedMessage> {
+    let data = match crate::archive::read(path) {
+        Ok(d) => d,
+        Err(_) => return Vec::new(),
+    };
+    let ui_messages: Vec<ClineUiMessage> = match lenient::from_slice(name, &data, SCHEMA) {
+        Some(m) => m,
+        None => return Vec::new(),
+    };
+
+    let session_id = path
+        .parent()
+        .and_then(|dir| dir.file_name())
+        .and_then(|n| n.to_str())
+        .unwrap_or("unknown")
+        .to_string();
+    let starts: Vec<Option<i64>> = ui_messages
+        .iter()
+        .filter(|msg| msg.say.as_deref() == Some("api_req_started"))
+        .map(|msg| msg.ts)
+        .collect();
+    let mut history = request_models(history_models(name, path), &starts).into_iter();
+    let mut model: Option<String> = None;
+    let mut messages = Vec::new();
+
+    for ui_message in ui_messages {
+        let from_history = match ui_message.say.as_deref() {
+            Some("api_req_started") => history.next().flatten(),
+            Some("deleted_api_reqs") => None,
+            _ => continue,
+        };
+        let recorded = ui_message.model_info.and_then(|info| info.model_id).filter(|m| !m.is_empty());
+        if let Some(next) = recorded.or(from_history) {
+            model = Some(next);
+        }
+
+        let Some(req) = ui_message
+            .text
+            .and_then(|text| lenient::from_slice::<ClineApiReqInfo>(name, text.as_bytes(), REQ_SCHEMA))
+        else {
+            continue;
+        };
+        let tokens = TokenBreakdown {
+            input: req.tokens_in.unwrap_or(0),
+            output: req.tokens_out.unwrap_or(0),
+            cache_read: req.cache_reads.unwrap_or(0),
+            cache_write: req.cache_writes.unwrap_or(0),
+            reasoning: 0,
+            training: 0,
+        };
+        // Requests cancelled or failed before the first token
+        if tokens.input + tokens.output + tokens.cache_read + tokens.cache_write == 0 {
+            continue;
+        }
+        let Some(timestamp) = ui_message.ts.filter(|ts| *ts > 0) else {
+            continue;
+        };
+
+        let model_id = model.clone().unwrap_or_else(|| "unknown".to_string());
+        messages.push(UnifiedMessage::new(
+            source.clone(),
+            model_id.clone(),
+            infer_provider(&source, &model_id),
+            session_id.clone(),
+            timestamp,
+            tokens,
+            req.cost.unwrap_or(0.0),
+        ));
+    }
+
+    messages
+}
+
+/// Parse a Cline task's ui_messages.json
+pub fn parse_cline_file(path: &Path) -> Vec<UnifiedMessage> {
+    parse_task(Source::Cline, "cline", path)
+}
+
+/// Parse a Roo Code task's ui_messages.json
+pub fn parse_roocode_file(path: &Path) -> Vec<UnifiedMessage> {
+    parse_task(Source::RooCode, "roocode", path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn say(ts: i64, say: &str, text: &str) -> String {
+        format!(r#"{{"ts":{},"type":"say","say":"{}","text":{}}}"#, ts, say, serde_json::to_string(text).unwrap())
+    }
+
+    fn api_req(ts: i64, text: &str) -> String {
+        say(ts, "api_req_started", text)
+    }
+
+    fn write_task(dir: &Path, ui_messages: &[String], history: Option<&str>) -> std::path::PathBuf {
+        let task = dir.join("1750075190000");
+        fs::create_dir_all(&task).unwrap();
+        let path = task.join("ui_messages.json");
+        fs::write(&path, format!("[{}]", ui_messages.join(","))).unwrap();
+        if let Some(history) = history {
+            fs::write(task.join("api_conversation_history.json"), history).unwrap();
+        }
+        path
+    }
+
+    #[test]
+    fn test_parse_requests_with_history_models() {
+        let dir = tempfile::tempdir().unwrap();
+        let ui_messages = vec![
+            say(1750075190000, "text", "Refactor the parser"),
+            api_req(1750075191000, r#"{"request":"...","tokensIn":12,"tokensOut":340,"cacheWrites":9000,"cost":0.04}"#),
+            api_req(1750075200000, r#"{"tokensIn":4,"tokensOut":120,"cacheWrites":200,"cacheReads":9000,"cost":0.01}"#),
+            api_req(1750075210000, r#"{"request":"...","cancelReason":"user_cancelled"}"#),
+            say(1750075220000, "deleted_api_reqs", r#"{"tokensIn":7,"tokensOut":70,"cost":0.002}"#),
+        ];
+        let history = r#"[
+            {"role":"user","content":[{"type":"text","text":"Refactor the parser"}]},
+            {"role":"assistant","content":[],
+             "modelInfo":{"modelId":"claude-sonnet-4-20250514","providerId":"anthropic"}},
+            {"role":"user","content":[]},
+            {"role":"assistant","content":[],
+             "modelInfo":{"modelId":"anthropic/claude-opus-4","providerId":"openrouter"}}
+        ]"#;
+        let path = write_task(dir.path(), &ui_messages, Some(history));
+
+        let messages = parse_cline_file(&path);
+        assert_eq!(messages.len(), 3, "the cancelled request carries no usage");
+        assert_eq!(messages[0].source, Source::Cline);
+        assert_eq!(messages[0].session_id, "1750075190000");
+        assert_eq!(messages[0].model_id, "claude-sonnet-4-20250514");
+        assert_eq!(messages[0].provider_id, "anthropic");
+        assert_eq!((messages[0].tokens.input, messages[0].tokens.output), (12, 340));
+        assert_eq!(messages[0].tokens.cache_write, 9000);
+        assert!((messages[0].cost - 0.04).abs() < 1e-9);
+        assert_eq!(messages[1].model_id, "anthropic/claude-opus-4");
+        assert_eq!(messages[1].tokens.cache_read, 9000);
+        assert_eq!(messages[2].model_id, "anthropic/claude-opus-4", "the last known model carries forward");
+        assert_eq!(messages[2].timestamp, 1750075220000);
+    }
+
+    #[test]
+    fn test_deleted_requests_keep_models_aligned() {
+        let dir = tempfile::tempdir().unwrap();
+        let ui_messages = vec![
+            api_req(1750075191000, r#"{"tokensIn":10,"tokensOut":1}"#),
+            say(1750075195000, "deleted_api_reqs", r#"{"tokensIn":7,"tokensOut":70}"#),
+            api_req(1750075200000, r#"{"tokensIn":20,"tokensOut":2}"#),
+            api_req(1750075210000, r#"{"tokensIn":30,"tokensOut":3}"#),
+        ];
+        // The deleted requests' history went with them
+        let history = r#"[
+            {"ts":1750075192000,"role":"assistant","modelInfo":{"modelId":"claude-sonnet-4"}},
+            {"ts":1750075201000,"role":"assistant","modelInfo":{"modelId":"gpt-4.1"}},
+            {"ts":1750075212000,"role":"assistant","modelInfo":{"modelId":"o3"}}
+        ]"#;
+        let path = write_task(dir.path(), &ui_messages, Some(history));
+        let models: Vec<String> = parse_cline_file(&path).into_iter().map(|m| m.model_id).collect();
+        assert_eq!(models, ["claude-sonnet-4", "claude-sonnet-4", "gpt-4.1", "o3"]);
+
+        // Without timestamps, requests still pair with answers in order
+        let history = r#"[
+            {"role":"assistant","modelInfo":{"modelId":"claude-sonnet-4"}},
+            {"role":"assistant","modelInfo":{"modelId":"gpt-4.1"}},
+            {"role":"assistant","modelInfo":{"modelId":"o3"}}
+        ]"#;
+        let path = write_task(dir.path(), &ui_messages, Some(history));
+        let models: Vec<String> = parse_cline_file(&path).into_iter().map(|m| m.model_id).collect();
+        assert_eq!(models, ["claude-sonnet-4", "claude-sonnet-4", "gpt-4.1", "o3"]);
+    }
+
+    #[test]
+    fn test_request_models_by_timestamp() {
+        let history = vec![(Some(15), Some("a".to_string())), (Some(35), Some("b".to_string()))];
+        // The request at 20 was cancelled before an answer was saved
+        let models = request_models(history, &[Some(10), Some(20), Some(30), None]);
+        assert_eq!(models, [Some("a".to_string()), None, Some("b".to_string()), None]);
+    }
+
+    #[test]
+    fn test_model_recorded_on_message() {
+        let dir = tempfile::tempdir().unwrap();
+        let ui_messages = vec![
+            r#"{"ts":1750075191000,"type":"say","say":"api_req_started",
+                "modelInfo":{"modelId":"gpt-4.1","providerId":"openai"},
+                "text":"{\"tokensIn\":100,\"tokensOut\":10,\"cost\":0.001}"}"#
+                .to_string(),
+            api_req(1750075200000, r#"{"tokensIn":50,"tokensOut":5}"#),
+        ];
+        let path = write_task(dir.path(), &ui_messages, None);
+
+        let messages = parse_roocode_file(&path);
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].source, Source::RooCode);
+        assert_eq!(messages[0].model_id, "gpt-4.1");
+        assert_eq!(messages[0].provider_id, "openai");
+        assert_eq!(messages[1].model_id, "gpt-4.1");
+        assert_eq!(messages[1].cost, 0.0);
+    }
+
+    #[test]
+    fn test_unknown_model_without_history() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_task(dir.path(), &[api_req(1750075191000, r#"{"tokensIn":1,"tokensOut":2}"#)], None);
+        assert_eq!(parse_cline_file(&path)[0].model_id, "unknown");
+
+        let broken = dir.path().join("broken.json");
+        fs::write(&broken, "[{\"ts\":").unwrap();
+        assert!(parse_cline_file(&broken).is_empty());
+    }
+}
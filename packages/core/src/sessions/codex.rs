@@ -2,8 +2,13 @@
 //!
 //! Parses JSONL files from ~/.codex/sessions/
 //! Note: This parser has stateful logic to track model and delta calculations.
+//!
+//! Prompts and tool calls logged between token counts are attributed to the
+//! next one, as the prompt's length and a count of calls; the prompt text is
+//! dropped once counted.
 
-use super::UnifiedMessage;
+use super::lenient::{self, FieldSpec, Kind};
+use super::{Source, UnifiedMessage};
 use crate::TokenBreakdown;
 use serde::Deserialize;
 use std::io::{BufRead, BufReader};
@@ -25,6 +30,17 @@ pub struct CodexPayload {
     pub model: Option<String>,
     pub model_name: Option<String>,
     pub info: Option<CodexInfo>,
+    /// Working directory, on `session_meta` and `turn_context` entries
+    pub cwd: Option<String>,
+    /// Repository state, on `session_meta` entries
+    pub git: Option<CodexGit>,
+    /// Prompt text, on `user_message` events; only its length is used
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CodexGit {
+    pub branch: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -43,16 +59,44 @@ pub struct CodexTokenUsage {
     pub cache_read_input_tokens: Option<i64>,
 }
 
+/// Fields checked and repaired when an entry no longer matches the structs above
+const SCHEMA: &[FieldSpec] = &[
+    ("/type", Kind::Str),
+    ("/timestamp", Kind::Str),
+    ("/payload", Kind::Obj),
+    ("/payload/type", Kind::Str),
+    ("/payload/model", Kind::Str),
+    ("/payload/model_name", Kind::Str),
+    ("/payload/cwd", Kind::Str),
+    ("/payload/git", Kind::Obj),
+    ("/payload/git/branch", Kind::Str),
+    ("/payload/message", Kind::Str),
+    ("/payload/info", Kind::Obj),
+    ("/payload/info/model", Kind::Str),
+    ("/payload/info/model_name", Kind::Str),
+    ("/payload/info/last_token_usage", Kind::Obj),
+    ("/payload/info/last_token_usage/input_tokens", Kind::Int),
+    ("/payload/info/last_token_usage/output_tokens", Kind::Int),
+    ("/payload/info/last_token_usage/cached_input_tokens", Kind::Int),
+    ("/payload/info/last_token_usage/cache_read_input_tokens", Kind::Int),
+    ("/payload/info/total_token_usage", Kind::Obj),
+    ("/payload/info/total_token_usage/input_tokens", Kind::Int),
+    ("/payload/info/total_token_usage/output_tokens", Kind::Int),
+    ("/payload/info/total_token_usage/cached_input_tokens", Kind::Int),
+    ("/payload/info/total_token_usage/cache_read_input_tokens", Kind::Int),
+];
+
+/// `response_item` payload types that are a call to a tool
+const TOOL_CALL_TYPES: &[&str] = &["function_call", "custom_tool_call", "local_shell_call", "web_search_call"];
+
 /// Parse a Codex JSONL file with stateful tracking
 pub fn parse_codex_file(path: &Path) -> Vec<UnifiedMessage> {
-    let file = match std::fs::File::open(path) {
+    let file = match crate::archive::open(path) {
         Ok(f) => f,
         Err(_) => return Vec::new(),
     };
 
-    let session_id = path
-        .file_stem()
-        .and_then(|s| s.to_str())
+    let session_id = crate::archive::file_stem(path)
         .unwrap_or("unknown")
         .to_string();
 
@@ -61,7 +105,11 @@ pub fn parse_codex_file(path: &Path) -> Vec<UnifiedMessage> {
 
     // Stateful tracking
     let mut current_model: Option<String> = None;
+    let mut current_cwd: Option<String> = None;
+    let mut current_branch: Option<String> = None;
     let mut previous_totals: Option<(i64, i64, i64)> = None; // (input, output, cached)
+    let mut pending_prompt: Option<u32> = None;
+    let mut pending_tool_calls: u32 = 0;
 
     for line in reader.lines() {
         let line = match line {
@@ -74,10 +122,9 @@ pub fn parse_codex_file(path: &Path) -> Vec<UnifiedMessage> {
             continue;
         }
 
-        let mut bytes = trimmed.as_bytes().to_vec();
-        let entry: CodexEntry = match simd_json::from_slice(&mut bytes) {
-            Ok(e) => e,
-            Err(_) => continue,
+        let entry: CodexEntry = match lenient::from_slice("codex", trimmed.as_bytes(), SCHEMA) {
+            Some(e) => e,
+            None => continue,
         };
 
         let payload = match entry.payload {
@@ -85,6 +132,27 @@ pub fn parse_codex_file(path: &Path) -> Vec<UnifiedMessage> {
             None => continue,
         };
 
+        if payload.cwd.is_some() {
+            current_cwd = payload.cwd.clone();
+        }
+        if let Some(branch) = payload.git.as_ref().and_then(|git| git.branch.clone()) {
+            current_branch = Some(branch);
+        }
+
+        match (entry.entry_type.as_str(), payload.payload_type.as_deref()) {
+            ("event_msg", Some("user_message")) => {
+                if let Some(prompt) = &payload.message {
+                    pending_prompt = Some(prompt.chars().count().min(u32::MAX as usize) as u32);
+                }
+                continue;
+            }
+            ("response_item", Some(kind)) if TOOL_CALL_TYPES.contains(&kind) => {
+                pending_tool_calls += 1;
+                continue;
+            }
+            _ => {}
+        }
+
         // Extract model from turn_context
         if entry.entry_type == "turn_context" {
             current_model = extract_model(&payload);
@@ -168,15 +236,15 @@ pub fn parse_codex_file(path: &Path) -> Vec<UnifiedMessage> {
             continue;
         }
 
-        let timestamp = entry
+        let (timestamp, naive) = entry
             .timestamp
             .or(payload.payload_type.clone()) // fallback
-            .and_then(|ts| chrono::DateTime::parse_from_rfc3339(&ts).ok())
-            .map(|dt| dt.timestamp_millis())
-            .unwrap_or_else(|| chrono::Utc::now().timestamp_millis());
+            .as_deref()
+            .and_then(crate::clock::parse_timestamp)
+            .unwrap_or_else(|| (chrono::Utc::now().timestamp_millis(), false));
 
-        messages.push(UnifiedMessage::new(
-            "codex",
+        let mut message = UnifiedMessage::new(
+            Source::Codex,
             model,
             "openai",
             session_id.clone(),
@@ -187,9 +255,17 @@ pub fn parse_codex_file(path: &Path) -> Vec<UnifiedMessage> {
                 cache_read: cached,
                 cache_write: 0,
                 reasoning: 0,
+                training: 0,
             },
             0.0, // Cost calculated later
-        ));
+        )
+        .with_cwd(current_cwd.clone())
+        .with_git_branch(current_branch.clone())
+        .with_prompt_chars(pending_prompt.take())
+        .with_tool_call(pending_tool_calls > 0)
+        .with_naive_timestamp(naive);
+        message.tool_calls = std::mem::take(&mut pending_tool_calls);
+        messages.push(message);
     }
 
     messages
@@ -204,3 +280,29 @@ fn extract_model(payload: &CodexPayload) -> Option<String> {
         .or(payload.info.as_ref().and_then(|i| i.model_name.clone()))
         .filter(|m| !m.is_empty())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_prompt_and_tool_calls_attributed_to_next_token_count() {
+        let content = r#"{"type":"turn_context","timestamp":"2025-06-16T09:00:01.000Z","payload":{"model":"gpt-5-codex"}}
+{"type":"event_msg","timestamp":"2025-06-16T09:00:02.000Z","payload":{"type":"user_message","message":"Add a test"}}
+{"type":"response_item","timestamp":"2025-06-16T09:00:05.000Z","payload":{"type":"function_call","name":"shell","arguments":"{}"}}
+{"type":"response_item","timestamp":"2025-06-16T09:00:06.000Z","payload":{"type":"custom_tool_call","name":"apply_patch","input":"..."}}
+{"type":"event_msg","timestamp":"2025-06-16T09:00:10.000Z","payload":{"type":"token_count","info":{"last_token_usage":{"input_tokens":500,"output_tokens":30}}}}
+{"type":"event_msg","timestamp":"2025-06-16T09:00:20.000Z","payload":{"type":"token_count","info":{"last_token_usage":{"input_tokens":800,"output_tokens":40}}}}"#;
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(content.as_bytes()).unwrap();
+
+        let messages = parse_codex_file(file.path());
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].prompt_chars, Some(10));
+        assert_eq!(messages[0].tool_calls, 2);
+        assert!(messages[0].tool_call);
+        assert_eq!((messages[1].prompt_chars, messages[1].tool_calls), (None, 0));
+        assert_eq!(messages[1].files_touched, None);
+    }
+}
@@ -6,33 +6,11 @@
 //! CSV Format (actual from API):
 //! Date,Kind,Model,Max Mode,Input (w/ Cache Write),Input (w/o Cache Write),Cache Read,Output Tokens,Total Tokens,Cost
 
-use super::UnifiedMessage;
+use super::provider::infer_provider;
+use super::{Source, UnifiedMessage};
 use crate::TokenBreakdown;
 use std::path::Path;
 
-/// Provider inference from model name
-fn infer_provider(model: &str) -> &'static str {
-    let lower = model.to_lowercase();
-
-    if lower.contains("claude")
-        || lower.contains("sonnet")
-        || lower.contains("opus")
-        || lower.contains("haiku")
-    {
-        "anthropic"
-    } else if lower.contains("gpt") || lower.contains("o1") || lower.contains("o3") {
-        "openai"
-    } else if lower.contains("gemini") {
-        "google"
-    } else if lower.contains("deepseek") {
-        "deepseek"
-    } else if lower.contains("llama") || lower.contains("mixtral") {
-        "meta"
-    } else {
-        "cursor"
-    }
-}
-
 /// Parse a cost string like "$0.50" or "0.50" to f64
 /// Returns 0.0 for empty strings, NaN values, or invalid formats
 fn parse_cost(cost_str: &str) -> f64 {
@@ -53,7 +31,7 @@ fn parse_cost(cost_str: &str) -> f64 {
 /// - New: Date,Kind,Model,Max Mode,Input (w/ Cache Write),Input (w/o Cache Write),Cache Read,Output Tokens,Total Tokens,Cost
 /// - Old: Date,Model,Input (w/ Cache Write),Input (w/o Cache Write),Cache Read,Output Tokens,Total Tokens,Cost,Cost to you
 pub fn parse_cursor_file(path: &Path) -> Vec<UnifiedMessage> {
-    let content = match std::fs::read_to_string(path) {
+    let content = match crate::archive::read_to_string(path) {
         Ok(c) => c,
         Err(_) => return vec![],
     };
@@ -141,6 +119,8 @@ pub fn parse_cursor_file(path: &Path) -> Vec<UnifiedMessage> {
         if timestamp == 0 {
             continue;
         }
+        // Times without a "Z" are read as UTC; `timestamps.timezones` can say otherwise
+        let naive = date_str.contains('T') && !date_str.ends_with('Z');
 
         // Cache write = input_with_cache_write - input_without_cache_write
         let cache_write = (input_with_cache_write - input_without_cache_write).max(0);
@@ -148,9 +128,9 @@ pub fn parse_cursor_file(path: &Path) -> Vec<UnifiedMessage> {
         let input = input_without_cache_write;
 
         messages.push(UnifiedMessage::new(
-            "cursor",
+            Source::Cursor,
             model,
-            infer_provider(model),
+            infer_provider(&Source::Cursor, model),
             date_str.to_string(),
             timestamp,
             TokenBreakdown {
@@ -159,9 +139,11 @@ pub fn parse_cursor_file(path: &Path) -> Vec<UnifiedMessage> {
                 cache_read,
                 cache_write,
                 reasoning: 0,
+                training: 0,
             },
             cost,
-        ));
+        )
+        .with_naive_timestamp(naive));
     }
 
     messages
@@ -232,12 +214,12 @@ mod tests {
 
     #[test]
     fn test_infer_provider() {
-        assert_eq!(infer_provider("claude-3-sonnet"), "anthropic");
-        assert_eq!(infer_provider("gpt-4o"), "openai");
-        assert_eq!(infer_provider("gemini-pro"), "google");
-        assert_eq!(infer_provider("deepseek-coder"), "deepseek");
-        assert_eq!(infer_provider("llama-3"), "meta");
-        assert_eq!(infer_provider("unknown-model"), "cursor");
+        assert_eq!(infer_provider(&Source::Cursor, "claude-3-sonnet"), "anthropic");
+        assert_eq!(infer_provider(&Source::Cursor, "gpt-4o"), "openai");
+        assert_eq!(infer_provider(&Source::Cursor, "gemini-pro"), "google");
+        assert_eq!(infer_provider(&Source::Cursor, "deepseek-coder"), "deepseek");
+        assert_eq!(infer_provider(&Source::Cursor, "llama-3"), "meta");
+        assert_eq!(infer_provider(&Source::Cursor, "unknown-model"), "cursor");
     }
 
     #[test]
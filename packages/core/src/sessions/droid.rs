@@ -2,7 +2,9 @@
 //!
 //! Parses JSON files from ~/.factory/sessions/
 
-use super::UnifiedMessage;
+use super::lenient::{self, FieldSpec, Kind};
+use super::provider::infer_provider;
+use super::{Provider, Source, UnifiedMessage};
 use crate::TokenBreakdown;
 use serde::Deserialize;
 use std::io::{BufRead, BufReader};
@@ -86,34 +88,6 @@ fn normalize_model_name(model: &str) -> String {
     collapsed
 }
 
-fn get_provider_from_model(model: &str) -> &'static str {
-    let lower = model.to_lowercase();
-
-    if lower.contains("claude")
-        || lower.contains("anthropic")
-        || lower.contains("opus")
-        || lower.contains("sonnet")
-        || lower.contains("haiku")
-    {
-        return "anthropic";
-    }
-    if lower.contains("gpt")
-        || lower.contains("openai")
-        || lower.contains("o1")
-        || lower.contains("o3")
-    {
-        return "openai";
-    }
-    if lower.contains("gemini") || lower.contains("google") {
-        return "google";
-    }
-    if lower.contains("grok") {
-        return "xai";
-    }
-
-    "unknown"
-}
-
 /// Get default model name based on provider when model field is missing
 fn get_default_model_from_provider(provider: &str) -> String {
     match provider.to_lowercase().as_str() {
@@ -128,7 +102,7 @@ fn get_default_model_from_provider(provider: &str) -> String {
 /// Try to extract model name from JSONL file's system-reminder
 /// Looks for pattern: "Model: Claude Opus 4.5 Thinking [Anthropic]"
 fn extract_model_from_jsonl(jsonl_path: &Path) -> Option<String> {
-    let file = std::fs::File::open(jsonl_path).ok()?;
+    let file = crate::archive::open(jsonl_path).ok()?;
     let reader = BufReader::new(file);
 
     // Scan more lines for parity with TypeScript which reads entire file
@@ -153,17 +127,29 @@ fn extract_model_from_jsonl(jsonl_path: &Path) -> Option<String> {
     None
 }
 
+/// Fields checked and repaired when settings no longer match the structs above
+const SCHEMA: &[FieldSpec] = &[
+    ("/model", Kind::Str),
+    ("/providerLock", Kind::Str),
+    ("/providerLockTimestamp", Kind::Str),
+    ("/tokenUsage", Kind::Obj),
+    ("/tokenUsage/inputTokens", Kind::Int),
+    ("/tokenUsage/outputTokens", Kind::Int),
+    ("/tokenUsage/cacheCreationTokens", Kind::Int),
+    ("/tokenUsage/cacheReadTokens", Kind::Int),
+    ("/tokenUsage/thinkingTokens", Kind::Int),
+];
+
 /// Parse a Droid settings.json file
 pub fn parse_droid_file(path: &Path) -> Vec<UnifiedMessage> {
-    let data = match std::fs::read(path) {
+    let data = match crate::archive::read(path) {
         Ok(d) => d,
         Err(_) => return Vec::new(),
     };
 
-    let mut bytes = data;
-    let settings: DroidSettingsJson = match simd_json::from_slice(&mut bytes) {
-        Ok(s) => s,
-        Err(_) => return Vec::new(),
+    let settings: DroidSettingsJson = match lenient::from_slice("droid", &data, SCHEMA) {
+        Some(s) => s,
+        None => return Vec::new(),
     };
 
     // Skip if no token usage data
@@ -184,9 +170,7 @@ pub fn parse_droid_file(path: &Path) -> Vec<UnifiedMessage> {
     }
 
     // Extract session ID from filename (e.g., "uuid.settings.json" -> "uuid")
-    let session_id = path
-        .file_stem()
-        .and_then(|s| s.to_str())
+    let session_id = crate::archive::file_stem(path)
         .unwrap_or("unknown")
         .to_string()
         .replace(".settings", "");
@@ -195,7 +179,8 @@ pub fn parse_droid_file(path: &Path) -> Vec<UnifiedMessage> {
     let provider = settings
         .provider_lock
         .clone()
-        .unwrap_or_else(|| get_provider_from_model(settings.model.as_deref().unwrap_or("")).to_string());
+        .map(Provider::from)
+        .unwrap_or_else(|| infer_provider(&Source::Droid, settings.model.as_deref().unwrap_or("")));
 
     let model = if let Some(m) = settings.model {
         normalize_model_name(&m)
@@ -207,9 +192,9 @@ pub fn parse_droid_file(path: &Path) -> Vec<UnifiedMessage> {
             .map(std::path::PathBuf::from);
 
         if let Some(ref jsonl) = jsonl_path {
-            extract_model_from_jsonl(jsonl).unwrap_or_else(|| get_default_model_from_provider(&provider))
+            extract_model_from_jsonl(jsonl).unwrap_or_else(|| get_default_model_from_provider(provider.as_str()))
         } else {
-            get_default_model_from_provider(&provider)
+            get_default_model_from_provider(provider.as_str())
         }
     };
 
@@ -235,7 +220,7 @@ pub fn parse_droid_file(path: &Path) -> Vec<UnifiedMessage> {
     }
 
     vec![UnifiedMessage::new(
-        "droid",
+        Source::Droid,
         model,
         provider,
         session_id,
@@ -246,6 +231,7 @@ pub fn parse_droid_file(path: &Path) -> Vec<UnifiedMessage> {
             cache_read: usage.cache_read_tokens.unwrap_or(0),
             cache_write: usage.cache_creation_tokens.unwrap_or(0),
             reasoning: usage.thinking_tokens.unwrap_or(0),
+            training: 0,
         },
         0.0, // Cost calculated later
     )]
@@ -280,17 +266,17 @@ mod tests {
     }
 
     #[test]
-    fn test_get_provider_from_model() {
-        assert_eq!(get_provider_from_model("claude-3-sonnet"), "anthropic");
-        assert_eq!(get_provider_from_model("opus-4"), "anthropic");
-        assert_eq!(get_provider_from_model("sonnet-4"), "anthropic");
-        assert_eq!(get_provider_from_model("haiku-3"), "anthropic");
-        assert_eq!(get_provider_from_model("gpt-4o"), "openai");
-        assert_eq!(get_provider_from_model("o1-preview"), "openai");
-        assert_eq!(get_provider_from_model("o3-mini"), "openai");
-        assert_eq!(get_provider_from_model("gemini-pro"), "google");
-        assert_eq!(get_provider_from_model("grok-2"), "xai");
-        assert_eq!(get_provider_from_model("unknown-model"), "unknown");
+    fn test_infer_provider() {
+        assert_eq!(infer_provider(&Source::Droid, "claude-3-sonnet"), "anthropic");
+        assert_eq!(infer_provider(&Source::Droid, "opus-4"), "anthropic");
+        assert_eq!(infer_provider(&Source::Droid, "sonnet-4"), "anthropic");
+        assert_eq!(infer_provider(&Source::Droid, "haiku-3"), "anthropic");
+        assert_eq!(infer_provider(&Source::Droid, "gpt-4o"), "openai");
+        assert_eq!(infer_provider(&Source::Droid, "o1-preview"), "openai");
+        assert_eq!(infer_provider(&Source::Droid, "o3-mini"), "openai");
+        assert_eq!(infer_provider(&Source::Droid, "gemini-pro"), "google");
+        assert_eq!(infer_provider(&Source::Droid, "grok-2"), "xai");
+        assert_eq!(infer_provider(&Source::Droid, "unknown-model"), "unknown");
     }
 
     #[test]
@@ -318,7 +304,7 @@ mod tests {
         }"#;
 
         let mut bytes = json.as_bytes().to_vec();
-        let settings: DroidSettingsJson = simd_json::from_slice(&mut bytes).unwrap();
+        let settings: DroidSettingsJson = crate::parser::from_slice(&mut bytes).unwrap();
 
         assert_eq!(
             settings.model,
@@ -2,7 +2,8 @@
 //!
 //! Parses JSON session files from ~/.gemini/tmp/*/chats/session-*.json
 
-use super::UnifiedMessage;
+use super::lenient::{self, FieldSpec, Kind};
+use super::{Source, UnifiedMessage};
 use crate::TokenBreakdown;
 use serde::Deserialize;
 use std::path::Path;
@@ -13,11 +14,11 @@ use std::path::Path;
 pub struct GeminiSession {
     #[serde(rename = "sessionId")]
     pub session_id: String,
-    #[serde(rename = "projectHash")]
+    #[serde(default, rename = "projectHash")]
     pub project_hash: String,
-    #[serde(rename = "startTime")]
+    #[serde(default, rename = "startTime")]
     pub start_time: String,
-    #[serde(rename = "lastUpdated")]
+    #[serde(default, rename = "lastUpdated")]
     pub last_updated: String,
     pub messages: Vec<GeminiMessage>,
 }
@@ -26,6 +27,7 @@ pub struct GeminiSession {
 #[derive(Debug, Deserialize)]
 #[allow(dead_code)]
 pub struct GeminiMessage {
+    #[serde(default)]
     pub id: String,
     pub timestamp: Option<String>,
     #[serde(rename = "type")]
@@ -47,17 +49,37 @@ pub struct GeminiTokens {
     pub total: Option<i64>,
 }
 
+/// Fields checked and repaired when a session no longer matches the structs above
+const SCHEMA: &[FieldSpec] = &[
+    ("/sessionId", Kind::Str),
+    ("/projectHash", Kind::Str),
+    ("/startTime", Kind::Str),
+    ("/lastUpdated", Kind::Str),
+    ("/messages", Kind::Arr),
+    ("/messages/*/id", Kind::Str),
+    ("/messages/*/timestamp", Kind::Str),
+    ("/messages/*/type", Kind::Str),
+    ("/messages/*/content", Kind::Str),
+    ("/messages/*/model", Kind::Str),
+    ("/messages/*/tokens", Kind::Obj),
+    ("/messages/*/tokens/input", Kind::Int),
+    ("/messages/*/tokens/output", Kind::Int),
+    ("/messages/*/tokens/cached", Kind::Int),
+    ("/messages/*/tokens/thoughts", Kind::Int),
+    ("/messages/*/tokens/tool", Kind::Int),
+    ("/messages/*/tokens/total", Kind::Int),
+];
+
 /// Parse a Gemini session file
 pub fn parse_gemini_file(path: &Path) -> Vec<UnifiedMessage> {
-    let data = match std::fs::read(path) {
+    let data = match crate::archive::read(path) {
         Ok(d) => d,
         Err(_) => return Vec::new(),
     };
 
-    let mut bytes = data;
-    let session: GeminiSession = match simd_json::from_slice(&mut bytes) {
-        Ok(s) => s,
-        Err(_) => return Vec::new(),
+    let session: GeminiSession = match lenient::from_slice("gemini", &data, SCHEMA) {
+        Some(s) => s,
+        None => return Vec::new(),
     };
 
     let mut messages = Vec::new();
@@ -79,18 +101,18 @@ pub fn parse_gemini_file(path: &Path) -> Vec<UnifiedMessage> {
             None => continue,
         };
 
-        let timestamp = msg
+        let (timestamp, naive) = msg
             .timestamp
-            .and_then(|ts| chrono::DateTime::parse_from_rfc3339(&ts).ok())
-            .map(|dt| dt.timestamp_millis())
-            .unwrap_or(0);
+            .as_deref()
+            .and_then(crate::clock::parse_timestamp)
+            .unwrap_or((0, false));
 
         if timestamp == 0 {
             continue;
         }
 
         messages.push(UnifiedMessage::new(
-            "gemini",
+            Source::Gemini,
             model,
             "google",
             session_id.clone(),
@@ -101,9 +123,11 @@ pub fn parse_gemini_file(path: &Path) -> Vec<UnifiedMessage> {
                 cache_read: tokens.cached.unwrap_or(0),
                 cache_write: 0,
                 reasoning: tokens.thoughts.unwrap_or(0),
+                training: 0,
             },
             0.0, // Cost calculated later
-        ));
+        )
+        .with_naive_timestamp(naive));
     }
 
     messages
@@ -146,7 +170,7 @@ mod tests {
         }"#;
 
         let mut bytes = json.as_bytes().to_vec();
-        let session: GeminiSession = simd_json::from_slice(&mut bytes).unwrap();
+        let session: GeminiSession = crate::parser::from_slice(&mut bytes).unwrap();
 
         assert_eq!(session.messages.len(), 2);
         assert_eq!(
@@ -154,4 +178,29 @@ mod tests {
             Some("gemini-2.0-flash".to_string())
         );
     }
+
+    #[test]
+    fn test_parse_gemini_file_with_schema_drift() {
+        // Newer CLI versions store content as a list of parts
+        let json = r#"{
+            "sessionId": "ses_drift",
+            "messages": [
+                {
+                    "id": "msg_1",
+                    "timestamp": "2025-06-15T12:01:00Z",
+                    "type": "gemini",
+                    "content": [{"text": "Hi there!"}],
+                    "model": "gemini-2.5-pro",
+                    "tokens": {"input": "10", "output": 20}
+                }
+            ]
+        }"#;
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        std::io::Write::write_all(&mut file, json.as_bytes()).unwrap();
+
+        let messages = parse_gemini_file(file.path());
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].tokens.input, 10);
+        assert_eq!(messages[0].tokens.output, 20);
+    }
 }
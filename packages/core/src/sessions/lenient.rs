@@ -0,0 +1,211 @@
+//! Fail-soft JSON parsing for session files
+//!
+//! Parsers deserialize into typed structs with simd-json. When a tool changes
+//! its schema (a count becomes a string, a text field becomes an array) that
+//! strict parse fails for the whole line or file. Instead of dropping it, the
+//! document is re-read as a generic value, every field a parser relies on is
+//! checked against its expected kind and coerced or nulled, and the typed
+//! parse is retried. Each repair is recorded as schema drift naming the field.
+//!
+//! The fast path is unchanged: the repair only runs after a strict failure.
+
+use crate::diagnostics::record_drift;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+/// JSON kind a parser expects at a field
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Str,
+    /// Integer; floats are truncated and numeric strings parsed
+    Int,
+    /// Any number; numeric strings are parsed
+    Num,
+    Obj,
+    Arr,
+    /// Boolean; "true" and "false" strings are parsed
+    Bool,
+}
+
+impl Kind {
+    fn name(self) -> &'static str {
+        match self {
+            Kind::Str => "string",
+            Kind::Int => "integer",
+            Kind::Num => "number",
+            Kind::Obj => "object",
+            Kind::Arr => "array",
+            Kind::Bool => "boolean",
+        }
+    }
+}
+
+/// Field path and expected kind. Paths are `/`-separated object keys; a `*`
+/// segment matches every element of an array.
+pub type FieldSpec = (&'static str, Kind);
+
+/// Parse `input` as `T`, repairing drifted fields listed in `schema` if the
+/// strict parse fails. Returns `None` for malformed JSON or documents that
+/// still don't fit `T` after repair.
+pub fn from_slice<T: DeserializeOwned>(
+    source: &'static str,
+    input: &[u8],
+    schema: &[FieldSpec],
+) -> Option<T> {
+    let mut bytes = input.to_vec();
+    if let Ok(parsed) = crate::parser::from_slice(&mut bytes) {
+        return Some(parsed);
+    }
+
+    // Not valid JSON at all (e.g. a line still being written): not drift
+    let mut value: Value = serde_json::from_slice(input).ok()?;
+    for (path, kind) in schema {
+        let segments: Vec<&str> = path.trim_start_matches('/').split('/').collect();
+        repair(source, &mut value, &segments, path.trim_start_matches('/'), *kind);
+    }
+
+    match serde_json::from_value(value) {
+        Ok(parsed) => Some(parsed),
+        Err(e) => {
+            record_drift(source, "(document)", "known schema", &e.to_string());
+            None
+        }
+    }
+}
+
+fn repair(source: &'static str, value: &mut Value, segments: &[&str], path: &str, kind: Kind) {
+    let Some((first, rest)) = segments.split_first() else {
+        return check(source, value, path, kind);
+    };
+
+    if *first == "*" {
+        if let Value::Array(items) = value {
+            for item in items {
+                repair(source, item, rest, path, kind);
+            }
+        }
+    } else if let Some(child) = value.as_object_mut().and_then(|o| o.get_mut(*first)) {
+        repair(source, child, rest, path, kind);
+    }
+}
+
+fn check(source: &'static str, value: &mut Value, path: &str, kind: Kind) {
+    let coerced = match (kind, &*value) {
+        (_, Value::Null) => return,
+        (Kind::Str, Value::String(_))
+        | (Kind::Num, Value::Number(_))
+        | (Kind::Obj, Value::Object(_))
+        | (Kind::Arr, Value::Array(_))
+        | (Kind::Bool, Value::Bool(_)) => return,
+        (Kind::Int, Value::Number(n)) if n.is_i64() || n.is_u64() => return,
+
+        (Kind::Int, Value::Number(n)) => n.as_f64().map(|f| Value::from(f as i64)),
+        (Kind::Int, Value::String(s)) => parse_number(s).map(|f| Value::from(f as i64)),
+        (Kind::Num, Value::String(s)) => parse_number(s).map(Value::from),
+        (Kind::Str, Value::Number(n)) => Some(Value::String(n.to_string())),
+        (Kind::Str, Value::Bool(b)) => Some(Value::String(b.to_string())),
+        (Kind::Bool, Value::String(s)) => s.trim().parse::<bool>().ok().map(Value::Bool),
+        _ => None,
+    };
+
+    record_drift(source, path, kind.name(), type_name(value));
+    *value = coerced.unwrap_or(Value::Null);
+}
+
+fn parse_number(s: &str) -> Option<f64> {
+    s.trim().parse::<f64>().ok().filter(|f| f.is_finite())
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(n) if n.is_f64() => "float",
+        Value::Number(_) => "integer",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostics::take_drift_for;
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Usage {
+        input: Option<i64>,
+        cost: Option<f64>,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Doc {
+        model: Option<String>,
+        usage: Option<Usage>,
+        items: Option<Vec<Usage>>,
+    }
+
+    const SCHEMA: &[FieldSpec] = &[
+        ("/model", Kind::Str),
+        ("/usage", Kind::Obj),
+        ("/usage/input", Kind::Int),
+        ("/usage/cost", Kind::Num),
+        ("/items", Kind::Arr),
+        ("/items/*/input", Kind::Int),
+    ];
+
+    #[test]
+    fn test_strict_parse_records_nothing() {
+        let doc: Doc = from_slice("lenient-strict", br#"{"model": "m", "extra": [1]}"#, SCHEMA).unwrap();
+        assert_eq!(doc.model.as_deref(), Some("m"));
+        assert!(take_drift_for("lenient-strict").is_empty());
+    }
+
+    #[test]
+    fn test_coerces_drifted_fields() {
+        let input = br#"{"model": 42, "usage": {"input": "120", "cost": "0.5"}, "items": [{"input": 3.9}, {"input": 1}]}"#;
+        let doc: Doc = from_slice("lenient-coerce", input, SCHEMA).unwrap();
+
+        assert_eq!(doc.model.as_deref(), Some("42"));
+        assert_eq!(doc.usage, Some(Usage { input: Some(120), cost: Some(0.5) }));
+        assert_eq!(doc.items.unwrap()[0].input, Some(3));
+
+        let fields: Vec<String> = take_drift_for("lenient-coerce").into_iter().map(|d| d.field).collect();
+        assert_eq!(fields, vec!["items/*/input", "model", "usage/cost", "usage/input"]);
+    }
+
+    #[test]
+    fn test_nulls_unrepairable_fields() {
+        let input = br#"{"model": ["a", "b"], "usage": {"input": {"n": 1}}}"#;
+        let doc: Doc = from_slice("lenient-null", input, SCHEMA).unwrap();
+
+        assert_eq!(doc.model, None);
+        assert_eq!(doc.usage, Some(Usage { input: None, cost: None }));
+
+        let drift = take_drift_for("lenient-null");
+        assert_eq!(drift.len(), 2);
+        assert_eq!(drift[0].field, "model");
+        assert_eq!(drift[0].found, "array");
+        assert_eq!(drift[1].expected, "integer");
+    }
+
+    #[test]
+    fn test_malformed_json_not_drift() {
+        assert!(from_slice::<Doc>("lenient-malformed", br#"{"model": "#, SCHEMA).is_none());
+        assert!(take_drift_for("lenient-malformed").is_empty());
+    }
+
+    #[test]
+    fn test_unfixable_document_reported() {
+        #[derive(Debug, Deserialize)]
+        #[allow(dead_code)]
+        struct Required {
+            id: String,
+        }
+        assert!(from_slice::<Required>("lenient-doc", br#"{"other": 1}"#, &[]).is_none());
+        let drift = take_drift_for("lenient-doc");
+        assert_eq!(drift[0].field, "(document)");
+    }
+}
@@ -4,19 +4,29 @@
 
 pub mod amp;
 pub mod claudecode;
+pub mod cline;
 pub mod codex;
 pub mod cursor;
 pub mod droid;
 pub mod gemini;
+pub mod lenient;
 pub mod opencode;
+pub mod provider;
+pub mod recorded;
+pub mod source;
+
+#[cfg(test)]
+mod snapshot;
 
 use crate::TokenBreakdown;
+pub use provider::Provider;
+pub use source::Source;
 
 #[derive(Debug, Clone)]
 pub struct UnifiedMessage {
-    pub source: String,
+    pub source: Source,
     pub model_id: String,
-    pub provider_id: String,
+    pub provider_id: Provider,
     pub session_id: String,
     pub timestamp: i64,
     pub date: String,
@@ -24,6 +34,30 @@ pub struct UnifiedMessage {
     pub cost: f64,
     pub agent: Option<String>,
     pub dedup_key: Option<String>,
+    /// Timestamp was synthesized rather than read from the session file
+    pub estimated_timestamp: bool,
+    /// Timestamp had no UTC offset in the file and was read as UTC; cleared
+    /// once [`crate::clock`] reads it in the source's timezone
+    pub naive_timestamp: bool,
+    /// Working directory the session ran in, for sources that record it
+    pub cwd: Option<String>,
+    /// Git branch checked out in `cwd`, for sources that record it
+    pub git_branch: Option<String>,
+    /// Written by a sub-agent (e.g. Claude Code's Task tool) rather than the
+    /// main conversation
+    pub sidechain: bool,
+    /// The response ended by asking to run a tool
+    pub tool_call: bool,
+    /// Characters in the user prompt this response is the first answer to.
+    /// Only the length is kept; the prompt itself is discarded while parsing.
+    pub prompt_chars: Option<u32>,
+    /// Tool calls in the response, for sources that log them
+    pub tool_calls: u32,
+    /// Distinct files the response's tool calls name, for sources that log them
+    pub files_touched: Option<u32>,
+    /// `cost` is what the provider billed (an imported OpenRouter generation),
+    /// kept instead of our pricing
+    pub billed: bool,
 }
 
 pub fn normalize_agent_name(agent: &str) -> String {
@@ -45,9 +79,9 @@ pub fn normalize_agent_name(agent: &str) -> String {
 
 impl UnifiedMessage {
     pub fn new(
-        source: impl Into<String>,
+        source: impl Into<Source>,
         model_id: impl Into<String>,
-        provider_id: impl Into<String>,
+        provider_id: impl Into<Provider>,
         session_id: impl Into<String>,
         timestamp: i64,
         tokens: TokenBreakdown,
@@ -56,10 +90,11 @@ impl UnifiedMessage {
         Self::new_full(source, model_id, provider_id, session_id, timestamp, tokens, cost, None, None)
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn new_with_agent(
-        source: impl Into<String>,
+        source: impl Into<Source>,
         model_id: impl Into<String>,
-        provider_id: impl Into<String>,
+        provider_id: impl Into<Provider>,
         session_id: impl Into<String>,
         timestamp: i64,
         tokens: TokenBreakdown,
@@ -69,10 +104,11 @@ impl UnifiedMessage {
         Self::new_full(source, model_id, provider_id, session_id, timestamp, tokens, cost, agent, None)
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn new_with_dedup(
-        source: impl Into<String>,
+        source: impl Into<Source>,
         model_id: impl Into<String>,
-        provider_id: impl Into<String>,
+        provider_id: impl Into<Provider>,
         session_id: impl Into<String>,
         timestamp: i64,
         tokens: TokenBreakdown,
@@ -82,10 +118,11 @@ impl UnifiedMessage {
         Self::new_full(source, model_id, provider_id, session_id, timestamp, tokens, cost, None, dedup_key)
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn new_full(
-        source: impl Into<String>,
+        source: impl Into<Source>,
         model_id: impl Into<String>,
-        provider_id: impl Into<String>,
+        provider_id: impl Into<Provider>,
         session_id: impl Into<String>,
         timestamp: i64,
         tokens: TokenBreakdown,
@@ -105,8 +142,61 @@ impl UnifiedMessage {
             cost,
             agent,
             dedup_key,
+            estimated_timestamp: false,
+            naive_timestamp: false,
+            cwd: None,
+            git_branch: None,
+            sidechain: false,
+            tool_call: false,
+            prompt_chars: None,
+            tool_calls: 0,
+            files_touched: None,
+            billed: false,
         }
     }
+
+    /// Mark the timestamp as synthesized
+    pub fn with_estimated_timestamp(mut self) -> Self {
+        self.estimated_timestamp = true;
+        self
+    }
+
+    pub fn with_naive_timestamp(mut self, naive: bool) -> Self {
+        self.naive_timestamp = naive;
+        self
+    }
+
+    /// Move the message to `timestamp`, keeping `date` in step
+    pub fn set_timestamp(&mut self, timestamp: i64) {
+        self.timestamp = timestamp;
+        self.date = timestamp_to_date(timestamp);
+    }
+
+    pub fn with_cwd(mut self, cwd: Option<String>) -> Self {
+        self.cwd = cwd.filter(|c| !c.is_empty());
+        self
+    }
+
+    pub fn with_sidechain(mut self, sidechain: bool) -> Self {
+        self.sidechain = sidechain;
+        self
+    }
+
+    pub fn with_tool_call(mut self, tool_call: bool) -> Self {
+        self.tool_call = tool_call;
+        self
+    }
+
+    pub fn with_prompt_chars(mut self, prompt_chars: Option<u32>) -> Self {
+        self.prompt_chars = prompt_chars;
+        self
+    }
+
+    /// A detached checkout is recorded as "HEAD", which isn't a branch
+    pub fn with_git_branch(mut self, branch: Option<String>) -> Self {
+        self.git_branch = branch.filter(|b| !b.is_empty() && b != "HEAD");
+        self
+    }
 }
 
 /// Convert Unix milliseconds timestamp to YYYY-MM-DD date string
@@ -156,6 +246,7 @@ mod tests {
             cache_read: 0,
             cache_write: 0,
             reasoning: 0,
+            training: 0,
         };
 
         let msg = UnifiedMessage::new(
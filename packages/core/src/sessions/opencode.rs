@@ -2,7 +2,8 @@
 //!
 //! Parses individual JSON files from ~/.local/share/opencode/storage/message/
 
-use super::{normalize_agent_name, UnifiedMessage};
+use super::lenient::{self, FieldSpec, Kind};
+use super::{normalize_agent_name, Source, UnifiedMessage};
 use crate::TokenBreakdown;
 use serde::Deserialize;
 use std::path::Path;
@@ -24,6 +25,8 @@ pub struct OpenCodeMessage {
     pub time: OpenCodeTime,
     pub agent: Option<String>,
     pub mode: Option<String>,
+    /// Why generation stopped; "tool-calls" when the model asked to run tools
+    pub finish: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -47,11 +50,32 @@ pub struct OpenCodeTime {
     pub completed: Option<f64>,
 }
 
-pub fn parse_opencode_file(path: &Path) -> Option<UnifiedMessage> {
-    let data = std::fs::read(path).ok()?;
-    let mut bytes = data;
+/// Fields checked and repaired when a message no longer matches the structs above
+const SCHEMA: &[FieldSpec] = &[
+    ("/id", Kind::Str),
+    ("/sessionID", Kind::Str),
+    ("/role", Kind::Str),
+    ("/modelID", Kind::Str),
+    ("/providerID", Kind::Str),
+    ("/cost", Kind::Num),
+    ("/tokens", Kind::Obj),
+    ("/tokens/input", Kind::Int),
+    ("/tokens/output", Kind::Int),
+    ("/tokens/reasoning", Kind::Int),
+    ("/tokens/cache", Kind::Obj),
+    ("/tokens/cache/read", Kind::Int),
+    ("/tokens/cache/write", Kind::Int),
+    ("/time", Kind::Obj),
+    ("/time/created", Kind::Num),
+    ("/time/completed", Kind::Num),
+    ("/agent", Kind::Str),
+    ("/mode", Kind::Str),
+    ("/finish", Kind::Str),
+];
 
-    let msg: OpenCodeMessage = simd_json::from_slice(&mut bytes).ok()?;
+pub fn parse_opencode_file(path: &Path) -> Option<UnifiedMessage> {
+    let data = crate::archive::read(path).ok()?;
+    let msg: OpenCodeMessage = lenient::from_slice("opencode", &data, SCHEMA)?;
 
     if msg.role != "assistant" {
         return None;
@@ -62,8 +86,10 @@ pub fn parse_opencode_file(path: &Path) -> Option<UnifiedMessage> {
     let agent_or_mode = msg.mode.or(msg.agent);
     let agent = agent_or_mode.map(|a| normalize_agent_name(&a));
 
+    let tool_call = msg.finish.as_deref() == Some("tool-calls");
+
     Some(UnifiedMessage::new_with_agent(
-        "opencode",
+        Source::OpenCode,
         model_id,
         msg.provider_id.unwrap_or_else(|| "unknown".to_string()),
         msg.session_id.clone(),
@@ -74,10 +100,12 @@ pub fn parse_opencode_file(path: &Path) -> Option<UnifiedMessage> {
             cache_read: tokens.cache.read,
             cache_write: tokens.cache.write,
             reasoning: tokens.reasoning.unwrap_or(0),
+            training: 0,
         },
         msg.cost.unwrap_or(0.0),
         agent,
-    ))
+    )
+    .with_tool_call(tool_call))
 }
 
 #[cfg(test)]
@@ -103,7 +131,7 @@ mod tests {
         }"#;
 
         let mut bytes = json.as_bytes().to_vec();
-        let msg: OpenCodeMessage = simd_json::from_slice(&mut bytes).unwrap();
+        let msg: OpenCodeMessage = crate::parser::from_slice(&mut bytes).unwrap();
 
         assert_eq!(msg.model_id, Some("claude-sonnet-4".to_string()));
         assert_eq!(msg.tokens.unwrap().input, 1000);
@@ -130,8 +158,42 @@ mod tests {
         }"#;
 
         let mut bytes = json.as_bytes().to_vec();
-        let msg: OpenCodeMessage = simd_json::from_slice(&mut bytes).unwrap();
+        let msg: OpenCodeMessage = crate::parser::from_slice(&mut bytes).unwrap();
 
         assert_eq!(msg.agent, Some("OmO".to_string()));
     }
+
+    #[test]
+    fn test_cache_tokens_map_to_breakdown() {
+        let json = r#"{
+            "id": "msg_123",
+            "sessionID": "ses_456",
+            "role": "assistant",
+            "modelID": "claude-sonnet-4",
+            "providerID": "anthropic",
+            "cost": 0.05,
+            "tokens": {
+                "input": 1000,
+                "output": 500,
+                "cache": { "read": 200, "write": 50 }
+            },
+            "time": { "created": 1700000000000.5, "completed": 1700000004000.0 },
+            "finish": "tool-calls"
+        }"#;
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        std::io::Write::write_all(&mut file, json.as_bytes()).unwrap();
+
+        let msg = parse_opencode_file(file.path()).unwrap();
+        assert_eq!(msg.session_id, "ses_456");
+        assert_eq!(msg.timestamp, 1_700_000_000_000);
+        assert_eq!((msg.tokens.input, msg.tokens.output), (1000, 500));
+        assert_eq!((msg.tokens.cache_read, msg.tokens.cache_write), (200, 50));
+        assert_eq!(msg.tokens.reasoning, 0, "reasoning is optional");
+        assert!(msg.tool_call);
+
+        let user = json.replace("\"assistant\"", "\"user\"");
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        std::io::Write::write_all(&mut file, user.as_bytes()).unwrap();
+        assert!(parse_opencode_file(file.path()).is_none(), "only assistant messages carry usage");
+    }
 }
@@ -0,0 +1,315 @@
+//! Provider inference from model names
+//!
+//! Sources that don't record a provider (Amp, Cursor, Droid without a
+//! `providerLock`, Cline and Roo Code, which only name the API they called)
+//! infer it from the model name. The mapping is shared here instead of living
+//! in each parser, and can be extended through
+//! `providerInference` in `~/.config/tokscale/settings.json`:
+//!
+//! ```json
+//! {
+//!   "providerInference": {
+//!     "rules": [{ "patterns": ["kimi"], "provider": "moonshotai" }],
+//!     "sources": { "amp": { "default": "openai" } }
+//!   }
+//! }
+//! ```
+//!
+//! Resolution order: per-source rules, global user rules, built-in rules, then
+//! the per-source default (user, then built-in), then "unknown".
+
+use super::source::Source;
+use crate::config::Settings;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, RwLock};
+
+/// Model provider. Providers outside the known set (including user-defined
+/// ones from `providerInference`) are kept verbatim in `Other`, so strings
+/// round-trip exactly.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Provider {
+    Anthropic,
+    OpenAI,
+    Google,
+    XAi,
+    DeepSeek,
+    Meta,
+    Cursor,
+    Unknown,
+    Other(String),
+}
+
+impl Provider {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Provider::Anthropic => "anthropic",
+            Provider::OpenAI => "openai",
+            Provider::Google => "google",
+            Provider::XAi => "xai",
+            Provider::DeepSeek => "deepseek",
+            Provider::Meta => "meta",
+            Provider::Cursor => "cursor",
+            Provider::Unknown => UNKNOWN_PROVIDER,
+            Provider::Other(name) => name,
+        }
+    }
+}
+
+impl From<&str> for Provider {
+    fn from(name: &str) -> Self {
+        match name {
+            "anthropic" => Provider::Anthropic,
+            "openai" => Provider::OpenAI,
+            "google" => Provider::Google,
+            "xai" => Provider::XAi,
+            "deepseek" => Provider::DeepSeek,
+            "meta" => Provider::Meta,
+            "cursor" => Provider::Cursor,
+            UNKNOWN_PROVIDER => Provider::Unknown,
+            other => Provider::Other(other.to_string()),
+        }
+    }
+}
+
+impl From<String> for Provider {
+    fn from(name: String) -> Self {
+        match Provider::from(name.as_str()) {
+            Provider::Other(_) => Provider::Other(name),
+            known => known,
+        }
+    }
+}
+
+impl fmt::Display for Provider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl PartialEq<str> for Provider {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for Provider {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+/// Built-in model patterns, tried in order. Patterns are lowercase substrings.
+const BUILTIN_RULES: &[(&[&str], &str)] = &[
+    (&["claude", "anthropic", "opus", "sonnet", "haiku"], "anthropic"),
+    (&["gpt", "openai", "o1", "o3"], "openai"),
+    (&["gemini", "google"], "google"),
+    (&["grok"], "xai"),
+    (&["deepseek"], "deepseek"),
+    (&["llama", "mixtral"], "meta"),
+];
+
+/// Provider assumed when no pattern matches, per source
+const BUILTIN_DEFAULTS: &[(&str, &str)] = &[("amp", "anthropic"), ("cursor", "cursor")];
+
+const UNKNOWN_PROVIDER: &str = "unknown";
+
+/// Inference rules and the settings they were built from, rebuilt on reload
+static INFERENCE: Lazy<RwLock<(Arc<Settings>, Arc<ProviderInference>)>> = Lazy::new(|| {
+    let settings = crate::config::settings();
+    let inference = Arc::new(ProviderInference::new(&settings.provider_inference));
+    RwLock::new((settings, inference))
+});
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProviderRule {
+    /// Case-insensitive substrings; any one matching selects `provider`
+    pub patterns: Vec<String>,
+    pub provider: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SourceProviderSettings {
+    pub rules: Vec<ProviderRule>,
+    pub default: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProviderSettings {
+    /// Rules applied to every source, before the built-in ones
+    pub rules: Vec<ProviderRule>,
+    /// Per-source rules and default provider, keyed by source name
+    pub sources: HashMap<String, SourceProviderSettings>,
+}
+
+pub struct ProviderInference {
+    global: Vec<ProviderRule>,
+    sources: HashMap<String, SourceProviderSettings>,
+}
+
+impl ProviderInference {
+    pub fn new(settings: &ProviderSettings) -> Self {
+        let lower_rules = |rules: &[ProviderRule]| -> Vec<ProviderRule> {
+            rules
+                .iter()
+                .filter(|r| !r.provider.is_empty())
+                .map(|r| ProviderRule {
+                    patterns: r.patterns.iter().map(|p| p.to_lowercase()).collect(),
+                    provider: r.provider.clone(),
+                })
+                .collect()
+        };
+
+        Self {
+            global: lower_rules(&settings.rules),
+            sources: settings
+                .sources
+                .iter()
+                .map(|(source, s)| {
+                    (
+                        source.to_lowercase(),
+                        SourceProviderSettings {
+                            rules: lower_rules(&s.rules),
+                            default: s.default.clone().filter(|d| !d.is_empty()),
+                        },
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    pub fn infer(&self, source: &str, model: &str) -> String {
+        let lower = model.to_lowercase();
+        let source = source.to_lowercase();
+        let overrides = self.sources.get(&source);
+
+        let user_match = overrides
+            .map(|s| s.rules.as_slice())
+            .into_iter()
+            .chain(std::iter::once(self.global.as_slice()))
+            .flatten()
+            .find(|r| r.patterns.iter().any(|p| !p.is_empty() && lower.contains(p.as_str())));
+        if let Some(rule) = user_match {
+            return rule.provider.clone();
+        }
+
+        if let Some((_, provider)) = BUILTIN_RULES
+            .iter()
+            .find(|(patterns, _)| patterns.iter().any(|p| lower.contains(p)))
+        {
+            return provider.to_string();
+        }
+
+        overrides
+            .and_then(|s| s.default.clone())
+            .or_else(|| {
+                BUILTIN_DEFAULTS
+                    .iter()
+                    .find(|(s, _)| *s == source)
+                    .map(|(_, p)| p.to_string())
+            })
+            .unwrap_or_else(|| UNKNOWN_PROVIDER.to_string())
+    }
+}
+
+/// Infer the provider for `model` as reported by `source`, using user settings
+pub fn infer_provider(source: &Source, model: &str) -> Provider {
+    Provider::from(current_inference().infer(source.as_str(), model))
+}
+
+fn current_inference() -> Arc<ProviderInference> {
+    let settings = crate::config::settings();
+    {
+        let cached = INFERENCE.read().unwrap_or_else(|e| e.into_inner());
+        if Arc::ptr_eq(&cached.0, &settings) {
+            return Arc::clone(&cached.1);
+        }
+    }
+    let inference = Arc::new(ProviderInference::new(&settings.provider_inference));
+    *INFERENCE.write().unwrap_or_else(|e| e.into_inner()) = (settings, Arc::clone(&inference));
+    inference
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn builtin() -> ProviderInference {
+        ProviderInference::new(&ProviderSettings::default())
+    }
+
+    #[test]
+    fn test_builtin_rules() {
+        let inference = builtin();
+        assert_eq!(inference.infer("droid", "claude-3-sonnet"), "anthropic");
+        assert_eq!(inference.infer("droid", "opus-4"), "anthropic");
+        assert_eq!(inference.infer("droid", "gpt-4o"), "openai");
+        assert_eq!(inference.infer("droid", "o3-mini"), "openai");
+        assert_eq!(inference.infer("droid", "gemini-pro"), "google");
+        assert_eq!(inference.infer("droid", "grok-2"), "xai");
+        assert_eq!(inference.infer("cursor", "deepseek-coder"), "deepseek");
+        assert_eq!(inference.infer("cursor", "llama-3"), "meta");
+    }
+
+    #[test]
+    fn test_builtin_source_defaults() {
+        let inference = builtin();
+        assert_eq!(inference.infer("amp", "mystery-model"), "anthropic");
+        assert_eq!(inference.infer("cursor", "mystery-model"), "cursor");
+        assert_eq!(inference.infer("droid", "mystery-model"), "unknown");
+    }
+
+    #[test]
+    fn test_user_rules_and_source_overrides() {
+        let settings: ProviderSettings = serde_json::from_str(
+            r#"{
+                "rules": [{"patterns": ["Kimi"], "provider": "moonshotai"}],
+                "sources": {
+                    "amp": {"default": "openai"},
+                    "cursor": {"rules": [{"patterns": ["claude"], "provider": "cursor"}]}
+                }
+            }"#,
+        )
+        .unwrap();
+        let inference = ProviderInference::new(&settings);
+
+        assert_eq!(inference.infer("droid", "kimi-k2"), "moonshotai");
+        assert_eq!(inference.infer("amp", "mystery-model"), "openai");
+        assert_eq!(inference.infer("amp", "claude-sonnet-4"), "anthropic");
+        assert_eq!(inference.infer("cursor", "claude-sonnet-4"), "cursor");
+        assert_eq!(inference.infer("droid", "claude-sonnet-4"), "anthropic");
+    }
+
+    #[test]
+    fn test_empty_user_entries_ignored() {
+        let settings = ProviderSettings {
+            rules: vec![
+                ProviderRule { patterns: vec!["".into()], provider: "nobody".into() },
+                ProviderRule { patterns: vec!["gpt".into()], provider: "".into() },
+            ],
+            sources: HashMap::from([(
+                "droid".to_string(),
+                SourceProviderSettings { rules: vec![], default: Some(String::new()) },
+            )]),
+        };
+        let inference = ProviderInference::new(&settings);
+        assert_eq!(inference.infer("droid", "gpt-4o"), "openai");
+        assert_eq!(inference.infer("droid", "mystery"), "unknown");
+    }
+
+    #[test]
+    fn test_provider_round_trip() {
+        for name in ["anthropic", "openai", "google", "xai", "deepseek", "meta", "cursor", "unknown"] {
+            let provider = Provider::from(name);
+            assert!(!matches!(provider, Provider::Other(_)), "{} should be known", name);
+            assert_eq!(provider.as_str(), name);
+        }
+        assert_eq!(Provider::from("moonshotai".to_string()), Provider::Other("moonshotai".into()));
+        assert_eq!(Provider::from("Anthropic").as_str(), "Anthropic");
+    }
+}
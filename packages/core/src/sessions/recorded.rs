@@ -0,0 +1,276 @@
+//! Recorded usage events
+//!
+//! Parses JSONL files from ~/.config/tokscale/recorded/, written by the
+//! [`Recorder`](crate::recorder::Recorder) from events that scripts and
+//! gateways pipe in. One event per line:
+//!
+//! ```json
+//! {"timestamp":1733011200000,"model":"gpt-4o","provider":"openai","input":1200,"output":300}
+//! ```
+//!
+//! `timestamp` is Unix milliseconds or an RFC 3339 string (the time of
+//! recording when omitted). `provider` is inferred from the model when
+//! omitted. Token counts default to zero; `cost` is only kept for models we
+//! have no pricing for.
+//!
+//! Embedding calls only have `input`. Fine-tuning jobs report `training`
+//! tokens, priced at the model's training rate.
+//!
+//! `"billed": true` marks `cost` as what the provider charged (see
+//! [`crate::openrouter_import`]); it is kept even for priced models, and
+//! wins over an unbilled event with the same `id`.
+
+use super::provider::infer_provider;
+use super::{Provider, Source, UnifiedMessage};
+use crate::TokenBreakdown;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum EventTime {
+    Millis(i64),
+    Text(String),
+}
+
+/// One usage event, as piped in and as stored
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordedEvent {
+    /// Stable id for deduplication when the same event is sent twice
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<EventTime>,
+    pub model: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+    #[serde(default)]
+    pub input: i64,
+    #[serde(default)]
+    pub output: i64,
+    #[serde(default)]
+    pub cache_read: i64,
+    #[serde(default)]
+    pub cache_write: i64,
+    #[serde(default)]
+    pub reasoning: i64,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub training: i64,
+    #[serde(default)]
+    pub cost: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub agent: Option<String>,
+    /// `cost` is what the provider billed
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub billed: bool,
+}
+
+impl RecordedEvent {
+    /// Parse and check one NDJSON line
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let event: RecordedEvent = serde_json::from_str(line).map_err(|e| e.to_string())?;
+        if event.model.trim().is_empty() {
+            return Err("model is empty".to_string());
+        }
+        let counts = [event.input, event.output, event.cache_read, event.cache_write, event.reasoning, event.training];
+        if counts.iter().any(|&n| n < 0) {
+            return Err("token counts must not be negative".to_string());
+        }
+        if !event.cost.is_finite() || event.cost < 0.0 {
+            return Err("cost must be a non-negative number".to_string());
+        }
+        event.timestamp_ms()?;
+        Ok(event)
+    }
+
+    /// Unix milliseconds; `None` when the event has no timestamp
+    pub fn timestamp_ms(&self) -> Result<Option<i64>, String> {
+        match &self.timestamp {
+            None => Ok(None),
+            Some(EventTime::Millis(ms)) => Ok(Some(*ms)),
+            Some(EventTime::Text(text)) => chrono::DateTime::parse_from_rfc3339(text)
+                .map(|dt| Some(dt.timestamp_millis()))
+                .map_err(|e| format!("invalid timestamp \"{}\": {}", text, e)),
+        }
+    }
+
+    pub fn into_message(self, default_timestamp: i64) -> UnifiedMessage {
+        let timestamp = self.timestamp_ms().ok().flatten().unwrap_or(default_timestamp);
+        let provider = match self.provider.filter(|p| !p.is_empty()) {
+            Some(provider) => Provider::from(provider),
+            None => infer_provider(&Source::Recorded, &self.model),
+        };
+        let tokens = TokenBreakdown {
+            input: self.input,
+            output: self.output,
+            cache_read: self.cache_read,
+            cache_write: self.cache_write,
+            reasoning: self.reasoning,
+            training: self.training,
+        };
+
+        let mut msg = UnifiedMessage::new_with_dedup(
+            Source::Recorded,
+            self.model,
+            provider,
+            self.session_id.unwrap_or_else(|| "recorded".to_string()),
+            timestamp,
+            tokens,
+            self.cost,
+            self.id.map(|id| format!("recorded:{}", id)),
+        );
+        msg.agent = self.agent;
+        msg.billed = self.billed;
+        msg
+    }
+
+    pub fn from_message(msg: &UnifiedMessage) -> Self {
+        Self {
+            id: msg.dedup_key.as_ref().map(|key| key.strip_prefix("recorded:").unwrap_or(key).to_string()),
+            timestamp: Some(EventTime::Millis(msg.timestamp)),
+            model: msg.model_id.clone(),
+            provider: Some(msg.provider_id.to_string()),
+            session_id: Some(msg.session_id.clone()),
+            input: msg.tokens.input,
+            output: msg.tokens.output,
+            cache_read: msg.tokens.cache_read,
+            cache_write: msg.tokens.cache_write,
+            reasoning: msg.tokens.reasoning,
+            training: msg.tokens.training,
+            cost: msg.cost,
+            agent: msg.agent.clone(),
+            billed: msg.billed,
+        }
+    }
+}
+
+fn is_zero(n: &i64) -> bool {
+    *n == 0
+}
+
+fn is_false(b: &bool) -> bool {
+    !*b
+}
+
+/// Drop events recorded more than once under the same id. The first copy
+/// stays, unless a later one is billed and it isn't.
+pub fn dedup(messages: Vec<UnifiedMessage>) -> Vec<UnifiedMessage> {
+    let mut kept: Vec<Option<UnifiedMessage>> = Vec::with_capacity(messages.len());
+    let mut by_key: HashMap<String, usize> = HashMap::new();
+    for msg in messages {
+        let Some(key) = msg.dedup_key.clone() else {
+            kept.push(Some(msg));
+            continue;
+        };
+        match by_key.get(&key) {
+            Some(&index) => {
+                if msg.billed && !kept[index].as_ref().is_some_and(|m| m.billed) {
+                    kept[index] = Some(msg);
+                }
+            }
+            None => {
+                by_key.insert(key, kept.len());
+                kept.push(Some(msg));
+            }
+        }
+    }
+    kept.into_iter().flatten().collect()
+}
+
+/// Parse a recorded-events file; malformed lines are skipped
+pub fn parse_recorded_file(path: &Path) -> Vec<UnifiedMessage> {
+    let file = match crate::archive::open(path) {
+        Ok(f) => f,
+        Err(_) => return Vec::new(),
+    };
+
+    BufReader::new(file)
+        .lines()
+        .map_while(Result::ok)
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| RecordedEvent::parse(&line).ok())
+        .map(|event| event.into_message(0))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_parse_event() {
+        let event = RecordedEvent::parse(
+            r#"{"id":"req-1","timestamp":"2024-12-01T00:00:00Z","model":"gpt-4o","input":1200,"output":300,"cacheRead":50}"#,
+        )
+        .unwrap();
+        let msg = event.into_message(0);
+        assert_eq!(msg.source, "recorded");
+        assert_eq!(msg.date, "2024-12-01");
+        assert_eq!(msg.timestamp, 1733011200000);
+        assert_eq!(msg.provider_id, "openai");
+        assert_eq!(msg.tokens.cache_read, 50);
+        assert_eq!(msg.dedup_key.as_deref(), Some("recorded:req-1"));
+
+        let msg = RecordedEvent::parse(r#"{"model":"claude-sonnet-4","provider":"bedrock"}"#)
+            .unwrap()
+            .into_message(1750075200000);
+        assert_eq!(msg.date, "2025-06-16");
+        assert_eq!(msg.provider_id, "bedrock");
+    }
+
+    #[test]
+    fn test_parse_event_rejects_bad_input() {
+        assert!(RecordedEvent::parse("not json").is_err());
+        assert!(RecordedEvent::parse(r#"{"model":"","input":1}"#).is_err());
+        assert!(RecordedEvent::parse(r#"{"model":"gpt-4o","input":-1}"#).is_err());
+        assert!(RecordedEvent::parse(r#"{"model":"gpt-4o","timestamp":"yesterday"}"#).is_err());
+    }
+
+    #[test]
+    fn test_message_round_trip() {
+        let msg = RecordedEvent::parse(r#"{"id":"a","timestamp":1733011200000,"model":"gpt-4o","output":7,"agent":"ci"}"#)
+            .unwrap()
+            .into_message(0);
+        let line = serde_json::to_string(&RecordedEvent::from_message(&msg)).unwrap();
+        let again = RecordedEvent::parse(&line).unwrap().into_message(0);
+        assert_eq!(again.dedup_key, msg.dedup_key);
+        assert_eq!(again.tokens.output, 7);
+        assert_eq!(again.agent.as_deref(), Some("ci"));
+        assert_eq!(again.provider_id, msg.provider_id);
+    }
+
+    #[test]
+    fn test_dedup_prefers_billed() {
+        let event = |line: &str| RecordedEvent::parse(line).unwrap().into_message(0);
+        let messages = vec![
+            event(r#"{"id":"gen-1","timestamp":1733011200000,"model":"openai/gpt-4o","output":7}"#),
+            event(r#"{"id":"gen-1","timestamp":1733011200000,"model":"openai/gpt-4o","output":7,"cost":0.02,"billed":true}"#),
+            event(r#"{"id":"gen-1","timestamp":1733011200000,"model":"openai/gpt-4o","output":7}"#),
+            event(r#"{"timestamp":1733011200000,"model":"o3","output":5}"#),
+        ];
+        let deduped = dedup(messages);
+        assert_eq!(deduped.len(), 2);
+        assert!(deduped[0].billed);
+        assert_eq!(deduped[0].cost, 0.02);
+        assert_eq!(deduped[1].model_id, "o3");
+    }
+
+    #[test]
+    fn test_parse_file_skips_malformed_lines() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        writeln!(file, r#"{{"timestamp":1733011200000,"model":"gpt-4o","input":10}}"#).unwrap();
+        writeln!(file, "{{truncated").unwrap();
+        writeln!(file).unwrap();
+        writeln!(file, r#"{{"timestamp":1733011200000,"model":"o3","output":5}}"#).unwrap();
+
+        let messages = parse_recorded_file(file.path());
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[1].model_id, "o3");
+    }
+}
@@ -0,0 +1,120 @@
+//! Session source identifiers
+//!
+//! The tools tokscale knows how to parse, plus `Other` for names that come
+//! from outside the parsers (e.g. messages handed back by a newer frontend).
+//! Strings round-trip exactly: `Source::from(s).as_str() == s`.
+
+use crate::scanner::SessionType;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Source {
+    OpenCode,
+    Claude,
+    Codex,
+    Gemini,
+    Cursor,
+    Amp,
+    Droid,
+    Cline,
+    RooCode,
+    Recorded,
+    Other(String),
+}
+
+impl Source {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Source::OpenCode => "opencode",
+            Source::Claude => "claude",
+            Source::Codex => "codex",
+            Source::Gemini => "gemini",
+            Source::Cursor => "cursor",
+            Source::Amp => "amp",
+            Source::Droid => "droid",
+            Source::Cline => "cline",
+            Source::RooCode => "roocode",
+            Source::Recorded => "recorded",
+            Source::Other(name) => name,
+        }
+    }
+}
+
+impl From<&str> for Source {
+    fn from(name: &str) -> Self {
+        match name {
+            "opencode" => Source::OpenCode,
+            "claude" => Source::Claude,
+            "codex" => Source::Codex,
+            "gemini" => Source::Gemini,
+            "cursor" => Source::Cursor,
+            "amp" => Source::Amp,
+            "droid" => Source::Droid,
+            "cline" => Source::Cline,
+            "roocode" => Source::RooCode,
+            "recorded" => Source::Recorded,
+            other => Source::Other(other.to_string()),
+        }
+    }
+}
+
+impl From<String> for Source {
+    fn from(name: String) -> Self {
+        match Source::from(name.as_str()) {
+            Source::Other(_) => Source::Other(name),
+            known => known,
+        }
+    }
+}
+
+impl From<SessionType> for Source {
+    fn from(session_type: SessionType) -> Self {
+        match session_type {
+            SessionType::OpenCode => Source::OpenCode,
+            SessionType::Claude => Source::Claude,
+            SessionType::Codex => Source::Codex,
+            SessionType::Gemini => Source::Gemini,
+            SessionType::Cursor => Source::Cursor,
+            SessionType::Amp => Source::Amp,
+            SessionType::Droid => Source::Droid,
+            SessionType::Cline => Source::Cline,
+            SessionType::RooCode => Source::RooCode,
+            SessionType::Recorded => Source::Recorded,
+        }
+    }
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl PartialEq<str> for Source {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for Source {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        for session_type in SessionType::ALL {
+            let source = Source::from(session_type);
+            assert_eq!(source.as_str(), session_type.name());
+            assert_eq!(Source::from(session_type.name()), source);
+        }
+        assert_eq!(Source::from("zed"), Source::Other("zed".into()));
+        assert_eq!(Source::from("Claude".to_string()).as_str(), "Claude");
+        assert_eq!(Source::Amp, "amp");
+    }
+}
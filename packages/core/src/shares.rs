@@ -0,0 +1,93 @@
+//! Share of total
+//!
+//! Table rows carry their part of the report's cost and tokens, and reports
+//! carry the grand totals the shares are taken of. Both are worked out here,
+//! from unrounded costs, so the CLI table, CSV exports and Markdown
+//! statements print the same figures instead of each summing rows again.
+//!
+//! Shares are fractions from 0 to 1. Tokens are input, output, cache read
+//! and cache write, as in the totals row; when a total is zero every share
+//! of it is zero.
+
+use crate::{ModelReport, MonthlyReport, Statement};
+
+/// `part` as a fraction of `total`
+pub fn share(part: f64, total: f64) -> f64 {
+    if total > 0.0 {
+        part / total
+    } else {
+        0.0
+    }
+}
+
+pub fn tokens(input: i64, output: i64, cache_read: i64, cache_write: i64) -> f64 {
+    (input + output + cache_read + cache_write) as f64
+}
+
+pub fn apply_to_model_report(report: &mut ModelReport) {
+    let total_tokens = tokens(report.total_input, report.total_output, report.total_cache_read, report.total_cache_write);
+    for entry in &mut report.entries {
+        entry.cost_share = share(entry.cost, report.total_cost);
+        entry.token_share = share(tokens(entry.input, entry.output, entry.cache_read, entry.cache_write), total_tokens);
+    }
+}
+
+pub fn apply_to_monthly_report(report: &mut MonthlyReport) {
+    let total_tokens = tokens(report.total_input, report.total_output, report.total_cache_read, report.total_cache_write);
+    for entry in &mut report.entries {
+        entry.cost_share = share(entry.cost, report.total_cost);
+        entry.token_share = share(tokens(entry.input, entry.output, entry.cache_read, entry.cache_write), total_tokens);
+    }
+}
+
+pub fn apply_to_statement(statement: &mut Statement) {
+    for line in &mut statement.lines {
+        line.cost_share = share(line.cost, statement.total_cost);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MonthlyUsage;
+
+    fn month(month: &str, input: i64, cost: f64) -> MonthlyUsage {
+        MonthlyUsage {
+            month: month.into(),
+            models: Vec::new(),
+            input,
+            output: 0,
+            cache_read: 0,
+            cache_write: 0,
+            message_count: 1,
+            cost,
+            cost_share: 0.0,
+            token_share: 0.0,
+        }
+    }
+
+    #[test]
+    fn test_monthly_shares() {
+        let mut report = MonthlyReport {
+            entries: vec![month("2025-05", 300, 1.0), month("2025-06", 100, 3.0)],
+            total_input: 400,
+            total_output: 0,
+            total_cache_read: 0,
+            total_cache_write: 0,
+            total_messages: 2,
+            total_cost: 4.0,
+            processing_time_ms: 0,
+            summary: None,
+        };
+        apply_to_monthly_report(&mut report);
+        let shares: Vec<(f64, f64)> = report.entries.iter().map(|e| (e.cost_share, e.token_share)).collect();
+        assert_eq!(shares, [(0.25, 0.75), (0.75, 0.25)]);
+    }
+
+    #[test]
+    fn test_zero_total() {
+        assert_eq!(share(0.0, 0.0), 0.0);
+        // Credits can leave a total at zero with rows on either side of it
+        assert_eq!(share(1.0, -1.0), 0.0);
+    }
+}
@@ -0,0 +1,135 @@
+//! Source metadata
+//!
+//! Reports what session data exists for each source without parsing it: file
+//! count, total size and the modification-time range of the session files.
+//! Used by `init`/`doctor` and by frontends showing data coverage.
+
+use crate::scanner::{scan_directory, source_roots, SessionType};
+use rayon::prelude::*;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// Session data found for one source
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceInfo {
+    pub session_type: SessionType,
+    /// Directory scanned for this source
+    pub root: PathBuf,
+    pub exists: bool,
+    pub file_count: usize,
+    pub total_bytes: u64,
+    /// Oldest session file modification time (Unix ms)
+    pub earliest: Option<i64>,
+    /// Newest session file modification time (Unix ms)
+    pub latest: Option<i64>,
+}
+
+/// Summarize every requested source (all sources when `sources` is empty)
+pub fn scan(home_dir: &str, sources: &[String]) -> Vec<SourceInfo> {
+    source_roots(home_dir, sources)
+        .into_par_iter()
+        .map(|(session_type, root, pattern)| {
+            let files = scan_directory(&root, pattern);
+            summarize(session_type, Path::new(&root), &files)
+        })
+        .collect()
+}
+
+fn summarize(session_type: SessionType, root: &Path, files: &[PathBuf]) -> SourceInfo {
+    let (total_bytes, earliest, latest) = files
+        .par_iter()
+        .filter_map(|path| {
+            let metadata = std::fs::metadata(path).ok()?;
+            let mtime = metadata
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map(|d| d.as_millis() as i64);
+            Some((metadata.len(), mtime, mtime))
+        })
+        .reduce(
+            || (0, None, None),
+            |a, b| (a.0 + b.0, min_opt(a.1, b.1), max_opt(a.2, b.2)),
+        );
+
+    SourceInfo {
+        session_type,
+        root: root.to_path_buf(),
+        exists: root.is_dir(),
+        file_count: files.len(),
+        total_bytes,
+        earliest,
+        latest,
+    }
+}
+
+fn min_opt(a: Option<i64>, b: Option<i64>) -> Option<i64> {
+    match (a, b) {
+        (Some(x), Some(y)) => Some(x.min(y)),
+        (x, y) => x.or(y),
+    }
+}
+
+fn max_opt(a: Option<i64>, b: Option<i64>) -> Option<i64> {
+    a.max(b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_scan_reports_counts_and_sizes() {
+        let home = TempDir::new().unwrap();
+        let claude = home.path().join(".claude/projects/p1");
+        fs::create_dir_all(&claude).unwrap();
+        fs::write(claude.join("a.jsonl"), "0123456789").unwrap();
+        fs::write(claude.join("b.jsonl"), "01234").unwrap();
+        fs::write(claude.join("notes.txt"), "ignored").unwrap();
+
+        let infos = scan(home.path().to_str().unwrap(), &["claude".to_string()]);
+        assert_eq!(infos.len(), 1);
+
+        let info = &infos[0];
+        assert_eq!(info.session_type, SessionType::Claude);
+        assert!(info.exists);
+        assert_eq!(info.file_count, 2);
+        assert_eq!(info.total_bytes, 15);
+        assert!(info.earliest.is_some());
+        assert!(info.earliest <= info.latest);
+    }
+
+    #[test]
+    fn test_scan_missing_source() {
+        let home = TempDir::new().unwrap();
+        let infos = scan(home.path().to_str().unwrap(), &["droid".to_string()]);
+
+        assert_eq!(infos.len(), 1);
+        assert!(!infos[0].exists);
+        assert_eq!(infos[0].file_count, 0);
+        assert_eq!(infos[0].total_bytes, 0);
+        assert_eq!(infos[0].earliest, None);
+        assert_eq!(infos[0].latest, None);
+    }
+
+    #[test]
+    fn test_scan_all_sources_listed() {
+        let home = TempDir::new().unwrap();
+        let infos = scan(home.path().to_str().unwrap(), &[]);
+        let mut types: Vec<SessionType> = infos.iter().map(|i| i.session_type).collect();
+        // Extension sources have a root per editor they were used in
+        types.dedup();
+        assert_eq!(types, SessionType::ALL.to_vec());
+    }
+
+    #[test]
+    fn test_min_max_opt() {
+        assert_eq!(min_opt(Some(3), Some(1)), Some(1));
+        assert_eq!(min_opt(None, Some(1)), Some(1));
+        assert_eq!(min_opt(None, None), None);
+        assert_eq!(max_opt(Some(3), None), Some(3));
+        assert_eq!(max_opt(Some(3), Some(5)), Some(5));
+    }
+}
@@ -0,0 +1,285 @@
+//! Monthly statements per provider
+//!
+//! Finance books AI spend per vendor and month, with line items it can check
+//! against the invoice. A statement groups one provider's usage in one
+//! calendar month (by message date) into a line per model: tokens by kind,
+//! the list rates the model is priced at, and its cost.
+//!
+//! Line costs are what tokscale computed per message, so they include tiered
+//! and per-request pricing the list rates don't show. Statements render as
+//! one CSV with every line item, or as a Markdown document per statement
+//! that converts cleanly to PDF, each line with its share of the statement
+//! and a total row per statement.
+
+use crate::pricing::rates::TOKENS_PER_MILLION;
+use crate::pricing::PricingService;
+use crate::sessions::UnifiedMessage;
+use crate::shares;
+use crate::{Statement, StatementLine};
+use std::collections::BTreeMap;
+
+pub const CSV_HEADER: &str = "provider,month,model,messages,input_tokens,output_tokens,cache_read_tokens,\
+cache_write_tokens,reasoning_tokens,input_per_million,output_per_million,cache_read_per_million,\
+cache_write_per_million,cost_usd,cost_share";
+
+fn empty_line(model: &str) -> StatementLine {
+    StatementLine {
+        model: model.to_string(),
+        message_count: 0,
+        input: 0,
+        output: 0,
+        cache_read: 0,
+        cache_write: 0,
+        reasoning: 0,
+        input_per_million: None,
+        output_per_million: None,
+        cache_read_per_million: None,
+        cache_write_per_million: None,
+        cost: 0.0,
+        cost_share: 0.0,
+    }
+}
+
+/// Statements ordered by month, then provider. Lines are most expensive first.
+pub fn build(messages: &[UnifiedMessage], pricing: Option<&PricingService>) -> Vec<Statement> {
+    let mut statements: BTreeMap<(&str, &str), BTreeMap<&str, StatementLine>> = BTreeMap::new();
+    for msg in messages {
+        let Some(month) = msg.date.get(..7) else { continue };
+        let line = statements
+            .entry((month, msg.provider_id.as_str()))
+            .or_default()
+            .entry(msg.model_id.as_str())
+            .or_insert_with(|| empty_line(&msg.model_id));
+        line.message_count += 1;
+        line.input += msg.tokens.input;
+        line.output += msg.tokens.output;
+        line.cache_read += msg.tokens.cache_read;
+        line.cache_write += msg.tokens.cache_write;
+        line.reasoning += msg.tokens.reasoning;
+        line.cost += msg.cost;
+    }
+
+    statements
+        .into_iter()
+        .map(|((month, provider), lines)| {
+            let mut lines: Vec<StatementLine> = lines.into_values().collect();
+            for line in &mut lines {
+                let list = pricing.and_then(|pricing| pricing.lookup_with_provider(&line.model, Some(provider)));
+                if let Some(list) = list {
+                    line.input_per_million = list.pricing.input_per_million();
+                    line.output_per_million = list.pricing.output_per_million();
+                    line.cache_read_per_million = list.pricing.cache_read_per_million();
+                    line.cache_write_per_million = list.pricing.cache_write_per_million();
+                }
+            }
+            lines.sort_by(|a, b| b.cost.total_cmp(&a.cost).then_with(|| a.model.cmp(&b.model)));
+            let mut statement = Statement {
+                provider: provider.to_string(),
+                month: month.to_string(),
+                message_count: lines.iter().map(|l| l.message_count).sum(),
+                input: lines.iter().map(|l| l.input).sum(),
+                output: lines.iter().map(|l| l.output).sum(),
+                cache_read: lines.iter().map(|l| l.cache_read).sum(),
+                cache_write: lines.iter().map(|l| l.cache_write).sum(),
+                reasoning: lines.iter().map(|l| l.reasoning).sum(),
+                total_cost: lines.iter().map(|l| l.cost).sum(),
+                lines,
+            };
+            shares::apply_to_statement(&mut statement);
+            statement
+        })
+        .collect()
+}
+
+/// Fixed-point with trailing zeros trimmed: 3.000000 → "3", 0.300000 → "0.3"
+fn decimal(value: f64, places: usize) -> String {
+    let text = format!("{:.*}", places, value);
+    if text.contains('.') {
+        text.trim_end_matches('0').trim_end_matches('.').to_string()
+    } else {
+        text
+    }
+}
+
+fn rate(value: Option<f64>) -> String {
+    value.map(|v| decimal(v, 6)).unwrap_or_default()
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Every line item of every statement, plus a "Total" row per statement
+pub fn to_csv(statements: &[Statement]) -> String {
+    let mut out = String::from(CSV_HEADER);
+    out.push('\n');
+    for statement in statements {
+        let provider = csv_field(&statement.provider);
+        for line in &statement.lines {
+            out.push_str(&format!(
+                "{},{},{},{},{},{},{},{},{},{},{},{},{},{},{}\n",
+                provider,
+                statement.month,
+                csv_field(&line.model),
+                line.message_count,
+                line.input,
+                line.output,
+                line.cache_read,
+                line.cache_write,
+                line.reasoning,
+                rate(line.input_per_million),
+                rate(line.output_per_million),
+                rate(line.cache_read_per_million),
+                rate(line.cache_write_per_million),
+                decimal(line.cost, 6),
+                decimal(line.cost_share, 4),
+            ));
+        }
+        out.push_str(&format!(
+            "{},{},Total,{},{},{},{},{},{},,,,,{},{}\n",
+            provider,
+            statement.month,
+            statement.message_count,
+            statement.input,
+            statement.output,
+            statement.cache_read,
+            statement.cache_write,
+            statement.reasoning,
+            decimal(statement.total_cost, 6),
+            decimal(shares::share(statement.total_cost, statement.total_cost), 4),
+        ));
+    }
+    out
+}
+
+/// 1234567 → "1,234,567"
+fn grouped(value: i64) -> String {
+    let digits = value.unsigned_abs().to_string();
+    let mut out = String::new();
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(',');
+        }
+        out.push(c);
+    }
+    if value < 0 {
+        out.insert(0, '-');
+    }
+    out
+}
+
+fn month_name(month: &str) -> String {
+    chrono::NaiveDate::parse_from_str(&format!("{}-01", month), "%Y-%m-%d")
+        .map(|date| date.format("%B %Y").to_string())
+        .unwrap_or_else(|_| month.to_string())
+}
+
+/// One statement as a Markdown document, costs in USD to `precision` places
+pub fn to_markdown(statement: &Statement, precision: usize) -> String {
+    let usd = |cost: f64| format!("${:.*}", precision, cost);
+    let list_rate = |value: Option<f64>| value.map(|v| format!("${}", decimal(v, 4))).unwrap_or_else(|| "-".to_string());
+    let percent = |share: f64| format!("{:.1}%", share * 100.0);
+
+    let mut out = format!("# {} usage statement, {}\n\n", statement.provider, month_name(&statement.month));
+    out.push_str(
+        "| Model | Messages | Input | Output | Cache read | Cache write | Input / 1M | Output / 1M | Cache read / 1M | Cache write / 1M | Cost | Share |\n",
+    );
+    out.push_str("|---|---:|---:|---:|---:|---:|---:|---:|---:|---:|---:|---:|\n");
+    for line in &statement.lines {
+        out.push_str(&format!(
+            "| {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} |\n",
+            line.model.replace('|', "\\|"),
+            grouped(line.message_count as i64),
+            grouped(line.input),
+            grouped(line.output + line.reasoning),
+            grouped(line.cache_read),
+            grouped(line.cache_write),
+            list_rate(line.input_per_million),
+            list_rate(line.output_per_million),
+            list_rate(line.cache_read_per_million),
+            list_rate(line.cache_write_per_million),
+            usd(line.cost),
+            percent(line.cost_share),
+        ));
+    }
+    out.push_str(&format!(
+        "| **Total** | {} | {} | {} | {} | {} | | | | | **{}** | **{}** |\n\n",
+        grouped(statement.message_count as i64),
+        grouped(statement.input),
+        grouped(statement.output + statement.reasoning),
+        grouped(statement.cache_read),
+        grouped(statement.cache_write),
+        usd(statement.total_cost),
+        percent(shares::share(statement.total_cost, statement.total_cost)),
+    ));
+    out.push_str(&format!(
+        "Output includes reasoning tokens. Rates are list prices in USD per {} tokens; costs are computed per message \
+         from local session logs and may differ from the invoice.\n",
+        grouped(TOKENS_PER_MILLION as i64),
+    ));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{message, noon_utc, TestMessage};
+    use crate::TokenBreakdown;
+
+    const USAGE: TokenBreakdown =
+        TokenBreakdown { input: 1000, output: 200, cache_read: 0, cache_write: 0, reasoning: 50, training: 0 };
+
+    #[test]
+    fn test_statements_per_provider_and_month() {
+        let messages = vec![
+            message("s1", noon_utc("2025-05-02"), 1.0).with_tokens(USAGE),
+            message("s1", noon_utc("2025-05-20"), 1.0).with_tokens(USAGE),
+            message("s1", noon_utc("2025-05-03"), 0.25).with_model("claude-haiku-4").with_tokens(USAGE),
+            message("s1", noon_utc("2025-05-03"), 0.5).with_model("gpt-4o").with_provider("openai").with_tokens(USAGE),
+            message("s1", noon_utc("2025-06-01"), 3.0).with_tokens(USAGE),
+        ];
+        let statements = build(&messages, None);
+        let keys: Vec<(&str, &str)> = statements.iter().map(|s| (s.month.as_str(), s.provider.as_str())).collect();
+        assert_eq!(keys, vec![("2025-05", "anthropic"), ("2025-05", "openai"), ("2025-06", "anthropic")]);
+
+        let may = &statements[0];
+        assert_eq!(may.lines.len(), 2);
+        assert_eq!(may.lines[0].model, "claude-sonnet-4");
+        assert_eq!(may.lines[0].message_count, 2);
+        assert_eq!(may.lines[0].input, 2000);
+        assert_eq!(may.total_cost, 2.25);
+        assert_eq!(may.lines[0].cost_share, 2.0 / 2.25);
+        assert_eq!(may.message_count, 3);
+    }
+
+    #[test]
+    fn test_csv_and_markdown() {
+        let mut statements = build(&[message("s1", noon_utc("2025-05-02"), 1.5).with_tokens(USAGE)], None);
+        statements[0].lines[0].input_per_million = Some(3.0);
+        statements[0].lines[0].output_per_million = Some(15.0);
+
+        let csv = to_csv(&statements);
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], CSV_HEADER);
+        assert_eq!(lines[1], "anthropic,2025-05,claude-sonnet-4,1,1000,200,0,0,50,3,15,,,1.5,1");
+        assert_eq!(lines[2], "anthropic,2025-05,Total,1,1000,200,0,0,50,,,,,1.5,1");
+
+        let markdown = to_markdown(&statements[0], 2);
+        assert!(markdown.starts_with("# anthropic usage statement, May 2025\n"));
+        assert!(markdown.contains("| claude-sonnet-4 | 1 | 1,000 | 250 | 0 | 0 | $3 | $15 | - | - | $1.50 | 100.0% |"));
+        assert!(markdown.contains("| **Total** | 1 | 1,000 | 250 | 0 | 0 | | | | | **$1.50** | **100.0%** |"));
+    }
+
+    #[test]
+    fn test_number_formatting() {
+        assert_eq!(grouped(1_234_567), "1,234,567");
+        assert_eq!(grouped(999), "999");
+        assert_eq!(decimal(0.300000, 6), "0.3");
+        assert_eq!(decimal(2.0, 6), "2");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+    }
+}
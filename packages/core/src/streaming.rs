@@ -0,0 +1,306 @@
+//! Bounded-memory ingest
+//!
+//! Reports normally parse every session file into memory and then aggregate,
+//! which is more than a small VPS has once a history reaches millions of
+//! messages. With `memory.streaming` in settings.json, the library API and
+//! `tokscale serve` aggregate while they parse instead:
+//!
+//! ```json
+//! "memory": { "streaming": true, "filesInFlight": 8, "sortChunk": 50000 }
+//! ```
+//!
+//! Parser threads hand each file's messages to one aggregating thread over a
+//! channel, and never start a file more than `filesInFlight` files ahead of
+//! the one being aggregated, so parsed messages waiting to be counted are
+//! bounded by that many files. Files are aggregated in scan order, as in the
+//! in-memory path. Graphs and model reports keep running totals only (and
+//! the messages a free tier may cover, which are consumed in time order).
+//! Message listings keep just the page being built, sorting candidates and
+//! cutting them back every `sortChunk` messages ([`TopN`]).
+//!
+//! Streaming reads past the parse cache, since the cache holds every message
+//! it has parsed.
+
+use crate::scanner::SessionType;
+use crate::sessions::UnifiedMessage;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Condvar, Mutex};
+use std::thread;
+
+const DEFAULT_FILES_IN_FLIGHT: usize = 8;
+const DEFAULT_SORT_CHUNK: usize = 50_000;
+
+/// `memory` in settings.json
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct MemorySettings {
+    /// Aggregate while parsing instead of holding every message
+    pub streaming: Option<bool>,
+    pub files_in_flight: Option<usize>,
+    pub sort_chunk: Option<usize>,
+}
+
+impl MemorySettings {
+    pub fn validate(&self) -> Result<(), String> {
+        for (name, value) in [("filesInFlight", self.files_in_flight), ("sortChunk", self.sort_chunk)] {
+            if value == Some(0) {
+                return Err(format!("{} must be at least 1", name));
+            }
+        }
+        Ok(())
+    }
+
+    /// Limits to stream within, when streaming is on
+    pub fn limits(&self) -> Option<MemoryLimits> {
+        if self.streaming != Some(true) {
+            return None;
+        }
+        let defaults = MemoryLimits::default();
+        Some(MemoryLimits {
+            files_in_flight: self.files_in_flight.unwrap_or(defaults.files_in_flight).max(1),
+            sort_chunk: self.sort_chunk.unwrap_or(defaults.sort_chunk).max(1),
+        })
+    }
+}
+
+/// How much a streamed ingest holds at once
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryLimits {
+    /// Parsed files waiting to be aggregated, at most
+    pub files_in_flight: usize,
+    /// Listing candidates sorted and cut back at a time
+    pub sort_chunk: usize,
+}
+
+impl Default for MemoryLimits {
+    fn default() -> Self {
+        Self {
+            files_in_flight: DEFAULT_FILES_IN_FLIGHT,
+            sort_chunk: DEFAULT_SORT_CHUNK,
+        }
+    }
+}
+
+/// Parse `files` on up to `files_in_flight` threads and pass each file's
+/// messages to `consume`, in the order of `files`, on the calling thread
+pub fn for_each_file<P, F>(files: &[(SessionType, PathBuf)], limits: MemoryLimits, parse: P, mut consume: F)
+where
+    P: Fn(SessionType, &Path) -> Vec<UnifiedMessage> + Sync,
+    F: FnMut(Vec<UnifiedMessage>),
+{
+    let window = limits.files_in_flight.max(1);
+    let threads = thread::available_parallelism().map_or(1, |n| n.get()).min(window).min(files.len());
+    let next = AtomicUsize::new(0);
+    // Files handed to `consume` so far
+    let consumed = (Mutex::new(0usize), Condvar::new());
+    let (tx, rx) = mpsc::sync_channel::<(usize, Vec<UnifiedMessage>)>(window);
+
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            let tx = tx.clone();
+            let (next, consumed, parse) = (&next, &consumed, &parse);
+            scope.spawn(move || loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some((session_type, path)) = files.get(index) else { break };
+                // Stay within `window` files of the one being aggregated,
+                // which is never waiting on this thread
+                let (lock, advanced) = consumed;
+                let mut done = lock.lock().unwrap_or_else(|e| e.into_inner());
+                while index >= *done + window {
+                    done = advanced.wait(done).unwrap_or_else(|e| e.into_inner());
+                }
+                drop(done);
+                if tx.send((index, parse(*session_type, path))).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(tx);
+
+        // Parsers finish out of order; hold early files until it's their turn
+        let mut early = BTreeMap::new();
+        let mut expected = 0;
+        for (index, messages) in rx {
+            early.insert(index, messages);
+            while let Some(messages) = early.remove(&expected) {
+                consume(messages);
+                expected += 1;
+                let (lock, advanced) = &consumed;
+                *lock.lock().unwrap_or_else(|e| e.into_inner()) = expected;
+                advanced.notify_all();
+            }
+        }
+    });
+}
+
+/// The `keep` greatest items pushed, by key. Candidates are sorted and cut
+/// back to `keep` each time `chunk` more arrive, so at most `keep + chunk`
+/// are held. Only serve-mode pagination needs it.
+#[cfg(feature = "server")]
+pub struct TopN<K: Ord, T> {
+    keep: usize,
+    chunk: usize,
+    items: Vec<(K, T)>,
+}
+
+#[cfg(feature = "server")]
+impl<K: Ord, T> TopN<K, T> {
+    pub fn new(keep: usize, chunk: usize) -> Self {
+        Self { keep, chunk: chunk.max(1), items: Vec::new() }
+    }
+
+    pub fn push(&mut self, key: K, item: T) {
+        self.items.push((key, item));
+        if self.items.len() >= self.keep + self.chunk {
+            self.cut();
+        }
+    }
+
+    fn cut(&mut self) {
+        self.items.sort_by(|a, b| b.0.cmp(&a.0));
+        self.items.truncate(self.keep);
+    }
+
+    /// The kept items, greatest first
+    pub fn into_sorted(mut self) -> Vec<T> {
+        self.cut();
+        self.items.into_iter().map(|(_, item)| item).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aggregator::{finish_days, DayAccumulator};
+    use crate::testutil::{message, TestMessage};
+    use std::collections::HashMap;
+
+    /// 2025-01-01T00:00:00Z
+    const YEAR_START: i64 = 1_735_689_600_000;
+    const DAY: i64 = 86_400_000;
+
+    fn fake_files(count: usize) -> Vec<(SessionType, PathBuf)> {
+        (0..count).map(|i| (SessionType::Claude, PathBuf::from(format!("s{}.jsonl", i)))).collect()
+    }
+
+    /// `per_file` messages for the file named `s<n>.jsonl`, spread over a year
+    fn fake_messages(path: &Path, per_file: usize) -> Vec<UnifiedMessage> {
+        let n: i64 = path.to_string_lossy().trim_start_matches('s').trim_end_matches(".jsonl").parse().unwrap();
+        let session = format!("session-{}", n);
+        (0..per_file as i64)
+            .map(|i| {
+                let model = ["sonnet", "opus", "haiku"][(i % 3) as usize];
+                let timestamp = YEAR_START + ((n * 7 + i) % 365) * DAY;
+                message(&session, timestamp, 0.001).with_model(model).with_input(100).with_output(10)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_files_consumed_in_order_within_limit() {
+        let files = fake_files(50);
+        let limits = MemoryLimits { files_in_flight: 3, ..MemoryLimits::default() };
+        let waiting = AtomicUsize::new(0);
+        let most_waiting = AtomicUsize::new(0);
+
+        let mut sessions = Vec::new();
+        for_each_file(
+            &files,
+            limits,
+            |_, path| {
+                let messages = fake_messages(path, 2);
+                let now = waiting.fetch_add(1, Ordering::SeqCst) + 1;
+                most_waiting.fetch_max(now, Ordering::SeqCst);
+                messages
+            },
+            |messages| {
+                waiting.fetch_sub(1, Ordering::SeqCst);
+                sessions.push(messages[0].session_id.clone());
+            },
+        );
+
+        let expected: Vec<String> = (0..50).map(|i| format!("session-{}", i)).collect();
+        assert_eq!(sessions, expected);
+        assert!(most_waiting.load(Ordering::SeqCst) <= 3, "{}", most_waiting.load(Ordering::SeqCst));
+    }
+
+    #[cfg(feature = "server")]
+    #[test]
+    fn test_top_n() {
+        let mut top = TopN::new(3, 2);
+        for n in [5, 1, 9, 3, 7, 2, 8] {
+            top.push(n, n.to_string());
+            assert!(top.items.len() <= 5);
+        }
+        assert_eq!(top.into_sorted(), ["9", "8", "7"]);
+        assert!(TopN::<i32, ()>::new(3, 2).into_sorted().is_empty());
+    }
+
+    #[test]
+    fn test_settings() {
+        assert_eq!(MemorySettings::default().limits(), None);
+        let settings = MemorySettings { streaming: Some(true), sort_chunk: Some(10), ..MemorySettings::default() };
+        assert_eq!(settings.limits(), Some(MemoryLimits { files_in_flight: DEFAULT_FILES_IN_FLIGHT, sort_chunk: 10 }));
+        assert!(MemorySettings { files_in_flight: Some(0), ..settings }.validate().is_err());
+    }
+
+    /// Peak resident memory and current resident memory, in KiB
+    #[cfg(target_os = "linux")]
+    fn rss_kib() -> (u64, u64) {
+        let status = std::fs::read_to_string("/proc/self/status").unwrap();
+        let field = |name: &str| {
+            status
+                .lines()
+                .find_map(|line| line.strip_prefix(name))
+                .and_then(|rest| rest.trim().trim_end_matches("kB").trim().parse().ok())
+                .unwrap()
+        };
+        (field("VmHWM:"), field("VmRSS:"))
+    }
+
+    /// Ingest `files` files of `per_file` messages each and check peak memory
+    /// grew by less than `ceiling_mib`
+    #[cfg(target_os = "linux")]
+    fn ingest_under_ceiling(files: usize, per_file: usize, ceiling_mib: u64) {
+        let (_, before) = rss_kib();
+        let mut days: HashMap<String, DayAccumulator> = HashMap::new();
+        let mut count = 0usize;
+        for_each_file(&fake_files(files), MemoryLimits::default(), |_, path| fake_messages(path, per_file), |messages| {
+            count += messages.len();
+            for msg in &messages {
+                days.entry(msg.date.clone()).or_default().add_message(msg);
+            }
+        });
+        let contributions = finish_days(days);
+        let (peak, _) = rss_kib();
+
+        assert_eq!(count, files * per_file);
+        assert_eq!(contributions.len(), 365);
+        let messages: i64 = contributions.iter().map(|c| c.totals.messages as i64).sum();
+        assert_eq!(messages, (files * per_file) as i64);
+        let grown_mib = peak.saturating_sub(before) / 1024;
+        assert!(grown_mib < ceiling_mib, "peak grew by {} MiB, ceiling {} MiB", grown_mib, ceiling_mib);
+    }
+
+    /// A tenth of the full stress test below. Holding every message would
+    /// take well over the ceiling; it is half the full one rather than a
+    /// tenth because the peak is shared with the tests running alongside.
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_half_million_messages_under_ceiling() {
+        ingest_under_ceiling(50, 10_000, 64);
+    }
+
+    /// Ingest 5M messages and check peak memory stays under a ceiling. Peak
+    /// memory is per process, so CI runs it alone and optimized (the
+    /// `stress` job in build-native.yml).
+    #[cfg(target_os = "linux")]
+    #[test]
+    #[ignore]
+    fn stress_five_million_messages_under_ceiling() {
+        ingest_under_ceiling(500, 10_000, 128);
+    }
+}
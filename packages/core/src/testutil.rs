@@ -0,0 +1,122 @@
+//! Test-only helpers
+//!
+//! A builder for the `UnifiedMessage`s most module tests feed their code,
+//! and proptest strategies producing adversarial strings: model-id-like input
+//! assembled from real fragments, and arbitrary text mixing ASCII,
+//! separators and Unicode that breaks byte-offset string handling.
+
+use crate::sessions::{Provider, Source, UnifiedMessage};
+use crate::TokenBreakdown;
+use proptest::prelude::*;
+use proptest::sample::select;
+
+/// A Claude Code `claude-sonnet-4` message with no tokens; adjust the rest
+/// with [`TestMessage`]
+pub fn message(session: &str, timestamp: i64, cost: f64) -> UnifiedMessage {
+    UnifiedMessage::new("claude", "claude-sonnet-4", "anthropic", session, timestamp, TokenBreakdown::default(), cost)
+}
+
+/// Noon UTC on `date` (`YYYY-MM-DD`), in milliseconds
+pub fn noon_utc(date: &str) -> i64 {
+    chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .unwrap()
+        .and_hms_opt(12, 0, 0)
+        .unwrap()
+        .and_utc()
+        .timestamp_millis()
+}
+
+/// Setters for the fields [`message`] fixes
+pub trait TestMessage {
+    fn with_source(self, source: &str) -> Self;
+    fn with_model(self, model: &str) -> Self;
+    fn with_provider(self, provider: &str) -> Self;
+    fn with_tokens(self, tokens: TokenBreakdown) -> Self;
+    fn with_input(self, input: i64) -> Self;
+    fn with_output(self, output: i64) -> Self;
+    fn with_billed(self, billed: bool) -> Self;
+    fn with_agent(self, agent: &str) -> Self;
+}
+
+impl TestMessage for UnifiedMessage {
+    fn with_source(mut self, source: &str) -> Self {
+        self.source = Source::from(source);
+        self
+    }
+
+    fn with_model(mut self, model: &str) -> Self {
+        self.model_id = model.to_string();
+        self
+    }
+
+    fn with_provider(mut self, provider: &str) -> Self {
+        self.provider_id = Provider::from(provider);
+        self
+    }
+
+    fn with_tokens(mut self, tokens: TokenBreakdown) -> Self {
+        self.tokens = tokens;
+        self
+    }
+
+    fn with_input(mut self, input: i64) -> Self {
+        self.tokens.input = input;
+        self
+    }
+
+    fn with_output(mut self, output: i64) -> Self {
+        self.tokens.output = output;
+        self
+    }
+
+    fn with_billed(mut self, billed: bool) -> Self {
+        self.billed = billed;
+        self
+    }
+
+    fn with_agent(mut self, agent: &str) -> Self {
+        self.agent = Some(agent.to_string());
+        self
+    }
+}
+
+/// Characters that historically break byte-offset string handling:
+/// multi-byte letters, combining marks, case-changing code points whose
+/// lowercase form has a different UTF-8 length, and emoji
+const TRICKY_CHARS: &[char] = &[
+    'é', 'ö', 'ü', 'ß', 'İ', 'K', 'Ω', 'ǅ', '\u{0301}', '日', '本', '語', '🦀', '—', '\u{200b}',
+];
+
+const SEPARATORS: &[char] = &['-', '.', '/', ':', '_', ' ', '"', '\\', '{', '}'];
+
+/// Fragments that steer generated ids into the interesting lookup paths
+const MODEL_FRAGMENTS: &[&str] = &[
+    "claude", "sonnet", "opus", "haiku", "gpt", "gemini", "glm", "grok", "o3", "4", "4.5", "4-5",
+    "5.1", "3-7", "-", ".", "/", ":", "_", "-codex", "-codex-max", "-high", "-low", ":free",
+    "-xhigh", "antigravity-", "Antigravity-", "openai/", "anthropic/", "vertex_ai/", "20251101",
+];
+
+/// Arbitrary string of up to `max_chars` characters mixing ASCII,
+/// separators and tricky Unicode
+pub fn string(max_chars: usize) -> impl Strategy<Value = String> {
+    let ch = prop_oneof![
+        1 => select(TRICKY_CHARS),
+        1 => select(SEPARATORS),
+        2 => proptest::char::range('a', 'z'),
+    ];
+    proptest::collection::vec(ch, 0..=max_chars).prop_map(|chars| chars.into_iter().collect())
+}
+
+/// Model-id-like string assembled from real fragments plus noise
+pub fn model_id() -> impl Strategy<Value = String> {
+    let part = prop_oneof![
+        4 => select(MODEL_FRAGMENTS).prop_map(str::to_string),
+        1 => string(3),
+    ];
+    proptest::collection::vec(part, 1..=6).prop_map(|parts| parts.concat())
+}
+
+/// A model id, or arbitrary text of up to `max_chars` characters
+pub fn model_id_or_string(max_chars: usize) -> impl Strategy<Value = String> {
+    prop_oneof![model_id(), string(max_chars)]
+}
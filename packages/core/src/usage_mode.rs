@@ -0,0 +1,147 @@
+//! Interactive vs agentic usage
+//!
+//! The same model is used two ways: answering a person who reads each reply
+//! before typing the next prompt, and running on its own through tool loops
+//! and sub-agents. A message counts as agentic when any of these hold:
+//!
+//! - it was written by a sub-agent
+//! - its response asked to run a tool
+//! - it came within [`BURST_GAP_MS`] of the previous message in its session,
+//!   too soon for a person to have read that reply and answered
+//!
+//! Everything else is interactive. Sources that don't record tool calls
+//! are classified by timing alone.
+
+use crate::sessions::UnifiedMessage;
+use crate::{UsageModeEntry, UsageModeReport};
+use std::collections::HashMap;
+
+/// Messages closer together than this are one automated burst
+pub const BURST_GAP_MS: i64 = 10_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UsageMode {
+    Interactive,
+    Agentic,
+}
+
+impl UsageMode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            UsageMode::Interactive => "interactive",
+            UsageMode::Agentic => "agentic",
+        }
+    }
+}
+
+/// Mode of each message, in the order given
+pub fn classify(messages: &[UnifiedMessage]) -> Vec<UsageMode> {
+    let mut order: Vec<usize> = (0..messages.len()).collect();
+    order.sort_by(|&a, &b| {
+        let (a, b) = (&messages[a], &messages[b]);
+        (a.source.as_str(), &a.session_id, a.timestamp).cmp(&(b.source.as_str(), &b.session_id, b.timestamp))
+    });
+
+    let mut modes = vec![UsageMode::Interactive; messages.len()];
+    let mut previous: Option<&UnifiedMessage> = None;
+    for index in order {
+        let msg = &messages[index];
+        let burst = previous.is_some_and(|prev| {
+            !msg.session_id.is_empty()
+                && prev.source == msg.source
+                && prev.session_id == msg.session_id
+                && msg.timestamp - prev.timestamp < BURST_GAP_MS
+        });
+        if msg.sidechain || msg.tool_call || burst {
+            modes[index] = UsageMode::Agentic;
+        }
+        previous = Some(msg);
+    }
+    modes
+}
+
+/// Spend per (source, mode), most expensive first
+pub fn report(messages: &[UnifiedMessage], processing_time_ms: u32) -> UsageModeReport {
+    let modes = classify(messages);
+    let mut groups: HashMap<(&str, UsageMode), UsageModeEntry> = HashMap::new();
+    for (msg, mode) in messages.iter().zip(modes) {
+        let entry = groups.entry((msg.source.as_str(), mode)).or_insert_with(|| UsageModeEntry {
+            source: msg.source.to_string(),
+            mode: mode.as_str().to_string(),
+            input: 0,
+            output: 0,
+            cache_read: 0,
+            cache_write: 0,
+            reasoning: 0,
+            message_count: 0,
+            cost: 0.0,
+        });
+        entry.input += msg.tokens.input;
+        entry.output += msg.tokens.output;
+        entry.cache_read += msg.tokens.cache_read;
+        entry.cache_write += msg.tokens.cache_write;
+        entry.reasoning += msg.tokens.reasoning;
+        entry.message_count += 1;
+        entry.cost += msg.cost;
+    }
+
+    let mut entries: Vec<UsageModeEntry> = groups.into_values().collect();
+    entries.sort_by(|a, b| {
+        b.cost
+            .total_cmp(&a.cost)
+            .then_with(|| a.source.cmp(&b.source))
+            .then_with(|| a.mode.cmp(&b.mode))
+    });
+    let cost_of = |mode: UsageMode| entries.iter().filter(|e| e.mode == mode.as_str()).map(|e| e.cost).sum::<f64>();
+    let (interactive_cost, agentic_cost) = (cost_of(UsageMode::Interactive), cost_of(UsageMode::Agentic));
+    let total = interactive_cost + agentic_cost;
+    UsageModeReport {
+        entries,
+        interactive_cost,
+        agentic_cost,
+        agentic_share: if total > 0.0 { agentic_cost / total } else { 0.0 },
+        processing_time_ms,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{message, TestMessage};
+
+    const T0: i64 = 1_740_823_200_000;
+
+    #[test]
+    fn test_classify() {
+        let messages = vec![
+            // A prompt answered directly, then a reply a minute later
+            message("s1", T0, 1.0),
+            message("s1", T0 + 60_000, 1.0),
+            // Same second, other session: not a burst
+            message("s2", T0, 1.0),
+            // A tool loop: the first step asked for a tool, the rest came in quick succession
+            message("s3", T0, 1.0).with_tool_call(true),
+            message("s3", T0 + 3_000, 1.0),
+            message("s1", T0 + 120_000, 1.0).with_sidechain(true),
+        ];
+
+        let modes = classify(&messages);
+        use UsageMode::*;
+        assert_eq!(modes, vec![Interactive, Interactive, Interactive, Agentic, Agentic, Agentic]);
+    }
+
+    #[test]
+    fn test_report_splits_spend_by_mode() {
+        let messages = vec![
+            message("s1", T0, 1.0),
+            message("s1", T0 + 2_000, 3.0),
+            message("c1", T0, 0.5).with_source("codex").with_model("gpt-5").with_provider("openai"),
+        ];
+
+        let report = report(&messages, 0);
+        assert_eq!(report.entries.len(), 3);
+        assert_eq!((report.entries[0].source.as_str(), report.entries[0].mode.as_str()), ("claude", "agentic"));
+        assert!((report.interactive_cost - 1.5).abs() < 1e-9);
+        assert!((report.agentic_share - 3.0 / 4.5).abs() < 1e-9);
+    }
+}
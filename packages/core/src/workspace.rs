@@ -0,0 +1,435 @@
+//! Project attribution from session working directories
+//!
+//! Sessions record the directory they ran in, which is often a subdirectory
+//! of a repository (a package in a monorepo, `src/`, a worktree checkout).
+//! Grouping by that raw string splits one project into many, so each
+//! directory is resolved to its repository root by walking up to the
+//! nearest `.git`:
+//!
+//! - a nested repository or submodule is its own project (nearest `.git` wins)
+//! - a linked worktree (`.git` file pointing into `<repo>/.git/worktrees/`)
+//!   belongs to the repository it was created from
+//! - a directory that no longer exists, or isn't in a repository, is its own
+//!   project
+//!
+//! Branch usage is keyed by project as well, since `main` in one repository
+//! has nothing to do with `main` in another.
+//!
+//! Sessions run inside a container or devcontainer record the path the
+//! repository is mounted at (`/workspaces/app`), not where it is on the host.
+//! `pathMappings` in settings.json rewrites those prefixes before resolving,
+//! so both count as one project:
+//!
+//! ```json
+//! "pathMappings": [{ "from": "/workspaces/app", "to": "/home/me/src/app" }]
+//! ```
+
+use crate::sessions::UnifiedMessage;
+use crate::{BranchReport, BranchUsage, ProjectReport, ProjectUsage};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Repository root (or the directory itself) a working directory belongs to
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Project {
+    pub root: String,
+    /// Last path component of `root`
+    pub name: String,
+}
+
+impl Project {
+    fn at(root: &Path) -> Self {
+        let name = root
+            .file_name()
+            .and_then(|n| n.to_str())
+            .map(str::to_string)
+            .unwrap_or_else(|| root.to_string_lossy().into_owned());
+        Self { root: root.to_string_lossy().into_owned(), name }
+    }
+}
+
+/// One entry of `pathMappings` in settings.json
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PathMapping {
+    /// Path prefix as recorded inside the container
+    pub from: String,
+    /// The same directory on the host
+    pub to: String,
+}
+
+impl PathMapping {
+    pub fn validate(&self) -> Result<(), String> {
+        if !Path::new(self.from.trim()).is_absolute() || !Path::new(self.to.trim()).is_absolute() {
+            return Err(format!("from and to must be absolute paths, got \"{}\" and \"{}\"", self.from, self.to));
+        }
+        Ok(())
+    }
+}
+
+/// Resolves working directories to projects, remembering every directory it
+/// has looked at so sessions in the same tree cost one walk
+#[derive(Debug, Default)]
+pub struct WorkspaceResolver {
+    roots: HashMap<PathBuf, Option<PathBuf>>,
+    /// Normalized (from, to) prefixes, longest `from` first
+    mappings: Vec<(PathBuf, PathBuf)>,
+}
+
+impl WorkspaceResolver {
+    /// A resolver applying the path mappings in settings
+    pub fn new() -> Self {
+        Self::with_mappings(&crate::config::settings().path_mappings)
+    }
+
+    pub fn with_mappings(mappings: &[PathMapping]) -> Self {
+        let mut mappings: Vec<(PathBuf, PathBuf)> = mappings
+            .iter()
+            .filter(|m| m.validate().is_ok())
+            .map(|m| (normalize(&m.from), normalize(&m.to)))
+            .collect();
+        mappings.sort_by_key(|(from, _)| std::cmp::Reverse(from.components().count()));
+        Self { roots: HashMap::new(), mappings }
+    }
+
+    /// `path` on the host, if it is under a mapped container prefix
+    fn map(&self, path: PathBuf) -> PathBuf {
+        for (from, to) in &self.mappings {
+            if let Ok(rest) = path.strip_prefix(from) {
+                return to.join(rest);
+            }
+        }
+        path
+    }
+
+    pub fn resolve(&mut self, cwd: &str) -> Project {
+        let path = self.map(normalize(cwd));
+        match self.repository_root(&path) {
+            Some(root) => Project::at(&root),
+            None => Project::at(&path),
+        }
+    }
+
+    fn repository_root(&mut self, dir: &Path) -> Option<PathBuf> {
+        let mut visited = Vec::new();
+        let mut found = None;
+        for ancestor in dir.ancestors() {
+            if let Some(cached) = self.roots.get(ancestor) {
+                found = cached.clone();
+                break;
+            }
+            visited.push(ancestor.to_path_buf());
+            if let Some(root) = git_root(ancestor) {
+                found = Some(root);
+                break;
+            }
+        }
+        // A directory's answer holds for everything between it and the root
+        for path in visited {
+            self.roots.insert(path, found.clone());
+        }
+        found
+    }
+}
+
+/// Usage per project, most expensive first. Messages from sources that
+/// don't record a working directory are counted as unattributed.
+pub fn project_report(messages: &[UnifiedMessage], processing_time_ms: u32) -> ProjectReport {
+    let mut resolver = WorkspaceResolver::new();
+    let mut projects: HashMap<Project, ProjectUsage> = HashMap::new();
+    let (mut unattributed_messages, mut unattributed_cost) = (0, 0.0);
+
+    for msg in messages {
+        let Some(cwd) = &msg.cwd else {
+            unattributed_messages += 1;
+            unattributed_cost += msg.cost;
+            continue;
+        };
+        let project = resolver.resolve(cwd);
+        let usage = projects.entry(project.clone()).or_insert_with(|| ProjectUsage {
+            project: project.root,
+            name: project.name,
+            paths: Vec::new(),
+            sources: Vec::new(),
+            input: 0,
+            output: 0,
+            cache_read: 0,
+            cache_write: 0,
+            reasoning: 0,
+            message_count: 0,
+            cost: 0.0,
+        });
+        if !usage.paths.contains(cwd) {
+            usage.paths.push(cwd.clone());
+        }
+        let source = msg.source.to_string();
+        if !usage.sources.contains(&source) {
+            usage.sources.push(source);
+        }
+        usage.input += msg.tokens.input;
+        usage.output += msg.tokens.output;
+        usage.cache_read += msg.tokens.cache_read;
+        usage.cache_write += msg.tokens.cache_write;
+        usage.reasoning += msg.tokens.reasoning;
+        usage.message_count += 1;
+        usage.cost += msg.cost;
+    }
+
+    let mut entries: Vec<ProjectUsage> = projects.into_values().collect();
+    for entry in &mut entries {
+        entry.paths.sort();
+    }
+    entries.sort_by(|a, b| b.cost.total_cmp(&a.cost).then_with(|| a.project.cmp(&b.project)));
+    let total_cost = entries.iter().map(|e| e.cost).sum::<f64>() + unattributed_cost;
+    ProjectReport { entries, unattributed_messages, unattributed_cost, total_cost, processing_time_ms }
+}
+
+/// A branch name within the project it was checked out in, if known
+type BranchKey<'a> = (Option<Project>, &'a str);
+
+/// Usage per (project, branch), most expensive first. Messages from
+/// sources that don't record a branch are counted as unattributed.
+pub fn branch_report(messages: &[UnifiedMessage], processing_time_ms: u32) -> BranchReport {
+    let mut resolver = WorkspaceResolver::new();
+    let mut branches: HashMap<BranchKey, (BranchUsage, Vec<&str>)> = HashMap::new();
+    let (mut unattributed_messages, mut unattributed_cost) = (0, 0.0);
+
+    for msg in messages {
+        let Some(branch) = &msg.git_branch else {
+            unattributed_messages += 1;
+            unattributed_cost += msg.cost;
+            continue;
+        };
+        let project = msg.cwd.as_deref().map(|cwd| resolver.resolve(cwd));
+        let (usage, sessions) = branches.entry((project.clone(), branch.as_str())).or_insert_with(|| {
+            let usage = BranchUsage {
+                branch: branch.clone(),
+                project: project.as_ref().map(|p| p.root.clone()),
+                name: project.map(|p| p.name),
+                sources: Vec::new(),
+                input: 0,
+                output: 0,
+                cache_read: 0,
+                cache_write: 0,
+                reasoning: 0,
+                message_count: 0,
+                session_count: 0,
+                first_date: msg.date.clone(),
+                last_date: msg.date.clone(),
+                cost: 0.0,
+            };
+            (usage, Vec::new())
+        });
+        let source = msg.source.to_string();
+        if !usage.sources.contains(&source) {
+            usage.sources.push(source);
+        }
+        if !sessions.contains(&msg.session_id.as_str()) {
+            sessions.push(&msg.session_id);
+        }
+        if msg.date < usage.first_date {
+            usage.first_date = msg.date.clone();
+        }
+        if msg.date > usage.last_date {
+            usage.last_date = msg.date.clone();
+        }
+        usage.input += msg.tokens.input;
+        usage.output += msg.tokens.output;
+        usage.cache_read += msg.tokens.cache_read;
+        usage.cache_write += msg.tokens.cache_write;
+        usage.reasoning += msg.tokens.reasoning;
+        usage.message_count += 1;
+        usage.cost += msg.cost;
+    }
+
+    let mut entries: Vec<BranchUsage> = branches
+        .into_values()
+        .map(|(mut usage, sessions)| {
+            usage.session_count = sessions.len() as i32;
+            usage
+        })
+        .collect();
+    entries.sort_by(|a, b| {
+        b.cost
+            .total_cmp(&a.cost)
+            .then_with(|| a.project.cmp(&b.project))
+            .then_with(|| a.branch.cmp(&b.branch))
+    });
+    let total_cost = entries.iter().map(|e| e.cost).sum::<f64>() + unattributed_cost;
+    BranchReport { entries, unattributed_messages, unattributed_cost, total_cost, processing_time_ms }
+}
+
+/// Root of the repository whose `.git` is directly in `dir`
+fn git_root(dir: &Path) -> Option<PathBuf> {
+    let git = dir.join(".git");
+    let meta = std::fs::metadata(&git).ok()?;
+    if meta.is_dir() {
+        return Some(dir.to_path_buf());
+    }
+    // Worktrees and submodules have a `.git` file: "gitdir: <path>"
+    let contents = std::fs::read_to_string(&git).ok()?;
+    let gitdir = contents.lines().find_map(|l| l.strip_prefix("gitdir:"))?.trim();
+    let gitdir = normalize_path(&dir.join(gitdir));
+    let mut components = gitdir.components().rev();
+    // <repo>/.git/worktrees/<name>
+    let is_worktree = components.nth(1).is_some_and(|c| c.as_os_str() == "worktrees")
+        && components.next().is_some_and(|c| c.as_os_str() == ".git");
+    if is_worktree {
+        gitdir.parent()?.parent()?.parent().map(Path::to_path_buf)
+    } else {
+        Some(dir.to_path_buf())
+    }
+}
+
+/// Trailing separators and `.` components removed, `..` applied lexically
+fn normalize(cwd: &str) -> PathBuf {
+    normalize_path(Path::new(cwd.trim()))
+}
+
+fn normalize_path(path: &Path) -> PathBuf {
+    use std::path::Component;
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                out.pop();
+            }
+            other => out.push(other.as_os_str()),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn path(dir: &Path) -> String {
+        dir.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn test_subdirectories_resolve_to_the_repository() {
+        let tmp = TempDir::new().unwrap();
+        let repo = tmp.path().join("mono");
+        fs::create_dir_all(repo.join(".git")).unwrap();
+        fs::create_dir_all(repo.join("packages/api/src")).unwrap();
+
+        let mut resolver = WorkspaceResolver::new();
+        let root = resolver.resolve(&path(&repo.join("packages/api/src")));
+        assert_eq!(root, Project { root: path(&repo), name: "mono".into() });
+        assert_eq!(resolver.resolve(&format!("{}/", path(&repo.join("packages")))), root);
+        assert_eq!(resolver.resolve(&path(&repo.join("packages/api/./src/.."))), root);
+    }
+
+    #[test]
+    fn test_nested_repositories_and_worktrees() {
+        let tmp = TempDir::new().unwrap();
+        let repo = tmp.path().join("app");
+        fs::create_dir_all(repo.join(".git/worktrees/feature")).unwrap();
+        // A submodule is its own project
+        let vendored = repo.join("vendor/lib");
+        fs::create_dir_all(&vendored).unwrap();
+        fs::write(vendored.join(".git"), "gitdir: ../../.git/modules/vendor/lib\n").unwrap();
+        // A linked worktree belongs to the repository it came from
+        let worktree = tmp.path().join("app-feature");
+        fs::create_dir_all(worktree.join("src")).unwrap();
+        fs::write(worktree.join(".git"), format!("gitdir: {}\n", path(&repo.join(".git/worktrees/feature")))).unwrap();
+
+        let mut resolver = WorkspaceResolver::new();
+        assert_eq!(resolver.resolve(&path(&vendored)).name, "lib");
+        assert_eq!(resolver.resolve(&path(&worktree.join("src"))).root, path(&repo));
+    }
+
+    #[test]
+    fn test_container_paths_map_to_the_host() {
+        let tmp = TempDir::new().unwrap();
+        let repo = tmp.path().join("src/app");
+        fs::create_dir_all(repo.join(".git")).unwrap();
+        let mappings = [
+            PathMapping { from: "/workspaces".into(), to: path(&tmp.path().join("src")) },
+            PathMapping { from: "/workspaces/app/".into(), to: path(&repo) },
+            PathMapping { from: "relative".into(), to: path(&repo) },
+        ];
+
+        let mut resolver = WorkspaceResolver::with_mappings(&mappings);
+        let host = resolver.resolve(&path(&repo.join("web")));
+        assert_eq!(host.root, path(&repo));
+        assert_eq!(resolver.resolve("/workspaces/app/web/src"), host);
+        // Prefixes match whole components only
+        assert_eq!(resolver.resolve("/workspaces/application").root, path(&tmp.path().join("src/application")));
+        assert_eq!(resolver.resolve("/srv/app").root, "/srv/app");
+        assert!(mappings[2].validate().is_err());
+    }
+
+    #[test]
+    fn test_project_report_groups_subdirectories() {
+        let tmp = TempDir::new().unwrap();
+        let repo = tmp.path().join("mono");
+        fs::create_dir_all(repo.join(".git")).unwrap();
+        let message = |cwd: Option<String>, cost: f64| testutil::message("s1", 1_740_823_200_000, cost).with_cwd(cwd);
+        let messages = vec![
+            message(Some(path(&repo)), 1.0),
+            message(Some(path(&repo.join("packages/web"))), 2.0),
+            message(Some(path(&tmp.path().join("scratch"))), 0.5),
+            message(None, 0.25),
+        ];
+
+        let report = project_report(&messages, 0);
+        assert_eq!(report.entries.len(), 2);
+        assert_eq!(report.entries[0].name, "mono");
+        assert_eq!(report.entries[0].message_count, 2);
+        assert_eq!(report.entries[0].paths.len(), 2);
+        assert!((report.entries[0].cost - 3.0).abs() < 1e-9);
+        assert_eq!(report.unattributed_messages, 1);
+        assert!((report.total_cost - 3.75).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_branch_report_keys_branches_by_project() {
+        let tmp = TempDir::new().unwrap();
+        let (app, docs) = (tmp.path().join("app"), tmp.path().join("docs"));
+        fs::create_dir_all(app.join(".git")).unwrap();
+        fs::create_dir_all(docs.join(".git")).unwrap();
+        let message = |cwd: &Path, branch: Option<&str>, session: &str, timestamp: i64, cost: f64| {
+            testutil::message(session, timestamp, cost)
+                .with_cwd(Some(path(cwd)))
+                .with_git_branch(branch.map(str::to_string))
+        };
+        let messages = vec![
+            message(&app.join("src"), Some("feature/export"), "s1", 1_740_823_200_000, 2.0),
+            message(&app, Some("feature/export"), "s2", 1_741_000_000_000, 1.0),
+            message(&app, Some("main"), "s3", 1_740_823_200_000, 0.5),
+            message(&docs, Some("main"), "s4", 1_740_823_200_000, 0.25),
+            message(&docs, None, "s5", 1_740_823_200_000, 0.125),
+        ];
+
+        let report = branch_report(&messages, 0);
+        assert_eq!(report.entries.len(), 3);
+        let export = &report.entries[0];
+        assert_eq!(export.branch, "feature/export");
+        assert_eq!(export.name.as_deref(), Some("app"));
+        assert_eq!(export.session_count, 2);
+        assert_eq!((export.first_date.as_str(), export.last_date.as_str()), ("2025-03-01", "2025-03-03"));
+        assert!((export.cost - 3.0).abs() < 1e-9);
+        assert_eq!(report.entries.iter().filter(|e| e.branch == "main").count(), 2);
+        assert_eq!(report.unattributed_messages, 1);
+        assert!((report.total_cost - 3.875).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_missing_directories_are_their_own_project() {
+        let tmp = TempDir::new().unwrap();
+        let gone = tmp.path().join("deleted/project");
+
+        let mut resolver = WorkspaceResolver::new();
+        let project = resolver.resolve(&path(&gone));
+        assert_eq!(project.root, path(&gone));
+        assert_eq!(project.name, "project");
+    }
+}
@@ -0,0 +1,61 @@
+import { describe, it, expect } from 'vitest';
+import { expandRollup, isRollupSubmission } from '../../src/lib/validation/rollup';
+import { validateSubmission } from '../../src/lib/validation/submission';
+
+/**
+ * Test suite for rollup submissions (pre-aggregated daily rows)
+ */
+
+function createRollup(rows: unknown[], sources = ['claude', 'codex']) {
+  return {
+    format: 'rollup',
+    version: 1,
+    meta: { generatedAt: '2024-12-04T00:00:00.000Z', version: '1.0.0' },
+    sources,
+    rows,
+  };
+}
+
+describe('Rollup submissions', () => {
+  it('should detect rollup bodies', () => {
+    expect(isRollupSubmission(createRollup([]))).toBe(true);
+    expect(isRollupSubmission({ meta: {}, contributions: [] })).toBe(false);
+    expect(isRollupSubmission(null)).toBe(false);
+  });
+
+  it('should expand rows into a valid submission', () => {
+    const expansion = expandRollup(createRollup([
+      ['2024-12-01', 'claude', 'claude-sonnet-4', 1000, 500, 100, 50, 0, 5, 1.5],
+      ['2024-12-01', 'codex', 'gpt-5', 200, 100, 0, 0, 40, 2, 0.5],
+      ['2024-12-03', 'claude', 'claude-sonnet-4', 300, 150, 0, 0, 0, 1, 0.4],
+    ]));
+
+    expect(expansion.valid).toBe(true);
+    const validation = validateSubmission(expansion.data);
+    expect(validation.errors).toEqual([]);
+    expect(validation.warnings).toEqual([]);
+
+    const data = validation.data!;
+    expect(data.meta.dateRange).toEqual({ start: '2024-12-01', end: '2024-12-03' });
+    expect(data.contributions.map(c => c.date)).toEqual(['2024-12-01', '2024-12-03']);
+    expect(data.contributions[0].sources.length).toBe(2);
+    expect(data.contributions[0].totals).toEqual({ tokens: 1990, cost: 2, messages: 7 });
+    expect(data.contributions[0].intensity).toBe(4);
+    expect(data.contributions[1].intensity).toBe(1);
+    expect(data.summary.sources).toEqual(['claude', 'codex']);
+    expect(data.summary.totalTokens).toBe(2440);
+    expect(data.years).toEqual([
+      { year: '2024', totalTokens: 2440, totalCost: 2.4, range: { start: '2024-12-01', end: '2024-12-03' } },
+    ]);
+  });
+
+  it('should reject malformed rows', () => {
+    const expansion = expandRollup(createRollup([
+      ['2024-12-01', 'claude', 'claude-sonnet-4', -1, 500, 0, 0, 0, 1, 0.1],
+      ['2024-12-01', 'zed', 'm', 1, 1, 0, 0, 0, 1, 0.1],
+    ]));
+
+    expect(expansion.valid).toBe(false);
+    expect(expansion.errors.length).toBe(2);
+  });
+});
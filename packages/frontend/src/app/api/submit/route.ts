@@ -7,6 +7,7 @@ import {
   generateSubmissionHash,
   type SubmissionData,
 } from "@/lib/validation/submission";
+import { expandRollup, isRollupSubmission } from "@/lib/validation/rollup";
 import {
   mergeSourceBreakdowns,
   recalculateDayTotals,
@@ -51,7 +52,8 @@ function normalizeSubmissionData(data: unknown): void {
  * Headers:
  *   Authorization: Bearer <api_token>
  *
- * Body: TokenContributionData JSON
+ * Body: TokenContributionData JSON, or a rollup ({ format: "rollup", rows })
+ * with one row per day, source and model (see lib/validation/rollup.ts)
  */
 export async function POST(request: Request) {
   try {
@@ -98,6 +100,17 @@ export async function POST(request: Request) {
       return NextResponse.json({ error: "Invalid JSON body" }, { status: 400 });
     }
 
+    if (isRollupSubmission(rawData)) {
+      const expansion = expandRollup(rawData);
+      if (!expansion.valid) {
+        return NextResponse.json(
+          { error: "Validation failed", details: expansion.errors },
+          { status: 400 }
+        );
+      }
+      rawData = expansion.data;
+    }
+
     normalizeSubmissionData(rawData);
 
     const validation = validateSubmission(rawData);
@@ -0,0 +1,172 @@
+/**
+ * Rollup Submissions
+ * - Compact alternative to the full TokenContributionData body: one row per
+ *   (day, source, model) and nothing derived from it
+ * - Expanded here into the regular submission shape, then validated and merged
+ *   exactly like a full submission
+ *
+ * Row layout is shared with the CLI (packages/cli/src/submit.ts)
+ */
+
+import { z } from "zod";
+
+// ============================================================================
+// SCHEMAS
+// ============================================================================
+
+const SOURCES = ["opencode", "claude", "codex", "gemini", "cursor", "amp", "droid"] as const;
+
+const count = z.number().int().min(0);
+
+/** [date, source, modelId, input, output, cacheRead, cacheWrite, reasoning, messages, cost] */
+const RollupRowSchema = z.tuple([
+  z.string().regex(/^\d{4}-\d{2}-\d{2}$/),
+  z.enum(SOURCES),
+  z.string(),
+  count,
+  count,
+  count,
+  count,
+  count,
+  count,
+  z.number().min(0),
+]);
+
+const RollupSubmissionSchema = z.object({
+  format: z.literal("rollup"),
+  version: z.literal(1),
+  meta: z.object({
+    generatedAt: z.string(),
+    version: z.string(),
+  }),
+  /** Sources covered by this submission, including ones with no rows */
+  sources: z.array(z.enum(SOURCES)),
+  rows: z.array(RollupRowSchema),
+});
+
+export type RollupSubmission = z.infer<typeof RollupSubmissionSchema>;
+
+// ============================================================================
+// EXPANSION
+// ============================================================================
+
+export interface RollupExpansion {
+  valid: boolean;
+  errors: string[];
+  /** Full submission body, ready for validateSubmission */
+  data?: unknown;
+}
+
+export function isRollupSubmission(data: unknown): boolean {
+  return !!data && typeof data === "object" && (data as Record<string, unknown>).format === "rollup";
+}
+
+/**
+ * Expand a rollup into the regular submission shape. Totals, summaries and
+ * intensities are recomputed from the rows rather than trusted from the client.
+ */
+export function expandRollup(data: unknown): RollupExpansion {
+  const parseResult = RollupSubmissionSchema.safeParse(data);
+  if (!parseResult.success) {
+    return {
+      valid: false,
+      errors: parseResult.error.errors.map((e) => `${e.path.join(".")}: ${e.message}`),
+    };
+  }
+
+  const rollup = parseResult.data;
+  const days = new Map<string, {
+    totals: { tokens: number; cost: number; messages: number };
+    tokenBreakdown: { input: number; output: number; cacheRead: number; cacheWrite: number; reasoning: number };
+    sources: unknown[];
+  }>();
+  const models = new Set<string>();
+
+  for (const [date, source, modelId, input, output, cacheRead, cacheWrite, reasoning, messages, cost] of rollup.rows) {
+    const tokens = input + output + cacheRead + cacheWrite + reasoning;
+    let day = days.get(date);
+    if (!day) {
+      day = {
+        totals: { tokens: 0, cost: 0, messages: 0 },
+        tokenBreakdown: { input: 0, output: 0, cacheRead: 0, cacheWrite: 0, reasoning: 0 },
+        sources: [],
+      };
+      days.set(date, day);
+    }
+
+    day.totals.tokens += tokens;
+    day.totals.cost += cost;
+    day.totals.messages += messages;
+    day.tokenBreakdown.input += input;
+    day.tokenBreakdown.output += output;
+    day.tokenBreakdown.cacheRead += cacheRead;
+    day.tokenBreakdown.cacheWrite += cacheWrite;
+    day.tokenBreakdown.reasoning += reasoning;
+    day.sources.push({
+      source,
+      modelId,
+      tokens: { input, output, cacheRead, cacheWrite, reasoning },
+      cost,
+      messages,
+    });
+    models.add(modelId);
+  }
+
+  const dates = [...days.keys()].sort();
+  const maxCost = Math.max(0, ...[...days.values()].map((d) => d.totals.cost));
+  const contributions = dates.map((date) => {
+    const day = days.get(date)!;
+    return { date, ...day, intensity: intensity(day.totals.cost, maxCost) };
+  });
+
+  const years = new Map<string, { totalTokens: number; totalCost: number; start: string; end: string }>();
+  for (const day of contributions) {
+    const year = day.date.slice(0, 4);
+    const summary = years.get(year) ?? { totalTokens: 0, totalCost: 0, start: day.date, end: day.date };
+    summary.totalTokens += day.totals.tokens;
+    summary.totalCost += day.totals.cost;
+    summary.end = day.date;
+    years.set(year, summary);
+  }
+
+  const totalTokens = contributions.reduce((sum, d) => sum + d.totals.tokens, 0);
+  const totalCost = contributions.reduce((sum, d) => sum + d.totals.cost, 0);
+  const activeDays = contributions.filter((d) => d.totals.tokens > 0).length;
+  const dateRange = { start: dates[0] ?? "", end: dates[dates.length - 1] ?? "" };
+
+  return {
+    valid: true,
+    errors: [],
+    data: {
+      meta: { ...rollup.meta, dateRange },
+      summary: {
+        totalTokens,
+        totalCost,
+        totalDays: contributions.length,
+        activeDays,
+        averagePerDay: activeDays > 0 ? totalCost / activeDays : 0,
+        maxCostInSingleDay: maxCost,
+        sources: rollup.sources,
+        models: [...models],
+      },
+      years: [...years.entries()].map(([year, y]) => ({
+        year,
+        totalTokens: y.totalTokens,
+        totalCost: y.totalCost,
+        range: { start: y.start, end: y.end },
+      })),
+      contributions,
+    },
+  };
+}
+
+/** Same buckets as the CLI graph: share of the most expensive day */
+function intensity(cost: number, maxCost: number): number {
+  if (maxCost <= 0) return 0;
+  const ratio = cost / maxCost;
+  if (ratio >= 0.75) return 4;
+  if (ratio >= 0.5) return 3;
+  if (ratio >= 0.25) return 2;
+  if (ratio > 0) return 1;
+  return 0;
+}
@@ -1,5 +1,3 @@
-extern crate napi_build;
-
 fn main() {
     napi_build::setup();
 }
@@ -0,0 +1,2066 @@
+//! Node.js bindings
+//!
+//! The functions `@tokscale/core` exports to JavaScript. They wrap
+//! `tokscale-core` in napi errors and async tasks; the report types they take
+//! and return live in the core, which makes them napi objects with its `napi`
+//! feature.
+
+use std::time::Instant;
+
+use rayon::prelude::*;
+use tokscale_core::*;
+use napi_derive::napi;
+
+/// Version of the native module
+#[napi]
+pub fn version() -> String {
+    env!("CARGO_PKG_VERSION").to_string()
+}
+
+/// Simple health check to verify the native module is working
+#[napi]
+pub fn health_check() -> String {
+    "tokscale-core is healthy!".to_string()
+}
+
+/// Optional functionality, by the names wrappers check for
+const FEATURES: &[&str] = &[
+    "activity",
+    "apiKeys",
+    "arrowExport",
+    "branches",
+    "budgets",
+    "chartSeries",
+    "commitCost",
+    "conformance",
+    "contextWindow",
+    "costDisplay",
+    "costDrivers",
+    "crashSafeRecording",
+    "dataVersion",
+    "diff",
+    "effectiveRates",
+    #[cfg(feature = "network")]
+    "openrouterImport",
+    "parseCache",
+    "parseDiagnostics",
+    "pricingTelemetry",
+    "projects",
+    "providerStatus",
+    #[cfg(feature = "server")]
+    "proxy",
+    #[cfg(feature = "server")]
+    "pushSync",
+    "recentBuckets",
+    "reconcile",
+    "recorder",
+    "reportSchema",
+    "resumableGraph",
+    #[cfg(feature = "server")]
+    "serve",
+    "sessionTags",
+    "settingsCheck",
+    "sourceScan",
+    "statements",
+    #[cfg(feature = "encryption")]
+    "teamEncryption",
+    "usageModes",
+];
+
+/// Features, sources, pricing providers and export formats compiled into this
+/// build, so wrappers can adapt to the installed version
+#[napi]
+pub fn capabilities() -> Capabilities {
+    let strings = |items: &[&str]| items.iter().map(|s| s.to_string()).collect();
+    Capabilities {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        features: strings(FEATURES),
+        sources: SessionType::ALL.iter().map(|s| s.name().to_string()).collect(),
+        pricing_providers: strings(pricing::SOURCES),
+        export_formats: strings(&["json", "arrow"]),
+        report_kinds: ReportKind::ALL.iter().map(|k| k.as_str().to_string()).collect(),
+        report_schema_version: REPORT_SCHEMA_VERSION.to_string(),
+    }
+}
+
+// =============================================================================
+// Shared Utilities
+// =============================================================================
+
+fn get_home_dir(home_dir_option: &Option<String>) -> napi::Result<String> {
+    home_dir_option
+        .clone()
+        .or_else(|| std::env::var(READ_ONLY_HOME_ENV).ok().filter(|h| !h.is_empty()))
+        .or_else(|| std::env::var("HOME").ok())
+        .or_else(|| dirs::home_dir().map(|p| p.to_string_lossy().into_owned()))
+        .ok_or_else(|| {
+            napi::Error::from_reason(
+                "HOME directory not specified and could not determine home directory",
+            )
+        })
+}
+
+// =============================================================================
+// Pricing-aware APIs
+// =============================================================================
+
+/// Parse session files and price their messages. Prices load while files
+/// are found and parsed (see [`pipeline`]), and are only waited for when
+/// there is something to price.
+async fn parse_all_messages_with_pricing(home_dir: &str, sources: &[String]) -> Result<Vec<UnifiedMessage>, String> {
+    let ingested = pipeline::ingest(home_dir, sources, true, pricing::PricingService::get_or_init()).await?;
+    let Some(pricing) = ingested.prices else {
+        return Ok(Vec::new());
+    };
+    let (recorded, parsed): (Vec<_>, Vec<_>) =
+        ingested.parsed.into_iter().partition(|(session_type, _)| *session_type == SessionType::Recorded);
+    let recorded = sessions::recorded::dedup(recorded.into_iter().flat_map(|(_, messages)| messages).collect());
+    let mut messages: Vec<UnifiedMessage> = parsed
+        .into_par_iter()
+        .chain(rayon::iter::once((SessionType::Recorded, recorded)))
+        .flat_map(|(session_type, messages)| {
+            messages
+                .into_iter()
+                .map(|msg| apply_pricing(session_type, msg, &pricing))
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    openrouter_import::drop_replaced(&mut messages, |msg| openrouter_import::Usage {
+        provider: msg.provider_id.as_str(),
+        model: &msg.model_id,
+        timestamp: msg.timestamp,
+        completion: msg.tokens.output + msg.tokens.reasoning,
+        billed: msg.billed,
+    });
+    Ok(messages)
+}
+
+/// Parse one session file and recalculate its costs with our pricing data
+fn parse_file_with_pricing(
+    session_type: SessionType,
+    path: &std::path::Path,
+    pricing: &pricing::PricingService,
+) -> Vec<UnifiedMessage> {
+    parse_file(session_type, path)
+        .into_iter()
+        .map(|msg| apply_pricing(session_type, msg, pricing))
+        .collect()
+}
+
+/// Get model usage report with pricing calculation
+#[napi]
+pub async fn get_model_report(options: ReportOptions) -> napi::Result<ModelReport> {
+    let start = Instant::now();
+
+    let home_dir = get_home_dir(&options.home_dir)?;
+
+    let sources = options.sources.clone().unwrap_or_else(|| {
+        vec![
+            "opencode".to_string(),
+            "claude".to_string(),
+            "codex".to_string(),
+            "gemini".to_string(),
+            "cursor".to_string(),
+            "amp".to_string(),
+            "droid".to_string(),
+            "cline".to_string(),
+            "roocode".to_string(),
+            "recorded".to_string(),
+        ]
+    });
+
+    let all_messages = parse_all_messages_with_pricing(&home_dir, &sources).await.map_err(napi::Error::from_reason)?;
+
+    Ok(build_model_report(all_messages, &options, start))
+}
+
+/// Helper struct for aggregating monthly data (avoids clippy::type_complexity)
+#[derive(Default)]
+struct MonthAggregator {
+    models: std::collections::BTreeSet<String>,
+    input: i64,
+    output: i64,
+    cache_read: i64,
+    cache_write: i64,
+    message_count: i32,
+    cost: f64,
+}
+
+/// Report of months aggregated in `month_map`, oldest first, unrounded
+fn finish_monthly(
+    month_map: std::collections::HashMap<String, MonthAggregator>,
+    start: Instant,
+    summary: Option<RunSummary>,
+) -> MonthlyReport {
+    let mut entries: Vec<MonthlyUsage> = month_map
+        .into_iter()
+        .map(|(month, agg)| MonthlyUsage {
+            month,
+            models: agg.models.into_iter().collect(),
+            input: agg.input,
+            output: agg.output,
+            cache_read: agg.cache_read,
+            cache_write: agg.cache_write,
+            message_count: agg.message_count,
+            cost: agg.cost,
+            cost_share: 0.0,
+            token_share: 0.0,
+        })
+        .collect();
+    entries.sort_by(|a, b| a.month.cmp(&b.month));
+
+    let mut report = MonthlyReport {
+        total_input: entries.iter().map(|e| e.input).sum(),
+        total_output: entries.iter().map(|e| e.output).sum(),
+        total_cache_read: entries.iter().map(|e| e.cache_read).sum(),
+        total_cache_write: entries.iter().map(|e| e.cache_write).sum(),
+        total_messages: entries.iter().map(|e| e.message_count).sum(),
+        total_cost: entries.iter().map(|e| e.cost).sum(),
+        entries,
+        processing_time_ms: start.elapsed().as_millis() as u32,
+        summary,
+    };
+    shares::apply_to_monthly_report(&mut report);
+    report
+}
+
+/// Get monthly usage report with pricing calculation
+#[napi]
+pub async fn get_monthly_report(options: ReportOptions) -> napi::Result<MonthlyReport> {
+    let start = Instant::now();
+
+    let home_dir = get_home_dir(&options.home_dir)?;
+
+    let sources = options.sources.clone().unwrap_or_else(|| {
+        vec![
+            "opencode".to_string(),
+            "claude".to_string(),
+            "codex".to_string(),
+            "gemini".to_string(),
+            "cursor".to_string(),
+            "amp".to_string(),
+            "droid".to_string(),
+            "cline".to_string(),
+            "roocode".to_string(),
+            "recorded".to_string(),
+        ]
+    });
+
+    let all_messages = parse_all_messages_with_pricing(&home_dir, &sources).await.map_err(napi::Error::from_reason)?;
+
+    // Apply date filters
+    let filtered = filter_messages_for_report(all_messages, &options);
+
+    // Aggregate by month
+    let mut month_map: std::collections::HashMap<String, MonthAggregator> =
+        std::collections::HashMap::new();
+
+    for msg in filtered {
+        // Extract month from date (YYYY-MM-DD -> YYYY-MM)
+        let month = if msg.date.len() >= 7 {
+            msg.date[..7].to_string()
+        } else {
+            continue;
+        };
+
+        let entry = month_map.entry(month).or_default();
+
+        entry.models.insert(msg.model_id.clone());
+        entry.input += msg.tokens.input;
+        entry.output += msg.tokens.output;
+        entry.cache_read += msg.tokens.cache_read;
+        entry.cache_write += msg.tokens.cache_write;
+        entry.message_count += 1;
+        entry.cost += msg.cost;
+    }
+
+    let mut report = finish_monthly(month_map, start, None);
+    cost_display::settings().apply_to_monthly_report(&mut report);
+    Ok(report)
+}
+
+/// Generate graph data with pricing calculation
+#[napi]
+pub async fn generate_graph_with_pricing(options: ReportOptions) -> napi::Result<GraphResult> {
+    let start = Instant::now();
+
+    let home_dir = get_home_dir(&options.home_dir)?;
+
+    let sources = options.sources.clone().unwrap_or_else(|| {
+        vec![
+            "opencode".to_string(),
+            "claude".to_string(),
+            "codex".to_string(),
+            "gemini".to_string(),
+            "cursor".to_string(),
+            "amp".to_string(),
+            "droid".to_string(),
+            "cline".to_string(),
+            "roocode".to_string(),
+            "recorded".to_string(),
+        ]
+    });
+
+    let all_messages = parse_all_messages_with_pricing(&home_dir, &sources).await.map_err(napi::Error::from_reason)?;
+
+    // Apply date filters
+    let filtered = filter_messages_for_report(all_messages, &options);
+
+    // Aggregate by date
+    let contributions = aggregator::aggregate_by_date(filtered);
+
+    // Generate result
+    let processing_time_ms = start.elapsed().as_millis() as u32;
+    let mut result = aggregator::generate_graph_result(contributions, processing_time_ms);
+    cost_display::settings().apply_to_graph(&mut result);
+
+    Ok(result)
+}
+
+/// Generate graph data with pricing, checkpointing progress so an interrupted
+/// first ingest of a large history resumes instead of starting over
+#[napi]
+pub async fn generate_graph_resumable(options: ReportOptions) -> napi::Result<GraphResult> {
+    let start = Instant::now();
+
+    let home_dir = get_home_dir(&options.home_dir)?;
+    let sources = options.sources.clone().unwrap_or_default();
+
+    // Look for session files while prices load
+    let (pricing, files) =
+        tokio::join!(pricing::PricingService::get_or_init(), pipeline::discover(&home_dir, &sources));
+    let pricing = pricing.map_err(napi::Error::from_reason)?;
+    let scope = format!(
+        "{}|{:?}|{:?}|{:?}|{:?}|{:?}",
+        home_dir, sources, options.year, options.since, options.until, options.exclude_estimated_timestamps
+    );
+
+    let checkpoint_path = chunked::ChunkedAggregator::default_checkpoint_path();
+    let outcome = chunked::ChunkedAggregator::new(checkpoint_path)
+        .with_scope(scope)
+        .run(&files, |session_type, path| {
+            let messages = parse_file_with_pricing(session_type, path, &pricing);
+            filter_messages_for_report(messages, &options)
+        })
+        .map_err(|e| napi::Error::from_reason(format!("Failed to write checkpoint: {}", e)))?;
+
+    if outcome.files_resumed > 0 {
+        eprintln!(
+            "[tokscale] resumed aggregation: {} files from checkpoint, {} parsed",
+            outcome.files_resumed, outcome.files_parsed
+        );
+    }
+
+    let processing_time_ms = start.elapsed().as_millis() as u32;
+    let mut result = aggregator::generate_graph_result(outcome.contributions, processing_time_ms);
+    cost_display::settings().apply_to_graph(&mut result);
+
+    Ok(result)
+}
+
+// =============================================================================
+// Two-Phase Processing Functions (for parallel execution optimization)
+// =============================================================================
+
+/// Prices archived as of `as_of` (YYYY-MM-DD), or the current ones. Reports
+/// render without current prices when they can't be loaded, flagged in the
+/// run summary (see [`degradation`]).
+async fn pricing_as_of(as_of: Option<&str>) -> napi::Result<std::sync::Arc<pricing::PricingService>> {
+    match as_of {
+        Some(date) => pricing::PricingService::as_of(date)
+            .map(std::sync::Arc::new)
+            .map_err(|e| napi::Error::from_reason(format!("prices as of {}: {}", date, e))),
+        None => Ok(pricing::PricingService::get_or_unavailable().await),
+    }
+}
+
+/// Parse local sources only (OpenCode, Claude, Codex, Gemini - NO Cursor)
+/// This can run in parallel with network operations (Cursor sync, pricing fetch)
+#[napi]
+pub fn parse_local_sources(options: LocalParseOptions) -> napi::Result<ParsedMessages> {
+    let home_dir = get_home_dir(&options.home_dir)?;
+    Ok(parse_local(&home_dir, &options))
+}
+
+/// Finalize model report: apply pricing to local messages, add Cursor, aggregate
+#[napi]
+pub async fn finalize_report(options: FinalizeReportOptions) -> napi::Result<ModelReport> {
+    let start = Instant::now();
+
+    let home_dir = get_home_dir(&options.home_dir)?;
+
+    let pricing = pricing_as_of(options.as_of.as_deref()).await?;
+
+    let mut files = options.local_messages.files.clone().unwrap_or_default();
+
+    // Convert local messages and apply pricing
+    let mut all_messages: Vec<UnifiedMessage> = options
+        .local_messages
+        .messages
+        .iter()
+        .map(|msg| price_parsed(msg, &pricing))
+        .collect();
+
+    // Add Cursor messages if enabled
+    if options.include_cursor {
+        let cursor_cache_dir = format!("{}/.config/tokscale/cursor-cache", home_dir);
+        let cursor_files = scanner::scan_directory(&cursor_cache_dir, "*.csv");
+
+        let cursor_messages: Vec<Vec<UnifiedMessage>> = cursor_files
+            .par_iter()
+            .map(|path| {
+                parse_file(SessionType::Cursor, path)
+                    .into_iter()
+                    .map(|mut msg| {
+                        let csv_cost = msg.cost;
+                        let calculated_cost = pricing.calculate_cost_with_provider(
+                            &msg.model_id,
+                            Some(msg.provider_id.as_str()),
+                            msg.tokens.input,
+                            msg.tokens.output,
+                            msg.tokens.cache_read,
+                            msg.tokens.cache_write,
+                            msg.tokens.reasoning,
+                        );
+                        msg.cost = if calculated_cost > 0.0 {
+                            calculated_cost
+                        } else {
+                            csv_cost
+                        };
+                        msg
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+
+        files.push(run_summary::count_files(SessionType::Cursor.name(), &cursor_messages, false));
+        all_messages.extend(cursor_messages.into_iter().flatten());
+    }
+
+    if let Some(as_of) = &options.as_of {
+        all_messages.retain(|m| m.date.as_str() <= as_of.as_str());
+    }
+
+    // Free tiers are used up over the whole history, then date filters apply
+    let mut covered = free_tier_coverage(&all_messages);
+    retain_with_coverage(&mut all_messages, &mut covered, |m| {
+        in_dates(&m.date, options.since.as_deref(), options.until.as_deref(), options.year.as_deref())
+    });
+
+    let summary = run_summary::build(
+        &all_messages,
+        files,
+        options.local_messages.deduped.unwrap_or(0) as i64,
+        &pricing,
+        chrono::Utc::now().timestamp() as u64,
+    );
+    let mut report = aggregate_covered_models(&all_messages, &covered, start);
+    report.summary = Some(summary);
+    if options.families == Some(true) {
+        report.families = Some(families::rollup(&report, &pricing::normalize::NormalizeRules::load()));
+    }
+    cost_display::settings().apply_to_model_report(&mut report);
+    Ok(report)
+}
+
+/// Finalize monthly report
+#[napi]
+pub async fn finalize_monthly_report(options: FinalizeMonthlyOptions) -> napi::Result<MonthlyReport> {
+    let start = Instant::now();
+
+    let home_dir = get_home_dir(&options.home_dir)?;
+
+    let pricing = pricing_as_of(options.as_of.as_deref()).await?;
+
+    let mut files = options.local_messages.files.clone().unwrap_or_default();
+
+    // Convert local messages and apply pricing
+    let mut all_messages: Vec<UnifiedMessage> = options
+        .local_messages
+        .messages
+        .iter()
+        .map(|msg| price_parsed(msg, &pricing))
+        .collect();
+
+    // Add Cursor messages if enabled
+    if options.include_cursor {
+        let cursor_cache_dir = format!("{}/.config/tokscale/cursor-cache", home_dir);
+        let cursor_files = scanner::scan_directory(&cursor_cache_dir, "*.csv");
+
+        let cursor_messages: Vec<Vec<UnifiedMessage>> = cursor_files
+            .par_iter()
+            .map(|path| {
+                parse_file(SessionType::Cursor, path)
+                    .into_iter()
+                    .map(|mut msg| {
+                        let csv_cost = msg.cost;
+                        let calculated_cost = pricing.calculate_cost_with_provider(
+                            &msg.model_id,
+                            Some(msg.provider_id.as_str()),
+                            msg.tokens.input,
+                            msg.tokens.output,
+                            msg.tokens.cache_read,
+                            msg.tokens.cache_write,
+                            msg.tokens.reasoning,
+                        );
+                        msg.cost = if calculated_cost > 0.0 {
+                            calculated_cost
+                        } else {
+                            csv_cost
+                        };
+                        msg
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+
+        files.push(run_summary::count_files(SessionType::Cursor.name(), &cursor_messages, false));
+        all_messages.extend(cursor_messages.into_iter().flatten());
+    }
+
+    // Apply date filters
+    if let Some(year) = &options.year {
+        let year_prefix = format!("{}-", year);
+        all_messages.retain(|m| m.date.starts_with(&year_prefix));
+    }
+    if let Some(since) = &options.since {
+        all_messages.retain(|m| m.date.as_str() >= since.as_str());
+    }
+    if let Some(until) = &options.until {
+        all_messages.retain(|m| m.date.as_str() <= until.as_str());
+    }
+    if let Some(as_of) = &options.as_of {
+        all_messages.retain(|m| m.date.as_str() <= as_of.as_str());
+    }
+
+    let summary = run_summary::build(
+        &all_messages,
+        files,
+        options.local_messages.deduped.unwrap_or(0) as i64,
+        &pricing,
+        chrono::Utc::now().timestamp() as u64,
+    );
+
+    // Aggregate by month
+    let mut month_map: std::collections::HashMap<String, MonthAggregator> =
+        std::collections::HashMap::new();
+
+    for msg in all_messages {
+        let month = if msg.date.len() >= 7 {
+            msg.date[..7].to_string()
+        } else {
+            continue;
+        };
+
+        let entry = month_map.entry(month).or_default();
+        entry.models.insert(msg.model_id.clone());
+        entry.input += msg.tokens.input;
+        entry.output += msg.tokens.output;
+        entry.cache_read += msg.tokens.cache_read;
+        entry.cache_write += msg.tokens.cache_write;
+        entry.message_count += 1;
+        entry.cost += msg.cost;
+    }
+
+    let mut report = finish_monthly(month_map, start, Some(summary));
+    cost_display::settings().apply_to_monthly_report(&mut report);
+    Ok(report)
+}
+
+/// Finalize graph
+#[napi]
+pub async fn finalize_graph(options: FinalizeGraphOptions) -> napi::Result<GraphResult> {
+    let start = Instant::now();
+
+    let home_dir = get_home_dir(&options.home_dir)?;
+
+    let pricing = pricing::PricingService::get_or_init()
+        .await
+        .map_err(napi::Error::from_reason)?;
+
+    // Convert local messages and apply pricing
+    let mut all_messages: Vec<UnifiedMessage> = options
+        .local_messages
+        .messages
+        .iter()
+        .map(|msg| price_parsed(msg, &pricing))
+        .collect();
+
+    // Add Cursor messages if enabled
+    if options.include_cursor {
+        let cursor_cache_dir = format!("{}/.config/tokscale/cursor-cache", home_dir);
+        let cursor_files = scanner::scan_directory(&cursor_cache_dir, "*.csv");
+
+        let cursor_messages: Vec<UnifiedMessage> = cursor_files
+            .par_iter()
+            .flat_map(|path| {
+                parse_file(SessionType::Cursor, path)
+                    .into_iter()
+                    .map(|mut msg| {
+                        let csv_cost = msg.cost;
+                        let calculated_cost = pricing.calculate_cost_with_provider(
+                            &msg.model_id,
+                            Some(msg.provider_id.as_str()),
+                            msg.tokens.input,
+                            msg.tokens.output,
+                            msg.tokens.cache_read,
+                            msg.tokens.cache_write,
+                            msg.tokens.reasoning,
+                        );
+                        msg.cost = if calculated_cost > 0.0 {
+                            calculated_cost
+                        } else {
+                            csv_cost
+                        };
+                        msg
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+
+        all_messages.extend(cursor_messages);
+    }
+
+    // Apply date filters
+    if let Some(year) = &options.year {
+        let year_prefix = format!("{}-", year);
+        all_messages.retain(|m| m.date.starts_with(&year_prefix));
+    }
+    if let Some(since) = &options.since {
+        all_messages.retain(|m| m.date.as_str() >= since.as_str());
+    }
+    if let Some(until) = &options.until {
+        all_messages.retain(|m| m.date.as_str() <= until.as_str());
+    }
+
+    // Aggregate by date
+    let contributions = aggregator::aggregate_by_date(all_messages);
+
+    // Generate result
+    let processing_time_ms = start.elapsed().as_millis() as u32;
+    let mut result = aggregator::generate_graph_result(contributions, processing_time_ms);
+    cost_display::settings().apply_to_graph(&mut result);
+
+    Ok(result)
+}
+
+/// One session's usage as a redacted bundle: token counts, costs and timing,
+/// without content, paths or raw IDs
+#[cfg(feature = "encryption")]
+#[napi]
+pub async fn share_session(options: ShareSessionOptions) -> napi::Result<SessionBundle> {
+    let home_dir = get_home_dir(&options.home_dir)?;
+    let sources = options.sources.unwrap_or_default();
+    let messages = parse_all_messages_with_pricing(&home_dir, &sources).await.map_err(napi::Error::from_reason)?;
+    let created_at = chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+    session_bundle::bundle(&messages, &options.session_id, created_at).map_err(napi::Error::from_reason)
+}
+
+/// Read a bundle written by `shareSession`
+#[cfg(feature = "encryption")]
+#[napi]
+pub fn inspect_session_bundle(json: String) -> napi::Result<SessionBundle> {
+    session_bundle::read(&json).map_err(napi::Error::from_reason)
+}
+
+/// Finalize both report and graph in a single call with shared pricing
+/// This ensures consistent costs between report and graph data
+#[napi]
+pub async fn finalize_report_and_graph(options: FinalizeReportOptions) -> napi::Result<ReportAndGraph> {
+    let start = Instant::now();
+
+    let home_dir = get_home_dir(&options.home_dir)?;
+    let granularity = recent_granularity(options.recent_granularity.as_deref())?;
+    let model_glob = options
+        .model
+        .as_deref()
+        .map(model_glob::ModelGlob::parse)
+        .transpose()
+        .map_err(napi::Error::from_reason)?;
+
+    // Single pricing lookup - shared by both report and graph
+    let pricing = pricing::PricingService::get_or_init()
+        .await
+        .map_err(napi::Error::from_reason)?;
+
+    // Convert local messages and apply pricing (once)
+    let mut all_messages: Vec<UnifiedMessage> = options
+        .local_messages
+        .messages
+        .iter()
+        .map(|msg| price_parsed(msg, &pricing))
+        .collect();
+
+    // Add Cursor messages if enabled
+    if options.include_cursor {
+        let cursor_cache_dir = format!("{}/.config/tokscale/cursor-cache", home_dir);
+        let cursor_files = scanner::scan_directory(&cursor_cache_dir, "*.csv");
+
+        let cursor_messages: Vec<UnifiedMessage> = cursor_files
+            .par_iter()
+            .flat_map(|path| {
+                parse_file(SessionType::Cursor, path)
+                    .into_iter()
+                    .map(|mut msg| {
+                        let csv_cost = msg.cost;
+                        let calculated_cost = pricing.calculate_cost_with_provider(
+                            &msg.model_id,
+                            Some(msg.provider_id.as_str()),
+                            msg.tokens.input,
+                            msg.tokens.output,
+                            msg.tokens.cache_read,
+                            msg.tokens.cache_write,
+                            msg.tokens.reasoning,
+                        );
+                        msg.cost = if calculated_cost > 0.0 {
+                            calculated_cost
+                        } else {
+                            csv_cost
+                        };
+                        msg
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+
+        all_messages.extend(cursor_messages);
+    }
+
+    // Free tiers are used up over the whole history, before any filter
+    let mut covered = free_tier_coverage(&all_messages);
+    if let Some(glob) = &model_glob {
+        retain_with_coverage(&mut all_messages, &mut covered, |m| glob.matches(&m.model_id));
+    }
+
+    // Recent usage is about now, so it's bucketed before date filters apply
+    let mut recent = recent::report(
+        &all_messages,
+        granularity,
+        recent::recent_days(options.recent_days),
+        chrono::Utc::now().timestamp_millis(),
+        start.elapsed().as_millis() as u32,
+    );
+
+    // Apply date filters
+    retain_with_coverage(&mut all_messages, &mut covered, |m| {
+        in_dates(&m.date, options.since.as_deref(), options.until.as_deref(), options.year.as_deref())
+    });
+
+    // --- Generate Report ---
+    let mut report = aggregate_covered_models(&all_messages, &covered, start);
+    let mut sessions = session_browser::sessions(&all_messages);
+    let messages_for_graph = all_messages;
+
+    // --- Generate Graph ---
+    let contributions = aggregator::aggregate_by_date(messages_for_graph);
+    let mut graph = aggregator::generate_graph_result(contributions, start.elapsed().as_millis() as u32);
+
+    let display = cost_display::settings();
+    display.apply_to_model_report(&mut report);
+    display.apply_to_graph(&mut graph);
+    display.apply_to_recent(&mut recent);
+    for session in &mut sessions {
+        session.cost = display.round(session.cost);
+        for point in &mut session.timeline {
+            *point = display.round(*point);
+        }
+    }
+
+    Ok(ReportAndGraph { report, graph, recent, sessions })
+}
+
+// =============================================================================
+// New Pricing API (Rust-native pricing fetching)
+// =============================================================================
+
+/// Load prices into the on-disk cache, fetching them if it is stale. The CLI
+/// runs this while it parses local sessions, so the step that prices
+/// messages afterwards reads a fresh cache instead of waiting on the network.
+#[napi]
+pub async fn preload_pricing() -> napi::Result<()> {
+    pricing::PricingService::get_or_init()
+        .await
+        .map(|_| ())
+        .map_err(napi::Error::from_reason)
+}
+
+/// Look up pricing for a model.
+///
+/// `provider` forces a pricing source ("litellm" or "openrouter"). `backend` is
+/// the provider the model ran on (e.g. "vertex_ai", "gemini"); when set and no
+/// source is forced, provider-scoped LiteLLM keys are preferred.
+#[napi]
+pub async fn lookup_pricing(
+    model_id: String,
+    provider: Option<String>,
+    backend: Option<String>,
+) -> napi::Result<PricingLookupResult> {
+    let service = pricing::PricingService::get_or_init()
+        .await
+        .map_err(napi::Error::from_reason)?;
+
+    let force_source = provider.as_deref();
+    let result = match force_source {
+        Some(_) => service.lookup_with_source(&model_id, force_source),
+        None => service.lookup_with_provider(&model_id, backend.as_deref()),
+    };
+
+    match result {
+        Some(result) => Ok(PricingLookupResult {
+            model_id,
+            matched_key: result.matched_key,
+            matched_prefix: result.matched_prefix,
+            source: result.source,
+            pricing: native_pricing(&result.pricing),
+        }),
+        None => Err(napi::Error::from_reason(format!(
+            "Model not found: {}{}",
+            model_id,
+            force_source.map(|s| format!(" (forced source: {})", s)).unwrap_or_default()
+        ))),
+    }
+}
+
+fn native_pricing(pricing: &pricing::ModelPricing) -> NativePricing {
+    NativePricing {
+        input_cost_per_token: pricing.input_cost_per_token.unwrap_or(0.0),
+        output_cost_per_token: pricing.output_cost_per_token.unwrap_or(0.0),
+        cache_read_input_token_cost: pricing.cache_read_input_token_cost,
+        cache_creation_input_token_cost: pricing.cache_creation_input_token_cost,
+        training_cost_per_token: pricing.training_cost_per_token,
+        input_per_million: pricing.input_per_million().unwrap_or(0.0),
+        output_per_million: pricing.output_per_million().unwrap_or(0.0),
+        cache_read_per_million: pricing.cache_read_per_million(),
+        cache_write_per_million: pricing.cache_write_per_million(),
+        blended_per_million: pricing.blended_rate(pricing::rates::DEFAULT_INPUT_RATIO),
+    }
+}
+
+/// Compare models by blended price per million tokens. `inputRatio` is the
+/// share of input tokens (default 0.75).
+#[napi]
+pub async fn compare_pricing(model_ids: Vec<String>, input_ratio: Option<f64>) -> napi::Result<RateComparison> {
+    let service = pricing::PricingService::get_or_init()
+        .await
+        .map_err(napi::Error::from_reason)?;
+
+    let mut matched_keys = std::collections::HashMap::new();
+    let mut priced = Vec::new();
+    for model_id in &model_ids {
+        if let Some(result) = service.lookup_with_source(model_id, None) {
+            matched_keys.insert(model_id.clone(), result.matched_key);
+            priced.push((model_id.clone(), result.pricing));
+        }
+    }
+
+    let rows = pricing::rates::compare(&priced, input_ratio.unwrap_or(pricing::rates::DEFAULT_INPUT_RATIO));
+    let unpriced = model_ids.into_iter().filter(|id| !rows.iter().any(|row| &row.model == id)).collect();
+    Ok(RateComparison {
+        table: pricing::rates::format_comparison(&rows),
+        rows: rows
+            .into_iter()
+            .map(|row| RateComparisonRow {
+                matched_key: matched_keys.remove(&row.model).unwrap_or_default(),
+                model_id: row.model,
+                blended_per_million: row.blended,
+                relative: row.relative,
+            })
+            .collect(),
+        unpriced,
+    })
+}
+
+/// Format a USD-per-million-tokens rate, e.g. "$3.00 / 1M tokens"
+#[napi]
+pub fn format_price_per_million(rate: f64) -> String {
+    pricing::rates::format_per_million(rate)
+}
+
+/// Format a cost using the rounding, precision and cents settings, so every
+/// frontend displays amounts the same way
+#[napi]
+pub fn format_cost(cost: f64) -> String {
+    cost_display::settings().format(cost)
+}
+
+// =============================================================================
+// Pricing health
+// =============================================================================
+
+/// Fetch each pricing source afresh, bypassing the cache. Provider status
+/// pages are checked when a fetch fails, or always with `providerStatus`, so
+/// a failure can be told apart from the provider being down.
+#[napi]
+pub async fn check_pricing_health(provider_status: Option<bool>) -> napi::Result<PricingHealth> {
+    let checks = pricing::check_sources().await;
+    let any_failed = checks.iter().any(|check| check.result.is_err());
+    let providers = if any_failed || provider_status.unwrap_or(false) {
+        provider_status::check().await
+    } else {
+        Vec::new()
+    };
+
+    let sources = checks
+        .into_iter()
+        .map(|check| {
+            let diagnosis = (check.result.is_err() && !providers.is_empty())
+                .then(|| provider_status::diagnose(Some(check.name.as_str()), &providers));
+            PricingSourceCheck {
+                ok: check.result.is_ok(),
+                model_count: check.result.as_ref().map_or(0, |count| *count as u32),
+                error: check.result.err(),
+                duration_ms: check.elapsed.as_millis() as u32,
+                cached_at: check
+                    .cached_at
+                    .and_then(|secs| chrono::DateTime::from_timestamp(secs as i64, 0))
+                    .map(|time| time.to_rfc3339_opts(chrono::SecondsFormat::Secs, true)),
+                name: check.name,
+                required: check.required,
+                diagnosis,
+            }
+        })
+        .collect();
+    Ok(PricingHealth { sources, providers })
+}
+
+/// Cache hits and misses, fetches, requests, retries and failures per pricing
+/// source in this process, with time spent on the network versus parsing
+#[napi]
+pub fn get_pricing_telemetry() -> Vec<PricingTelemetry> {
+    pricing::telemetry::snapshot()
+        .into_iter()
+        .map(|(source, t)| PricingTelemetry {
+            source,
+            cache_hits: t.cache_hits as u32,
+            cache_misses: t.cache_misses as u32,
+            fetches: t.fetches as u32,
+            failures: t.failures as u32,
+            requests: t.requests as u32,
+            retries: t.retries as u32,
+            bytes: t.bytes as i64,
+            fetch_ms: t.fetch_time.as_millis() as u32,
+            network_ms: t.network_time.as_millis() as u32,
+            parse_ms: t.parse_time.as_millis() as u32,
+        })
+        .collect()
+}
+
+// =============================================================================
+// Settings
+// =============================================================================
+
+/// Re-read settings.json if it changed since it was last loaded
+#[napi]
+pub fn reload_settings() -> SettingsReload {
+    let (status, error) = match config::reload() {
+        config::Reload::Unchanged => ("unchanged", None),
+        config::Reload::Applied => ("applied", None),
+        config::Reload::Rejected(reason) => ("rejected", Some(reason)),
+    };
+    SettingsReload { status: status.to_string(), error }
+}
+
+/// Check settings.json (or `path`) for unknown keys, mistyped values and
+/// wildcards in substring patterns, with line and column for each
+#[napi]
+pub fn check_settings(path: Option<String>) -> napi::Result<SettingsCheck> {
+    let path = path.map(std::path::PathBuf::from).unwrap_or_else(config::settings_path);
+    let content = match std::fs::read_to_string(&path) {
+        Ok(content) => Some(content),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => return Err(napi::Error::from_reason(format!("{}: {}", path.display(), e))),
+    };
+    let issues = content
+        .as_deref()
+        .map(config_schema::check)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|issue| SettingsIssue {
+            severity: issue.severity.as_str().to_string(),
+            path: issue.path,
+            line: issue.line as u32,
+            column: issue.column as u32,
+            message: issue.message,
+        })
+        .collect();
+    Ok(SettingsCheck { path: path.display().to_string(), exists: content.is_some(), issues })
+}
+
+/// Apply edits to settings.json without a restart by polling it every
+/// `intervalMs` (default 2000) on a background thread
+#[napi]
+pub fn watch_settings(interval_ms: Option<u32>) {
+    config::watch(std::time::Duration::from_millis(interval_ms.unwrap_or(2000).max(100) as u64));
+}
+
+// =============================================================================
+// API keys
+// =============================================================================
+
+/// Store an API key (e.g. for "openrouter") in the OS keychain
+#[napi]
+pub fn store_api_key(name: String, key: String) -> napi::Result<()> {
+    secrets::store(&name, &key).map_err(napi::Error::from_reason)
+}
+
+/// Whether an API key is configured, and where
+#[napi]
+pub fn api_key_status(name: String) -> ApiKeyStatus {
+    ApiKeyStatus {
+        backend: secrets::resolve(&name).map(|(_, backend)| backend.as_str().to_string()),
+        env_var: secrets::fallback_env_var(&name),
+        name,
+    }
+}
+
+// =============================================================================
+// Team encryption
+// =============================================================================
+
+/// Generate a team key pair (same format as `age-keygen`)
+#[cfg(feature = "encryption")]
+#[napi]
+pub fn generate_team_key() -> napi::Result<TeamKey> {
+    let identity = age::Identity::generate().map_err(napi::Error::from_reason)?;
+    let recipient = identity.recipient().map_err(napi::Error::from_reason)?;
+    Ok(TeamKey {
+        public_key: recipient.to_string(),
+        secret_key: identity.to_secret_string(),
+    })
+}
+
+/// Encrypt data to one or more team public keys ("age1...")
+#[cfg(feature = "encryption")]
+#[napi]
+pub fn encrypt_for_team(
+    data: napi::bindgen_prelude::Buffer,
+    recipients: Vec<String>,
+) -> napi::Result<napi::bindgen_prelude::Buffer> {
+    let recipients = recipients
+        .iter()
+        .map(|r| age::Recipient::parse(r))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(napi::Error::from_reason)?;
+    let encrypted = age::encrypt(&data, &recipients).map_err(napi::Error::from_reason)?;
+    Ok(encrypted.into())
+}
+
+/// Decrypt team data with the contents of an age key file
+#[cfg(feature = "encryption")]
+#[napi]
+pub fn decrypt_for_team(
+    data: napi::bindgen_prelude::Buffer,
+    identities: String,
+) -> napi::Result<napi::bindgen_prelude::Buffer> {
+    let identities = age::parse_identities(&identities).map_err(napi::Error::from_reason)?;
+    let decrypted = age::decrypt(&data, &identities).map_err(napi::Error::from_reason)?;
+    Ok(decrypted.into())
+}
+
+// =============================================================================
+// Recorded usage
+// =============================================================================
+
+/// Record NDJSON usage events under ~/.config/tokscale/recorded/
+#[napi]
+pub fn record_events(ndjson: String, home_dir: Option<String>) -> napi::Result<RecordResult> {
+    let home_dir = get_home_dir(&home_dir)?;
+    let recorder = Recorder::open(&home_dir).map_err(napi::Error::from_reason)?;
+    let stats = recorder.ingest(ndjson.as_bytes()).map_err(napi::Error::from_reason)?;
+    Ok(RecordResult {
+        recorded: stats.recorded as u32,
+        errors: stats
+            .errors
+            .into_iter()
+            .map(|(line, message)| RecordError { line: line as u32, message })
+            .collect(),
+    })
+}
+
+/// Find lines in ~/.config/tokscale/recorded/ that no longer parse, such as
+/// an event torn by a crash. Reports skip them; nothing is modified.
+#[napi]
+pub fn check_recorded(home_dir: Option<String>) -> napi::Result<Vec<DamagedRecord>> {
+    let home_dir = get_home_dir(&home_dir)?;
+    let damaged = recorder::check(&recorder::recorded_dir(&home_dir)).map_err(napi::Error::from_reason)?;
+    Ok(damaged
+        .into_iter()
+        .map(|d| DamagedRecord { file: d.file.display().to_string(), line: d.line as u32, reason: d.reason })
+        .collect())
+}
+
+/// Fetch OpenRouter generations (native tokens and billed cost) and record
+/// them. Uses the "openrouter" API key (see `setSecret`).
+#[napi]
+pub async fn import_openrouter_generations(options: OpenRouterImportOptions) -> napi::Result<OpenRouterImportResult> {
+    // Imports are recorded into the home's store, which must not be another user's
+    check_writable_home("OpenRouter import").map_err(napi::Error::from_reason)?;
+    let home_dir = get_home_dir(&options.home_dir)?;
+    let (api_key, _) = secrets::resolve(openrouter_import::SECRET).ok_or_else(|| {
+        napi::Error::from_reason(format!(
+            "No OpenRouter API key: run `tokscale secrets set {}` or set {}",
+            openrouter_import::SECRET,
+            secrets::fallback_env_var(openrouter_import::SECRET)
+        ))
+    })?;
+    let mut ids = options.ids.unwrap_or_default();
+    if let Some(text) = &options.ids_text {
+        ids.extend(openrouter_import::generation_ids(text));
+    }
+    let stats = openrouter_import::import(&home_dir, &api_key, ids).await.map_err(napi::Error::from_reason)?;
+    Ok(OpenRouterImportResult {
+        imported: stats.imported as u32,
+        skipped: stats.skipped as u32,
+        failed: stats.failed.into_iter().map(|(id, reason)| ImportFailure { id, reason }).collect(),
+    })
+}
+
+// =============================================================================
+// Metering proxy
+// =============================================================================
+
+/// Run the metering proxy; resolves only if it fails
+#[cfg(feature = "server")]
+#[napi]
+pub async fn run_proxy(options: ProxyOptions) -> napi::Result<()> {
+    let home_dir = get_home_dir(&options.home_dir)?;
+    let host = options.host.as_deref().unwrap_or("127.0.0.1");
+    let port = options.port.unwrap_or(proxy::DEFAULT_PORT as u32);
+    let listen = format!("{}:{}", host, port)
+        .parse()
+        .map_err(|_| napi::Error::from_reason(format!("Invalid listen address {}:{}", host, port)))?;
+
+    let defaults = ProxyConfig::default();
+    let config = ProxyConfig {
+        listen,
+        openai_upstream: options.openai_upstream.unwrap_or(defaults.openai_upstream),
+        anthropic_upstream: options.anthropic_upstream.unwrap_or(defaults.anthropic_upstream),
+    };
+    let recorder = Recorder::open(&home_dir).map_err(napi::Error::from_reason)?;
+    let proxy = Proxy::bind(config, recorder).await.map_err(napi::Error::from_reason)?;
+    proxy.serve().await.map_err(napi::Error::from_reason)
+}
+
+// =============================================================================
+// Serve mode
+// =============================================================================
+
+/// Serve reports and accept recorded events over HTTP; resolves only if it fails
+#[cfg(feature = "server")]
+#[napi]
+pub async fn run_server(options: ServeOptions) -> napi::Result<()> {
+    let home_dir = get_home_dir(&options.home_dir)?;
+    let host = options.host.as_deref().unwrap_or("127.0.0.1");
+    let port = options.port.unwrap_or(serve::DEFAULT_PORT as u32);
+    let listen = format!("{}:{}", host, port)
+        .parse()
+        .map_err(|_| napi::Error::from_reason(format!("Invalid listen address {}:{}", host, port)))?;
+
+    let mut config = ServeConfig { listen, ..ServeConfig::new(home_dir) };
+    if let Some(per_minute) = options.rate_limit_per_minute {
+        config.rate_limit_per_minute = Some(per_minute);
+    }
+    if options.emit_events.unwrap_or(false) {
+        config.sinks.push(std::sync::Arc::new(serve::sinks::ConfiguredSink::stdout()));
+    }
+    let server = Server::bind(config).await.map_err(napi::Error::from_reason)?;
+    server.serve().await.map_err(napi::Error::from_reason)
+}
+
+#[cfg(feature = "server")]
+fn serve_tokens(home_dir: &Option<String>) -> napi::Result<TokenStore> {
+    let home_dir = get_home_dir(home_dir)?;
+    TokenStore::load(serve::tokens::tokens_path(&home_dir)).map_err(napi::Error::from_reason)
+}
+
+/// Create a serve-mode API token; the returned token is not stored and cannot be shown again
+#[cfg(feature = "server")]
+#[napi]
+pub fn create_serve_token(name: String, scope: String, home_dir: Option<String>) -> napi::Result<String> {
+    let scope = Scope::parse(&scope).map_err(napi::Error::from_reason)?;
+    serve_tokens(&home_dir)?
+        .create(&name, scope)
+        .map_err(napi::Error::from_reason)
+}
+
+#[cfg(feature = "server")]
+#[napi]
+pub fn list_serve_tokens(home_dir: Option<String>) -> napi::Result<Vec<ServeToken>> {
+    Ok(serve_tokens(&home_dir)?
+        .entries()
+        .iter()
+        .map(|e| ServeToken {
+            name: e.name.clone(),
+            scope: e.scope.as_str().to_string(),
+            created_at: e.created_at.clone(),
+        })
+        .collect())
+}
+
+/// Revoke a serve-mode API token; false if there was none by that name
+#[cfg(feature = "server")]
+#[napi]
+pub fn revoke_serve_token(name: String, home_dir: Option<String>) -> napi::Result<bool> {
+    serve_tokens(&home_dir)?
+        .revoke(&name)
+        .map_err(napi::Error::from_reason)
+}
+
+/// Send local usage to a team server, uploading only what it hasn't
+/// acknowledged yet. An interrupted push continues where it stopped when run
+/// again.
+#[cfg(feature = "server")]
+#[napi]
+pub async fn push_usage(options: PushOptions) -> napi::Result<PushResult> {
+    let home_dir = get_home_dir(&options.home_dir)?;
+    let token = options.token.or_else(|| secrets::resolve(serve::sync::TOKEN_SECRET).map(|(token, _)| token));
+
+    let report_options = ReportOptions {
+        home_dir: None,
+        sources: options.sources,
+        since: options.since,
+        until: None,
+        year: None,
+        exclude_estimated_timestamps: None,
+    };
+    let messages = priced_messages_for_report(&home_dir, &report_options).await.map_err(napi::Error::from_reason)?;
+
+    let batch_size = options.batch_size.map_or(serve::sync::DEFAULT_BATCH_SIZE, |n| n as usize);
+    let stats = serve::sync::push(&options.server, token.as_deref(), &messages, batch_size)
+        .await
+        .map_err(napi::Error::from_reason)?;
+    Ok(PushResult {
+        recorded: stats.recorded as u32,
+        skipped: stats.skipped as u32,
+        unsent: stats.unsent as u32,
+        batches: stats.batches as u32,
+        watermark: stats.watermark.map(|ms| ms as f64),
+    })
+}
+
+// =============================================================================
+// Report schema
+// =============================================================================
+
+/// JSON Schema for a `--json` report: "models", "monthly" or "graph"
+#[napi]
+pub fn get_report_schema(kind: String) -> napi::Result<serde_json::Value> {
+    let kind = report_schema::ReportKind::parse(&kind)
+        .ok_or_else(|| napi::Error::from_reason(format!("Unknown report kind: {}", kind)))?;
+    Ok(report_schema::json_schema(kind))
+}
+
+/// Semver of the report schema, printed as `schemaVersion` in `--json` output
+#[napi]
+pub fn report_schema_version() -> String {
+    report_schema::SCHEMA_VERSION.to_string()
+}
+
+// =============================================================================
+// Arrow IPC export
+// =============================================================================
+
+async fn priced_messages_for_report(home_dir: &str, options: &ReportOptions) -> Result<Vec<UnifiedMessage>, String> {
+    let sources = options.sources.clone().unwrap_or_default();
+    let all_messages = parse_all_messages_with_pricing(home_dir, &sources).await?;
+    Ok(filter_messages_for_report(all_messages, options))
+}
+
+/// Parse and price all messages, returned as an Arrow IPC stream
+/// (one row per message) for `tableFromIPC` in apache-arrow
+#[napi]
+pub async fn get_messages_arrow(options: ReportOptions) -> napi::Result<napi::bindgen_prelude::Buffer> {
+    let home_dir = get_home_dir(&options.home_dir)?;
+
+    let messages = priced_messages_for_report(&home_dir, &options).await.map_err(napi::Error::from_reason)?;
+
+    Ok(arrow::messages_to_ipc(&messages).into())
+}
+
+/// Daily contributions as an Arrow IPC stream, one row per (date, source, model)
+#[napi]
+pub async fn generate_graph_arrow(options: ReportOptions) -> napi::Result<napi::bindgen_prelude::Buffer> {
+    let home_dir = get_home_dir(&options.home_dir)?;
+
+    let messages = priced_messages_for_report(&home_dir, &options).await.map_err(napi::Error::from_reason)?;
+    let contributions = aggregator::aggregate_by_date(messages);
+
+    Ok(arrow::contributions_to_ipc(&contributions).into())
+}
+
+// =============================================================================
+// Chart series
+// =============================================================================
+
+/// Per-message time series downsampled to at most `points` points
+#[napi]
+pub async fn get_chart_series(options: ChartSeriesOptions) -> napi::Result<ChartSeries> {
+    let start = Instant::now();
+
+    let metric = match options.metric.as_deref() {
+        None => downsample::Metric::Tokens,
+        Some(s) => downsample::Metric::parse(s)
+            .ok_or_else(|| napi::Error::from_reason(format!("Unknown chart metric: {}", s)))?,
+    };
+    let method = match options.method.as_deref() {
+        None => downsample::Method::Lttb,
+        Some(s) => downsample::Method::parse(s)
+            .ok_or_else(|| napi::Error::from_reason(format!("Unknown downsampling method: {}", s)))?,
+    };
+    let target = options.points.unwrap_or(500) as usize;
+
+    let home_dir = get_home_dir(&options.home_dir)?;
+    let report_options = ReportOptions {
+        home_dir: options.home_dir,
+        sources: options.sources,
+        since: options.since,
+        until: options.until,
+        year: options.year,
+        exclude_estimated_timestamps: options.exclude_estimated_timestamps,
+    };
+
+    let messages = priced_messages_for_report(&home_dir, &report_options).await.map_err(napi::Error::from_reason)?;
+
+    let points = downsample::message_points(&messages, metric);
+    let sampled = downsample::downsample(&points, target, method);
+
+    Ok(ChartSeries {
+        metric: metric.as_str().to_string(),
+        method: method.as_str().to_string(),
+        points: sampled
+            .into_iter()
+            .map(|p| ChartPoint { timestamp: p.timestamp, value: p.value })
+            .collect(),
+        total_points: points.len() as u32,
+        processing_time_ms: start.elapsed().as_millis() as u32,
+    })
+}
+
+// =============================================================================
+// Recent usage
+// =============================================================================
+
+fn recent_granularity(granularity: Option<&str>) -> napi::Result<recent::Granularity> {
+    match granularity {
+        None => Ok(recent::Granularity::Hour),
+        Some(s) => recent::Granularity::parse(s)
+            .ok_or_else(|| napi::Error::from_reason(format!("Unknown bucket granularity: {}", s))),
+    }
+}
+
+/// Hourly or 15-minute usage over the last few days, and the current burn
+/// rate
+#[napi]
+pub async fn get_recent_usage(options: RecentUsageOptions) -> napi::Result<RecentUsage> {
+    let start = Instant::now();
+    let granularity = recent_granularity(options.granularity.as_deref())?;
+    let days = recent::recent_days(options.days);
+
+    let home_dir = get_home_dir(&options.home_dir)?;
+    let now = chrono::Utc::now();
+    let report_options = ReportOptions {
+        home_dir: options.home_dir,
+        sources: options.sources,
+        // A day of slack, since message dates may be local rather than UTC
+        since: Some((now - chrono::Duration::days(days as i64 + 1)).format("%Y-%m-%d").to_string()),
+        until: None,
+        year: None,
+        exclude_estimated_timestamps: Some(true),
+    };
+
+    let messages = priced_messages_for_report(&home_dir, &report_options).await.map_err(napi::Error::from_reason)?;
+
+    let mut recent = recent::report(
+        &messages,
+        granularity,
+        days,
+        now.timestamp_millis(),
+        start.elapsed().as_millis() as u32,
+    );
+    cost_display::settings().apply_to_recent(&mut recent);
+    Ok(recent)
+}
+
+// =============================================================================
+// Effective rates
+// =============================================================================
+
+/// Realized USD per million tokens per model (total cost over total tokens,
+/// also split into input and output), next to list prices
+#[napi]
+pub async fn get_effective_rates(options: ReportOptions) -> napi::Result<EffectiveRateReport> {
+    let start = Instant::now();
+    let home_dir = get_home_dir(&options.home_dir)?;
+
+    let messages = priced_messages_for_report(&home_dir, &options).await.map_err(napi::Error::from_reason)?;
+    let pricing = pricing::PricingService::get_or_init()
+        .await
+        .map_err(napi::Error::from_reason)?;
+
+    let entries = effective_rates::build(&messages, Some(&pricing));
+    let mut report = effective_rates::report(entries, start.elapsed().as_millis() as u32);
+    let display = cost_display::settings();
+    for entry in &mut report.entries {
+        entry.cost = display.round(entry.cost);
+    }
+    report.total_cost = display.round(report.total_cost);
+    Ok(report)
+}
+
+// =============================================================================
+// Session activity
+// =============================================================================
+
+/// Active hours and cost per active hour. Gaps between messages longer than
+/// the idle threshold don't count, so a session left open overnight counts
+/// only the time it was being used.
+#[napi]
+pub async fn get_session_activity(options: ActivityOptions) -> napi::Result<ActivityReport> {
+    let start = Instant::now();
+    let home_dir = get_home_dir(&options.home_dir)?;
+    let idle_minutes = activity::idle_minutes(options.idle_minutes);
+    if let Some(tag) = &options.tag {
+        session_tags::validate_tag(tag).map_err(napi::Error::from_reason)?;
+    }
+
+    let report_options = ReportOptions {
+        home_dir: options.home_dir,
+        sources: options.sources,
+        since: options.since,
+        until: options.until,
+        year: options.year,
+        // Synthesized timestamps would make up durations
+        exclude_estimated_timestamps: Some(true),
+    };
+    let messages = priced_messages_for_report(&home_dir, &report_options).await.map_err(napi::Error::from_reason)?;
+
+    let mut sessions = activity::sessions(&messages, idle_minutes, &config::settings().session_tags);
+    if let Some(tag) = &options.tag {
+        sessions.retain(|session| session.tags.contains(tag));
+    }
+    let mut report = activity::report(sessions, idle_minutes, start.elapsed().as_millis() as u32);
+    let display = cost_display::settings();
+    for session in &mut report.sessions {
+        session.cost = display.round(session.cost);
+    }
+    report.cost = display.round(report.cost);
+    report.cost_per_active_hour = display.round(report.cost_per_active_hour);
+    Ok(report)
+}
+
+// =============================================================================
+// Cost efficiency
+// =============================================================================
+
+/// Sources ranked by cost per active hour, with cost per session, over
+/// comparable sessions only
+#[napi]
+pub async fn get_efficiency_leaderboard(options: EfficiencyOptions) -> napi::Result<EfficiencyReport> {
+    let start = Instant::now();
+    let home_dir = get_home_dir(&options.home_dir)?;
+    let idle_minutes = activity::idle_minutes(options.idle_minutes);
+    let workload = efficiency::Workload {
+        min_messages: options.min_messages.unwrap_or(efficiency::DEFAULT_MIN_MESSAGES),
+        min_active_minutes: options
+            .min_active_minutes
+            .filter(|m| *m >= 0.0)
+            .unwrap_or(efficiency::DEFAULT_MIN_ACTIVE_MINUTES),
+        family: options.family,
+    };
+
+    let report_options = ReportOptions {
+        home_dir: options.home_dir,
+        sources: options.sources,
+        since: options.since,
+        until: options.until,
+        year: options.year,
+        // Synthesized timestamps would make up durations
+        exclude_estimated_timestamps: Some(true),
+    };
+    let messages = priced_messages_for_report(&home_dir, &report_options).await.map_err(napi::Error::from_reason)?;
+
+    let rules = pricing::normalize::NormalizeRules::load();
+    let mut entries = efficiency::leaderboard(&messages, idle_minutes, &workload, &rules);
+    let display = cost_display::settings();
+    for entry in &mut entries {
+        entry.cost = display.round(entry.cost);
+        entry.cost_per_session = display.round(entry.cost_per_session);
+        entry.median_session_cost = display.round(entry.median_session_cost);
+        entry.cost_per_active_hour = display.round(entry.cost_per_active_hour);
+        entry.cost_per_million_tokens = display.round(entry.cost_per_million_tokens);
+    }
+    Ok(efficiency::report(entries, idle_minutes, workload, start.elapsed().as_millis() as u32))
+}
+
+// =============================================================================
+// Usage modes
+// =============================================================================
+
+/// Spend split between interactive chat and agentic use (tool loops,
+/// sub-agents and automated bursts), per source
+#[napi]
+pub async fn get_usage_modes(options: ReportOptions) -> napi::Result<UsageModeReport> {
+    let start = Instant::now();
+    let home_dir = get_home_dir(&options.home_dir)?;
+
+    let messages = priced_messages_for_report(&home_dir, &options).await.map_err(napi::Error::from_reason)?;
+
+    let mut report = usage_mode::report(&messages, start.elapsed().as_millis() as u32);
+    let display = cost_display::settings();
+    for entry in &mut report.entries {
+        entry.cost = display.round(entry.cost);
+    }
+    report.interactive_cost = display.round(report.interactive_cost);
+    report.agentic_cost = display.round(report.agentic_cost);
+    Ok(report)
+}
+
+// =============================================================================
+// Cost drivers
+// =============================================================================
+
+/// Cost of turns (a prompt and the responses to it) by prompt length, tool
+/// calls and files touched. Prompts are only measured, never kept.
+#[napi]
+pub async fn get_cost_drivers(options: ReportOptions) -> napi::Result<CostDriverReport> {
+    let start = Instant::now();
+    let home_dir = get_home_dir(&options.home_dir)?;
+
+    let messages = priced_messages_for_report(&home_dir, &options).await.map_err(napi::Error::from_reason)?;
+
+    let mut report = cost_drivers::report(&messages, start.elapsed().as_millis() as u32);
+    let display = cost_display::settings();
+    for bucket in report
+        .prompt_length
+        .iter_mut()
+        .chain(report.tool_calls.iter_mut())
+        .chain(report.files_touched.iter_mut())
+    {
+        bucket.cost = display.round(bucket.cost);
+        bucket.average_cost = display.round(bucket.average_cost);
+    }
+    report.attributed_cost = display.round(report.attributed_cost);
+    report.unattributed_cost = display.round(report.unattributed_cost);
+    Ok(report)
+}
+
+// =============================================================================
+// Context window pressure
+// =============================================================================
+
+/// Messages whose context approaches or exceeds the model's context window,
+/// per model, with the windows from pricing catalog metadata
+#[napi]
+pub async fn get_context_pressure(options: ContextOptions) -> napi::Result<ContextPressureReport> {
+    let start = Instant::now();
+    let home_dir = get_home_dir(&options.home_dir)?;
+    let threshold = options.threshold.unwrap_or(context_window::DEFAULT_THRESHOLD);
+    context_window::validate_threshold(threshold).map_err(napi::Error::from_reason)?;
+
+    let report_options = ReportOptions {
+        home_dir: options.home_dir,
+        sources: options.sources,
+        since: options.since,
+        until: options.until,
+        year: options.year,
+        exclude_estimated_timestamps: None,
+    };
+    let messages = priced_messages_for_report(&home_dir, &report_options).await.map_err(napi::Error::from_reason)?;
+    let pricing = pricing::PricingService::get_or_init()
+        .await
+        .map_err(napi::Error::from_reason)?;
+
+    let window = |model: &str, provider: &str| {
+        pricing
+            .lookup_with_provider(model, Some(provider))
+            .and_then(|result| result.pricing.max_input_tokens)
+    };
+    let mut report = context_window::report(&messages, threshold, window, start.elapsed().as_millis() as u32);
+    let display = cost_display::settings();
+    for model in &mut report.models {
+        model.near_limit_cost = display.round(model.near_limit_cost);
+    }
+    report.near_limit_cost = display.round(report.near_limit_cost);
+    Ok(report)
+}
+
+// =============================================================================
+// Projects
+// =============================================================================
+
+/// Usage per project. Sessions are grouped by the git repository their
+/// working directory is in, so subdirectories, monorepo packages and linked
+/// worktrees count toward the same project.
+#[napi]
+pub async fn get_project_report(options: ReportOptions) -> napi::Result<ProjectReport> {
+    let start = Instant::now();
+    let home_dir = get_home_dir(&options.home_dir)?;
+
+    let messages = priced_messages_for_report(&home_dir, &options).await.map_err(napi::Error::from_reason)?;
+
+    let mut report = workspace::project_report(&messages, start.elapsed().as_millis() as u32);
+    let display = cost_display::settings();
+    for entry in &mut report.entries {
+        entry.cost = display.round(entry.cost);
+    }
+    report.unattributed_cost = display.round(report.unattributed_cost);
+    report.total_cost = display.round(report.total_cost);
+    Ok(report)
+}
+
+/// Usage per git branch, keyed by project so same-named branches in
+/// different repositories stay apart. Shows what developing a feature
+/// branch cost.
+#[napi]
+pub async fn get_branch_report(options: ReportOptions) -> napi::Result<BranchReport> {
+    let start = Instant::now();
+    let home_dir = get_home_dir(&options.home_dir)?;
+
+    let messages = priced_messages_for_report(&home_dir, &options).await.map_err(napi::Error::from_reason)?;
+
+    let mut report = workspace::branch_report(&messages, start.elapsed().as_millis() as u32);
+    let display = cost_display::settings();
+    for entry in &mut report.entries {
+        entry.cost = display.round(entry.cost);
+    }
+    report.unattributed_cost = display.round(report.unattributed_cost);
+    report.total_cost = display.round(report.total_cost);
+    Ok(report)
+}
+
+// =============================================================================
+// Cost per commit
+// =============================================================================
+
+/// Estimated agent cost per commit. Each message is charged to the first
+/// commit in the same repository at or after it, read from the local git
+/// history.
+#[napi]
+pub async fn get_commit_costs(options: CommitCostOptions) -> napi::Result<CommitCostReport> {
+    let start = Instant::now();
+    let home_dir = get_home_dir(&options.home_dir)?;
+    let window_hours = options.window_hours.unwrap_or(commits::DEFAULT_WINDOW_HOURS);
+    if window_hours.is_nan() || window_hours <= 0.0 {
+        return Err(napi::Error::from_reason("windowHours must be positive"));
+    }
+
+    let report_options = ReportOptions {
+        home_dir: options.home_dir,
+        sources: options.sources,
+        since: options.since,
+        until: options.until,
+        year: options.year,
+        exclude_estimated_timestamps: None,
+    };
+    let messages = priced_messages_for_report(&home_dir, &report_options).await.map_err(napi::Error::from_reason)?;
+
+    let author = options.author.as_deref();
+    let mut report = commits::report(&messages, window_hours, author, start.elapsed().as_millis() as u32);
+    let display = cost_display::settings();
+    for commit in &mut report.commits {
+        commit.cost = display.round(commit.cost);
+    }
+    report.committed_cost = display.round(report.committed_cost);
+    report.mean_cost_per_commit = display.round(report.mean_cost_per_commit);
+    report.median_cost_per_commit = display.round(report.median_cost_per_commit);
+    report.uncommitted_cost = display.round(report.uncommitted_cost);
+    report.unattributed_cost = display.round(report.unattributed_cost);
+    Ok(report)
+}
+
+// =============================================================================
+// Invoice reconciliation
+// =============================================================================
+
+/// Compare a provider billing export with the costs tokscale computed for
+/// the same days, per day and model
+#[napi]
+pub async fn reconcile_invoice(options: ReconcileOptions) -> napi::Result<ReconciliationReport> {
+    let provider = match options.provider.as_deref() {
+        None => None,
+        Some(name) => Some(
+            reconcile::InvoiceProvider::parse(name)
+                .ok_or_else(|| napi::Error::from_reason(format!("Unknown invoice provider: {}", name)))?,
+        ),
+    };
+    let invoice = reconcile::Invoice::parse_csv(&options.csv, provider).map_err(napi::Error::from_reason)?;
+    let defaults = reconcile::Tolerance::default();
+    let tolerance = reconcile::Tolerance {
+        relative: options.tolerance.unwrap_or(defaults.relative),
+        absolute: options.min_difference.unwrap_or(defaults.absolute),
+    };
+
+    let home_dir = get_home_dir(&options.home_dir)?;
+    let (since, until) = match invoice.date_range() {
+        Some((since, until)) => (Some(since.to_string()), Some(until.to_string())),
+        None => (None, None),
+    };
+    let sources = options.sources.unwrap_or_default();
+    let messages = parse_all_messages_with_pricing(&home_dir, &sources).await.map_err(napi::Error::from_reason)?;
+
+    let lines = reconcile::reconcile(&invoice, &messages, tolerance);
+    Ok(ReconciliationReport {
+        provider: invoice.provider.as_str().to_string(),
+        since,
+        until,
+        total_billed: lines.iter().map(|l| l.billed).sum(),
+        total_computed: lines.iter().map(|l| l.computed).sum(),
+        mismatches: lines.iter().filter(|l| l.mismatch).count() as u32,
+        lines: lines
+            .into_iter()
+            .map(|line| ReconciliationLine {
+                difference: line.difference(),
+                date: line.date,
+                model: line.model,
+                billed: line.billed,
+                computed: line.computed,
+                mismatch: line.mismatch,
+            })
+            .collect(),
+    })
+}
+
+// =============================================================================
+// Budgets
+// =============================================================================
+
+/// Status of every budget in settings.json
+#[napi]
+pub async fn get_budget_status(home_dir: Option<String>) -> napi::Result<Vec<BudgetStatus>> {
+    let budgets = config::settings().budgets.clone();
+    if budgets.is_empty() {
+        return Ok(Vec::new());
+    }
+    let home_dir = get_home_dir(&home_dir)?;
+    let messages = parse_all_messages_with_pricing(&home_dir, &[]).await.map_err(napi::Error::from_reason)?;
+
+    let now = chrono::Utc::now();
+    let display = cost_display::settings();
+    Ok(budgets
+        .iter()
+        .map(|b| {
+            let mut status = budget::status(b, &messages, now);
+            status.spent = display.round(status.spent);
+            status.projected_cost = display.round(status.projected_cost);
+            status
+        })
+        .collect())
+}
+
+/// Run the `enforce` hook of each budget exceeded in its current period and
+/// not enforced yet in it. Returns the runs, which are also logged.
+#[napi]
+pub async fn enforce_budgets(home_dir: Option<String>) -> napi::Result<Vec<EnforcementEvent>> {
+    // Hooks would act on another home's spend and log into it
+    check_writable_home("Budget enforcement").map_err(napi::Error::from_reason)?;
+    let budgets = config::settings().budgets.clone();
+    if !budgets.iter().any(|b| b.enforce.is_some()) {
+        return Ok(Vec::new());
+    }
+    let home_dir = get_home_dir(&home_dir)?;
+    let messages = parse_all_messages_with_pricing(&home_dir, &[]).await.map_err(napi::Error::from_reason)?;
+
+    let path = enforcement::log_path(&home_dir);
+    tokio::task::spawn_blocking(move || enforcement::enforce(&budgets, &messages, chrono::Utc::now(), &path))
+        .await
+        .map_err(|e| napi::Error::from_reason(format!("Enforcement failed: {}", e)))?
+        .map_err(napi::Error::from_reason)
+}
+
+// =============================================================================
+// CI checks
+// =============================================================================
+
+/// Check usage against CI thresholds (see [`checks`])
+#[napi]
+pub async fn run_checks(options: CheckOptions) -> napi::Result<CheckReport> {
+    let thresholds = checks::Thresholds {
+        max_daily: options.max_daily,
+        max_unknown_model_share: options.max_unknown_model_share,
+    };
+    thresholds.validate().map_err(napi::Error::from_reason)?;
+    let home_dir = get_home_dir(&options.home_dir)?;
+    let report_options = ReportOptions {
+        home_dir: options.home_dir,
+        sources: options.sources,
+        since: options.since,
+        until: options.until,
+        year: options.year,
+        exclude_estimated_timestamps: None,
+    };
+    let messages = priced_messages_for_report(&home_dir, &report_options).await.map_err(napi::Error::from_reason)?;
+    // Not the stand-in without prices: every model would count as unknown
+    let pricing = match thresholds.max_unknown_model_share {
+        Some(_) if !messages.is_empty() => {
+            Some(pricing::PricingService::get_or_init().await.map_err(napi::Error::from_reason)?)
+        }
+        _ => None,
+    };
+
+    let mut report = checks::run(&messages, pricing.as_deref(), &thresholds);
+    let display = cost_display::settings();
+    for check in report.checks.iter_mut().filter(|check| check.name == checks::MAX_DAILY) {
+        check.actual = display.round(check.actual);
+    }
+    Ok(report)
+}
+
+// =============================================================================
+// Monthly statements
+// =============================================================================
+
+/// One statement per provider per month, ordered by month then provider,
+/// with a line item per model
+#[napi]
+pub async fn get_statements(options: StatementOptions) -> napi::Result<Vec<Statement>> {
+    let home_dir = get_home_dir(&options.home_dir)?;
+    let report_options = ReportOptions {
+        home_dir: None,
+        sources: options.sources,
+        since: options.since,
+        until: options.until,
+        year: options.year,
+        exclude_estimated_timestamps: options.exclude_estimated_timestamps,
+    };
+    let mut messages = priced_messages_for_report(&home_dir, &report_options).await.map_err(napi::Error::from_reason)?;
+    let pricing = pricing::PricingService::get_or_init()
+        .await
+        .map_err(napi::Error::from_reason)?;
+    if let Some(provider) = &options.provider {
+        messages.retain(|m| m.provider_id.as_str().eq_ignore_ascii_case(provider));
+    }
+
+    let mut statements = statement::build(&messages, Some(&pricing));
+    let display = cost_display::settings();
+    for statement in &mut statements {
+        for line in &mut statement.lines {
+            line.cost = display.round(line.cost);
+        }
+        statement.total_cost = display.round(statement.total_cost);
+    }
+    Ok(statements)
+}
+
+/// All statements as one CSV, a row per line item and a "Total" row per statement
+#[napi]
+pub fn statements_to_csv(statements: Vec<Statement>) -> String {
+    statement::to_csv(&statements)
+}
+
+/// A statement as a Markdown document, ready to convert to PDF
+#[napi]
+pub fn statement_to_markdown(statement: Statement) -> String {
+    let precision = cost_display::settings().precision.unwrap_or(2) as usize;
+    statement::to_markdown(&statement, precision)
+}
+
+// =============================================================================
+// Usage diff
+// =============================================================================
+
+fn round_diff(mut diff: UsageDiff) -> UsageDiff {
+    let display = cost_display::settings();
+    diff.from.cost = display.round(diff.from.cost);
+    diff.to.cost = display.round(diff.to.cost);
+    diff.cost_change = display.round(diff.cost_change);
+    for entry in diff.models.iter_mut().chain(diff.projects.iter_mut()).flatten() {
+        entry.from_cost = display.round(entry.from_cost);
+        entry.to_cost = display.round(entry.to_cost);
+        entry.cost_change = display.round(entry.cost_change);
+    }
+    diff
+}
+
+/// Compare totals, model mix and per-project usage between two periods
+#[napi]
+pub async fn get_usage_diff(options: DiffOptions) -> napi::Result<UsageDiff> {
+    let range = |period: &str| {
+        diff::period_range(period).ok_or_else(|| {
+            napi::Error::from_reason(format!("Invalid period: {} (expected YYYY, YYYY-MM or YYYY-MM-DD)", period))
+        })
+    };
+    let (from_since, from_until) = range(&options.from)?;
+    let (to_since, to_until) = range(&options.to)?;
+    let home_dir = get_home_dir(&options.home_dir)?;
+
+    let sources = options.sources.unwrap_or_default();
+    let mut messages = parse_all_messages_with_pricing(&home_dir, &sources).await.map_err(napi::Error::from_reason)?;
+    if options.exclude_estimated_timestamps.unwrap_or(false) {
+        messages.retain(|m| !m.estimated_timestamp);
+    }
+
+    let within = |since: &str, until: &str| -> Vec<UnifiedMessage> {
+        messages
+            .iter()
+            .filter(|m| m.date.as_str() >= since && m.date.as_str() <= until)
+            .cloned()
+            .collect()
+    };
+    let from = diff::snapshot(&within(&from_since, &from_until));
+    let to = diff::snapshot(&within(&to_since, &to_until));
+    Ok(round_diff(diff::diff(options.from, &from, options.to, &to)))
+}
+
+/// Compare two exported reports (`models --json` or `projects --json` output)
+#[napi]
+pub fn diff_report_exports(from_json: String, to_json: String, from_label: String, to_label: String) -> napi::Result<UsageDiff> {
+    let read = |json: &str, label: &str| {
+        diff::snapshot_from_export(json).map_err(|e| napi::Error::from_reason(format!("{}: {}", label, e)))
+    };
+    let from = read(&from_json, &from_label)?;
+    let to = read(&to_json, &to_label)?;
+    Ok(round_diff(diff::diff(from_label, &from, to_label, &to)))
+}
+
+// =============================================================================
+// Cache maintenance
+// =============================================================================
+
+/// Remove orphaned temp files, stale checkpoints and parse-cache entries for
+/// deleted session files from the cache directory
+#[napi]
+pub fn clean_cache(options: CacheCleanOptions) -> napi::Result<CacheCleanReport> {
+    let dry_run = options.dry_run.unwrap_or(false);
+    let dir = pricing::cache::get_cache_dir();
+
+    let report = cache_gc::collect(&dir, dry_run, std::time::SystemTime::now())
+        .map_err(|e| napi::Error::from_reason(format!("Failed to clean cache: {}", e)))?;
+
+    Ok(CacheCleanReport {
+        cache_dir: dir.to_string_lossy().into_owned(),
+        dry_run,
+        removed: report
+            .removed
+            .into_iter()
+            .map(|item| CacheCleanEntry {
+                path: item.path.to_string_lossy().into_owned(),
+                bytes: item.bytes as i64,
+                reason: item.reason.as_str().to_string(),
+            })
+            .collect(),
+        bytes_freed: report.bytes_freed as i64,
+        parse_entries_pruned: report.parse_entries_pruned as u32,
+        total_bytes: report.total_bytes as i64,
+    })
+}
+
+// =============================================================================
+// Parse diagnostics
+// =============================================================================
+
+/// Schema drift and detected file formats seen by parsers, pricing conflicts
+/// seen by lookups, and corrected timestamps, since the last call. Drifted
+/// fields are coerced or skipped instead of dropping the whole record.
+#[napi]
+pub fn take_parse_diagnostics() -> ParseDiagnostics {
+    let drift = diagnostics::take_drift()
+        .into_iter()
+        .map(|d| ParseDiagnostic {
+            source: d.source,
+            field: d.field,
+            expected: d.expected,
+            found: d.found,
+            count: d.count as i64,
+        })
+        .collect();
+    let formats = diagnostics::take_formats()
+        .into_iter()
+        .map(|f| ParseFormat {
+            source: f.source,
+            format: f.format,
+            count: f.count as i64,
+        })
+        .collect();
+
+    let pricing_conflicts = diagnostics::take_price_conflicts()
+        .into_iter()
+        .map(|c| PricingConflict {
+            model_id: c.model_id,
+            litellm_key: c.litellm_key,
+            openrouter_key: c.openrouter_key,
+            litellm_input: c.litellm.0,
+            litellm_output: c.litellm.1,
+            openrouter_input: c.openrouter.0,
+            openrouter_output: c.openrouter.1,
+            chosen: c.chosen.to_string(),
+        })
+        .collect();
+
+    let timestamp_corrections = diagnostics::take_timestamp_corrections()
+        .into_iter()
+        .map(|c| TimestampCorrection {
+            source: c.source,
+            kind: c.kind,
+            count: c.count as i64,
+        })
+        .collect();
+
+    ParseDiagnostics { drift, formats, pricing_conflicts, timestamp_corrections }
+}
+
+// =============================================================================
+// Parser conformance
+// =============================================================================
+
+/// Parse `paths` (files, or directories searched recursively) with the
+/// parser for `source` and report anything tokscale would lose or have to
+/// repair, so tools can check their log output stays readable
+#[napi]
+pub fn check_parser_conformance(source: String, paths: Vec<String>) -> napi::Result<ConformanceReport> {
+    let paths: Vec<std::path::PathBuf> = paths.into_iter().map(std::path::PathBuf::from).collect();
+    let report = conformance::check(&source, &paths).map_err(napi::Error::from_reason)?;
+    Ok(ConformanceReport {
+        passed: report.passed(),
+        source: report.source,
+        file_count: report.files as u32,
+        message_count: report.messages as u32,
+        formats: report.formats,
+        findings: report
+            .findings
+            .into_iter()
+            .map(|f| ConformanceFinding {
+                severity: f.severity.as_str().to_string(),
+                code: f.code.to_string(),
+                file: f.file.map(|p| p.display().to_string()),
+                message: f.message,
+                count: f.count as i64,
+            })
+            .collect(),
+    })
+}
+
+// =============================================================================
+// Source metadata API
+// =============================================================================
+
+/// List sources with file counts, total size and date ranges, without parsing
+#[napi]
+pub fn scan_sources(options: SourceScanOptions) -> napi::Result<Vec<SourceSummary>> {
+    let home_dir = get_home_dir(&options.home_dir)?;
+    let sources = options.sources.unwrap_or_default();
+
+    Ok(sources::scan(&home_dir, &sources)
+        .into_iter()
+        .map(|info| SourceSummary {
+            source: info.session_type.name().to_string(),
+            path: info.root.to_string_lossy().into_owned(),
+            exists: info.exists,
+            file_count: info.file_count as u32,
+            total_bytes: info.total_bytes as i64,
+            earliest_timestamp: info.earliest,
+            latest_timestamp: info.latest,
+        })
+        .collect())
+}
+
+/// Changes whenever a session file of the requested sources is added,
+/// removed or written (by path, size and modification time). Only scans
+/// directories, so it is cheap enough to poll, and lets a file watcher skip
+/// events that don't touch session data.
+#[napi]
+pub fn get_data_version(options: SourceScanOptions) -> napi::Result<String> {
+    let home_dir = get_home_dir(&options.home_dir)?;
+    let sources = options.sources.unwrap_or_default();
+    let files = scanner::scan_all_sources(&home_dir, &sources).all_files();
+    Ok(format!("{:016x}", parse_cache::fingerprint(&files)))
+}
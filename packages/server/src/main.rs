@@ -0,0 +1,124 @@
+//! `tokscale serve` as a standalone binary
+//!
+//! Serves the same HTTP API as the CLI's serve mode, for hosts that run the
+//! server without Node.js. Tokens are managed with `tokscale serve token`
+//! and settings are read from the same `~/.config/tokscale/settings.json`.
+
+use std::net::SocketAddr;
+use std::process::ExitCode;
+use tokscale_core::{ServeConfig, Server};
+
+const USAGE: &str = "Usage: tokscale-server [--host <addr>] [--port <port>] [--rate-limit <per-minute>] [--home <dir>]";
+
+/// Options not given keep their `ServeConfig` default
+#[derive(Debug, Default)]
+struct Args {
+    host: Option<String>,
+    port: Option<u16>,
+    rate_limit: Option<u32>,
+    home: Option<String>,
+}
+
+fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Args, String> {
+    let mut parsed = Args::default();
+    let mut args = args.into_iter();
+    while let Some(flag) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("{} needs a value", flag));
+        match flag.as_str() {
+            "--host" => parsed.host = Some(value()?),
+            "--port" => parsed.port = Some(value()?.parse().map_err(|_| "--port must be a port number")?),
+            "--rate-limit" => parsed.rate_limit = Some(value()?.parse().map_err(|_| "--rate-limit must be a number")?),
+            "--home" => parsed.home = Some(value()?),
+            _ => return Err(format!("Unknown argument '{}'", flag)),
+        }
+    }
+    Ok(parsed)
+}
+
+fn config(args: Args) -> Result<ServeConfig, String> {
+    let home = args
+        .home
+        .or_else(|| std::env::var("HOME").ok())
+        .or_else(|| dirs::home_dir().map(|p| p.to_string_lossy().into_owned()))
+        .ok_or("Could not determine the home directory; pass --home")?;
+    let mut config = ServeConfig::new(home);
+    let host = args.host.unwrap_or_else(|| config.listen.ip().to_string());
+    let port = args.port.unwrap_or(config.listen.port());
+    config.listen = format!("{}:{}", host, port)
+        .parse::<SocketAddr>()
+        .map_err(|_| format!("Invalid listen address {}:{}", host, port))?;
+    if let Some(per_minute) = args.rate_limit {
+        config.rate_limit_per_minute = Some(per_minute);
+    }
+    Ok(config)
+}
+
+async fn run(args: Args) -> Result<(), String> {
+    let server = Server::bind(config(args)?).await?;
+    eprintln!("[tokscale] serving on http://{}", server.local_addr()?);
+    server.serve().await
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.iter().any(|a| a == "--help" || a == "-h") {
+        println!("{}", USAGE);
+        return ExitCode::SUCCESS;
+    }
+    let result = match parse_args(args) {
+        Ok(args) => run(args).await,
+        Err(e) => Err(format!("{}\n{}", e, USAGE)),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("[tokscale] {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Result<Args, String> {
+        parse_args(list.iter().map(|s| s.to_string()))
+    }
+
+    #[test]
+    fn test_defaults_follow_serve_config() {
+        let config = config(args(&["--home", "/srv/usage"]).unwrap()).unwrap();
+        let defaults = ServeConfig::new("/srv/usage");
+        assert_eq!(config.listen, defaults.listen);
+        assert_eq!(config.rate_limit_per_minute, defaults.rate_limit_per_minute);
+        assert_eq!(config.home_dir, defaults.home_dir);
+    }
+
+    #[test]
+    fn test_listen_and_rate_limit() {
+        let parsed = args(&[
+            "--host",
+            "0.0.0.0",
+            "--port",
+            "9000",
+            "--rate-limit",
+            "0",
+            "--home",
+            "/h",
+        ])
+        .unwrap();
+        let config = config(parsed).unwrap();
+        assert_eq!(config.listen, "0.0.0.0:9000".parse::<SocketAddr>().unwrap());
+        assert_eq!(config.rate_limit_per_minute, Some(0));
+    }
+
+    #[test]
+    fn test_bad_arguments() {
+        assert!(args(&["--port"]).unwrap_err().contains("needs a value"));
+        assert!(args(&["--port", "http"]).is_err());
+        assert!(args(&["--verbose"]).unwrap_err().contains("Unknown argument"));
+        assert!(config(args(&["--host", "not a host", "--home", "/h"]).unwrap()).is_err());
+    }
+}